@@ -0,0 +1,192 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Authentication backends. Protocols which support authentication take an
+//! `Authenticator` trait object so that where credentials come from is decided
+//! by the server configuration and not by the protocol.
+
+use boring::hash::{hash, MessageDigest};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+/// Checks a set of credentials presented by a client.
+pub trait Authenticator: Send + Sync {
+    /// Returns `true` if the secret is valid for the user. Protocols which
+    /// only carry a secret, such as the single argument form of the RESP
+    /// `AUTH` command, pass `None` for the username.
+    fn verify(&self, username: Option<&[u8]>, secret: &[u8]) -> bool;
+}
+
+/// Compares two secrets without exiting early on the first mismatched byte.
+/// Both sides are hashed first, so neither the position of a mismatch nor
+/// the length of the expected secret can be learned from timing.
+fn secret_eq(a: &[u8], b: &[u8]) -> bool {
+    match (
+        hash(MessageDigest::sha256(), a),
+        hash(MessageDigest::sha256(), b),
+    ) {
+        (Ok(a), Ok(b)) => boring::memcmp::eq(&a, &b),
+        _ => false,
+    }
+}
+
+/// Accepts a single set of credentials which are provided up front, typically
+/// from the config file.
+pub struct StaticAuthenticator {
+    username: Option<Vec<u8>>,
+    secret: Vec<u8>,
+}
+
+impl StaticAuthenticator {
+    pub fn new(username: Option<&[u8]>, secret: &[u8]) -> Self {
+        Self {
+            username: username.map(|v| v.to_vec()),
+            secret: secret.to_vec(),
+        }
+    }
+}
+
+impl Authenticator for StaticAuthenticator {
+    fn verify(&self, username: Option<&[u8]>, secret: &[u8]) -> bool {
+        // the secret is always checked, so a bad username takes as long to
+        // reject as a bad secret
+        let secret = secret_eq(&self.secret, secret);
+        secret && self.username.as_deref() == username
+    }
+}
+
+enum Credential {
+    /// `{SHA}` followed by the base64 encoded SHA-1 digest of the secret, as
+    /// written by `htpasswd -s`
+    Sha1(Vec<u8>),
+    Plain(Vec<u8>),
+}
+
+/// Accepts credentials listed in an htpasswd-style file. Each line holds a
+/// `username:secret` pair, where the secret is either stored in plaintext or
+/// as a `{SHA}` digest. Blank lines and lines starting with `#` are ignored.
+pub struct FileAuthenticator {
+    users: HashMap<Vec<u8>, Credential>,
+}
+
+impl FileAuthenticator {
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)?;
+
+        let mut users = HashMap::new();
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (username, secret) = line.split_once(':').ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}:{}: expected username:secret", path, number + 1),
+                )
+            })?;
+
+            let credential = if let Some(digest) = secret.strip_prefix("{SHA}") {
+                let digest = boring::base64::decode_block(digest).map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{}:{}: bad {{SHA}} digest", path, number + 1),
+                    )
+                })?;
+                Credential::Sha1(digest)
+            } else {
+                Credential::Plain(secret.as_bytes().to_vec())
+            };
+
+            users.insert(username.as_bytes().to_vec(), credential);
+        }
+
+        Ok(Self { users })
+    }
+}
+
+impl Authenticator for FileAuthenticator {
+    fn verify(&self, username: Option<&[u8]>, secret: &[u8]) -> bool {
+        let credential = username.and_then(|username| self.users.get(username));
+
+        match credential {
+            Some(Credential::Sha1(digest)) => match hash(MessageDigest::sha1(), secret) {
+                Ok(d) => d.len() == digest.len() && boring::memcmp::eq(&d, digest),
+                Err(_) => false,
+            },
+            Some(Credential::Plain(expected)) => secret_eq(expected, secret),
+            None => {
+                // do the same work as for a known user, so that valid
+                // usernames can't be discovered from timing
+                let _ = secret_eq(secret, secret);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn secret_comparison() {
+        assert!(secret_eq(b"password", b"password"));
+        assert!(!secret_eq(b"password", b"passwore"));
+
+        // secrets of different lengths are compared without panicking
+        assert!(!secret_eq(b"password", b"pass"));
+        assert!(!secret_eq(b"password", b""));
+        assert!(secret_eq(b"", b""));
+    }
+
+    #[test]
+    fn static_authenticator() {
+        let auth = StaticAuthenticator::new(None, b"password");
+        assert!(auth.verify(None, b"password"));
+        assert!(!auth.verify(None, b"passwore"));
+        assert!(!auth.verify(None, b"password2"));
+        assert!(!auth.verify(Some(&b"alice"[..]), b"password"));
+
+        let auth = StaticAuthenticator::new(Some(&b"alice"[..]), b"password");
+        assert!(auth.verify(Some(&b"alice"[..]), b"password"));
+        assert!(!auth.verify(Some(&b"bob"[..]), b"password"));
+        assert!(!auth.verify(None, b"password"));
+    }
+
+    #[test]
+    fn file_authenticator() {
+        let path = std::env::temp_dir().join(format!("pelikan-htpasswd-{}", std::process::id()));
+        let mut file = std::fs::File::create(&path).expect("failed to create file");
+        file.write_all(
+            b"# users\n\
+            alice:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=\n\
+            \n\
+            bob:hunter2\n",
+        )
+        .expect("failed to write file");
+
+        let auth = FileAuthenticator::load(path.to_str().unwrap()).expect("failed to load");
+        let _ = std::fs::remove_file(&path);
+
+        assert!(auth.verify(Some(&b"alice"[..]), b"password"));
+        assert!(!auth.verify(Some(&b"alice"[..]), b"hunter2"));
+        assert!(auth.verify(Some(&b"bob"[..]), b"hunter2"));
+        assert!(!auth.verify(Some(&b"bob"[..]), b"hunter"));
+        assert!(!auth.verify(Some(&b"carol"[..]), b"password"));
+        assert!(!auth.verify(None, b"password"));
+    }
+
+    #[test]
+    fn file_authenticator_malformed() {
+        let path =
+            std::env::temp_dir().join(format!("pelikan-htpasswd-bad-{}", std::process::id()));
+        std::fs::write(&path, b"alice\n").expect("failed to write file");
+        assert!(FileAuthenticator::load(path.to_str().unwrap()).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
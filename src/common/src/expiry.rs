@@ -78,3 +78,60 @@ impl Expiry {
         Duration::<Seconds<u32>>::from_secs(self.as_secs())
     }
 }
+
+/// An operator configured ceiling on the TTL of stored items, which stops
+/// clients from keeping items for longer than eviction planning allows.
+///
+/// TTLs above the ceiling are clamped down to it, or rejected when the limit
+/// is strict. Items which would never expire are always given the ceiling as
+/// their TTL, since that is what most clients ask for when they don't care.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MaxTtl {
+    secs: u64,
+    strict: bool,
+}
+
+impl MaxTtl {
+    /// Creates a ceiling of `secs` seconds, where zero means there is no
+    /// ceiling.
+    pub fn new(secs: u64, strict: bool) -> Self {
+        Self { secs, strict }
+    }
+
+    /// Applies the ceiling to a TTL in seconds, where a TTL of zero means that
+    /// the item never expires. Returns the TTL to store the item with, or
+    /// `None` if the TTL is above a strict ceiling.
+    pub fn apply(&self, ttl: u64) -> Option<u64> {
+        if self.secs == 0 || (ttl != 0 && ttl <= self.secs) {
+            Some(ttl)
+        } else if ttl == 0 || !self.strict {
+            Some(self.secs)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_ttl() {
+        let unlimited = MaxTtl::default();
+        assert_eq!(unlimited.apply(0), Some(0));
+        assert_eq!(unlimited.apply(u64::MAX), Some(u64::MAX));
+
+        let lenient = MaxTtl::new(3600, false);
+        assert_eq!(lenient.apply(1), Some(1));
+        assert_eq!(lenient.apply(3600), Some(3600));
+        assert_eq!(lenient.apply(3601), Some(3600));
+        assert_eq!(lenient.apply(0), Some(3600));
+
+        let strict = MaxTtl::new(3600, true);
+        assert_eq!(strict.apply(1), Some(1));
+        assert_eq!(strict.apply(3600), Some(3600));
+        assert_eq!(strict.apply(3601), None);
+        assert_eq!(strict.apply(0), Some(3600));
+    }
+}
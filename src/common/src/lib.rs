@@ -2,6 +2,7 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+pub mod auth;
 pub mod bytes;
 pub mod expiry;
 pub mod metrics;
@@ -9,3 +10,4 @@ pub mod signal;
 pub mod ssl;
 pub mod time;
 pub mod traits;
+pub mod upgrade;
@@ -2,8 +2,79 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+use std::net::SocketAddr;
+use std::sync::mpsc::SyncSender;
+use std::time::Duration;
+
 #[derive(Clone)]
 pub enum Signal {
     FlushAll,
     Shutdown,
+    /// Asks the thread which owns the storage to describe what is stored for
+    /// a key. The description, or `None` if the key is not stored, is sent on
+    /// the channel. Threads which don't own the storage ignore this signal.
+    DumpKey(Box<[u8]>, SyncSender<Option<String>>),
+    /// Asks the thread which owns the storage to immediately evict the given
+    /// number of segments. The number of items evicted, or `None` if the
+    /// storage doesn't support forced eviction, is sent on the channel. This is
+    /// only intended for testing eviction policies.
+    Evict(usize, SyncSender<Option<usize>>),
+    /// Asks the thread which owns the storage to check its consistency. The
+    /// result, with a description of the first inconsistency found, or `None`
+    /// if the storage doesn't support this, is sent on the channel.
+    Verify(SyncSender<Option<Result<(), String>>>),
+    /// Asks the thread which owns the storage to turn eager expiration on or
+    /// off. Whether the storage supports this is sent on the channel. This is
+    /// only intended for testing lazy expiration.
+    ActiveExpire(bool, SyncSender<bool>),
+    /// Asks the thread which owns the storage to describe how the value for a
+    /// key is stored, in the format of the redis `DEBUG OBJECT` reply. The
+    /// description, or `None` if the key is not stored or the storage doesn't
+    /// support this, is sent on the channel.
+    DebugObject(Box<[u8]>, SyncSender<Option<String>>),
+    /// Asks the thread which owns the storage to round-trip its contents
+    /// through a snapshot into a fresh instance of the storage. The number of
+    /// items restored, an error, or `None` if the storage doesn't support
+    /// this, is sent on the channel. This is only intended for testing
+    /// persistence.
+    Reload(SyncSender<Option<Result<usize, String>>>),
+    /// Sent once a newly started copy of the process has taken over the
+    /// listening sockets. Threads stop accepting new sessions, but continue to
+    /// serve the sessions they have until they are told to shutdown.
+    Drain,
+    /// Asks the process to stop accepting new sessions and to close each of
+    /// its sessions once it has finished its current request. Unlike `Drain`,
+    /// the listening socket is closed, so new connections are refused. The
+    /// process shuts down once no sessions are left, or after the timeout.
+    GracefulDrain(Duration),
+    /// Asks each worker to close its client sessions which match the filter.
+    /// Every worker sends the number of sessions it closed on the channel,
+    /// threads which don't own client sessions ignore this signal.
+    KillClient(ClientFilter, SyncSender<usize>),
+    /// Asks each worker to mark its client sessions which match the filter as
+    /// exempt from eviction, or to clear the mark. Every worker sends the
+    /// number of sessions it changed on the channel, threads which don't own
+    /// client sessions ignore this signal.
+    NoEvictClient(ClientFilter, bool, SyncSender<usize>),
+    /// Asks each worker to describe its client sessions. Every worker sends
+    /// the id and client address of each of its sessions on the channel,
+    /// threads which don't own client sessions ignore this signal.
+    ListClients(SyncSender<Vec<(u64, Option<SocketAddr>)>>),
+}
+
+/// Selects client sessions by the id they were assigned when accepted or by
+/// the address of the client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientFilter {
+    Id(u64),
+    Addr(SocketAddr),
+}
+
+impl ClientFilter {
+    pub fn matches(&self, id: u64, addr: Option<SocketAddr>) -> bool {
+        match self {
+            Self::Id(target) => *target == id,
+            Self::Addr(target) => Some(*target) == addr,
+        }
+    }
 }
@@ -15,6 +15,12 @@ pub trait TlsConfig {
     fn certificate(&self) -> Option<String>;
 
     fn ca_file(&self) -> Option<String>;
+
+    /// Whether clients must present a certificate signed by a CA in the CA
+    /// file.
+    fn require_client_auth(&self) -> bool {
+        false
+    }
 }
 
 /// Create an `TlsTcpAcceptor` from the given `TlsConfig`. Returns an error if
@@ -56,5 +62,7 @@ pub fn tls_acceptor(config: &dyn TlsConfig) -> Result<Option<TlsTcpAcceptor>, st
         builder = builder.certificate_chain_file(f);
     }
 
+    builder = builder.require_client_auth(config.require_client_auth());
+
     Ok(Some(builder.build()?))
 }
@@ -0,0 +1,189 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Hands the listening sockets of a running process to a newly started copy
+//! of its binary, so that the binary can be replaced without refusing any
+//! connections.
+//!
+//! The running process starts the new one with the same arguments and leaves
+//! its data and admin listeners, and the http listener if there is one, open
+//! across the exec. Their fd numbers, along
+//! with one end of a socket pair, are passed in the environment. The new
+//! process uses the inherited listeners instead of binding, and once it is
+//! running it writes to the socket pair to tell the old process that it can
+//! stop accepting and drain its sessions. The environment also names the pid
+//! of the process which set it, so that any process started later by the new
+//! one ignores it.
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::os::unix::net::UnixStream;
+use std::os::unix::prelude::{AsRawFd, CommandExt, FromRawFd, RawFd};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+const UPGRADE_FDS: &str = "PELIKAN_UPGRADE_FDS";
+const UPGRADE_PID: &str = "PELIKAN_UPGRADE_PID";
+
+// set from the signal handler, and cleared when the request is taken
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// the ready fd is closed after it is written, so it must only be used once
+static NOTIFIED: AtomicBool = AtomicBool::new(false);
+
+/// The fds of the listening sockets which are handed to the new process.
+#[derive(Clone, Copy, Debug)]
+pub struct Listeners {
+    pub data: RawFd,
+    pub admin: RawFd,
+    pub http: Option<RawFd>,
+}
+
+/// The fds left open for this process by the process it is replacing.
+struct Inherited {
+    listeners: Listeners,
+    ready: RawFd,
+}
+
+fn inherited() -> Option<Inherited> {
+    let pid: u32 = std::env::var(UPGRADE_PID).ok()?.parse().ok()?;
+    if pid != std::os::unix::process::parent_id() {
+        return None;
+    }
+
+    let fds: Vec<RawFd> = std::env::var(UPGRADE_FDS)
+        .ok()?
+        .split(',')
+        .map(|fd| fd.parse().ok())
+        .collect::<Option<_>>()?;
+
+    let (data, admin, ready, http) = match fds[..] {
+        [data, admin, ready] => (data, admin, ready, None),
+        [data, admin, ready, http] => (data, admin, ready, Some(http)),
+        _ => {
+            return None;
+        }
+    };
+
+    Some(Inherited {
+        listeners: Listeners { data, admin, http },
+        ready,
+    })
+}
+
+/// Returns the fd of the data listener if this process was started to
+/// replace a running one.
+pub fn data_listener() -> Option<RawFd> {
+    inherited().map(|fds| fds.listeners.data)
+}
+
+/// Returns the fd of the admin listener if this process was started to
+/// replace a running one.
+pub fn admin_listener() -> Option<RawFd> {
+    inherited().map(|fds| fds.listeners.admin)
+}
+
+/// Returns the fd of the http admin listener if this process was started to
+/// replace a running one which had the http listener enabled.
+pub fn http_listener() -> Option<RawFd> {
+    inherited().and_then(|fds| fds.listeners.http)
+}
+
+/// Tells the process which this one is replacing that it is now serving.
+/// Does nothing if this process was not started by an upgrade.
+pub fn notify_ready() {
+    if let Some(fds) = inherited() {
+        if !NOTIFIED.swap(true, Ordering::Relaxed) {
+            // SAFETY: the fd was left open for our exclusive use, and the
+            // flag above makes sure it is only taken once
+            let mut ready = unsafe { UnixStream::from_raw_fd(fds.ready) };
+            let _ = ready.write_all(b"+");
+        }
+    }
+}
+
+/// Starts a new copy of this process which takes over the listeners, and
+/// waits up to `timeout` for it to report that it is serving. Returns the pid
+/// of the new process. If it exits or doesn't become ready in time, an error
+/// is returned and this process should carry on serving.
+///
+/// The binary is found the same way it was when this process was started, so
+/// a binary which has been replaced on disk is the one which runs.
+pub fn spawn(listeners: Listeners, timeout: Duration) -> Result<u32> {
+    let mut args = std::env::args_os();
+    let program = args
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::Other, "missing program name"))?;
+
+    let (mut ready_rx, ready_tx) = UnixStream::pair()?;
+
+    let mut fds = vec![listeners.data, listeners.admin, ready_tx.as_raw_fd()];
+    fds.extend(listeners.http);
+
+    let fd_list: Vec<String> = fds.iter().map(|fd| fd.to_string()).collect();
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .env(UPGRADE_PID, std::process::id().to_string())
+        .env(UPGRADE_FDS, fd_list.join(","));
+
+    // SAFETY: only async-signal-safe functions are called between the fork
+    // and the exec. clearing close-on-exec here only affects the child
+    unsafe {
+        command.pre_exec(move || {
+            for fd in &fds {
+                if libc::fcntl(*fd, libc::F_SETFD, 0) == -1 {
+                    return Err(Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+
+    let mut child = command.spawn()?;
+
+    // the child now holds the only copy of the write end, so the read below
+    // sees the end of the stream if the child exits before it is ready
+    drop(ready_tx);
+
+    ready_rx.set_read_timeout(Some(timeout))?;
+    let mut buf = [0];
+    match ready_rx.read(&mut buf) {
+        Ok(1) => Ok(child.id()),
+        Ok(_) => {
+            let _ = child.wait();
+            Err(Error::new(
+                ErrorKind::Other,
+                "new process exited before it was ready",
+            ))
+        }
+        Err(e) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(Error::new(
+                e.kind(),
+                format!("new process was not ready: {}", e),
+            ))
+        }
+    }
+}
+
+/// Requests an upgrade whenever the process receives `SIGUSR2`.
+pub fn handle_signal() {
+    extern "C" fn request(_: libc::c_int) {
+        REQUESTED.store(true, Ordering::Relaxed);
+    }
+
+    // SAFETY: the handler only stores to an atomic, which is signal-safe
+    unsafe {
+        libc::signal(libc::SIGUSR2, request as libc::sighandler_t);
+    }
+}
+
+/// Returns `true` if an upgrade has been requested by signal since the last
+/// call.
+pub fn requested() -> bool {
+    REQUESTED.swap(false, Ordering::Relaxed)
+}
@@ -18,6 +18,13 @@ const ADMIN_TW_TICK: usize = 10;
 const ADMIN_TW_CAP: usize = 1000;
 const ADMIN_TW_NTICK: usize = 100;
 const ADMIN_USE_TLS: bool = false;
+const ADMIN_HTTP_AUTH_TOKEN: Option<&str> = None;
+const ADMIN_UPGRADE_TIMEOUT: usize = 30_000;
+const ADMIN_DRAIN_TIMEOUT: usize = 60_000;
+const ADMIN_STATS_DELTA_INTERVAL: usize = 60_000;
+const ADMIN_THREADS: usize = 1;
+const ADMIN_IDLE_TIMEOUT: usize = 300_000;
+const ADMIN_MAX_REQUESTS: usize = 0;
 
 // TODO(bmartin): we will eventually migrate to HTTP by default and make the
 // legacy admin port as optional. At that time, we should consider consolidating
@@ -71,6 +78,34 @@ fn use_tls() -> bool {
     ADMIN_USE_TLS
 }
 
+fn http_auth_token() -> Option<String> {
+    ADMIN_HTTP_AUTH_TOKEN.map(|v| v.to_string())
+}
+
+fn upgrade_timeout() -> usize {
+    ADMIN_UPGRADE_TIMEOUT
+}
+
+fn drain_timeout() -> usize {
+    ADMIN_DRAIN_TIMEOUT
+}
+
+fn stats_delta_interval() -> usize {
+    ADMIN_STATS_DELTA_INTERVAL
+}
+
+fn threads() -> usize {
+    ADMIN_THREADS
+}
+
+fn idle_timeout() -> usize {
+    ADMIN_IDLE_TIMEOUT
+}
+
+fn max_requests() -> usize {
+    ADMIN_MAX_REQUESTS
+}
+
 // definitions
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Admin {
@@ -96,6 +131,20 @@ pub struct Admin {
     tw_ntick: usize,
     #[serde(default = "use_tls")]
     use_tls: bool,
+    #[serde(default = "http_auth_token")]
+    http_auth_token: Option<String>,
+    #[serde(default = "upgrade_timeout")]
+    upgrade_timeout: usize,
+    #[serde(default = "drain_timeout")]
+    drain_timeout: usize,
+    #[serde(default = "stats_delta_interval")]
+    stats_delta_interval: usize,
+    #[serde(default = "threads")]
+    threads: usize,
+    #[serde(default = "idle_timeout")]
+    idle_timeout: usize,
+    #[serde(default = "max_requests")]
+    max_requests: usize,
 }
 
 // implementation
@@ -145,6 +194,52 @@ impl Admin {
     pub fn use_tls(&self) -> bool {
         self.use_tls
     }
+
+    /// The bearer token which must be presented to use HTTP endpoints that
+    /// expose stored data. Those endpoints are disabled when this is unset.
+    pub fn http_auth_token(&self) -> Option<String> {
+        self.http_auth_token.clone()
+    }
+
+    /// How long to wait, in milliseconds, for the new process started by an
+    /// upgrade to begin serving before the upgrade is abandoned.
+    pub fn upgrade_timeout(&self) -> usize {
+        self.upgrade_timeout
+    }
+
+    /// How long, in milliseconds, a process which has been upgraded continues
+    /// to serve its open sessions before it exits.
+    pub fn drain_timeout(&self) -> usize {
+        self.drain_timeout
+    }
+
+    /// The length, in milliseconds, of the interval over which counters are
+    /// reported by `stats delta`.
+    pub fn stats_delta_interval(&self) -> usize {
+        self.stats_delta_interval
+    }
+
+    /// The number of threads which serve admin connections. This is only used
+    /// by the momento proxy, which runs its admin port on a runtime of its own
+    /// so that heavy scraping doesn't compete with the data threads.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// The time in milliseconds an admin connection may wait for its next
+    /// request before it is closed, which reaps connections left open by
+    /// clients that have gone away. Zero disables the timeout. This is only
+    /// used by the momento proxy.
+    pub fn idle_timeout(&self) -> usize {
+        self.idle_timeout
+    }
+
+    /// The number of requests served on an admin connection before it is
+    /// closed, so that long-lived clients reconnect from time to time. Zero
+    /// places no limit on the requests. This is only used by the momento proxy.
+    pub fn max_requests(&self) -> usize {
+        self.max_requests
+    }
 }
 
 // trait implementations
@@ -162,6 +257,13 @@ impl Default for Admin {
             tw_cap: tw_cap(),
             tw_ntick: tw_ntick(),
             use_tls: use_tls(),
+            http_auth_token: http_auth_token(),
+            upgrade_timeout: upgrade_timeout(),
+            drain_timeout: drain_timeout(),
+            stats_delta_interval: stats_delta_interval(),
+            threads: threads(),
+            idle_timeout: idle_timeout(),
+            max_requests: max_requests(),
         }
     }
 }
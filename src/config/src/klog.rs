@@ -30,6 +30,9 @@ const SAMPLE: usize = 100;
 // single message buffer size in bytes
 const SINGLE_MESSAGE_SIZE: usize = KB;
 
+// the layout of each log line
+const FORMAT: KlogFormat = KlogFormat::Default;
+
 ////////////////////////////////////////////////////////////////////////////////
 // helper functions
 ////////////////////////////////////////////////////////////////////////////////
@@ -62,10 +65,39 @@ fn single_message_size() -> usize {
     SINGLE_MESSAGE_SIZE
 }
 
+fn format() -> KlogFormat {
+    FORMAT
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // struct definitions
 ////////////////////////////////////////////////////////////////////////////////
 
+/// The layout of each line in the command log.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KlogFormat {
+    /// Each line echoes the command with its arguments, followed by the
+    /// response code and length, eg: `"get key" 4 5`.
+    Default,
+    /// Each line has the same fixed fields, in the style of the Common Log
+    /// Format, so that it can be read by existing log-analysis tools:
+    ///
+    /// `<timestamp> <client> "<command> <key>" <code> <bytes> <latency>`
+    ///
+    /// The client is the address of the peer and the latency is in
+    /// microseconds, either of which is `-` if unknown. Bytes in the key which
+    /// are not printable, as well as spaces, quotes, and backslashes, are
+    /// escaped as in a Rust string literal, eg: `\x00`.
+    Common,
+}
+
+impl Default for KlogFormat {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Klog {
     #[serde(default = "backup")]
@@ -82,6 +114,8 @@ pub struct Klog {
     sample: usize,
     #[serde(default = "single_message_size")]
     single_message_size: usize,
+    #[serde(default = "format")]
+    format: KlogFormat,
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -119,6 +153,11 @@ impl Klog {
     pub fn single_message_size(&self) -> usize {
         self.single_message_size
     }
+
+    /// The layout of each log line.
+    pub fn format(&self) -> KlogFormat {
+        self.format
+    }
 }
 
 // trait implementations
@@ -132,6 +171,7 @@ impl Default for Klog {
             queue_depth: queue_depth(),
             sample: sample(),
             single_message_size: single_message_size(),
+            format: format(),
         }
     }
 }
@@ -31,7 +31,7 @@ pub use array::ArrayConfig;
 pub use buf::{Buf, BufConfig};
 pub use dbuf::DbufConfig;
 pub use debug::{Debug, DebugConfig};
-pub use klog::{Klog, KlogConfig};
+pub use klog::{Klog, KlogConfig, KlogFormat};
 pub use momento_proxy::MomentoProxyConfig;
 pub use pingproxy::PingproxyConfig;
 pub use pingserver::PingserverConfig;
@@ -41,6 +41,6 @@ pub use server::{Server, ServerConfig};
 pub use sockio::{Sockio, SockioConfig};
 pub use stats_log::StatsLogConfig;
 pub use tcp::{Tcp, TcpConfig};
-pub use time::{Time, TimeConfig, TimeType};
+pub use time::{MaxTtl, Time, TimeConfig, TimeType};
 pub use tls::{Tls, TlsConfig};
 pub use worker::{Worker, WorkerConfig};
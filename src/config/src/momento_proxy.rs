@@ -1,4 +1,4 @@
-use crate::{Admin, AdminConfig, Debug, DebugConfig, Klog, KlogConfig};
+use crate::{Admin, AdminConfig, Debug, DebugConfig, Klog, KlogConfig, MaxTtl};
 use core::num::NonZeroU64;
 use std::net::AddrParseError;
 use std::net::SocketAddr;
@@ -20,6 +20,36 @@ impl Default for Protocol {
     }
 }
 
+/// What happens to a pub/sub subscriber whose queue of undelivered messages
+/// is full when another message is published to it.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowSubscriberPolicy {
+    /// The oldest queued message is dropped to make room for the new one
+    DropOldest,
+    /// The subscriber is disconnected
+    Disconnect,
+}
+
+impl Default for SlowSubscriberPolicy {
+    fn default() -> Self {
+        Self::DropOldest
+    }
+}
+
+// constants to define default values
+const PUBSUB_MAX_CHANNELS: usize = 1024;
+const PUBSUB_MAX_QUEUED_MESSAGES: usize = 1024;
+
+// helper functions
+fn max_channels() -> usize {
+    PUBSUB_MAX_CHANNELS
+}
+
+fn max_queued_messages() -> usize {
+    PUBSUB_MAX_QUEUED_MESSAGES
+}
+
 // struct definitions
 #[derive(Clone, Serialize, Default, Deserialize, Debug)]
 pub struct MomentoProxyConfig {
@@ -39,6 +69,33 @@ pub struct MomentoProxyConfig {
 #[derive(Default, Clone, Copy, Serialize, Deserialize, Debug)]
 pub struct Proxy {
     threads: Option<usize>,
+    #[serde(default)]
+    chaos: Option<Chaos>,
+    #[serde(default)]
+    pubsub: PubSub,
+}
+
+/// Limits on the pub/sub state each resp connection may hold.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct PubSub {
+    #[serde(default = "max_channels")]
+    max_channels: usize,
+    #[serde(default = "max_queued_messages")]
+    max_queued_messages: usize,
+    #[serde(default)]
+    slow_subscriber_policy: SlowSubscriberPolicy,
+}
+
+/// Fault injection for exercising client timeout and retry handling. This is
+/// only honored by builds of the proxy with the `chaos` feature enabled.
+#[derive(Default, Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct Chaos {
+    #[serde(default)]
+    delay_min_ms: u64,
+    #[serde(default)]
+    delay_max_ms: u64,
+    #[serde(default)]
+    drop_fraction: f64,
 }
 
 // definitions
@@ -49,7 +106,13 @@ pub struct Cache {
     cache_name: String,
     default_ttl: NonZeroU64,
     #[serde(default)]
+    max_ttl: u64,
+    #[serde(default)]
+    max_ttl_strict: bool,
+    #[serde(default)]
     protocol: Protocol,
+    #[serde(default)]
+    lenient_newlines: bool,
 }
 
 // implementation
@@ -79,9 +142,67 @@ impl Cache {
         self.default_ttl
     }
 
+    /// The ceiling, in seconds, on the TTL which clients may set, as it is
+    /// applied to their requests. A ceiling of zero is disabled.
+    pub fn max_ttl(&self) -> MaxTtl {
+        MaxTtl::new(self.max_ttl, self.max_ttl_strict)
+    }
+
     pub fn protocol(&self) -> Protocol {
         self.protocol
     }
+
+    /// Whether a bare LF is accepted, as well as a CRLF, at the end of a text
+    /// command line
+    pub fn lenient_newlines(&self) -> bool {
+        self.lenient_newlines
+    }
+}
+
+impl Chaos {
+    /// The shortest delay, in milliseconds, added before each backend request
+    pub fn delay_min_ms(&self) -> u64 {
+        self.delay_min_ms
+    }
+
+    /// The longest delay, in milliseconds, added before each backend request.
+    /// When this is not above the minimum, the delay is fixed at the minimum.
+    pub fn delay_max_ms(&self) -> u64 {
+        self.delay_max_ms.max(self.delay_min_ms)
+    }
+
+    /// The fraction of responses, between 0.0 and 1.0, which are never sent
+    /// back to the client
+    pub fn drop_fraction(&self) -> f64 {
+        self.drop_fraction
+    }
+}
+
+impl PubSub {
+    /// The most channels a single connection may be subscribed to
+    pub fn max_channels(&self) -> usize {
+        self.max_channels
+    }
+
+    /// The most messages which may be queued for a subscriber before the
+    /// slow subscriber policy is applied
+    pub fn max_queued_messages(&self) -> usize {
+        self.max_queued_messages.max(1)
+    }
+
+    pub fn slow_subscriber_policy(&self) -> SlowSubscriberPolicy {
+        self.slow_subscriber_policy
+    }
+}
+
+impl Default for PubSub {
+    fn default() -> Self {
+        Self {
+            max_channels: max_channels(),
+            max_queued_messages: max_queued_messages(),
+            slow_subscriber_policy: Default::default(),
+        }
+    }
 }
 
 // implementation
@@ -109,6 +230,14 @@ impl MomentoProxyConfig {
     pub fn threads(&self) -> Option<usize> {
         self.proxy.threads
     }
+
+    pub fn chaos(&self) -> Option<Chaos> {
+        self.proxy.chaos
+    }
+
+    pub fn pubsub(&self) -> PubSub {
+        self.proxy.pubsub
+    }
 }
 
 impl AdminConfig for MomentoProxyConfig {
@@ -124,6 +124,10 @@ impl TimeConfig for PingproxyConfig {
     fn time(&self) -> &Time {
         &self.time
     }
+
+    fn time_mut(&mut self) -> &mut Time {
+        &mut self.time
+    }
 }
 
 impl TlsConfig for PingproxyConfig {
@@ -90,6 +90,10 @@ impl ServerConfig for PingserverConfig {
     fn server(&self) -> &Server {
         &self.server
     }
+
+    fn server_mut(&mut self) -> &mut Server {
+        &mut self.server
+    }
 }
 
 impl SockioConfig for PingserverConfig {
@@ -108,6 +112,10 @@ impl TimeConfig for PingserverConfig {
     fn time(&self) -> &Time {
         &self.time
     }
+
+    fn time_mut(&mut self) -> &mut Time {
+        &mut self.time
+    }
 }
 
 impl TlsConfig for PingserverConfig {
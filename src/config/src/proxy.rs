@@ -16,6 +16,8 @@ const NEVENT_MAX: usize = 1024;
 const FRONTEND_THREADS: usize = 1;
 const BACKEND_THREADS: usize = 1;
 const BACKEND_POOLSIZE: usize = 1;
+const BACKEND_KEEPALIVE_INTERVAL_MS: usize = 0;
+const BACKEND_CONNECT_TIMEOUT_MS: usize = 0;
 
 // helper functions
 fn address() -> String {
@@ -42,6 +44,14 @@ fn backend_poolsize() -> usize {
     BACKEND_POOLSIZE
 }
 
+fn backend_keepalive_interval() -> usize {
+    BACKEND_KEEPALIVE_INTERVAL_MS
+}
+
+fn backend_connect_timeout() -> usize {
+    BACKEND_CONNECT_TIMEOUT_MS
+}
+
 // definitions
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Listener {
@@ -51,6 +61,8 @@ pub struct Listener {
     timeout: usize,
     #[serde(default = "nevent")]
     nevent: usize,
+    #[serde(default)]
+    tcp_congestion_control: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -73,6 +85,10 @@ pub struct Backend {
     threads: usize,
     #[serde(default = "backend_poolsize")]
     poolsize: usize,
+    #[serde(default = "backend_keepalive_interval")]
+    keepalive_interval: usize,
+    #[serde(default = "backend_connect_timeout")]
+    connect_timeout: usize,
     endpoints: Vec<String>,
     zk_server: Option<String>,
     zk_path: Option<String>,
@@ -95,6 +111,12 @@ impl Listener {
     pub fn nevent(&self) -> usize {
         self.nevent
     }
+
+    /// The TCP congestion control algorithm for accepted connections, when
+    /// set. Only supported on Linux
+    pub fn tcp_congestion_control(&self) -> Option<&str> {
+        self.tcp_congestion_control.as_deref()
+    }
 }
 
 impl Frontend {
@@ -135,6 +157,23 @@ impl Backend {
         self.nevent
     }
 
+    /// The time in milliseconds a connection to a server endpoint may sit
+    /// idle before a keepalive is sent on it. A connection which doesn't
+    /// answer its keepalive within the same time is replaced. Keepalives are
+    /// disabled when this is zero, or when the proxy has no keepalive request
+    /// for its backend protocol
+    pub fn keepalive_interval(&self) -> usize {
+        self.keepalive_interval
+    }
+
+    /// The time in milliseconds allowed to establish a connection to a server
+    /// endpoint. A connection which isn't established in time is closed and
+    /// another is attempted. There is no limit, other than that of the
+    /// operating system, when this is zero
+    pub fn connect_timeout(&self) -> usize {
+        self.connect_timeout
+    }
+
     // TODO(bmartin): the handling of ZK service discovery is based on how
     // Aurora serversets work and needs to be factored out into some more
     // general way of handling service discovery. We may want to allow for
@@ -250,6 +289,7 @@ impl Default for Listener {
             address: address(),
             timeout: timeout(),
             nevent: nevent(),
+            tcp_congestion_control: None,
         }
     }
 }
@@ -275,6 +315,8 @@ impl Default for Backend {
             zk_path: None,
             zk_endpoint: None,
             poolsize: backend_poolsize(),
+            keepalive_interval: backend_keepalive_interval(),
+            connect_timeout: backend_connect_timeout(),
         }
     }
 }
@@ -3,6 +3,7 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +27,16 @@ const MERGE_MAX: usize = 8;
 
 // datapool
 const DATAPOOL_PATH: Option<&str> = None;
+const LOCK_MEMORY: bool = false;
+const NUMA_NODE: Option<usize> = None;
+const FLUSH_INTERVAL: u64 = 0;
+const FLUSH_TIMEOUT: u64 = 30;
+
+// eviction exemption for privileged clients, disabled by default
+const NO_EVICT_CAP: usize = 0;
+
+// multi-get
+const ORDERED_MULTIGET: bool = true;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum Eviction {
@@ -75,8 +86,32 @@ fn datapool_path() -> Option<String> {
     DATAPOOL_PATH.map(|v| v.to_string())
 }
 
+fn lock_memory() -> bool {
+    LOCK_MEMORY
+}
+
+fn numa_node() -> Option<usize> {
+    NUMA_NODE
+}
+
+fn flush_interval() -> u64 {
+    FLUSH_INTERVAL
+}
+
+fn flush_timeout() -> u64 {
+    FLUSH_TIMEOUT
+}
+
+fn no_evict_cap() -> usize {
+    NO_EVICT_CAP
+}
+
+fn ordered_multiget() -> bool {
+    ORDERED_MULTIGET
+}
+
 // definitions
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Seg {
     #[serde(default = "hash_power")]
     hash_power: u8,
@@ -96,6 +131,18 @@ pub struct Seg {
     compact_target: usize,
     #[serde(default = "datapool_path")]
     datapool_path: Option<String>,
+    #[serde(default = "lock_memory")]
+    lock_memory: bool,
+    #[serde(default = "numa_node")]
+    numa_node: Option<usize>,
+    #[serde(default = "flush_interval")]
+    flush_interval: u64,
+    #[serde(default = "flush_timeout")]
+    flush_timeout: u64,
+    #[serde(default = "no_evict_cap")]
+    no_evict_cap: usize,
+    #[serde(default = "ordered_multiget")]
+    ordered_multiget: bool,
 }
 
 impl Default for Seg {
@@ -110,6 +157,12 @@ impl Default for Seg {
             merge_max: merge_max(),
             compact_target: compact_target(),
             datapool_path: datapool_path(),
+            lock_memory: lock_memory(),
+            numa_node: numa_node(),
+            flush_interval: flush_interval(),
+            flush_timeout: flush_timeout(),
+            no_evict_cap: no_evict_cap(),
+            ordered_multiget: ordered_multiget(),
         }
     }
 }
@@ -151,6 +204,50 @@ impl Seg {
     pub fn datapool_path(&self) -> Option<PathBuf> {
         self.datapool_path.as_ref().map(|v| Path::new(v).to_owned())
     }
+
+    pub fn lock_memory(&self) -> bool {
+        self.lock_memory
+    }
+
+    /// The NUMA node to allocate the heap from. The thread which owns the
+    /// storage is pinned to the CPUs of the same node, so that it does not
+    /// access the heap across sockets. Only supported on Linux.
+    pub fn numa_node(&self) -> Option<usize> {
+        self.numa_node
+    }
+
+    /// How often the storage thread flushes the datapool, in seconds. If a
+    /// flush fails the storage rejects writes until a later flush succeeds.
+    /// Zero disables the periodic flush.
+    pub fn flush_interval(&self) -> Option<Duration> {
+        if self.flush_interval == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.flush_interval))
+        }
+    }
+
+    /// The longest time, in seconds, to wait for the datapool to be flushed
+    /// when the server shuts down. Past this the process exits anyway, and
+    /// the data in the datapool file may be incomplete.
+    pub fn flush_timeout(&self) -> Duration {
+        Duration::from_secs(self.flush_timeout)
+    }
+
+    /// The most bytes which may be held by items written by clients which are
+    /// exempt from eviction, set with `client noevict` on the admin port.
+    /// Beyond this, their items are stored as ordinary items. Zero disables
+    /// the exemption.
+    pub fn no_evict_cap(&self) -> usize {
+        self.no_evict_cap
+    }
+
+    /// Whether the values in a reply to a multi-key get must follow the
+    /// order of the requested keys. When this is off the storage may return
+    /// them in whatever order it looks them up in.
+    pub fn ordered_multiget(&self) -> bool {
+        self.ordered_multiget
+    }
 }
 
 // trait definitions
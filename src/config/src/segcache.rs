@@ -140,6 +140,10 @@ impl ServerConfig for SegcacheConfig {
     fn server(&self) -> &Server {
         &self.server
     }
+
+    fn server_mut(&mut self) -> &mut Server {
+        &mut self.server
+    }
 }
 
 impl SockioConfig for SegcacheConfig {
@@ -158,6 +162,10 @@ impl TimeConfig for SegcacheConfig {
     fn time(&self) -> &Time {
         &self.time
     }
+
+    fn time_mut(&mut self) -> &mut Time {
+        &mut self.time
+    }
 }
 
 impl TlsConfig for SegcacheConfig {
@@ -2,6 +2,8 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+use crate::units::KB;
+
 use serde::{Deserialize, Serialize};
 
 use std::net::{AddrParseError, SocketAddr};
@@ -11,6 +13,9 @@ const SERVER_HOST: &str = "0.0.0.0";
 const SERVER_PORT: &str = "12321";
 const SERVER_TIMEOUT: usize = 100;
 const SERVER_NEVENT: usize = 1024;
+const SERVER_READ_SIZE_MIN: usize = 4 * KB;
+const SERVER_READ_SIZE_MAX: usize = 256 * KB;
+const SERVER_HANDSHAKE_TIMEOUT: usize = 10_000;
 
 // helper functions
 fn host() -> String {
@@ -29,6 +34,18 @@ fn nevent() -> usize {
     SERVER_NEVENT
 }
 
+fn read_size_min() -> usize {
+    SERVER_READ_SIZE_MIN
+}
+
+fn read_size_max() -> usize {
+    SERVER_READ_SIZE_MAX
+}
+
+fn handshake_timeout() -> usize {
+    SERVER_HANDSHAKE_TIMEOUT
+}
+
 // definitions
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Server {
@@ -40,6 +57,24 @@ pub struct Server {
     timeout: usize,
     #[serde(default = "nevent")]
     nevent: usize,
+    #[serde(default)]
+    listen_fd: Option<i32>,
+    #[serde(default)]
+    tcp_congestion_control: Option<String>,
+    #[serde(default = "read_size_min")]
+    read_size_min: usize,
+    #[serde(default = "read_size_max")]
+    read_size_max: usize,
+    #[serde(default)]
+    linger: Option<u64>,
+    #[serde(default)]
+    max_connections_per_ip: Option<usize>,
+    #[serde(default)]
+    lenient_newlines: bool,
+    #[serde(default)]
+    max_line_length: Option<usize>,
+    #[serde(default = "handshake_timeout")]
+    handshake_timeout: usize,
 }
 
 // implementation
@@ -68,6 +103,88 @@ impl Server {
     pub fn nevent(&self) -> usize {
         self.nevent
     }
+
+    /// An already listening socket to accept on instead of binding to the
+    /// host and port
+    pub fn listen_fd(&self) -> Option<i32> {
+        self.listen_fd
+    }
+
+    pub fn set_listen_fd(&mut self, fd: Option<i32>) {
+        self.listen_fd = fd
+    }
+
+    /// The TCP congestion control algorithm for accepted connections, when
+    /// set. Only supported on Linux
+    pub fn tcp_congestion_control(&self) -> Option<&str> {
+        self.tcp_congestion_control.as_deref()
+    }
+
+    /// The smallest size in bytes of each read from a client connection. The
+    /// read size of each connection adapts to the size of what it sends
+    pub fn read_size_min(&self) -> usize {
+        self.read_size_min
+    }
+
+    /// The largest size in bytes of each read from a client connection
+    pub fn read_size_max(&self) -> usize {
+        self.read_size_max
+    }
+
+    /// The `SO_LINGER` timeout in seconds for accepted connections, when set.
+    /// Only zero is supported, which resets connections when they are closed,
+    /// discarding unsent data, so a client which is dropped sees a RST rather
+    /// than a FIN and the server keeps no sockets in TIME_WAIT. Other values
+    /// are rejected at startup, as they would make closing a connection block
+    /// the worker thread. Connections closed while draining or shutting down
+    /// have their pending responses flushed to the socket first, but any of
+    /// those still unsent when the connection is reset are lost.
+    pub fn linger(&self) -> Option<u64> {
+        self.linger
+    }
+
+    pub fn set_linger(&mut self, linger: Option<u64>) {
+        self.linger = linger
+    }
+
+    /// The most connections which may be open from a single client IP
+    /// address, when set. Connections from an address which is already at the
+    /// limit are closed as soon as they are accepted
+    pub fn max_connections_per_ip(&self) -> Option<usize> {
+        self.max_connections_per_ip
+    }
+
+    pub fn set_max_connections_per_ip(&mut self, max: Option<usize>) {
+        self.max_connections_per_ip = max
+    }
+
+    /// Whether a bare LF is accepted, as well as a CRLF, at the end of a text
+    /// command line. Some clients, such as telnet, send only an LF. Data
+    /// blocks and other length-prefixed values still need a CRLF
+    pub fn lenient_newlines(&self) -> bool {
+        self.lenient_newlines
+    }
+
+    /// The longest text command line in bytes, not counting its terminator,
+    /// when set. A client which sends a longer line gets a protocol error
+    /// and is disconnected. When unset, the protocol's default limit applies
+    pub fn max_line_length(&self) -> Option<usize> {
+        self.max_line_length
+    }
+
+    pub fn set_max_line_length(&mut self, max: Option<usize>) {
+        self.max_line_length = max
+    }
+
+    /// The time in milliseconds which a client connection has to complete
+    /// the TLS handshake, after which it is closed. Zero disables the timeout
+    pub fn handshake_timeout(&self) -> usize {
+        self.handshake_timeout
+    }
+
+    pub fn set_handshake_timeout(&mut self, timeout: usize) {
+        self.handshake_timeout = timeout
+    }
 }
 
 // trait implementations
@@ -78,6 +195,15 @@ impl Default for Server {
             port: port(),
             timeout: timeout(),
             nevent: nevent(),
+            listen_fd: None,
+            tcp_congestion_control: None,
+            read_size_min: read_size_min(),
+            read_size_max: read_size_max(),
+            linger: None,
+            max_connections_per_ip: None,
+            lenient_newlines: false,
+            max_line_length: None,
+            handshake_timeout: handshake_timeout(),
         }
     }
 }
@@ -85,4 +211,6 @@ impl Default for Server {
 // trait definitions
 pub trait ServerConfig {
     fn server(&self) -> &Server;
+
+    fn server_mut(&mut self) -> &mut Server;
 }
@@ -2,24 +2,39 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
-pub use common::expiry::TimeType;
+pub use common::expiry::{MaxTtl, TimeType};
 use serde::{Deserialize, Serialize};
 
 // TODO(bmartin): set the default back to unix
 
 // constants to define default values
 pub const DEFAULT_TIME_TYPE: TimeType = TimeType::Memcache;
+// a value of zero disables the ttl ceiling
+pub const DEFAULT_MAX_TTL: u64 = 0;
+pub const DEFAULT_MAX_TTL_STRICT: bool = false;
 
 // helper functions
 fn time_type() -> TimeType {
     DEFAULT_TIME_TYPE
 }
 
+fn max_ttl() -> u64 {
+    DEFAULT_MAX_TTL
+}
+
+fn max_ttl_strict() -> bool {
+    DEFAULT_MAX_TTL_STRICT
+}
+
 // definitions
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Time {
     #[serde(default = "time_type")]
     time_type: TimeType,
+    #[serde(default = "max_ttl")]
+    max_ttl: u64,
+    #[serde(default = "max_ttl_strict")]
+    max_ttl_strict: bool,
 }
 
 // implementation
@@ -27,6 +42,27 @@ impl Time {
     pub fn time_type(&self) -> TimeType {
         self.time_type
     }
+
+    /// The ceiling, in seconds, on the TTL of stored items. Zero disables the
+    /// ceiling.
+    pub fn max_ttl(&self) -> u64 {
+        self.max_ttl
+    }
+
+    /// Whether a TTL above the ceiling is rejected instead of being clamped.
+    pub fn max_ttl_strict(&self) -> bool {
+        self.max_ttl_strict
+    }
+
+    pub fn set_max_ttl(&mut self, max_ttl: u64, strict: bool) {
+        self.max_ttl = max_ttl;
+        self.max_ttl_strict = strict;
+    }
+
+    /// The TTL ceiling as it is applied to stored items.
+    pub fn ttl_limit(&self) -> MaxTtl {
+        MaxTtl::new(self.max_ttl, self.max_ttl_strict)
+    }
 }
 
 // trait implementations
@@ -34,6 +70,8 @@ impl Default for Time {
     fn default() -> Self {
         Self {
             time_type: time_type(),
+            max_ttl: max_ttl(),
+            max_ttl_strict: max_ttl_strict(),
         }
     }
 }
@@ -41,4 +79,6 @@ impl Default for Time {
 // trait definitions
 pub trait TimeConfig {
     fn time(&self) -> &Time;
+
+    fn time_mut(&mut self) -> &mut Time;
 }
@@ -15,6 +15,8 @@ pub struct Tls {
     certificate: Option<String>,
     #[serde(default)]
     ca_file: Option<String>,
+    #[serde(default)]
+    require_client_auth: bool,
 }
 
 // implementation
@@ -34,6 +36,10 @@ impl common::ssl::TlsConfig for Tls {
     fn ca_file(&self) -> Option<String> {
         self.ca_file.clone()
     }
+
+    fn require_client_auth(&self) -> bool {
+        self.require_client_auth
+    }
 }
 
 // trait definitions
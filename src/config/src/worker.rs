@@ -8,6 +8,19 @@ use serde::{Deserialize, Serialize};
 const WORKER_TIMEOUT: usize = 100;
 const WORKER_NEVENT: usize = 1024;
 const WORKER_THREADS: usize = 1;
+// a value of zero disables the request read timeout
+const WORKER_REQUEST_READ_TIMEOUT: usize = 0;
+// a value of zero disables closing sessions with a stalled request
+const WORKER_REQUEST_STALL_SIZE: usize = 0;
+const WORKER_REQUEST_STALL_TIMEOUT: usize = 1000;
+// a value of zero disables output backpressure
+const WORKER_OUTPUT_HIGH_WATERMARK: usize = 0;
+const WORKER_OUTPUT_LOW_WATERMARK: usize = 0;
+const WORKER_STORAGE_BATCH: usize = 32;
+// a value of zero disables the storage command timeout
+const WORKER_STORAGE_COMMAND_TIMEOUT: usize = 0;
+// a value of zero disables releasing the buffers of idle sessions
+const WORKER_BUFFER_IDLE_TIMEOUT: usize = 0;
 
 // helper functions
 fn timeout() -> usize {
@@ -22,6 +35,38 @@ fn threads() -> usize {
     WORKER_THREADS
 }
 
+fn request_read_timeout() -> usize {
+    WORKER_REQUEST_READ_TIMEOUT
+}
+
+fn request_stall_size() -> usize {
+    WORKER_REQUEST_STALL_SIZE
+}
+
+fn request_stall_timeout() -> usize {
+    WORKER_REQUEST_STALL_TIMEOUT
+}
+
+fn output_high_watermark() -> usize {
+    WORKER_OUTPUT_HIGH_WATERMARK
+}
+
+fn output_low_watermark() -> usize {
+    WORKER_OUTPUT_LOW_WATERMARK
+}
+
+fn storage_batch() -> usize {
+    WORKER_STORAGE_BATCH
+}
+
+fn storage_command_timeout() -> usize {
+    WORKER_STORAGE_COMMAND_TIMEOUT
+}
+
+fn buffer_idle_timeout() -> usize {
+    WORKER_BUFFER_IDLE_TIMEOUT
+}
+
 // definitions
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Worker {
@@ -31,6 +76,22 @@ pub struct Worker {
     nevent: usize,
     #[serde(default = "threads")]
     threads: usize,
+    #[serde(default = "request_read_timeout")]
+    request_read_timeout: usize,
+    #[serde(default = "request_stall_size")]
+    request_stall_size: usize,
+    #[serde(default = "request_stall_timeout")]
+    request_stall_timeout: usize,
+    #[serde(default = "output_high_watermark")]
+    output_high_watermark: usize,
+    #[serde(default = "output_low_watermark")]
+    output_low_watermark: usize,
+    #[serde(default = "storage_batch")]
+    storage_batch: usize,
+    #[serde(default = "storage_command_timeout")]
+    storage_command_timeout: usize,
+    #[serde(default = "buffer_idle_timeout")]
+    buffer_idle_timeout: usize,
 }
 
 // implementation
@@ -50,6 +111,87 @@ impl Worker {
     pub fn set_threads(&mut self, threads: usize) {
         self.threads = threads
     }
+
+    /// The time in milliseconds that a client has to finish sending a request
+    /// once its first bytes have been read. Zero disables the timeout.
+    pub fn request_read_timeout(&self) -> usize {
+        self.request_read_timeout
+    }
+
+    pub fn set_request_read_timeout(&mut self, timeout: usize) {
+        self.request_read_timeout = timeout
+    }
+
+    /// The size in bytes beyond which an incomplete request is considered
+    /// stalled if it has not completed within the request stall timeout. The
+    /// session is sent a protocol error and closed, so a client can't make
+    /// the server parse an ever growing buffer which never holds a complete
+    /// request. Zero disables this.
+    pub fn request_stall_size(&self) -> usize {
+        self.request_stall_size
+    }
+
+    pub fn set_request_stall_size(&mut self, bytes: usize) {
+        self.request_stall_size = bytes
+    }
+
+    /// The time in milliseconds that a request which has grown beyond the
+    /// request stall size has to complete. This should allow enough time for
+    /// the largest expected request to arrive from a slow client.
+    pub fn request_stall_timeout(&self) -> usize {
+        self.request_stall_timeout
+    }
+
+    pub fn set_request_stall_timeout(&mut self, timeout: usize) {
+        self.request_stall_timeout = timeout
+    }
+
+    /// The number of response bytes which may wait to be written to a client
+    /// before the session stops processing its requests. Zero disables this.
+    pub fn output_high_watermark(&self) -> usize {
+        self.output_high_watermark
+    }
+
+    /// The number of response bytes which a backpressured session must drain
+    /// down to before it processes requests again.
+    pub fn output_low_watermark(&self) -> usize {
+        self.output_low_watermark
+    }
+
+    /// The most requests a worker sends to the storage thread as one batch
+    /// when there are multiple worker threads. One sends each request on its
+    /// own.
+    pub fn storage_batch(&self) -> usize {
+        self.storage_batch
+    }
+
+    pub fn set_storage_batch(&mut self, batch: usize) {
+        self.storage_batch = batch
+    }
+
+    /// The time in microseconds that storage may spend executing a single
+    /// command. Commands which can be cut short stop once they exceed this
+    /// and fail with a server error, rather than return a partial result.
+    /// Zero disables the timeout.
+    pub fn storage_command_timeout(&self) -> usize {
+        self.storage_command_timeout
+    }
+
+    pub fn set_storage_command_timeout(&mut self, timeout: usize) {
+        self.storage_command_timeout = timeout
+    }
+
+    /// The time in milliseconds that a session must be idle before its read
+    /// and write buffers are freed. They are allocated again when the client
+    /// next sends a request. Zero keeps the buffers for the life of the
+    /// session.
+    pub fn buffer_idle_timeout(&self) -> usize {
+        self.buffer_idle_timeout
+    }
+
+    pub fn set_buffer_idle_timeout(&mut self, timeout: usize) {
+        self.buffer_idle_timeout = timeout
+    }
 }
 
 // trait implementations
@@ -59,6 +201,14 @@ impl Default for Worker {
             timeout: timeout(),
             nevent: nevent(),
             threads: threads(),
+            request_read_timeout: request_read_timeout(),
+            request_stall_size: request_stall_size(),
+            request_stall_timeout: request_stall_timeout(),
+            output_high_watermark: output_high_watermark(),
+            output_low_watermark: output_low_watermark(),
+            storage_batch: storage_batch(),
+            storage_command_timeout: storage_command_timeout(),
+            buffer_idle_timeout: buffer_idle_timeout(),
         }
     }
 }
@@ -4,8 +4,10 @@
 
 use ::net::event::{Event, Source};
 use ::net::*;
+use common::auth::{Authenticator, StaticAuthenticator};
 use common::signal::Signal;
 use common::ssl::tls_acceptor;
+use common::upgrade::Listeners;
 use config::{AdminConfig, TlsConfig};
 use crossbeam_channel::Receiver;
 use logger::*;
@@ -16,8 +18,11 @@ use session::{Buf, ServerSession, Session};
 use slab::Slab;
 use std::collections::VecDeque;
 use std::io::{Error, ErrorKind, Result};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::SyncSender;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tiny_http::{Method, Request, Response};
 use waker::Waker;
 
@@ -66,11 +71,41 @@ counter!(
 
 gauge!(ADMIN_SESSION_CURR, "current number of admin sessions");
 
+counter!(ADMIN_UPGRADE, "number of times the process was upgraded");
+counter!(ADMIN_UPGRADE_EX, "number of upgrades which failed");
+
 // consts
 
 const LISTENER_TOKEN: Token = Token(usize::MAX - 1);
 const WAKER_TOKEN: Token = Token(usize::MAX);
 
+// how long to wait for the storage thread to look up a key for /key/<key>
+const DUMP_KEY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// how long to wait for the storage thread to finish an /evict/<n> request
+const EVICT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// how long to wait for the storage thread to answer a `debug
+// set-active-expire` or `debug object` command
+const DEBUG_TIMEOUT: Duration = Duration::from_secs(1);
+
+// how long to wait for the storage thread to finish a /verify request, which
+// reads the entire hashtable
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// how long to wait for the storage thread to finish a `debug reload` command,
+// which writes out and reads back every item. the reload can't be cancelled,
+// so it still finishes after the command has timed out
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+// how long to wait for the workers to reply to a `client` command
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// how long after an upgrade before the sessions left to drain are counted.
+// this gives sessions which were accepted just before the listener stopped
+// time to reach a worker
+const DRAIN_GRACE: Duration = Duration::from_secs(1);
+
 const KB: u64 = 1024; // one kilobyte in bytes
 const S: u64 = 1_000_000_000; // one second in nanoseconds
 const US: u64 = 1_000; // one microsecond in nanoseconds
@@ -84,9 +119,116 @@ fn map_err(e: std::io::Error) -> Result<()> {
     }
 }
 
+// decodes `%XX` escapes in a url path segment, so that keys containing bytes
+// which can't appear in a url may still be requested
+fn percent_decode(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = input
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    decoded
+}
+
+// broadcasts a signal which carries a reply channel to the sibling threads
+// and collects the replies. each thread replies at most once and then drops
+// its copy of the sender, so the channel disconnects once every reply is in.
+// threads which haven't replied by the timeout are left out
+fn gather<T>(
+    signal_queue_tx: &mut Queues<Signal, ()>,
+    signal: impl FnOnce(SyncSender<T>) -> Signal,
+) -> Vec<T> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let _ = signal_queue_tx.try_send_all(signal(tx));
+    let _ = signal_queue_tx.wake();
+
+    let deadline = std::time::Instant::now() + CLIENT_TIMEOUT;
+    let mut replies = Vec::new();
+    while let Some(timeout) = deadline.checked_duration_since(std::time::Instant::now()) {
+        match rx.recv_timeout(timeout) {
+            Ok(reply) => replies.push(reply),
+            Err(_) => break,
+        }
+    }
+    replies
+}
+
+// asks the storage thread to turn eager expiration on or off
+fn active_expire(signal_queue_tx: &mut Queues<Signal, ()>, enabled: bool) -> AdminResponse {
+    // only the storage thread replies, every other thread drops its copy of
+    // the sender
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let _ = signal_queue_tx.try_send_all(Signal::ActiveExpire(enabled, tx));
+    let _ = signal_queue_tx.wake();
+
+    match rx.recv_timeout(DEBUG_TIMEOUT) {
+        Ok(true) => AdminResponse::Ok,
+        Ok(false) => AdminResponse::server_error("active expire is not supported".to_string()),
+        Err(_) => AdminResponse::server_error("active expire timed out".to_string()),
+    }
+}
+
+// asks the storage thread to describe how the value for a key is stored
+fn debug_object(signal_queue_tx: &mut Queues<Signal, ()>, key: Vec<u8>) -> AdminResponse {
+    // only the storage thread replies, every other thread drops its copy of
+    // the sender
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let _ = signal_queue_tx.try_send_all(Signal::DebugObject(key.into_boxed_slice(), tx));
+    let _ = signal_queue_tx.wake();
+
+    match rx.recv_timeout(DEBUG_TIMEOUT) {
+        Ok(Some(description)) => AdminResponse::object(description),
+        Ok(None) => AdminResponse::not_found(),
+        Err(_) => AdminResponse::server_error("debug object timed out".to_string()),
+    }
+}
+
+// asks the storage thread to reload the storage through a snapshot, which
+// blocks the admin thread until it is done
+fn reload(signal_queue_tx: &mut Queues<Signal, ()>) -> AdminResponse {
+    // only the storage thread replies, every other thread drops its copy of
+    // the sender
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let _ = signal_queue_tx.try_send_all(Signal::Reload(tx));
+    let _ = signal_queue_tx.wake();
+
+    match rx.recv_timeout(RELOAD_TIMEOUT) {
+        Ok(Some(Ok(items))) => AdminResponse::reloaded(items),
+        Ok(Some(Err(e))) => {
+            error!("reload failed: {}", e);
+            AdminResponse::server_error(format!("reload failed: {}", e))
+        }
+        Ok(None) => AdminResponse::server_error("reload is not supported".to_string()),
+        Err(_) => AdminResponse::server_error("reload timed out".to_string()),
+    }
+}
+
 pub struct Admin {
     /// A backlog of tokens that need to be handled
     backlog: VecDeque<Token>,
+    /// The fd of the data listener, which is handed to the new process on an
+    /// upgrade. Upgrades are not supported when this isn't set
+    data_listener: Option<RawFd>,
+    drain_timeout: Duration,
+    /// When set, the process has been upgraded, or asked to drain, at this
+    /// time and is draining its sessions
+    draining: Option<Instant>,
+    /// Checks the bearer token for HTTP endpoints which expose stored data
+    http_auth: Option<Box<dyn Authenticator>>,
+    /// The fd of the listener owned by the HTTP server
+    http_listener: Option<RawFd>,
     http_server: Option<tiny_http::Server>,
     /// The actual network listener for the ASCII Admin Endpoint
     listener: ::net::Listener,
@@ -96,6 +238,10 @@ pub struct Admin {
     nevent: usize,
     /// The actual poll instantance
     poll: Poll,
+    /// Whether the server is refusing writes
+    read_only: Arc<AtomicBool>,
+    /// The number of data sessions held by the worker threads
+    session_count: Arc<AtomicUsize>,
     /// The sessions which have been opened
     sessions: Slab<ServerSession<AdminRequestParser, AdminResponse, AdminRequest>>,
     /// A queue for receiving signals from the parent thread
@@ -104,6 +250,8 @@ pub struct Admin {
     signal_queue_tx: Queues<Signal, ()>,
     /// The timeout for each call to poll
     timeout: Duration,
+    /// How long to wait for the new process to start on an upgrade
+    upgrade_timeout: Duration,
     /// The version of the service
     version: String,
     /// The waker for this thread
@@ -112,12 +260,19 @@ pub struct Admin {
 
 pub struct AdminBuilder {
     backlog: VecDeque<Token>,
+    data_listener: Option<RawFd>,
+    drain_timeout: Duration,
+    http_auth: Option<Box<dyn Authenticator>>,
+    http_listener: Option<RawFd>,
     http_server: Option<tiny_http::Server>,
     listener: ::net::Listener,
     nevent: usize,
     poll: Poll,
+    read_only: Arc<AtomicBool>,
+    session_count: Arc<AtomicUsize>,
     sessions: Slab<ServerSession<AdminRequestParser, AdminResponse, AdminRequest>>,
     timeout: Duration,
+    upgrade_timeout: Duration,
     version: String,
     waker: Arc<Waker>,
 }
@@ -127,12 +282,18 @@ impl AdminBuilder {
         let tls_config = config.tls();
         let config = config.admin();
 
-        let addr = config.socket_addr().map_err(|e| {
-            error!("{}", e);
-            std::io::Error::new(std::io::ErrorKind::Other, "Bad listen address")
-        })?;
+        let tcp_listener = if let Some(fd) = common::upgrade::admin_listener() {
+            // SAFETY: the fd was left open for our exclusive use by the
+            // process we are replacing
+            unsafe { TcpListener::from_listen_fd(fd) }?
+        } else {
+            let addr = config.socket_addr().map_err(|e| {
+                error!("{}", e);
+                std::io::Error::new(std::io::ErrorKind::Other, "Bad listen address")
+            })?;
 
-        let tcp_listener = TcpListener::bind(addr)?;
+            TcpListener::bind(addr)?
+        };
 
         let mut listener = match (config.use_tls(), tls_acceptor(tls_config)?) {
             (true, Some(tls_acceptor)) => ::net::Listener::from((tcp_listener, tls_acceptor)),
@@ -155,39 +316,88 @@ impl AdminBuilder {
 
         let backlog = VecDeque::new();
 
-        let http_server = if config.http_enabled() {
-            let addr = config.http_socket_addr().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::Other, "Bad HTTP listen address")
-            })?;
-            let server = tiny_http::Server::http(addr).map_err(|_| {
+        let (http_server, http_listener) = if config.http_enabled() {
+            let listener = if let Some(fd) = common::upgrade::http_listener() {
+                // SAFETY: the fd was left open for our exclusive use by the
+                // process we are replacing
+                unsafe { std::net::TcpListener::from_raw_fd(fd) }
+            } else {
+                let addr = config.http_socket_addr().map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::Other, "Bad HTTP listen address")
+                })?;
+                std::net::TcpListener::bind(addr)?
+            };
+            let fd = listener.as_raw_fd();
+            let server = tiny_http::Server::from_listener(listener, None).map_err(|_| {
                 std::io::Error::new(std::io::ErrorKind::Other, "Failed to create HTTP server")
             })?;
-            Some(server)
+            (Some(server), Some(fd))
         } else {
-            None
+            (None, None)
         };
 
+        let http_auth = config.http_auth_token().map(|token| {
+            Box::new(StaticAuthenticator::new(None, token.as_bytes())) as Box<dyn Authenticator>
+        });
+
+        let drain_timeout = Duration::from_millis(config.drain_timeout() as u64);
+        let upgrade_timeout = Duration::from_millis(config.upgrade_timeout() as u64);
+
         Ok(Self {
             backlog,
+            data_listener: None,
+            drain_timeout,
+            http_auth,
+            http_listener,
             http_server,
             listener,
             nevent,
             poll,
+            read_only: Arc::new(AtomicBool::new(false)),
+            session_count: Arc::new(AtomicUsize::new(0)),
             sessions,
             timeout,
+            upgrade_timeout,
             version,
             waker,
         })
     }
 
+    /// Allows the process to be upgraded in place by handing its listeners to
+    /// a new copy of the binary. The data listener is owned by another thread,
+    /// and must stay open for as long as the admin thread runs.
+    pub fn upgradable(&mut self, data_listener: RawFd) {
+        self.data_listener = Some(data_listener);
+    }
+
     pub fn version(&mut self, version: &str) {
         self.version = version.to_string();
     }
 
+    /// Replaces the authenticator which checks the bearer token sent to HTTP
+    /// endpoints which expose stored data. By default a static token is taken
+    /// from the config, and without one these endpoints refuse all requests.
+    pub fn http_auth(&mut self, auth: Box<dyn Authenticator>) {
+        self.http_auth = Some(auth);
+    }
+
     pub fn waker(&self) -> Arc<Waker> {
         self.waker.clone()
     }
 
+    /// Returns the flag which indicates that the server is in read-only mode.
+    /// The admin endpoints toggle this flag, and it should be shared with any
+    /// threads which need to refuse writes while it is set.
+    pub fn read_only(&self) -> Arc<AtomicBool> {
+        self.read_only.clone()
+    }
+
+    /// Returns the count of open data sessions. The worker threads keep it up
+    /// to date, and a draining process polls it to tell when it can exit.
+    pub fn session_count(&self) -> Arc<AtomicUsize> {
+        self.session_count.clone()
+    }
+
     pub fn build(
         self,
         log_drain: Box<dyn Drain>,
@@ -196,15 +406,23 @@ impl AdminBuilder {
     ) -> Admin {
         Admin {
             backlog: self.backlog,
+            data_listener: self.data_listener,
+            drain_timeout: self.drain_timeout,
+            draining: None,
+            http_auth: self.http_auth,
+            http_listener: self.http_listener,
             http_server: self.http_server,
             listener: self.listener,
             log_drain,
             nevent: self.nevent,
             poll: self.poll,
+            read_only: self.read_only,
+            session_count: self.session_count,
             sessions: self.sessions,
             signal_queue_rx,
             signal_queue_tx,
             timeout: self.timeout,
+            upgrade_timeout: self.upgrade_timeout,
             version: self.version,
             waker: self.waker,
         }
@@ -260,6 +478,11 @@ fn get_rusage() {
 impl Admin {
     /// Call accept one time
     fn accept(&mut self) {
+        // once upgraded, new sessions are left for the new process to accept
+        if self.draining.is_some() {
+            return;
+        }
+
         ADMIN_SESSION_ACCEPT.increment();
 
         match self
@@ -314,6 +537,33 @@ impl Admin {
 
                 // do some request handling
                 match request {
+                    AdminRequest::ClientKill(filter) => {
+                        let killed = gather(&mut self.signal_queue_tx, |tx| {
+                            Signal::KillClient(filter, tx)
+                        });
+                        session.send(AdminResponse::clients_killed(killed.iter().sum()))?;
+                    }
+                    AdminRequest::ClientNoEvict(filter, no_evict) => {
+                        let updated = gather(&mut self.signal_queue_tx, |tx| {
+                            Signal::NoEvictClient(filter, no_evict, tx)
+                        });
+                        session.send(AdminResponse::clients_updated(updated.iter().sum()))?;
+                    }
+                    AdminRequest::ClientList => {
+                        let mut clients =
+                            gather(&mut self.signal_queue_tx, Signal::ListClients).concat();
+                        clients.sort();
+                        session.send(AdminResponse::clients(clients))?;
+                    }
+                    AdminRequest::DebugActiveExpire(enabled) => {
+                        session.send(active_expire(&mut self.signal_queue_tx, enabled))?;
+                    }
+                    AdminRequest::DebugObject(key) => {
+                        session.send(debug_object(&mut self.signal_queue_tx, key))?;
+                    }
+                    AdminRequest::DebugReload => {
+                        session.send(reload(&mut self.signal_queue_tx))?;
+                    }
                     AdminRequest::FlushAll => {
                         let _ = self.signal_queue_tx.try_send_all(Signal::FlushAll);
                         session.send(AdminResponse::Ok)?;
@@ -321,9 +571,25 @@ impl Admin {
                     AdminRequest::Quit => {
                         return Err(Error::new(ErrorKind::Other, "should hangup"));
                     }
+                    AdminRequest::ReadOnly(enabled) => {
+                        self.read_only.store(enabled, Ordering::Relaxed);
+                        session.send(AdminResponse::Ok)?;
+                    }
                     AdminRequest::Stats => {
                         session.send(AdminResponse::Stats)?;
                     }
+                    AdminRequest::StatsDelta(_) => {
+                        session.send(AdminResponse::server_error(
+                            "stats delta is not supported".to_string(),
+                        ))?;
+                    }
+                    AdminRequest::Upgrade => {
+                        let response = self.upgrade();
+                        self.sessions
+                            .get_mut(token.0)
+                            .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?
+                            .send(response)?;
+                    }
                     AdminRequest::Version => {
                         session.send(AdminResponse::version(self.version.clone()))?;
                     }
@@ -331,6 +597,13 @@ impl Admin {
 
                 ADMIN_RESPONSE_COMPOSE.increment();
 
+                // the session is looked up again, as an upgrade needs to
+                // borrow all of `self`
+                let session = self
+                    .sessions
+                    .get_mut(token.0)
+                    .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?;
+
                 match session.flush() {
                     Ok(_) => Ok(()),
                     Err(e) => map_err(e),
@@ -369,6 +642,83 @@ impl Admin {
         }
     }
 
+    /// Starts a new copy of the binary which takes over the listeners. Once it
+    /// is serving, this process stops accepting and drains its sessions before
+    /// it exits. This blocks the admin thread until the new process is ready.
+    fn upgrade(&mut self) -> AdminResponse {
+        match self.try_upgrade() {
+            Ok(pid) => {
+                ADMIN_UPGRADE.increment();
+                AdminResponse::upgraded(pid)
+            }
+            Err(e) => {
+                ADMIN_UPGRADE_EX.increment();
+                error!("upgrade failed: {}", e);
+                AdminResponse::server_error(e.to_string())
+            }
+        }
+    }
+
+    fn try_upgrade(&mut self) -> Result<u32> {
+        let data = self
+            .data_listener
+            .ok_or_else(|| Error::new(ErrorKind::Other, "upgrade is not supported"))?;
+
+        if self.draining.is_some() {
+            return Err(Error::new(ErrorKind::Other, "already upgraded"));
+        }
+
+        let listeners = Listeners {
+            data,
+            admin: self.listener.as_raw_fd(),
+            http: self.http_listener,
+        };
+        let pid = common::upgrade::spawn(listeners, self.upgrade_timeout)?;
+        info!("upgraded to pid {}, draining sessions", pid);
+
+        // the new process accepts on the shared sockets from here on
+        let _ = self.listener.deregister(self.poll.registry());
+        self.http_server = None;
+        let _ = self.signal_queue_tx.try_send_all(Signal::Drain);
+        let _ = self.signal_queue_tx.wake();
+
+        self.draining = Some(Instant::now());
+
+        Ok(pid)
+    }
+
+    /// Broadcasts a graceful drain to all sibling threads, which stop
+    /// accepting and close each session after its in-flight request. The
+    /// event loop shuts down once no sessions remain or the timeout passes.
+    fn drain(&mut self, timeout: Duration) {
+        if self.draining.is_some() {
+            return;
+        }
+
+        info!("draining sessions");
+
+        let _ = self
+            .signal_queue_tx
+            .try_send_all(Signal::GracefulDrain(timeout));
+        if self.signal_queue_tx.wake().is_err() {
+            fatal!("error waking threads for drain");
+        }
+
+        self.drain_timeout = timeout;
+        self.draining = Some(Instant::now());
+    }
+
+    /// Broadcasts a shutdown to all sibling threads. The caller should stop
+    /// its event loop after this returns.
+    fn shutdown(&mut self) {
+        info!("shutting down");
+        let _ = self.signal_queue_tx.try_send_all(Signal::Shutdown);
+        if self.signal_queue_tx.wake().is_err() {
+            fatal!("error waking threads for shutdown");
+        }
+        let _ = self.log_drain.flush();
+    }
+
     /// Closes the session with the given token
     fn close(&mut self, token: Token) {
         if self.sessions.contains(token.0) {
@@ -601,8 +951,143 @@ impl Admin {
         parts.join("_")
     }
 
+    /// Checks the bearer token sent with a request for a route which exposes
+    /// or modifies stored data. Returns the status to respond with if the
+    /// request is refused, which is always the case if no token is configured.
+    fn refused(&self, request: &Request) -> Option<u16> {
+        let auth = match &self.http_auth {
+            Some(auth) => auth,
+            None => {
+                return Some(403);
+            }
+        };
+
+        let authorized = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+            .map(|token| auth.verify(None, token.as_bytes()))
+            .unwrap_or(false);
+
+        if authorized {
+            None
+        } else {
+            Some(401)
+        }
+    }
+
+    /// Handle a request for the stored value and metadata for a key. The
+    /// lookup is done by the thread which owns the storage, so it sees the
+    /// same data as requests on the data port.
+    fn dump_key(&mut self, request: Request, key: Vec<u8>) {
+        if let Some(status) = self.refused(&request) {
+            let _ = request.respond(Response::empty(status));
+            return;
+        }
+
+        if key.is_empty() {
+            let _ = request.respond(Response::empty(400));
+            return;
+        }
+
+        // only the storage thread replies, every other thread drops its copy
+        // of the sender
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let _ = self
+            .signal_queue_tx
+            .try_send_all(Signal::DumpKey(key.into_boxed_slice(), tx));
+        let _ = self.signal_queue_tx.wake();
+
+        match rx.recv_timeout(DUMP_KEY_TIMEOUT) {
+            Ok(Some(dump)) => {
+                let _ = request.respond(Response::from_string(dump));
+            }
+            Ok(None) => {
+                let _ = request.respond(Response::empty(404));
+            }
+            Err(_) => {
+                let _ = request.respond(Response::empty(503));
+            }
+        }
+    }
+
+    /// Handle a request to immediately evict a number of segments, responding
+    /// with the number of items evicted. Eviction is done by the thread which
+    /// owns the storage, between requests on the data port. This is only
+    /// supported by debug builds.
+    fn evict(&mut self, request: Request, segments: &str) {
+        if let Some(status) = self.refused(&request) {
+            let _ = request.respond(Response::empty(status));
+            return;
+        }
+
+        let segments = match segments.parse::<usize>() {
+            Ok(segments) => segments,
+            Err(_) => {
+                let _ = request.respond(Response::empty(400));
+                return;
+            }
+        };
+
+        // only the storage thread replies, every other thread drops its copy
+        // of the sender
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let _ = self
+            .signal_queue_tx
+            .try_send_all(Signal::Evict(segments, tx));
+        let _ = self.signal_queue_tx.wake();
+
+        match rx.recv_timeout(EVICT_TIMEOUT) {
+            Ok(Some(evicted)) => {
+                let _ = request.respond(Response::from_string(format!("{}\n", evicted)));
+            }
+            Ok(None) => {
+                let _ = request.respond(Response::empty(501));
+            }
+            Err(_) => {
+                let _ = request.respond(Response::empty(503));
+            }
+        }
+    }
+
+    /// Handle a request to check the consistency of the storage, responding
+    /// with `OK` or a description of the first inconsistency found. The check
+    /// is done by the thread which owns the storage, between requests on the
+    /// data port. This is only supported by debug builds.
+    fn verify(&mut self, request: Request) {
+        if let Some(status) = self.refused(&request) {
+            let _ = request.respond(Response::empty(status));
+            return;
+        }
+
+        // only the storage thread replies, every other thread drops its copy
+        // of the sender
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let _ = self.signal_queue_tx.try_send_all(Signal::Verify(tx));
+        let _ = self.signal_queue_tx.wake();
+
+        match rx.recv_timeout(VERIFY_TIMEOUT) {
+            Ok(Some(Ok(()))) => {
+                let _ = request.respond(Response::from_string("OK\n"));
+            }
+            Ok(Some(Err(inconsistency))) => {
+                error!("storage verification failed: {}", inconsistency);
+                let _ = request.respond(
+                    Response::from_string(format!("{}\n", inconsistency)).with_status_code(500),
+                );
+            }
+            Ok(None) => {
+                let _ = request.respond(Response::empty(501));
+            }
+            Err(_) => {
+                let _ = request.respond(Response::empty(503));
+            }
+        }
+    }
+
     /// Handle a HTTP request
-    fn handle_http_request(&self, request: Request) {
+    fn handle_http_request(&mut self, request: Request) {
         let url = request.url();
         let parts: Vec<&str> = url.split('?').collect();
         let url = parts[0];
@@ -637,6 +1122,59 @@ impl Admin {
                     let _ = request.respond(Response::empty(400));
                 }
             },
+            // read-only mode can be inspected with a GET, enabled with a PUT,
+            // and disabled with a DELETE
+            "/readonly" => match request.method() {
+                Method::Get => {
+                    let enabled = self.read_only.load(Ordering::Relaxed);
+                    let _ = request.respond(Response::from_string(format!("{}\n", enabled)));
+                }
+                Method::Put => {
+                    self.read_only.store(true, Ordering::Relaxed);
+                    let _ = request.respond(Response::empty(200));
+                }
+                Method::Delete => {
+                    self.read_only.store(false, Ordering::Relaxed);
+                    let _ = request.respond(Response::empty(200));
+                }
+                _ => {
+                    let _ = request.respond(Response::empty(400));
+                }
+            },
+            // the value stored for a key can be inspected with a GET. this
+            // exposes stored data, so a token must be configured and sent
+            url if url.starts_with("/key/") => {
+                let key = percent_decode(&url[5..]);
+                match request.method() {
+                    Method::Get => {
+                        self.dump_key(request, key);
+                    }
+                    _ => {
+                        let _ = request.respond(Response::empty(400));
+                    }
+                }
+            }
+            // segments can be evicted with a PUT, to test eviction policies.
+            // this discards stored data, so a token must be configured and sent
+            url if url.starts_with("/evict/") => match request.method() {
+                Method::Put => {
+                    let segments = url[7..].to_string();
+                    self.evict(request, &segments);
+                }
+                _ => {
+                    let _ = request.respond(Response::empty(400));
+                }
+            },
+            // the storage can be checked for consistency with a GET. this is
+            // expensive, so a token must be configured and sent
+            "/verify" => match request.method() {
+                Method::Get => {
+                    self.verify(request);
+                }
+                _ => {
+                    let _ = request.respond(Response::empty(400));
+                }
+            },
             _ => {
                 let _ = request.respond(Response::empty(404));
             }
@@ -687,31 +1225,57 @@ impl Admin {
             }
 
             // handle all http requests if the http server is enabled
-            if let Some(ref server) = self.http_server {
-                while let Ok(Some(request)) = server.try_recv() {
-                    self.handle_http_request(request);
-                }
+            while let Some(request) = self
+                .http_server
+                .as_ref()
+                .and_then(|server| server.try_recv().ok().flatten())
+            {
+                self.handle_http_request(request);
             }
 
             // handle all signals
             while let Ok(signal) = self.signal_queue_rx.try_recv() {
                 match signal {
-                    Signal::FlushAll => {}
+                    Signal::FlushAll
+                    | Signal::DumpKey(..)
+                    | Signal::Evict(..)
+                    | Signal::Verify(..)
+                    | Signal::ActiveExpire(..)
+                    | Signal::DebugObject(..)
+                    | Signal::Reload(..)
+                    | Signal::Drain
+                    | Signal::KillClient(..)
+                    | Signal::NoEvictClient(..)
+                    | Signal::ListClients(..) => {}
+                    Signal::GracefulDrain(timeout) => {
+                        self.drain(timeout);
+                    }
                     Signal::Shutdown => {
                         // if a shutdown is received from any
                         // thread, we will broadcast it to all
                         // sibling threads and stop our event loop
-                        info!("shutting down");
-                        let _ = self.signal_queue_tx.try_send_all(Signal::Shutdown);
-                        if self.signal_queue_tx.wake().is_err() {
-                            fatal!("error waking threads for shutdown");
-                        }
-                        let _ = self.log_drain.flush();
+                        self.shutdown();
                         return;
                     }
                 }
             }
 
+            // an upgrade may also be requested by sending the process SIGUSR2
+            if common::upgrade::requested() {
+                let _ = self.upgrade();
+            }
+
+            // once upgraded or draining, exit when the workers have no sessions
+            // left or the drain timeout has passed
+            if let Some(elapsed) = self.draining.map(|start| start.elapsed()) {
+                let sessions = self.session_count.load(Ordering::Relaxed);
+                if (sessions == 0 && elapsed >= DRAIN_GRACE) || elapsed >= self.drain_timeout {
+                    info!("drained with {} sessions remaining", sessions);
+                    self.shutdown();
+                    return;
+                }
+            }
+
             // flush pending log entries to log destinations
             let _ = self.log_drain.flush();
         }
@@ -7,6 +7,7 @@ use crate::*;
 use session::ClientSession;
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::net::SocketAddr;
 
 heatmap!(
     BACKEND_EVENT_DEPTH,
@@ -25,9 +26,21 @@ counter!(
 counter!(BACKEND_EVENT_READ, "the number of read events received");
 counter!(BACKEND_EVENT_TOTAL, "the total number of events received");
 counter!(BACKEND_EVENT_WRITE, "the number of write events received");
+counter!(
+    BACKEND_CONNECT_FAILURES,
+    "the number of backend connections replaced after they failed to connect"
+);
+counter!(
+    BACKEND_KEEPALIVE_FAILURES,
+    "the number of backend connections replaced after their keepalive failed"
+);
 
 pub struct BackendWorkerBuilder<Parser, Request, Response> {
+    connect_timeout: Option<Duration>,
+    endpoints: HashMap<Token, SocketAddr>,
     free_queue: VecDeque<Token>,
+    keepalive: Option<fn() -> Request>,
+    keepalive_interval: Option<Duration>,
     nevent: usize,
     parser: Parser,
     poll: Poll,
@@ -52,12 +65,21 @@ where
 
         let nevent = config.nevent();
         let timeout = Duration::from_millis(config.timeout() as u64);
+        let keepalive_interval = match config.keepalive_interval() {
+            0 => None,
+            ms => Some(Duration::from_millis(ms as u64)),
+        };
+        let connect_timeout = match config.connect_timeout() {
+            0 => None,
+            ms => Some(Duration::from_millis(ms as u64)),
+        };
 
         let mut sessions = Slab::new();
         let mut free_queue = VecDeque::new();
+        let mut endpoints = HashMap::new();
 
         for endpoint in config.socket_addrs()? {
-            let stream = TcpStream::connect(endpoint)?;
+            let stream = connect(endpoint, connect_timeout)?;
             let mut session = ClientSession::new(Session::from(stream), parser.clone());
             let s = sessions.vacant_entry();
             let interest = session.interest();
@@ -65,11 +87,16 @@ where
                 .register(poll.registry(), Token(s.key()), interest)
                 .expect("failed to register");
             free_queue.push_back(Token(s.key()));
+            endpoints.insert(Token(s.key()), endpoint);
             s.insert(session);
         }
 
         Ok(Self {
+            connect_timeout,
+            endpoints,
             free_queue,
+            keepalive: None,
+            keepalive_interval,
             nevent,
             parser,
             poll,
@@ -83,15 +110,29 @@ where
         self.waker.clone()
     }
 
+    /// Sets the request which is sent as a keepalive on idle connections.
+    pub fn keepalive(&mut self, keepalive: fn() -> Request) {
+        self.keepalive = Some(keepalive);
+    }
+
     pub fn build(
         self,
         data_queue: Queues<(Request, Response, Token), (Request, Token)>,
         signal_queue: Queues<(), Signal>,
     ) -> BackendWorker<Parser, Request, Response> {
+        let now = std::time::Instant::now();
+        let idle_since = self.free_queue.iter().map(|token| (*token, now)).collect();
+
         BackendWorker {
             backlog: VecDeque::new(),
+            connect_timeout: self.connect_timeout,
             data_queue,
+            endpoints: self.endpoints,
             free_queue: self.free_queue,
+            idle_since,
+            keepalive: self.keepalive,
+            keepalive_interval: self.keepalive_interval,
+            keepalives: HashMap::new(),
             nevent: self.nevent,
             parser: self.parser,
             pending: HashMap::new(),
@@ -106,8 +147,16 @@ where
 
 pub struct BackendWorker<Parser, Request, Response> {
     backlog: VecDeque<(Request, Token)>,
+    connect_timeout: Option<Duration>,
     data_queue: Queues<(Request, Response, Token), (Request, Token)>,
+    endpoints: HashMap<Token, SocketAddr>,
     free_queue: VecDeque<Token>,
+    // when each session in the free queue became idle
+    idle_since: HashMap<Token, std::time::Instant>,
+    keepalive: Option<fn() -> Request>,
+    keepalive_interval: Option<Duration>,
+    // when each session with an outstanding keepalive sent it
+    keepalives: HashMap<Token, std::time::Instant>,
     nevent: usize,
     parser: Parser,
     pending: HashMap<Token, Token>,
@@ -143,9 +192,14 @@ where
 
         // process up to one request
         match session.receive() {
+            Ok(_) if self.keepalives.remove(&token).is_some() => {
+                // the connection is alive, so it can serve requests again
+                self.free(token);
+                Ok(())
+            }
             Ok((request, response)) => {
                 if let Some(fe_token) = self.pending.remove(&token) {
-                    self.free_queue.push_back(token);
+                    self.free(token);
                     self.data_queue
                         .try_send_to(0, (request, response, fe_token))
                         .map_err(|_| Error::new(ErrorKind::Other, "data queue is full"))
@@ -157,6 +211,143 @@ where
         }
     }
 
+    /// Returns a session which has finished its request to the free queue, or
+    /// sends it the oldest request which is waiting for a session.
+    fn free(&mut self, token: Token) {
+        if let Some((request, fe_token)) = self.backlog.pop_front() {
+            let session = &mut self.sessions[token.0];
+            if session.send(request).is_err() {
+                panic!("we don't handle this right now");
+            }
+            let _ = session.flush();
+            self.pending.insert(token, fe_token);
+        } else {
+            self.free_queue.push_back(token);
+            self.idle_since.insert(token, std::time::Instant::now());
+        }
+    }
+
+    /// Sends a keepalive on each session which has been idle for the
+    /// keepalive interval, and replaces each session which has not answered
+    /// its keepalive within the interval.
+    fn keepalive(&mut self) {
+        let (keepalive, interval) = match (self.keepalive, self.keepalive_interval) {
+            (Some(keepalive), Some(interval)) => (keepalive, interval),
+            _ => {
+                return;
+            }
+        };
+
+        let now = std::time::Instant::now();
+
+        let failed: Vec<Token> = self
+            .keepalives
+            .iter()
+            .filter(|(_, sent)| now.duration_since(**sent) >= interval)
+            .map(|(token, _)| *token)
+            .collect();
+        for token in failed {
+            BACKEND_KEEPALIVE_FAILURES.increment();
+            self.keepalives.remove(&token);
+            self.replace(token);
+        }
+
+        let idle: Vec<Token> = self
+            .free_queue
+            .iter()
+            .filter(|token| {
+                self.idle_since
+                    .get(token)
+                    .map(|since| now.duration_since(*since) >= interval)
+                    .unwrap_or(false)
+            })
+            .copied()
+            .collect();
+        for token in idle {
+            self.free_queue.retain(|t| *t != token);
+            self.idle_since.remove(&token);
+
+            // the session may have been closed while it was idle
+            let sent = match self.sessions.get_mut(token.0) {
+                Some(session) => {
+                    session.send(keepalive()).is_ok() && session.flush().or_else(map_err).is_ok()
+                }
+                None => false,
+            };
+            if sent {
+                self.keepalives.insert(token, now);
+            } else {
+                BACKEND_KEEPALIVE_FAILURES.increment();
+                self.replace(token);
+            }
+        }
+    }
+
+    /// Closes a session and opens a new connection to the same endpoint in
+    /// its place. The endpoint is dropped from the pool if it can't be
+    /// reached.
+    fn replace(&mut self, token: Token) {
+        self.close(token);
+
+        let endpoint = match self.endpoints.remove(&token) {
+            Some(endpoint) => endpoint,
+            None => {
+                return;
+            }
+        };
+
+        let stream = match connect(endpoint, self.connect_timeout) {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!(
+                    "failed to reconnect to backend endpoint {}: {}",
+                    endpoint, e
+                );
+                return;
+            }
+        };
+
+        let mut session = ClientSession::new(Session::from(stream), self.parser.clone());
+        let s = self.sessions.vacant_entry();
+        let token = Token(s.key());
+        let interest = session.interest();
+        if session
+            .register(self.poll.registry(), token, interest)
+            .is_err()
+        {
+            error!("failed to register backend session for {}", endpoint);
+            return;
+        }
+        s.insert(session);
+        self.endpoints.insert(token, endpoint);
+        self.free(token);
+    }
+
+    /// Replaces each session which failed to connect, or which is still
+    /// connecting after the connect timeout. A request which was sent on the
+    /// session is lost, as it is when a session is closed.
+    fn check_connects(&mut self) {
+        let failed: Vec<Token> = self
+            .sessions
+            .iter_mut()
+            .filter_map(|(key, session)| session.check_connect().err().map(|_| Token(key)))
+            .collect();
+
+        for token in failed {
+            self.connect_failed(token);
+        }
+    }
+
+    /// Replaces a session which failed to connect.
+    fn connect_failed(&mut self, token: Token) {
+        BACKEND_CONNECT_FAILURES.increment();
+        self.free_queue.retain(|t| *t != token);
+        self.idle_since.remove(&token);
+        self.keepalives.remove(&token);
+        self.pending.remove(&token);
+        self.replace(token);
+    }
+
     /// Handle write by flushing the session
     fn write(&mut self, token: Token) -> Result<()> {
         let session = self
@@ -210,10 +401,13 @@ where
                                 if session.send(request).is_err() {
                                     panic!("we don't handle this right now");
                                 } else {
+                                    // the session may not get another write
+                                    // event, so the request is flushed now
+                                    let _ = session.flush();
                                     self.pending.insert(be_token, fe_token);
                                 }
                             } else {
-                                self.backlog.push_back((request, token));
+                                self.backlog.push_back((request, fe_token));
                             }
                         }
 
@@ -222,7 +416,18 @@ where
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::FlushAll
+                                | Signal::DumpKey(..)
+                                | Signal::Evict(..)
+                                | Signal::Verify(..)
+                                | Signal::ActiveExpire(..)
+                                | Signal::DebugObject(..)
+                                | Signal::Reload(..)
+                                | Signal::Drain
+                                | Signal::GracefulDrain(_)
+                                | Signal::KillClient(..)
+                                | Signal::NoEvictClient(..)
+                                | Signal::ListClients(..) => {}
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -242,6 +447,18 @@ where
                         if event.is_writable() {
                             BACKEND_EVENT_WRITE.increment();
 
+                            // a session becomes writable once its connect
+                            // has finished, whether or not it succeeded
+                            if self
+                                .sessions
+                                .get_mut(token.0)
+                                .map(|session| session.check_connect().is_err())
+                                .unwrap_or(false)
+                            {
+                                self.connect_failed(token);
+                                continue;
+                            }
+
                             if self.write(token).is_err() {
                                 self.close(token);
                                 continue;
@@ -260,12 +477,24 @@ where
                 }
             }
 
+            self.check_connects();
+            self.keepalive();
+
             // wakes the storage thread if necessary
             let _ = self.data_queue.wake();
         }
     }
 }
 
+/// Starts a connection to the endpoint, which must be established within the
+/// timeout if there is one.
+fn connect(endpoint: SocketAddr, timeout: Option<Duration>) -> Result<TcpStream> {
+    match timeout {
+        Some(timeout) => TcpStream::connect_timeout(endpoint, timeout),
+        None => TcpStream::connect(endpoint),
+    }
+}
+
 pub struct BackendBuilder<Parser, Request, Response> {
     builders: Vec<BackendWorkerBuilder<Parser, Request, Response>>,
 }
@@ -292,6 +521,12 @@ where
         self.builders.iter().map(|b| b.waker()).collect()
     }
 
+    pub fn keepalive(&mut self, keepalive: fn() -> BackendRequest) {
+        for builder in &mut self.builders {
+            builder.keepalive(keepalive);
+        }
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn build(
         mut self,
@@ -130,7 +130,7 @@ impl<FrontendParser, FrontendRequest, FrontendResponse, BackendRequest, BackendR
         BackendResponse,
     >
 where
-    FrontendParser: Parse<FrontendRequest> + Clone,
+    FrontendParser: Parse<FrontendRequest> + ParseErrorReply<FrontendResponse> + Clone,
     FrontendResponse: Compose,
     FrontendResponse: From<BackendResponse>,
     BackendRequest: From<FrontendRequest>,
@@ -163,7 +163,17 @@ where
                 .data_queue
                 .try_send_to(0, (BackendRequest::from(request), token))
                 .map_err(|_| Error::new(ErrorKind::Other, "data queue is full")),
-            Err(e) => map_err(e),
+            Err(e) => {
+                // let the client know why it is being disconnected. the reply
+                // is flushed by the listener when the session is closed
+                if e.kind() != ErrorKind::WouldBlock {
+                    let buffer: &[u8] = (*session).borrow();
+                    if let Some(reply) = self.parser.parse_error_reply(buffer, &e) {
+                        let _ = session.send(reply);
+                    }
+                }
+                map_err(e)
+            }
         }
     }
 
@@ -265,7 +275,18 @@ where
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::FlushAll
+                                | Signal::DumpKey(..)
+                                | Signal::Evict(..)
+                                | Signal::Verify(..)
+                                | Signal::ActiveExpire(..)
+                                | Signal::DebugObject(..)
+                                | Signal::Reload(..)
+                                | Signal::Drain
+                                | Signal::GracefulDrain(_)
+                                | Signal::KillClient(..)
+                                | Signal::NoEvictClient(..)
+                                | Signal::ListClients(..) => {}
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -336,7 +357,7 @@ impl<FrontendParser, FrontendRequest, FrontendResponse, BackendRequest, BackendR
         BackendResponse,
     >
 where
-    FrontendParser: Parse<FrontendRequest> + Clone,
+    FrontendParser: Parse<FrontendRequest> + ParseErrorReply<FrontendResponse> + Clone,
     FrontendResponse: Compose,
     FrontendResponse: From<BackendResponse>,
     BackendRequest: From<FrontendRequest>,
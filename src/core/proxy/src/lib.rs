@@ -23,11 +23,12 @@ use core::time::Duration;
 use crossbeam_channel::{bounded, Receiver, Sender};
 use entrystore::EntryStore;
 use logger::Drain;
-use protocol_common::{Compose, Execute, Parse};
+use protocol_common::{Compose, Execute, Parse, ParseErrorReply};
 use queues::Queues;
 use rustcommon_metrics::*;
 use session::{Buf, ServerSession, Session};
 use slab::Slab;
+use std::borrow::Borrow;
 use std::io::{Error, ErrorKind, Result};
 use std::sync::Arc;
 use waker::Waker;
@@ -41,6 +41,15 @@ impl ListenerBuilder {
 
         let tcp_listener = TcpListener::bind(addr)?;
 
+        if let Some(algorithm) = config.tcp_congestion_control() {
+            tcp_listener
+                .set_congestion_control(algorithm)
+                .map_err(|e| {
+                    error!("{}", e);
+                    e
+                })?;
+        }
+
         let mut listener = if let Some(tls_acceptor) = tls_acceptor(tls_config)? {
             ::net::Listener::from((tcp_listener, tls_acceptor))
         } else {
@@ -295,7 +304,18 @@ impl Listener {
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::FlushAll
+                                | Signal::DumpKey(..)
+                                | Signal::Evict(..)
+                                | Signal::Verify(..)
+                                | Signal::ActiveExpire(..)
+                                | Signal::DebugObject(..)
+                                | Signal::Reload(..)
+                                | Signal::Drain
+                                | Signal::GracefulDrain(_)
+                                | Signal::KillClient(..)
+                                | Signal::NoEvictClient(..)
+                                | Signal::ListClients(..) => {}
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -49,7 +49,8 @@ where
     BackendParser: 'static + Parse<BackendResponse> + Clone + Send,
     BackendRequest: 'static + Send + Compose + From<FrontendRequest> + Compose,
     BackendResponse: 'static + Compose + Send,
-    FrontendParser: 'static + Parse<FrontendRequest> + Clone + Send,
+    FrontendParser:
+        'static + Parse<FrontendRequest> + ParseErrorReply<FrontendResponse> + Clone + Send,
     FrontendRequest: 'static + Send,
     FrontendResponse: 'static + Compose + Send,
     FrontendResponse: From<BackendResponse> + Compose,
@@ -79,6 +80,13 @@ where
         self
     }
 
+    /// Sets the request which is sent to keep idle backend connections alive
+    /// when the backend has a keepalive interval.
+    pub fn keepalive(mut self, keepalive: fn() -> BackendRequest) -> Self {
+        self.backend.keepalive(keepalive);
+        self
+    }
+
     pub fn spawn(self) -> Process {
         let mut thread_wakers = vec![self.listener.waker()];
         thread_wakers.extend_from_slice(&self.backend.wakers());
@@ -94,31 +94,40 @@ extern crate logger;
 use ::net::event::{Event, Source};
 use ::net::*;
 use admin::AdminBuilder;
-use common::signal::Signal;
+use common::auth::Authenticator;
+use common::signal::{ClientFilter, Signal};
 use common::ssl::tls_acceptor;
 use config::*;
 use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use core::time::Duration;
 use crossbeam_channel::{bounded, Sender};
 use entrystore::EntryStore;
 use logger::{Drain, Klog};
-use protocol_common::{Compose, Execute, Parse};
+use protocol_common::{
+    Compose, Execute, KeyspaceEvents, Parse, ParseErrorReply, ReadOnlyMode, SessionAction,
+};
 use queues::Queues;
 use rustcommon_metrics::*;
-use session::{Buf, ServerSession, Session};
+use session::{Buf, IpLimiter, ServerSession, Session};
 use slab::Slab;
+use std::borrow::Borrow;
 use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use waker::Waker;
 
 mod listener;
 mod process;
+mod validate;
 mod workers;
 
 use listener::ListenerBuilder;
 use workers::WorkersBuilder;
 
 pub use process::{Process, ProcessBuilder};
+pub use protocol_common::{KeyEvent, KeyEventKind, KeyspaceNotifier};
+pub use validate::validate;
 
 type Instant = rustcommon_metrics::time::Instant<rustcommon_metrics::time::Nanoseconds<u64>>;
 
@@ -149,6 +158,10 @@ pub static PERCENTILES: &[(&str, f64)] = &[
 
 // stats
 counter!(PROCESS_REQ);
+counter!(
+    PROCESS_READ_ONLY_REJECT,
+    "the number of writes refused while in read-only mode"
+);
 
 fn map_err(e: std::io::Error) -> Result<()> {
     match e.kind() {
@@ -157,4 +170,45 @@ fn map_err(e: std::io::Error) -> Result<()> {
     }
 }
 
+/// Executes a request against the storage. Writes are refused with the
+/// protocol's error response while the server is read-only. Items written by
+/// a session which is marked no-evict are exempt from eviction. The keys which
+/// the request changed are reported to the notifier, if there is one. Any
+/// action the request asks for is returned with the response, and should be
+/// applied to the session after the response is sent.
+fn execute<Request, Response, Storage>(
+    storage: &mut Storage,
+    read_only: &AtomicBool,
+    request: &Request,
+    no_evict: bool,
+    notifier: &mut Option<Box<dyn KeyspaceNotifier>>,
+) -> (Response, Option<SessionAction>)
+where
+    Request: ReadOnlyMode<Response> + KeyspaceEvents<Response>,
+    Response: Compose,
+    Storage: Execute<Request, Response> + EntryStore,
+{
+    if read_only.load(Ordering::Relaxed) {
+        if let Some(response) = request.read_only_error() {
+            PROCESS_READ_ONLY_REJECT.increment();
+            return (response, None);
+        }
+    }
+
+    let (response, action) = if no_evict {
+        storage.set_no_evict(true);
+        let result = storage.execute_with_action(request);
+        storage.set_no_evict(false);
+        result
+    } else {
+        storage.execute_with_action(request)
+    };
+
+    if let Some(notifier) = notifier {
+        request.keyspace_events(&response, notifier.as_mut());
+    }
+
+    (response, action)
+}
+
 common::metrics::test_no_duplicates!();
@@ -3,8 +3,12 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use crate::*;
+use std::os::unix::prelude::{AsRawFd, RawFd};
 use std::time::Duration;
 
+// the first fd passed by systemd socket activation, see sd_listen_fds(3)
+const SD_LISTEN_FDS_START: RawFd = 3;
+
 counter!(LISTENER_EVENT_ERROR, "the number of error events received");
 counter!(
     LISTENER_EVENT_LOOP,
@@ -18,14 +22,54 @@ counter!(
     LISTENER_SESSION_DISCARD,
     "the number of sessions discarded by the listener"
 );
+counter!(
+    PER_IP_CONNECTION_REJECTED,
+    "the number of connections closed because their client address was at the connection limit"
+);
+
+/// Returns the fd of an already listening socket to use instead of binding,
+/// either handed over by the process this one is replacing, from the config,
+/// or passed with systemd socket activation.
+pub(crate) fn listen_fd(config: &Server) -> Option<RawFd> {
+    if let Some(fd) = common::upgrade::data_listener() {
+        return Some(fd);
+    }
+
+    if let Some(fd) = config.listen_fd() {
+        return Some(fd);
+    }
+
+    // LISTEN_PID guards against using fds which were meant for a parent
+    let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+    let fds: usize = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+
+    if pid == std::process::id() && fds > 0 {
+        Some(SD_LISTEN_FDS_START)
+    } else {
+        None
+    }
+}
 
 pub struct Listener {
-    /// The actual network listener server
-    listener: ::net::Listener,
+    /// Set once the listening socket has been handed to a new process, or
+    /// the process is draining gracefully, after which no more sessions are
+    /// accepted
+    draining: bool,
+    /// The actual network listener server, which is closed when the process
+    /// is draining gracefully
+    listener: Option<::net::Listener>,
+    /// How long a session has to complete its handshake before it is closed
+    handshake_timeout: Option<Duration>,
     /// The maximum number of events to process per call to poll
     nevent: usize,
     /// The actual poll instantance
     poll: Poll,
+    /// The bounds on the read size of each session
+    read_size_limits: (usize, usize),
+    /// The `SO_LINGER` timeout to set on each accepted stream
+    linger: Option<Duration>,
+    /// Counts the sessions from each client address, when they are limited
+    per_ip_limit: Option<IpLimiter>,
     /// Sessions which have been opened, but are not fully established
     sessions: Slab<Session>,
     /// Queues for sending established sessions to the worker thread(s) and to
@@ -39,10 +83,27 @@ pub struct Listener {
     waker: Arc<Waker>,
 }
 
+/// Returns the `SO_LINGER` timeout for accepted streams, or an error if it is
+/// one which would make closing them block.
+pub(crate) fn check_linger(config: &Server) -> Result<Option<Duration>> {
+    match config.linger() {
+        Some(0) => Ok(Some(Duration::ZERO)),
+        Some(_) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "linger must be 0, other values block when closing connections",
+        )),
+        None => Ok(None),
+    }
+}
+
 pub struct ListenerBuilder {
+    handshake_timeout: Option<Duration>,
+    linger: Option<Duration>,
     listener: ::net::Listener,
     nevent: usize,
+    per_ip_limit: Option<IpLimiter>,
     poll: Poll,
+    read_size_limits: (usize, usize),
     sessions: Slab<Session>,
     timeout: Duration,
     waker: Arc<Waker>,
@@ -53,12 +114,30 @@ impl ListenerBuilder {
         let tls_config = config.tls();
         let config = config.server();
 
-        let addr = config.socket_addr().map_err(|e| {
-            error!("{}", e);
-            std::io::Error::new(std::io::ErrorKind::Other, "Bad listen address")
-        })?;
+        let tcp_listener = if let Some(fd) = listen_fd(config) {
+            // SAFETY: the fd was handed to us for our exclusive use, and is
+            // checked to be a listening socket before it is used
+            unsafe { TcpListener::from_listen_fd(fd) }.map_err(|e| {
+                error!("listen fd {}: {}", fd, e);
+                std::io::Error::new(std::io::ErrorKind::Other, "Bad listen fd")
+            })?
+        } else {
+            let addr = config.socket_addr().map_err(|e| {
+                error!("{}", e);
+                std::io::Error::new(std::io::ErrorKind::Other, "Bad listen address")
+            })?;
 
-        let tcp_listener = TcpListener::bind(addr)?;
+            TcpListener::bind(addr)?
+        };
+
+        if let Some(algorithm) = config.tcp_congestion_control() {
+            tcp_listener
+                .set_congestion_control(algorithm)
+                .map_err(|e| {
+                    error!("{}", e);
+                    e
+                })?;
+        }
 
         let mut listener = if let Some(tls_acceptor) = tls_acceptor(tls_config)? {
             ::net::Listener::from((tcp_listener, tls_acceptor))
@@ -75,13 +154,27 @@ impl ListenerBuilder {
 
         let nevent = config.nevent();
         let timeout = Duration::from_millis(config.timeout() as u64);
+        let read_size_limits = (config.read_size_min(), config.read_size_max());
+        let linger = check_linger(config).map_err(|e| {
+            error!("{}", e);
+            std::io::Error::new(std::io::ErrorKind::Other, "Bad linger")
+        })?;
+        let per_ip_limit = config.max_connections_per_ip().map(IpLimiter::new);
+        let handshake_timeout = match config.handshake_timeout() {
+            0 => None,
+            ms => Some(Duration::from_millis(ms as u64)),
+        };
 
         let sessions = Slab::new();
 
         Ok(Self {
+            handshake_timeout,
+            linger,
             listener,
             nevent,
+            per_ip_limit,
             poll,
+            read_size_limits,
             sessions,
             timeout,
             waker,
@@ -92,15 +185,26 @@ impl ListenerBuilder {
         self.waker.clone()
     }
 
+    /// Returns the fd of the listening socket, which is handed to the new
+    /// process when the process is upgraded.
+    pub fn listener_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+
     pub fn build(
         self,
         signal_queue: Queues<(), Signal>,
         session_queue: Queues<Session, Session>,
     ) -> Listener {
         Listener {
-            listener: self.listener,
+            draining: false,
+            handshake_timeout: self.handshake_timeout,
+            linger: self.linger,
+            listener: Some(self.listener),
             nevent: self.nevent,
+            per_ip_limit: self.per_ip_limit,
             poll: self.poll,
+            read_size_limits: self.read_size_limits,
             sessions: self.sessions,
             session_queue,
             signal_queue,
@@ -111,10 +215,60 @@ impl ListenerBuilder {
 }
 
 impl Listener {
+    /// Creates a session for a newly accepted stream. Returns `None` if the
+    /// client address already has as many sessions as the limit allows, and
+    /// the stream is closed as it is dropped.
+    fn session(&self, mut stream: Stream) -> Option<Session> {
+        // PROXY protocol headers aren't supported, so the peer is the client
+        let permit = match &self.per_ip_limit {
+            Some(limiter) => {
+                let ip = stream.peer_addr().ok()?.ip();
+                match limiter.acquire(ip) {
+                    Some(permit) => Some(permit),
+                    None => {
+                        PER_IP_CONNECTION_REJECTED.increment();
+                        return None;
+                    }
+                }
+            }
+            None => None,
+        };
+
+        if let Some(linger) = self.linger {
+            if let Err(e) = stream.set_linger(Some(linger)) {
+                warn!("failed to set linger: {}", e);
+            }
+        }
+
+        let mut session = Session::from(stream);
+        let (min, max) = self.read_size_limits;
+        session.set_read_size_limits(min, max);
+        if let Some(permit) = permit {
+            session.set_ip_permit(permit);
+        }
+        Some(session)
+    }
+
     /// Accept new sessions
     fn accept(&mut self) {
+        if self.draining {
+            return;
+        }
+
         for _ in 0..ACCEPT_BATCH {
-            if let Ok(mut session) = self.listener.accept().map(Session::from) {
+            let accepted = match self.listener.as_ref() {
+                Some(listener) => listener.accept(),
+                None => {
+                    return;
+                }
+            };
+
+            if let Ok(session) = accepted.map(|s| self.session(s)) {
+                let mut session = match session {
+                    Some(session) => session,
+                    None => continue,
+                };
+
                 if session.is_handshaking() {
                     let s = self.sessions.vacant_entry();
                     let interest = session.interest();
@@ -148,12 +302,13 @@ impl Listener {
         }
 
         // reregister is needed here so we will call accept if there is a backlog
-        if self
-            .listener
-            .reregister(self.poll.registry(), LISTENER_TOKEN, Interest::READABLE)
-            .is_err()
-        {
-            // failed to reregister listener? how do we handle this?
+        if let Some(listener) = self.listener.as_mut() {
+            if listener
+                .reregister(self.poll.registry(), LISTENER_TOKEN, Interest::READABLE)
+                .is_err()
+            {
+                // failed to reregister listener? how do we handle this?
+            }
         }
     }
 
@@ -197,6 +352,25 @@ impl Listener {
         }
     }
 
+    /// Close any sessions which have been handshaking for longer than the
+    /// handshake timeout, such as clients which connect but never send a
+    /// `ClientHello`.
+    fn close_handshake_timed_out(&mut self) {
+        if let Some(timeout) = self.handshake_timeout {
+            let timed_out: Vec<Token> = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.handshake_timed_out(timeout))
+                .map(|(key, _)| Token(key))
+                .collect();
+
+            for token in timed_out {
+                STREAM_HANDSHAKE_TIMEOUT.increment();
+                self.close(token);
+            }
+        }
+    }
+
     fn handshake(&mut self, token: Token) -> Result<()> {
         let session = self
             .sessions
@@ -256,12 +430,14 @@ impl Listener {
         info!(
             "running server on: {}",
             self.listener
-                .local_addr()
+                .as_ref()
+                .and_then(|l| l.local_addr().ok())
                 .map(|v| format!("{v}"))
-                .unwrap_or_else(|_| "unknown address".to_string())
+                .unwrap_or_else(|| "unknown address".to_string())
         );
 
         let mut events = Events::with_capacity(self.nevent);
+        let mut last_sweep = Instant::now();
 
         // repeatedly run accepting new connections and moving them to the worker
         loop {
@@ -294,7 +470,32 @@ impl Listener {
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::FlushAll
+                                | Signal::DumpKey(..)
+                                | Signal::Evict(..)
+                                | Signal::Verify(..)
+                                | Signal::ActiveExpire(..)
+                                | Signal::DebugObject(..)
+                                | Signal::Reload(..)
+                                | Signal::KillClient(..)
+                                | Signal::NoEvictClient(..)
+                                | Signal::ListClients(..) => {}
+                                Signal::Drain => {
+                                    // the new process accepts on the shared
+                                    // socket from here on
+                                    self.draining = true;
+                                    if let Some(listener) = self.listener.as_mut() {
+                                        let _ = listener.deregister(self.poll.registry());
+                                    }
+                                }
+                                Signal::GracefulDrain(_) => {
+                                    // closing the socket refuses any new
+                                    // connections
+                                    self.draining = true;
+                                    if let Some(mut listener) = self.listener.take() {
+                                        let _ = listener.deregister(self.poll.registry());
+                                    }
+                                }
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -310,6 +511,13 @@ impl Listener {
             }
 
             let _ = self.session_queue.wake();
+
+            // periodically check for sessions which are stuck handshaking
+            let timestamp = Instant::now();
+            if (timestamp - last_sweep).as_nanos() >= self.timeout.as_nanos() as u64 {
+                last_sweep = timestamp;
+                self.close_handshake_timed_out();
+            }
         }
     }
 }
@@ -14,8 +14,13 @@ pub struct ProcessBuilder<Parser, Request, Response, Storage> {
 
 impl<Parser, Request, Response, Storage> ProcessBuilder<Parser, Request, Response, Storage>
 where
-    Parser: 'static + Parse<Request> + Clone + Send,
-    Request: 'static + Klog + Klog<Response = Response> + Send,
+    Parser: 'static + Parse<Request> + ParseErrorReply<Response> + Clone + Send,
+    Request: 'static
+        + Klog
+        + Klog<Response = Response>
+        + ReadOnlyMode<Response>
+        + KeyspaceEvents<Response>
+        + Send,
     Response: 'static + Compose + Send,
     Storage: 'static + Execute<Request, Response> + EntryStore + Send,
 {
@@ -25,10 +30,14 @@ where
         parser: Parser,
         storage: Storage,
     ) -> Result<Self> {
-        let admin = AdminBuilder::new(config)?;
+        let mut admin = AdminBuilder::new(config)?;
         let listener = ListenerBuilder::new(config)?;
         let workers = WorkersBuilder::new(config, parser, storage)?;
 
+        // the admin thread upgrades the process by handing the listeners to a
+        // new copy of the binary
+        admin.upgradable(listener.listener_fd());
+
         Ok(Self {
             admin,
             listener,
@@ -42,10 +51,33 @@ where
         self
     }
 
+    /// Checks the bearer token sent to admin HTTP endpoints which expose
+    /// stored data with the authenticator, instead of the static token from
+    /// the config.
+    pub fn http_auth(mut self, auth: Box<dyn Authenticator>) -> Self {
+        self.admin.http_auth(auth);
+        self
+    }
+
+    /// Reports the keys changed by each request to the notifier. By default
+    /// there is no notifier, and nothing is reported.
+    pub fn keyspace_notifier(mut self, notifier: Box<dyn KeyspaceNotifier>) -> Self {
+        self.workers.keyspace_notifier(notifier);
+        self
+    }
+
     pub fn spawn(self) -> Process {
         let mut thread_wakers = vec![self.listener.waker()];
         thread_wakers.extend_from_slice(&self.workers.wakers());
 
+        // the read-only flag is shared between the admin and worker threads so
+        // that the mode can be toggled from either
+        let read_only = self.admin.read_only();
+
+        // the workers count their sessions so that a draining admin thread
+        // can tell when they have all closed
+        let session_count = self.admin.session_count();
+
         // channel for the parent `Process` to send `Signal`s to the admin thread
         let (signal_tx, signal_rx) = bounded(QUEUE_CAPACITY);
 
@@ -68,7 +100,12 @@ where
             .listener
             .build(signal_queue_rx.remove(0), listener_session_queues.remove(0));
 
-        let workers = self.workers.build(worker_session_queues, signal_queue_rx);
+        let workers = self.workers.build(
+            worker_session_queues,
+            signal_queue_rx,
+            read_only,
+            session_count,
+        );
 
         let admin = std::thread::Builder::new()
             .name(format!("{}_admin", THREAD_PREFIX))
@@ -82,6 +119,11 @@ where
 
         let workers = workers.spawn();
 
+        // an upgrade can be requested by signal, and if this process is the
+        // result of an upgrade, the process it replaces can now drain
+        common::upgrade::handle_signal();
+        common::upgrade::notify_ready();
+
         Process {
             admin,
             listener,
@@ -117,6 +159,30 @@ impl Process {
         self.wait()
     }
 
+    /// Gracefully drains the `Process` before shutting it down. New
+    /// connections are refused, and each open session is closed once it has
+    /// finished its in-flight request. The process shuts down once no sessions
+    /// remain, or when the timeout has passed.
+    ///
+    /// Will terminate ungracefully if it encounters an error in sending the
+    /// drain to the admin thread.
+    ///
+    /// This function will block until all threads have terminated.
+    pub fn drain(self, timeout: Duration) {
+        // the admin thread broadcasts the drain to all sibling threads and
+        // shuts them down once the sessions are drained
+        if self
+            .signal_tx
+            .try_send(Signal::GracefulDrain(timeout))
+            .is_err()
+        {
+            fatal!("error sending drain signal to thread");
+        }
+
+        // wait and join all threads
+        self.wait()
+    }
+
     /// Will block until all threads terminate. This should be used to keep the
     /// process alive while the child threads run.
     pub fn wait(self) {
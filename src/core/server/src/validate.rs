@@ -0,0 +1,165 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Checks a configuration for problems which would otherwise only be found
+//! while launching the server. Nothing here binds a socket or spawns a thread,
+//! so it is safe to run alongside a live instance using the same config.
+
+use crate::listener::{check_linger, listen_fd};
+use crate::*;
+use common::ssl::TlsConfig as _;
+
+/// Runs the same checks that are performed during startup, returning a
+/// description of each problem found. An empty result means the config is
+/// valid. Storage is not checked here, see the storage's own `validate`.
+pub fn validate<T>(config: &T) -> Vec<String>
+where
+    T: AdminConfig + ServerConfig + TlsConfig + WorkerConfig,
+{
+    let mut errors = Vec::new();
+
+    let server = config.server();
+    if let Err(e) = server.socket_addr() {
+        errors.push(format!(
+            "server: bad listen address {}:{}: {}",
+            server.host(),
+            server.port(),
+            e
+        ));
+    }
+    if server.nevent() == 0 {
+        errors.push("server: nevent must be greater than zero".to_string());
+    }
+    if let Some(fd) = listen_fd(server) {
+        if let Err(e) = TcpListener::check_listen_fd(fd) {
+            errors.push(format!("server: listen fd {}: {}", fd, e));
+        }
+    }
+    if let Err(e) = check_linger(server) {
+        errors.push(format!("server: {}", e));
+    }
+    if let Some(algorithm) = server.tcp_congestion_control() {
+        if let Err(e) = check_congestion_control(algorithm) {
+            errors.push(format!("server: {}", e));
+        }
+    }
+
+    let admin = config.admin();
+    if let Err(e) = admin.socket_addr() {
+        errors.push(format!(
+            "admin: bad listen address {}:{}: {}",
+            admin.host(),
+            admin.port(),
+            e
+        ));
+    }
+    if admin.http_enabled() {
+        if let Err(e) = admin.http_socket_addr() {
+            errors.push(format!("admin: bad http listen address: {}", e));
+        }
+    }
+
+    let worker = config.worker();
+    if worker.threads() == 0 {
+        errors.push("worker: threads must be greater than zero".to_string());
+    }
+    if worker.nevent() == 0 {
+        errors.push("worker: nevent must be greater than zero".to_string());
+    }
+
+    // report unreadable files individually, as the error from the tls builder
+    // does not say which file was the problem
+    let tls = config.tls();
+    let files = [
+        ("certificate_chain", tls.certificate_chain()),
+        ("private_key", tls.private_key()),
+        ("certificate", tls.certificate()),
+        ("ca_file", tls.ca_file()),
+    ];
+    let mut readable = true;
+    for (name, file) in files {
+        if let Some(file) = file {
+            if let Err(e) = std::fs::File::open(&file) {
+                errors.push(format!("tls: cannot read {}: {}: {}", name, file, e));
+                readable = false;
+            }
+        }
+    }
+    if readable {
+        if let Err(e) = tls_acceptor(tls) {
+            errors.push(format!("tls: {}", e));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn load(name: &str, content: &str) -> SegcacheConfig {
+        let path = std::env::temp_dir().join(format!(
+            "pelikan-validate-{}-{}.toml",
+            name,
+            std::process::id()
+        ));
+        let mut file = std::fs::File::create(&path).expect("failed to create config");
+        file.write_all(content.as_bytes())
+            .expect("failed to write config");
+        let config = SegcacheConfig::load(path.to_str().unwrap()).expect("failed to load config");
+        let _ = std::fs::remove_file(&path);
+        config
+    }
+
+    #[test]
+    fn good() {
+        let config = SegcacheConfig::default();
+        assert!(validate(&config).is_empty());
+    }
+
+    #[test]
+    fn listen_fd() {
+        use std::os::unix::prelude::{FromRawFd, IntoRawFd};
+
+        let fd = std::net::UdpSocket::bind("127.0.0.1:0")
+            .expect("failed to bind")
+            .into_raw_fd();
+
+        let mut config = SegcacheConfig::default();
+        config.server_mut().set_listen_fd(Some(fd));
+
+        let errors = validate(&config);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with(&format!("server: listen fd {}", fd)));
+
+        // the fd is only inspected, and is still open afterwards
+        assert!(unsafe { libc::fcntl(fd, libc::F_GETFD) } >= 0);
+        drop(unsafe { std::net::UdpSocket::from_raw_fd(fd) });
+    }
+
+    #[test]
+    fn bad() {
+        let config = load(
+            "bad",
+            "[server]\n\
+            host = \"not-an-address\"\n\
+            linger = 5\n\
+            [worker]\n\
+            threads = 0\n\
+            [tls]\n\
+            private_key = \"/nonexistent/pelikan.key\"\n\
+            certificate = \"/nonexistent/pelikan.crt\"\n",
+        );
+
+        let errors = validate(&config);
+        assert_eq!(errors.len(), 5);
+        assert!(errors[0].starts_with("server: bad listen address"));
+        assert!(errors[1].starts_with("server: linger"));
+        assert!(errors[2].starts_with("worker: threads"));
+        assert!(errors[3].starts_with("tls: cannot read private_key"));
+        assert!(errors[4].starts_with("tls: cannot read certificate"));
+    }
+}
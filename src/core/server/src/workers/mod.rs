@@ -30,6 +30,126 @@ counter!(
 counter!(WORKER_EVENT_READ, "the number of read events received");
 counter!(WORKER_EVENT_TOTAL, "the total number of events received");
 counter!(WORKER_EVENT_WRITE, "the number of write events received");
+counter!(
+    WORKER_BATCH_SEND,
+    "the number of batches of requests sent to the storage thread"
+);
+counter!(
+    WORKER_CLIENT_KILL,
+    "the number of sessions closed at the request of an operator"
+);
+counter!(
+    WORKER_DRAIN_CLOSE,
+    "the number of sessions closed after finishing their last request during a graceful drain"
+);
+counter!(
+    WORKER_REQUEST_READ_TIMEOUT,
+    "the number of sessions closed for not completing a request in time"
+);
+
+/// Converts the configured request read timeout, where zero means disabled.
+fn request_read_timeout<T: WorkerConfig>(config: &T) -> Option<Duration> {
+    match config.worker().request_read_timeout() {
+        0 => None,
+        ms => Some(Duration::from_millis(ms as u64)),
+    }
+}
+
+/// Converts the configured request stall size and timeout, where a size of
+/// zero means disabled.
+fn request_stall<T: WorkerConfig>(config: &T) -> Option<(usize, Duration)> {
+    match config.worker().request_stall_size() {
+        0 => None,
+        size => Some((
+            size,
+            Duration::from_millis(config.worker().request_stall_timeout() as u64),
+        )),
+    }
+}
+
+/// Converts the configured time before the buffers of an idle session are
+/// released, where zero means disabled.
+fn buffer_idle_timeout<T: WorkerConfig>(config: &T) -> Option<Duration> {
+    match config.worker().buffer_idle_timeout() {
+        0 => None,
+        ms => Some(Duration::from_millis(ms as u64)),
+    }
+}
+
+/// Converts the configured output watermarks, where a high watermark of zero
+/// means that output backpressure is disabled.
+fn output_watermarks<T: WorkerConfig>(config: &T) -> Option<(usize, usize)> {
+    match config.worker().output_high_watermark() {
+        0 => None,
+        high => Some((high, config.worker().output_low_watermark())),
+    }
+}
+
+/// Converts the configured storage command timeout, where zero means
+/// disabled.
+fn storage_command_timeout<T: WorkerConfig>(config: &T) -> Option<Duration> {
+    match config.worker().storage_command_timeout() {
+        0 => None,
+        us => Some(Duration::from_micros(us as u64)),
+    }
+}
+
+/// Pins the calling thread to the CPUs of a NUMA node, so that the thread
+/// which owns the storage runs on the node its memory was allocated from.
+/// Failing to pin is not fatal, the thread keeps running wherever the
+/// scheduler puts it.
+fn pin_to_numa_node(node: usize) {
+    if let Err(e) = set_numa_affinity(node) {
+        warn!(
+            "failed to pin thread to NUMA node {}: {}, continuing without pinning",
+            node, e
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_numa_affinity(node: usize) -> Result<()> {
+    let cpulist =
+        std::fs::read_to_string(format!("/sys/devices/system/node/node{}/cpulist", node))?;
+
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let mut cpus = 0;
+
+    // the list is a comma separated set of cpus and inclusive ranges of
+    // cpus, eg: `0-3,8-11`
+    for range in cpulist.trim().split(',').filter(|r| !r.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start: usize = start
+            .parse()
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "malformed cpulist"))?;
+        let end: usize = end
+            .parse()
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "malformed cpulist"))?;
+
+        for cpu in start..=end.min(libc::CPU_SETSIZE as usize - 1) {
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+            cpus += 1;
+        }
+    }
+
+    if cpus == 0 {
+        return Err(Error::new(ErrorKind::Other, "node has no cpus"));
+    }
+
+    if unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) } != 0 {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_numa_affinity(_node: usize) -> Result<()> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "not supported on this platform",
+    ))
+}
 
 fn map_result(result: Result<usize>) -> Result<()> {
     match result {
@@ -51,8 +171,13 @@ pub enum Workers<Parser, Request, Response, Storage> {
 
 impl<Parser, Request, Response, Storage> Workers<Parser, Request, Response, Storage>
 where
-    Parser: 'static + Parse<Request> + Clone + Send,
-    Request: 'static + Klog + Klog<Response = Response> + Send,
+    Parser: 'static + Parse<Request> + ParseErrorReply<Response> + Clone + Send,
+    Request: 'static
+        + Klog
+        + Klog<Response = Response>
+        + ReadOnlyMode<Response>
+        + KeyspaceEvents<Response>
+        + Send,
     Response: 'static + Compose + Send,
     Storage: 'static + EntryStore + Execute<Request, Response> + Send,
 {
@@ -61,7 +186,10 @@ where
             Self::Single { mut worker } => {
                 vec![std::thread::Builder::new()
                     .name(format!("{}_work", THREAD_PREFIX))
-                    .spawn(move || worker.run())
+                    .spawn(move || {
+                        worker.run();
+                        worker.shutdown();
+                    })
                     .unwrap()]
             }
             Self::Multi {
@@ -70,7 +198,10 @@ where
             } => {
                 let mut join_handles = vec![std::thread::Builder::new()
                     .name(format!("{}_storage", THREAD_PREFIX))
-                    .spawn(move || storage.run())
+                    .spawn(move || {
+                        storage.run();
+                        storage.shutdown();
+                    })
                     .unwrap()];
 
                 for (id, mut worker) in workers.drain(..).enumerate() {
@@ -107,6 +238,9 @@ where
     pub fn new<T: WorkerConfig>(config: &T, parser: Parser, storage: Storage) -> Result<Self> {
         let threads = config.worker().threads();
 
+        let mut storage = storage;
+        storage.set_command_timeout(storage_command_timeout(config));
+
         if threads > 1 {
             let mut workers = vec![];
             for _ in 0..threads {
@@ -124,6 +258,15 @@ where
         }
     }
 
+    /// Sets the notifier which the keys changed by each request are reported
+    /// to, on whichever thread owns the storage.
+    pub fn keyspace_notifier(&mut self, notifier: Box<dyn KeyspaceNotifier>) {
+        match self {
+            Self::Single { worker } => worker.keyspace_notifier(notifier),
+            Self::Multi { storage, .. } => storage.keyspace_notifier(notifier),
+        }
+    }
+
     pub fn worker_wakers(&self) -> Vec<Arc<Waker>> {
         match self {
             Self::Single { worker } => {
@@ -155,6 +298,8 @@ where
         self,
         session_queues: Vec<Queues<Session, Session>>,
         signal_queues: Vec<Queues<(), Signal>>,
+        read_only: Arc<AtomicBool>,
+        session_count: Arc<AtomicUsize>,
     ) -> Workers<Parser, Request, Response, Storage> {
         let mut signal_queues = signal_queues;
         let mut session_queues = session_queues;
@@ -174,7 +319,11 @@ where
                 // only) element of `request_queues`. We remove these and build
                 // the storage so we can loop through the remaining signal
                 // queues when launching the worker threads.
-                let s = storage.build(storage_data_queues.remove(0), signal_queues.remove(0));
+                let s = storage.build(
+                    storage_data_queues.remove(0),
+                    signal_queues.remove(0),
+                    read_only,
+                );
 
                 let mut w = Vec::new();
                 for worker_builder in workers.drain(..) {
@@ -182,6 +331,7 @@ where
                         worker_data_queues.remove(0),
                         session_queues.remove(0),
                         signal_queues.remove(0),
+                        session_count.clone(),
                     ));
                 }
 
@@ -191,7 +341,12 @@ where
                 }
             }
             Self::Single { worker } => Workers::Single {
-                worker: worker.build(session_queues.remove(0), signal_queues.remove(0)),
+                worker: worker.build(
+                    session_queues.remove(0),
+                    signal_queues.remove(0),
+                    read_only,
+                    session_count,
+                ),
             },
         }
     }
@@ -5,9 +5,14 @@
 use super::*;
 
 pub struct MultiWorkerBuilder<Parser, Request, Response> {
+    batch_size: usize,
+    buffer_idle_timeout: Option<Duration>,
     nevent: usize,
+    output_watermarks: Option<(usize, usize)>,
     parser: Parser,
     poll: Poll,
+    request_read_timeout: Option<Duration>,
+    request_stall: Option<(usize, Duration)>,
     sessions: Slab<ServerSession<Parser, Response, Request>>,
     timeout: Duration,
     waker: Arc<Waker>,
@@ -15,8 +20,14 @@ pub struct MultiWorkerBuilder<Parser, Request, Response> {
 
 impl<Parser, Request, Response> MultiWorkerBuilder<Parser, Request, Response> {
     pub fn new<T: WorkerConfig>(config: &T, parser: Parser) -> Result<Self> {
+        let buffer_idle_timeout = buffer_idle_timeout(config);
+        let output_watermarks = output_watermarks(config);
+        let request_read_timeout = request_read_timeout(config);
+        let request_stall = request_stall(config);
         let config = config.worker();
 
+        let batch_size = config.storage_batch().max(1);
+
         let poll = Poll::new()?;
 
         let waker = Arc::new(Waker::from(
@@ -27,9 +38,14 @@ impl<Parser, Request, Response> MultiWorkerBuilder<Parser, Request, Response> {
         let timeout = Duration::from_millis(config.timeout() as u64);
 
         Ok(Self {
+            buffer_idle_timeout,
+            batch_size,
             nevent,
+            output_watermarks,
             parser,
             poll,
+            request_read_timeout,
+            request_stall,
             sessions: Slab::new(),
             timeout,
             waker,
@@ -42,15 +58,27 @@ impl<Parser, Request, Response> MultiWorkerBuilder<Parser, Request, Response> {
 
     pub fn build(
         self,
-        data_queue: Queues<(Request, Token), (Request, Response, Token)>,
+        data_queue: Queues<
+            Vec<(Request, Token, bool)>,
+            Vec<(Request, Response, Option<SessionAction>, Token)>,
+        >,
         session_queue: Queues<Session, Session>,
         signal_queue: Queues<(), Signal>,
+        session_count: Arc<AtomicUsize>,
     ) -> MultiWorker<Parser, Request, Response> {
         MultiWorker {
+            batch: Vec::with_capacity(self.batch_size),
+            batch_size: self.batch_size,
             data_queue,
+            buffer_idle_timeout: self.buffer_idle_timeout,
+            draining: false,
             nevent: self.nevent,
+            output_watermarks: self.output_watermarks,
             parser: self.parser,
             poll: self.poll,
+            request_read_timeout: self.request_read_timeout,
+            request_stall: self.request_stall,
+            session_count,
             session_queue,
             sessions: self.sessions,
             signal_queue,
@@ -61,10 +89,24 @@ impl<Parser, Request, Response> MultiWorkerBuilder<Parser, Request, Response> {
 }
 
 pub struct MultiWorker<Parser, Request, Response> {
-    data_queue: Queues<(Request, Token), (Request, Response, Token)>,
+    /// Requests which have been read but not yet sent to the storage thread
+    batch: Vec<(Request, Token, bool)>,
+    batch_size: usize,
+    data_queue:
+        Queues<Vec<(Request, Token, bool)>, Vec<(Request, Response, Option<SessionAction>, Token)>>,
+    buffer_idle_timeout: Option<Duration>,
+    /// Set once the process is draining gracefully, after which sessions
+    /// are closed as soon as they are idle
+    draining: bool,
     nevent: usize,
+    output_watermarks: Option<(usize, usize)>,
     parser: Parser,
     poll: Poll,
+    request_read_timeout: Option<Duration>,
+    request_stall: Option<(usize, Duration)>,
+    /// The number of open sessions across all workers, shared with the admin
+    /// thread
+    session_count: Arc<AtomicUsize>,
     session_queue: Queues<Session, Session>,
     sessions: Slab<ServerSession<Parser, Response, Request>>,
     signal_queue: Queues<(), Signal>,
@@ -74,7 +116,7 @@ pub struct MultiWorker<Parser, Request, Response> {
 
 impl<Parser, Request, Response> MultiWorker<Parser, Request, Response>
 where
-    Parser: Parse<Request> + Clone,
+    Parser: Parse<Request> + ParseErrorReply<Response> + Clone,
     Request: Klog + Klog<Response = Response>,
     Response: Compose,
 {
@@ -82,12 +124,128 @@ where
     fn close(&mut self, token: Token) {
         if self.sessions.contains(token.0) {
             let mut session = self.sessions.remove(token.0).into_inner();
+            self.session_count.fetch_sub(1, Ordering::Relaxed);
             let _ = session.deregister(self.poll.registry());
             let _ = self.session_queue.try_send_any(session);
             let _ = self.session_queue.wake();
         }
     }
 
+    /// Close any sessions which have been holding an incomplete request for
+    /// longer than the request read timeout.
+    fn close_stalled(&mut self) {
+        if let Some(timeout) = self.request_read_timeout {
+            let stalled: Vec<Token> = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.read_timed_out(timeout))
+                .map(|(key, _)| Token(key))
+                .collect();
+
+            for token in stalled {
+                WORKER_REQUEST_READ_TIMEOUT.increment();
+                self.close(token);
+            }
+        }
+    }
+
+    /// Close any sessions which have finished their last request, once the
+    /// process is draining gracefully.
+    fn close_idle(&mut self) {
+        if self.draining {
+            let idle: Vec<Token> = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.is_idle())
+                .map(|(key, _)| Token(key))
+                .collect();
+
+            for token in idle {
+                WORKER_DRAIN_CLOSE.increment();
+                self.close(token);
+            }
+        }
+    }
+
+    /// Release the buffers of any sessions which have been idle for longer
+    /// than the buffer idle timeout. Their sockets stay registered, and the
+    /// buffers are allocated again on the next read.
+    fn release_idle_buffers(&mut self) {
+        if let Some(idle) = self.buffer_idle_timeout {
+            for (_, session) in self.sessions.iter_mut() {
+                session.release_idle_buffers(idle);
+            }
+        }
+    }
+
+    /// Describe each session by its id and client address
+    fn clients(&self) -> Vec<(u64, Option<SocketAddr>)> {
+        self.sessions
+            .iter()
+            .map(|(_, session)| (session.id(), session.peer_addr().ok()))
+            .collect()
+    }
+
+    /// Close the sessions which match the filter, returning how many were
+    /// closed
+    fn kill(&mut self, filter: ClientFilter) -> usize {
+        let matched: Vec<Token> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| filter.matches(session.id(), session.peer_addr().ok()))
+            .map(|(key, _)| Token(key))
+            .collect();
+
+        for token in &matched {
+            WORKER_CLIENT_KILL.increment();
+            self.close(*token);
+        }
+
+        matched.len()
+    }
+
+    /// Mark the sessions which match the filter as no-evict, or clear the
+    /// mark, returning how many were updated
+    fn no_evict(&mut self, filter: ClientFilter, no_evict: bool) -> usize {
+        let mut updated = 0;
+        for (_, session) in self.sessions.iter_mut() {
+            if filter.matches(session.id(), session.peer_addr().ok()) {
+                session.set_no_evict(no_evict);
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// Send the batched requests to the storage thread as one message. If the
+    /// queue stays full, the requests are dropped and their sessions closed.
+    fn dispatch(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+
+        let mut batch = std::mem::replace(&mut self.batch, Vec::with_capacity(self.batch_size));
+        for attempt in 1..=QUEUE_RETRIES {
+            match self.data_queue.try_send_to(0, batch) {
+                Ok(()) => {
+                    WORKER_BATCH_SEND.increment();
+                    return;
+                }
+                Err(b) => {
+                    if attempt < QUEUE_RETRIES {
+                        let _ = self.data_queue.wake();
+                    }
+                    batch = b;
+                }
+            }
+        }
+
+        error!("data queue is full");
+        for (_, token, _) in batch {
+            self.close(token);
+        }
+    }
+
     /// Handle up to one request for a session
     fn read(&mut self, token: Token) -> Result<()> {
         let session = self
@@ -95,17 +253,39 @@ where
             .get_mut(token.0)
             .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?;
 
+        // leave requests from a client which isn't reading its responses in
+        // the socket until the write buffer drains
+        if session.output_backpressured() {
+            return Ok(());
+        }
+
         // fill the session
         map_result(session.fill())?;
 
-        // process up to one request
-        match session.receive() {
-            Ok(request) => self
-                .data_queue
-                .try_send_to(0, (request, token))
-                .map_err(|_| Error::new(ErrorKind::Other, "data queue is full")),
-            Err(e) => map_err(e),
+        // process up to one request, which is sent to storage along with any
+        // others read before the batch fills or the event loop comes around
+        let request = match session.receive() {
+            Ok(request) => request,
+            Err(e) => {
+                // let the client know why it is being disconnected. the reply
+                // is flushed by the listener when the session is closed
+                if e.kind() != ErrorKind::WouldBlock {
+                    let buffer: &[u8] = (*session).borrow();
+                    if let Some(reply) = self.parser.parse_error_reply(buffer, &e) {
+                        let _ = session.send(reply);
+                    }
+                }
+                return map_err(e);
+            }
+        };
+
+        let no_evict = session.no_evict();
+        self.batch.push((request, token, no_evict));
+        if self.batch.len() >= self.batch_size {
+            self.dispatch();
         }
+
+        Ok(())
     }
 
     /// Handle write by flushing the session
@@ -115,10 +295,24 @@ where
             .get_mut(token.0)
             .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?;
 
+        let backpressured = session.output_backpressured();
+
         match session.flush() {
             Ok(_) => Ok(()),
             Err(e) => map_err(e),
+        }?;
+
+        if session.should_close() {
+            return Err(Error::new(ErrorKind::Other, "closed after response"));
         }
+
+        // requests held back by output backpressure won't generate another
+        // read event, so pick them up once the write buffer has drained
+        if backpressured && !session.output_backpressured() {
+            self.read(token)?;
+        }
+
+        Ok(())
     }
 
     /// Run the worker in a loop, handling new events.
@@ -127,6 +321,7 @@ where
         // events and queue messages
         let mut events = Events::with_capacity(self.nevent);
         let mut messages = Vec::with_capacity(QUEUE_CAPACITY);
+        let mut last_sweep = Instant::now();
 
         loop {
             WORKER_EVENT_LOOP.increment();
@@ -162,7 +357,15 @@ where
                                 .register(self.poll.registry(), Token(s.key()), interest)
                                 .is_ok()
                             {
-                                s.insert(ServerSession::new(session, self.parser.clone()));
+                                let mut session = ServerSession::new(session, self.parser.clone());
+                                if let Some((high, low)) = self.output_watermarks {
+                                    session.set_output_watermarks(high, low);
+                                }
+                                if let Some((size, timeout)) = self.request_stall {
+                                    session.set_stall_limit(size, timeout);
+                                }
+                                s.insert(session);
+                                self.session_count.fetch_add(1, Ordering::Relaxed);
                             } else {
                                 let _ = self.session_queue.try_send_any(session);
                             }
@@ -173,18 +376,36 @@ where
 
                         // handle all pending messages on the data queue
                         self.data_queue.try_recv_all(&mut messages);
-                        for (request, response, token) in messages.drain(..).map(|v| v.into_inner())
+                        for (request, response, action, token) in
+                            messages.drain(..).flat_map(|v| v.into_inner())
                         {
+                            // the storage thread could not deliver earlier
+                            // responses to the session, so it is closed
+                            // without this one
+                            if action == Some(SessionAction::Close) {
+                                self.close(token);
+                                continue;
+                            }
+
+                            if logger::current_klog_format() == KlogFormat::Common {
+                                let session = self.sessions.get(token.0);
+                                logger::set_klog_context(
+                                    session.and_then(|s| s.peer_addr().ok()),
+                                    session.and_then(|s| s.request_latency()),
+                                );
+                            }
                             request.klog(&response);
                             if let Some(session) = self.sessions.get_mut(token.0) {
-                                if response.should_hangup() {
-                                    let _ = session.send(response);
-                                    self.close(token);
-                                    continue;
-                                } else if session.send(response).is_err() {
+                                if session.send(response).is_err() {
                                     self.close(token);
                                     continue;
-                                } else if session.write_pending() > 0 {
+                                }
+
+                                if let Some(action) = action {
+                                    session.apply(action);
+                                }
+
+                                if session.write_pending() > 0 {
                                     // try to immediately flush, if we still
                                     // have pending bytes, reregister. This
                                     // saves us one syscall when flushing would
@@ -208,6 +429,13 @@ where
                                     }
                                 }
 
+                                // the session is closed once the response it
+                                // asked to be closed after has been flushed
+                                if session.should_close() {
+                                    self.close(token);
+                                    continue;
+                                }
+
                                 if session.remaining() > 0 && self.read(token).is_err() {
                                     self.close(token);
                                     continue;
@@ -220,7 +448,26 @@ where
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::FlushAll
+                                | Signal::DumpKey(..)
+                                | Signal::Evict(..)
+                                | Signal::Verify(..)
+                                | Signal::ActiveExpire(..)
+                                | Signal::DebugObject(..)
+                                | Signal::Reload(..)
+                                | Signal::Drain => {}
+                                Signal::KillClient(filter, reply) => {
+                                    let _ = reply.send(self.kill(filter));
+                                }
+                                Signal::NoEvictClient(filter, no_evict, reply) => {
+                                    let _ = reply.send(self.no_evict(filter, no_evict));
+                                }
+                                Signal::ListClients(reply) => {
+                                    let _ = reply.send(self.clients());
+                                }
+                                Signal::GracefulDrain(_) => {
+                                    self.draining = true;
+                                }
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -258,7 +505,19 @@ where
                 }
             }
 
-            // wakes the storage thread if necessary
+            // periodically check for sessions which stalled mid-request, for
+            // idle sessions which can give up their buffers, and for idle
+            // sessions to close while draining
+            if (timestamp - last_sweep).as_nanos() >= self.timeout.as_nanos() as u64 {
+                last_sweep = timestamp;
+                self.close_stalled();
+                self.close_idle();
+                self.release_idle_buffers();
+            }
+
+            // sends any requests which didn't fill a batch and wakes the
+            // storage thread if necessary
+            self.dispatch();
             let _ = self.data_queue.wake();
         }
     }
@@ -6,18 +6,27 @@ use super::*;
 use std::collections::VecDeque;
 
 pub struct SingleWorkerBuilder<Parser, Request, Response, Storage> {
+    buffer_idle_timeout: Option<Duration>,
     nevent: usize,
+    output_watermarks: Option<(usize, usize)>,
     parser: Parser,
     pending: VecDeque<Token>,
     poll: Poll,
+    request_read_timeout: Option<Duration>,
+    request_stall: Option<(usize, Duration)>,
     sessions: Slab<ServerSession<Parser, Response, Request>>,
     storage: Storage,
+    notifier: Option<Box<dyn KeyspaceNotifier>>,
     timeout: Duration,
     waker: Arc<Waker>,
 }
 
 impl<Parser, Request, Response, Storage> SingleWorkerBuilder<Parser, Request, Response, Storage> {
     pub fn new<T: WorkerConfig>(config: &T, parser: Parser, storage: Storage) -> Result<Self> {
+        let buffer_idle_timeout = buffer_idle_timeout(config);
+        let output_watermarks = output_watermarks(config);
+        let request_read_timeout = request_read_timeout(config);
+        let request_stall = request_stall(config);
         let config = config.worker();
 
         let poll = Poll::new()?;
@@ -30,12 +39,17 @@ impl<Parser, Request, Response, Storage> SingleWorkerBuilder<Parser, Request, Re
         let timeout = Duration::from_millis(config.timeout() as u64);
 
         Ok(Self {
+            buffer_idle_timeout,
             nevent,
+            output_watermarks,
             parser,
             pending: VecDeque::new(),
             poll,
+            request_read_timeout,
+            request_stall,
             sessions: Slab::new(),
             storage,
+            notifier: None,
             timeout,
             waker,
         })
@@ -45,20 +59,34 @@ impl<Parser, Request, Response, Storage> SingleWorkerBuilder<Parser, Request, Re
         self.waker.clone()
     }
 
+    pub fn keyspace_notifier(&mut self, notifier: Box<dyn KeyspaceNotifier>) {
+        self.notifier = Some(notifier);
+    }
+
     pub fn build(
         self,
         session_queue: Queues<Session, Session>,
         signal_queue: Queues<(), Signal>,
+        read_only: Arc<AtomicBool>,
+        session_count: Arc<AtomicUsize>,
     ) -> SingleWorker<Parser, Request, Response, Storage> {
         SingleWorker {
+            buffer_idle_timeout: self.buffer_idle_timeout,
+            draining: false,
             nevent: self.nevent,
+            output_watermarks: self.output_watermarks,
             parser: self.parser,
             pending: self.pending,
             poll: self.poll,
+            read_only,
+            request_read_timeout: self.request_read_timeout,
+            request_stall: self.request_stall,
+            session_count,
             session_queue,
             sessions: self.sessions,
             signal_queue,
             storage: self.storage,
+            notifier: self.notifier,
             timeout: self.timeout,
             waker: self.waker,
         }
@@ -66,22 +94,34 @@ impl<Parser, Request, Response, Storage> SingleWorkerBuilder<Parser, Request, Re
 }
 
 pub struct SingleWorker<Parser, Request, Response, Storage> {
+    buffer_idle_timeout: Option<Duration>,
+    /// Set once the process is draining gracefully, after which sessions
+    /// are closed as soon as they are idle
+    draining: bool,
     nevent: usize,
+    output_watermarks: Option<(usize, usize)>,
     parser: Parser,
     pending: VecDeque<Token>,
     poll: Poll,
+    read_only: Arc<AtomicBool>,
+    request_read_timeout: Option<Duration>,
+    request_stall: Option<(usize, Duration)>,
+    /// The number of open sessions across all workers, shared with the admin
+    /// thread
+    session_count: Arc<AtomicUsize>,
     session_queue: Queues<Session, Session>,
     sessions: Slab<ServerSession<Parser, Response, Request>>,
     signal_queue: Queues<(), Signal>,
     storage: Storage,
+    notifier: Option<Box<dyn KeyspaceNotifier>>,
     timeout: Duration,
     waker: Arc<Waker>,
 }
 
 impl<Parser, Request, Response, Storage> SingleWorker<Parser, Request, Response, Storage>
 where
-    Parser: Parse<Request> + Clone,
-    Request: Klog + Klog<Response = Response>,
+    Parser: Parse<Request> + ParseErrorReply<Response> + Clone,
+    Request: Klog + Klog<Response = Response> + ReadOnlyMode<Response> + KeyspaceEvents<Response>,
     Response: Compose,
     Storage: EntryStore + Execute<Request, Response>,
 {
@@ -89,12 +129,99 @@ where
     fn close(&mut self, token: Token) {
         if self.sessions.contains(token.0) {
             let mut session = self.sessions.remove(token.0).into_inner();
+            self.session_count.fetch_sub(1, Ordering::Relaxed);
             let _ = self.poll.registry().deregister(&mut session);
             let _ = self.session_queue.try_send_any(session);
             let _ = self.session_queue.wake();
         }
     }
 
+    /// Close any sessions which have been holding an incomplete request for
+    /// longer than the request read timeout.
+    fn close_stalled(&mut self) {
+        if let Some(timeout) = self.request_read_timeout {
+            let stalled: Vec<Token> = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.read_timed_out(timeout))
+                .map(|(key, _)| Token(key))
+                .collect();
+
+            for token in stalled {
+                WORKER_REQUEST_READ_TIMEOUT.increment();
+                self.close(token);
+            }
+        }
+    }
+
+    /// Close any sessions which have finished their last request, once the
+    /// process is draining gracefully.
+    fn close_idle(&mut self) {
+        if self.draining {
+            let idle: Vec<Token> = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.is_idle())
+                .map(|(key, _)| Token(key))
+                .collect();
+
+            for token in idle {
+                WORKER_DRAIN_CLOSE.increment();
+                self.close(token);
+            }
+        }
+    }
+
+    /// Release the buffers of any sessions which have been idle for longer
+    /// than the buffer idle timeout. Their sockets stay registered, and the
+    /// buffers are allocated again on the next read.
+    fn release_idle_buffers(&mut self) {
+        if let Some(idle) = self.buffer_idle_timeout {
+            for (_, session) in self.sessions.iter_mut() {
+                session.release_idle_buffers(idle);
+            }
+        }
+    }
+
+    /// Describe each session by its id and client address
+    fn clients(&self) -> Vec<(u64, Option<SocketAddr>)> {
+        self.sessions
+            .iter()
+            .map(|(_, session)| (session.id(), session.peer_addr().ok()))
+            .collect()
+    }
+
+    /// Close the sessions which match the filter, returning how many were
+    /// closed
+    fn kill(&mut self, filter: ClientFilter) -> usize {
+        let matched: Vec<Token> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| filter.matches(session.id(), session.peer_addr().ok()))
+            .map(|(key, _)| Token(key))
+            .collect();
+
+        for token in &matched {
+            WORKER_CLIENT_KILL.increment();
+            self.close(*token);
+        }
+
+        matched.len()
+    }
+
+    /// Mark the sessions which match the filter as no-evict, or clear the
+    /// mark, returning how many were updated
+    fn no_evict(&mut self, filter: ClientFilter, no_evict: bool) -> usize {
+        let mut updated = 0;
+        for (_, session) in self.sessions.iter_mut() {
+            if filter.matches(session.id(), session.peer_addr().ok()) {
+                session.set_no_evict(no_evict);
+                updated += 1;
+            }
+        }
+        updated
+    }
+
     /// Handle up to one request for a session
     fn read(&mut self, token: Token) -> Result<()> {
         let session = self
@@ -102,21 +229,36 @@ where
             .get_mut(token.0)
             .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?;
 
+        // leave requests from a client which isn't reading its responses in
+        // the socket until the write buffer drains
+        if session.output_backpressured() {
+            return Ok(());
+        }
+
         // fill the session
         map_result(session.fill())?;
 
         // process up to one pending request
         match session.receive() {
             Ok(request) => {
-                let response = self.storage.execute(&request);
+                let (response, action) = execute(
+                    &mut self.storage,
+                    &self.read_only,
+                    &request,
+                    session.no_evict(),
+                    &mut self.notifier,
+                );
                 PROCESS_REQ.increment();
-                if response.should_hangup() {
-                    let _ = session.send(response);
-                    return Err(Error::new(ErrorKind::Other, "should hangup"));
+                if logger::current_klog_format() == KlogFormat::Common {
+                    logger::set_klog_context(session.peer_addr().ok(), session.request_latency());
                 }
                 request.klog(&response);
                 match session.send(response) {
                     Ok(_) => {
+                        if let Some(action) = action {
+                            session.apply(action);
+                        }
+
                         // attempt to flush immediately if there's now data in
                         // the write buffer
                         if session.write_pending() > 0 {
@@ -126,6 +268,12 @@ where
                             }?;
                         }
 
+                        // the session is closed once the response it asked
+                        // to be closed after has been flushed
+                        if session.should_close() {
+                            return Err(Error::new(ErrorKind::Other, "closed after response"));
+                        }
+
                         // reregister to get writable event
                         if session.write_pending() > 0 {
                             let interest = session.interest();
@@ -160,6 +308,13 @@ where
                 if e.kind() == ErrorKind::WouldBlock {
                     Ok(())
                 } else {
+                    // let the client know why it is being disconnected. the
+                    // reply is flushed by the listener when the session is
+                    // closed
+                    let buffer: &[u8] = (*session).borrow();
+                    if let Some(reply) = self.parser.parse_error_reply(buffer, &e) {
+                        let _ = session.send(reply);
+                    }
                     Err(e)
                 }
             }
@@ -172,20 +327,50 @@ where
             .get_mut(token.0)
             .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?;
 
+        let backpressured = session.output_backpressured();
+
         match session.flush() {
             Ok(_) => Ok(()),
             Err(e) => map_err(e),
+        }?;
+
+        if session.should_close() {
+            return Err(Error::new(ErrorKind::Other, "closed after response"));
+        }
+
+        // requests held back by output backpressure won't generate another
+        // read event, so pick them up once the write buffer has drained
+        if backpressured && !session.output_backpressured() {
+            self.pending.push_back(token);
+        }
+
+        Ok(())
+    }
+
+    /// Persists the storage once the event loop has stopped, blocking for as
+    /// long as the storage allows.
+    pub fn shutdown(self) {
+        if let Err(e) = self.storage.shutdown() {
+            error!("failed to persist storage on shutdown: {}", e);
         }
     }
 
     /// Run the worker in a loop, handling new events.
     pub fn run(&mut self) {
+        // the storage may be allocated from a NUMA node, in which case this
+        // thread should run on that node too
+        if let Some(node) = self.storage.numa_node() {
+            pin_to_numa_node(node);
+        }
+
         let mut events = Events::with_capacity(self.nevent);
+        let mut last_sweep = Instant::now();
 
         loop {
             WORKER_EVENT_LOOP.increment();
 
             self.storage.expire();
+            self.storage.flush();
 
             // we need another wakeup if there are still pending reads
             if !self.pending.is_empty() {
@@ -233,7 +418,15 @@ where
                                 .register(self.poll.registry(), Token(s.key()), interest)
                                 .is_ok()
                             {
-                                s.insert(ServerSession::new(session, self.parser.clone()));
+                                let mut session = ServerSession::new(session, self.parser.clone());
+                                if let Some((high, low)) = self.output_watermarks {
+                                    session.set_output_watermarks(high, low);
+                                }
+                                if let Some((size, timeout)) = self.request_stall {
+                                    session.set_stall_limit(size, timeout);
+                                }
+                                s.insert(session);
+                                self.session_count.fetch_add(1, Ordering::Relaxed);
                             } else {
                                 let _ = self.session_queue.try_send_any(session);
                             }
@@ -248,6 +441,37 @@ where
                                 Signal::FlushAll => {
                                     self.storage.clear();
                                 }
+                                Signal::DumpKey(key, reply) => {
+                                    let _ = reply.try_send(self.storage.dump(&key));
+                                }
+                                Signal::Evict(segments, reply) => {
+                                    let _ = reply.try_send(self.storage.evict(segments));
+                                }
+                                Signal::Verify(reply) => {
+                                    let _ = reply.try_send(self.storage.verify());
+                                }
+                                Signal::ActiveExpire(enabled, reply) => {
+                                    let _ = reply.try_send(self.storage.set_active_expire(enabled));
+                                }
+                                Signal::DebugObject(key, reply) => {
+                                    let _ = reply.try_send(self.storage.debug_object(&key));
+                                }
+                                Signal::Reload(reply) => {
+                                    let _ = reply.try_send(self.storage.reload());
+                                }
+                                Signal::Drain => {}
+                                Signal::KillClient(filter, reply) => {
+                                    let _ = reply.send(self.kill(filter));
+                                }
+                                Signal::NoEvictClient(filter, no_evict, reply) => {
+                                    let _ = reply.send(self.no_evict(filter, no_evict));
+                                }
+                                Signal::ListClients(reply) => {
+                                    let _ = reply.send(self.clients());
+                                }
+                                Signal::GracefulDrain(_) => {
+                                    self.draining = true;
+                                }
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -284,6 +508,16 @@ where
                     }
                 }
             }
+
+            // periodically check for sessions which stalled mid-request, for
+            // idle sessions which can give up their buffers, and for idle
+            // sessions to close while draining
+            if (timestamp - last_sweep).as_nanos() >= self.timeout.as_nanos() as u64 {
+                last_sweep = timestamp;
+                self.close_stalled();
+                self.close_idle();
+                self.release_idle_buffers();
+            }
         }
     }
 }
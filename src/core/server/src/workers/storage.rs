@@ -2,7 +2,16 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+use super::pin_to_numa_node;
 use crate::*;
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+
+// The most response batches which are held for a worker whose queue is full.
+// Past this the worker is taken to be stuck, and the sessions which the held
+// responses are for are closed instead, so the held responses don't grow
+// without bound.
+const MAX_UNDELIVERED: usize = 1024;
 
 counter!(
     STORAGE_EVENT_LOOP,
@@ -13,11 +22,25 @@ heatmap!(
     1_000_000,
     "the distribution of the depth of the storage queue on each loop"
 );
+counter!(
+    STORAGE_RESPONSE_HELD,
+    "the number of response batches held because a worker's queue was full"
+);
+counter!(
+    STORAGE_RESPONSE_DROPPED,
+    "the number of held response batches dropped because a worker's queue stayed full"
+);
+heatmap!(
+    STORAGE_BATCH_SIZE,
+    1_000_000,
+    "the distribution of the number of requests in each batch from a worker"
+);
 
 pub struct StorageWorkerBuilder<Request, Response, Storage> {
     nevent: usize,
     poll: Poll,
     storage: Storage,
+    notifier: Option<Box<dyn KeyspaceNotifier>>,
     timeout: Duration,
     waker: Arc<Waker>,
     _request: PhantomData<Request>,
@@ -41,6 +64,7 @@ impl<Request, Response, Storage> StorageWorkerBuilder<Request, Response, Storage
             nevent,
             poll,
             storage,
+            notifier: None,
             timeout,
             waker,
             _request: PhantomData,
@@ -52,18 +76,29 @@ impl<Request, Response, Storage> StorageWorkerBuilder<Request, Response, Storage
         self.waker.clone()
     }
 
+    pub fn keyspace_notifier(&mut self, notifier: Box<dyn KeyspaceNotifier>) {
+        self.notifier = Some(notifier);
+    }
+
     pub fn build(
         self,
-        data_queue: Queues<(Request, Response, Token), (Request, Token)>,
+        data_queue: Queues<
+            Vec<(Request, Response, Option<SessionAction>, Token)>,
+            Vec<(Request, Token, bool)>,
+        >,
         signal_queue: Queues<(), Signal>,
+        read_only: Arc<AtomicBool>,
     ) -> StorageWorker<Request, Response, Storage, Token> {
         StorageWorker {
             data_queue,
             nevent: self.nevent,
             poll: self.poll,
+            read_only,
             signal_queue,
             storage: self.storage,
+            notifier: self.notifier,
             timeout: self.timeout,
+            undelivered: VecDeque::new(),
             waker: self.waker,
             _request: PhantomData,
             _response: PhantomData,
@@ -71,13 +106,31 @@ impl<Request, Response, Storage> StorageWorkerBuilder<Request, Response, Storage
     }
 }
 
+/// Executes the requests of all the workers against the storage.
+///
+/// Each worker sends its requests in the order it read them, and they are
+/// executed in that order, with the responses sent back to the worker in the
+/// same order. As a session belongs to a single worker, its requests are
+/// executed in the order the client sent them, even when pipelined: a read
+/// always observes the writes which the same session made before it. Requests
+/// from different sessions have no ordering relative to each other.
 pub struct StorageWorker<Request, Response, Storage, Token> {
-    data_queue: Queues<(Request, Response, Token), (Request, Token)>,
+    data_queue:
+        Queues<Vec<(Request, Response, Option<SessionAction>, Token)>, Vec<(Request, Token, bool)>>,
     nevent: usize,
     poll: Poll,
+    read_only: Arc<AtomicBool>,
     signal_queue: Queues<(), Signal>,
     storage: Storage,
+    notifier: Option<Box<dyn KeyspaceNotifier>>,
     timeout: Duration,
+    /// Responses which couldn't be sent back to a worker yet, by worker, in
+    /// the order they must be sent. At most `MAX_UNDELIVERED` batches are held
+    /// for each worker
+    undelivered: VecDeque<(
+        usize,
+        Vec<(Request, Response, Option<SessionAction>, Token)>,
+    )>,
     #[allow(dead_code)]
     waker: Arc<Waker>,
     _request: PhantomData<Request>,
@@ -87,11 +140,104 @@ pub struct StorageWorker<Request, Response, Storage, Token> {
 impl<Request, Response, Storage, Token> StorageWorker<Request, Response, Storage, Token>
 where
     Storage: Execute<Request, Response> + EntryStore,
-    Request: Klog + Klog<Response = Response>,
+    Token: Copy + Eq + Hash,
+    Request: Klog + Klog<Response = Response> + ReadOnlyMode<Response> + KeyspaceEvents<Response>,
     Response: Compose,
 {
+    /// Send the responses to a batch back to the worker which sent it. If the
+    /// worker's queue stays full, the responses are held and sent later.
+    /// Dropping them instead would leave the worker's sessions matching later
+    /// responses to earlier requests.
+    fn send(
+        &mut self,
+        sender: usize,
+        mut message: Vec<(Request, Response, Option<SessionAction>, Token)>,
+    ) {
+        // responses held for the worker must be sent before any newer ones
+        if self.undelivered.iter().all(|(s, _)| *s != sender) {
+            for _ in 0..QUEUE_RETRIES {
+                match self.data_queue.try_send_to(sender, message) {
+                    Ok(()) => {
+                        return;
+                    }
+                    Err(m) => {
+                        // wake workers immediately
+                        let _ = self.data_queue.wake();
+                        message = m;
+                    }
+                }
+            }
+        }
+
+        STORAGE_RESPONSE_HELD.increment();
+        self.undelivered.push_back((sender, message));
+
+        if self
+            .undelivered
+            .iter()
+            .filter(|(s, _)| *s == sender)
+            .count()
+            > MAX_UNDELIVERED
+        {
+            self.drop_undelivered(sender);
+        }
+    }
+
+    /// Drops the responses held for a worker which has stopped taking them,
+    /// and holds one batch in their place which closes each session they were
+    /// for. The batch has a single entry for each session, so it is bounded by
+    /// the number of sessions the worker has.
+    fn drop_undelivered(&mut self, sender: usize) {
+        let mut closing = HashSet::new();
+        let mut close = Vec::new();
+        for (s, message) in std::mem::take(&mut self.undelivered) {
+            if s != sender {
+                self.undelivered.push_back((s, message));
+                continue;
+            }
+
+            STORAGE_RESPONSE_DROPPED.increment();
+            for (request, response, _, token) in message {
+                if closing.insert(token) {
+                    close.push((request, response, Some(SessionAction::Close), token));
+                }
+            }
+        }
+        self.undelivered.push_back((sender, close));
+    }
+
+    /// Retry sending the held responses, keeping the order for each worker.
+    fn send_undelivered(&mut self) {
+        let mut blocked = Vec::new();
+        for (sender, message) in std::mem::take(&mut self.undelivered) {
+            if blocked.contains(&sender) {
+                self.undelivered.push_back((sender, message));
+                continue;
+            }
+
+            if let Err(message) = self.data_queue.try_send_to(sender, message) {
+                blocked.push(sender);
+                self.undelivered.push_back((sender, message));
+            }
+        }
+    }
+
+    /// Persists the storage once the event loop has stopped, blocking for as
+    /// long as the storage allows.
+    pub fn shutdown(self) {
+        if let Err(e) = self.storage.shutdown() {
+            error!("failed to persist storage on shutdown: {}", e);
+        }
+    }
+
     /// Run the `StorageWorker` in a loop, handling new session events.
     pub fn run(&mut self) {
+        // the storage may be allocated from a NUMA node, in which case this
+        // thread should run on that node too
+        if let Some(node) = self.storage.numa_node() {
+            pin_to_numa_node(node);
+        }
+
         let mut events = Events::with_capacity(self.nevent);
         let mut messages = Vec::with_capacity(1024);
 
@@ -99,6 +245,12 @@ where
             STORAGE_EVENT_LOOP.increment();
 
             self.storage.expire();
+            self.storage.flush();
+
+            if !self.undelivered.is_empty() {
+                self.send_undelivered();
+                let _ = self.data_queue.wake();
+            }
 
             // get events with timeout
             if self.poll.poll(&mut events, Some(self.timeout)).is_err() {
@@ -118,23 +270,27 @@ where
 
                 for message in messages.drain(..) {
                     let sender = message.sender();
-                    let (request, token) = message.into_inner();
-                    trace!("handling request from worker: {}", sender);
-                    let response = self.storage.execute(&request);
-                    PROCESS_REQ.increment();
-                    let mut message = (request, response, token);
-                    for retry in 0..QUEUE_RETRIES {
-                        if let Err(m) = self.data_queue.try_send_to(sender, message) {
-                            if (retry + 1) == QUEUE_RETRIES {
-                                error!("error sending message to worker");
-                            }
-                            // wake workers immediately
-                            let _ = self.data_queue.wake();
-                            message = m;
-                        } else {
-                            break;
-                        }
-                    }
+                    let batch = message.into_inner();
+                    trace!("handling {} requests from worker: {}", batch.len(), sender);
+                    STORAGE_BATCH_SIZE.increment(timestamp, batch.len() as _, 1);
+
+                    // the responses are sent back in the same order as the
+                    // requests, each one executed on its own
+                    let message: Vec<(Request, Response, Option<SessionAction>, Token)> = batch
+                        .into_iter()
+                        .map(|(request, token, no_evict)| {
+                            let (response, action) = execute(
+                                &mut self.storage,
+                                &self.read_only,
+                                &request,
+                                no_evict,
+                                &mut self.notifier,
+                            );
+                            PROCESS_REQ.increment();
+                            (request, response, action, token)
+                        })
+                        .collect();
+                    self.send(sender, message);
                 }
 
                 let _ = self.data_queue.wake();
@@ -146,13 +302,33 @@ where
                             warn!("received flush_all");
                             self.storage.clear();
                         }
+                        Signal::DumpKey(key, reply) => {
+                            let _ = reply.try_send(self.storage.dump(&key));
+                        }
+                        Signal::Evict(segments, reply) => {
+                            let _ = reply.try_send(self.storage.evict(segments));
+                        }
+                        Signal::Verify(reply) => {
+                            let _ = reply.try_send(self.storage.verify());
+                        }
+                        Signal::ActiveExpire(enabled, reply) => {
+                            let _ = reply.try_send(self.storage.set_active_expire(enabled));
+                        }
+                        Signal::DebugObject(key, reply) => {
+                            let _ = reply.try_send(self.storage.debug_object(&key));
+                        }
+                        Signal::Reload(reply) => {
+                            let _ = reply.try_send(self.storage.reload());
+                        }
+                        Signal::Drain
+                        | Signal::GracefulDrain(_)
+                        | Signal::KillClient(..)
+                        | Signal::NoEvictClient(..)
+                        | Signal::ListClients(..) => {}
                         Signal::Shutdown => {
                             // if we received a shutdown, we can return and stop
-                            // processing events
-
-                            // TODO(bmartin): graceful shutdown would occur here
-                            // when we add persistence
-
+                            // processing events. the storage is persisted by
+                            // `shutdown` once this returns
                             return;
                         }
                     }
@@ -6,11 +6,22 @@
 //! typical storage module will implement one or more storage protocol traits in
 //! addition to the base `EntryStore` trait. For example [`Seg`] implements both
 //! [`EntryStore`] and [`protocol::memcache::MemcacheStorage`].
+//!
+//! Each storage backend, and each storage protocol which the backends
+//! implement, is behind a cargo feature of the same name, so that a server only
+//! builds the ones it uses. The `Noop` backend is always available.
 
 mod noop;
+// the helpers which the protocol implementations share are unused when no
+// protocol is enabled
+#[cfg(feature = "seg")]
+#[cfg_attr(not(feature = "memcache"), allow(dead_code))]
 mod seg;
 
+use std::time::Duration;
+
 pub use self::noop::*;
+#[cfg(feature = "seg")]
 pub use self::seg::*;
 
 /// A trait defining the basic requirements of a type which may be used for
@@ -22,6 +33,92 @@ pub trait EntryStore {
     /// expiration should implement their own handling logic for this function.
     fn expire(&mut self) {}
 
+    /// Persists the stored data, if the storage type is backed by a file and
+    /// it is time to do so. This is called on each turn of the event loop.
+    /// The default implementation is a no-op.
+    fn flush(&mut self) {}
+
+    /// Persists the stored data once the server has stopped serving requests,
+    /// consuming the storage. Implementations should bound how long this
+    /// blocks. The default implementation is a no-op.
+    fn shutdown(self) -> Result<(), std::io::Error>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+
     /// Remove all existing values from the entry store.
     fn clear(&mut self);
+
+    /// Limits the time spent executing a single command. Storage types which
+    /// have commands that may run for a long time should check the elapsed
+    /// time as they run and stop early, returning a partial result, once the
+    /// limit is exceeded. `None` removes the limit. The default implementation
+    /// ignores the limit.
+    fn set_command_timeout(&mut self, _timeout: Option<Duration>) {}
+
+    /// The NUMA node which the storage is allocated from, if it was bound to
+    /// one. The thread which owns the storage is pinned to the CPUs of this
+    /// node. The default implementation returns `None`.
+    fn numa_node(&self) -> Option<usize> {
+        None
+    }
+
+    /// Exempts the values written by the commands which follow from eviction,
+    /// up to a cap which is set by the storage configuration. Workers turn
+    /// this on around the requests from clients which are marked as exempt.
+    /// The default implementation ignores this.
+    fn set_no_evict(&mut self, _no_evict: bool) {}
+
+    /// Describes the stored value and metadata for a key, with the value as
+    /// hex so that the exact bytes can be inspected. This is intended for
+    /// debugging and is not used on the request path. Returns `None` if the key
+    /// is not stored, or if the storage type does not support this.
+    fn dump(&mut self, _key: &[u8]) -> Option<String> {
+        None
+    }
+
+    /// Immediately evicts up to the given number of segments, or the storage
+    /// type's equivalent unit, returning the number of items evicted. This is
+    /// intended for testing eviction policies and is not used on the request
+    /// path. Returns `None` if the storage type does not support this.
+    fn evict(&mut self, _segments: usize) -> Option<usize> {
+        None
+    }
+
+    /// Turns eager expiration on or off, so that `expire` does nothing while
+    /// it is off. Expired values are still missed when they are accessed. This
+    /// is intended for testing lazy expiration. Returns `false` if the storage
+    /// type does not support this.
+    fn set_active_expire(&mut self, _enabled: bool) -> bool {
+        false
+    }
+
+    /// Describes how the value for a key is stored, as a single line in the
+    /// format of the redis `DEBUG OBJECT` reply. This is intended for
+    /// debugging and is not used on the request path. Returns `None` if the key
+    /// is not stored, or if the storage type does not support this.
+    fn debug_object(&mut self, _key: &[u8]) -> Option<String> {
+        None
+    }
+
+    /// Checks the consistency of the storage, returning a description of the
+    /// first inconsistency found. This is intended for debugging corruption
+    /// and is not used on the request path. Returns `None` if the storage type
+    /// does not support this.
+    fn verify(&mut self) -> Option<Result<(), String>> {
+        None
+    }
+
+    /// Dumps the stored values to a snapshot and restores them into a fresh
+    /// instance of the storage, which replaces the current one. Returns the
+    /// number of values restored. This is intended for testing persistence
+    /// and is not used on the request path. Returns `None` if the storage type
+    /// does not support this.
+    fn reload(&mut self) -> Option<Result<usize, String>> {
+        None
+    }
 }
+
+common::metrics::test_no_duplicates!();
@@ -6,6 +6,7 @@
 
 use crate::EntryStore;
 
+#[cfg(feature = "ping")]
 mod ping;
 
 #[derive(Default)]
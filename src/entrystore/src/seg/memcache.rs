@@ -9,13 +9,116 @@ use super::*;
 use protocol_common::*;
 
 use protocol_memcache::*;
+use seg::SegError;
 
 use std::time::Duration;
 
+const TTL_ABOVE_MAX: &str = "ttl is above the maximum";
+const STORAGE_DEGRADED: &str = "storage is degraded, writes are rejected";
+const COMMAND_TIMED_OUT: &str = "command timed out";
+
+impl Seg {
+    /// Converts a requested TTL in seconds, where zero means no expiry, to
+    /// the TTL to store the item with. Returns `None` if the TTL is rejected
+    /// by the ceiling.
+    fn ttl(&self, ttl: u64) -> Option<Duration> {
+        self.max_ttl.apply(ttl).map(Duration::from_secs)
+    }
+
+    /// Stores an item, which is exempt from eviction if the client which
+    /// wrote it is.
+    fn insert<'a, T: Into<::seg::Value<'a>>>(
+        &mut self,
+        key: &'a [u8],
+        value: T,
+        optional: Option<&[u8]>,
+        ttl: Duration,
+    ) -> Result<(), SegError> {
+        if self.no_evict {
+            self.data.insert_no_evict(key, value, optional, ttl)
+        } else {
+            self.data.insert(key, value, optional, ttl)
+        }
+    }
+
+    /// The reply to a write which failed to store an item. This explains the
+    /// failure if writes are rejected because the storage is degraded.
+    fn store_failed(&self) -> Response {
+        if self.data.is_degraded() {
+            Response::server_error(STORAGE_DEGRADED)
+        } else {
+            Response::server_error("")
+        }
+    }
+
+    /// Looks up each of the keys and changes the TTL of the items which are
+    /// found. This is the reply to `gat`, or to `gats` if `cas` is set. A
+    /// negative TTL returns the values and then deletes the items.
+    fn get_and_touch(&mut self, keys: &[Box<[u8]>], ttl: Ttl, cas: bool) -> Response {
+        let ttl = ttl.get().unwrap_or(0);
+
+        let ttl = if ttl < 0 {
+            None
+        } else {
+            match self.ttl(ttl as u64) {
+                Some(ttl) => Some(ttl),
+                None => return Response::client_error(TTL_ABOVE_MAX),
+            }
+        };
+
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys.iter() {
+            // the item is rewritten by the touch, so it is looked up after
+            // to get the cas value it now has
+            if let Some(ttl) = ttl {
+                match self.data.touch(key, ttl) {
+                    Ok(()) => {}
+                    Err(SegError::NotFound) => {
+                        values.push(Value::none(key));
+                        continue;
+                    }
+                    Err(_) => return self.store_failed(),
+                }
+            }
+
+            let item = match self.data.get(key) {
+                Some(item) => item,
+                None => {
+                    values.push(Value::none(key));
+                    continue;
+                }
+            };
+            let flags = item.flags();
+            let cas = if cas { Some(item.cas().into()) } else { None };
+            match item.value() {
+                seg::Value::Bytes(b) => {
+                    values.push(Value::new(item.key(), flags, cas, b));
+                }
+                seg::Value::U64(v) => {
+                    values.push(Value::new(
+                        item.key(),
+                        flags,
+                        cas,
+                        format!("{}", v).as_bytes(),
+                    ));
+                }
+            }
+
+            if ttl.is_none() {
+                self.data.delete(key);
+            }
+        }
+        Values::new(values.into_boxed_slice()).into()
+    }
+}
+
 impl Execute<Request, Response> for Seg {
     fn execute(&mut self, request: &Request) -> Response {
         match request {
             Request::Get(get) => self.get(get),
+            Request::GetAndTouch(gat) => self.gat(gat),
+            Request::GetsAndTouch(gats) => self.gats(gats),
+            Request::GetDel(getdel) => self.getdel(getdel),
             Request::Gets(gets) => self.gets(gets),
             Request::Set(set) => self.set(set),
             Request::Add(add) => self.add(add),
@@ -26,19 +129,46 @@ impl Execute<Request, Response> for Seg {
             Request::Append(append) => self.append(append),
             Request::Prepend(prepend) => self.prepend(prepend),
             Request::Delete(delete) => self.delete(delete),
+            Request::Touch(touch) => self.touch(touch),
             Request::FlushAll(flush_all) => self.flush_all(flush_all),
             Request::Quit(quit) => self.quit(quit),
         }
     }
+
+    // quit has no reply of its own, and closes the connection once the
+    // responses to any earlier requests have been flushed
+    fn execute_with_action(&mut self, request: &Request) -> (Response, Option<SessionAction>) {
+        let response = self.execute(request);
+        let action = if matches!(request, Request::Quit(_)) || response.should_hangup() {
+            Some(SessionAction::CloseAfterResponse)
+        } else {
+            None
+        };
+        (response, action)
+    }
 }
 
 impl Storage for Seg {
     fn get(&mut self, get: &Get) -> Response {
+        let deadline = self.deadline();
         let mut values = Vec::with_capacity(get.keys().len());
-        for key in get.keys().iter() {
-            if let Some(item) = self.data.get(key) {
-                let o = item.optional().unwrap_or(&[0, 0, 0, 0]);
-                let flags = u32::from_be_bytes([o[0], o[1], o[2], o[3]]);
+        // the keys are looked up in batches, one for each check of the deadline
+        for (batch, keys) in get.keys().chunks(TIMEOUT_CHECK_INTERVAL).enumerate() {
+            // leaving out the keys which are not looked up would look the
+            // same as misses to the client, so the whole command fails
+            if timed_out(deadline, batch * TIMEOUT_CHECK_INTERVAL) {
+                return Response::server_error(COMMAND_TIMED_OUT);
+            }
+            let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+            for (index, item) in self.get_batch(&keys) {
+                let item = match item {
+                    Some(item) => item,
+                    None => {
+                        values.push(Value::none(keys[index]));
+                        continue;
+                    }
+                };
+                let flags = item.flags();
                 match item.value() {
                     seg::Value::Bytes(b) => {
                         values.push(Value::new(item.key(), flags, None, b));
@@ -52,19 +182,61 @@ impl Storage for Seg {
                         ));
                     }
                 }
-            } else {
-                values.push(Value::none(key));
             }
         }
         Values::new(values.into_boxed_slice()).into()
     }
 
+    fn getdel(&mut self, getdel: &GetDel) -> Response {
+        let value = match self.data.get(getdel.key()) {
+            Some(item) => {
+                let flags = item.flags();
+                match item.value() {
+                    seg::Value::Bytes(b) => Value::new(item.key(), flags, None, b),
+                    seg::Value::U64(v) => {
+                        Value::new(item.key(), flags, None, format!("{}", v).as_bytes())
+                    }
+                }
+            }
+            None => {
+                return Values::new(vec![Value::none(getdel.key())].into_boxed_slice()).into();
+            }
+        };
+
+        // storage is only accessed from a single thread, so no other request
+        // can read the item between the lookup and the delete
+        self.data.delete(getdel.key());
+        Values::new(vec![value].into_boxed_slice()).into()
+    }
+
+    fn gat(&mut self, gat: &GetAndTouch) -> Response {
+        self.get_and_touch(gat.keys(), gat.ttl(), false)
+    }
+
+    fn gats(&mut self, gats: &GetsAndTouch) -> Response {
+        self.get_and_touch(gats.keys(), gats.ttl(), true)
+    }
+
     fn gets(&mut self, get: &Gets) -> Response {
+        let deadline = self.deadline();
         let mut values = Vec::with_capacity(get.keys().len());
-        for key in get.keys().iter() {
-            if let Some(item) = self.data.get(key) {
-                let o = item.optional().unwrap_or(&[0, 0, 0, 0]);
-                let flags = u32::from_be_bytes([o[0], o[1], o[2], o[3]]);
+        // the keys are looked up in batches, one for each check of the deadline
+        for (batch, keys) in get.keys().chunks(TIMEOUT_CHECK_INTERVAL).enumerate() {
+            // leaving out the keys which are not looked up would look the
+            // same as misses to the client, so the whole command fails
+            if timed_out(deadline, batch * TIMEOUT_CHECK_INTERVAL) {
+                return Response::server_error(COMMAND_TIMED_OUT);
+            }
+            let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+            for (index, item) in self.get_batch(&keys) {
+                let item = match item {
+                    Some(item) => item,
+                    None => {
+                        values.push(Value::none(keys[index]));
+                        continue;
+                    }
+                };
+                let flags = item.flags();
                 match item.value() {
                     seg::Value::Bytes(b) => {
                         values.push(Value::new(item.key(), flags, Some(item.cas().into()), b));
@@ -78,8 +250,6 @@ impl Storage for Seg {
                         ));
                     }
                 }
-            } else {
-                values.push(Value::none(key));
             }
         }
         Values::new(values.into_boxed_slice()).into()
@@ -91,50 +261,49 @@ impl Storage for Seg {
         if ttl < 0 {
             // immediate expire maps to a delete
             self.data.delete(set.key());
-            Response::stored(set.noreply())
-        } else if let Ok(s) = std::str::from_utf8(set.value()) {
+            return Response::stored(set.noreply());
+        }
+
+        let ttl = match self.ttl(ttl as u64) {
+            Some(ttl) => ttl,
+            None => return Response::client_error(TTL_ABOVE_MAX),
+        };
+
+        if let Ok(s) = std::str::from_utf8(set.value()) {
             if let Ok(v) = s.parse::<u64>() {
                 if self
-                    .data
-                    .insert(
-                        set.key(),
-                        v,
-                        Some(&set.flags().to_be_bytes()),
-                        Duration::from_secs(ttl as u64),
-                    )
+                    .insert(set.key(), v, Some(&set.flags().to_be_bytes()), ttl)
                     .is_ok()
                 {
                     Response::stored(set.noreply())
                 } else {
-                    Response::server_error("")
+                    self.store_failed()
                 }
             } else if self
-                .data
                 .insert(
                     set.key(),
                     set.value(),
                     Some(&set.flags().to_be_bytes()),
-                    Duration::from_secs(ttl as u64),
+                    ttl,
                 )
                 .is_ok()
             {
                 Response::stored(set.noreply())
             } else {
-                Response::server_error("")
+                self.store_failed()
             }
         } else if self
-            .data
             .insert(
                 set.key(),
                 set.value(),
                 Some(&set.flags().to_be_bytes()),
-                Duration::from_secs(ttl as u64),
+                ttl,
             )
             .is_ok()
         {
             Response::stored(set.noreply())
         } else {
-            Response::server_error("")
+            self.store_failed()
         }
     }
 
@@ -148,50 +317,49 @@ impl Storage for Seg {
         if ttl < 0 {
             // immediate expire maps to a delete
             self.data.delete(add.key());
-            Response::stored(add.noreply())
-        } else if let Ok(s) = std::str::from_utf8(add.value()) {
+            return Response::stored(add.noreply());
+        }
+
+        let ttl = match self.ttl(ttl as u64) {
+            Some(ttl) => ttl,
+            None => return Response::client_error(TTL_ABOVE_MAX),
+        };
+
+        if let Ok(s) = std::str::from_utf8(add.value()) {
             if let Ok(v) = s.parse::<u64>() {
                 if self
-                    .data
-                    .insert(
-                        add.key(),
-                        v,
-                        Some(&add.flags().to_be_bytes()),
-                        Duration::from_secs(ttl as u64),
-                    )
+                    .insert(add.key(), v, Some(&add.flags().to_be_bytes()), ttl)
                     .is_ok()
                 {
                     Response::stored(add.noreply())
                 } else {
-                    Response::server_error("")
+                    self.store_failed()
                 }
             } else if self
-                .data
                 .insert(
                     add.key(),
                     add.value(),
                     Some(&add.flags().to_be_bytes()),
-                    Duration::from_secs(ttl as u64),
+                    ttl,
                 )
                 .is_ok()
             {
                 Response::stored(add.noreply())
             } else {
-                Response::server_error("")
+                self.store_failed()
             }
         } else if self
-            .data
             .insert(
                 add.key(),
                 add.value(),
                 Some(&add.flags().to_be_bytes()),
-                Duration::from_secs(ttl as u64),
+                ttl,
             )
             .is_ok()
         {
             Response::stored(add.noreply())
         } else {
-            Response::server_error("")
+            self.store_failed()
         }
     }
 
@@ -205,59 +373,68 @@ impl Storage for Seg {
         if ttl < 0 {
             // immediate expire maps to a delete
             self.data.delete(replace.key());
-            Response::stored(replace.noreply())
-        } else if let Ok(s) = std::str::from_utf8(replace.value()) {
+            return Response::stored(replace.noreply());
+        }
+
+        let ttl = match self.ttl(ttl as u64) {
+            Some(ttl) => ttl,
+            None => return Response::client_error(TTL_ABOVE_MAX),
+        };
+
+        if let Ok(s) = std::str::from_utf8(replace.value()) {
             if let Ok(v) = s.parse::<u64>() {
                 if self
-                    .data
-                    .insert(
-                        replace.key(),
-                        v,
-                        Some(&replace.flags().to_be_bytes()),
-                        Duration::from_secs(ttl as u64),
-                    )
+                    .insert(replace.key(), v, Some(&replace.flags().to_be_bytes()), ttl)
                     .is_ok()
                 {
                     Response::stored(replace.noreply())
                 } else {
-                    Response::server_error("")
+                    self.store_failed()
                 }
             } else if self
-                .data
                 .insert(
                     replace.key(),
                     replace.value(),
                     Some(&replace.flags().to_be_bytes()),
-                    Duration::from_secs(ttl as u64),
+                    ttl,
                 )
                 .is_ok()
             {
                 Response::stored(replace.noreply())
             } else {
-                Response::server_error("")
+                self.store_failed()
             }
         } else if self
-            .data
             .insert(
                 replace.key(),
                 replace.value(),
                 Some(&replace.flags().to_be_bytes()),
-                Duration::from_secs(ttl as u64),
+                ttl,
             )
             .is_ok()
         {
             Response::stored(replace.noreply())
         } else {
-            Response::server_error("")
+            self.store_failed()
         }
     }
 
-    fn append(&mut self, _: &Append) -> Response {
-        Response::error()
+    // the flags and ttl of the request are ignored, the item keeps its own
+    fn append(&mut self, append: &Append) -> Response {
+        match self.data.append(append.key(), append.value()) {
+            Ok(()) => Response::stored(append.noreply()),
+            Err(SegError::NotFound) => Response::not_stored(append.noreply()),
+            Err(_) => self.store_failed(),
+        }
     }
 
-    fn prepend(&mut self, _: &Prepend) -> Response {
-        Response::error()
+    // the flags and ttl of the request are ignored, the item keeps its own
+    fn prepend(&mut self, prepend: &Prepend) -> Response {
+        match self.data.prepend(prepend.key(), prepend.value()) {
+            Ok(()) => Response::stored(prepend.noreply()),
+            Err(SegError::NotFound) => Response::not_stored(prepend.noreply()),
+            Err(_) => self.store_failed(),
+        }
     }
 
     fn incr(&mut self, incr: &Incr) -> Response {
@@ -268,7 +445,7 @@ impl Storage for Seg {
             },
             Err(SegError::NotFound) => Response::not_found(incr.noreply()),
             Err(SegError::NotNumeric) => Response::error(),
-            Err(_) => Response::server_error(""),
+            Err(_) => self.store_failed(),
         }
     }
 
@@ -280,7 +457,7 @@ impl Storage for Seg {
             },
             Err(SegError::NotFound) => Response::not_found(decr.noreply()),
             Err(SegError::NotNumeric) => Response::error(),
-            Err(_) => Response::server_error(""),
+            Err(_) => self.store_failed(),
         }
     }
 
@@ -289,12 +466,11 @@ impl Storage for Seg {
         // no way of checking the cas value without performing a cas
         // and checking the result, setting the shortest possible ttl
         // results in nearly immediate expiry
-        let ttl = cas.ttl().get().unwrap_or(1);
+        let ttl = cas.ttl().get().unwrap_or(1).max(0);
 
-        let ttl = if ttl < 0 {
-            Duration::from_secs(0)
-        } else {
-            Duration::from_secs(ttl as u64)
+        let ttl = match self.ttl(ttl as u64) {
+            Some(ttl) => ttl,
+            None => return Response::client_error(TTL_ABOVE_MAX),
         };
 
         if let Ok(s) = std::str::from_utf8(cas.value()) {
@@ -309,6 +485,7 @@ impl Storage for Seg {
                     Ok(_) => Response::stored(cas.noreply()),
                     Err(SegError::NotFound) => Response::not_found(cas.noreply()),
                     Err(SegError::Exists) => Response::exists(cas.noreply()),
+                    Err(SegError::Degraded) => Response::server_error(STORAGE_DEGRADED),
                     Err(_) => Response::error(),
                 }
             } else {
@@ -322,6 +499,7 @@ impl Storage for Seg {
                     Ok(_) => Response::stored(cas.noreply()),
                     Err(SegError::NotFound) => Response::not_found(cas.noreply()),
                     Err(SegError::Exists) => Response::exists(cas.noreply()),
+                    Err(SegError::Degraded) => Response::server_error(STORAGE_DEGRADED),
                     Err(_) => Response::error(),
                 }
             }
@@ -336,6 +514,7 @@ impl Storage for Seg {
                 Ok(_) => Response::stored(cas.noreply()),
                 Err(SegError::NotFound) => Response::not_found(cas.noreply()),
                 Err(SegError::Exists) => Response::exists(cas.noreply()),
+                Err(SegError::Degraded) => Response::server_error(STORAGE_DEGRADED),
                 Err(_) => Response::error(),
             }
         }
@@ -349,6 +528,30 @@ impl Storage for Seg {
         }
     }
 
+    fn touch(&mut self, touch: &Touch) -> Response {
+        let ttl = touch.ttl().get().unwrap_or(0);
+
+        if ttl < 0 {
+            // immediate expire maps to a delete
+            if self.data.delete(touch.key()) {
+                return Response::touched(touch.noreply());
+            } else {
+                return Response::not_found(touch.noreply());
+            }
+        }
+
+        let ttl = match self.ttl(ttl as u64) {
+            Some(ttl) => ttl,
+            None => return Response::client_error(TTL_ABOVE_MAX),
+        };
+
+        match self.data.touch(touch.key(), ttl) {
+            Ok(()) => Response::touched(touch.noreply()),
+            Err(SegError::NotFound) => Response::not_found(touch.noreply()),
+            Err(_) => self.store_failed(),
+        }
+    }
+
     fn flush_all(&mut self, _flush_all: &FlushAll) -> Response {
         Response::error()
     }
@@ -0,0 +1,10 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use rustcommon_metrics::*;
+
+counter!(
+    STORAGE_COMMAND_TIMEOUT,
+    "the number of commands which failed because they exceeded the storage command timeout"
+);
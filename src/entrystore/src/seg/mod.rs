@@ -9,58 +9,822 @@
 use crate::EntryStore;
 
 use config::seg::Eviction;
-use config::SegConfig;
-use seg::{Policy, SegError};
+use config::{MaxTtl, SegConfig, TimeConfig};
+use seg::{Policy, ReadKind};
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "memcache")]
 mod memcache;
+mod metrics;
+
+use metrics::*;
+
+// the number of keys a multi-key command looks up between checks of the
+// command timeout, so that the clock isn't read for every key
+const TIMEOUT_CHECK_INTERVAL: usize = 16;
 
 /// A wrapper around [`seg::Seg`] which implements `EntryStore` and storage
 /// protocol traits.
 pub struct Seg {
+    active_expire: bool,
+    command_timeout: Option<Duration>,
+    // kept so that a fresh instance of the storage can be built on reload
+    config: config::Seg,
     data: ::seg::Seg,
+    flush_interval: Option<Duration>,
+    flushed_at: Instant,
+    max_ttl: MaxTtl,
+    // set while executing requests from clients which are exempt from
+    // eviction
+    no_evict: bool,
+    ordered_multiget: bool,
 }
 
 impl Seg {
     /// Create `Seg` storage based on the config and the `TimeType` which is
     /// used to interpret various expiry time formats.
-    pub fn new<T: SegConfig>(config: &T) -> Result<Self, std::io::Error> {
+    pub fn new<T: SegConfig + TimeConfig>(config: &T) -> Result<Self, std::io::Error> {
+        let max_ttl = config.time().ttl_limit();
         let config = config.seg();
 
-        // build up the eviction policy from the config
-        let eviction = match config.eviction() {
-            Eviction::None => Policy::None,
-            Eviction::Random => Policy::Random,
-            Eviction::RandomFifo => Policy::RandomFifo,
-            Eviction::Fifo => Policy::Fifo,
-            Eviction::Cte => Policy::Cte,
-            Eviction::Util => Policy::Util,
-            Eviction::Merge => Policy::Merge {
-                max: config.merge_max(),
-                merge: config.merge_target(),
-                compact: config.compact_target(),
-            },
-        };
+        let data = build(config)?;
 
-        // build the datastructure from the config
-        let data = ::seg::Seg::builder()
-            .hash_power(config.hash_power())
-            .overflow_factor(config.overflow_factor())
-            .heap_size(config.heap_size())
-            .segment_size(config.segment_size())
-            .eviction(eviction)
-            .datapool_path(config.datapool_path())
-            .build()?;
+        Ok(Self {
+            active_expire: true,
+            command_timeout: None,
+            config: config.clone(),
+            data,
+            flush_interval: config.flush_interval(),
+            flushed_at: Instant::now(),
+            max_ttl,
+            no_evict: false,
+            ordered_multiget: config.ordered_multiget(),
+        })
+    }
+
+    /// Checks the config for problems which would make `new` fail, such as a
+    /// heap which doesn't divide into segments, without allocating anything.
+    pub fn validate<T: SegConfig>(config: &T) -> Result<(), std::io::Error> {
+        builder(config.seg()).validate()
+    }
+
+    /// Dumps the items to a snapshot file at `path` and restores them into a
+    /// newly built instance of the storage, which then replaces this one.
+    /// Returns the number of items restored. The file must not already exist.
+    #[cfg(feature = "debug")]
+    fn reload_through(&mut self, path: &std::path::Path) -> Result<usize, std::io::Error> {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        let dumped = self.data.dump(file)?;
+
+        let mut data = build(&self.config)?;
+        let restored = data.restore(std::fs::File::open(path)?)?;
+        if restored != dumped {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("only {} of {} items were restored", restored, dumped),
+            ));
+        }
+
+        // the items are cleared from the replaced instance so that the item
+        // metrics only count those in the new one
+        let mut replaced = std::mem::replace(&mut self.data, data);
+        replaced.clear();
+
+        Ok(restored)
+    }
+
+    /// Returns the time by which a command starting now must finish, if the
+    /// command timeout is enabled.
+    fn deadline(&self) -> Option<Instant> {
+        self.command_timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    /// Looks up a batch of keys for a multi-key get, returning each item with
+    /// the index of its key. The items follow the order of the keys unless
+    /// ordered multi-get is disabled, in which case the storage picks it.
+    fn get_batch(&mut self, keys: &[&[u8]]) -> Vec<(usize, Option<::seg::Item>)> {
+        if self.ordered_multiget {
+            self.data.get_batch(keys).into_iter().enumerate().collect()
+        } else {
+            self.data.get_batch_unordered(keys)
+        }
+    }
+}
+
+/// Builds the datastructure from the config.
+fn build(config: &config::Seg) -> Result<::seg::Seg, std::io::Error> {
+    builder(config).build()
+}
+
+/// Configures a builder for the datastructure from the config.
+fn builder(config: &config::Seg) -> ::seg::Builder {
+    // build up the eviction policy from the config
+    let eviction = match config.eviction() {
+        Eviction::None => Policy::None,
+        Eviction::Random => Policy::Random,
+        Eviction::RandomFifo => Policy::RandomFifo,
+        Eviction::Fifo => Policy::Fifo,
+        Eviction::Cte => Policy::Cte,
+        Eviction::Util => Policy::Util,
+        Eviction::Merge => Policy::Merge {
+            max: config.merge_max(),
+            merge: config.merge_target(),
+            compact: config.compact_target(),
+        },
+    };
+
+    ::seg::Seg::builder()
+        .hash_power(config.hash_power())
+        .overflow_factor(config.overflow_factor())
+        .heap_size(config.heap_size())
+        .segment_size(config.segment_size())
+        .eviction(eviction)
+        .datapool_path(config.datapool_path())
+        .lock_memory(config.lock_memory())
+        .numa_node(config.numa_node())
+        .no_evict_cap(config.no_evict_cap())
+}
 
-        Ok(Self { data })
+/// Checks whether a command which has done `done` units of work should stop
+/// because it has passed its deadline. The clock is only read every
+/// `TIMEOUT_CHECK_INTERVAL` units, so a command always does at least that much
+/// work before it is stopped.
+fn timed_out(deadline: Option<Instant>, done: usize) -> bool {
+    match deadline {
+        Some(deadline) if done > 0 && done % TIMEOUT_CHECK_INTERVAL == 0 => {
+            if Instant::now() > deadline {
+                STORAGE_COMMAND_TIMEOUT.increment();
+                true
+            } else {
+                false
+            }
+        }
+        _ => false,
     }
 }
 
 impl EntryStore for Seg {
     fn expire(&mut self) {
-        self.data.expire();
+        if self.active_expire {
+            self.data.expire();
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(interval) = self.flush_interval {
+            if self.flushed_at.elapsed() >= interval {
+                // a failure is logged by the storage, which then rejects
+                // writes until a later flush succeeds
+                let _ = self.data.flush();
+                self.flushed_at = Instant::now();
+            }
+        }
+    }
+
+    fn shutdown(self) -> Result<(), std::io::Error> {
+        // only a datapool which is backed by a file has anything to persist
+        if self.config.datapool_path().is_none() {
+            return Ok(());
+        }
+
+        self.data
+            .flush_async()?
+            .wait_timeout(self.config.flush_timeout())
     }
 
     fn clear(&mut self) {
         self.data.clear();
     }
+
+    fn set_command_timeout(&mut self, timeout: Option<Duration>) {
+        self.command_timeout = timeout;
+    }
+
+    fn numa_node(&self) -> Option<usize> {
+        self.config.numa_node()
+    }
+
+    fn set_no_evict(&mut self, no_evict: bool) {
+        self.no_evict = no_evict;
+    }
+
+    fn dump(&mut self, key: &[u8]) -> Option<String> {
+        let item = self.data.read(key, ReadKind::Metadata)?;
+
+        let value = match item.value() {
+            seg::Value::Bytes(b) => hex(b),
+            // numeric values are stored in native byte order
+            seg::Value::U64(v) => hex(&v.to_ne_bytes()),
+        };
+
+        Some(format!(
+            "flags: {}\nttl: {}\nlength: {}\nvalue: {}\n",
+            item.flags(),
+            item.ttl().as_secs(),
+            item.value_len(),
+            value
+        ))
+    }
+
+    // forced eviction is only available in debug builds
+    #[cfg(feature = "debug")]
+    fn evict(&mut self, segments: usize) -> Option<usize> {
+        Some(self.data.evict(segments))
+    }
+
+    // as with forced eviction, these are only available in debug builds
+    #[cfg(feature = "debug")]
+    fn set_active_expire(&mut self, enabled: bool) -> bool {
+        self.active_expire = enabled;
+        true
+    }
+
+    #[cfg(feature = "debug")]
+    fn debug_object(&mut self, key: &[u8]) -> Option<String> {
+        let item = self.data.read(key, ReadKind::Metadata)?;
+
+        // redis stores short strings with the `embstr` encoding
+        let encoding = match item.value() {
+            seg::Value::U64(_) => "int",
+            seg::Value::Bytes(b) if b.len() <= 44 => "embstr",
+            seg::Value::Bytes(_) => "raw",
+        };
+
+        let now = common::time::Instant::<common::time::Seconds<u32>>::recent();
+
+        Some(format!(
+            "Value at:{:p} refcount:1 encoding:{} serializedlength:{} lru_seconds_idle:{}",
+            item.key().as_ptr(),
+            encoding,
+            item.value_len(),
+            (now - item.last_access()).as_secs(),
+        ))
+    }
+
+    #[cfg(feature = "debug")]
+    fn verify(&mut self) -> Option<Result<(), String>> {
+        Some(self.data.verify())
+    }
+
+    // as with forced eviction, this is only available in debug builds. it
+    // holds a second copy of the heap, and blocks the storage thread, until
+    // the reload is done
+    #[cfg(feature = "debug")]
+    fn reload(&mut self) -> Option<Result<usize, String>> {
+        // the new instance would need its own datapool file, which the
+        // datapool can't yet be restored from
+        if self.config.datapool_path().is_some() {
+            return Some(Err(
+                "reload is not supported with a datapool file".to_string()
+            ));
+        }
+
+        // the snapshot is written into a directory which only this user can
+        // access, and which is removed along with it once the reload is done
+        let result = tempfile::Builder::new()
+            .prefix("pelikan-reload")
+            .tempdir()
+            .and_then(|dir| self.reload_through(&dir.path().join("snapshot")));
+
+        Some(result.map_err(|e| e.to_string()))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(all(test, feature = "memcache"))]
+mod tests {
+    use super::*;
+    use config::SegcacheConfig;
+    use protocol_common::{
+        Compose, Execute, KeyEvent, KeyEventKind, KeyspaceEvents, KeyspaceNotifier, Parse,
+    };
+    use protocol_memcache::{Request, RequestParser, Response};
+
+    #[test]
+    fn validate() {
+        assert!(Seg::validate(&SegcacheConfig::default()).is_ok());
+
+        let dir = tempfile::tempdir().expect("failed to create dir");
+        let path = dir.path().join("segcache.toml");
+        std::fs::write(&path, "[seg]\nheap_size = 1000000\nsegment_size = 300000\n")
+            .expect("failed to write config");
+        let config = SegcacheConfig::load(path.to_str().unwrap()).expect("failed to load config");
+
+        // the heap doesn't hold a whole number of segments, which would also
+        // stop the storage from being built
+        let e = Seg::validate(&config).expect_err("validated a bad heap size");
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(Seg::new(&config).is_err());
+    }
+
+    #[test]
+    fn dump() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+
+        assert_eq!(storage.dump(b"missing"), None);
+
+        storage
+            .data
+            .insert(
+                b"binary",
+                &b"\x00ab\x00\xff\x00"[..],
+                Some(&42_u32.to_be_bytes()),
+                Duration::ZERO,
+            )
+            .expect("failed to insert");
+
+        // the ttl reported is that of the ttl bucket the item was written to
+        let ttl = storage.data.get_no_freq_incr(b"binary").unwrap().ttl();
+
+        assert_eq!(
+            storage.dump(b"binary"),
+            Some(format!(
+                "flags: 42\nttl: {}\nlength: 6\nvalue: 00616200ff00\n",
+                ttl.as_secs()
+            ))
+        );
+    }
+
+    #[test]
+    fn command_timeout() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+
+        let keys: Vec<String> = (0..1024).map(|i| format!("key_{}", i)).collect();
+        for key in &keys {
+            storage
+                .data
+                .insert(key.as_bytes(), &b"value"[..], None, Duration::ZERO)
+                .expect("failed to insert");
+        }
+
+        let get_all = request(&format!("get {}\r\n", keys.join(" ")));
+        let get_one = request("get key_1023\r\n");
+
+        assert_eq!(hits(storage.execute(&get_all)), 1024);
+
+        // with a budget which every command exceeds, the multi-key get stops
+        // at its first check and fails, rather than returning a partial reply
+        // which would look like misses for the keys it didn't look up
+        storage.set_command_timeout(Some(Duration::from_nanos(1)));
+        let timeouts = STORAGE_COMMAND_TIMEOUT.value();
+        assert_eq!(
+            compose(storage.execute(&get_all)),
+            b"SERVER_ERROR command timed out\r\n"
+        );
+        let gets_all = request(&format!("gets {}\r\n", keys.join(" ")));
+        assert_eq!(
+            compose(storage.execute(&gets_all)),
+            b"SERVER_ERROR command timed out\r\n"
+        );
+        assert_eq!(STORAGE_COMMAND_TIMEOUT.value(), timeouts + 2);
+
+        // requests which finish before their first check are unaffected
+        assert_eq!(hits(storage.execute(&get_one)), 1);
+
+        storage.set_command_timeout(None);
+        assert_eq!(hits(storage.execute(&get_all)), 1024);
+    }
+
+    #[test]
+    fn max_ttl() {
+        let mut config = SegcacheConfig::default();
+        config.time_mut().set_max_ttl(3600, false);
+        let mut storage = Seg::new(&config).expect("failed to create storage");
+
+        // ttls above the ceiling, and no expiry, are clamped down to it
+        for (key, exptime) in [("below", 60), ("at", 3600), ("above", 86400), ("never", 0)] {
+            let set = request(&format!("set {} 0 {} 1\r\n0\r\n", key, exptime));
+            assert_eq!(storage.execute(&set), Response::stored(false));
+        }
+
+        let mut ttl = |key: &[u8]| storage.data.get_no_freq_incr(key).unwrap().ttl().as_secs();
+        let at = ttl(b"at");
+        assert!(ttl(b"below") < at);
+        assert_eq!(ttl(b"above"), at);
+        assert_eq!(ttl(b"never"), at);
+
+        config.time_mut().set_max_ttl(3600, true);
+        let mut storage = Seg::new(&config).expect("failed to create storage");
+
+        // in strict mode ttls above the ceiling are rejected
+        let set = request("set above 0 86400 1\r\n0\r\n");
+        assert_eq!(
+            storage.execute(&set),
+            Response::client_error("ttl is above the maximum")
+        );
+        assert!(storage.data.get_no_freq_incr(b"above").is_none());
+
+        let cas = request("cas above 0 86400 1 0\r\n0\r\n");
+        assert_eq!(
+            storage.execute(&cas),
+            Response::client_error("ttl is above the maximum")
+        );
+
+        for set in ["set at 0 3600 1\r\n0\r\n", "set never 0 0 1\r\n0\r\n"] {
+            assert_eq!(storage.execute(&request(set)), Response::stored(false));
+        }
+    }
+
+    #[test]
+    fn append_prepend() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+
+        // nothing is created for a missing key
+        let append = request("append missing 0 0 1\r\n0\r\n");
+        assert_eq!(storage.execute(&append), Response::not_stored(false));
+        let prepend = request("prepend missing 0 0 1\r\n0\r\n");
+        assert_eq!(storage.execute(&prepend), Response::not_stored(false));
+        assert!(storage.data.get_no_freq_incr(b"missing").is_none());
+
+        let set = request("set key 42 3600 5\r\nmiddl\r\n");
+        assert_eq!(storage.execute(&set), Response::stored(false));
+        let ttl = storage.data.get_no_freq_incr(b"key").unwrap().ttl();
+
+        // the flags and ttl of the existing item are kept
+        let append = request("append key 7 0 2\r\ne!\r\n");
+        assert_eq!(storage.execute(&append), Response::stored(false));
+        let prepend = request("prepend key 7 0 2\r\nin\r\n");
+        assert_eq!(storage.execute(&prepend), Response::stored(false));
+
+        let item = storage.data.get_no_freq_incr(b"key").unwrap();
+        assert_eq!(item.value(), b"inmiddle!");
+        assert_eq!(item.flags(), 42);
+        assert!(item.ttl().as_secs() <= ttl.as_secs());
+
+        // numeric values are extended as their decimal representation
+        let set = request("set number 0 0 2\r\n12\r\n");
+        assert_eq!(storage.execute(&set), Response::stored(false));
+        let append = request("append number 0 0 1\r\n3\r\n");
+        assert_eq!(storage.execute(&append), Response::stored(false));
+        assert_eq!(
+            storage.data.get_no_freq_incr(b"number").unwrap().value(),
+            b"123"
+        );
+    }
+
+    // records each event it is notified of
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<(Vec<u8>, KeyEventKind)>,
+    }
+
+    impl KeyspaceNotifier for Recorder {
+        fn notify(&mut self, event: KeyEvent) {
+            self.events.push((event.key().to_vec(), event.kind()));
+        }
+    }
+
+    #[test]
+    fn keyspace_events() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+        let mut recorder = Recorder::default();
+
+        for buffer in [
+            "set key 0 0 5\r\nvalue\r\n",
+            "get key\r\n",
+            "delete key\r\n",
+            "delete key\r\n",
+        ] {
+            let request = request(buffer);
+            let response = storage.execute(&request);
+            request.keyspace_events(&response, &mut recorder);
+        }
+
+        // reads, and deletes of keys which don't exist, change nothing
+        assert_eq!(
+            recorder.events,
+            vec![
+                (b"key".to_vec(), KeyEventKind::Set),
+                (b"key".to_vec(), KeyEventKind::Delete)
+            ]
+        );
+    }
+
+    #[test]
+    fn getdel() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+
+        let set = request("set token 7 0 5\r\nvalue\r\n");
+        assert_eq!(storage.execute(&set), Response::stored(false));
+
+        // the value is returned once, and the key is gone afterward
+        let getdel = request("getdel token\r\n");
+        assert_eq!(
+            compose(storage.execute(&getdel)),
+            b"VALUE token 7 5\r\nvalue\r\nEND\r\n"
+        );
+        assert!(storage.data.get_no_freq_incr(b"token").is_none());
+
+        // a miss is not an error, and is composed as just `END`
+        assert_eq!(compose(storage.execute(&getdel)), b"END\r\n");
+    }
+
+    #[test]
+    fn touch() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+
+        let set = request("set a 7 60 5\r\nvalue\r\n");
+        assert_eq!(storage.execute(&set), Response::stored(false));
+
+        // touching removes the expiry and keeps the value and flags
+        assert_eq!(
+            compose(storage.execute(&request("touch a 0\r\n"))),
+            b"TOUCHED\r\n"
+        );
+        assert_eq!(storage.data.expire_time(b"a"), Some(None));
+        assert_eq!(
+            compose(storage.execute(&request("get a\r\n"))),
+            b"VALUE a 7 5\r\nvalue\r\nEND\r\n"
+        );
+        assert_eq!(
+            compose(storage.execute(&request("touch b 0\r\n"))),
+            b"NOT_FOUND\r\n"
+        );
+
+        // gat and gats return the values of the keys they touch
+        assert_eq!(
+            compose(storage.execute(&request("gat 60 a b\r\n"))),
+            b"VALUE a 7 5\r\nvalue\r\nEND\r\n"
+        );
+        assert!(storage.data.expire_time(b"a").unwrap().is_some());
+        let reply = compose(storage.execute(&request("gats 0 a\r\n")));
+        assert!(reply.starts_with(b"VALUE a 7 5 "));
+        assert_eq!(storage.data.expire_time(b"a"), Some(None));
+
+        // a negative ttl expires the item immediately
+        assert_eq!(
+            compose(storage.execute(&request("touch a -1\r\n"))),
+            b"TOUCHED\r\n"
+        );
+        assert_eq!(
+            compose(storage.execute(&request("gat 0 a\r\n"))),
+            b"END\r\n"
+        );
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn active_expire() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+        assert!(storage.set_active_expire(false));
+
+        let set = request("set key 0 1 5\r\nvalue\r\n");
+        assert_eq!(storage.execute(&set), Response::stored(false));
+
+        // the key expires, but isn't removed while active expiration is off
+        std::thread::sleep(Duration::from_secs(2));
+        storage.expire();
+        assert_eq!(storage.data.items(), 1);
+
+        // it is still missed when it is accessed
+        common::time::refresh_clock();
+        assert_eq!(
+            compose(storage.execute(&request("get key\r\n"))),
+            b"END\r\n"
+        );
+
+        assert!(storage.set_active_expire(true));
+        storage.expire();
+        assert_eq!(storage.data.items(), 0);
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn debug_object() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+
+        assert_eq!(storage.debug_object(b"missing"), None);
+
+        let long = "x".repeat(100);
+        for (key, value) in [("short", "value"), ("long", long.as_str())] {
+            let set = request(&format!("set {} 0 0 {}\r\n{}\r\n", key, value.len(), value));
+            assert_eq!(storage.execute(&set), Response::stored(false));
+        }
+        storage
+            .data
+            .insert(b"number", seg::Value::U64(42), None, Duration::ZERO)
+            .expect("failed to insert");
+
+        let short = storage.debug_object(b"short").unwrap();
+        assert!(short.starts_with("Value at:0x"), "bad line: {}", short);
+        assert!(short.contains(" refcount:1 encoding:embstr serializedlength:5 lru_seconds_idle:"));
+
+        let long = storage.debug_object(b"long").unwrap();
+        assert!(long.contains(" encoding:raw serializedlength:100 "));
+
+        let number = storage.debug_object(b"number").unwrap();
+        assert!(number.contains(" encoding:int "));
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn verify() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+        assert_eq!(storage.verify(), Some(Ok(())));
+
+        for key in ["a", "b", "c"] {
+            let set = request(&format!("set {} 0 0 1\r\n{}\r\n", key, key));
+            assert_eq!(storage.execute(&set), Response::stored(false));
+        }
+        storage.execute(&request("delete b\r\n"));
+        assert_eq!(storage.verify(), Some(Ok(())));
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn reload() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+        assert_eq!(storage.reload(), Some(Ok(0)));
+
+        storage.execute(&request("set a 7 0 1\r\na\r\n"));
+        storage.execute(&request("set b 0 3600 2\r\nbb\r\n"));
+        storage.execute(&request("set c 0 0 1\r\n1\r\n"));
+        storage.execute(&request("incr c 41\r\n"));
+        let ttl = storage.data.get_no_freq_incr(b"b").unwrap().ttl().as_secs();
+
+        assert_eq!(storage.reload(), Some(Ok(3)));
+
+        assert_eq!(
+            compose(storage.execute(&request("get a b c\r\n"))),
+            b"VALUE a 7 1\r\na\r\nVALUE b 0 2\r\nbb\r\nVALUE c 0 2\r\n42\r\nEND\r\n"
+        );
+        assert_eq!(storage.data.expire_time(b"a"), Some(None));
+        assert!(storage.data.get_no_freq_incr(b"b").unwrap().ttl().as_secs() <= ttl);
+        assert_eq!(storage.verify(), Some(Ok(())));
+    }
+
+    #[test]
+    fn ordered_multiget() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+
+        for key in ["a", "b", "c", "d"] {
+            let set = request(&format!("set {} 0 0 1\r\n{}\r\n", key, key));
+            assert_eq!(storage.execute(&set), Response::stored(false));
+        }
+
+        // hits follow the order of the requested keys, with misses left out
+        let get = request("get d x a y c b z\r\n");
+        let expected = b"VALUE d 0 1\r\nd\r\nVALUE a 0 1\r\na\r\n\
+            VALUE c 0 1\r\nc\r\nVALUE b 0 1\r\nb\r\nEND\r\n";
+        assert_eq!(compose(storage.execute(&get)), expected);
+
+        let gets = request("gets c y a\r\n");
+        let reply = String::from_utf8(compose(storage.execute(&gets))).unwrap();
+        let keys: Vec<&str> = reply
+            .lines()
+            .filter_map(|line| line.strip_prefix("VALUE "))
+            .map(|line| line.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(keys, ["c", "a"]);
+
+        // unordered, the reply holds the same values in some order
+        storage.ordered_multiget = false;
+        let mut reply: Vec<String> = String::from_utf8(compose(storage.execute(&get)))
+            .unwrap()
+            .lines()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(reply.pop().as_deref(), Some("END"));
+        let mut values: Vec<String> = reply.chunks(2).map(|value| value.join("\n")).collect();
+        values.sort();
+        assert_eq!(
+            values,
+            [
+                "VALUE a 0 1\na",
+                "VALUE b 0 1\nb",
+                "VALUE c 0 1\nc",
+                "VALUE d 0 1\nd"
+            ]
+        );
+    }
+
+    #[test]
+    fn shutdown() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let path = dir.path().join("segcache.toml");
+        let datapool = dir.path().join("datapool");
+        std::fs::write(
+            &path,
+            format!(
+                "[seg]\nheap_size = 1048576\nsegment_size = 4096\ndatapool_path = {:?}\n",
+                datapool
+            ),
+        )
+        .expect("failed to write config");
+        let config = SegcacheConfig::load(path.to_str().unwrap()).expect("failed to load config");
+
+        let mut storage = Seg::new(&config).expect("failed to create storage");
+        let set = request("set drink 0 0 6\r\ncoffee\r\n");
+        assert_eq!(storage.execute(&set), Response::stored(false));
+
+        // the datapool file is flushed as the storage is shut down
+        assert!(storage.shutdown().is_ok());
+        let data = std::fs::read(&datapool).expect("failed to read datapool");
+        assert!(data.windows(6).any(|w| w == b"coffee"));
+    }
+
+    // a datapool whose flushes fail while the shared flag is set
+    #[cfg(feature = "debug")]
+    struct FailingDatapool {
+        inner: Box<dyn datapool::Datapool>,
+        fail: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    #[cfg(feature = "debug")]
+    impl datapool::Datapool for FailingDatapool {
+        fn as_slice(&self) -> &[u8] {
+            self.inner.as_slice()
+        }
+
+        fn as_mut_slice(&mut self) -> &mut [u8] {
+            self.inner.as_mut_slice()
+        }
+
+        fn flush(&mut self) -> Result<(), std::io::Error> {
+            if self.fail.load(std::sync::atomic::Ordering::Relaxed) {
+                Err(std::io::Error::from_raw_os_error(28)) // ENOSPC
+            } else {
+                self.inner.flush()
+            }
+        }
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn degraded() {
+        let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+        assert_eq!(storage.flush_interval, None);
+        storage.flush_interval = Some(Duration::from_secs(1));
+
+        let fail = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let failing = fail.clone();
+        storage
+            .data
+            .wrap_datapool(|inner| {
+                Box::new(FailingDatapool {
+                    inner,
+                    fail: failing,
+                })
+            })
+            .expect("failed to wrap datapool");
+
+        let set = request("set drink 0 0 6\r\ncoffee\r\n");
+        assert_eq!(storage.execute(&set), Response::stored(false));
+
+        // the datapool is not flushed before the interval has passed
+        fail.store(true, std::sync::atomic::Ordering::Relaxed);
+        storage.flush();
+        assert!(!storage.data.is_degraded());
+
+        // once it has, a failed flush makes the storage reject writes
+        storage.flushed_at -= Duration::from_secs(1);
+        storage.flush();
+        assert!(storage.data.is_degraded());
+
+        let set = request("set drink 0 0 3\r\ntea\r\n");
+        assert_eq!(
+            compose(storage.execute(&set)),
+            b"SERVER_ERROR storage is degraded, writes are rejected\r\n"
+        );
+
+        // while reads are still served
+        let get = request("get drink\r\n");
+        assert_eq!(
+            compose(storage.execute(&get)),
+            b"VALUE drink 0 6\r\ncoffee\r\nEND\r\n"
+        );
+
+        // and the next flush which succeeds accepts writes again
+        fail.store(false, std::sync::atomic::Ordering::Relaxed);
+        storage.flushed_at -= Duration::from_secs(1);
+        storage.flush();
+        assert!(!storage.data.is_degraded());
+        assert_eq!(storage.execute(&set), Response::stored(false));
+    }
+
+    fn request(request: &str) -> Request {
+        RequestParser::new()
+            .parse(request.as_bytes())
+            .expect("bad request")
+            .into_inner()
+    }
+
+    fn compose(response: Response) -> Vec<u8> {
+        let mut buf = Vec::new();
+        response.compose(&mut buf);
+        buf
+    }
+
+    fn hits(response: Response) -> usize {
+        match response {
+            Response::Values(values) => values.values().len(),
+            response => panic!("unexpected response: {:?}", response),
+        }
+    }
 }
@@ -34,9 +34,15 @@
 //! a file, while letting all other log messages pass to standard out. This
 //! could allow splitting command/access/audit logs from the normal logging.
 
+pub use config::KlogFormat;
 pub use rustcommon_logger::*;
 
 use config::{DebugConfig, KlogConfig};
+use core::fmt::{Display, Formatter};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use std::cell::Cell;
+use std::net::SocketAddr;
 
 ////////////////////////////////////////////////////////////////////////////////
 // TODO(bmartin): everything below is Pelikan specific, and should be factored
@@ -52,12 +58,113 @@ macro_rules! klog {
     )
 }
 
+/// Logs a command which accesses a single key. In the `common` klog format
+/// this writes an [`AccessLog`] line built from the command, key, response
+/// code, and response length. Otherwise the remaining arguments are written,
+/// as with `klog!`.
+#[macro_export]
+macro_rules! klog_access {
+    ($command:expr, $key:expr, $code:expr, $len:expr, $($arg:tt)*) => (
+        if $crate::current_klog_format() == $crate::KlogFormat::Common {
+            $crate::klog!("{}", $crate::AccessLog::new($command, $key, $code, $len));
+        } else {
+            $crate::klog!($($arg)*);
+        }
+    )
+}
+
 pub trait Klog {
     type Response;
 
     fn klog(&self, response: &Self::Response);
 }
 
+// true if klog lines are written in the `common` format
+static KLOG_COMMON: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // the client and latency of the request being logged on this thread
+    static KLOG_CONTEXT: Cell<(Option<SocketAddr>, Option<Duration>)> = Cell::new((None, None));
+}
+
+/// Returns the layout used for klog lines.
+pub fn current_klog_format() -> KlogFormat {
+    if KLOG_COMMON.load(Ordering::Relaxed) {
+        KlogFormat::Common
+    } else {
+        KlogFormat::Default
+    }
+}
+
+/// Sets the layout used for klog lines. This is done by `configure_logging`.
+pub fn set_klog_format(format: KlogFormat) {
+    KLOG_COMMON.store(format == KlogFormat::Common, Ordering::Relaxed);
+}
+
+/// Sets the client address and the latency which are included in the
+/// [`AccessLog`] lines written by the calling thread, until they are next set.
+/// Workers call this before logging each request.
+pub fn set_klog_context(client: Option<SocketAddr>, latency: Option<Duration>) {
+    KLOG_CONTEXT.with(|context| context.set((client, latency)));
+}
+
+/// A klog line in the `common` format. The timestamp is added by the log
+/// output, so the line as written is:
+///
+/// `<timestamp> <client> "<command> <key>" <code> <bytes> <latency>`
+///
+/// The latency is in microseconds. Unknown fields are written as `-`, and the
+/// key is escaped so that the line is always a single line with a fixed
+/// number of space separated fields.
+pub struct AccessLog<'a> {
+    client: Option<SocketAddr>,
+    command: &'a str,
+    key: &'a [u8],
+    code: u8,
+    bytes: usize,
+    latency: Option<Duration>,
+}
+
+impl<'a> AccessLog<'a> {
+    /// Creates the line for a command, taking the client and latency from
+    /// the context set by `set_klog_context`.
+    pub fn new(command: &'a str, key: &'a [u8], code: u8, bytes: usize) -> Self {
+        let (client, latency) = KLOG_CONTEXT.with(|context| context.get());
+        Self {
+            client,
+            command,
+            key,
+            code,
+            bytes,
+            latency,
+        }
+    }
+}
+
+impl Display for AccessLog<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.client {
+            Some(client) => write!(f, "{} ", client)?,
+            None => write!(f, "- ")?,
+        }
+
+        write!(f, "\"{} ", self.command)?;
+        for byte in self.key {
+            if *byte == b' ' {
+                write!(f, "\\x20")?;
+            } else {
+                write!(f, "{}", core::ascii::escape_default(*byte))?;
+            }
+        }
+        write!(f, "\" {} {} ", self.code, self.bytes)?;
+
+        match self.latency {
+            Some(latency) => write!(f, "{}", latency.as_micros()),
+            None => write!(f, "-"),
+        }
+    }
+}
+
 pub fn configure_logging<T: DebugConfig + KlogConfig>(config: &T) -> Box<dyn Drain> {
     let debug_config = config.debug();
 
@@ -80,6 +187,8 @@ pub fn configure_logging<T: DebugConfig + KlogConfig>(config: &T) -> Box<dyn Dra
 
     let klog_config = config.klog();
 
+    set_klog_format(klog_config.format());
+
     let klog = if let Some(file) = klog_config.file() {
         let backup = klog_config.backup().unwrap_or(format!("{}.old", file));
         let output = Box::new(
@@ -104,3 +213,49 @@ pub fn configure_logging<T: DebugConfig + KlogConfig>(config: &T) -> Box<dyn Dra
         .build()
         .start()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // checks the line has the fields documented for `AccessLog`
+    fn fields(line: &str) -> Vec<&str> {
+        let (client, rest) = line.split_once(" \"").expect("missing request");
+        let (request, rest) = rest.split_once("\" ").expect("unterminated request");
+        let mut fields = vec![client];
+        fields.extend(request.split(' '));
+        fields.extend(rest.split(' '));
+        assert_eq!(fields.len(), 6, "wrong number of fields in: {}", line);
+        fields
+    }
+
+    #[test]
+    fn access_log() {
+        set_klog_context(
+            Some("127.0.0.1:40000".parse().unwrap()),
+            Some(Duration::from_micros(42)),
+        );
+
+        // a get hit for a 5 byte value
+        let line = AccessLog::new("get", b"key", 4, 5).to_string();
+        assert_eq!(line, "127.0.0.1:40000 \"get key\" 4 5 42");
+        assert_eq!(
+            fields(&line),
+            ["127.0.0.1:40000", "get", "key", "4", "5", "42"]
+        );
+
+        // a set which is stored, with an 8 byte response
+        let line = AccessLog::new("set", b"key", 5, 8).to_string();
+        assert_eq!(line, "127.0.0.1:40000 \"set key\" 5 8 42");
+        assert_eq!(
+            fields(&line),
+            ["127.0.0.1:40000", "set", "key", "5", "8", "42"]
+        );
+
+        // binary keys are escaped and unknown fields are dashes
+        set_klog_context(None, None);
+        let line = AccessLog::new("get", b"a b\"\r\n\x00", 0, 0).to_string();
+        assert_eq!(line, "- \"get a\\x20b\\\"\\r\\n\\x00\" 0 0 -");
+        assert_eq!(fields(&line)[2], "a\\x20b\\\"\\r\\n\\x00");
+    }
+}
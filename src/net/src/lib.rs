@@ -43,6 +43,10 @@ counter!(
 );
 counter!(TCP_CLOSE, "number of TCP streams closed");
 gauge!(TCP_CONN_CURR, "current number of open TCP streams");
+counter!(
+    BACKEND_CONNECT_TIMEOUT,
+    "number of attempts to connect a TCP stream which timed out"
+);
 counter!(TCP_RECV_BYTE, "number of bytes received on TCP streams");
 counter!(TCP_SEND_BYTE, "number of bytes sent on TCP streams");
 
@@ -60,6 +64,10 @@ counter!(
     STREAM_HANDSHAKE_EX,
     "number of exceptions while handshaking"
 );
+counter!(
+    STREAM_HANDSHAKE_TIMEOUT,
+    "number of streams closed for not completing the handshake in time"
+);
 counter!(STREAM_SHUTDOWN, "number of streams gracefully shutdown");
 counter!(
     STREAM_SHUTDOWN_EX,
@@ -3,6 +3,7 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use crate::*;
+use std::os::unix::prelude::{AsRawFd, RawFd};
 
 pub struct Listener {
     inner: ListenerType,
@@ -67,6 +68,15 @@ impl Listener {
     }
 }
 
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        match &self.inner {
+            ListenerType::Plain(listener) => listener.as_raw_fd(),
+            ListenerType::Tls((listener, _acceptor)) => listener.as_raw_fd(),
+        }
+    }
+}
+
 impl event::Source for Listener {
     fn register(
         &mut self,
@@ -46,6 +46,15 @@ impl Stream {
         }
     }
 
+    /// Returns an error if the underlying TCP connection failed to connect or
+    /// timed out while connecting, see `TcpStream::check_connect`.
+    pub fn check_connect(&mut self) -> Result<()> {
+        match &mut self.inner {
+            StreamType::Tcp(s) => s.check_connect(),
+            StreamType::TlsTcp(s) => s.check_connect(),
+        }
+    }
+
     pub fn is_handshaking(&self) -> bool {
         match &self.inner {
             StreamType::Tcp(_) => false,
@@ -67,6 +76,44 @@ impl Stream {
         }
     }
 
+    /// Sets `SO_LINGER`, which controls how the stream closes when it is
+    /// dropped. A timeout of zero resets the connection, discarding any unsent
+    /// data, while `None` restores the default graceful close. Nonzero
+    /// timeouts are rejected, as they make dropping the stream block.
+    pub fn set_linger(&mut self, timeout: Option<std::time::Duration>) -> Result<()> {
+        tcp::set_linger(self.as_raw_fd(), timeout)
+    }
+
+    /// Returns the `SO_LINGER` timeout, or `None` if lingering is disabled.
+    pub fn linger(&self) -> Result<Option<std::time::Duration>> {
+        tcp::linger(self.as_raw_fd())
+    }
+
+    /// Returns the application protocol negotiated with ALPN, which is always
+    /// `None` for plaintext streams.
+    pub fn negotiated_alpn(&self) -> Option<Vec<u8>> {
+        match &self.inner {
+            StreamType::Tcp(_) => None,
+            StreamType::TlsTcp(s) => s.negotiated_alpn(),
+        }
+    }
+
+    /// Returns the DER encoding of the leaf certificate presented by the
+    /// peer, which is always `None` for plaintext streams.
+    pub fn peer_certificate(&self) -> Option<Vec<u8>> {
+        match &self.inner {
+            StreamType::Tcp(_) => None,
+            StreamType::TlsTcp(s) => s.peer_certificate(),
+        }
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        match &self.inner {
+            StreamType::Tcp(s) => s.peer_addr(),
+            StreamType::TlsTcp(s) => s.peer_addr(),
+        }
+    }
+
     pub fn shutdown(&mut self) -> Result<bool> {
         let result = match &mut self.inner {
             StreamType::Tcp(s) => s.shutdown(Shutdown::Both).map(|_| true),
@@ -3,10 +3,18 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use crate::*;
-use std::os::unix::prelude::FromRawFd;
+use std::os::unix::prelude::{AsRawFd, FromRawFd, RawFd};
 
 pub use std::net::Shutdown;
 
+// lists the congestion control algorithms which are loaded in the kernel
+#[cfg(target_os = "linux")]
+const AVAILABLE_CONGESTION_CONTROL: &str = "/proc/sys/net/ipv4/tcp_available_congestion_control";
+
+// the longest congestion control algorithm name, including the nul terminator
+#[cfg(target_os = "linux")]
+const TCP_CA_NAME_MAX: usize = 16;
+
 #[derive(PartialEq)]
 enum State {
     Connecting,
@@ -16,6 +24,8 @@ enum State {
 pub struct TcpStream {
     inner: mio::net::TcpStream,
     state: State,
+    // when a connection which is still being established times out
+    deadline: Option<std::time::Instant>,
 }
 
 impl TcpStream {
@@ -28,9 +38,46 @@ impl TcpStream {
         Ok(Self {
             inner,
             state: State::Connecting,
+            deadline: None,
         })
     }
 
+    /// Starts a non-blocking connect to the address, which must be established
+    /// within the timeout. The stream is returned while it is still
+    /// connecting, and `check_connect` reports whether the timeout has passed.
+    pub fn connect_timeout(addr: SocketAddr, timeout: std::time::Duration) -> Result<Self> {
+        let mut stream = Self::connect(addr)?;
+        stream.deadline = Some(std::time::Instant::now() + timeout);
+        Ok(stream)
+    }
+
+    /// Returns an error if the connection could not be established, or if it
+    /// is still being established after its connect timeout, in which case
+    /// the error is of kind `TimedOut`. This should be called when the stream
+    /// becomes writable, and periodically while it is still connecting, since
+    /// a connect which is never answered doesn't produce any events.
+    pub fn check_connect(&mut self) -> Result<()> {
+        if self.state == State::Established {
+            return Ok(());
+        }
+
+        if let Some(e) = self.inner.take_error()? {
+            return Err(e);
+        }
+
+        if self.is_established() {
+            return Ok(());
+        }
+
+        match self.deadline {
+            Some(deadline) if std::time::Instant::now() >= deadline => {
+                BACKEND_CONNECT_TIMEOUT.increment();
+                Err(Error::new(ErrorKind::TimedOut, "connect timed out"))
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn is_established(&mut self) -> bool {
         if self.state == State::Established {
             true
@@ -50,7 +97,11 @@ impl TcpStream {
             State::Connecting
         };
 
-        Self { inner, state }
+        Self {
+            inner,
+            state,
+            deadline: None,
+        }
     }
 
     pub fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
@@ -140,7 +191,11 @@ impl FromRawFd for TcpStream {
             State::Connecting
         };
 
-        Self { inner, state }
+        Self {
+            inner,
+            state,
+            deadline: None,
+        }
     }
 }
 
@@ -168,12 +223,121 @@ impl TcpListener {
         Ok(Self { inner })
     }
 
+    /// Wraps a listening socket which was opened elsewhere, such as one passed
+    /// down by a supervisor for socket activation. Returns an error if the
+    /// socket is not a listening TCP socket, in which case the fd is left open
+    /// and remains owned by the caller.
+    ///
+    /// # Safety
+    ///
+    /// The fd must be open and not owned by anything else, as ownership of it
+    /// is taken once it has been validated.
+    pub unsafe fn from_listen_fd(fd: RawFd) -> Result<TcpListener> {
+        // the fd is only inspected here, so that it isn't closed on error
+        Self::check_listen_fd(fd)?;
+
+        let listener = std::net::TcpListener::from_raw_fd(fd);
+        listener.set_nonblocking(true)?;
+
+        let inner = mio::net::TcpListener::from_std(listener);
+
+        Ok(Self { inner })
+    }
+
+    /// Returns an error if the fd is not a listening TCP socket, without
+    /// taking ownership of it. This is the check made by `from_listen_fd`.
+    pub fn check_listen_fd(fd: RawFd) -> Result<()> {
+        match socket_family(fd)? {
+            libc::AF_INET | libc::AF_INET6 => {}
+            _ => {
+                return Err(Error::new(ErrorKind::InvalidInput, "not an inet socket"));
+            }
+        }
+
+        if socket_option(fd, libc::SO_TYPE)? != libc::SOCK_STREAM
+            || socket_option(fd, libc::SO_ACCEPTCONN)? == 0
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "not a listening tcp socket",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Sets the congestion control algorithm, such as `bbr` or `cubic`, used
+    /// by the streams accepted from this listener. This is only supported on
+    /// Linux, where the algorithm must be available in the kernel.
+    #[cfg(target_os = "linux")]
+    pub fn set_congestion_control(&self, algorithm: &str) -> Result<()> {
+        check_congestion_control(algorithm)?;
+
+        // SAFETY: the name is valid for reads of its length
+        let ret = unsafe {
+            libc::setsockopt(
+                self.inner.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_CONGESTION,
+                algorithm.as_ptr() as *const libc::c_void,
+                algorithm.len() as libc::socklen_t,
+            )
+        };
+
+        if ret < 0 {
+            let e = Error::last_os_error();
+            Err(Error::new(
+                e.kind(),
+                format!(
+                    "failed to set tcp congestion control to {}: {}",
+                    algorithm, e
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn set_congestion_control(&self, algorithm: &str) -> Result<()> {
+        check_congestion_control(algorithm)
+    }
+
+    /// Returns the congestion control algorithm used by the streams accepted
+    /// from this listener.
+    #[cfg(target_os = "linux")]
+    pub fn congestion_control(&self) -> Result<String> {
+        let mut name = [0u8; TCP_CA_NAME_MAX];
+        let mut len = name.len() as libc::socklen_t;
+
+        // SAFETY: name and len are valid for writes and len holds the size
+        // of name
+        let ret = unsafe {
+            libc::getsockopt(
+                self.inner.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_CONGESTION,
+                name.as_mut_ptr() as *mut libc::c_void,
+                &mut len,
+            )
+        };
+
+        if ret < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let name = &name[0..(len as usize).min(TCP_CA_NAME_MAX)];
+        let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        Ok(String::from_utf8_lossy(&name[0..end]).into_owned())
+    }
+
     pub fn accept(&self) -> Result<(TcpStream, SocketAddr)> {
         let result = self.inner.accept().map(|(stream, addr)| {
             (
                 TcpStream {
                     inner: stream,
                     state: State::Established,
+                    deadline: None,
                 },
                 addr,
             )
@@ -216,9 +380,182 @@ impl event::Source for TcpListener {
     }
 }
 
+/// Checks that a TCP congestion control algorithm can be used by a listener,
+/// returning an error which lists the available algorithms if it can't.
+#[cfg(target_os = "linux")]
+pub fn check_congestion_control(algorithm: &str) -> Result<()> {
+    let available = std::fs::read_to_string(AVAILABLE_CONGESTION_CONTROL)?;
+    if available.split_whitespace().any(|a| a == algorithm) {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "tcp congestion control {} is not available, expected one of: {}",
+                algorithm,
+                available.trim()
+            ),
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn check_congestion_control(_algorithm: &str) -> Result<()> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "tcp congestion control can only be set on linux",
+    ))
+}
+
+/// Sets `SO_LINGER` on a socket. A timeout of zero discards any unsent data
+/// when the socket is closed and resets the connection with a RST rather than
+/// closing it with a FIN. With `None`, the socket closes in the background,
+/// which is the default.
+///
+/// Any other timeout is rejected, as it makes `close()` block until the data
+/// is delivered or the timeout passes, even for a non-blocking socket, which
+/// would stall the event loop of the thread dropping the socket.
+pub(crate) fn set_linger(fd: RawFd, timeout: Option<std::time::Duration>) -> Result<()> {
+    if matches!(timeout, Some(t) if !t.is_zero()) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "only a linger timeout of zero is supported, as others block on close",
+        ));
+    }
+
+    let linger = libc::linger {
+        l_onoff: timeout.is_some() as libc::c_int,
+        l_linger: timeout
+            .map(|t| t.as_secs().min(libc::c_int::MAX as u64) as libc::c_int)
+            .unwrap_or(0),
+    };
+
+    // SAFETY: linger is valid for reads and is correctly sized for the option
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_LINGER,
+            &linger as *const libc::linger as *const libc::c_void,
+            std::mem::size_of::<libc::linger>() as libc::socklen_t,
+        )
+    };
+
+    if ret < 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Reads `SO_LINGER` from a socket, see `set_linger`.
+pub(crate) fn linger(fd: RawFd) -> Result<Option<std::time::Duration>> {
+    let mut linger = libc::linger {
+        l_onoff: 0,
+        l_linger: 0,
+    };
+    let mut len = std::mem::size_of::<libc::linger>() as libc::socklen_t;
+
+    // SAFETY: linger and len are valid for writes and len holds the size of
+    // linger
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_LINGER,
+            &mut linger as *mut libc::linger as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret < 0 {
+        Err(Error::last_os_error())
+    } else if linger.l_onoff == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(std::time::Duration::from_secs(
+            linger.l_linger.max(0) as u64
+        )))
+    }
+}
+
+/// Reads an integer valued socket level option.
+fn socket_option(fd: RawFd, option: libc::c_int) -> Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+    // SAFETY: value and len are valid for writes and are correctly sized for
+    // an integer option
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            option,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret < 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(value)
+    }
+}
+
+fn socket_family(fd: RawFd) -> Result<libc::c_int> {
+    // SAFETY: an all-zero sockaddr_storage is valid
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+
+    // SAFETY: addr and len are valid for writes and len is the size of addr,
+    // which is large enough for any address family
+    let ret = unsafe {
+        libc::getsockname(
+            fd,
+            &mut addr as *mut libc::sockaddr_storage as *mut libc::sockaddr,
+            &mut len,
+        )
+    };
+
+    if ret < 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(addr.ss_family as libc::c_int)
+    }
+}
+
+/// Starts a non-blocking connect to the first of the addresses for which the
+/// connect can be started. With a timeout, the connection must be established
+/// within it, see `TcpStream::connect_timeout`.
+///
+/// Later addresses are only tried when a connect can't be started at all. A
+/// connect which is started, but then fails or times out, is reported by
+/// `check_connect` after this has returned, and is not retried with the next
+/// address. Doing so would replace the socket, which the caller has already
+/// registered for events, so it is left to the caller to connect again.
+pub(crate) fn connect_any<A: ToSocketAddrs>(
+    addr: A,
+    timeout: Option<std::time::Duration>,
+) -> Result<TcpStream> {
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    let mut s = Err(Error::new(ErrorKind::Other, "failed to resolve"));
+    for addr in addrs {
+        s = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(addr, timeout),
+            None => TcpStream::connect(addr),
+        };
+        if s.is_ok() {
+            break;
+        }
+    }
+
+    s
+}
+
 #[derive(Default)]
 pub struct TcpConnector {
-    _inner: (),
+    connect_timeout: Option<std::time::Duration>,
 }
 
 impl TcpConnector {
@@ -226,17 +563,20 @@ impl TcpConnector {
         Self::default()
     }
 
-    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> Result<TcpStream> {
-        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
-        let mut s = Err(Error::new(ErrorKind::Other, "failed to resolve"));
-        for addr in addrs {
-            s = TcpStream::connect(addr);
-            if s.is_ok() {
-                break;
-            }
-        }
+    /// Sets the time allowed to establish a connection. Connecting never
+    /// blocks, and the stream is returned while the connection is still being
+    /// established. With a timeout, `Stream::check_connect` fails once it has
+    /// passed. With `None`, which is the default, the connect is only limited
+    /// by the operating system. Only one address is connected to, so when a
+    /// name resolves to several, a connect which times out is not retried
+    /// with the others.
+    pub fn connect_timeout(mut self, timeout: Option<std::time::Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
 
-        s
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> Result<TcpStream> {
+        connect_any(addr, self.connect_timeout)
     }
 }
 
@@ -261,11 +601,165 @@ mod tests {
         let _ = create_listener("127.0.0.1:0");
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn listener_congestion_control() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+
+        // reno is built into every kernel and may be used without privileges
+        listener
+            .set_congestion_control("reno")
+            .expect("failed to set congestion control");
+        assert_eq!(
+            listener
+                .congestion_control()
+                .expect("failed to get congestion control"),
+            "reno"
+        );
+
+        let e = listener
+            .set_congestion_control("not-an-algorithm")
+            .expect_err("set an unknown congestion control");
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn listener_from_fd() {
+        use std::os::unix::prelude::IntoRawFd;
+
+        let fd = std::net::TcpListener::bind("127.0.0.1:0")
+            .expect("failed to bind")
+            .into_raw_fd();
+
+        // as if the fd had been inherited from a supervisor
+        let listener = unsafe { TcpListener::from_listen_fd(fd) };
+        let listener = Listener::from(listener.expect("failed to use listener"));
+
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let _client_stream = create_connector().connect(addr).expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(listener.accept().is_ok());
+    }
+
+    #[test]
+    fn listener_from_fd_not_listening() {
+        use std::os::unix::prelude::IntoRawFd;
+
+        let fd = std::net::UdpSocket::bind("127.0.0.1:0")
+            .expect("failed to bind")
+            .into_raw_fd();
+
+        assert!(unsafe { TcpListener::from_listen_fd(fd) }.is_err());
+
+        // the fd still belongs to the caller after the error
+        assert!(unsafe { libc::fcntl(fd, libc::F_GETFD) } >= 0);
+        drop(unsafe { std::net::UdpSocket::from_raw_fd(fd) });
+    }
+
+    #[test]
+    fn stream_linger() {
+        let listener = create_listener("127.0.0.1:0");
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let _client_stream = create_connector().connect(addr).expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let mut stream = listener.accept().expect("failed to accept");
+
+        assert_eq!(stream.linger().expect("failed to get linger"), None);
+
+        let timeout = std::time::Duration::ZERO;
+        stream
+            .set_linger(Some(timeout))
+            .expect("failed to set linger");
+        assert_eq!(
+            stream.linger().expect("failed to get linger"),
+            Some(timeout)
+        );
+
+        // a nonzero timeout would block the thread closing the stream
+        let e = stream
+            .set_linger(Some(std::time::Duration::from_secs(5)))
+            .expect_err("set a nonzero linger");
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            stream.linger().expect("failed to get linger"),
+            Some(timeout)
+        );
+
+        stream.set_linger(None).expect("failed to set linger");
+        assert_eq!(stream.linger().expect("failed to get linger"), None);
+    }
+
+    #[test]
+    fn stream_zero_linger_resets() {
+        let listener = create_listener("127.0.0.1:0");
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let mut client_stream = create_connector().connect(addr).expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let mut stream = listener.accept().expect("failed to accept");
+
+        // closing with a zero linger resets the connection instead of sending
+        // a FIN, which the client sees as an error rather than end of stream
+        stream
+            .set_linger(Some(std::time::Duration::ZERO))
+            .expect("failed to set linger");
+        drop(stream);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let mut buf = [0; 64];
+        match client_stream.read(&mut buf) {
+            Err(e) => assert_eq!(e.kind(), ErrorKind::ConnectionReset),
+            Ok(n) => panic!("read: {} bytes but expected a reset", n),
+        }
+    }
+
     #[test]
     fn connector() {
         let _ = create_connector();
     }
 
+    #[test]
+    fn connect_timeout() {
+        let timeout = std::time::Duration::from_millis(200);
+        let connector = Connector::from(TcpConnector::new().connect_timeout(Some(timeout)));
+
+        // a listener whose accept queue is full, so that any further connect
+        // to it is never answered. listening again changes the backlog
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        assert_eq!(unsafe { libc::listen(listener.as_raw_fd(), 0) }, 0);
+        let addr = listener.local_addr().expect("listener has no local addr");
+        let _queued = std::net::TcpStream::connect(addr).expect("failed to connect");
+
+        // connecting doesn't block, and the stream fails once the timeout has
+        // passed
+        let start = std::time::Instant::now();
+        let mut stream = connector.connect(addr).expect("failed to connect");
+        assert!(start.elapsed() < timeout);
+        loop {
+            match stream.check_connect() {
+                Ok(()) => {
+                    assert!(start.elapsed() < timeout * 5, "connect did not time out");
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Err(e) => {
+                    assert_eq!(e.kind(), ErrorKind::TimedOut);
+                    break;
+                }
+            }
+        }
+        assert!(start.elapsed() >= timeout);
+
+        // connections which are answered are established within the timeout
+        let listener = create_listener("127.0.0.1:0");
+        let addr = listener.local_addr().expect("listener has no local addr");
+        let mut stream = connector.connect(addr).expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(stream.check_connect().is_ok());
+        assert!(stream.is_established());
+    }
+
     #[test]
     fn ping_pong() {
         let connector = create_connector();
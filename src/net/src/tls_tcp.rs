@@ -5,8 +5,9 @@
 pub use boring::ssl::{ShutdownResult, SslVerifyMode};
 use std::os::unix::prelude::AsRawFd;
 
-use boring::ssl::{ErrorCode, Ssl, SslFiletype, SslMethod, SslStream};
-use boring::x509::X509;
+use boring::hash::{hash, MessageDigest};
+use boring::ssl::{AlpnError, ErrorCode, Ssl, SslFiletype, SslMethod, SslStream};
+use boring::x509::{X509StoreContextRef, X509};
 
 use crate::*;
 
@@ -34,10 +35,20 @@ impl TlsTcpStream {
         self.inner.get_mut().set_nodelay(nodelay)
     }
 
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.get_ref().peer_addr()
+    }
+
     pub fn is_handshaking(&self) -> bool {
         self.state == TlsState::Handshaking
     }
 
+    /// Checks the connect of the underlying TCP stream, see
+    /// `TcpStream::check_connect`.
+    pub fn check_connect(&mut self) -> Result<()> {
+        self.inner.get_mut().check_connect()
+    }
+
     pub fn interest(&self) -> Interest {
         if self.is_handshaking() {
             Interest::READABLE.add(Interest::WRITABLE)
@@ -77,6 +88,23 @@ impl TlsTcpStream {
         }
     }
 
+    /// Returns the application protocol which was negotiated with ALPN during
+    /// the handshake, or `None` if no protocol was negotiated.
+    pub fn negotiated_alpn(&self) -> Option<Vec<u8>> {
+        self.inner
+            .ssl()
+            .selected_alpn_protocol()
+            .map(|p| p.to_vec())
+    }
+
+    /// Returns the DER encoding of the leaf certificate presented by the peer,
+    /// if it presented one. Once the handshake is complete, a server which
+    /// requires client authentication can use this to authorize the client,
+    /// for example by its subject.
+    pub fn peer_certificate(&self) -> Option<Vec<u8>> {
+        self.inner.ssl().peer_certificate()?.to_der().ok()
+    }
+
     pub fn shutdown(&mut self) -> Result<ShutdownResult> {
         self.inner
             .shutdown()
@@ -164,6 +192,8 @@ impl TlsTcpAcceptor {
             certificate_file: None,
             certificate_chain_file: None,
             private_key_file: None,
+            alpn_protocols: Vec::new(),
+            require_client_auth: false,
         })
     }
 
@@ -203,10 +233,26 @@ pub struct TlsTcpAcceptorBuilder {
     certificate_file: Option<PathBuf>,
     certificate_chain_file: Option<PathBuf>,
     private_key_file: Option<PathBuf>,
+    alpn_protocols: Vec<Vec<u8>>,
+    require_client_auth: bool,
 }
 
 impl TlsTcpAcceptorBuilder {
     pub fn build(mut self) -> Result<TlsTcpAcceptor> {
+        // client certificates are verified against the CA file, so one must
+        // be provided to require them
+        if self.require_client_auth {
+            if self.ca_file.is_none() {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    "client authentication requires a CA file",
+                ));
+            }
+
+            self.inner
+                .set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        }
+
         // load the CA file, if provided
         if let Some(f) = self.ca_file {
             self.inner.set_ca_file(f.clone()).map_err(|e| {
@@ -318,16 +364,43 @@ impl TlsTcpAcceptorBuilder {
             }
         }
 
+        // select the first of our protocols which the client offers, a client
+        // which offers none of them is rejected
+        if !self.alpn_protocols.is_empty() {
+            alpn_wire_format(&self.alpn_protocols)?;
+            let protocols = self.alpn_protocols.clone();
+            self.inner.set_alpn_select_callback(move |_ssl, client| {
+                alpn_select(&protocols, client).ok_or(AlpnError::ALERT_FATAL)
+            });
+        }
+
         let inner = self.inner.build().into_context();
 
         Ok(TlsTcpAcceptor { inner })
     }
 
+    /// Sets the application protocols, such as `h2` or `http/1.1`, which may
+    /// be negotiated with ALPN, in order of preference. A client which offers
+    /// ALPN but none of these protocols fails the handshake. By default, no
+    /// protocol is negotiated.
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
     pub fn verify(mut self, mode: SslVerifyMode) -> Self {
         self.inner.set_verify(mode);
         self
     }
 
+    /// Require each client to present a certificate which is signed by one of
+    /// the CAs in the CA file, failing the handshake otherwise. This is off
+    /// by default. When enabled, `build` fails unless a CA file is provided.
+    pub fn require_client_auth(mut self, required: bool) -> Self {
+        self.require_client_auth = required;
+        self
+    }
+
     /// Load trusted root certificates from a file.
     ///
     /// The file should contain a sequence of PEM-formatted CA certificates.
@@ -377,6 +450,8 @@ impl TlsTcpAcceptorBuilder {
 #[allow(dead_code)]
 pub struct TlsTcpConnector {
     inner: boring::ssl::SslContext,
+    connect_timeout: Option<std::time::Duration>,
+    server_name: Option<String>,
 }
 
 impl TlsTcpConnector {
@@ -390,20 +465,23 @@ impl TlsTcpConnector {
             certificate_file: None,
             certificate_chain_file: None,
             private_key_file: None,
+            connect_timeout: None,
+            alpn_protocols: Vec::new(),
+            server_name: None,
         })
     }
 
     pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> Result<TlsTcpStream> {
-        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
-        let mut s = Err(Error::new(ErrorKind::Other, "failed to resolve"));
-        for addr in addrs {
-            s = TcpStream::connect(addr);
-            if s.is_ok() {
-                break;
-            }
-        }
+        let s = connect_any(addr, self.connect_timeout);
 
-        let ssl = Ssl::new(&self.inner)?;
+        let mut ssl = Ssl::new(&self.inner)?;
+
+        // the server certificate is checked against the configured name, and
+        // never against the address which was resolved and connected to
+        if let Some(name) = &self.server_name {
+            ssl.set_hostname(name)?;
+            ssl.param_mut().set_host(name)?;
+        }
 
         let stream = unsafe { SslStream::from_raw_parts(ssl.into_ptr(), s?) };
 
@@ -438,6 +516,9 @@ pub struct TlsTcpConnectorBuilder {
     certificate_file: Option<PathBuf>,
     certificate_chain_file: Option<PathBuf>,
     private_key_file: Option<PathBuf>,
+    connect_timeout: Option<std::time::Duration>,
+    alpn_protocols: Vec<Vec<u8>>,
+    server_name: Option<String>,
 }
 
 impl TlsTcpConnectorBuilder {
@@ -532,9 +613,47 @@ impl TlsTcpConnectorBuilder {
             }
         }
 
+        if !self.alpn_protocols.is_empty() {
+            let protocols = alpn_wire_format(&self.alpn_protocols)?;
+            self.inner.set_alpn_protos(&protocols).map_err(|e| {
+                Error::new(
+                    ErrorKind::Other,
+                    format!("failed to set alpn protocols: {}", e),
+                )
+            })?;
+        }
+
         let inner = self.inner.build().into_context();
 
-        Ok(TlsTcpConnector { inner })
+        Ok(TlsTcpConnector {
+            inner,
+            connect_timeout: self.connect_timeout,
+            server_name: self.server_name,
+        })
+    }
+
+    /// Sets the application protocols which are offered to the server with
+    /// ALPN, in order of preference. By default, none are offered.
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    /// Sets the time allowed to establish the TCP connection, see
+    /// `TcpConnector::connect_timeout`. The TLS handshake is not covered by
+    /// the timeout.
+    pub fn connect_timeout(mut self, timeout: Option<std::time::Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets the name of the server, which is sent with SNI and which the
+    /// server certificate must be valid for. The name is used however the
+    /// address passed to `connect` is resolved. If this is not set, no name is
+    /// sent and the certificate is accepted for any name.
+    pub fn server_name(mut self, name: String) -> Self {
+        self.server_name = Some(name);
+        self
     }
 
     pub fn verify(mut self, mode: SslVerifyMode) -> Self {
@@ -583,6 +702,435 @@ impl TlsTcpConnectorBuilder {
         self.private_key_file = Some(file.as_ref().to_path_buf());
         self
     }
+
+    /// Replace certificate verification with a custom callback. The callback
+    /// is called for each certificate in the chain presented by the server,
+    /// with the result of the default verification of that certificate, and
+    /// returns whether the certificate should be accepted.
+    ///
+    /// # Safety
+    ///
+    /// This is an advanced option. The callback is responsible for all trust
+    /// decisions, and a callback which ignores the default result can accept
+    /// certificates which have not been issued by a trusted CA. Only use this
+    /// where the callback enforces an equivalent or stronger check.
+    pub fn danger_verify_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(bool, &mut X509StoreContextRef) -> bool + 'static + Sync + Send,
+    {
+        self.inner
+            .set_verify_callback(SslVerifyMode::PEER, callback);
+        self
+    }
+
+    /// Pin the public key of the server. The connection is accepted only if the
+    /// SHA-256 digest of the DER-encoded SubjectPublicKeyInfo of the server's
+    /// leaf certificate matches the pin.
+    ///
+    /// # Safety
+    ///
+    /// This is an advanced option. The pin replaces validation against the CA
+    /// roots, so expiry and issuer are not checked and any certificate which
+    /// carries the pinned key is trusted. Rotating the server key requires
+    /// updating the pin.
+    pub fn danger_pin_spki_sha256(self, pin: [u8; 32]) -> Self {
+        self.danger_verify_callback(move |_preverified, ctx| {
+            // intermediates and roots are not checked, trust rests on the
+            // leaf carrying the pinned key
+            if ctx.error_depth() != 0 {
+                return true;
+            }
+
+            ctx.current_cert()
+                .and_then(|cert| cert.public_key().ok())
+                .and_then(|key| key.public_key_to_der().ok())
+                .and_then(|der| hash(MessageDigest::sha256(), &der).ok())
+                .map(|digest| boring::memcmp::eq(&digest, &pin))
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// Encodes protocols in the ALPN wire format, where each protocol is prefixed
+/// with its length. Protocols must be between 1 and 255 bytes long.
+fn alpn_wire_format(protocols: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    for protocol in protocols {
+        if protocol.is_empty() || protocol.len() > 255 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "alpn protocols must be between 1 and 255 bytes",
+            ));
+        }
+        encoded.push(protocol.len() as u8);
+        encoded.extend_from_slice(protocol);
+    }
+    Ok(encoded)
+}
+
+/// Selects the first of our protocols which the client offers. The selection
+/// is returned from the client's list, which outlives the select callback.
+fn alpn_select<'a>(protocols: &[Vec<u8>], client: &'a [u8]) -> Option<&'a [u8]> {
+    protocols.iter().find_map(|protocol| {
+        let mut offered = client;
+        while let Some((len, rest)) = offered.split_first() {
+            if rest.len() < *len as usize {
+                return None;
+            }
+            let (candidate, rest) = rest.split_at(*len as usize);
+            if candidate == &protocol[..] {
+                return Some(candidate);
+            }
+            offered = rest;
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boring::asn1::Asn1Time;
+    use boring::ec::{EcGroup, EcKey};
+    use boring::nid::Nid;
+    use boring::pkey::{PKey, Private};
+    use boring::x509::extension::SubjectAlternativeName;
+    use boring::x509::X509NameBuilder;
+
+    // generates a self-signed certificate for `localhost`, writing the key and
+    // certificate to files so they can be loaded by the builders
+    fn self_signed(name: &str) -> (PKey<Private>, PathBuf, PathBuf) {
+        self_signed_for(name, "localhost")
+    }
+
+    // as above, with the certificate issued for the given dns name
+    fn self_signed_for(name: &str, dns: &str) -> (PKey<Private>, PathBuf, PathBuf) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", "localhost").unwrap();
+        let subject = subject.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&subject).unwrap();
+        cert.set_issuer_name(&subject).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns(dns)
+            .build(&cert.x509v3_context(None, None))
+            .unwrap();
+        cert.append_extension(san).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = cert.build();
+
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let key_file = dir.join(format!("pelikan-{}-{}.key", name, id));
+        let cert_file = dir.join(format!("pelikan-{}-{}.crt", name, id));
+        std::fs::write(&key_file, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        std::fs::write(&cert_file, cert.to_pem().unwrap()).unwrap();
+
+        (key, key_file, cert_file)
+    }
+
+    fn spki_sha256(key: &PKey<Private>) -> [u8; 32] {
+        let digest = hash(MessageDigest::sha256(), &key.public_key_to_der().unwrap()).unwrap();
+        let mut pin = [0; 32];
+        pin.copy_from_slice(&digest);
+        pin
+    }
+
+    // returns whether the client completed the handshake with a server
+    // presenting the given certificate
+    fn handshake(connector: TlsTcpConnector, key_file: &Path, cert_file: &Path) -> bool {
+        let acceptor = TlsTcpAcceptor::mozilla_intermediate_v5()
+            .expect("failed to create builder")
+            .certificate_file(cert_file)
+            .private_key_file(key_file)
+            .build()
+            .expect("failed to initialize tls acceptor");
+        negotiate(acceptor, connector).is_some()
+    }
+
+    // returns the client and server streams once the client has completed the
+    // handshake, or `None` if the handshake fails
+    fn negotiate(acceptor: TlsTcpAcceptor, connector: TlsTcpConnector) -> Option<(Stream, Stream)> {
+        let listener = Listener::from((
+            TcpListener::bind("127.0.0.1:0").expect("failed to bind"),
+            acceptor,
+        ));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let mut client = Stream::from(connector.connect(addr).ok()?);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        // the server may already fail the handshake as it accepts, such as
+        // when there is no protocol in common
+        let mut server = listener.accept().ok()?;
+
+        for _ in 0..50 {
+            let _ = server.do_handshake();
+            match client.do_handshake() {
+                Ok(()) => {
+                    // the server may still need to process the end of the
+                    // handshake
+                    let _ = server.do_handshake();
+                    return Some((client, server));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Err(_) => {
+                    return None;
+                }
+            }
+        }
+
+        None
+    }
+
+    #[test]
+    fn pinned_spki() {
+        let (server_key, server_key_file, server_cert_file) = self_signed("pin-server");
+        let (client_key, client_key_file, client_cert_file) = self_signed("pin-client");
+
+        let connector = |pin| {
+            TlsTcpConnector::builder()
+                .expect("failed to create builder")
+                .certificate_file(&client_cert_file)
+                .private_key_file(&client_key_file)
+                .danger_pin_spki_sha256(pin)
+                .build()
+                .expect("failed to initialize tls connector")
+        };
+
+        // the server certificate is self-signed, so it is only trusted if the
+        // pin matches
+        assert!(handshake(
+            connector(spki_sha256(&server_key)),
+            &server_key_file,
+            &server_cert_file
+        ));
+        assert!(!handshake(
+            connector(spki_sha256(&client_key)),
+            &server_key_file,
+            &server_cert_file
+        ));
+
+        for file in [
+            server_key_file,
+            server_cert_file,
+            client_key_file,
+            client_cert_file,
+        ] {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn server_name() {
+        let (_, server_key_file, server_cert_file) =
+            self_signed_for("name-server", "cache.example.com");
+        let (_, client_key_file, client_cert_file) = self_signed("name-client");
+
+        // the self-signed server certificate is trusted as its own root, so
+        // only the name decides whether the handshake succeeds
+        let connector = |name: &str| {
+            TlsTcpConnector::builder()
+                .expect("failed to create builder")
+                .ca_file(&server_cert_file)
+                .certificate_file(&client_cert_file)
+                .private_key_file(&client_key_file)
+                .verify(SslVerifyMode::PEER)
+                .server_name(name.to_string())
+                .build()
+                .expect("failed to initialize tls connector")
+        };
+
+        // the connection is to an ip address, and the name is checked instead
+        assert!(handshake(
+            connector("cache.example.com"),
+            &server_key_file,
+            &server_cert_file
+        ));
+        assert!(!handshake(
+            connector("other.example.com"),
+            &server_key_file,
+            &server_cert_file
+        ));
+
+        for file in [
+            server_key_file,
+            server_cert_file,
+            client_key_file,
+            client_cert_file,
+        ] {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn alpn() {
+        let (_, server_key_file, server_cert_file) = self_signed("alpn-server");
+        let (_, client_key_file, client_cert_file) = self_signed("alpn-client");
+
+        let acceptor = |protocols: &[&[u8]]| {
+            TlsTcpAcceptor::mozilla_intermediate_v5()
+                .expect("failed to create builder")
+                .certificate_file(&server_cert_file)
+                .private_key_file(&server_key_file)
+                .alpn_protocols(protocols.iter().map(|p| p.to_vec()).collect())
+                .build()
+                .expect("failed to initialize tls acceptor")
+        };
+        let connector = |protocols: &[&[u8]]| {
+            TlsTcpConnector::builder()
+                .expect("failed to create builder")
+                .certificate_file(&client_cert_file)
+                .private_key_file(&client_key_file)
+                .verify(SslVerifyMode::NONE)
+                .alpn_protocols(protocols.iter().map(|p| p.to_vec()).collect())
+                .build()
+                .expect("failed to initialize tls connector")
+        };
+
+        // the protocol offered by both sides is negotiated
+        let (client, server) = negotiate(acceptor(&[b"h2"]), connector(&[b"http/1.1", b"h2"]))
+            .expect("handshake failed");
+        assert_eq!(client.negotiated_alpn(), Some(b"h2".to_vec()));
+        assert_eq!(server.negotiated_alpn(), Some(b"h2".to_vec()));
+
+        // nothing is negotiated when the client doesn't use alpn
+        let (client, server) =
+            negotiate(acceptor(&[b"h2"]), connector(&[])).expect("handshake failed");
+        assert_eq!(client.negotiated_alpn(), None);
+        assert_eq!(server.negotiated_alpn(), None);
+
+        // and the handshake fails when there is no protocol in common
+        assert!(negotiate(acceptor(&[b"h2"]), connector(&[b"http/1.1"])).is_none());
+
+        // protocols must fit the wire format
+        assert!(TlsTcpAcceptor::mozilla_intermediate_v5()
+            .expect("failed to create builder")
+            .certificate_file(&server_cert_file)
+            .private_key_file(&server_key_file)
+            .alpn_protocols(vec![Vec::new()])
+            .build()
+            .is_err());
+
+        for file in [
+            server_key_file,
+            server_cert_file,
+            client_key_file,
+            client_cert_file,
+        ] {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    // returns whether the server completed the handshake with the client. with
+    // TLS 1.3 the client finishes before the server has checked its
+    // certificate, so the server side decides
+    fn server_accepts(acceptor: TlsTcpAcceptor, connector: TlsTcpConnector) -> Option<Stream> {
+        let (_client, mut server) = negotiate(acceptor, connector)?;
+        for _ in 0..50 {
+            match server.do_handshake() {
+                Ok(()) => return Some(server),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Err(_) => return None,
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn client_auth() {
+        let (_, server_key_file, server_cert_file) = self_signed("mtls-server");
+        let (_, client_key_file, client_cert_file) = self_signed("mtls-client");
+        let (_, other_key_file, other_cert_file) = self_signed("mtls-other");
+
+        // the self-signed client certificate is trusted as its own root
+        let acceptor = || {
+            TlsTcpAcceptor::mozilla_intermediate_v5()
+                .expect("failed to create builder")
+                .certificate_file(&server_cert_file)
+                .private_key_file(&server_key_file)
+                .ca_file(&client_cert_file)
+                .require_client_auth(true)
+                .build()
+                .expect("failed to initialize tls acceptor")
+        };
+        let connector = |key_file: &Path, cert_file: &Path| {
+            TlsTcpConnector::builder()
+                .expect("failed to create builder")
+                .certificate_file(cert_file)
+                .private_key_file(key_file)
+                .verify(SslVerifyMode::NONE)
+                .build()
+                .expect("failed to initialize tls connector")
+        };
+
+        // a client with a trusted certificate is accepted, and the server sees
+        // the certificate it presented
+        let server = server_accepts(acceptor(), connector(&client_key_file, &client_cert_file))
+            .expect("handshake failed");
+        let pem = std::fs::read(&client_cert_file).unwrap();
+        assert_eq!(
+            server.peer_certificate(),
+            Some(X509::from_pem(&pem).unwrap().to_der().unwrap())
+        );
+
+        // a client with an untrusted certificate is rejected
+        assert!(server_accepts(acceptor(), connector(&other_key_file, &other_cert_file)).is_none());
+
+        // as is a client which presents no certificate at all
+        let mut inner = boring::ssl::SslConnector::builder(SslMethod::tls_client()).unwrap();
+        inner.set_verify(SslVerifyMode::NONE);
+        let anonymous = TlsTcpConnector {
+            inner: inner.build().into_context(),
+            connect_timeout: None,
+            server_name: None,
+        };
+        assert!(server_accepts(acceptor(), anonymous).is_none());
+
+        // without client auth, no certificate is requested
+        let acceptor = TlsTcpAcceptor::mozilla_intermediate_v5()
+            .expect("failed to create builder")
+            .certificate_file(&server_cert_file)
+            .private_key_file(&server_key_file)
+            .build()
+            .expect("failed to initialize tls acceptor");
+        let server = server_accepts(acceptor, connector(&client_key_file, &client_cert_file))
+            .expect("handshake failed");
+        assert_eq!(server.peer_certificate(), None);
+
+        // client auth can't be required without a CA to verify against
+        assert!(TlsTcpAcceptor::mozilla_intermediate_v5()
+            .expect("failed to create builder")
+            .certificate_file(&server_cert_file)
+            .private_key_file(&server_key_file)
+            .require_client_auth(true)
+            .build()
+            .is_err());
+
+        for file in [
+            server_key_file,
+            server_cert_file,
+            client_key_file,
+            client_cert_file,
+            other_key_file,
+            other_cert_file,
+        ] {
+            let _ = std::fs::remove_file(file);
+        }
+    }
 }
 
 // NOTE: these tests only work if there's a `test` folder within this crate that
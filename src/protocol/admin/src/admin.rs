@@ -9,17 +9,40 @@
 
 use crate::*;
 use common::bytes::SliceExtension;
+use common::signal::ClientFilter;
 use rustcommon_metrics::*;
 
 use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
 
 // TODO(bmartin): see TODO for protocol::data::Request, this is cleaner here
 // since the variants are simple, but better to take the same approach in both
 // modules.
 #[derive(PartialEq, Eq, Debug)]
 pub enum AdminRequest {
+    /// Close the client sessions which match the filter
+    ClientKill(ClientFilter),
+    /// List the id and address of each client session
+    ClientList,
+    /// Mark the client sessions which match the filter as privileged, so that
+    /// the items they write are exempt from eviction, or clear the mark
+    ClientNoEvict(ClientFilter, bool),
+    /// Turn eager expiration on or off, for testing lazy expiration
+    DebugActiveExpire(bool),
+    /// Describe how the value for a key is stored
+    DebugObject(Vec<u8>),
+    /// Round-trip the stored items through a snapshot into a fresh instance
+    /// of the storage, for testing persistence
+    DebugReload,
     FlushAll,
+    ReadOnly(bool),
     Stats,
+    /// The change in each counter over the last complete interval, rather
+    /// than its cumulative value. When set, the current interval is ended
+    /// early so that the reply is up to date
+    StatsDelta(bool),
+    /// Hand the listeners to a new copy of the binary and drain this process
+    Upgrade,
     Version,
     Quit,
 }
@@ -46,10 +69,41 @@ impl Parse<AdminRequest> for AdminRequestParser {
             let mut single_byte_windows = trimmed_buffer.windows(1);
             if let Some(command_verb_end) = single_byte_windows.position(|w| w == b" ") {
                 let command_verb = &trimmed_buffer[0..command_verb_end];
-                // TODO(bmartin): 'stats slab' will go here eventually which will
-                // remove the need for ignoring this lint.
-                #[allow(clippy::match_single_binding)]
-                match command_verb {
+                let argument = trimmed_buffer[(command_verb_end + 1)..].trim();
+                // TODO(bmartin): 'stats slab' will go here eventually
+                match (command_verb, argument) {
+                    (b"readonly", b"on") => Ok(ParseOk::new(
+                        AdminRequest::ReadOnly(true),
+                        command_end + CRLF.len(),
+                    )),
+                    (b"readonly", b"off") => Ok(ParseOk::new(
+                        AdminRequest::ReadOnly(false),
+                        command_end + CRLF.len(),
+                    )),
+                    (b"stats", b"delta") => Ok(ParseOk::new(
+                        AdminRequest::StatsDelta(false),
+                        command_end + CRLF.len(),
+                    )),
+                    (b"stats", b"delta fresh") => Ok(ParseOk::new(
+                        AdminRequest::StatsDelta(true),
+                        command_end + CRLF.len(),
+                    )),
+                    (b"client", b"list") => Ok(ParseOk::new(
+                        AdminRequest::ClientList,
+                        command_end + CRLF.len(),
+                    )),
+                    (b"debug", b"reload") => Ok(ParseOk::new(
+                        AdminRequest::DebugReload,
+                        command_end + CRLF.len(),
+                    )),
+                    (b"debug", argument) => match debug(argument) {
+                        Some(request) => Ok(ParseOk::new(request, command_end + CRLF.len())),
+                        None => Err(Error::from(ErrorKind::InvalidInput)),
+                    },
+                    (b"client", argument) => match client(argument) {
+                        Some(request) => Ok(ParseOk::new(request, command_end + CRLF.len())),
+                        None => Err(Error::from(ErrorKind::InvalidInput)),
+                    },
                     _ => Err(Error::from(ErrorKind::InvalidInput)),
                 }
             } else {
@@ -60,6 +114,10 @@ impl Parse<AdminRequest> for AdminRequestParser {
                     )),
                     b"stats" => Ok(ParseOk::new(AdminRequest::Stats, command_end + CRLF.len())),
                     b"quit" => Ok(ParseOk::new(AdminRequest::Quit, command_end + CRLF.len())),
+                    b"upgrade" => Ok(ParseOk::new(
+                        AdminRequest::Upgrade,
+                        command_end + CRLF.len(),
+                    )),
                     b"version" => Ok(ParseOk::new(
                         AdminRequest::Version,
                         command_end + CRLF.len(),
@@ -73,6 +131,49 @@ impl Parse<AdminRequest> for AdminRequestParser {
     }
 }
 
+// parses the arguments to `client`, which are either `kill <filter>` or
+// `noevict <filter> on|off`, where the filter is `id <id>` or
+// `addr <ip:port>`
+fn client(argument: &[u8]) -> Option<AdminRequest> {
+    let argument = std::str::from_utf8(argument).ok()?;
+    let tokens: Vec<&str> = argument.split_whitespace().collect();
+    match tokens[..] {
+        ["kill", kind, value] => client_filter(kind, value).map(AdminRequest::ClientKill),
+        ["noevict", kind, value, state] => {
+            let no_evict = match state {
+                "on" => true,
+                "off" => false,
+                _ => return None,
+            };
+            client_filter(kind, value).map(|filter| AdminRequest::ClientNoEvict(filter, no_evict))
+        }
+        _ => None,
+    }
+}
+
+// parses the arguments to `debug` other than `reload`, which are either
+// `set-active-expire 0|1` or `object <key>`
+fn debug(argument: &[u8]) -> Option<AdminRequest> {
+    let tokens: Vec<&[u8]> = argument
+        .split(|b| *b == b' ')
+        .filter(|token| !token.is_empty())
+        .collect();
+    match tokens[..] {
+        [b"set-active-expire", b"0"] => Some(AdminRequest::DebugActiveExpire(false)),
+        [b"set-active-expire", b"1"] => Some(AdminRequest::DebugActiveExpire(true)),
+        [b"object", key] => Some(AdminRequest::DebugObject(key.to_vec())),
+        _ => None,
+    }
+}
+
+fn client_filter(kind: &str, value: &str) -> Option<ClientFilter> {
+    match kind {
+        "id" => value.parse().ok().map(ClientFilter::Id),
+        "addr" => value.parse().ok().map(ClientFilter::Addr),
+        _ => None,
+    }
+}
+
 pub struct Version {
     version: String,
 }
@@ -88,25 +189,65 @@ impl Compose for Version {
 }
 
 pub enum AdminResponse {
+    Clients(Vec<(u64, Option<SocketAddr>)>),
+    ClientsKilled(usize),
+    ClientsUpdated(usize),
     Hangup,
+    NotFound,
+    Object(String),
     Ok,
+    Reloaded(usize),
+    ServerError(String),
     Stats,
+    Upgraded(u32),
     Version(Version),
 }
 
 impl AdminResponse {
+    pub fn clients(clients: Vec<(u64, Option<SocketAddr>)>) -> Self {
+        Self::Clients(clients)
+    }
+
+    pub fn clients_killed(count: usize) -> Self {
+        Self::ClientsKilled(count)
+    }
+
+    pub fn clients_updated(count: usize) -> Self {
+        Self::ClientsUpdated(count)
+    }
+
     pub fn hangup() -> Self {
         Self::Hangup
     }
 
+    pub fn not_found() -> Self {
+        Self::NotFound
+    }
+
+    pub fn object(description: String) -> Self {
+        Self::Object(description)
+    }
+
     pub fn ok() -> Self {
         Self::Ok
     }
 
+    pub fn reloaded(items: usize) -> Self {
+        Self::Reloaded(items)
+    }
+
+    pub fn server_error(message: String) -> Self {
+        Self::ServerError(message)
+    }
+
     pub fn stats() -> Self {
         Self::Stats
     }
 
+    pub fn upgraded(pid: u32) -> Self {
+        Self::Upgraded(pid)
+    }
+
     pub fn version(version: String) -> Self {
         Self::Version(Version { version })
     }
@@ -115,11 +256,53 @@ impl AdminResponse {
 impl Compose for AdminResponse {
     fn compose(&self, buf: &mut dyn BufMut) -> usize {
         match self {
+            Self::Clients(clients) => {
+                let mut size = 0;
+                for (id, addr) in clients {
+                    let addr = addr
+                        .map(|v| v.to_string())
+                        .unwrap_or_else(|| "unknown".to_string());
+                    let line = format!("CLIENT id={} addr={}\r\n", id, addr);
+                    size += line.len();
+                    buf.put_slice(line.as_bytes());
+                }
+                buf.put_slice(b"END\r\n");
+                size + 5
+            }
+            Self::ClientsKilled(count) => {
+                let data = format!("KILLED {}\r\n", count);
+                buf.put_slice(data.as_bytes());
+                data.len()
+            }
+            Self::ClientsUpdated(count) => {
+                let data = format!("UPDATED {}\r\n", count);
+                buf.put_slice(data.as_bytes());
+                data.len()
+            }
             Self::Hangup => 0,
+            Self::NotFound => {
+                buf.put_slice(b"NOT_FOUND\r\n");
+                11
+            }
+            Self::Object(description) => {
+                let data = format!("{}\r\n", description);
+                buf.put_slice(data.as_bytes());
+                data.len()
+            }
             Self::Ok => {
                 buf.put_slice(b"OK\r\n");
                 4
             }
+            Self::Reloaded(items) => {
+                let data = format!("RELOADED {}\r\n", items);
+                buf.put_slice(data.as_bytes());
+                data.len()
+            }
+            Self::ServerError(message) => {
+                let data = format!("SERVER_ERROR {}\r\n", message);
+                buf.put_slice(data.as_bytes());
+                data.len()
+            }
             Self::Stats => {
                 let mut size = 0;
                 let mut data = Vec::new();
@@ -157,6 +340,11 @@ impl Compose for AdminResponse {
                 buf.put_slice(b"END\r\n");
                 size + 5
             }
+            Self::Upgraded(pid) => {
+                let data = format!("UPGRADED {}\r\n", pid);
+                buf.put_slice(data.as_bytes());
+                data.len()
+            }
             Self::Version(v) => v.compose(buf),
         }
     }
@@ -207,6 +395,25 @@ mod tests {
         assert_eq!(parsed.unwrap().into_inner(), AdminRequest::Stats);
     }
 
+    #[test]
+    fn parse_stats_delta() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"stats delta\r\n");
+        assert!(parsed.is_ok());
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::StatsDelta(false)
+        );
+
+        let parsed = parser.parse(b"stats delta fresh\r\n");
+        assert!(parsed.is_ok());
+        assert_eq!(parsed.unwrap().into_inner(), AdminRequest::StatsDelta(true));
+
+        assert!(parser.parse(b"stats slab\r\n").is_err());
+        assert!(parser.parse(b"stats delta stale\r\n").is_err());
+    }
+
     #[test]
     fn parse_version() {
         let parser = AdminRequestParser::new();
@@ -216,6 +423,163 @@ mod tests {
         assert_eq!(parsed.unwrap().into_inner(), AdminRequest::Version);
     }
 
+    #[test]
+    fn parse_read_only() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"readonly on\r\n");
+        assert!(parsed.is_ok());
+        assert_eq!(parsed.unwrap().into_inner(), AdminRequest::ReadOnly(true));
+
+        let parsed = parser.parse(b"readonly off\r\n");
+        assert!(parsed.is_ok());
+        assert_eq!(parsed.unwrap().into_inner(), AdminRequest::ReadOnly(false));
+
+        assert!(parser.parse(b"readonly\r\n").is_err());
+        assert!(parser.parse(b"readonly maybe\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_client_kill() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"client kill id 42\r\n");
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::ClientKill(ClientFilter::Id(42))
+        );
+
+        let parsed = parser.parse(b"client kill addr 127.0.0.1:51234\r\n");
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::ClientKill(ClientFilter::Addr("127.0.0.1:51234".parse().unwrap()))
+        );
+
+        assert!(parser.parse(b"client kill\r\n").is_err());
+        assert!(parser.parse(b"client kill id\r\n").is_err());
+        assert!(parser.parse(b"client kill id abc\r\n").is_err());
+        assert!(parser.parse(b"client kill addr 127.0.0.1\r\n").is_err());
+        assert!(parser.parse(b"client kill id 1 2\r\n").is_err());
+
+        let parsed = parser.parse(b"client list\r\n");
+        assert_eq!(parsed.unwrap().into_inner(), AdminRequest::ClientList);
+    }
+
+    #[test]
+    fn parse_client_no_evict() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"client noevict id 42 on\r\n");
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::ClientNoEvict(ClientFilter::Id(42), true)
+        );
+
+        let parsed = parser.parse(b"client noevict addr 127.0.0.1:51234 off\r\n");
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::ClientNoEvict(
+                ClientFilter::Addr("127.0.0.1:51234".parse().unwrap()),
+                false
+            )
+        );
+
+        assert!(parser.parse(b"client noevict id 42\r\n").is_err());
+        assert!(parser.parse(b"client noevict id 42 maybe\r\n").is_err());
+        assert!(parser.parse(b"client noevict name 42 on\r\n").is_err());
+        assert!(parser.parse(b"client noevict on\r\n").is_err());
+    }
+
+    #[test]
+    fn compose_clients() {
+        let mut buf = Vec::new();
+        let clients = vec![(1, Some("127.0.0.1:51234".parse().unwrap())), (7, None)];
+        let len = AdminResponse::clients(clients).compose(&mut buf);
+        assert_eq!(
+            buf,
+            b"CLIENT id=1 addr=127.0.0.1:51234\r\nCLIENT id=7 addr=unknown\r\nEND\r\n"
+        );
+        assert_eq!(len, buf.len());
+
+        let mut buf = Vec::new();
+        AdminResponse::clients_killed(2).compose(&mut buf);
+        assert_eq!(buf, b"KILLED 2\r\n");
+
+        let mut buf = Vec::new();
+        AdminResponse::clients_updated(1).compose(&mut buf);
+        assert_eq!(buf, b"UPDATED 1\r\n");
+    }
+
+    #[test]
+    fn parse_upgrade() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"upgrade\r\n");
+        assert_eq!(parsed.unwrap().into_inner(), AdminRequest::Upgrade);
+
+        assert!(parser.parse(b"upgrade now\r\n").is_err());
+    }
+
+    #[test]
+    fn compose_upgraded() {
+        let mut buf = Vec::new();
+        let len = AdminResponse::upgraded(4242).compose(&mut buf);
+        assert_eq!(buf, b"UPGRADED 4242\r\n");
+        assert_eq!(len, buf.len());
+
+        let mut buf = Vec::new();
+        let len = AdminResponse::server_error("upgrade failed".to_string()).compose(&mut buf);
+        assert_eq!(buf, b"SERVER_ERROR upgrade failed\r\n");
+        assert_eq!(len, buf.len());
+    }
+
+    #[test]
+    fn parse_debug_reload() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"debug reload\r\n");
+        assert_eq!(parsed.unwrap().into_inner(), AdminRequest::DebugReload);
+
+        assert!(parser.parse(b"debug\r\n").is_err());
+        assert!(parser.parse(b"debug reload now\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_debug() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"debug set-active-expire 0\r\n");
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::DebugActiveExpire(false)
+        );
+
+        let parsed = parser.parse(b"debug set-active-expire 1\r\n");
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::DebugActiveExpire(true)
+        );
+
+        let parsed = parser.parse(b"debug object key\r\n");
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::DebugObject(b"key".to_vec())
+        );
+
+        assert!(parser.parse(b"debug set-active-expire\r\n").is_err());
+        assert!(parser.parse(b"debug set-active-expire on\r\n").is_err());
+        assert!(parser.parse(b"debug object\r\n").is_err());
+        assert!(parser.parse(b"debug object a b\r\n").is_err());
+    }
+
+    #[test]
+    fn compose_reloaded() {
+        let mut buf = Vec::new();
+        let len = AdminResponse::reloaded(3).compose(&mut buf);
+        assert_eq!(buf, b"RELOADED 3\r\n");
+        assert_eq!(len, buf.len());
+    }
+
     #[test]
     fn parse_commands_with_whitespace_leading_or_trailing() {
         let parser = AdminRequestParser::new();
@@ -21,8 +21,100 @@ pub trait Compose {
     }
 }
 
+/// A change to the state of a connection which a request asks for along with
+/// its response. The worker applies it once the response has been composed,
+/// so the client always receives the response first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SessionAction {
+    /// Close the connection once the response has been flushed to it
+    CloseAfterResponse,
+    /// Close the connection at once, without sending the response. This is
+    /// used when responses to the connection were lost, as later ones would
+    /// otherwise be taken as the replies to earlier requests.
+    Close,
+}
+
 pub trait Execute<Request, Response: Compose> {
     fn execute(&mut self, request: &Request) -> Response;
+
+    /// Executes a request, returning its response along with any action to
+    /// apply to the connection after the response is sent. By default, a
+    /// response which should hang up closes the connection once it has been
+    /// flushed.
+    fn execute_with_action(&mut self, request: &Request) -> (Response, Option<SessionAction>) {
+        let response = self.execute(request);
+        let action = if response.should_hangup() {
+            Some(SessionAction::CloseAfterResponse)
+        } else {
+            None
+        };
+        (response, action)
+    }
+}
+
+/// Allows a server to be placed into read-only mode, where requests which
+/// would modify the stored data are refused while reads continue to be served.
+/// The mode is toggled from the admin port.
+pub trait ReadOnlyMode<Response> {
+    /// Returns the response to send in place of executing this request while
+    /// the server is read-only. Requests which do not modify stored data
+    /// should return `None`.
+    fn read_only_error(&self) -> Option<Response>;
+}
+
+/// The kind of change a request made to a key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyEventKind {
+    /// The key was stored, or its value was changed
+    Set,
+    /// The key was removed
+    Delete,
+    /// The ttl of the key was changed, such as by a touch. This does not mean
+    /// the key has expired.
+    Touch,
+}
+
+/// A change made to a key by a request, which is reported to a
+/// `KeyspaceNotifier`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyEvent<'a> {
+    key: &'a [u8],
+    kind: KeyEventKind,
+}
+
+impl<'a> KeyEvent<'a> {
+    pub fn new(key: &'a [u8], kind: KeyEventKind) -> Self {
+        Self { key, kind }
+    }
+
+    pub fn key(&self) -> &'a [u8] {
+        self.key
+    }
+
+    pub fn kind(&self) -> KeyEventKind {
+        self.kind
+    }
+}
+
+/// Receives the changes made to the keyspace, for example to feed a
+/// change-data-capture consumer. The notifier is called on the thread which
+/// owns the storage, right after each request which changed a key, so it
+/// should hand the event off rather than block.
+///
+/// Only changes which a request makes to the keys it names are reported. Keys
+/// which the storage removes itself, as they expire or are evicted, and keys
+/// cleared by a `flush_all` are not, so a consumer which mirrors the keyspace
+/// has to apply the ttls itself.
+pub trait KeyspaceNotifier: Send {
+    fn notify(&mut self, event: KeyEvent);
+}
+
+/// Allows a request to report the keys it changed to a `KeyspaceNotifier`.
+pub trait KeyspaceEvents<Response> {
+    /// Reports each key which this request changed, given the response it
+    /// produced. Requests which failed or which do not change any keys report
+    /// nothing.
+    fn keyspace_events(&self, _response: &Response, _notifier: &mut dyn KeyspaceNotifier) {}
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -47,4 +139,23 @@ impl<T> ParseOk<T> {
 
 pub trait Parse<T> {
     fn parse(&self, buffer: &[u8]) -> Result<ParseOk<T>, std::io::Error>;
+
+    /// Returns the number of additional bytes needed to complete the message
+    /// at the start of a buffer which failed to parse with `WouldBlock`. This
+    /// lets the caller size its next read. `None` means the parser can't tell
+    /// how much more is needed.
+    fn bytes_needed(&self, _buffer: &[u8]) -> Option<usize> {
+        None
+    }
+}
+
+/// Allows a parser to describe why it rejected the contents of a buffer, so
+/// that the client can be told before the connection is closed.
+pub trait ParseErrorReply<Response> {
+    /// Returns a response to send to the client for a buffer which failed to
+    /// parse with the given error. Protocols which have no way to signal an
+    /// error to the client should return `None`.
+    fn parse_error_reply(&self, _buffer: &[u8], _error: &std::io::Error) -> Option<Response> {
+        None
+    }
 }
@@ -46,6 +46,34 @@ fuzz_target!(|data: &[u8]| {
                     validate_key(key);
                 }
             }
+            Request::GetDel(getdel) => {
+                validate_key(getdel.key());
+            }
+            Request::GetAndTouch(gat) => {
+                if gat.keys().is_empty() {
+                    panic!("no keys");
+                }
+                if gat.keys().len() > MAX_BATCH_SIZE {
+                    panic!("batch size exceeds max");
+                }
+                for key in gat.keys().iter() {
+                    validate_key(key);
+                }
+            }
+            Request::GetsAndTouch(gats) => {
+                if gats.keys().is_empty() {
+                    panic!("no keys");
+                }
+                if gats.keys().len() > MAX_BATCH_SIZE {
+                    panic!("batch size exceeds max");
+                }
+                for key in gats.keys().iter() {
+                    validate_key(key);
+                }
+            }
+            Request::Touch(touch) => {
+                validate_key(touch.key());
+            }
             Request::Set(set) => {
                 validate_key(set.key());
                 validate_value(set.value());
@@ -2,6 +2,14 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+//! A protocol crate for the memcache text protocol.
+//!
+//! Only the text protocol is implemented, there is no binary protocol and so
+//! no quiet opcodes such as `GetQ` or `SetQ`. Storage commands which take a
+//! `noreply` argument are the text protocol's equivalent, and their responses
+//! are composed as empty. Misses are already left out of the reply to `get`,
+//! which ends with `END` whether or not any keys were found.
+
 #[macro_use]
 extern crate logger;
 
@@ -44,12 +52,29 @@ counter!(GET_KEY);
 counter!(GET_KEY_HIT);
 counter!(GET_KEY_MISS);
 
+counter!(GETDEL);
+counter!(GETDEL_EX);
+counter!(GETDEL_KEY_HIT);
+counter!(GETDEL_KEY_MISS);
+
 counter!(GETS);
 counter!(GETS_EX);
 counter!(GETS_KEY);
 counter!(GETS_KEY_HIT);
 counter!(GETS_KEY_MISS);
 
+counter!(GAT);
+counter!(GAT_EX);
+counter!(GAT_KEY);
+counter!(GAT_KEY_HIT);
+counter!(GAT_KEY_MISS);
+
+counter!(GATS);
+counter!(GATS_EX);
+counter!(GATS_KEY);
+counter!(GATS_KEY_HIT);
+counter!(GATS_KEY_MISS);
+
 counter!(SET);
 counter!(SET_EX);
 counter!(SET_STORED);
@@ -80,6 +105,11 @@ counter!(DELETE_EX);
 counter!(DELETE_DELETED);
 counter!(DELETE_NOT_FOUND);
 
+counter!(TOUCH);
+counter!(TOUCH_EX);
+counter!(TOUCH_TOUCHED);
+counter!(TOUCH_NOT_FOUND);
+
 counter!(INCR);
 counter!(INCR_EX);
 counter!(INCR_STORED);
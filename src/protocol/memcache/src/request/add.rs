@@ -115,7 +115,11 @@ impl Klog for Add {
                 return;
             }
         };
-        klog!(
+        klog_access!(
+            "add",
+            self.key(),
+            code,
+            len,
             "\"add {} {} {} {}\" {} {}",
             string_key(self.key()),
             self.flags(),
@@ -115,7 +115,11 @@ impl Klog for Append {
                 return;
             }
         };
-        klog!(
+        klog_access!(
+            "append",
+            self.key(),
+            code,
+            len,
             "\"append {} {} {} {}\" {} {}",
             string_key(self.key()),
             self.flags(),
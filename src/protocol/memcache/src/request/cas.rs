@@ -78,9 +78,9 @@ impl RequestParser {
         }
 
         let (input, _) = space0(input)?;
-        let (input, _) = crlf(input)?;
-        let (input, value) = take(bytes)(input)?;
-        let (input, _) = crlf(input)?;
+        let (input, _) = self.line_end(input)?;
+        // the data block must always end with a CRLF
+        let (input, value) = self.data_block(input, bytes)?;
 
         Ok((
             input,
@@ -171,7 +171,11 @@ impl Klog for Cas {
                 return;
             }
         };
-        klog!(
+        klog_access!(
+            "cas",
+            self.key(),
+            code,
+            len,
             "\"cas {} {} {} {} {}\" {} {}",
             string_key(self.key()),
             self.flags(),
@@ -90,7 +90,16 @@ impl Klog for Decr {
                 return;
             }
         };
-        klog!("\"decr {}\" {} {}", string_key(self.key()), code, len);
+        klog_access!(
+            "decr",
+            self.key(),
+            code,
+            len,
+            "\"decr {}\" {} {}",
+            string_key(self.key()),
+            code,
+            len
+        );
     }
 }
 
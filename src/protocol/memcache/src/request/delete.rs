@@ -46,7 +46,7 @@ impl RequestParser {
 
         let (input, _) = space0(input)?;
 
-        let (input, _) = crlf(input)?;
+        let (input, _) = self.line_end(input)?;
         Ok((
             input,
             Delete {
@@ -110,7 +110,16 @@ impl Klog for Delete {
                 return;
             }
         };
-        klog!("\"delete {}\" {} {}", string_key(self.key()), code, len);
+        klog_access!(
+            "delete",
+            self.key(),
+            code,
+            len,
+            "\"delete {}\" {} {}",
+            string_key(self.key()),
+            code,
+            len
+        );
     }
 }
 
@@ -32,7 +32,7 @@ impl RequestParser {
         if let Ok((i, _)) = space1(input) {
             // we need to check to make sure we didn't stop because
             // of the CRLF
-            let (i, c) = take_till(|b| (b == b' ' || b == b'\r'))(i)?;
+            let (i, c) = take_till(|b| (b == b' ' || b == b'\r' || b == b'\n'))(i)?;
             if !c.is_empty() {
                 // make sure it's a valid string
                 let c = std::str::from_utf8(c)
@@ -58,7 +58,7 @@ impl RequestParser {
         }
 
         let (input, _) = space0(input)?;
-        let (input, _) = crlf(input)?;
+        let (input, _) = self.line_end(input)?;
 
         Ok((input, FlushAll { delay, noreply }))
     }
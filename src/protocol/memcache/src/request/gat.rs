@@ -0,0 +1,196 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+
+/// Returns the values stored for the keys, like `get`, and changes the TTL
+/// of each item which is found.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GetAndTouch {
+    pub(crate) ttl: Ttl,
+    pub(crate) keys: Box<[Box<[u8]>]>,
+}
+
+impl GetAndTouch {
+    pub fn ttl(&self) -> Ttl {
+        self.ttl
+    }
+
+    pub fn keys(&self) -> &[Box<[u8]>] {
+        self.keys.as_ref()
+    }
+}
+
+impl RequestParser {
+    // this is to be called after parsing the command, so we do not match the verb
+    pub(crate) fn parse_gat_no_stats<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], GetAndTouch> {
+        let (input, _) = space1(input)?;
+        let (input, ttl) = parse_ttl(input, self.time_type)?;
+
+        // the keys follow the ttl just as they follow the verb of a get
+        let (input, request) = self.parse_get_no_stats(input)?;
+
+        Ok((
+            input,
+            GetAndTouch {
+                ttl,
+                keys: request.keys,
+            },
+        ))
+    }
+
+    // this is to be called after parsing the command, so we do not match the verb
+    pub fn parse_gat<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], GetAndTouch> {
+        match self.parse_gat_no_stats(input) {
+            Ok((input, request)) => {
+                GAT.increment();
+                GAT_KEY.add(request.keys.len() as _);
+                Ok((input, request))
+            }
+            Err(e) => {
+                if !e.is_incomplete() {
+                    GAT.increment();
+                    GAT_EX.increment();
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+// composes a `gat` or `gats` request
+pub(crate) fn compose_gat(
+    session: &mut dyn BufMut,
+    verb: &[u8],
+    ttl: Ttl,
+    keys: &[Box<[u8]>],
+) -> usize {
+    let ttl = format!(" {}", ttl.get().unwrap_or(0)).into_bytes();
+
+    let mut size = verb.len() + ttl.len() + CRLF.len();
+
+    session.put_slice(verb);
+    session.put_slice(&ttl);
+    for key in keys.iter() {
+        session.put_slice(b" ");
+        session.put_slice(key);
+        size += 1 + key.len();
+    }
+    session.put_slice(CRLF);
+
+    size
+}
+
+impl Compose for GetAndTouch {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        compose_gat(session, b"gat", self.ttl, &self.keys)
+    }
+}
+
+impl Klog for GetAndTouch {
+    type Response = Response;
+
+    fn klog(&self, response: &Self::Response) {
+        if let Response::Values(ref res) = response {
+            let mut hit_keys = 0;
+            let mut miss_keys = 0;
+
+            for value in res.values() {
+                if value.len().is_none() {
+                    miss_keys += 1;
+
+                    klog_access!(
+                        "gat",
+                        value.key(),
+                        MISS,
+                        0,
+                        "\"gat {} {}\" {} 0",
+                        self.ttl.get().unwrap_or(0),
+                        String::from_utf8_lossy(value.key()),
+                        MISS
+                    );
+                } else {
+                    hit_keys += 1;
+
+                    klog_access!(
+                        "gat",
+                        value.key(),
+                        HIT,
+                        value.len().unwrap(),
+                        "\"gat {} {}\" {} {}",
+                        self.ttl.get().unwrap_or(0),
+                        String::from_utf8_lossy(value.key()),
+                        HIT,
+                        value.len().unwrap(),
+                    );
+                }
+            }
+
+            GAT_KEY_HIT.add(hit_keys as _);
+            GAT_KEY_MISS.add(miss_keys as _);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parser = RequestParser::new();
+
+        // basic gat command
+        assert_eq!(
+            parser.parse_request(b"gat 60 key\r\n"),
+            Ok((
+                &b""[..],
+                Request::GetAndTouch(GetAndTouch {
+                    ttl: Ttl::new(60, TimeType::Memcache),
+                    keys: vec![b"key".to_vec().into_boxed_slice()].into_boxed_slice(),
+                })
+            ))
+        );
+
+        // command name is not case sensitive, and trailing spaces don't matter
+        assert_eq!(
+            parser.parse_request(b"gat 60 key\r\n"),
+            parser.parse_request(b"GAT 60 key  \r\n"),
+        );
+
+        // request can have multiple keys
+        assert_eq!(
+            parser.parse_request(b"gat 0 a b\r\n"),
+            Ok((
+                &b""[..],
+                Request::GetAndTouch(GetAndTouch {
+                    ttl: Ttl::none(),
+                    keys: vec![
+                        b"a".to_vec().into_boxed_slice(),
+                        b"b".to_vec().into_boxed_slice(),
+                    ]
+                    .into_boxed_slice(),
+                })
+            ))
+        );
+
+        // the ttl and at least one key are required
+        assert!(parser.parse_request(b"gat\r\n").is_err());
+        assert!(parser.parse_request(b"gat 60\r\n").is_err());
+        assert!(parser.parse_request(b"gat 60 \r\n").is_err());
+        assert!(parser.parse_request(b"gat key\r\n").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let parser = RequestParser::new();
+
+        for request in [&b"gat 60 key\r\n"[..], b"gat 0 a b c\r\n"] {
+            let (_, parsed) = parser.parse_request(request).unwrap();
+            let mut buffer = Vec::new();
+            assert_eq!(parsed.compose(&mut buffer), request.len());
+            assert_eq!(buffer, request);
+        }
+    }
+}
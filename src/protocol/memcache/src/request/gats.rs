@@ -0,0 +1,145 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+
+/// Returns the values stored for the keys with their CAS values, like
+/// `gets`, and changes the TTL of each item which is found.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GetsAndTouch {
+    pub(crate) ttl: Ttl,
+    pub(crate) keys: Box<[Box<[u8]>]>,
+}
+
+impl GetsAndTouch {
+    pub fn ttl(&self) -> Ttl {
+        self.ttl
+    }
+
+    pub fn keys(&self) -> &[Box<[u8]>] {
+        self.keys.as_ref()
+    }
+}
+
+impl RequestParser {
+    // this is to be called after parsing the command, so we do not match the verb
+    pub(crate) fn parse_gats<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], GetsAndTouch> {
+        // we can use the gat parser here and convert the request
+        match self.parse_gat_no_stats(input) {
+            Ok((input, request)) => {
+                GATS.increment();
+                GATS_KEY.add(request.keys.len() as _);
+                Ok((
+                    input,
+                    GetsAndTouch {
+                        ttl: request.ttl,
+                        keys: request.keys,
+                    },
+                ))
+            }
+            Err(e) => {
+                if !e.is_incomplete() {
+                    GATS.increment();
+                    GATS_EX.increment();
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Compose for GetsAndTouch {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        super::gat::compose_gat(session, b"gats", self.ttl, &self.keys)
+    }
+}
+
+impl Klog for GetsAndTouch {
+    type Response = Response;
+
+    fn klog(&self, response: &Self::Response) {
+        if let Response::Values(ref res) = response {
+            let mut hit_keys = 0;
+            let mut miss_keys = 0;
+
+            for value in res.values() {
+                if value.len().is_none() {
+                    miss_keys += 1;
+
+                    klog_access!(
+                        "gats",
+                        value.key(),
+                        MISS,
+                        0,
+                        "\"gats {} {}\" {} 0",
+                        self.ttl.get().unwrap_or(0),
+                        String::from_utf8_lossy(value.key()),
+                        MISS
+                    );
+                } else {
+                    hit_keys += 1;
+
+                    klog_access!(
+                        "gats",
+                        value.key(),
+                        HIT,
+                        value.len().unwrap(),
+                        "\"gats {} {}\" {} {}",
+                        self.ttl.get().unwrap_or(0),
+                        String::from_utf8_lossy(value.key()),
+                        HIT,
+                        value.len().unwrap(),
+                    );
+                }
+            }
+
+            GATS_KEY_HIT.add(hit_keys as _);
+            GATS_KEY_MISS.add(miss_keys as _);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parser = RequestParser::new();
+
+        // basic gats command
+        assert_eq!(
+            parser.parse_request(b"gats 60 key\r\n"),
+            Ok((
+                &b""[..],
+                Request::GetsAndTouch(GetsAndTouch {
+                    ttl: Ttl::new(60, TimeType::Memcache),
+                    keys: vec![b"key".to_vec().into_boxed_slice()].into_boxed_slice(),
+                })
+            ))
+        );
+
+        // command name is not case sensitive
+        assert_eq!(
+            parser.parse_request(b"gats 60 a b\r\n"),
+            parser.parse_request(b"GATS 60 a b\r\n"),
+        );
+
+        // the ttl and at least one key are required
+        assert!(parser.parse_request(b"gats\r\n").is_err());
+        assert!(parser.parse_request(b"gats 60\r\n").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let parser = RequestParser::new();
+
+        for request in [&b"gats 60 key\r\n"[..], b"gats 0 a b c\r\n"] {
+            let (_, parsed) = parser.parse_request(request).unwrap();
+            let mut buffer = Vec::new();
+            assert_eq!(parsed.compose(&mut buffer), request.len());
+            assert_eq!(buffer, request);
+        }
+    }
+}
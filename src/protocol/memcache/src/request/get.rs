@@ -51,7 +51,7 @@ impl RequestParser {
         }
 
         let (input, _) = space0(input)?;
-        let (input, _) = crlf(input)?;
+        let (input, _) = self.line_end(input)?;
         Ok((
             input,
             Get {
@@ -111,7 +111,11 @@ impl Klog for Get {
                 if value.len().is_none() {
                     miss_keys += 1;
 
-                    klog!(
+                    klog_access!(
+                        "get",
+                        value.key(),
+                        MISS,
+                        0,
                         "\"get {}\" {} 0",
                         String::from_utf8_lossy(value.key()),
                         MISS
@@ -119,7 +123,11 @@ impl Klog for Get {
                 } else {
                     hit_keys += 1;
 
-                    klog!(
+                    klog_access!(
+                        "get",
+                        value.key(),
+                        HIT,
+                        value.len().unwrap(),
                         "\"get {}\" {} {}",
                         String::from_utf8_lossy(value.key()),
                         HIT,
@@ -0,0 +1,161 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+
+/// Returns the value stored for a single key and deletes it, in one atomic
+/// step. This is a Pelikan extension which isn't part of the memcache
+/// protocol, and is useful for one-shot tokens which must be read only once.
+///
+/// The response is the same as for a `get` of the key, so a miss is just
+/// `END`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GetDel {
+    pub(crate) key: Box<[u8]>,
+}
+
+impl GetDel {
+    pub fn key(&self) -> &[u8] {
+        self.key.as_ref()
+    }
+}
+
+impl RequestParser {
+    // this is to be called after parsing the command, so we do not match the verb
+    pub(crate) fn parse_getdel_no_stats<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], GetDel> {
+        let (input, _) = space1(input)?;
+
+        let (input, key) = key(input, self.max_key_len)?;
+
+        let key = match key {
+            Some(k) => k,
+            None => {
+                return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+            }
+        };
+
+        let (input, _) = space0(input)?;
+        let (input, _) = self.line_end(input)?;
+        Ok((
+            input,
+            GetDel {
+                key: key.to_owned().into_boxed_slice(),
+            },
+        ))
+    }
+
+    // this is to be called after parsing the command, so we do not match the verb
+    pub fn parse_getdel<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], GetDel> {
+        match self.parse_getdel_no_stats(input) {
+            Ok((input, request)) => {
+                GETDEL.increment();
+                Ok((input, request))
+            }
+            Err(e) => {
+                if !e.is_incomplete() {
+                    GETDEL.increment();
+                    GETDEL_EX.increment();
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Compose for GetDel {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        let verb = b"getdel ";
+
+        let size = verb.len() + self.key.len() + CRLF.len();
+
+        session.put_slice(verb);
+        session.put_slice(&self.key);
+        session.put_slice(CRLF);
+
+        size
+    }
+}
+
+impl Klog for GetDel {
+    type Response = Response;
+
+    fn klog(&self, response: &Self::Response) {
+        if let Response::Values(ref res) = response {
+            match res.values().first().and_then(|value| value.len()) {
+                Some(len) => {
+                    GETDEL_KEY_HIT.increment();
+                    klog_access!(
+                        "getdel",
+                        self.key(),
+                        HIT,
+                        len,
+                        "\"getdel {}\" {} {}",
+                        string_key(self.key()),
+                        HIT,
+                        len
+                    );
+                }
+                None => {
+                    GETDEL_KEY_MISS.increment();
+                    klog_access!(
+                        "getdel",
+                        self.key(),
+                        MISS,
+                        0,
+                        "\"getdel {}\" {} 0",
+                        string_key(self.key()),
+                        MISS
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parser = RequestParser::new();
+
+        // basic getdel command
+        assert_eq!(
+            parser.parse_request(b"getdel key\r\n"),
+            Ok((
+                &b""[..],
+                Request::GetDel(GetDel {
+                    key: b"key".to_vec().into_boxed_slice(),
+                })
+            ))
+        );
+
+        // command name is not case sensitive and trailing spaces don't matter
+        assert_eq!(
+            parser.parse_request(b"getdel key\r\n"),
+            parser.parse_request(b"GETDEL key  \r\n"),
+        );
+
+        // exactly one key is required
+        assert!(parser.parse_request(b"getdel\r\n").is_err());
+        assert!(parser.parse_request(b"getdel a b\r\n").is_err());
+
+        // an incomplete request needs more data
+        assert!(matches!(
+            parser.parse_request(b"getdel key"),
+            Err(Err::Incomplete(_))
+        ));
+    }
+
+    #[test]
+    fn compose() {
+        let request = GetDel {
+            key: b"key".to_vec().into_boxed_slice(),
+        };
+        let mut buf = Vec::new();
+        assert_eq!(request.compose(&mut buf), 12);
+        assert_eq!(buf, b"getdel key\r\n");
+    }
+}
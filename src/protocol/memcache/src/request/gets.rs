@@ -67,7 +67,11 @@ impl Klog for Gets {
                 if value.len().is_none() {
                     miss_keys += 1;
 
-                    klog!(
+                    klog_access!(
+                        "gets",
+                        value.key(),
+                        MISS,
+                        0,
                         "\"gets {}\" {} 0",
                         String::from_utf8_lossy(value.key()),
                         MISS
@@ -75,7 +79,11 @@ impl Klog for Gets {
                 } else {
                     hit_keys += 1;
 
-                    klog!(
+                    klog_access!(
+                        "gets",
+                        value.key(),
+                        HIT,
+                        value.len().unwrap(),
                         "\"gets {}\" {} {}",
                         String::from_utf8_lossy(value.key()),
                         HIT,
@@ -52,7 +52,7 @@ impl RequestParser {
         }
 
         let (input, _) = space0(input)?;
-        let (input, _) = crlf(input)?;
+        let (input, _) = self.line_end(input)?;
 
         Ok((
             input,
@@ -119,7 +119,16 @@ impl Klog for Incr {
                 return;
             }
         };
-        klog!("\"incr {}\" {} {}", string_key(self.key()), code, len);
+        klog_access!(
+            "incr",
+            self.key(),
+            code,
+            len,
+            "\"incr {}\" {} {}",
+            string_key(self.key()),
+            code,
+            len
+        );
     }
 }
 
@@ -6,7 +6,10 @@ use crate::*;
 use common::expiry::TimeType;
 use core::fmt::{Display, Formatter};
 use core::num::NonZeroI32;
-use protocol_common::{BufMut, Parse, ParseOk};
+use protocol_common::{
+    BufMut, KeyEvent, KeyEventKind, KeyspaceEvents, KeyspaceNotifier, Parse, ParseErrorReply,
+    ParseOk, ReadOnlyMode,
+};
 use std::borrow::Cow;
 
 mod add;
@@ -15,13 +18,17 @@ mod cas;
 mod decr;
 mod delete;
 mod flush_all;
+mod gat;
+mod gats;
 mod get;
+mod getdel;
 mod gets;
 mod incr;
 mod prepend;
 mod quit;
 mod replace;
 mod set;
+mod touch;
 
 pub use add::Add;
 pub use append::Append;
@@ -29,17 +36,22 @@ pub use cas::Cas;
 pub use decr::Decr;
 pub use delete::Delete;
 pub use flush_all::FlushAll;
+pub use gat::GetAndTouch;
+pub use gats::GetsAndTouch;
 pub use get::Get;
+pub use getdel::GetDel;
 pub use gets::Gets;
 pub use incr::Incr;
 pub use prepend::Prepend;
 pub use quit::Quit;
 pub use replace::Replace;
 pub use set::Set;
+pub use touch::Touch;
 
 pub const DEFAULT_MAX_BATCH_SIZE: usize = 1024;
 pub const DEFAULT_MAX_KEY_LEN: usize = 250;
 pub const DEFAULT_MAX_VALUE_SIZE: usize = 512 * 1024 * 1024; // 512MB max value size
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 512 * 1024; // fits a full batch of max length keys
 
 // response codes for klog
 const MISS: u8 = 0;
@@ -49,6 +61,7 @@ const EXISTS: u8 = 6;
 const DELETED: u8 = 7;
 const NOT_FOUND: u8 = 8;
 const NOT_STORED: u8 = 9;
+const TOUCHED: u8 = 10;
 
 fn string_key(key: &[u8]) -> Cow<'_, str> {
     String::from_utf8_lossy(key)
@@ -59,7 +72,9 @@ pub struct RequestParser {
     max_value_size: usize,
     max_batch_size: usize,
     max_key_len: usize,
+    max_line_length: usize,
     time_type: TimeType,
+    lenient_newlines: bool,
 }
 
 impl RequestParser {
@@ -87,8 +102,65 @@ impl RequestParser {
         self
     }
 
+    /// The longest command line, not counting its terminator, which will be
+    /// accepted. A client which sends more than this without ending the line
+    /// gets an error instead of the line being buffered until it ends.
+    pub fn max_line_length(mut self, bytes: usize) -> Self {
+        self.max_line_length = bytes;
+        self
+    }
+
+    /// Accept a bare LF, as well as a CRLF, at the end of a command line, as
+    /// memcached does. This is off by default. The data block of a storage
+    /// command must still be followed by a CRLF.
+    pub fn lenient_newlines(mut self, enabled: bool) -> Self {
+        self.lenient_newlines = enabled;
+        self
+    }
+
+    // checks whether the command line at the start of the buffer is longer
+    // than the max line length. a line without a terminator is too long once
+    // the buffer holds more than the longest line and its CRLF
+    fn line_too_long(&self, buffer: &[u8]) -> bool {
+        let limit = self.max_line_length.saturating_add(CRLF.len());
+        match buffer.iter().take(limit).position(|b| *b == b'\n') {
+            Some(end) => {
+                let len = if end > 0 && buffer[end - 1] == b'\r' {
+                    end - 1
+                } else {
+                    end
+                };
+                len > self.max_line_length
+            }
+            None => buffer.len() >= limit,
+        }
+    }
+
+    // consumes the end of a command line
+    fn line_end<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+        if self.lenient_newlines && input.first() == Some(&b'\n') {
+            return Ok((&input[1..], &input[..1]));
+        }
+        crlf(input)
+    }
+
+    // consumes the data block of a storage command, which must be exactly
+    // `bytes` long and followed by a CRLF. anything else in place of the CRLF
+    // means the declared length doesn't match the data, which is a failure
+    // that is reported to the client as a bad data chunk
+    fn data_block<'a>(&self, input: &'a [u8], bytes: usize) -> IResult<&'a [u8], &'a [u8]> {
+        let (input, value) = take(bytes)(input)?;
+        let terminator: IResult<&[u8], &[u8]> = crlf(input);
+        match terminator {
+            Ok((input, _)) => Ok((input, value)),
+            Err(Err::Incomplete(needed)) => Err(Err::Incomplete(needed)),
+            Err(_) => Err(Err::Failure((input, ErrorKind::CrLf))),
+        }
+    }
+
     fn parse_command<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Command> {
-        let (remaining, command_bytes) = take_till(|b| (b == b' ' || b == b'\r'))(input)?;
+        let (remaining, command_bytes) =
+            take_till(|b| (b == b' ' || b == b'\r' || b == b'\n'))(input)?;
         let command = match command_bytes {
             b"add" | b"ADD" => Command::Add,
             b"append" | b"APPEND" => Command::Append,
@@ -96,13 +168,17 @@ impl RequestParser {
             b"decr" | b"DECR" => Command::Decr,
             b"delete" | b"DELETE" => Command::Delete,
             b"flush_all" | b"FLUSH_ALL" => Command::FlushAll,
+            b"gat" | b"GAT" => Command::GetAndTouch,
+            b"gats" | b"GATS" => Command::GetsAndTouch,
             b"incr" | b"INCR" => Command::Incr,
             b"get" | b"GET" => Command::Get,
+            b"getdel" | b"GETDEL" => Command::GetDel,
             b"gets" | b"GETS" => Command::Gets,
             b"prepend" | b"PREPEND" => Command::Prepend,
             b"quit" | b"QUIT" => Command::Quit,
             b"replace" | b"REPLACE" => Command::Replace,
             b"set" | b"SET" => Command::Set,
+            b"touch" | b"TOUCH" => Command::Touch,
             _ => {
                 // TODO(bmartin): we can return an unknown command error here
                 return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
@@ -137,6 +213,14 @@ impl RequestParser {
                 let (input, request) = self.parse_flush_all(input)?;
                 Ok((input, Request::FlushAll(request)))
             }
+            (input, Command::GetAndTouch) => {
+                let (input, request) = self.parse_gat(input)?;
+                Ok((input, Request::GetAndTouch(request)))
+            }
+            (input, Command::GetsAndTouch) => {
+                let (input, request) = self.parse_gats(input)?;
+                Ok((input, Request::GetsAndTouch(request)))
+            }
             (input, Command::Incr) => {
                 let (input, request) = self.parse_incr(input)?;
                 Ok((input, Request::Incr(request)))
@@ -145,6 +229,10 @@ impl RequestParser {
                 let (input, request) = self.parse_get(input)?;
                 Ok((input, Request::Get(request)))
             }
+            (input, Command::GetDel) => {
+                let (input, request) = self.parse_getdel(input)?;
+                Ok((input, Request::GetDel(request)))
+            }
             (input, Command::Gets) => {
                 let (input, request) = self.parse_gets(input)?;
                 Ok((input, Request::Gets(request)))
@@ -165,6 +253,10 @@ impl RequestParser {
                 let (input, request) = self.parse_set(input)?;
                 Ok((input, Request::Set(request)))
             }
+            (input, Command::Touch) => {
+                let (input, request) = self.parse_touch(input)?;
+                Ok((input, Request::Touch(request)))
+            }
         }
     }
 }
@@ -175,21 +267,51 @@ impl Default for RequestParser {
             max_value_size: DEFAULT_MAX_VALUE_SIZE,
             max_batch_size: DEFAULT_MAX_BATCH_SIZE,
             max_key_len: DEFAULT_MAX_KEY_LEN,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
             time_type: TimeType::Memcache,
+            lenient_newlines: false,
         }
     }
 }
 
 impl Parse<Request> for RequestParser {
     fn parse(&self, buffer: &[u8]) -> Result<ParseOk<Request>, std::io::Error> {
+        if self.line_too_long(buffer) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "line too long",
+            ));
+        }
+
         match self.parse_request(buffer) {
             Ok((input, request)) => Ok(ParseOk::new(request, buffer.len() - input.len())),
             Err(Err::Incomplete(_)) => Err(std::io::Error::from(std::io::ErrorKind::WouldBlock)),
+            Err(Err::Failure((_, ErrorKind::CrLf))) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "bad data chunk",
+            )),
             Err(_) => Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
         }
     }
 }
 
+impl ParseErrorReply<Response> for RequestParser {
+    fn parse_error_reply(&self, buffer: &[u8], error: &std::io::Error) -> Option<Response> {
+        match error.kind() {
+            std::io::ErrorKind::InvalidData => {
+                if self.line_too_long(buffer) {
+                    Some(Response::client_error("line too long"))
+                } else {
+                    Some(Response::client_error("bad data chunk"))
+                }
+            }
+            // a session holding a large request which isn't completing
+            std::io::ErrorKind::TimedOut => Some(Response::client_error("request stalled")),
+            _ => None,
+        }
+    }
+}
+
 impl Compose for Request {
     fn compose(&self, session: &mut dyn BufMut) -> usize {
         match self {
@@ -199,13 +321,17 @@ impl Compose for Request {
             Self::Decr(r) => r.compose(session),
             Self::Delete(r) => r.compose(session),
             Self::FlushAll(r) => r.compose(session),
+            Self::GetAndTouch(r) => r.compose(session),
+            Self::GetsAndTouch(r) => r.compose(session),
             Self::Incr(r) => r.compose(session),
             Self::Get(r) => r.compose(session),
+            Self::GetDel(r) => r.compose(session),
             Self::Gets(r) => r.compose(session),
             Self::Prepend(r) => r.compose(session),
             Self::Quit(r) => r.compose(session),
             Self::Replace(r) => r.compose(session),
             Self::Set(r) => r.compose(session),
+            Self::Touch(r) => r.compose(session),
         }
     }
 }
@@ -221,13 +347,17 @@ impl Klog for Request {
             Self::Decr(r) => r.klog(response),
             Self::Delete(r) => r.klog(response),
             Self::FlushAll(r) => r.klog(response),
+            Self::GetAndTouch(r) => r.klog(response),
+            Self::GetsAndTouch(r) => r.klog(response),
             Self::Incr(r) => r.klog(response),
             Self::Get(r) => r.klog(response),
+            Self::GetDel(r) => r.klog(response),
             Self::Gets(r) => r.klog(response),
             Self::Prepend(r) => r.klog(response),
             Self::Quit(r) => r.klog(response),
             Self::Replace(r) => r.klog(response),
             Self::Set(r) => r.klog(response),
+            Self::Touch(r) => r.klog(response),
         }
     }
 }
@@ -240,13 +370,17 @@ pub enum Request {
     Decr(Decr),
     Delete(Delete),
     FlushAll(FlushAll),
+    GetAndTouch(GetAndTouch),
+    GetsAndTouch(GetsAndTouch),
     Incr(Incr),
     Get(Get),
+    GetDel(GetDel),
     Gets(Gets),
     Prepend(Prepend),
     Quit(Quit),
     Replace(Replace),
     Set(Set),
+    Touch(Touch),
 }
 
 impl Display for Request {
@@ -258,17 +392,64 @@ impl Display for Request {
             Request::Decr(_) => write!(f, "decr"),
             Request::Delete(_) => write!(f, "delete"),
             Request::FlushAll(_) => write!(f, "flush_all"),
+            Request::GetAndTouch(_) => write!(f, "gat"),
+            Request::GetsAndTouch(_) => write!(f, "gats"),
             Request::Incr(_) => write!(f, "incr"),
             Request::Get(_) => write!(f, "get"),
+            Request::GetDel(_) => write!(f, "getdel"),
             Request::Gets(_) => write!(f, "gets"),
             Request::Prepend(_) => write!(f, "prepend"),
             Request::Quit(_) => write!(f, "quit"),
             Request::Replace(_) => write!(f, "replace"),
             Request::Set(_) => write!(f, "set"),
+            Request::Touch(_) => write!(f, "touch"),
+        }
+    }
+}
+
+impl ReadOnlyMode<Response> for Request {
+    fn read_only_error(&self) -> Option<Response> {
+        match self {
+            Self::Get(_) | Self::Gets(_) | Self::Quit(_) => None,
+            _ => Some(Response::server_error("server is in read-only mode")),
         }
     }
 }
 
+impl KeyspaceEvents<Response> for Request {
+    fn keyspace_events(&self, response: &Response, notifier: &mut dyn KeyspaceNotifier) {
+        let (key, kind) = match (self, response) {
+            (Self::Add(r), Response::Stored(_)) => (r.key(), KeyEventKind::Set),
+            (Self::Append(r), Response::Stored(_)) => (r.key(), KeyEventKind::Set),
+            (Self::Cas(r), Response::Stored(_)) => (r.key(), KeyEventKind::Set),
+            (Self::Decr(r), Response::Numeric(_)) => (r.key(), KeyEventKind::Set),
+            (Self::Incr(r), Response::Numeric(_)) => (r.key(), KeyEventKind::Set),
+            (Self::Prepend(r), Response::Stored(_)) => (r.key(), KeyEventKind::Set),
+            (Self::Replace(r), Response::Stored(_)) => (r.key(), KeyEventKind::Set),
+            (Self::Set(r), Response::Stored(_)) => (r.key(), KeyEventKind::Set),
+            (Self::Delete(r), Response::Deleted(_)) => (r.key(), KeyEventKind::Delete),
+            (Self::GetDel(r), Response::Values(values)) => {
+                if values.values().first().and_then(|v| v.len()).is_none() {
+                    return;
+                }
+                (r.key(), KeyEventKind::Delete)
+            }
+            (Self::Touch(r), Response::Touched(_)) => (r.key(), KeyEventKind::Touch),
+            // each key which was found has had its ttl changed
+            (Self::GetAndTouch(_) | Self::GetsAndTouch(_), Response::Values(values)) => {
+                for value in values.values().iter().filter(|v| v.len().is_some()) {
+                    notifier.notify(KeyEvent::new(value.key(), KeyEventKind::Touch));
+                }
+                return;
+            }
+            _ => {
+                return;
+            }
+        };
+        notifier.notify(KeyEvent::new(key, kind));
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Command {
     Add,
@@ -277,13 +458,17 @@ pub enum Command {
     Decr,
     Delete,
     FlushAll,
+    GetAndTouch,
+    GetsAndTouch,
     Incr,
     Get,
+    GetDel,
     Gets,
     Prepend,
     Quit,
     Replace,
     Set,
+    Touch,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -413,4 +598,135 @@ mod tests {
             Ok((&b" key \"value\"\r\n"[..], Command::Set))
         );
     }
+
+    #[test]
+    fn bare_newline() {
+        // a bare LF is an error by default
+        let parser = RequestParser::new();
+        assert!(matches!(
+            parser.parse_request(b"get key\n"),
+            Err(Err::Error(_))
+        ));
+        assert!(parser.parse_request(b"quit\n").is_err());
+
+        // and ends the command line when lenient
+        let parser = RequestParser::new().lenient_newlines(true);
+        assert_eq!(
+            parser.parse_request(b"get a b\nget c\r\n"),
+            Ok((
+                &b"get c\r\n"[..],
+                Request::Get(Get {
+                    keys: vec![
+                        b"a".to_vec().into_boxed_slice(),
+                        b"b".to_vec().into_boxed_slice()
+                    ]
+                    .into_boxed_slice(),
+                })
+            ))
+        );
+        assert_eq!(
+            parser.parse_request(b"delete key noreply \n"),
+            parser.parse_request(b"delete key noreply\r\n"),
+        );
+        assert_eq!(
+            parser.parse_request(b"quit\n"),
+            Ok((&b""[..], Request::Quit(Quit {})))
+        );
+
+        // the data block of a storage command still needs a CRLF
+        assert_eq!(
+            parser.parse_request(b"set key 0 0 1\nv\r\n"),
+            parser.parse_request(b"set key 0 0 1\r\nv\r\n"),
+        );
+        assert!(parser.parse_request(b"set key 0 0 1\nv\n").is_err());
+        assert!(parser.parse_request(b"cas key 0 0 1 7\nv\n").is_err());
+
+        // an incomplete command line still needs more data
+        assert!(matches!(
+            parser.parse_request(b"get key"),
+            Err(Err::Incomplete(_))
+        ));
+    }
+
+    #[test]
+    fn bad_data_chunk() {
+        let parser = RequestParser::new();
+
+        // a well-formed data block is accepted
+        assert!(parser.parse(b"set key 0 0 5\r\nvalue\r\n").is_ok());
+
+        // a data block missing its terminator, or with a length which doesn't
+        // match the data, is a bad data chunk
+        for buffer in [
+            &b"set key 0 0 5\r\nvalueXX"[..],
+            &b"set key 0 0 5\r\nvalue\nget key\r\n"[..],
+            &b"set key 0 0 3\r\nvalue\r\n"[..],
+            &b"set key 0 0 5\r\nabc\r\nget key\r\n"[..],
+            &b"cas key 0 0 3 1\r\nvalue\r\n"[..],
+        ] {
+            let error = parser.parse(buffer).map(|_| ()).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(
+                parser.parse_error_reply(buffer, &error),
+                Some(Response::client_error("bad data chunk"))
+            );
+        }
+
+        // a data block which is longer than the data so far needs more data
+        let error = parser
+            .parse(b"set key 0 0 6\r\nabc\r\n")
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::WouldBlock);
+
+        // other parse errors have no reply
+        let error = parser.parse(b"bogus\r\n").map(|_| ()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(parser.parse_error_reply(b"bogus\r\n", &error), None);
+
+        // a stalled request, which the session detects rather than the
+        // parser, is reported to the client
+        let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "request stalled");
+        assert_eq!(
+            parser.parse_error_reply(b"set key 0 0 1000000\r\n", &error),
+            Some(Response::client_error("request stalled"))
+        );
+    }
+
+    #[test]
+    fn max_line_length() {
+        let parser = RequestParser::new().max_line_length(16);
+
+        // a line right at the limit is accepted, and the connection carries
+        // on with the next request
+        let buffer = b"get aaaaa bbbbbb\r\nget c\r\n";
+        let parsed = parser.parse(buffer).unwrap();
+        assert_eq!(parsed.consumed(), 18);
+        assert!(parser.parse(&buffer[parsed.consumed()..]).is_ok());
+
+        // the data block of a storage command doesn't count toward the line
+        assert!(parser
+            .parse(b"set k 0 0 32\r\naaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n")
+            .is_ok());
+
+        // a line which doesn't fit is an error, whether or not it has ended
+        for buffer in [
+            &b"get aaaaa bbbbbbb\r\n"[..],
+            &b"get aaaaa bbbbbbbb"[..],
+            &b"get aaaaa bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"[..],
+        ] {
+            let error = parser.parse(buffer).map(|_| ()).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(
+                parser.parse_error_reply(buffer, &error),
+                Some(Response::client_error("line too long"))
+            );
+        }
+
+        // until then, an unterminated line just needs more data
+        let error = parser.parse(b"get aaaaa bbbbbb").map(|_| ()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::WouldBlock);
+        let error = parser.parse(b"get aaaaa bbbbbb\r").map(|_| ()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::WouldBlock);
+    }
 }
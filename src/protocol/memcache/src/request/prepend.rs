@@ -115,7 +115,11 @@ impl Klog for Prepend {
                 return;
             }
         };
-        klog!(
+        klog_access!(
+            "prepend",
+            self.key(),
+            code,
+            len,
             "\"prepend {} {} {} {}\" {} {}",
             string_key(self.key()),
             self.flags(),
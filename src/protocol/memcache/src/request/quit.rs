@@ -13,7 +13,7 @@ impl RequestParser {
     // this is to be called after parsing the command, so we do not match the verb
     pub fn parse_quit<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Quit> {
         let (input, _) = space0(input)?;
-        let (input, _) = crlf(input)?;
+        let (input, _) = self.line_end(input)?;
 
         QUIT.increment();
 
@@ -115,7 +115,11 @@ impl Klog for Replace {
                 return;
             }
         };
-        klog!(
+        klog_access!(
+            "replace",
+            self.key(),
+            code,
+            len,
             "\"replace {} {} {} {}\" {} {}",
             string_key(self.key()),
             self.flags(),
@@ -70,9 +70,9 @@ impl RequestParser {
         }
 
         let (input, _) = space0(input)?;
-        let (input, _) = crlf(input)?;
-        let (input, value) = take(bytes)(input)?;
-        let (input, _) = crlf(input)?;
+        let (input, _) = self.line_end(input)?;
+        // the data block must always end with a CRLF
+        let (input, value) = self.data_block(input, bytes)?;
 
         Ok((
             input,
@@ -154,7 +154,11 @@ impl Klog for Set {
                 return;
             }
         };
-        klog!(
+        klog_access!(
+            "set",
+            self.key(),
+            code,
+            len,
             "\"set {} {} {} {}\" {} {}",
             string_key(self.key()),
             self.flags(),
@@ -203,5 +207,20 @@ mod tests {
                 })
             ))
         );
+
+        // zero-length value
+        assert_eq!(
+            parser.parse_request(b"set 0 0 0 0\r\n\r\n"),
+            Ok((
+                &b""[..],
+                Request::Set(Set {
+                    key: b"0".to_vec().into_boxed_slice(),
+                    value: b"".to_vec().into_boxed_slice(),
+                    flags: 0,
+                    ttl: Ttl::none(),
+                    noreply: false,
+                })
+            ))
+        );
     }
 }
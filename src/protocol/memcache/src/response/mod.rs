@@ -14,6 +14,7 @@ mod not_stored;
 mod numeric;
 mod server_error;
 mod stored;
+mod touched;
 mod values;
 
 pub use client_error::ClientError;
@@ -25,6 +26,7 @@ pub use not_stored::NotStored;
 pub use numeric::Numeric;
 pub use server_error::ServerError;
 pub use stored::Stored;
+pub use touched::Touched;
 pub use values::{Value, Values};
 
 #[derive(Debug, PartialEq, Eq)]
@@ -39,6 +41,8 @@ pub enum Response {
     Values(Values),
     Numeric(Numeric),
     Deleted(Deleted),
+    Touched(Touched),
+    /// An empty response, for a request which closes the connection
     Hangup,
 }
 
@@ -90,6 +94,10 @@ impl Response {
     pub fn deleted(noreply: bool) -> Self {
         Self::Deleted(Deleted::new(noreply))
     }
+
+    pub fn touched(noreply: bool) -> Self {
+        Self::Touched(Touched::new(noreply))
+    }
 }
 
 impl From<Values> for Response {
@@ -111,12 +119,13 @@ impl Compose for Response {
             Self::Values(e) => e.compose(session),
             Self::Numeric(e) => e.compose(session),
             Self::Deleted(e) => e.compose(session),
+            Self::Touched(e) => e.compose(session),
             Self::Hangup => 0,
         }
     }
 
     fn should_hangup(&self) -> bool {
-        matches!(self, Self::Error(_) | Self::ClientError(_) | Self::Hangup)
+        matches!(self, Self::Error(_) | Self::ClientError(_))
     }
 }
 
@@ -133,6 +142,7 @@ pub enum ResponseType {
     Empty,
     Numeric(u64),
     Deleted,
+    Touched,
 }
 
 pub struct ResponseParser {}
@@ -150,6 +160,7 @@ pub(crate) fn response_type(input: &[u8]) -> IResult<&[u8], ResponseType> {
         b"VALUE" => ResponseType::Values,
         b"END" => ResponseType::Empty,
         b"DELETED" => ResponseType::Deleted,
+        b"TOUCHED" => ResponseType::Touched,
         _ => {
             if let Ok(s) = std::str::from_utf8(response_type_token) {
                 if let Ok(value) = s.parse::<u64>() {
@@ -219,6 +230,10 @@ pub(crate) fn response(input: &[u8]) -> IResult<&[u8], Response> {
             let (input, response) = deleted::parse(input)?;
             Ok((input, Response::Deleted(response)))
         }
+        (input, ResponseType::Touched) => {
+            let (input, response) = touched::parse(input)?;
+            Ok((input, Response::Touched(response)))
+        }
     }
 }
 
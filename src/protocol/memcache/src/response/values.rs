@@ -228,4 +228,18 @@ mod tests {
             Ok((&b""[..], Response::values(vec![].into_boxed_slice()),))
         );
     }
+
+    #[test]
+    fn compose() {
+        // a zero-length value is present and must be distinct from a miss
+        let mut buf = Vec::new();
+        let values = Response::values(vec![Value::new(b"0", 0, None, b"")].into_boxed_slice());
+        assert_eq!(values.compose(&mut buf), 20);
+        assert_eq!(&buf, b"VALUE 0 0 0\r\n\r\nEND\r\n");
+
+        let mut buf = Vec::new();
+        let values = Response::values(vec![Value::none(b"0")].into_boxed_slice());
+        assert_eq!(values.compose(&mut buf), 5);
+        assert_eq!(&buf, b"END\r\n");
+    }
 }
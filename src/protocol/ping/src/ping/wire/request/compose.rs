@@ -10,7 +10,7 @@ impl Compose for Request {
     fn compose(&self, dst: &mut dyn BufMut) -> usize {
         match self {
             Self::Ping => {
-                dst.put_slice(b"ping\r\n");
+                dst.put_slice(b"PING\r\n");
                 6
             }
         }
@@ -11,13 +11,13 @@ mod parse;
 #[cfg(test)]
 mod test;
 
-use crate::Response;
+use crate::{KeyspaceEvents, ReadOnlyMode, Response};
 pub use keyword::Keyword;
 use logger::Klog;
 
 pub use parse::Parser as RequestParser;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 /// A collection of all possible `Ping` request types.
 pub enum Request {
     Ping,
@@ -32,3 +32,14 @@ impl Klog for Request {
         }
     }
 }
+
+impl ReadOnlyMode<Response> for Request {
+    fn read_only_error(&self) -> Option<Response> {
+        match self {
+            Request::Ping => None,
+        }
+    }
+}
+
+// pings don't change any keys
+impl KeyspaceEvents<Response> for Request {}
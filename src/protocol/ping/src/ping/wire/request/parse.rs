@@ -28,6 +28,8 @@ impl Parse<Request> for Parser {
     }
 }
 
+impl ParseErrorReply<Response> for Parser {}
+
 struct ParseState<'a> {
     single_byte: Windows<'a, u8>,
     double_byte: Windows<'a, u8>,
@@ -15,6 +15,7 @@ mod test;
 pub use parse::Parser as ResponseParser;
 
 /// A collection of all possible `Ping` responses
+#[derive(Debug, PartialEq, Eq)]
 pub enum Response {
     Pong,
 }
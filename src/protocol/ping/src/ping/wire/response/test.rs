@@ -56,3 +56,24 @@ fn pipelined() {
 
     assert!(parser.parse(b"pong\r\npong\r\n").is_ok());
 }
+
+#[test]
+fn round_trip() {
+    let parser = ResponseParser::new();
+
+    let mut buffer = Vec::new();
+    assert_eq!(Response::Pong.compose(&mut buffer), 6);
+    assert_eq!(buffer, b"PONG\r\n");
+
+    let parsed = parser.parse(&buffer).expect("failed to parse");
+    assert_eq!(parsed.consumed(), buffer.len());
+    assert_eq!(parsed.into_inner(), Response::Pong);
+
+    // a partial message needs more data
+    for end in 0..buffer.len() {
+        match parser.parse(&buffer[..end]) {
+            Err(e) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+            Ok(_) => panic!("parsed a partial message"),
+        }
+    }
+}
@@ -2,13 +2,35 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+//! A protocol crate for RESP, the redis serialization protocol.
+//!
+//! Requests and responses are parsed and composed, but nothing here executes
+//! them: there is no storage for resp in this tree. The momento proxy is the
+//! only user, and it translates a few of the requests into calls to its own
+//! backend. Options which only storage could give a meaning to, such as the
+//! `IFEQ` condition of `SET`, are parsed so that they can be forwarded or
+//! refused, and are otherwise left to whatever executes the request.
+//!
+//! For the same reason no latency is recorded per command. It would measure
+//! the time storage takes to execute each request, and there is no storage to
+//! measure. The proxy answers only a handful of commands, and the time it
+//! takes for those is almost all spent waiting on its backend.
+//!
+//! Nor is there a `READONLY` or `READWRITE` command. Read-only mode belongs to
+//! a server and is toggled from its admin port, so the `ReadOnlyMode` impl only
+//! says which requests a read-only server would refuse, and with what error.
+
 mod message;
 mod request;
 mod response;
 mod util;
 
+#[cfg(test)]
+mod tests;
+
 pub(crate) use util::*;
 
+pub use message::compose_array;
 pub use request::*;
 pub use response::*;
 
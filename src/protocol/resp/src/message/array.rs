@@ -14,14 +14,12 @@ impl Compose for Array {
     fn compose(&self, session: &mut dyn BufMut) -> usize {
         let mut len = 0;
         if let Some(values) = &self.inner {
-            let header = format!("${}\r\n", values.len());
+            let header = format!("*{}\r\n", values.len());
             session.put_slice(header.as_bytes());
             len += header.as_bytes().len();
             for value in values {
                 len += value.compose(session);
             }
-            session.put_slice(b"\r\n");
-            len += 2;
         } else {
             session.put_slice(b"*-1\r\n");
             len += 5;
@@ -30,6 +28,25 @@ impl Compose for Array {
     }
 }
 
+/// Composes an array of bulk strings directly into the buffer, returning the
+/// number of bytes written. The output is the same as composing a
+/// [`Message::Array`] of [`Message::BulkString`]s, but without building the
+/// message first, which avoids an allocation for the array and for each of
+/// its values.
+pub fn compose_array(buf: &mut dyn BufMut, values: &[&[u8]]) -> usize {
+    let header = format!("*{}\r\n", values.len());
+    buf.put_slice(header.as_bytes());
+    let mut len = header.len();
+    for value in values {
+        let header = format!("${}\r\n", value.len());
+        buf.put_slice(header.as_bytes());
+        buf.put_slice(value);
+        buf.put_slice(b"\r\n");
+        len += header.len() + value.len() + 2;
+    }
+    len
+}
+
 pub fn parse(input: &[u8]) -> IResult<&[u8], Array> {
     match input.first() {
         Some(b'-') => {
@@ -88,4 +105,29 @@ mod tests {
             Ok((&b""[..], Message::bulk_string("HELLO WORLD".as_bytes())))
         );
     }
+
+    #[test]
+    fn compose_direct() {
+        let cases: &[&[&[u8]]] = &[
+            &[],
+            &[b""],
+            &[b"coffee"],
+            &[b"key", b"\0\r\n", b"", b"value"],
+            &[&[0xA5; 1000], b"a"],
+        ];
+
+        for values in cases {
+            let message = Message::Array(Array {
+                inner: Some(values.iter().map(|v| Message::bulk_string(v)).collect()),
+            });
+            let mut expected = Vec::new();
+            let expected_len = message.compose(&mut expected);
+
+            let mut composed = Vec::new();
+            let len = compose_array(&mut composed, values);
+
+            assert_eq!(composed, expected);
+            assert_eq!(len, expected_len);
+        }
+    }
 }
@@ -18,7 +18,10 @@ impl Compose for Integer {
 }
 
 pub fn parse(input: &[u8]) -> IResult<&[u8], Integer> {
-    let (input, string) = digit1(input)?;
+    // a leading minus sign is kept so that it's parsed along with the digits
+    let start = input;
+    let (input, _) = digit1(input.strip_prefix(b"-").unwrap_or(input))?;
+    let string = &start[..(start.len() - input.len())];
     let (input, _) = crlf(input)?;
 
     let string = unsafe { std::str::from_utf8_unchecked(string).to_owned() };
@@ -40,5 +43,7 @@ mod tests {
             message(b":1000\r\n"),
             Ok((&b""[..], Message::integer(1000),))
         );
+
+        assert_eq!(message(b":-2\r\n"), Ok((&b""[..], Message::integer(-2),)));
     }
 }
@@ -11,7 +11,7 @@ mod error;
 mod integer;
 mod simple_string;
 
-pub use array::Array;
+pub use array::{compose_array, Array};
 pub use bulk_string::BulkString;
 pub use error::Error;
 pub use integer::Integer;
@@ -116,6 +116,40 @@ pub(crate) fn message(input: &[u8]) -> IResult<&[u8], Message> {
     }
 }
 
+// parses the length and trailing CRLF from a bulk string or array header
+fn length(input: &[u8]) -> IResult<&[u8], usize> {
+    let (input, len) = digit1(input)?;
+    let len = unsafe { std::str::from_utf8_unchecked(len) }
+        .parse::<usize>()
+        .map_err(|_| nom::Err::Failure((input, nom::error::ErrorKind::Tag)))?;
+    let (input, _) = crlf(input)?;
+    Ok((input, len))
+}
+
+// determines how many more bytes are needed to complete a message. this is
+// only known once the length of a bulk string has been read. for arrays, the
+// count is for the incomplete element and later elements may need more bytes
+pub(crate) fn bytes_needed(input: &[u8]) -> Option<usize> {
+    match message_type(input).ok()? {
+        (input, MessageType::BulkString) => {
+            let (input, len) = length(input).ok()?;
+            (len + 2).checked_sub(input.len()).filter(|n| *n > 0)
+        }
+        (input, MessageType::Array) => {
+            let (mut input, len) = length(input).ok()?;
+            for _ in 0..len {
+                match message(input) {
+                    Ok((i, _)) => input = i,
+                    Err(Err::Incomplete(_)) => return bytes_needed(input),
+                    Err(_) => return None,
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
 impl Parse<Message> for MessageParser {
     fn parse(&self, buffer: &[u8]) -> Result<ParseOk<Message>, std::io::Error> {
         match message(buffer) {
@@ -127,4 +161,38 @@ impl Parse<Message> for MessageParser {
             )),
         }
     }
+
+    fn bytes_needed(&self, buffer: &[u8]) -> Option<usize> {
+        bytes_needed(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_needed() {
+        let parser = MessageParser::default();
+
+        let message = b"$6\r\nCOFFEE\r\n";
+
+        // the length isn't known until the header is complete
+        assert_eq!(parser.bytes_needed(&message[0..3]), None);
+
+        for end in 4..message.len() {
+            assert!(parser.parse(&message[0..end]).is_err());
+            assert_eq!(
+                parser.bytes_needed(&message[0..end]),
+                Some(message.len() - end)
+            );
+        }
+
+        // within an array, the count is for the incomplete bulk string
+        let message = b"*2\r\n$3\r\nGET\r\n$6\r\nCOFFEE\r\n";
+        assert_eq!(parser.bytes_needed(&message[0..message.len() - 5]), Some(5));
+
+        // simple strings have no length to go by
+        assert_eq!(parser.bytes_needed(b"+OK"), None);
+    }
 }
@@ -0,0 +1,237 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// The unit in which the bounds of a [`BitRange`] are given.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BitUnit {
+    Byte,
+    Bit,
+}
+
+/// An inclusive range of a value given as byte or bit offsets. Negative
+/// offsets count back from the end of the value, so `-1` is the last byte or
+/// bit.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BitRange {
+    start: i64,
+    end: i64,
+    unit: BitUnit,
+}
+
+impl BitRange {
+    pub fn new(start: i64, end: i64, unit: BitUnit) -> Self {
+        Self { start, end, unit }
+    }
+
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    pub fn end(&self) -> i64 {
+        self.end
+    }
+
+    pub fn unit(&self) -> BitUnit {
+        self.unit
+    }
+}
+
+/// Counts the set bits in the value of a key, optionally limited to a range
+/// of it. The reply is an integer, which is `0` for a missing key.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct BitCountRequest {
+    key: Arc<Box<[u8]>>,
+    range: Option<BitRange>,
+}
+
+impl TryFrom<Message> for BitCountRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            // the range is optional, but its start and end must be given
+            // together, and the unit may only follow them
+            if !matches!(array.len(), 2 | 4 | 5) {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let range = if array.is_empty() {
+                None
+            } else {
+                let start = take_bulk_string_as_i64(&mut array)?
+                    .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                let end = take_bulk_string_as_i64(&mut array)?
+                    .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                let unit = match take_bulk_string_as_utf8(&mut array)?.as_deref() {
+                    None | Some("BYTE") | Some("byte") => BitUnit::Byte,
+                    Some("BIT") | Some("bit") => BitUnit::Bit,
+                    Some(_) => {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+                };
+
+                Some(BitRange::new(start, end, unit))
+            };
+
+            Ok(Self { key, range })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl BitCountRequest {
+    pub fn new(key: &[u8], range: Option<BitRange>) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            range,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn range(&self) -> Option<BitRange> {
+        self.range
+    }
+}
+
+impl From<&BitCountRequest> for Message {
+    fn from(other: &BitCountRequest) -> Message {
+        let mut v = vec![
+            Message::bulk_string(b"BITCOUNT"),
+            Message::BulkString(BulkString::from(other.key.clone())),
+        ];
+
+        if let Some(range) = other.range {
+            v.push(Message::bulk_string(format!("{}", range.start).as_bytes()));
+            v.push(Message::bulk_string(format!("{}", range.end).as_bytes()));
+
+            // bytes are the default unit, so they are left implicit
+            if range.unit == BitUnit::Bit {
+                v.push(Message::bulk_string(b"BIT"));
+            }
+        }
+
+        Message::Array(Array { inner: Some(v) })
+    }
+}
+
+impl Compose for BitCountRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"bitcount 0\r\n").unwrap().into_inner(),
+            Request::BitCount(BitCountRequest::new(b"0", None))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*2\r\n$8\r\nBITCOUNT\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::BitCount(BitCountRequest::new(b"0", None))
+        );
+
+        assert!(parser.parse(b"bitcount\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_range() {
+        let parser = RequestParser::new();
+        let range = |start, end, unit| {
+            let range = BitRange::new(start, end, unit);
+            Request::BitCount(BitCountRequest::new(b"0", Some(range)))
+        };
+
+        assert_eq!(
+            parser.parse(b"bitcount 0 1 2\r\n").unwrap().into_inner(),
+            range(1, 2, BitUnit::Byte)
+        );
+        assert_eq!(
+            parser.parse(b"bitcount 0 0 -1\r\n").unwrap().into_inner(),
+            range(0, -1, BitUnit::Byte)
+        );
+        assert_eq!(
+            parser
+                .parse(b"bitcount 0 -2 -1 BYTE\r\n")
+                .unwrap()
+                .into_inner(),
+            range(-2, -1, BitUnit::Byte)
+        );
+        assert_eq!(
+            parser
+                .parse(b"bitcount 0 5 30 BIT\r\n")
+                .unwrap()
+                .into_inner(),
+            range(5, 30, BitUnit::Bit)
+        );
+        assert_eq!(
+            parser
+                .parse(b"*5\r\n$8\r\nBITCOUNT\r\n$1\r\n0\r\n$1\r\n5\r\n$2\r\n30\r\n$3\r\nbit\r\n")
+                .unwrap()
+                .into_inner(),
+            range(5, 30, BitUnit::Bit)
+        );
+
+        // the start and end must be given together
+        assert!(parser.parse(b"bitcount 0 1\r\n").is_err());
+        assert!(parser.parse(b"bitcount 0 1 BIT\r\n").is_err());
+
+        // the bounds must be integers and the unit must be known
+        assert!(parser.parse(b"bitcount 0 one 2\r\n").is_err());
+        assert!(parser.parse(b"bitcount 0 1 2.5\r\n").is_err());
+        assert!(parser.parse(b"bitcount 0 1 2 WORD\r\n").is_err());
+        assert!(parser.parse(b"bitcount 0 1 2 BIT BIT\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        BitCountRequest::new(b"0", None).compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$8\r\nBITCOUNT\r\n$1\r\n0\r\n");
+
+        let range = BitRange::new(0, -1, BitUnit::Bit);
+        let mut buffer = Vec::new();
+        BitCountRequest::new(b"0", Some(range)).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*5\r\n$8\r\nBITCOUNT\r\n$1\r\n0\r\n$1\r\n0\r\n$2\r\n-1\r\n$3\r\nBIT\r\n"
+        );
+    }
+}
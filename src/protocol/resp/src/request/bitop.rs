@@ -0,0 +1,195 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// The bitwise operation performed by a [`BitOpRequest`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+impl BitOperation {
+    fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Self::And => b"AND",
+            Self::Or => b"OR",
+            Self::Xor => b"XOR",
+            Self::Not => b"NOT",
+        }
+    }
+}
+
+/// Performs a bitwise operation across the values of one or more keys and
+/// stores the result in the destination key. `NOT` takes exactly one source
+/// key. Shorter values are treated as if they were extended with zero bytes
+/// to the length of the longest. The reply is an integer holding the length
+/// of the stored value.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct BitOpRequest {
+    operation: BitOperation,
+    destination: Arc<Box<[u8]>>,
+    keys: Vec<Arc<Box<[u8]>>>,
+}
+
+impl TryFrom<Message> for BitOpRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 4 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let operation = match take_bulk_string_as_utf8(&mut array)?.as_deref() {
+                Some("AND") | Some("and") => BitOperation::And,
+                Some("OR") | Some("or") => BitOperation::Or,
+                Some("XOR") | Some("xor") => BitOperation::Xor,
+                Some("NOT") | Some("not") => BitOperation::Not,
+                _ => {
+                    return Err(Error::new(ErrorKind::Other, "malformed command"));
+                }
+            };
+
+            // NOT inverts a single value, there is nothing to combine
+            if operation == BitOperation::Not && array.len() != 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut keys = Vec::with_capacity(array.len());
+            while !array.is_empty() {
+                let key = take_bulk_string(&mut array)?
+                    .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                if key.is_empty() {
+                    return Err(Error::new(ErrorKind::Other, "malformed command"));
+                }
+
+                keys.push(key);
+            }
+
+            let destination = keys.remove(0);
+
+            Ok(Self {
+                operation,
+                destination,
+                keys,
+            })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl BitOpRequest {
+    pub fn new(operation: BitOperation, destination: &[u8], keys: &[&[u8]]) -> Self {
+        Self {
+            operation,
+            destination: Arc::new(destination.to_owned().into_boxed_slice()),
+            keys: keys
+                .iter()
+                .map(|k| Arc::new(k.to_vec().into_boxed_slice()))
+                .collect(),
+        }
+    }
+
+    pub fn operation(&self) -> BitOperation {
+        self.operation
+    }
+
+    pub fn destination(&self) -> &[u8] {
+        &self.destination
+    }
+
+    /// The keys holding the source values, in the order they were given.
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|k| &***k)
+    }
+}
+
+impl From<&BitOpRequest> for Message {
+    fn from(other: &BitOpRequest) -> Message {
+        let mut array = vec![
+            Message::bulk_string(b"BITOP"),
+            Message::bulk_string(other.operation.as_bytes()),
+            Message::BulkString(BulkString::from(other.destination.clone())),
+        ];
+        for key in &other.keys {
+            array.push(Message::BulkString(BulkString::from(key.clone())));
+        }
+
+        Message::Array(Array { inner: Some(array) })
+    }
+}
+
+impl Compose for BitOpRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        let parse = |request: &[u8]| parser.parse(request).map(|r| r.into_inner());
+
+        assert_eq!(
+            parse(b"bitop and dest a b c\r\n").unwrap(),
+            Request::BitOp(BitOpRequest::new(
+                BitOperation::And,
+                b"dest",
+                &[b"a", b"b", b"c"]
+            ))
+        );
+        assert_eq!(
+            parse(b"bitop OR dest a b\r\n").unwrap(),
+            Request::BitOp(BitOpRequest::new(BitOperation::Or, b"dest", &[b"a", b"b"]))
+        );
+        assert_eq!(
+            parse(b"BITOP xor dest a\r\n").unwrap(),
+            Request::BitOp(BitOpRequest::new(BitOperation::Xor, b"dest", &[b"a"]))
+        );
+        assert_eq!(
+            parse(b"*4\r\n$5\r\nBITOP\r\n$3\r\nNOT\r\n$4\r\ndest\r\n$1\r\na\r\n").unwrap(),
+            Request::BitOp(BitOpRequest::new(BitOperation::Not, b"dest", &[b"a"]))
+        );
+
+        // NOT takes exactly one source key
+        assert!(parse(b"bitop not dest a b\r\n").is_err());
+        assert!(parse(b"bitop not dest\r\n").is_err());
+
+        // every other operation takes at least one
+        assert!(parse(b"bitop and dest\r\n").is_err());
+        assert!(parse(b"bitop nand dest a b\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        BitOpRequest::new(BitOperation::Not, b"dest", &[b"a"]).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*4\r\n$5\r\nBITOP\r\n$3\r\nNOT\r\n$4\r\ndest\r\n$1\r\na\r\n"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Commands for testing and debugging the server. These are only parsed and
+/// composed here, as no server in this tree executes resp requests against
+/// storage. Debug builds of segcache serve the same operations from the admin
+/// port, as `debug set-active-expire`, `debug object` and `debug reload`, and
+/// the `/evict` and `/verify` HTTP endpoints.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub enum DebugRequest {
+    /// Immediately evicts the given number of segments, following the
+    /// eviction policy. The reply is the number of items evicted.
+    Evict {
+        segments: u64,
+    },
+    Help,
+    /// Describes how the value for a key is stored, as a single line with
+    /// the encoding and serialized length of the value.
+    Object {
+        key: Arc<Box<[u8]>>,
+    },
+    /// Writes the storage to a snapshot and restores it into a fresh instance
+    /// of the storage, which replaces the current one. This tests that every
+    /// key survives persistence. The reply is `OK` once the reload is done.
+    Reload,
+    /// Turns the background removal of expired keys on or off. Expired keys
+    /// are still missed when they are accessed, so turning this off allows
+    /// testing lazy expiration.
+    SetActiveExpire {
+        enabled: bool,
+    },
+    /// Runs a self-consistency check over the storage. The reply is `OK`, or
+    /// an error describing the first inconsistency found.
+    Verify,
+}
+
+impl TryFrom<Message> for DebugRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let subcommand = take_bulk_string_as_utf8(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if is_help(&subcommand, array.len()) {
+                return Ok(Self::Help);
+            }
+
+            match subcommand.to_ascii_uppercase().as_str() {
+                "EVICT" => {
+                    let segments = take_bulk_string_as_u64(&mut array)?
+                        .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                    if !array.is_empty() {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+
+                    Ok(Self::Evict { segments })
+                }
+                "OBJECT" => {
+                    let key = take_bulk_string(&mut array)?
+                        .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                    if key.is_empty() || !array.is_empty() {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+
+                    Ok(Self::Object { key })
+                }
+                "SET-ACTIVE-EXPIRE" => {
+                    let enabled = match take_bulk_string_as_u64(&mut array)? {
+                        Some(0) => false,
+                        Some(1) => true,
+                        _ => {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+                    };
+
+                    if !array.is_empty() {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+
+                    Ok(Self::SetActiveExpire { enabled })
+                }
+                "RELOAD" => {
+                    if !array.is_empty() {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+
+                    Ok(Self::Reload)
+                }
+                "VERIFY" => {
+                    if !array.is_empty() {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+
+                    Ok(Self::Verify)
+                }
+                _ => Err(Error::new(ErrorKind::Other, "malformed command")),
+            }
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl DebugRequest {
+    pub fn evict(segments: u64) -> Self {
+        Self::Evict { segments }
+    }
+
+    pub fn object(key: &[u8]) -> Self {
+        Self::Object {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+        }
+    }
+
+    pub fn set_active_expire(enabled: bool) -> Self {
+        Self::SetActiveExpire { enabled }
+    }
+
+    /// The reply to `DEBUG HELP`.
+    pub(crate) fn help() -> Message {
+        help(
+            "DEBUG",
+            &[
+                (
+                    "EVICT <segments>",
+                    "Evict <segments> segments now. Return the number of items evicted.",
+                ),
+                (
+                    "OBJECT <key>",
+                    "Show low-level information about the <key> and its value.",
+                ),
+                (
+                    "RELOAD",
+                    "Save the storage to a snapshot and load it back into a fresh instance.",
+                ),
+                (
+                    "SET-ACTIVE-EXPIRE <0|1>",
+                    "Turn the background removal of expired keys off or on.",
+                ),
+                (
+                    "VERIFY",
+                    "Check the consistency of the storage. Return OK or the first inconsistency found.",
+                ),
+            ],
+        )
+    }
+}
+
+impl From<&DebugRequest> for Message {
+    fn from(other: &DebugRequest) -> Message {
+        let mut v = vec![Message::bulk_string(b"DEBUG")];
+
+        match other {
+            DebugRequest::Evict { segments } => {
+                v.push(Message::bulk_string(b"EVICT"));
+                v.push(Message::bulk_string(format!("{}", segments).as_bytes()));
+            }
+            DebugRequest::Help => {
+                v.push(Message::bulk_string(b"HELP"));
+            }
+            DebugRequest::Object { key } => {
+                v.push(Message::bulk_string(b"OBJECT"));
+                v.push(Message::BulkString(BulkString::from(key.clone())));
+            }
+            DebugRequest::Reload => {
+                v.push(Message::bulk_string(b"RELOAD"));
+            }
+            DebugRequest::SetActiveExpire { enabled } => {
+                v.push(Message::bulk_string(b"SET-ACTIVE-EXPIRE"));
+                v.push(Message::bulk_string(if *enabled { b"1" } else { b"0" }));
+            }
+            DebugRequest::Verify => {
+                v.push(Message::bulk_string(b"VERIFY"));
+            }
+        }
+
+        Message::Array(Array { inner: Some(v) })
+    }
+}
+
+impl Compose for DebugRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"debug evict 2\r\n").unwrap().into_inner(),
+            Request::Debug(DebugRequest::evict(2))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$5\r\nDEBUG\r\n$5\r\nEVICT\r\n$1\r\n2\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Debug(DebugRequest::evict(2))
+        );
+
+        assert_eq!(
+            parser.parse(b"DEBUG HELP\r\n").unwrap().into_inner(),
+            Request::Debug(DebugRequest::Help)
+        );
+
+        // evict requires a count, and only one
+        assert!(parser.parse(b"debug evict\r\n").is_err());
+        assert!(parser.parse(b"debug evict two\r\n").is_err());
+        assert!(parser.parse(b"debug evict 1 2\r\n").is_err());
+
+        assert_eq!(
+            parser.parse(b"debug object key\r\n").unwrap().into_inner(),
+            Request::Debug(DebugRequest::object(b"key"))
+        );
+        assert!(parser.parse(b"debug object\r\n").is_err());
+        assert!(parser.parse(b"debug object a b\r\n").is_err());
+
+        assert_eq!(
+            parser
+                .parse(b"DEBUG set-active-expire 0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Debug(DebugRequest::set_active_expire(false))
+        );
+        assert_eq!(
+            parser
+                .parse(b"debug SET-ACTIVE-EXPIRE 1\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Debug(DebugRequest::set_active_expire(true))
+        );
+
+        // only 0 and 1 are accepted
+        assert!(parser.parse(b"debug set-active-expire\r\n").is_err());
+        assert!(parser.parse(b"debug set-active-expire 2\r\n").is_err());
+        assert!(parser.parse(b"debug set-active-expire yes\r\n").is_err());
+
+        assert_eq!(
+            parser.parse(b"DEBUG verify\r\n").unwrap().into_inner(),
+            Request::Debug(DebugRequest::Verify)
+        );
+        assert!(parser.parse(b"debug verify all\r\n").is_err());
+
+        assert_eq!(
+            parser.parse(b"debug RELOAD\r\n").unwrap().into_inner(),
+            Request::Debug(DebugRequest::Reload)
+        );
+        assert!(parser.parse(b"debug reload now\r\n").is_err());
+
+        assert!(parser.parse(b"debug segfault\r\n").is_err());
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Counts how many of the keys exist, as `EXISTS key [key ...]`. A key which
+/// is given more than once is counted each time. The reply is the count.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct ExistsRequest {
+    keys: Vec<Arc<Box<[u8]>>>,
+}
+
+impl TryFrom<Message> for ExistsRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let mut keys = Vec::with_capacity(array.len());
+            while !array.is_empty() {
+                let key = take_bulk_string(&mut array)?
+                    .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                if key.is_empty() {
+                    return Err(Error::new(ErrorKind::Other, "malformed command"));
+                }
+
+                keys.push(key);
+            }
+
+            Ok(Self { keys })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl ExistsRequest {
+    pub fn new(keys: &[&[u8]]) -> Self {
+        Self {
+            keys: keys
+                .iter()
+                .map(|k| Arc::new(k.to_vec().into_boxed_slice()))
+                .collect(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|k| &***k)
+    }
+}
+
+impl From<&ExistsRequest> for Message {
+    fn from(other: &ExistsRequest) -> Message {
+        let mut array = vec![Message::bulk_string(b"EXISTS")];
+        for key in &other.keys {
+            array.push(Message::BulkString(BulkString::from(key.clone())));
+        }
+
+        Message::Array(Array { inner: Some(array) })
+    }
+}
+
+impl Compose for ExistsRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"exists 0\r\n").unwrap().into_inner(),
+            Request::Exists(ExistsRequest::new(&[b"0"]))
+        );
+
+        assert_eq!(
+            parser.parse(b"EXISTS a b a\r\n").unwrap().into_inner(),
+            Request::Exists(ExistsRequest::new(&[b"a", b"b", b"a"]))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$6\r\nEXISTS\r\n$1\r\na\r\n$1\r\nb\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Exists(ExistsRequest::new(&[b"a", b"b"]))
+        );
+
+        // the keys are kept in order, including any which are repeated
+        let request = ExistsRequest::new(&[b"a", b"b", b"a"]);
+        assert_eq!(
+            request.keys().collect::<Vec<_>>(),
+            [&b"a"[..], &b"b"[..], &b"a"[..]]
+        );
+
+        // at least one key is required
+        assert!(parser.parse(b"exists\r\n").is_err());
+        assert!(parser.parse(b"*2\r\n$6\r\nEXISTS\r\n$0\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        ExistsRequest::new(&[b"a", b"b"]).compose(&mut buffer);
+        assert_eq!(buffer, b"*3\r\n$6\r\nEXISTS\r\n$1\r\na\r\n$1\r\nb\r\n");
+    }
+}
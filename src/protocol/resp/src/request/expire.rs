@@ -0,0 +1,128 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Sets a key to expire after the given number of seconds. A timeout which
+/// isn't positive deletes the key. The reply is the integer `1` if the
+/// timeout was set and `0` if the key does not exist.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct ExpireRequest {
+    key: Arc<Box<[u8]>>,
+    seconds: i64,
+}
+
+impl TryFrom<Message> for ExpireRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 3 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let seconds = take_bulk_string_as_i64(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            Ok(Self { key, seconds })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl ExpireRequest {
+    pub fn new(key: &[u8], seconds: i64) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            seconds,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+}
+
+impl From<&ExpireRequest> for Message {
+    fn from(other: &ExpireRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"EXPIRE"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+                Message::bulk_string(format!("{}", other.seconds).as_bytes()),
+            ]),
+        })
+    }
+}
+
+impl Compose for ExpireRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"expire 0 10\r\n").unwrap().into_inner(),
+            Request::Expire(ExpireRequest::new(b"0", 10))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$6\r\nEXPIRE\r\n$1\r\n0\r\n$2\r\n10\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Expire(ExpireRequest::new(b"0", 10))
+        );
+
+        // a negative timeout is allowed, and deletes the key
+        assert_eq!(
+            parser.parse(b"EXPIRE 0 -1\r\n").unwrap().into_inner(),
+            Request::Expire(ExpireRequest::new(b"0", -1))
+        );
+
+        // the timeout is required and must be an integer
+        assert!(parser.parse(b"expire 0\r\n").is_err());
+        assert!(parser.parse(b"expire 0 ten\r\n").is_err());
+        assert!(parser.parse(b"expire 0 1.5\r\n").is_err());
+        assert!(parser.parse(b"expire 0 10 NX\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        ExpireRequest::new(b"0", 10).compose(&mut buffer);
+        assert_eq!(buffer, b"*3\r\n$6\r\nEXPIRE\r\n$1\r\n0\r\n$2\r\n10\r\n");
+    }
+}
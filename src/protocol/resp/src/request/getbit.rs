@@ -0,0 +1,125 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Returns the bit at an offset in the value of a key, treating the value as
+/// an array of bits. The reply is an integer, which is `0` for offsets beyond
+/// the end of the value and for a missing key.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct GetBitRequest {
+    key: Arc<Box<[u8]>>,
+    offset: u64,
+}
+
+impl TryFrom<Message> for GetBitRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 3 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let offset = take_bulk_string_as_u64(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if offset > MAX_BIT_OFFSET {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            Ok(Self { key, offset })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl GetBitRequest {
+    pub fn new(key: &[u8], offset: u64) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            offset,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl From<&GetBitRequest> for Message {
+    fn from(other: &GetBitRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"GETBIT"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+                Message::bulk_string(format!("{}", other.offset).as_bytes()),
+            ]),
+        })
+    }
+}
+
+impl Compose for GetBitRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"getbit 0 7\r\n").unwrap().into_inner(),
+            Request::GetBit(GetBitRequest::new(b"0", 7))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$6\r\nGETBIT\r\n$1\r\n0\r\n$2\r\n10\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::GetBit(GetBitRequest::new(b"0", 10))
+        );
+
+        assert!(parser.parse(b"getbit 0\r\n").is_err());
+        assert!(parser.parse(b"getbit 0 7 1\r\n").is_err());
+        assert!(parser.parse(b"getbit 0 -1\r\n").is_err());
+        assert!(parser.parse(b"getbit 0 4294967296\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        GetBitRequest::new(b"0", 7).compose(&mut buffer);
+        assert_eq!(buffer, b"*3\r\n$6\r\nGETBIT\r\n$1\r\n0\r\n$1\r\n7\r\n");
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Gets the value of a key and optionally changes its expiry. At most one of
+/// `EX`, `PX`, `EXAT`, `PXAT` or `PERSIST` may be given. Without any of them
+/// the expiry is left as it is. The reply is the value as a bulk string, or a
+/// null bulk string if the key does not exist.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct GetExRequest {
+    key: Arc<Box<[u8]>>,
+    expire_time: Option<ExpireTime>,
+    persist: bool,
+}
+
+impl TryFrom<Message> for GetExRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut expire_time = None;
+            let mut persist = false;
+
+            while let Some(token) = take_bulk_string_as_utf8(&mut array)? {
+                // the modifiers are mutually exclusive
+                if expire_time.is_some() || persist {
+                    return Err(Error::new(ErrorKind::Other, "malformed command"));
+                }
+
+                match token.to_ascii_uppercase().as_str() {
+                    "EX" => {
+                        let s = take_bulk_string_as_u64(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        expire_time = Some(ExpireTime::Seconds(s));
+                    }
+                    "PX" => {
+                        let ms = take_bulk_string_as_u64(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        expire_time = Some(ExpireTime::Milliseconds(ms));
+                    }
+                    "EXAT" => {
+                        let s = take_bulk_string_as_u64(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        expire_time = Some(ExpireTime::UnixSeconds(s));
+                    }
+                    "PXAT" => {
+                        let ms = take_bulk_string_as_u64(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        expire_time = Some(ExpireTime::UnixMilliseconds(ms));
+                    }
+                    "PERSIST" => {
+                        persist = true;
+                    }
+                    _ => {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+                }
+            }
+
+            Ok(Self {
+                key,
+                expire_time,
+                persist,
+            })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl GetExRequest {
+    /// Create a request which sets a new expiry for the key. Passing `None`,
+    /// or `ExpireTime::KeepTtl`, leaves the expiry as it is.
+    pub fn new(key: &[u8], expire_time: Option<ExpireTime>) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            expire_time: expire_time.filter(|e| *e != ExpireTime::KeepTtl),
+            persist: false,
+        }
+    }
+
+    /// Create a request which removes the expiry from the key.
+    pub fn persist(key: &[u8]) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            expire_time: None,
+            persist: true,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// The new expiry for the key, if it is to be changed.
+    pub fn expire_time(&self) -> Option<ExpireTime> {
+        self.expire_time
+    }
+
+    /// Whether the expiry is to be removed from the key.
+    pub fn is_persist(&self) -> bool {
+        self.persist
+    }
+}
+
+impl From<&GetExRequest> for Message {
+    fn from(other: &GetExRequest) -> Message {
+        let mut v = vec![
+            Message::bulk_string(b"GETEX"),
+            Message::BulkString(BulkString::from(other.key.clone())),
+        ];
+
+        match other.expire_time {
+            Some(ExpireTime::Seconds(s)) => {
+                v.push(Message::bulk_string(b"EX"));
+                v.push(Message::bulk_string(format!("{}", s).as_bytes()));
+            }
+            Some(ExpireTime::Milliseconds(ms)) => {
+                v.push(Message::bulk_string(b"PX"));
+                v.push(Message::bulk_string(format!("{}", ms).as_bytes()));
+            }
+            Some(ExpireTime::UnixSeconds(s)) => {
+                v.push(Message::bulk_string(b"EXAT"));
+                v.push(Message::bulk_string(format!("{}", s).as_bytes()));
+            }
+            Some(ExpireTime::UnixMilliseconds(ms)) => {
+                v.push(Message::bulk_string(b"PXAT"));
+                v.push(Message::bulk_string(format!("{}", ms).as_bytes()));
+            }
+            Some(ExpireTime::KeepTtl) | None => {}
+        }
+
+        if other.persist {
+            v.push(Message::bulk_string(b"PERSIST"));
+        }
+
+        Message::Array(Array { inner: Some(v) })
+    }
+}
+
+impl Compose for GetExRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"getex 0\r\n").unwrap().into_inner(),
+            Request::GetEx(GetExRequest::new(b"0", None))
+        );
+
+        assert_eq!(
+            parser.parse(b"getex 0 EX 10\r\n").unwrap().into_inner(),
+            Request::GetEx(GetExRequest::new(b"0", Some(ExpireTime::Seconds(10))))
+        );
+
+        assert_eq!(
+            parser.parse(b"GETEX 0 px 1500\r\n").unwrap().into_inner(),
+            Request::GetEx(GetExRequest::new(
+                b"0",
+                Some(ExpireTime::Milliseconds(1500))
+            ))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"getex 0 EXAT 1700000000\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::GetEx(GetExRequest::new(
+                b"0",
+                Some(ExpireTime::UnixSeconds(1700000000))
+            ))
+        );
+
+        assert_eq!(
+            parser.parse(b"getex 0 PERSIST\r\n").unwrap().into_inner(),
+            Request::GetEx(GetExRequest::persist(b"0"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*2\r\n$5\r\nGETEX\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::GetEx(GetExRequest::new(b"0", None))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*4\r\n$5\r\nGETEX\r\n$1\r\n0\r\n$4\r\nPXAT\r\n$13\r\n1700000000000\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::GetEx(GetExRequest::new(
+                b"0",
+                Some(ExpireTime::UnixMilliseconds(1700000000000))
+            ))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$5\r\nGETEX\r\n$1\r\n0\r\n$7\r\nPERSIST\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::GetEx(GetExRequest::persist(b"0"))
+        );
+
+        // the modifiers are mutually exclusive
+        assert!(parser.parse(b"getex 0 EX 10 PX 100\r\n").is_err());
+        assert!(parser.parse(b"getex 0 EX 10 PERSIST\r\n").is_err());
+        assert!(parser.parse(b"getex 0 PERSIST EXAT 10\r\n").is_err());
+        assert!(parser.parse(b"getex 0 PERSIST PERSIST\r\n").is_err());
+        assert!(parser
+            .parse(b"*5\r\n$5\r\nGETEX\r\n$1\r\n0\r\n$7\r\nPERSIST\r\n$2\r\nEX\r\n$2\r\n10\r\n")
+            .is_err());
+
+        // a key is required, and each expiry needs a value
+        assert!(parser.parse(b"getex\r\n").is_err());
+        assert!(parser.parse(b"getex 0 EX\r\n").is_err());
+        assert!(parser.parse(b"getex 0 EX ten\r\n").is_err());
+        assert!(parser.parse(b"getex 0 KEEPTTL\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        GetExRequest::new(b"0", Some(ExpireTime::Seconds(10))).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*4\r\n$5\r\nGETEX\r\n$1\r\n0\r\n$2\r\nEX\r\n$2\r\n10\r\n"
+        );
+
+        let mut buffer = Vec::new();
+        GetExRequest::persist(b"0").compose(&mut buffer);
+        assert_eq!(buffer, b"*3\r\n$5\r\nGETEX\r\n$1\r\n0\r\n$7\r\nPERSIST\r\n");
+
+        // keeping the ttl is the same as giving no modifier
+        let mut buffer = Vec::new();
+        GetExRequest::new(b"0", Some(ExpireTime::KeepTtl)).compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$5\r\nGETEX\r\n$1\r\n0\r\n");
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Gets the value of a key along with its version, which changes each time the
+/// key is written. The reply is an array of the value as a bulk string and the
+/// version as an integer, or a null array if the key does not exist. The
+/// version can be given to `SET` with `IFVERSION` to only replace the value if
+/// nothing else has written the key since.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct GetWithVersionRequest {
+    key: Arc<Box<[u8]>>,
+}
+
+impl TryFrom<Message> for GetWithVersionRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            Ok(Self { key })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl GetWithVersionRequest {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl From<&GetWithVersionRequest> for Message {
+    fn from(other: &GetWithVersionRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"GETV"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+            ]),
+        })
+    }
+}
+
+impl Compose for GetWithVersionRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"getv 0\r\n").unwrap().into_inner(),
+            Request::GetWithVersion(GetWithVersionRequest::new(b"0"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*2\r\n$4\r\nGETV\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::GetWithVersion(GetWithVersionRequest::new(b"0"))
+        );
+
+        // exactly one key is required
+        assert!(parser.parse(b"getv\r\n").is_err());
+        assert!(parser.parse(b"getv 0 1\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        GetWithVersionRequest::new(b"0").compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$4\r\nGETV\r\n$1\r\n0\r\n");
+    }
+}
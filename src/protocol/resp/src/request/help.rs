@@ -0,0 +1,94 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Container commands, which take a subcommand as their first argument, also
+//! accept a `HELP` subcommand. It is answered with an array of lines which
+//! describe the subcommands, and tools such as `redis-cli` issue it expecting
+//! a reply rather than an error.
+
+use super::*;
+
+/// Returns `true` if the subcommand, which has `args` arguments following it,
+/// is a request for help. Matching is case-insensitive, and `HELP` takes no
+/// arguments.
+pub(crate) fn is_help(subcommand: &str, args: usize) -> bool {
+    args == 0 && subcommand.eq_ignore_ascii_case("HELP")
+}
+
+/// Builds the reply to `<command> HELP`. Each subcommand is given as its
+/// usage and a description, and the `HELP` subcommand itself is added to the
+/// end.
+pub(crate) fn help(command: &str, subcommands: &[(&str, &str)]) -> Message {
+    let mut lines = vec![Message::simple_string(format!(
+        "{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+        command
+    ))];
+
+    let help = ("HELP", "Print this help.");
+
+    for (usage, description) in subcommands.iter().chain(std::iter::once(&help)) {
+        lines.push(Message::simple_string(usage));
+        lines.push(Message::simple_string(format!("    {}", description)));
+    }
+
+    Message::Array(Array { inner: Some(lines) })
+}
+
+impl Request {
+    /// Returns the reply if this is a `HELP` subcommand. The reply doesn't
+    /// depend on the storage, so it can be answered before the request is
+    /// executed.
+    pub fn help(&self) -> Option<Response> {
+        match self {
+            Self::Debug(DebugRequest::Help) => Some(DebugRequest::help()),
+            Self::Memory(MemoryRequest::Help) => Some(MemoryRequest::help()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_help() {
+        assert!(is_help("HELP", 0));
+        assert!(is_help("help", 0));
+        assert!(!is_help("HELP", 1));
+        assert!(!is_help("USAGE", 0));
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        help("TEST", &[("RUN <name>", "Run a test.")]).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*5\r\n\
+              +TEST <subcommand> [<arg> [value] [opt] ...]. Subcommands are:\r\n\
+              +RUN <name>\r\n\
+              +    Run a test.\r\n\
+              +HELP\r\n\
+              +    Print this help.\r\n"
+        );
+    }
+
+    #[test]
+    fn request() {
+        let parser = RequestParser::new();
+
+        let request = parser.parse(b"memory help\r\n").unwrap().into_inner();
+        match request.help() {
+            Some(Response::Array(array)) => {
+                assert!(!array.inner.unwrap().is_empty());
+            }
+            reply => panic!("unexpected reply: {:?}", reply),
+        }
+
+        // requests which aren't for help have no reply here
+        let request = parser.parse(b"memory stats\r\n").unwrap().into_inner();
+        assert_eq!(request.help(), None);
+    }
+}
@@ -0,0 +1,497 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// When `HEXPIRE` may change the expiry of a field. A field without an expiry
+/// is treated as having an infinite one when its expiry is compared.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ExpireCondition {
+    /// Only if the field has no expiry
+    Nx,
+    /// Only if the field has an expiry
+    Xx,
+    /// Only if the new expiry is later than the current one
+    Gt,
+    /// Only if the new expiry is earlier than the current one
+    Lt,
+}
+
+impl ExpireCondition {
+    fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Self::Nx => b"NX",
+            Self::Xx => b"XX",
+            Self::Gt => b"GT",
+            Self::Lt => b"LT",
+        }
+    }
+}
+
+/// Sets fields of a hash to expire after the given number of seconds, as
+/// `HEXPIRE key seconds [NX|XX|GT|LT] FIELDS numfields field [field ...]`. A
+/// timeout of zero deletes the fields. The reply is an array with an integer
+/// for each field, which is `-2` if the field does not exist, `0` if the
+/// condition was not met, `1` if the expiry was set, and `2` if the field was
+/// deleted.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct HashExpireRequest {
+    key: Arc<Box<[u8]>>,
+    seconds: u64,
+    condition: Option<ExpireCondition>,
+    fields: Vec<Arc<Box<[u8]>>>,
+}
+
+/// Returns the number of seconds until each of the given fields of a hash
+/// expires, as `HTTL key FIELDS numfields field [field ...]`. The reply is an
+/// array with an integer for each field, which is `-1` if the field has no
+/// expiry and `-2` if the field does not exist.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct HashTtlRequest {
+    key: Arc<Box<[u8]>>,
+    fields: Vec<Arc<Box<[u8]>>>,
+}
+
+/// Removes the expiry from each of the given fields of a hash, as
+/// `HPERSIST key FIELDS numfields field [field ...]`. The reply is an array
+/// with an integer for each field, which is `1` if the expiry was removed,
+/// `-1` if the field has no expiry, and `-2` if the field does not exist.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct HashPersistRequest {
+    key: Arc<Box<[u8]>>,
+    fields: Vec<Arc<Box<[u8]>>>,
+}
+
+/// Takes the key which leads the arguments of each of these commands.
+#[allow(clippy::redundant_allocation)]
+fn take_key(array: &mut Vec<Message>) -> Result<Arc<Box<[u8]>>, Error> {
+    let key = take_bulk_string(array)?.ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+    if key.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "malformed command"));
+    }
+
+    Ok(key)
+}
+
+/// Takes the `FIELDS numfields field [field ...]` arguments which end each of
+/// these commands. There must be at least one field, and exactly as many
+/// fields as given by `numfields`.
+#[allow(clippy::redundant_allocation)]
+fn take_fields(array: &mut Vec<Message>) -> Result<Vec<Arc<Box<[u8]>>>, Error> {
+    let token = take_bulk_string_as_utf8(array)?
+        .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+    if !token.eq_ignore_ascii_case("FIELDS") {
+        return Err(Error::new(ErrorKind::Other, "malformed command"));
+    }
+
+    let numfields =
+        take_bulk_string_as_u64(array)?.ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+    if numfields == 0 || numfields != array.len() as u64 {
+        return Err(Error::new(ErrorKind::Other, "malformed command"));
+    }
+
+    let mut fields = Vec::with_capacity(array.len());
+    while let Some(field) = take_bulk_string(array)? {
+        fields.push(field);
+    }
+
+    Ok(fields)
+}
+
+fn to_fields(fields: &[&[u8]]) -> Vec<Arc<Box<[u8]>>> {
+    fields
+        .iter()
+        .map(|f| Arc::new(f.to_vec().into_boxed_slice()))
+        .collect()
+}
+
+#[allow(clippy::redundant_allocation)]
+fn compose_fields(v: &mut Vec<Message>, fields: &[Arc<Box<[u8]>>]) {
+    v.push(Message::bulk_string(b"FIELDS"));
+    v.push(Message::bulk_string(format!("{}", fields.len()).as_bytes()));
+    for field in fields {
+        v.push(Message::BulkString(BulkString::from(field.clone())));
+    }
+}
+
+impl TryFrom<Message> for HashExpireRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 6 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_key(&mut array)?;
+
+            let seconds = take_bulk_string_as_u64(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            // the condition is optional, and comes before the fields
+            let condition = match &array[0] {
+                Message::BulkString(s) => match s.inner.as_ref().map(|v| v.as_ref().as_ref()) {
+                    Some(b"nx") | Some(b"NX") => Some(ExpireCondition::Nx),
+                    Some(b"xx") | Some(b"XX") => Some(ExpireCondition::Xx),
+                    Some(b"gt") | Some(b"GT") => Some(ExpireCondition::Gt),
+                    Some(b"lt") | Some(b"LT") => Some(ExpireCondition::Lt),
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            if condition.is_some() {
+                let _condition = take_bulk_string(&mut array)?;
+            }
+
+            let fields = take_fields(&mut array)?;
+
+            Ok(Self {
+                key,
+                seconds,
+                condition,
+                fields,
+            })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl HashExpireRequest {
+    pub fn new(
+        key: &[u8],
+        seconds: u64,
+        condition: Option<ExpireCondition>,
+        fields: &[&[u8]],
+    ) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            seconds,
+            condition,
+            fields: to_fields(fields),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn seconds(&self) -> u64 {
+        self.seconds
+    }
+
+    pub fn condition(&self) -> Option<ExpireCondition> {
+        self.condition
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &[u8]> {
+        self.fields.iter().map(|f| f.as_ref().as_ref())
+    }
+}
+
+impl From<&HashExpireRequest> for Message {
+    fn from(other: &HashExpireRequest) -> Message {
+        let mut v = vec![
+            Message::bulk_string(b"HEXPIRE"),
+            Message::BulkString(BulkString::from(other.key.clone())),
+            Message::bulk_string(format!("{}", other.seconds).as_bytes()),
+        ];
+
+        if let Some(condition) = other.condition {
+            v.push(Message::bulk_string(condition.as_bytes()));
+        }
+
+        compose_fields(&mut v, &other.fields);
+
+        Message::Array(Array { inner: Some(v) })
+    }
+}
+
+impl Compose for HashExpireRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+impl TryFrom<Message> for HashTtlRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 5 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_key(&mut array)?;
+
+            let fields = take_fields(&mut array)?;
+
+            Ok(Self { key, fields })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl HashTtlRequest {
+    pub fn new(key: &[u8], fields: &[&[u8]]) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            fields: to_fields(fields),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &[u8]> {
+        self.fields.iter().map(|f| f.as_ref().as_ref())
+    }
+}
+
+impl From<&HashTtlRequest> for Message {
+    fn from(other: &HashTtlRequest) -> Message {
+        let mut v = vec![
+            Message::bulk_string(b"HTTL"),
+            Message::BulkString(BulkString::from(other.key.clone())),
+        ];
+
+        compose_fields(&mut v, &other.fields);
+
+        Message::Array(Array { inner: Some(v) })
+    }
+}
+
+impl Compose for HashTtlRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+impl TryFrom<Message> for HashPersistRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 5 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_key(&mut array)?;
+
+            let fields = take_fields(&mut array)?;
+
+            Ok(Self { key, fields })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl HashPersistRequest {
+    pub fn new(key: &[u8], fields: &[&[u8]]) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            fields: to_fields(fields),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &[u8]> {
+        self.fields.iter().map(|f| f.as_ref().as_ref())
+    }
+}
+
+impl From<&HashPersistRequest> for Message {
+    fn from(other: &HashPersistRequest) -> Message {
+        let mut v = vec![
+            Message::bulk_string(b"HPERSIST"),
+            Message::BulkString(BulkString::from(other.key.clone())),
+        ];
+
+        compose_fields(&mut v, &other.fields);
+
+        Message::Array(Array { inner: Some(v) })
+    }
+}
+
+impl Compose for HashPersistRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser
+                .parse(b"hexpire h 60 FIELDS 1 a\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::HashExpire(HashExpireRequest::new(b"h", 60, None, &[b"a"]))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"HEXPIRE h 0 nx fields 2 a b\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::HashExpire(HashExpireRequest::new(
+                b"h",
+                0,
+                Some(ExpireCondition::Nx),
+                &[b"a", b"b"]
+            ))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*7\r\n$7\r\nHEXPIRE\r\n$1\r\nh\r\n$2\r\n10\r\n$2\r\nGT\r\n$6\r\nFIELDS\r\n$1\r\n1\r\n$1\r\na\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::HashExpire(HashExpireRequest::new(
+                b"h",
+                10,
+                Some(ExpireCondition::Gt),
+                &[b"a"]
+            ))
+        );
+
+        assert_eq!(
+            parser.parse(b"httl h FIELDS 1 a\r\n").unwrap().into_inner(),
+            Request::HashTtl(HashTtlRequest::new(b"h", &[b"a"]))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"HPERSIST h fields 3 a b c\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::HashPersist(HashPersistRequest::new(b"h", &[b"a", b"b", b"c"]))
+        );
+
+        // the timeout must be a non-negative integer, and the condition known
+        assert!(parser.parse(b"hexpire h -1 FIELDS 1 a\r\n").is_err());
+        assert!(parser.parse(b"hexpire h ten FIELDS 1 a\r\n").is_err());
+        assert!(parser.parse(b"hexpire h 10 AB FIELDS 1 a\r\n").is_err());
+        assert!(parser.parse(b"hexpire h 10 NX XX FIELDS 1 a\r\n").is_err());
+        assert!(parser.parse(b"hexpire h FIELDS 1 a\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_fields() {
+        let parser = RequestParser::new();
+
+        for command in ["hexpire h 10", "httl h", "hpersist h"] {
+            let parse = |args: &str| parser.parse(format!("{} {}\r\n", command, args).as_bytes());
+
+            assert!(parse("FIELDS 1 a").is_ok());
+            assert!(parse("fields 2 a b").is_ok());
+
+            // the fields must be introduced by the FIELDS token
+            assert!(parse("1 a").is_err());
+            assert!(parse("FIELD 1 a").is_err());
+
+            // there must be at least one field
+            assert!(parse("FIELDS 0").is_err());
+            assert!(parse("FIELDS").is_err());
+
+            // the number of fields must match numfields
+            assert!(parse("FIELDS 2 a").is_err());
+            assert!(parse("FIELDS 1 a b").is_err());
+            assert!(parse("FIELDS one a").is_err());
+            assert!(parse("FIELDS -1 a").is_err());
+        }
+
+        // a field may be named FIELDS
+        assert_eq!(
+            parser
+                .parse(b"httl h FIELDS 2 FIELDS a\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::HashTtl(HashTtlRequest::new(b"h", &[b"FIELDS", b"a"]))
+        );
+
+        // and the key is required
+        assert!(parser.parse(b"httl FIELDS 1 a\r\n").is_err());
+        assert!(parser.parse(b"hpersist FIELDS 1 a\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        HashExpireRequest::new(b"h", 60, None, &[b"a"]).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*6\r\n$7\r\nHEXPIRE\r\n$1\r\nh\r\n$2\r\n60\r\n$6\r\nFIELDS\r\n$1\r\n1\r\n$1\r\na\r\n"
+        );
+
+        let mut buffer = Vec::new();
+        HashExpireRequest::new(b"h", 5, Some(ExpireCondition::Lt), &[b"a", b"b"])
+            .compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*8\r\n$7\r\nHEXPIRE\r\n$1\r\nh\r\n$1\r\n5\r\n$2\r\nLT\r\n$6\r\nFIELDS\r\n$1\r\n2\r\n$1\r\na\r\n$1\r\nb\r\n"
+        );
+
+        let mut buffer = Vec::new();
+        HashTtlRequest::new(b"h", &[b"a"]).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*5\r\n$4\r\nHTTL\r\n$1\r\nh\r\n$6\r\nFIELDS\r\n$1\r\n1\r\n$1\r\na\r\n"
+        );
+
+        let mut buffer = Vec::new();
+        HashPersistRequest::new(b"h", &[b"a"]).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*5\r\n$8\r\nHPERSIST\r\n$1\r\nh\r\n$6\r\nFIELDS\r\n$1\r\n1\r\n$1\r\na\r\n"
+        );
+    }
+}
@@ -0,0 +1,453 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Adds one to the integer stored at a key, as `INCR key`. A key which does
+/// not exist is treated as holding `0`. The reply is the new value.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct IncrRequest {
+    key: Arc<Box<[u8]>>,
+}
+
+/// Subtracts one from the integer stored at a key, as `DECR key`. A key which
+/// does not exist is treated as holding `0`. The reply is the new value.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct DecrRequest {
+    key: Arc<Box<[u8]>>,
+}
+
+/// Adds the increment to the integer stored at a key, as
+/// `INCRBY key increment`. The increment must be a signed 64bit integer. A
+/// key which does not exist is treated as holding `0`, so it is set to the
+/// increment. The reply is the new value.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct IncrByRequest {
+    key: Arc<Box<[u8]>>,
+    increment: i64,
+}
+
+/// Subtracts the decrement from the integer stored at a key, as
+/// `DECRBY key decrement`. The decrement must be a signed 64bit integer. A
+/// key which does not exist is treated as holding `0`. The reply is the new
+/// value.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct DecrByRequest {
+    key: Arc<Box<[u8]>>,
+    decrement: i64,
+}
+
+/// Adds the increment to the floating point number stored at a key, as
+/// `INCRBYFLOAT key increment`. The increment must be a finite number, and may
+/// use exponential notation. A key which does not exist is treated as holding
+/// `0`. The reply is the new value as a bulk string.
+#[derive(Debug, PartialEq)]
+#[allow(clippy::redundant_allocation)]
+pub struct IncrByFloatRequest {
+    key: Arc<Box<[u8]>>,
+    increment: f64,
+}
+
+// the increment is never NaN, so it is always equal to itself
+impl Eq for IncrByFloatRequest {}
+
+/// Takes the arguments of each of these commands, which are the key and, if
+/// `delta` is set, the amount to change the value by.
+#[allow(clippy::redundant_allocation)]
+fn take_arguments(
+    other: Message,
+    delta: bool,
+) -> Result<(Arc<Box<[u8]>>, Option<Arc<Box<[u8]>>>), Error> {
+    if let Message::Array(array) = other {
+        if array.inner.is_none() {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        let mut array = array.inner.unwrap();
+
+        if array.len() != if delta { 3 } else { 2 } {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        let _command = take_bulk_string(&mut array)?;
+
+        let key = take_bulk_string(&mut array)?
+            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+        if key.is_empty() {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        Ok((key, take_bulk_string(&mut array)?))
+    } else {
+        Err(Error::new(ErrorKind::Other, "malformed command"))
+    }
+}
+
+fn parse_integer(delta: Option<Arc<Box<[u8]>>>) -> Result<i64, Error> {
+    delta
+        .and_then(|d| std::str::from_utf8(&d).ok()?.parse::<i64>().ok())
+        .ok_or(Error::new(ErrorKind::Other, "malformed command"))
+}
+
+impl TryFrom<Message> for IncrRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (key, _) = take_arguments(other, false)?;
+        Ok(Self { key })
+    }
+}
+
+impl TryFrom<Message> for DecrRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (key, _) = take_arguments(other, false)?;
+        Ok(Self { key })
+    }
+}
+
+impl TryFrom<Message> for IncrByRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (key, increment) = take_arguments(other, true)?;
+        let increment = parse_integer(increment)?;
+        Ok(Self { key, increment })
+    }
+}
+
+impl TryFrom<Message> for DecrByRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (key, decrement) = take_arguments(other, true)?;
+        let decrement = parse_integer(decrement)?;
+        Ok(Self { key, decrement })
+    }
+}
+
+impl TryFrom<Message> for IncrByFloatRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (key, increment) = take_arguments(other, true)?;
+
+        let increment = increment
+            .and_then(|i| std::str::from_utf8(&i).ok()?.parse::<f64>().ok())
+            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+        // `inf` and `nan` parse as floats, but are not valid increments
+        if !increment.is_finite() {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        Ok(Self { key, increment })
+    }
+}
+
+impl IncrRequest {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl DecrRequest {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl IncrByRequest {
+    pub fn new(key: &[u8], increment: i64) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            increment,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn increment(&self) -> i64 {
+        self.increment
+    }
+}
+
+impl DecrByRequest {
+    pub fn new(key: &[u8], decrement: i64) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            decrement,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn decrement(&self) -> i64 {
+        self.decrement
+    }
+}
+
+impl IncrByFloatRequest {
+    /// Panics if the increment is not finite, as it could not be parsed from
+    /// a request.
+    pub fn new(key: &[u8], increment: f64) -> Self {
+        assert!(increment.is_finite(), "increment must be finite");
+
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            increment,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn increment(&self) -> f64 {
+        self.increment
+    }
+}
+
+impl From<&IncrRequest> for Message {
+    fn from(other: &IncrRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"INCR"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+            ]),
+        })
+    }
+}
+
+impl From<&DecrRequest> for Message {
+    fn from(other: &DecrRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"DECR"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+            ]),
+        })
+    }
+}
+
+impl From<&IncrByRequest> for Message {
+    fn from(other: &IncrByRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"INCRBY"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+                Message::bulk_string(format!("{}", other.increment).as_bytes()),
+            ]),
+        })
+    }
+}
+
+impl From<&DecrByRequest> for Message {
+    fn from(other: &DecrByRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"DECRBY"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+                Message::bulk_string(format!("{}", other.decrement).as_bytes()),
+            ]),
+        })
+    }
+}
+
+impl From<&IncrByFloatRequest> for Message {
+    fn from(other: &IncrByFloatRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"INCRBYFLOAT"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+                Message::bulk_string(format!("{}", other.increment).as_bytes()),
+            ]),
+        })
+    }
+}
+
+impl Compose for IncrRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+impl Compose for DecrRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+impl Compose for IncrByRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+impl Compose for DecrByRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+impl Compose for IncrByFloatRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"incr 0\r\n").unwrap().into_inner(),
+            Request::Incr(IncrRequest::new(b"0"))
+        );
+
+        assert_eq!(
+            parser.parse(b"DECR 0\r\n").unwrap().into_inner(),
+            Request::Decr(DecrRequest::new(b"0"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*2\r\n$4\r\nINCR\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Incr(IncrRequest::new(b"0"))
+        );
+
+        // the key is required, and nothing may follow it
+        assert!(parser.parse(b"incr\r\n").is_err());
+        assert!(parser.parse(b"decr\r\n").is_err());
+        assert!(parser.parse(b"incr 0 1\r\n").is_err());
+        assert!(parser.parse(b"decr 0 1\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_integer_delta() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"incrby 0 10\r\n").unwrap().into_inner(),
+            Request::IncrBy(IncrByRequest::new(b"0", 10))
+        );
+
+        assert_eq!(
+            parser.parse(b"DECRBY 0 -10\r\n").unwrap().into_inner(),
+            Request::DecrBy(DecrByRequest::new(b"0", -10))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$6\r\nINCRBY\r\n$1\r\n0\r\n$20\r\n-9223372036854775808\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::IncrBy(IncrByRequest::new(b"0", i64::MIN))
+        );
+
+        // the delta is required and must be a 64bit integer
+        for command in ["incrby", "decrby"] {
+            for args in [
+                "0",
+                "0 ten",
+                "0 1.5",
+                "0 1e3",
+                "0 9223372036854775808",
+                "0 1 2",
+            ] {
+                let request = format!("{} {}\r\n", command, args);
+                assert!(parser.parse(request.as_bytes()).is_err(), "{}", request);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_float_delta() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"incrbyfloat 0 0.1\r\n").unwrap().into_inner(),
+            Request::IncrByFloat(IncrByFloatRequest::new(b"0", 0.1))
+        );
+
+        // integers and exponential notation are floats too
+        assert_eq!(
+            parser.parse(b"INCRBYFLOAT 0 -5\r\n").unwrap().into_inner(),
+            Request::IncrByFloat(IncrByFloatRequest::new(b"0", -5.0))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"incrbyfloat 0 2.0e3\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::IncrByFloat(IncrByFloatRequest::new(b"0", 2000.0))
+        );
+
+        // the delta is required and must be a finite float
+        assert!(parser.parse(b"incrbyfloat 0\r\n").is_err());
+        assert!(parser.parse(b"incrbyfloat 0 ten\r\n").is_err());
+        assert!(parser.parse(b"incrbyfloat 0 inf\r\n").is_err());
+        assert!(parser.parse(b"incrbyfloat 0 nan\r\n").is_err());
+        assert!(parser.parse(b"incrbyfloat 0 1.5 1\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        IncrRequest::new(b"0").compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$4\r\nINCR\r\n$1\r\n0\r\n");
+
+        let mut buffer = Vec::new();
+        DecrRequest::new(b"0").compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$4\r\nDECR\r\n$1\r\n0\r\n");
+
+        let mut buffer = Vec::new();
+        IncrByRequest::new(b"0", 10).compose(&mut buffer);
+        assert_eq!(buffer, b"*3\r\n$6\r\nINCRBY\r\n$1\r\n0\r\n$2\r\n10\r\n");
+
+        let mut buffer = Vec::new();
+        DecrByRequest::new(b"0", -1).compose(&mut buffer);
+        assert_eq!(buffer, b"*3\r\n$6\r\nDECRBY\r\n$1\r\n0\r\n$2\r\n-1\r\n");
+
+        let mut buffer = Vec::new();
+        IncrByFloatRequest::new(b"0", 1.5).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*3\r\n$11\r\nINCRBYFLOAT\r\n$1\r\n0\r\n$3\r\n1.5\r\n"
+        );
+    }
+}
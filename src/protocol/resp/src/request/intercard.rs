@@ -0,0 +1,288 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Counts the members which are in every one of the sets at the keys, as
+/// `SINTERCARD numkeys key [key ...] [LIMIT limit]`. A key which does not
+/// exist is treated as an empty set. Counting stops once the limit is reached,
+/// which is cheaper than finding the whole intersection. The reply is the
+/// count.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct SetInterCardRequest {
+    keys: Vec<Arc<Box<[u8]>>>,
+    limit: Option<u64>,
+}
+
+/// Counts the members which are in every one of the sorted sets at the keys,
+/// as `ZINTERCARD numkeys key [key ...] [LIMIT limit]`. The scores are
+/// ignored. A key which does not exist is treated as an empty sorted set.
+/// Counting stops once the limit is reached. The reply is the count.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct SortedSetInterCardRequest {
+    keys: Vec<Arc<Box<[u8]>>>,
+    limit: Option<u64>,
+}
+
+/// The arguments shared by `SINTERCARD` and `ZINTERCARD`, which are
+/// `numkeys key [key ...] [LIMIT limit]`. Returns the keys and the limit if
+/// one is given.
+#[allow(clippy::type_complexity)]
+fn parse(other: Message) -> Result<(Vec<Arc<Box<[u8]>>>, Option<u64>), Error> {
+    if let Message::Array(array) = other {
+        if array.inner.is_none() {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        let mut array = array.inner.unwrap();
+
+        if array.len() < 3 {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        let _command = take_bulk_string(&mut array)?;
+
+        let numkeys = take_bulk_string_as_u64(&mut array)?
+            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+        // there must be at least one key, and no more than are given
+        if numkeys == 0 || numkeys > array.len() as u64 {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        let mut keys = Vec::with_capacity(numkeys as usize);
+        for _ in 0..numkeys {
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            keys.push(key);
+        }
+
+        let limit = if array.is_empty() {
+            None
+        } else {
+            let option = take_bulk_string_as_utf8(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if !option.eq_ignore_ascii_case("LIMIT") {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            Some(
+                take_bulk_string_as_u64(&mut array)?
+                    .ok_or(Error::new(ErrorKind::Other, "malformed command"))?,
+            )
+        };
+
+        if !array.is_empty() {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        Ok((keys, limit))
+    } else {
+        Err(Error::new(ErrorKind::Other, "malformed command"))
+    }
+}
+
+#[allow(clippy::redundant_allocation)]
+fn compose_message(command: &[u8], keys: &[Arc<Box<[u8]>>], limit: Option<u64>) -> Message {
+    let mut array = vec![
+        Message::bulk_string(command),
+        Message::bulk_string(format!("{}", keys.len()).as_bytes()),
+    ];
+    for key in keys {
+        array.push(Message::BulkString(BulkString::from(key.clone())));
+    }
+    if let Some(limit) = limit {
+        array.push(Message::bulk_string(b"LIMIT"));
+        array.push(Message::bulk_string(format!("{}", limit).as_bytes()));
+    }
+
+    Message::Array(Array { inner: Some(array) })
+}
+
+fn to_keys(keys: &[&[u8]]) -> Vec<Arc<Box<[u8]>>> {
+    keys.iter()
+        .map(|k| Arc::new(k.to_vec().into_boxed_slice()))
+        .collect()
+}
+
+impl TryFrom<Message> for SetInterCardRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (keys, limit) = parse(other)?;
+        Ok(Self { keys, limit })
+    }
+}
+
+impl SetInterCardRequest {
+    pub fn new(keys: &[&[u8]], limit: Option<u64>) -> Self {
+        Self {
+            keys: to_keys(keys),
+            limit,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|k| &***k)
+    }
+
+    /// The count at which counting stops, if any. A limit of zero is the same
+    /// as no limit.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit.filter(|limit| *limit > 0)
+    }
+}
+
+impl From<&SetInterCardRequest> for Message {
+    fn from(other: &SetInterCardRequest) -> Message {
+        compose_message(b"SINTERCARD", &other.keys, other.limit)
+    }
+}
+
+impl Compose for SetInterCardRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+impl TryFrom<Message> for SortedSetInterCardRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (keys, limit) = parse(other)?;
+        Ok(Self { keys, limit })
+    }
+}
+
+impl SortedSetInterCardRequest {
+    pub fn new(keys: &[&[u8]], limit: Option<u64>) -> Self {
+        Self {
+            keys: to_keys(keys),
+            limit,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|k| &***k)
+    }
+
+    /// The count at which counting stops, if any. A limit of zero is the same
+    /// as no limit.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit.filter(|limit| *limit > 0)
+    }
+}
+
+impl From<&SortedSetInterCardRequest> for Message {
+    fn from(other: &SortedSetInterCardRequest) -> Message {
+        compose_message(b"ZINTERCARD", &other.keys, other.limit)
+    }
+}
+
+impl Compose for SortedSetInterCardRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"sintercard 2 a b\r\n").unwrap().into_inner(),
+            Request::SetInterCard(SetInterCardRequest::new(&[b"a", b"b"], None))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*4\r\n$10\r\nZINTERCARD\r\n$1\r\n2\r\n$1\r\na\r\n$1\r\nb\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::SortedSetInterCard(SortedSetInterCardRequest::new(&[b"a", b"b"], None))
+        );
+
+        let request = SetInterCardRequest::new(&[b"a", b"b"], None);
+        assert_eq!(request.keys().collect::<Vec<_>>(), [&b"a"[..], &b"b"[..]]);
+
+        // the number of keys must match the keys which are given
+        assert!(parser.parse(b"sintercard 0\r\n").is_err());
+        assert!(parser.parse(b"sintercard 0 a\r\n").is_err());
+        assert!(parser.parse(b"sintercard 2 a\r\n").is_err());
+        assert!(parser.parse(b"sintercard 1 a b\r\n").is_err());
+        assert!(parser.parse(b"zintercard two a b\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_limit() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser
+                .parse(b"sintercard 2 a b LIMIT 5\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::SetInterCard(SetInterCardRequest::new(&[b"a", b"b"], Some(5)))
+        );
+
+        assert_eq!(
+            parser
+                .parse(
+                    b"*5\r\n$10\r\nZINTERCARD\r\n$1\r\n1\r\n$1\r\na\r\n$5\r\nlimit\r\n$1\r\n1\r\n"
+                )
+                .unwrap()
+                .into_inner(),
+            Request::SortedSetInterCard(SortedSetInterCardRequest::new(&[b"a"], Some(1)))
+        );
+
+        // a limit of zero is no limit
+        let request = parser
+            .parse(b"sintercard 1 a limit 0\r\n")
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            request,
+            Request::SetInterCard(SetInterCardRequest::new(&[b"a"], Some(0)))
+        );
+        if let Request::SetInterCard(request) = request {
+            assert_eq!(request.limit(), None);
+        }
+
+        // the limit must be a positive integer following the keys
+        assert!(parser.parse(b"sintercard 1 a limit\r\n").is_err());
+        assert!(parser.parse(b"sintercard 1 a limit -1\r\n").is_err());
+        assert!(parser.parse(b"sintercard 1 a limit ten\r\n").is_err());
+        assert!(parser.parse(b"sintercard 1 a count 2\r\n").is_err());
+        assert!(parser.parse(b"sintercard 1 a limit 2 3\r\n").is_err());
+        assert!(parser.parse(b"zintercard 1 limit 2 a\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        SetInterCardRequest::new(&[b"a", b"b"], Some(3)).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*6\r\n$10\r\nSINTERCARD\r\n$1\r\n2\r\n$1\r\na\r\n$1\r\nb\r\n$5\r\nLIMIT\r\n$1\r\n3\r\n"
+        );
+
+        let mut buffer = Vec::new();
+        SortedSetInterCardRequest::new(&[b"a"], None).compose(&mut buffer);
+        assert_eq!(buffer, b"*3\r\n$10\r\nZINTERCARD\r\n$1\r\n1\r\n$1\r\na\r\n");
+    }
+}
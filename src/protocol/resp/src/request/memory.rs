@@ -0,0 +1,201 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Introspection of memory use. Only `USAGE` returns real data, `DOCTOR` and
+/// `STATS` are accepted so that tooling which issues them gets a reply.
+/// `HELP` is answered by [`Request::help`].
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub enum MemoryRequest {
+    /// Approximate number of bytes used to store the key and its value.
+    Usage {
+        key: Arc<Box<[u8]>>,
+    },
+    Doctor,
+    Help,
+    Stats,
+}
+
+impl TryFrom<Message> for MemoryRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let subcommand = take_bulk_string_as_utf8(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if is_help(&subcommand, array.len()) {
+                return Ok(Self::Help);
+            }
+
+            match subcommand.to_ascii_uppercase().as_str() {
+                "USAGE" => {
+                    let key = take_bulk_string(&mut array)?
+                        .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                    if key.is_empty() {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+
+                    // SAMPLES controls how many elements of an aggregate value
+                    // are inspected. we only store flat values, so the count
+                    // is validated and then ignored
+                    if let Some(token) = take_bulk_string_as_utf8(&mut array)? {
+                        if token.to_ascii_uppercase() != "SAMPLES" {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+                        take_bulk_string_as_u64(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+                    }
+
+                    if !array.is_empty() {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+
+                    Ok(Self::Usage { key })
+                }
+                "DOCTOR" if array.is_empty() => Ok(Self::Doctor),
+                "STATS" if array.is_empty() => Ok(Self::Stats),
+                _ => Err(Error::new(ErrorKind::Other, "malformed command")),
+            }
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl MemoryRequest {
+    pub fn usage(key: &[u8]) -> Self {
+        Self::Usage {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+        }
+    }
+
+    /// The key to report on, for a `USAGE` request.
+    pub fn key(&self) -> Option<&[u8]> {
+        match self {
+            Self::Usage { key } => Some(key),
+            Self::Doctor | Self::Help | Self::Stats => None,
+        }
+    }
+
+    /// The reply to `MEMORY HELP`.
+    pub(crate) fn help() -> Message {
+        help(
+            "MEMORY",
+            &[
+                ("DOCTOR", "Return memory problems reports."),
+                (
+                    "STATS",
+                    "Return information about the memory usage of the server.",
+                ),
+                (
+                    "USAGE <key> [SAMPLES <count>]",
+                    "Return memory in bytes used by <key> and its value.",
+                ),
+            ],
+        )
+    }
+}
+
+impl From<&MemoryRequest> for Message {
+    fn from(other: &MemoryRequest) -> Message {
+        let mut v = vec![Message::bulk_string(b"MEMORY")];
+
+        match other {
+            MemoryRequest::Usage { key } => {
+                v.push(Message::bulk_string(b"USAGE"));
+                v.push(Message::BulkString(BulkString::from(key.clone())));
+            }
+            MemoryRequest::Doctor => {
+                v.push(Message::bulk_string(b"DOCTOR"));
+            }
+            MemoryRequest::Help => {
+                v.push(Message::bulk_string(b"HELP"));
+            }
+            MemoryRequest::Stats => {
+                v.push(Message::bulk_string(b"STATS"));
+            }
+        }
+
+        Message::Array(Array { inner: Some(v) })
+    }
+}
+
+impl Compose for MemoryRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"memory usage 0\r\n").unwrap().into_inner(),
+            Request::Memory(MemoryRequest::usage(b"0"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"MEMORY USAGE 0 SAMPLES 5\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Memory(MemoryRequest::usage(b"0"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$6\r\nmemory\r\n$5\r\nusage\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Memory(MemoryRequest::usage(b"0"))
+        );
+
+        assert_eq!(
+            parser.parse(b"memory doctor\r\n").unwrap().into_inner(),
+            Request::Memory(MemoryRequest::Doctor)
+        );
+
+        assert_eq!(
+            parser.parse(b"memory stats\r\n").unwrap().into_inner(),
+            Request::Memory(MemoryRequest::Stats)
+        );
+
+        assert_eq!(
+            parser.parse(b"MEMORY HELP\r\n").unwrap().into_inner(),
+            Request::Memory(MemoryRequest::Help)
+        );
+
+        // usage requires a key
+        assert!(parser.parse(b"memory usage\r\n").is_err());
+
+        // samples requires a count
+        assert!(parser.parse(b"memory usage 0 samples\r\n").is_err());
+
+        assert!(parser.parse(b"memory purge\r\n").is_err());
+        assert!(parser.parse(b"memory help usage\r\n").is_err());
+    }
+}
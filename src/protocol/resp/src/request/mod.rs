@@ -4,31 +4,115 @@
 
 use crate::message::*;
 use crate::*;
+use help::{help, is_help};
 use protocol_common::BufMut;
 use protocol_common::Parse;
 use protocol_common::ParseOk;
+use protocol_common::ReadOnlyMode;
 use std::io::{Error, ErrorKind};
 use std::sync::Arc;
 
 mod badd;
+mod bitcount;
+mod bitop;
+mod debug;
+mod exists;
+mod expire;
+mod expiretime;
 mod get;
+mod getbit;
+mod getdel;
+mod getex;
+mod getv;
+mod help;
+mod hexpire;
+mod incr;
+mod intercard;
+mod memory;
+mod mpop;
+mod persist;
+mod pexpire;
+mod pexpiretime;
+mod pttl;
+mod publish;
+mod scan;
 mod set;
+mod setbit;
+mod subscribe;
+mod ttl;
+mod r#type;
+mod unsubscribe;
+mod wait;
 
 pub use badd::BAddRequest;
+pub use bitcount::{BitCountRequest, BitRange, BitUnit};
+pub use bitop::{BitOpRequest, BitOperation};
+pub use debug::DebugRequest;
+pub use exists::ExistsRequest;
+pub use expire::ExpireRequest;
+pub use expiretime::ExpireTimeRequest;
 pub use get::GetRequest;
-pub use set::SetRequest;
+pub use getbit::GetBitRequest;
+pub use getdel::GetDelRequest;
+pub use getex::GetExRequest;
+pub use getv::GetWithVersionRequest;
+pub use hexpire::{ExpireCondition, HashExpireRequest, HashPersistRequest, HashTtlRequest};
+pub use incr::{DecrByRequest, DecrRequest, IncrByFloatRequest, IncrByRequest, IncrRequest};
+pub use intercard::{SetInterCardRequest, SortedSetInterCardRequest};
+pub use memory::MemoryRequest;
+pub use mpop::{ListEnd, ListMultiPopRequest, SortedSetEnd, SortedSetMultiPopRequest};
+pub use persist::PersistRequest;
+pub use pexpire::PExpireRequest;
+pub use pexpiretime::PExpireTimeRequest;
+pub use pttl::PTtlRequest;
+pub use publish::PublishRequest;
+pub use r#type::TypeRequest;
+pub use scan::{scan_reply, ScanRequest};
+pub use set::{SetCondition, SetRequest};
+pub use setbit::{SetBitRequest, MAX_BIT_OFFSET};
+pub use subscribe::SubscribeRequest;
+pub use ttl::TtlRequest;
+pub use unsubscribe::UnsubscribeRequest;
+pub use wait::WaitRequest;
+
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 64 * 1024;
 
-#[derive(Default)]
 pub struct RequestParser {
     message_parser: MessageParser,
+    lenient_newlines: bool,
+    max_line_length: usize,
 }
 
 impl RequestParser {
     pub fn new() -> Self {
         Self {
             message_parser: MessageParser {},
+            lenient_newlines: false,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
         }
     }
+
+    /// Accept a bare LF, as well as a CRLF, at the end of an inline command.
+    /// This is off by default. RESP framed requests always require a CRLF.
+    pub fn lenient_newlines(mut self, enabled: bool) -> Self {
+        self.lenient_newlines = enabled;
+        self
+    }
+
+    /// The longest inline command, not counting its terminator, which will be
+    /// accepted. A client which sends more than this without ending the line
+    /// gets an error instead of the line being buffered until it ends. RESP
+    /// framed requests are not limited by this.
+    pub fn max_line_length(mut self, bytes: usize) -> Self {
+        self.max_line_length = bytes;
+        self
+    }
+}
+
+impl Default for RequestParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Parse<Request> for RequestParser {
@@ -62,15 +146,29 @@ impl Parse<Request> for RequestParser {
                 }
             }
 
-            if &remaining[0..2] != b"\r\n" {
+            let terminator = if remaining.starts_with(b"\r\n") {
+                2
+            } else if remaining.starts_with(b"\n") {
+                if !self.lenient_newlines {
+                    return Err(Error::new(ErrorKind::Other, "malformed command"));
+                }
+                1
+            } else if buffer.len() > self.max_line_length.saturating_add(1) {
+                // there's no room left for the terminator
+                return Err(Error::new(ErrorKind::Other, "line too long"));
+            } else {
                 return Err(Error::from(ErrorKind::WouldBlock));
+            };
+
+            if buffer.len() - remaining.len() > self.max_line_length {
+                return Err(Error::new(ErrorKind::Other, "line too long"));
             }
 
             let message = Message::Array(Array {
                 inner: Some(message),
             });
 
-            let consumed = (buffer.len() - remaining.len()) + 2;
+            let consumed = (buffer.len() - remaining.len()) + terminator;
 
             (message, consumed)
         };
@@ -92,12 +190,117 @@ impl Parse<Request> for RequestParser {
                         Some(b"badd") | Some(b"BADD") => {
                             BAddRequest::try_from(message).map(Request::from)
                         }
+                        Some(b"bitcount") | Some(b"BITCOUNT") => {
+                            BitCountRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"bitop") | Some(b"BITOP") => {
+                            BitOpRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"debug") | Some(b"DEBUG") => {
+                            DebugRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"decr") | Some(b"DECR") => {
+                            DecrRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"decrby") | Some(b"DECRBY") => {
+                            DecrByRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"exists") | Some(b"EXISTS") => {
+                            ExistsRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"expire") | Some(b"EXPIRE") => {
+                            ExpireRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"expiretime") | Some(b"EXPIRETIME") => {
+                            ExpireTimeRequest::try_from(message).map(Request::from)
+                        }
                         Some(b"get") | Some(b"GET") => {
                             GetRequest::try_from(message).map(Request::from)
                         }
+                        Some(b"getbit") | Some(b"GETBIT") => {
+                            GetBitRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"getdel") | Some(b"GETDEL") => {
+                            GetDelRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"getex") | Some(b"GETEX") => {
+                            GetExRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"getv") | Some(b"GETV") => {
+                            GetWithVersionRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"hexpire") | Some(b"HEXPIRE") => {
+                            HashExpireRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"hpersist") | Some(b"HPERSIST") => {
+                            HashPersistRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"httl") | Some(b"HTTL") => {
+                            HashTtlRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"incr") | Some(b"INCR") => {
+                            IncrRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"incrby") | Some(b"INCRBY") => {
+                            IncrByRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"incrbyfloat") | Some(b"INCRBYFLOAT") => {
+                            IncrByFloatRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"lmpop") | Some(b"LMPOP") => {
+                            ListMultiPopRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"memory") | Some(b"MEMORY") => {
+                            MemoryRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"persist") | Some(b"PERSIST") => {
+                            PersistRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"pexpire") | Some(b"PEXPIRE") => {
+                            PExpireRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"pexpiretime") | Some(b"PEXPIRETIME") => {
+                            PExpireTimeRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"publish") | Some(b"PUBLISH") => {
+                            PublishRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"pttl") | Some(b"PTTL") => {
+                            PTtlRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"scan") | Some(b"SCAN") => {
+                            ScanRequest::try_from(message).map(Request::from)
+                        }
                         Some(b"set") | Some(b"SET") => {
                             SetRequest::try_from(message).map(Request::from)
                         }
+                        Some(b"setbit") | Some(b"SETBIT") => {
+                            SetBitRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"sintercard") | Some(b"SINTERCARD") => {
+                            SetInterCardRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"subscribe") | Some(b"SUBSCRIBE") => {
+                            SubscribeRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"ttl") | Some(b"TTL") => {
+                            TtlRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"type") | Some(b"TYPE") => {
+                            TypeRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"unsubscribe") | Some(b"UNSUBSCRIBE") => {
+                            UnsubscribeRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"wait") | Some(b"WAIT") => {
+                            WaitRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"zintercard") | Some(b"ZINTERCARD") => {
+                            SortedSetInterCardRequest::try_from(message).map(Request::from)
+                        }
+                        Some(b"zmpop") | Some(b"ZMPOP") => {
+                            SortedSetMultiPopRequest::try_from(message).map(Request::from)
+                        }
                         _ => Err(Error::new(ErrorKind::Other, "unknown command")),
                     },
                     _ => {
@@ -113,14 +316,55 @@ impl Parse<Request> for RequestParser {
         }
         .map(|v| ParseOk::new(v, consumed))
     }
+
+    fn bytes_needed(&self, buffer: &[u8]) -> Option<usize> {
+        // inline commands end at a CRLF, so only RESP framed requests have a
+        // length which can be used
+        self.message_parser.bytes_needed(buffer)
+    }
 }
 
 impl Compose for Request {
     fn compose(&self, buf: &mut dyn BufMut) -> usize {
         match self {
             Self::BAdd(r) => r.compose(buf),
+            Self::BitCount(r) => r.compose(buf),
+            Self::BitOp(r) => r.compose(buf),
+            Self::Debug(r) => r.compose(buf),
+            Self::Decr(r) => r.compose(buf),
+            Self::DecrBy(r) => r.compose(buf),
+            Self::Exists(r) => r.compose(buf),
+            Self::Expire(r) => r.compose(buf),
+            Self::ExpireTime(r) => r.compose(buf),
             Self::Get(r) => r.compose(buf),
+            Self::GetBit(r) => r.compose(buf),
+            Self::GetDel(r) => r.compose(buf),
+            Self::GetEx(r) => r.compose(buf),
+            Self::GetWithVersion(r) => r.compose(buf),
+            Self::HashExpire(r) => r.compose(buf),
+            Self::HashPersist(r) => r.compose(buf),
+            Self::HashTtl(r) => r.compose(buf),
+            Self::Incr(r) => r.compose(buf),
+            Self::IncrBy(r) => r.compose(buf),
+            Self::IncrByFloat(r) => r.compose(buf),
+            Self::ListMultiPop(r) => r.compose(buf),
+            Self::Memory(r) => r.compose(buf),
+            Self::Persist(r) => r.compose(buf),
+            Self::PExpire(r) => r.compose(buf),
+            Self::PExpireTime(r) => r.compose(buf),
+            Self::Publish(r) => r.compose(buf),
+            Self::PTtl(r) => r.compose(buf),
+            Self::Scan(r) => r.compose(buf),
             Self::Set(r) => r.compose(buf),
+            Self::SetBit(r) => r.compose(buf),
+            Self::SetInterCard(r) => r.compose(buf),
+            Self::Subscribe(r) => r.compose(buf),
+            Self::Ttl(r) => r.compose(buf),
+            Self::Type(r) => r.compose(buf),
+            Self::Unsubscribe(r) => r.compose(buf),
+            Self::SortedSetInterCard(r) => r.compose(buf),
+            Self::SortedSetMultiPop(r) => r.compose(buf),
+            Self::Wait(r) => r.compose(buf),
         }
     }
 }
@@ -128,8 +372,43 @@ impl Compose for Request {
 #[derive(Debug, PartialEq, Eq)]
 pub enum Request {
     BAdd(BAddRequest),
+    BitCount(BitCountRequest),
+    BitOp(BitOpRequest),
+    Debug(DebugRequest),
+    Decr(DecrRequest),
+    DecrBy(DecrByRequest),
+    Exists(ExistsRequest),
+    Expire(ExpireRequest),
+    ExpireTime(ExpireTimeRequest),
     Get(GetRequest),
+    GetBit(GetBitRequest),
+    GetDel(GetDelRequest),
+    GetEx(GetExRequest),
+    GetWithVersion(GetWithVersionRequest),
+    HashExpire(HashExpireRequest),
+    HashPersist(HashPersistRequest),
+    HashTtl(HashTtlRequest),
+    Incr(IncrRequest),
+    IncrBy(IncrByRequest),
+    IncrByFloat(IncrByFloatRequest),
+    ListMultiPop(ListMultiPopRequest),
+    Memory(MemoryRequest),
+    Persist(PersistRequest),
+    PExpire(PExpireRequest),
+    PExpireTime(PExpireTimeRequest),
+    Publish(PublishRequest),
+    PTtl(PTtlRequest),
+    Scan(ScanRequest),
     Set(SetRequest),
+    SetBit(SetBitRequest),
+    SetInterCard(SetInterCardRequest),
+    SortedSetInterCard(SortedSetInterCardRequest),
+    SortedSetMultiPop(SortedSetMultiPopRequest),
+    Subscribe(SubscribeRequest),
+    Ttl(TtlRequest),
+    Type(TypeRequest),
+    Unsubscribe(UnsubscribeRequest),
+    Wait(WaitRequest),
 }
 
 impl From<BAddRequest> for Request {
@@ -138,23 +417,320 @@ impl From<BAddRequest> for Request {
     }
 }
 
+impl From<BitCountRequest> for Request {
+    fn from(other: BitCountRequest) -> Self {
+        Self::BitCount(other)
+    }
+}
+
+impl From<BitOpRequest> for Request {
+    fn from(other: BitOpRequest) -> Self {
+        Self::BitOp(other)
+    }
+}
+
+impl From<DebugRequest> for Request {
+    fn from(other: DebugRequest) -> Self {
+        Self::Debug(other)
+    }
+}
+
+impl From<DecrRequest> for Request {
+    fn from(other: DecrRequest) -> Self {
+        Self::Decr(other)
+    }
+}
+
+impl From<DecrByRequest> for Request {
+    fn from(other: DecrByRequest) -> Self {
+        Self::DecrBy(other)
+    }
+}
+
+impl From<ExistsRequest> for Request {
+    fn from(other: ExistsRequest) -> Self {
+        Self::Exists(other)
+    }
+}
+
+impl From<ExpireRequest> for Request {
+    fn from(other: ExpireRequest) -> Self {
+        Self::Expire(other)
+    }
+}
+
+impl From<ExpireTimeRequest> for Request {
+    fn from(other: ExpireTimeRequest) -> Self {
+        Self::ExpireTime(other)
+    }
+}
+
 impl From<GetRequest> for Request {
     fn from(other: GetRequest) -> Self {
         Self::Get(other)
     }
 }
 
+impl From<GetBitRequest> for Request {
+    fn from(other: GetBitRequest) -> Self {
+        Self::GetBit(other)
+    }
+}
+
+impl From<GetDelRequest> for Request {
+    fn from(other: GetDelRequest) -> Self {
+        Self::GetDel(other)
+    }
+}
+
+impl From<GetExRequest> for Request {
+    fn from(other: GetExRequest) -> Self {
+        Self::GetEx(other)
+    }
+}
+
+impl From<GetWithVersionRequest> for Request {
+    fn from(other: GetWithVersionRequest) -> Self {
+        Self::GetWithVersion(other)
+    }
+}
+
+impl From<HashExpireRequest> for Request {
+    fn from(other: HashExpireRequest) -> Self {
+        Self::HashExpire(other)
+    }
+}
+
+impl From<HashPersistRequest> for Request {
+    fn from(other: HashPersistRequest) -> Self {
+        Self::HashPersist(other)
+    }
+}
+
+impl From<HashTtlRequest> for Request {
+    fn from(other: HashTtlRequest) -> Self {
+        Self::HashTtl(other)
+    }
+}
+
+impl From<IncrRequest> for Request {
+    fn from(other: IncrRequest) -> Self {
+        Self::Incr(other)
+    }
+}
+
+impl From<IncrByRequest> for Request {
+    fn from(other: IncrByRequest) -> Self {
+        Self::IncrBy(other)
+    }
+}
+
+impl From<IncrByFloatRequest> for Request {
+    fn from(other: IncrByFloatRequest) -> Self {
+        Self::IncrByFloat(other)
+    }
+}
+
+impl From<ListMultiPopRequest> for Request {
+    fn from(other: ListMultiPopRequest) -> Self {
+        Self::ListMultiPop(other)
+    }
+}
+
+impl From<MemoryRequest> for Request {
+    fn from(other: MemoryRequest) -> Self {
+        Self::Memory(other)
+    }
+}
+
+impl From<PersistRequest> for Request {
+    fn from(other: PersistRequest) -> Self {
+        Self::Persist(other)
+    }
+}
+
+impl From<PExpireRequest> for Request {
+    fn from(other: PExpireRequest) -> Self {
+        Self::PExpire(other)
+    }
+}
+
+impl From<PExpireTimeRequest> for Request {
+    fn from(other: PExpireTimeRequest) -> Self {
+        Self::PExpireTime(other)
+    }
+}
+
+impl From<PublishRequest> for Request {
+    fn from(other: PublishRequest) -> Self {
+        Self::Publish(other)
+    }
+}
+
+impl From<PTtlRequest> for Request {
+    fn from(other: PTtlRequest) -> Self {
+        Self::PTtl(other)
+    }
+}
+
+impl From<ScanRequest> for Request {
+    fn from(other: ScanRequest) -> Self {
+        Self::Scan(other)
+    }
+}
+
 impl From<SetRequest> for Request {
     fn from(other: SetRequest) -> Self {
         Self::Set(other)
     }
 }
 
+impl From<SetBitRequest> for Request {
+    fn from(other: SetBitRequest) -> Self {
+        Self::SetBit(other)
+    }
+}
+
+impl From<SetInterCardRequest> for Request {
+    fn from(other: SetInterCardRequest) -> Self {
+        Self::SetInterCard(other)
+    }
+}
+
+impl From<SortedSetInterCardRequest> for Request {
+    fn from(other: SortedSetInterCardRequest) -> Self {
+        Self::SortedSetInterCard(other)
+    }
+}
+
+impl From<SortedSetMultiPopRequest> for Request {
+    fn from(other: SortedSetMultiPopRequest) -> Self {
+        Self::SortedSetMultiPop(other)
+    }
+}
+
+impl From<SubscribeRequest> for Request {
+    fn from(other: SubscribeRequest) -> Self {
+        Self::Subscribe(other)
+    }
+}
+
+impl From<TtlRequest> for Request {
+    fn from(other: TtlRequest) -> Self {
+        Self::Ttl(other)
+    }
+}
+
+impl From<TypeRequest> for Request {
+    fn from(other: TypeRequest) -> Self {
+        Self::Type(other)
+    }
+}
+
+impl From<UnsubscribeRequest> for Request {
+    fn from(other: UnsubscribeRequest) -> Self {
+        Self::Unsubscribe(other)
+    }
+}
+
+impl From<WaitRequest> for Request {
+    fn from(other: WaitRequest) -> Self {
+        Self::Wait(other)
+    }
+}
+
+impl ReadOnlyMode<Message> for Request {
+    fn read_only_error(&self) -> Option<Message> {
+        match self {
+            Self::BAdd(_)
+            | Self::BitOp(_)
+            | Self::Debug(DebugRequest::Evict { .. })
+            | Self::Debug(DebugRequest::Reload)
+            | Self::Decr(_)
+            | Self::DecrBy(_)
+            | Self::Expire(_)
+            | Self::GetDel(_)
+            | Self::GetEx(_)
+            | Self::HashExpire(_)
+            | Self::HashPersist(_)
+            | Self::Incr(_)
+            | Self::IncrBy(_)
+            | Self::IncrByFloat(_)
+            | Self::ListMultiPop(_)
+            | Self::Persist(_)
+            | Self::PExpire(_)
+            | Self::Set(_)
+            | Self::SetBit(_)
+            | Self::SortedSetMultiPop(_) => Some(Message::error(
+                "READONLY You can't write against a read only replica.",
+            )),
+            Self::BitCount(_)
+            | Self::Debug(DebugRequest::Help)
+            | Self::Debug(DebugRequest::Object { .. })
+            | Self::Debug(DebugRequest::SetActiveExpire { .. })
+            | Self::Debug(DebugRequest::Verify)
+            | Self::Exists(_)
+            | Self::ExpireTime(_)
+            | Self::Get(_)
+            | Self::GetBit(_)
+            | Self::GetWithVersion(_)
+            | Self::HashTtl(_)
+            | Self::Memory(_)
+            | Self::PExpireTime(_)
+            | Self::Publish(_)
+            | Self::PTtl(_)
+            | Self::Scan(_)
+            | Self::SetInterCard(_)
+            | Self::SortedSetInterCard(_)
+            | Self::Subscribe(_)
+            | Self::Ttl(_)
+            | Self::Type(_)
+            | Self::Unsubscribe(_)
+            | Self::Wait(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Command {
     BAdd,
+    BitCount,
+    BitOp,
+    Debug,
+    Decr,
+    DecrBy,
+    Exists,
+    Expire,
+    ExpireTime,
     Get,
+    GetBit,
+    GetDel,
+    GetEx,
+    GetWithVersion,
+    HashExpire,
+    HashPersist,
+    HashTtl,
+    Incr,
+    IncrBy,
+    IncrByFloat,
+    ListMultiPop,
+    Memory,
+    Persist,
+    PExpire,
+    PExpireTime,
+    Publish,
+    PTtl,
+    Scan,
     Set,
+    SetBit,
+    SetInterCard,
+    SortedSetInterCard,
+    SortedSetMultiPop,
+    Subscribe,
+    Ttl,
+    Type,
+    Unsubscribe,
+    Wait,
 }
 
 impl TryFrom<&[u8]> for Command {
@@ -163,8 +739,43 @@ impl TryFrom<&[u8]> for Command {
     fn try_from(other: &[u8]) -> Result<Self, ()> {
         match other {
             b"badd" | b"BADD" => Ok(Command::BAdd),
+            b"bitcount" | b"BITCOUNT" => Ok(Command::BitCount),
+            b"bitop" | b"BITOP" => Ok(Command::BitOp),
+            b"debug" | b"DEBUG" => Ok(Command::Debug),
+            b"decr" | b"DECR" => Ok(Command::Decr),
+            b"decrby" | b"DECRBY" => Ok(Command::DecrBy),
+            b"exists" | b"EXISTS" => Ok(Command::Exists),
+            b"expire" | b"EXPIRE" => Ok(Command::Expire),
+            b"expiretime" | b"EXPIRETIME" => Ok(Command::ExpireTime),
             b"get" | b"GET" => Ok(Command::Get),
+            b"getbit" | b"GETBIT" => Ok(Command::GetBit),
+            b"getdel" | b"GETDEL" => Ok(Command::GetDel),
+            b"getex" | b"GETEX" => Ok(Command::GetEx),
+            b"getv" | b"GETV" => Ok(Command::GetWithVersion),
+            b"hexpire" | b"HEXPIRE" => Ok(Command::HashExpire),
+            b"hpersist" | b"HPERSIST" => Ok(Command::HashPersist),
+            b"httl" | b"HTTL" => Ok(Command::HashTtl),
+            b"incr" | b"INCR" => Ok(Command::Incr),
+            b"incrby" | b"INCRBY" => Ok(Command::IncrBy),
+            b"incrbyfloat" | b"INCRBYFLOAT" => Ok(Command::IncrByFloat),
+            b"lmpop" | b"LMPOP" => Ok(Command::ListMultiPop),
+            b"memory" | b"MEMORY" => Ok(Command::Memory),
+            b"persist" | b"PERSIST" => Ok(Command::Persist),
+            b"pexpire" | b"PEXPIRE" => Ok(Command::PExpire),
+            b"pexpiretime" | b"PEXPIRETIME" => Ok(Command::PExpireTime),
+            b"publish" | b"PUBLISH" => Ok(Command::Publish),
+            b"pttl" | b"PTTL" => Ok(Command::PTtl),
+            b"scan" | b"SCAN" => Ok(Command::Scan),
             b"set" | b"SET" => Ok(Command::Set),
+            b"setbit" | b"SETBIT" => Ok(Command::SetBit),
+            b"sintercard" | b"SINTERCARD" => Ok(Command::SetInterCard),
+            b"subscribe" | b"SUBSCRIBE" => Ok(Command::Subscribe),
+            b"ttl" | b"TTL" => Ok(Command::Ttl),
+            b"type" | b"TYPE" => Ok(Command::Type),
+            b"unsubscribe" | b"UNSUBSCRIBE" => Ok(Command::Unsubscribe),
+            b"wait" | b"WAIT" => Ok(Command::Wait),
+            b"zintercard" | b"ZINTERCARD" => Ok(Command::SortedSetInterCard),
+            b"zmpop" | b"ZMPOP" => Ok(Command::SortedSetMultiPop),
             _ => Err(()),
         }
     }
@@ -178,3 +789,89 @@ pub enum ExpireTime {
     UnixMilliseconds(u64),
     KeepTtl,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_newline() {
+        // a bare LF is an error by default
+        let parser = RequestParser::new();
+        assert!(parser.parse(b"GET test\n").is_err());
+        assert!(parser.parse(b"get\n").is_err());
+
+        // and ends an inline command when lenient
+        let parser = RequestParser::new().lenient_newlines(true);
+        let parsed = parser.parse(b"GET test\nGET next\r\n").unwrap();
+        assert_eq!(parsed.consumed(), 9);
+        assert_eq!(parsed.into_inner(), Request::Get(GetRequest::new(b"test")));
+
+        // a quoted LF is part of the value
+        let parsed = parser.parse(b"set 0 \"a\nb\"\n").unwrap();
+        assert_eq!(parsed.consumed(), 12);
+        assert_eq!(
+            parsed.into_inner(),
+            parser.parse(b"set 0 \"a\nb\"\r\n").unwrap().into_inner()
+        );
+
+        // an incomplete inline command still needs more data
+        assert_eq!(
+            parser.parse(b"get test").map(|_| ()).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        assert_eq!(
+            parser.parse(b"get test\r").map(|_| ()).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+
+        // RESP framed requests always need exact framing
+        assert!(parser.parse(b"*2\n$3\nGET\n$4\ntest\n").is_err());
+        assert!(parser.parse(b"*2\r\n$3\r\nGET\r\n$4\r\ntest\n").is_err());
+    }
+
+    #[test]
+    fn max_line_length() {
+        let parser = RequestParser::new().max_line_length(16);
+
+        // an inline command right at the limit is accepted, and the
+        // connection carries on with the next request
+        let buffer = b"set key valueabc\r\nget key\r\n";
+        let parsed = parser.parse(buffer).unwrap();
+        assert_eq!(parsed.consumed(), 18);
+        assert_eq!(
+            parser
+                .parse(&buffer[parsed.consumed()..])
+                .unwrap()
+                .into_inner(),
+            Request::Get(GetRequest::new(b"key"))
+        );
+
+        // an inline command which doesn't fit is an error, whether or not it
+        // has ended
+        for buffer in [
+            &b"set key valueabcd\r\n"[..],
+            &b"set key valueabcde"[..],
+            &b"set key \"value\nwith\nnewlines\""[..],
+        ] {
+            let error = parser.parse(buffer).map(|_| ()).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Other);
+            assert_eq!(error.to_string(), "line too long");
+        }
+
+        // until then, an unterminated command just needs more data
+        assert_eq!(
+            parser
+                .parse(b"set key valueabc\r")
+                .map(|_| ())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::WouldBlock
+        );
+
+        // RESP framed requests aren't limited
+        assert!(parser
+            .parse(b"*3\r\n$3\r\nset\r\n$3\r\nkey\r\n$16\r\nvaluevaluevalue!\r\n")
+            .is_ok());
+    }
+}
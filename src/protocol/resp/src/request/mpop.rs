@@ -0,0 +1,317 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// The end of a list which a [`ListMultiPopRequest`] pops from.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+/// The end of a sorted set which a [`SortedSetMultiPopRequest`] pops from,
+/// either the members with the lowest or the highest scores.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SortedSetEnd {
+    Min,
+    Max,
+}
+
+/// Pops up to `count` elements from the first of the keys which holds a
+/// non-empty list. The reply is an array of the key and an array of the
+/// popped elements, or a null array if every list is empty.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct ListMultiPopRequest {
+    keys: Vec<Arc<Box<[u8]>>>,
+    end: ListEnd,
+    count: Option<u64>,
+}
+
+/// Pops up to `count` members from the first of the keys which holds a
+/// non-empty sorted set. The reply is an array of the key and an array of the
+/// popped members, each with its score, or a null array if every sorted set
+/// is empty.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct SortedSetMultiPopRequest {
+    keys: Vec<Arc<Box<[u8]>>>,
+    end: SortedSetEnd,
+    count: Option<u64>,
+}
+
+/// The arguments shared by `LMPOP` and `ZMPOP`, which are
+/// `numkeys key [key ...] <end> [COUNT count]`. Returns the keys, the end
+/// which is still to be parsed by the caller, and the count if one is given.
+#[allow(clippy::type_complexity)]
+fn parse(other: Message) -> Result<(Vec<Arc<Box<[u8]>>>, String, Option<u64>), Error> {
+    if let Message::Array(array) = other {
+        if array.inner.is_none() {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        let mut array = array.inner.unwrap();
+
+        if array.len() < 4 {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        let _command = take_bulk_string(&mut array)?;
+
+        let numkeys = take_bulk_string_as_u64(&mut array)?
+            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+        // there must be at least one key, and room for the end after them
+        if numkeys == 0 || numkeys >= array.len() as u64 {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        let mut keys = Vec::with_capacity(numkeys as usize);
+        for _ in 0..numkeys {
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            keys.push(key);
+        }
+
+        let end = take_bulk_string_as_utf8(&mut array)?
+            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+        let count = if array.is_empty() {
+            None
+        } else {
+            let option = take_bulk_string_as_utf8(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if !option.eq_ignore_ascii_case("COUNT") {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            match take_bulk_string_as_u64(&mut array)? {
+                Some(count) if count > 0 => Some(count),
+                _ => {
+                    return Err(Error::new(ErrorKind::Other, "malformed command"));
+                }
+            }
+        };
+
+        if !array.is_empty() {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        Ok((keys, end, count))
+    } else {
+        Err(Error::new(ErrorKind::Other, "malformed command"))
+    }
+}
+
+#[allow(clippy::redundant_allocation)]
+fn compose_message(
+    command: &[u8],
+    keys: &[Arc<Box<[u8]>>],
+    end: &[u8],
+    count: Option<u64>,
+) -> Message {
+    let mut array = vec![
+        Message::bulk_string(command),
+        Message::bulk_string(format!("{}", keys.len()).as_bytes()),
+    ];
+    for key in keys {
+        array.push(Message::BulkString(BulkString::from(key.clone())));
+    }
+    array.push(Message::bulk_string(end));
+    if let Some(count) = count {
+        array.push(Message::bulk_string(b"COUNT"));
+        array.push(Message::bulk_string(format!("{}", count).as_bytes()));
+    }
+
+    Message::Array(Array { inner: Some(array) })
+}
+
+fn to_keys(keys: &[&[u8]]) -> Vec<Arc<Box<[u8]>>> {
+    keys.iter()
+        .map(|k| Arc::new(k.to_vec().into_boxed_slice()))
+        .collect()
+}
+
+impl TryFrom<Message> for ListMultiPopRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (keys, end, count) = parse(other)?;
+
+        let end = match end.to_ascii_uppercase().as_str() {
+            "LEFT" => ListEnd::Left,
+            "RIGHT" => ListEnd::Right,
+            _ => {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+        };
+
+        Ok(Self { keys, end, count })
+    }
+}
+
+impl ListMultiPopRequest {
+    pub fn new(keys: &[&[u8]], end: ListEnd, count: Option<u64>) -> Self {
+        Self {
+            keys: to_keys(keys),
+            end,
+            count,
+        }
+    }
+
+    /// The keys to pop from, in the order they are tried.
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|k| &***k)
+    }
+
+    pub fn end(&self) -> ListEnd {
+        self.end
+    }
+
+    /// The most elements to pop, which is one unless a count is given.
+    pub fn count(&self) -> u64 {
+        self.count.unwrap_or(1)
+    }
+}
+
+impl From<&ListMultiPopRequest> for Message {
+    fn from(other: &ListMultiPopRequest) -> Message {
+        let end: &[u8] = match other.end {
+            ListEnd::Left => b"LEFT",
+            ListEnd::Right => b"RIGHT",
+        };
+        compose_message(b"LMPOP", &other.keys, end, other.count)
+    }
+}
+
+impl Compose for ListMultiPopRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+impl TryFrom<Message> for SortedSetMultiPopRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        let (keys, end, count) = parse(other)?;
+
+        let end = match end.to_ascii_uppercase().as_str() {
+            "MIN" => SortedSetEnd::Min,
+            "MAX" => SortedSetEnd::Max,
+            _ => {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+        };
+
+        Ok(Self { keys, end, count })
+    }
+}
+
+impl SortedSetMultiPopRequest {
+    pub fn new(keys: &[&[u8]], end: SortedSetEnd, count: Option<u64>) -> Self {
+        Self {
+            keys: to_keys(keys),
+            end,
+            count,
+        }
+    }
+
+    /// The keys to pop from, in the order they are tried.
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|k| &***k)
+    }
+
+    pub fn end(&self) -> SortedSetEnd {
+        self.end
+    }
+
+    /// The most members to pop, which is one unless a count is given.
+    pub fn count(&self) -> u64 {
+        self.count.unwrap_or(1)
+    }
+}
+
+impl From<&SortedSetMultiPopRequest> for Message {
+    fn from(other: &SortedSetMultiPopRequest) -> Message {
+        let end: &[u8] = match other.end {
+            SortedSetEnd::Min => b"MIN",
+            SortedSetEnd::Max => b"MAX",
+        };
+        compose_message(b"ZMPOP", &other.keys, end, other.count)
+    }
+}
+
+impl Compose for SortedSetMultiPopRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"lmpop 2 a b LEFT\r\n").unwrap().into_inner(),
+            Request::ListMultiPop(ListMultiPopRequest::new(&[b"a", b"b"], ListEnd::Left, None))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*6\r\n$5\r\nLMPOP\r\n$1\r\n1\r\n$1\r\na\r\n$5\r\nRIGHT\r\n$5\r\nCOUNT\r\n$1\r\n3\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::ListMultiPop(ListMultiPopRequest::new(&[b"a"], ListEnd::Right, Some(3)))
+        );
+
+        let request = ListMultiPopRequest::new(&[b"a", b"b"], ListEnd::Left, None);
+        assert_eq!(request.keys().collect::<Vec<_>>(), [&b"a"[..], &b"b"[..]]);
+        assert_eq!(request.count(), 1);
+
+        // the number of keys must match the keys which are given
+        assert!(parser.parse(b"lmpop 0 left\r\n").is_err());
+        assert!(parser.parse(b"lmpop 2 a left\r\n").is_err());
+        assert!(parser.parse(b"lmpop 1 a b left\r\n").is_err());
+        assert!(parser.parse(b"lmpop two a b left\r\n").is_err());
+
+        // the end is required, and the count must be positive
+        assert!(parser.parse(b"lmpop 1 a\r\n").is_err());
+        assert!(parser.parse(b"lmpop 1 a up\r\n").is_err());
+        assert!(parser.parse(b"lmpop 1 a left count\r\n").is_err());
+        assert!(parser.parse(b"lmpop 1 a left count 0\r\n").is_err());
+        assert!(parser.parse(b"lmpop 1 a left limit 2\r\n").is_err());
+        assert!(parser.parse(b"lmpop 1 a left count 2 3\r\n").is_err());
+
+        assert_eq!(
+            parser
+                .parse(b"ZMPOP 2 a b max count 2\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::SortedSetMultiPop(SortedSetMultiPopRequest::new(
+                &[b"a", b"b"],
+                SortedSetEnd::Max,
+                Some(2)
+            ))
+        );
+
+        // sorted sets and lists have different ends
+        assert!(parser.parse(b"zmpop 1 a left\r\n").is_err());
+        assert!(parser.parse(b"lmpop 1 a min\r\n").is_err());
+    }
+}
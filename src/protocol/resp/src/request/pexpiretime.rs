@@ -0,0 +1,110 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Returns the absolute Unix time, in milliseconds, at which a key expires. The
+/// reply is an integer, which is `-1` if the key exists but has no expiry and
+/// `-2` if the key does not exist.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct PExpireTimeRequest {
+    key: Arc<Box<[u8]>>,
+}
+
+impl TryFrom<Message> for PExpireTimeRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            Ok(Self { key })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl PExpireTimeRequest {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl From<&PExpireTimeRequest> for Message {
+    fn from(other: &PExpireTimeRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"PEXPIRETIME"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+            ]),
+        })
+    }
+}
+
+impl Compose for PExpireTimeRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"pexpiretime 0\r\n").unwrap().into_inner(),
+            Request::PExpireTime(PExpireTimeRequest::new(b"0"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*2\r\n$11\r\nPEXPIRETIME\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::PExpireTime(PExpireTimeRequest::new(b"0"))
+        );
+
+        // exactly one key is required
+        assert!(parser.parse(b"pexpiretime\r\n").is_err());
+        assert!(parser.parse(b"pexpiretime 0 1\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        PExpireTimeRequest::new(b"0").compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$11\r\nPEXPIRETIME\r\n$1\r\n0\r\n");
+    }
+}
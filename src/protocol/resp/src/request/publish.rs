@@ -0,0 +1,121 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Publishes a message to a channel. The reply is an integer holding the
+/// number of subscribers the message was delivered to.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct PublishRequest {
+    channel: Arc<Box<[u8]>>,
+    message: Arc<Box<[u8]>>,
+}
+
+impl TryFrom<Message> for PublishRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 3 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let channel = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if channel.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let message = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            Ok(Self { channel, message })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl PublishRequest {
+    pub fn new(channel: &[u8], message: &[u8]) -> Self {
+        Self {
+            channel: Arc::new(channel.to_owned().into_boxed_slice()),
+            message: Arc::new(message.to_owned().into_boxed_slice()),
+        }
+    }
+
+    pub fn channel(&self) -> &[u8] {
+        &self.channel
+    }
+
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+}
+
+impl From<&PublishRequest> for Message {
+    fn from(other: &PublishRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"PUBLISH"),
+                Message::BulkString(BulkString::from(other.channel.clone())),
+                Message::BulkString(BulkString::from(other.message.clone())),
+            ]),
+        })
+    }
+}
+
+impl Compose for PublishRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"publish news hi\r\n").unwrap().into_inner(),
+            Request::Publish(PublishRequest::new(b"news", b"hi"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$7\r\nPUBLISH\r\n$4\r\nnews\r\n$0\r\n\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Publish(PublishRequest::new(b"news", b""))
+        );
+
+        assert!(parser.parse(b"publish news\r\n").is_err());
+        assert!(parser.parse(b"publish news hello world\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        PublishRequest::new(b"news", b"hello").compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*3\r\n$7\r\nPUBLISH\r\n$4\r\nnews\r\n$5\r\nhello\r\n"
+        );
+    }
+}
@@ -0,0 +1,349 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Iterates over the keys, a batch at a time. Iteration starts with a cursor
+/// of `0` and continues with the cursor from each reply until it is `0` again.
+/// The keys may be limited to those matching a glob-style `MATCH` pattern or
+/// holding a value of the given `TYPE`, and `COUNT` is a hint for how much of
+/// the keyspace to visit per call. The reply is built by [`scan_reply`].
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct ScanRequest {
+    cursor: u64,
+    pattern: Option<Arc<Box<[u8]>>>,
+    count: Option<u64>,
+    key_type: Option<Arc<Box<[u8]>>>,
+}
+
+impl TryFrom<Message> for ScanRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let cursor = take_bulk_string_as_u64(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            let mut pattern = None;
+            let mut count = None;
+            let mut key_type = None;
+
+            // the options may be given in any order, but each at most once
+            while let Some(option) = take_bulk_string_as_utf8(&mut array)? {
+                match option.to_ascii_uppercase().as_str() {
+                    "MATCH" if pattern.is_none() => {
+                        pattern = Some(
+                            take_bulk_string(&mut array)?
+                                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?,
+                        );
+                    }
+                    "COUNT" if count.is_none() => match take_bulk_string_as_u64(&mut array)? {
+                        Some(c) if c > 0 => count = Some(c),
+                        _ => {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+                    },
+                    "TYPE" if key_type.is_none() => {
+                        let t = take_bulk_string(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        if t.is_empty() {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+
+                        key_type = Some(t);
+                    }
+                    _ => {
+                        return Err(Error::new(ErrorKind::Other, "malformed command"));
+                    }
+                }
+            }
+
+            Ok(Self {
+                cursor,
+                pattern,
+                count,
+                key_type,
+            })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl ScanRequest {
+    pub fn new(
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: Option<u64>,
+        key_type: Option<&[u8]>,
+    ) -> Self {
+        Self {
+            cursor,
+            pattern: pattern.map(|p| Arc::new(p.to_owned().into_boxed_slice())),
+            count,
+            key_type: key_type.map(|t| Arc::new(t.to_owned().into_boxed_slice())),
+        }
+    }
+
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    pub fn pattern(&self) -> Option<&[u8]> {
+        self.pattern.as_ref().map(|p| p.as_ref().as_ref())
+    }
+
+    pub fn count(&self) -> Option<u64> {
+        self.count
+    }
+
+    pub fn key_type(&self) -> Option<&[u8]> {
+        self.key_type.as_ref().map(|t| t.as_ref().as_ref())
+    }
+}
+
+impl From<&ScanRequest> for Message {
+    fn from(other: &ScanRequest) -> Message {
+        let mut v = vec![
+            Message::bulk_string(b"SCAN"),
+            Message::bulk_string(format!("{}", other.cursor).as_bytes()),
+        ];
+
+        if let Some(pattern) = &other.pattern {
+            v.push(Message::bulk_string(b"MATCH"));
+            v.push(Message::BulkString(BulkString::from(pattern.clone())));
+        }
+
+        if let Some(count) = other.count {
+            v.push(Message::bulk_string(b"COUNT"));
+            v.push(Message::bulk_string(format!("{}", count).as_bytes()));
+        }
+
+        if let Some(key_type) = &other.key_type {
+            v.push(Message::bulk_string(b"TYPE"));
+            v.push(Message::BulkString(BulkString::from(key_type.clone())));
+        }
+
+        Message::Array(Array { inner: Some(v) })
+    }
+}
+
+impl Compose for ScanRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+/// Builds the reply to `SCAN`, which is an array of the cursor to continue
+/// from, as a bulk string, and an array of the keys in this batch. A cursor of
+/// `0` ends the iteration.
+pub fn scan_reply<'a>(cursor: u64, keys: impl IntoIterator<Item = &'a [u8]>) -> Message {
+    let keys = keys.into_iter().map(Message::bulk_string).collect();
+
+    Message::Array(Array {
+        inner: Some(vec![
+            Message::bulk_string(format!("{}", cursor).as_bytes()),
+            Message::Array(Array { inner: Some(keys) }),
+        ]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"scan 0\r\n").unwrap().into_inner(),
+            Request::Scan(ScanRequest::new(0, None, None, None))
+        );
+
+        assert_eq!(
+            parser.parse(b"SCAN 17\r\n").unwrap().into_inner(),
+            Request::Scan(ScanRequest::new(17, None, None, None))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*2\r\n$4\r\nSCAN\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Scan(ScanRequest::new(0, None, None, None))
+        );
+
+        // the cursor is required and must be an unsigned integer
+        assert!(parser.parse(b"scan\r\n").is_err());
+        assert!(parser.parse(b"scan abc\r\n").is_err());
+        assert!(parser.parse(b"scan -1\r\n").is_err());
+        assert!(parser.parse(b"scan 1.5\r\n").is_err());
+        assert!(parser.parse(b"scan 18446744073709551616\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_match() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser
+                .parse(b"scan 0 MATCH user:*\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Scan(ScanRequest::new(0, Some(b"user:*"), None, None))
+        );
+
+        assert_eq!(
+            parser.parse(b"scan 0 match *\r\n").unwrap().into_inner(),
+            Request::Scan(ScanRequest::new(0, Some(b"*"), None, None))
+        );
+
+        // patterns are binary safe
+        assert_eq!(
+            parser
+                .parse(b"*4\r\n$4\r\nSCAN\r\n$1\r\n0\r\n$5\r\nMATCH\r\n$4\r\na\r\n*\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Scan(ScanRequest::new(0, Some(b"a\r\n*"), None, None))
+        );
+
+        // the pattern must be given, and only once
+        assert!(parser.parse(b"scan 0 MATCH\r\n").is_err());
+        assert!(parser.parse(b"scan 0 MATCH a* MATCH b*\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_count() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"scan 0 COUNT 100\r\n").unwrap().into_inner(),
+            Request::Scan(ScanRequest::new(0, None, Some(100), None))
+        );
+
+        assert_eq!(
+            parser.parse(b"scan 5 count 1\r\n").unwrap().into_inner(),
+            Request::Scan(ScanRequest::new(5, None, Some(1), None))
+        );
+
+        // the count must be a positive integer, given only once
+        assert!(parser.parse(b"scan 0 COUNT\r\n").is_err());
+        assert!(parser.parse(b"scan 0 COUNT 0\r\n").is_err());
+        assert!(parser.parse(b"scan 0 COUNT -1\r\n").is_err());
+        assert!(parser.parse(b"scan 0 COUNT ten\r\n").is_err());
+        assert!(parser.parse(b"scan 0 COUNT 1 COUNT 2\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_type() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser
+                .parse(b"scan 0 TYPE string\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Scan(ScanRequest::new(0, None, None, Some(b"string")))
+        );
+
+        assert_eq!(
+            parser.parse(b"scan 0 type zset\r\n").unwrap().into_inner(),
+            Request::Scan(ScanRequest::new(0, None, None, Some(b"zset")))
+        );
+
+        // the type must be given, and only once
+        assert!(parser.parse(b"scan 0 TYPE\r\n").is_err());
+        assert!(parser.parse(b"scan 0 TYPE \"\"\r\n").is_err());
+        assert!(parser.parse(b"scan 0 TYPE list TYPE set\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_options() {
+        let parser = RequestParser::new();
+        let all = Request::Scan(ScanRequest::new(42, Some(b"k?y"), Some(10), Some(b"hash")));
+
+        assert_eq!(
+            parser
+                .parse(b"scan 42 MATCH k?y COUNT 10 TYPE hash\r\n")
+                .unwrap()
+                .into_inner(),
+            all
+        );
+
+        // the options may be given in any order
+        assert_eq!(
+            parser
+                .parse(b"scan 42 TYPE hash match k?y Count 10\r\n")
+                .unwrap()
+                .into_inner(),
+            all
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"scan 42 COUNT 10 MATCH k?y\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Scan(ScanRequest::new(42, Some(b"k?y"), Some(10), None))
+        );
+
+        // unknown options are rejected
+        assert!(parser.parse(b"scan 0 LIMIT 10\r\n").is_err());
+        assert!(parser.parse(b"scan 0 MATCH * 10\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        ScanRequest::new(0, None, None, None).compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$4\r\nSCAN\r\n$1\r\n0\r\n");
+
+        let mut buffer = Vec::new();
+        ScanRequest::new(42, Some(b"k*"), None, None).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*4\r\n$4\r\nSCAN\r\n$2\r\n42\r\n$5\r\nMATCH\r\n$2\r\nk*\r\n"
+        );
+
+        let mut buffer = Vec::new();
+        ScanRequest::new(1, None, Some(10), Some(b"set")).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*6\r\n$4\r\nSCAN\r\n$1\r\n1\r\n$5\r\nCOUNT\r\n$2\r\n10\r\n$4\r\nTYPE\r\n$3\r\nset\r\n"
+        );
+
+        // options are composed in the order MATCH, COUNT, TYPE
+        let mut buffer = Vec::new();
+        ScanRequest::new(7, Some(b"*"), Some(5), Some(b"list")).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*8\r\n$4\r\nSCAN\r\n$1\r\n7\r\n$5\r\nMATCH\r\n$1\r\n*\r\n$5\r\nCOUNT\r\n$1\r\n5\r\n$4\r\nTYPE\r\n$4\r\nlist\r\n"
+        );
+    }
+
+    #[test]
+    fn reply() {
+        let mut buffer = Vec::new();
+        scan_reply(0, []).compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$1\r\n0\r\n*0\r\n");
+
+        let mut buffer = Vec::new();
+        scan_reply(17, [&b"a"[..], b"bc"]).compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$2\r\n17\r\n*2\r\n$1\r\na\r\n$2\r\nbc\r\n");
+    }
+}
@@ -13,6 +13,19 @@ pub enum SetMode {
     Set,
 }
 
+/// A condition on the value stored at the key, which must hold for the new
+/// value to be stored. The key must exist for any condition to hold.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[allow(clippy::redundant_allocation)]
+pub enum SetCondition {
+    /// `IFEQ`, the stored value is equal to this one
+    Equal(Arc<Box<[u8]>>),
+    /// `IFGT`, the stored value is an integer greater than this one
+    GreaterThan(i64),
+    /// `IFLT`, the stored value is an integer less than this one
+    LessThan(i64),
+}
+
 #[derive(Debug, PartialEq, Eq)]
 #[allow(clippy::redundant_allocation)]
 pub struct SetRequest {
@@ -21,6 +34,8 @@ pub struct SetRequest {
     expire_time: Option<ExpireTime>,
     mode: SetMode,
     get_old: bool,
+    if_version: Option<u64>,
+    condition: Option<SetCondition>,
 }
 
 impl SetRequest {
@@ -43,6 +58,20 @@ impl SetRequest {
     pub fn get_old(&self) -> bool {
         self.get_old
     }
+
+    /// The version the key must have for the value to be stored, as given with
+    /// `IFVERSION`. The version of a key is read with `GETV`, and the set
+    /// fails with a `VERSIONMISMATCH` error if the key has been written since.
+    pub fn if_version(&self) -> Option<u64> {
+        self.if_version
+    }
+
+    /// The condition on the stored value, as given with `IFEQ`, `IFGT`, or
+    /// `IFLT`. This is an experimental extension, giving clients an atomic
+    /// compare-and-swap on the value itself.
+    pub fn condition(&self) -> Option<&SetCondition> {
+        self.condition.as_ref()
+    }
 }
 
 impl TryFrom<Message> for SetRequest {
@@ -75,6 +104,8 @@ impl TryFrom<Message> for SetRequest {
             let mut expire_time = None;
             let mut mode = SetMode::Set;
             let mut get_old = false;
+            let mut if_version = None;
+            let mut condition = None;
 
             while let Some(token) = take_bulk_string_as_utf8(&mut array)? {
                 match token.as_str() {
@@ -145,18 +176,71 @@ impl TryFrom<Message> for SetRequest {
 
                         get_old = true;
                     }
+                    "IFVERSION" => {
+                        if if_version.is_some() {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+
+                        let version = take_bulk_string_as_u64(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        if_version = Some(version);
+                    }
+                    "IFEQ" => {
+                        if condition.is_some() {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+
+                        let value = take_bulk_string(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        condition = Some(SetCondition::Equal(value));
+                    }
+                    "IFGT" => {
+                        if condition.is_some() {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+
+                        let value = take_bulk_string_as_i64(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        condition = Some(SetCondition::GreaterThan(value));
+                    }
+                    "IFLT" => {
+                        if condition.is_some() {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+
+                        let value = take_bulk_string_as_i64(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        condition = Some(SetCondition::LessThan(value));
+                    }
                     _ => {
                         return Err(Error::new(ErrorKind::Other, "malformed command"));
                     }
                 }
             }
 
+            // a key which must not exist can't have a version
+            if mode == SetMode::Add && if_version.is_some() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            // a condition on the value already requires the key to exist, so
+            // it doesn't combine with either mode
+            if mode != SetMode::Set && condition.is_some() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
             Ok(Self {
                 key,
                 value,
                 expire_time,
                 mode,
                 get_old,
+                if_version,
+                condition,
             })
         } else {
             Err(Error::new(ErrorKind::Other, "malformed command"))
@@ -209,6 +293,27 @@ impl From<&SetRequest> for Message {
             v.push(Message::bulk_string(b"GET"));
         }
 
+        if let Some(version) = other.if_version {
+            v.push(Message::bulk_string(b"IFVERSION"));
+            v.push(Message::bulk_string(format!("{}", version).as_bytes()));
+        }
+
+        match &other.condition {
+            Some(SetCondition::Equal(value)) => {
+                v.push(Message::bulk_string(b"IFEQ"));
+                v.push(Message::BulkString(BulkString::from(value.clone())));
+            }
+            Some(SetCondition::GreaterThan(value)) => {
+                v.push(Message::bulk_string(b"IFGT"));
+                v.push(Message::bulk_string(format!("{}", value).as_bytes()));
+            }
+            Some(SetCondition::LessThan(value)) => {
+                v.push(Message::bulk_string(b"IFLT"));
+                v.push(Message::bulk_string(format!("{}", value).as_bytes()));
+            }
+            None => {}
+        }
+
         Message::Array(Array { inner: Some(v) })
     }
 }
@@ -268,5 +373,142 @@ mod tests {
         } else {
             panic!("invalid parse result");
         }
+
+        // zero-length values are valid
+        if let Request::Set(request) = parser
+            .parse(b"*3\r\n$3\r\nset\r\n$1\r\n0\r\n$0\r\n\r\n")
+            .unwrap()
+            .into_inner()
+        {
+            assert_eq!(request.key(), b"0");
+            assert_eq!(request.value(), b"");
+        } else {
+            panic!("invalid parse result");
+        }
+
+        if let Request::Set(request) = parser.parse(b"set 0 \"\"\r\n").unwrap().into_inner() {
+            assert_eq!(request.key(), b"0");
+            assert_eq!(request.value(), b"");
+            assert_eq!(request.if_version(), None);
+        } else {
+            panic!("invalid parse result");
+        }
+    }
+
+    #[test]
+    fn if_version() {
+        let parser = RequestParser::new();
+        if let Request::Set(request) = parser
+            .parse(b"SET key value IFVERSION 42\r\n")
+            .unwrap()
+            .into_inner()
+        {
+            assert_eq!(request.key(), b"key");
+            assert_eq!(request.value(), b"value");
+            assert_eq!(request.if_version(), Some(42));
+            assert_eq!(request.mode(), SetMode::Set);
+        } else {
+            panic!("invalid parse result");
+        }
+
+        // it combines with the other options, in any order
+        if let Request::Set(request) = parser
+            .parse(b"SET key value IFVERSION 7 XX EX 10 GET\r\n")
+            .unwrap()
+            .into_inner()
+        {
+            assert_eq!(request.if_version(), Some(7));
+            assert_eq!(request.mode(), SetMode::Replace);
+            assert_eq!(request.expire_time(), Some(ExpireTime::Seconds(10)));
+            assert!(request.get_old());
+        } else {
+            panic!("invalid parse result");
+        }
+
+        // the version is required, must be a number, and can only be given once
+        assert!(parser.parse(b"SET key value IFVERSION\r\n").is_err());
+        assert!(parser.parse(b"SET key value IFVERSION abc\r\n").is_err());
+        assert!(parser.parse(b"SET key value IFVERSION -1\r\n").is_err());
+        assert!(parser
+            .parse(b"SET key value IFVERSION 1 IFVERSION 2\r\n")
+            .is_err());
+
+        // a key which must not exist can't have a version
+        assert!(parser.parse(b"SET key value NX IFVERSION 1\r\n").is_err());
+        assert!(parser.parse(b"SET key value IFVERSION 1 NX\r\n").is_err());
+    }
+
+    #[test]
+    fn condition() {
+        let parser = RequestParser::new();
+        if let Request::Set(request) = parser
+            .parse(b"SET key value IFEQ old\r\n")
+            .unwrap()
+            .into_inner()
+        {
+            assert_eq!(request.key(), b"key");
+            assert_eq!(request.value(), b"value");
+            assert_eq!(
+                request.condition(),
+                Some(&SetCondition::Equal(Arc::new(
+                    b"old".to_vec().into_boxed_slice()
+                )))
+            );
+            assert_eq!(request.mode(), SetMode::Set);
+        } else {
+            panic!("invalid parse result");
+        }
+
+        if let Request::Set(request) = parser
+            .parse(b"SET key 5 IFGT -3 EX 10\r\n")
+            .unwrap()
+            .into_inner()
+        {
+            assert_eq!(request.condition(), Some(&SetCondition::GreaterThan(-3)));
+            assert_eq!(request.expire_time(), Some(ExpireTime::Seconds(10)));
+        } else {
+            panic!("invalid parse result");
+        }
+
+        if let Request::Set(request) = parser
+            .parse(b"SET key 5 GET IFLT 8\r\n")
+            .unwrap()
+            .into_inner()
+        {
+            assert_eq!(request.condition(), Some(&SetCondition::LessThan(8)));
+            assert!(request.get_old());
+        } else {
+            panic!("invalid parse result");
+        }
+
+        // the operand is required, and must be a number to compare with
+        assert!(parser.parse(b"SET key value IFEQ\r\n").is_err());
+        assert!(parser.parse(b"SET key value IFGT\r\n").is_err());
+        assert!(parser.parse(b"SET key value IFGT abc\r\n").is_err());
+        assert!(parser.parse(b"SET key value IFLT 1.5\r\n").is_err());
+
+        // only one condition can be given
+        assert!(parser.parse(b"SET key value IFEQ a IFEQ b\r\n").is_err());
+        assert!(parser.parse(b"SET key value IFGT 1 IFLT 5\r\n").is_err());
+
+        // the key must exist for a condition to hold, so neither mode applies
+        for options in ["NX IFEQ a", "IFEQ a NX", "XX IFGT 1", "IFLT 1 XX"] {
+            let command = format!("SET key value {}\r\n", options);
+            assert!(parser.parse(command.as_bytes()).is_err());
+        }
+
+        // each condition is composed back into the same request
+        for command in [
+            &b"SET key value IFEQ old\r\n"[..],
+            &b"SET key value IFGT -3\r\n"[..],
+            &b"SET key value PX 100 IFLT 8\r\n"[..],
+        ] {
+            let request = parser.parse(command).unwrap().into_inner();
+            let mut buffer = Vec::new();
+            request.compose(&mut buffer);
+            let parsed = parser.parse(&buffer).unwrap();
+            assert_eq!(parsed.consumed(), buffer.len());
+            assert_eq!(parsed.into_inner(), request);
+        }
     }
 }
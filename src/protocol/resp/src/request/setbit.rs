@@ -0,0 +1,159 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// The largest bit offset which may be set or read. This limits a value which
+/// is grown by `SETBIT` to 512MB.
+pub const MAX_BIT_OFFSET: u64 = u32::MAX as u64;
+
+/// Sets or clears the bit at an offset in the value of a key, treating the
+/// value as an array of bits. The value is grown with zero bytes to cover the
+/// offset, and a missing key is treated as an empty value. The reply is an
+/// integer holding the previous value of the bit. Bits are numbered from the
+/// most significant bit of the first byte.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct SetBitRequest {
+    key: Arc<Box<[u8]>>,
+    offset: u64,
+    value: bool,
+}
+
+impl TryFrom<Message> for SetBitRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 4 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let offset = take_bulk_string_as_u64(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if offset > MAX_BIT_OFFSET {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let value = match take_bulk_string(&mut array)?.as_deref().map(|v| &**v) {
+                Some(b"0") => false,
+                Some(b"1") => true,
+                _ => {
+                    return Err(Error::new(ErrorKind::Other, "malformed command"));
+                }
+            };
+
+            Ok(Self { key, offset, value })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl SetBitRequest {
+    pub fn new(key: &[u8], offset: u64, value: bool) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+            offset,
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn value(&self) -> bool {
+        self.value
+    }
+}
+
+impl From<&SetBitRequest> for Message {
+    fn from(other: &SetBitRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"SETBIT"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+                Message::bulk_string(format!("{}", other.offset).as_bytes()),
+                Message::bulk_string(if other.value { b"1" } else { b"0" }),
+            ]),
+        })
+    }
+}
+
+impl Compose for SetBitRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"setbit 0 7 1\r\n").unwrap().into_inner(),
+            Request::SetBit(SetBitRequest::new(b"0", 7, true))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*4\r\n$6\r\nSETBIT\r\n$1\r\n0\r\n$2\r\n10\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::SetBit(SetBitRequest::new(b"0", 10, false))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"setbit 0 4294967295 1\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::SetBit(SetBitRequest::new(b"0", MAX_BIT_OFFSET, true))
+        );
+
+        assert!(parser.parse(b"setbit 0 7\r\n").is_err());
+        assert!(parser.parse(b"setbit 0 7 1 1\r\n").is_err());
+        assert!(parser.parse(b"setbit 0 -1 1\r\n").is_err());
+        assert!(parser.parse(b"setbit 0 4294967296 1\r\n").is_err());
+        assert!(parser.parse(b"setbit 0 7 2\r\n").is_err());
+        assert!(parser.parse(b"setbit 0 7 true\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        SetBitRequest::new(b"0", 7, true).compose(&mut buffer);
+        assert_eq!(
+            buffer,
+            b"*4\r\n$6\r\nSETBIT\r\n$1\r\n0\r\n$1\r\n7\r\n$1\r\n1\r\n"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Subscribes the connection to one or more channels. Once subscribed, the
+/// connection is in subscriber mode: it receives the messages published to
+/// its channels and may only issue pub/sub commands. Each channel is
+/// confirmed with a `subscribe` reply holding the number of channels the
+/// connection is subscribed to.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct SubscribeRequest {
+    channels: Vec<Arc<Box<[u8]>>>,
+}
+
+impl TryFrom<Message> for SubscribeRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() < 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let channels = take_channels(&mut array)?;
+
+            Ok(Self { channels })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl SubscribeRequest {
+    pub fn new(channels: &[&[u8]]) -> Self {
+        Self {
+            channels: channels
+                .iter()
+                .map(|c| Arc::new(c.to_vec().into_boxed_slice()))
+                .collect(),
+        }
+    }
+
+    pub fn channels(&self) -> impl Iterator<Item = &[u8]> {
+        self.channels.iter().map(|c| &***c)
+    }
+}
+
+impl From<&SubscribeRequest> for Message {
+    fn from(other: &SubscribeRequest) -> Message {
+        let mut array = vec![Message::bulk_string(b"SUBSCRIBE")];
+        for channel in &other.channels {
+            array.push(Message::BulkString(BulkString::from(channel.clone())));
+        }
+
+        Message::Array(Array { inner: Some(array) })
+    }
+}
+
+impl Compose for SubscribeRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let mut values = vec![&b"SUBSCRIBE"[..]];
+        values.extend(self.channels());
+        compose_array(buf, &values)
+    }
+}
+
+/// Takes the remaining elements of a pub/sub command as channel names, none
+/// of which may be empty.
+#[allow(clippy::redundant_allocation)]
+pub(crate) fn take_channels(array: &mut Vec<Message>) -> Result<Vec<Arc<Box<[u8]>>>, Error> {
+    let mut channels = Vec::with_capacity(array.len());
+    while !array.is_empty() {
+        let channel =
+            take_bulk_string(array)?.ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+        if channel.is_empty() {
+            return Err(Error::new(ErrorKind::Other, "malformed command"));
+        }
+
+        channels.push(channel);
+    }
+    Ok(channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"subscribe news\r\n").unwrap().into_inner(),
+            Request::Subscribe(SubscribeRequest::new(&[b"news"]))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$9\r\nSUBSCRIBE\r\n$4\r\nnews\r\n$7\r\nweather\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Subscribe(SubscribeRequest::new(&[b"news", b"weather"]))
+        );
+
+        assert!(parser.parse(b"subscribe\r\n").is_err());
+        assert!(parser
+            .parse(b"*2\r\n$9\r\nSUBSCRIBE\r\n$0\r\n\r\n")
+            .is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        SubscribeRequest::new(&[b"news"]).compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$9\r\nSUBSCRIBE\r\n$4\r\nnews\r\n");
+    }
+}
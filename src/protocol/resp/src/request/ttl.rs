@@ -0,0 +1,130 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Returns the number of seconds until a key expires. The reply is an integer,
+/// which is `-1` if the key exists but has no expiry and `-2` if the key does
+/// not exist.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct TtlRequest {
+    key: Arc<Box<[u8]>>,
+}
+
+impl TryFrom<Message> for TtlRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let key = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if key.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            Ok(Self { key })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl TtlRequest {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: Arc::new(key.to_owned().into_boxed_slice()),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl From<&TtlRequest> for Message {
+    fn from(other: &TtlRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"TTL"),
+                Message::BulkString(BulkString::from(other.key.clone())),
+            ]),
+        })
+    }
+}
+
+impl Compose for TtlRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"ttl 0\r\n").unwrap().into_inner(),
+            Request::Ttl(TtlRequest::new(b"0"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*2\r\n$3\r\nTTL\r\n$1\r\n0\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Ttl(TtlRequest::new(b"0"))
+        );
+
+        // exactly one key is required
+        assert!(parser.parse(b"ttl\r\n").is_err());
+        assert!(parser.parse(b"ttl 0 1\r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        TtlRequest::new(b"0").compose(&mut buffer);
+        assert_eq!(buffer, b"*2\r\n$3\r\nTTL\r\n$1\r\n0\r\n");
+    }
+
+    #[test]
+    fn reply() {
+        // the reply is the remaining seconds, or a negative integer which
+        // signals a missing key or a key without an expiry
+        let parser = ResponseParser::default();
+        for (ttl, golden) in [
+            (10, &b":10\r\n"[..]),
+            (-1, &b":-1\r\n"[..]),
+            (-2, &b":-2\r\n"[..]),
+        ] {
+            let mut buffer = Vec::new();
+            Response::integer(ttl).compose(&mut buffer);
+            assert_eq!(buffer, golden);
+
+            let parsed = parser.parse(golden).unwrap();
+            assert_eq!(parsed.consumed(), golden.len());
+            assert_eq!(parsed.into_inner(), Response::integer(ttl));
+        }
+    }
+}
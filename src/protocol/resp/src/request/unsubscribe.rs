@@ -0,0 +1,110 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::subscribe::take_channels;
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Unsubscribes the connection from the given channels, or from all of its
+/// channels if none are given. Each channel is confirmed with an
+/// `unsubscribe` reply holding the number of channels the connection is
+/// still subscribed to, and the connection leaves subscriber mode once that
+/// reaches zero.
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct UnsubscribeRequest {
+    channels: Vec<Arc<Box<[u8]>>>,
+}
+
+impl TryFrom<Message> for UnsubscribeRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let channels = take_channels(&mut array)?;
+
+            Ok(Self { channels })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl UnsubscribeRequest {
+    pub fn new(channels: &[&[u8]]) -> Self {
+        Self {
+            channels: channels
+                .iter()
+                .map(|c| Arc::new(c.to_vec().into_boxed_slice()))
+                .collect(),
+        }
+    }
+
+    /// The channels to unsubscribe from, which is empty when unsubscribing
+    /// from all channels.
+    pub fn channels(&self) -> impl Iterator<Item = &[u8]> {
+        self.channels.iter().map(|c| &***c)
+    }
+}
+
+impl From<&UnsubscribeRequest> for Message {
+    fn from(other: &UnsubscribeRequest) -> Message {
+        let mut array = vec![Message::bulk_string(b"UNSUBSCRIBE")];
+        for channel in &other.channels {
+            array.push(Message::BulkString(BulkString::from(channel.clone())));
+        }
+
+        Message::Array(Array { inner: Some(array) })
+    }
+}
+
+impl Compose for UnsubscribeRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let mut values = vec![&b"UNSUBSCRIBE"[..]];
+        values.extend(self.channels());
+        compose_array(buf, &values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"unsubscribe news\r\n").unwrap().into_inner(),
+            Request::Unsubscribe(UnsubscribeRequest::new(&[b"news"]))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*1\r\n$11\r\nUNSUBSCRIBE\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Unsubscribe(UnsubscribeRequest::new(&[]))
+        );
+
+        assert!(parser
+            .parse(b"*2\r\n$11\r\nUNSUBSCRIBE\r\n$0\r\n\r\n")
+            .is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        UnsubscribeRequest::new(&[]).compose(&mut buffer);
+        assert_eq!(buffer, b"*1\r\n$11\r\nUNSUBSCRIBE\r\n");
+    }
+}
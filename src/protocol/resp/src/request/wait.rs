@@ -0,0 +1,131 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+
+/// Blocks until prior writes are acknowledged by the given number of replicas
+/// or the timeout (in milliseconds) elapses.
+///
+/// Replication is not supported, so there are never any replicas to wait for.
+/// `WAIT` is a no-op which should be answered immediately with the integer
+/// `0`, allowing clients which issue it after each write to carry on.
+#[derive(Debug, PartialEq, Eq)]
+pub struct WaitRequest {
+    replicas: u64,
+    timeout: u64,
+}
+
+impl TryFrom<Message> for WaitRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 3 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let replicas = take_bulk_string_as_u64(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            let timeout = take_bulk_string_as_u64(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            Ok(Self { replicas, timeout })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl WaitRequest {
+    pub fn new(replicas: u64, timeout: u64) -> Self {
+        Self { replicas, timeout }
+    }
+
+    pub fn replicas(&self) -> u64 {
+        self.replicas
+    }
+
+    /// The timeout in milliseconds, where zero means to wait forever.
+    pub fn timeout(&self) -> u64 {
+        self.timeout
+    }
+}
+
+impl From<&WaitRequest> for Message {
+    fn from(other: &WaitRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::bulk_string(b"WAIT"),
+                Message::bulk_string(format!("{}", other.replicas).as_bytes()),
+                Message::bulk_string(format!("{}", other.timeout).as_bytes()),
+            ]),
+        })
+    }
+}
+
+impl Compose for WaitRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"wait 0 0\r\n").unwrap().into_inner(),
+            Request::Wait(WaitRequest::new(0, 0))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$3\r\n100\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Wait(WaitRequest::new(1, 100))
+        );
+
+        assert!(parser.parse(b"wait 0\r\n").is_err());
+        assert!(parser.parse(b"wait 0 0 0\r\n").is_err());
+        assert!(parser.parse(b"wait -1 0\r\n").is_err());
+        assert!(parser.parse(b"wait 0 forever\r\n").is_err());
+    }
+
+    #[test]
+    fn after_set() {
+        // clients which want durability pipeline a WAIT behind their writes
+        let parser = RequestParser::new();
+        let buffer = b"set 0 1\r\nwait 0 0\r\n";
+
+        let parsed = parser.parse(buffer).unwrap();
+        let consumed = parsed.consumed();
+        assert!(matches!(parsed.into_inner(), Request::Set(_)));
+
+        let parsed = parser.parse(&buffer[consumed..]).unwrap();
+        assert_eq!(consumed + parsed.consumed(), buffer.len());
+        assert_eq!(parsed.into_inner(), Request::Wait(WaitRequest::new(0, 0)));
+    }
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        WaitRequest::new(0, 0).compose(&mut buffer);
+        assert_eq!(buffer, b"*3\r\n$4\r\nWAIT\r\n$1\r\n0\r\n$1\r\n0\r\n");
+    }
+}
@@ -0,0 +1,361 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Golden encodings for requests and responses. Each value is composed and
+//! checked byte-for-byte against its expected encoding, and the expected
+//! encoding is parsed to check that it gives back the same value. Covering a
+//! new command only takes another line in one of the tables below.
+
+use crate::message::Array;
+use crate::*;
+use protocol_common::Parse;
+use std::fmt::Debug;
+
+#[test]
+fn requests() {
+    let parser = RequestParser::new();
+    let check = |request: Request, golden: &[u8]| check(&parser, request, golden);
+
+    check(
+        inline("badd outer inner 42"),
+        b"*4\r\n$4\r\nBADD\r\n$5\r\nouter\r\n$5\r\ninner\r\n$2\r\n42\r\n",
+    );
+    check(
+        BitCountRequest::new(b"0", None).into(),
+        b"*2\r\n$8\r\nBITCOUNT\r\n$1\r\n0\r\n",
+    );
+    check(
+        BitCountRequest::new(b"0", Some(BitRange::new(1, -1, BitUnit::Bit))).into(),
+        b"*5\r\n$8\r\nBITCOUNT\r\n$1\r\n0\r\n$1\r\n1\r\n$2\r\n-1\r\n$3\r\nBIT\r\n",
+    );
+    check(
+        BitOpRequest::new(BitOperation::Xor, b"0", &[b"1", b"2"]).into(),
+        b"*5\r\n$5\r\nBITOP\r\n$3\r\nXOR\r\n$1\r\n0\r\n$1\r\n1\r\n$1\r\n2\r\n",
+    );
+    check(
+        DebugRequest::evict(2).into(),
+        b"*3\r\n$5\r\nDEBUG\r\n$5\r\nEVICT\r\n$1\r\n2\r\n",
+    );
+    check(
+        DebugRequest::object(b"0").into(),
+        b"*3\r\n$5\r\nDEBUG\r\n$6\r\nOBJECT\r\n$1\r\n0\r\n",
+    );
+    check(
+        DebugRequest::set_active_expire(false).into(),
+        b"*3\r\n$5\r\nDEBUG\r\n$17\r\nSET-ACTIVE-EXPIRE\r\n$1\r\n0\r\n",
+    );
+    check(
+        DebugRequest::Reload.into(),
+        b"*2\r\n$5\r\nDEBUG\r\n$6\r\nRELOAD\r\n",
+    );
+    check(
+        DebugRequest::Verify.into(),
+        b"*2\r\n$5\r\nDEBUG\r\n$6\r\nVERIFY\r\n",
+    );
+    check(
+        DecrRequest::new(b"0").into(),
+        b"*2\r\n$4\r\nDECR\r\n$1\r\n0\r\n",
+    );
+    check(
+        DecrByRequest::new(b"0", 10).into(),
+        b"*3\r\n$6\r\nDECRBY\r\n$1\r\n0\r\n$2\r\n10\r\n",
+    );
+    check(
+        ExistsRequest::new(&[b"0", b"1"]).into(),
+        b"*3\r\n$6\r\nEXISTS\r\n$1\r\n0\r\n$1\r\n1\r\n",
+    );
+    check(
+        ExpireRequest::new(b"0", 10).into(),
+        b"*3\r\n$6\r\nEXPIRE\r\n$1\r\n0\r\n$2\r\n10\r\n",
+    );
+    check(
+        ExpireRequest::new(b"0", -1).into(),
+        b"*3\r\n$6\r\nEXPIRE\r\n$1\r\n0\r\n$2\r\n-1\r\n",
+    );
+    check(
+        ExpireTimeRequest::new(b"0").into(),
+        b"*2\r\n$10\r\nEXPIRETIME\r\n$1\r\n0\r\n",
+    );
+    check(
+        GetRequest::new(b"0").into(),
+        b"*2\r\n$3\r\nGET\r\n$1\r\n0\r\n",
+    );
+    check(
+        GetRequest::new(b"\0\r\n key").into(),
+        b"*2\r\n$3\r\nGET\r\n$7\r\n\0\r\n key\r\n",
+    );
+    check(
+        GetBitRequest::new(b"0", 7).into(),
+        b"*3\r\n$6\r\nGETBIT\r\n$1\r\n0\r\n$1\r\n7\r\n",
+    );
+    check(
+        GetDelRequest::new(b"0").into(),
+        b"*2\r\n$6\r\nGETDEL\r\n$1\r\n0\r\n",
+    );
+    check(
+        GetExRequest::new(b"0", None).into(),
+        b"*2\r\n$5\r\nGETEX\r\n$1\r\n0\r\n",
+    );
+    check(
+        GetWithVersionRequest::new(b"0").into(),
+        b"*2\r\n$4\r\nGETV\r\n$1\r\n0\r\n",
+    );
+    check(
+        GetExRequest::new(b"0", Some(ExpireTime::Milliseconds(1500))).into(),
+        b"*4\r\n$5\r\nGETEX\r\n$1\r\n0\r\n$2\r\nPX\r\n$4\r\n1500\r\n",
+    );
+    check(
+        GetExRequest::persist(b"0").into(),
+        b"*3\r\n$5\r\nGETEX\r\n$1\r\n0\r\n$7\r\nPERSIST\r\n",
+    );
+    check(
+        HashExpireRequest::new(b"0", 10, Some(ExpireCondition::Xx), &[b"a"]).into(),
+        b"*7\r\n$7\r\nHEXPIRE\r\n$1\r\n0\r\n$2\r\n10\r\n$2\r\nXX\r\n$6\r\nFIELDS\r\n$1\r\n1\r\n$1\r\na\r\n",
+    );
+    check(
+        HashPersistRequest::new(b"0", &[b"a"]).into(),
+        b"*5\r\n$8\r\nHPERSIST\r\n$1\r\n0\r\n$6\r\nFIELDS\r\n$1\r\n1\r\n$1\r\na\r\n",
+    );
+    check(
+        HashTtlRequest::new(b"0", &[b"a", b"b"]).into(),
+        b"*6\r\n$4\r\nHTTL\r\n$1\r\n0\r\n$6\r\nFIELDS\r\n$1\r\n2\r\n$1\r\na\r\n$1\r\nb\r\n",
+    );
+    check(
+        IncrRequest::new(b"0").into(),
+        b"*2\r\n$4\r\nINCR\r\n$1\r\n0\r\n",
+    );
+    check(
+        IncrByRequest::new(b"0", -5).into(),
+        b"*3\r\n$6\r\nINCRBY\r\n$1\r\n0\r\n$2\r\n-5\r\n",
+    );
+    check(
+        IncrByFloatRequest::new(b"0", 0.25).into(),
+        b"*3\r\n$11\r\nINCRBYFLOAT\r\n$1\r\n0\r\n$4\r\n0.25\r\n",
+    );
+    check(
+        ListMultiPopRequest::new(&[b"0", b"1"], ListEnd::Left, None).into(),
+        b"*5\r\n$5\r\nLMPOP\r\n$1\r\n2\r\n$1\r\n0\r\n$1\r\n1\r\n$4\r\nLEFT\r\n",
+    );
+    check(
+        ListMultiPopRequest::new(&[b"0"], ListEnd::Right, Some(3)).into(),
+        b"*6\r\n$5\r\nLMPOP\r\n$1\r\n1\r\n$1\r\n0\r\n$5\r\nRIGHT\r\n$5\r\nCOUNT\r\n$1\r\n3\r\n",
+    );
+    check(
+        MemoryRequest::usage(b"0").into(),
+        b"*3\r\n$6\r\nMEMORY\r\n$5\r\nUSAGE\r\n$1\r\n0\r\n",
+    );
+    check(
+        MemoryRequest::Doctor.into(),
+        b"*2\r\n$6\r\nMEMORY\r\n$6\r\nDOCTOR\r\n",
+    );
+    check(
+        MemoryRequest::Help.into(),
+        b"*2\r\n$6\r\nMEMORY\r\n$4\r\nHELP\r\n",
+    );
+    check(
+        MemoryRequest::Stats.into(),
+        b"*2\r\n$6\r\nMEMORY\r\n$5\r\nSTATS\r\n",
+    );
+    check(
+        PersistRequest::new(b"0").into(),
+        b"*2\r\n$7\r\nPERSIST\r\n$1\r\n0\r\n",
+    );
+    check(
+        PExpireRequest::new(b"0", 1500).into(),
+        b"*3\r\n$7\r\nPEXPIRE\r\n$1\r\n0\r\n$4\r\n1500\r\n",
+    );
+    check(
+        PExpireTimeRequest::new(b"0").into(),
+        b"*2\r\n$11\r\nPEXPIRETIME\r\n$1\r\n0\r\n",
+    );
+    check(
+        PublishRequest::new(b"news", b"hello").into(),
+        b"*3\r\n$7\r\nPUBLISH\r\n$4\r\nnews\r\n$5\r\nhello\r\n",
+    );
+    check(
+        PTtlRequest::new(b"0").into(),
+        b"*2\r\n$4\r\nPTTL\r\n$1\r\n0\r\n",
+    );
+    check(
+        ScanRequest::new(0, None, None, None).into(),
+        b"*2\r\n$4\r\nSCAN\r\n$1\r\n0\r\n",
+    );
+    check(
+        ScanRequest::new(3, Some(b"k*"), Some(10), Some(b"string")).into(),
+        b"*8\r\n$4\r\nSCAN\r\n$1\r\n3\r\n$5\r\nMATCH\r\n$2\r\nk*\r\n$5\r\nCOUNT\r\n$2\r\n10\r\n$4\r\nTYPE\r\n$6\r\nstring\r\n",
+    );
+    check(
+        inline("set 0 1"),
+        b"*3\r\n$3\r\nSET\r\n$1\r\n0\r\n$1\r\n1\r\n",
+    );
+    check(
+        inline("set 0 \"\""),
+        b"*3\r\n$3\r\nSET\r\n$1\r\n0\r\n$0\r\n\r\n",
+    );
+    check(
+        inline("set 0 1 EX 10"),
+        b"*5\r\n$3\r\nSET\r\n$1\r\n0\r\n$1\r\n1\r\n$2\r\nEX\r\n$2\r\n10\r\n",
+    );
+    check(
+        inline("set 0 1 PX 100 NX GET"),
+        b"*7\r\n$3\r\nSET\r\n$1\r\n0\r\n$1\r\n1\r\n$2\r\nPX\r\n$3\r\n100\r\n$2\r\nNX\r\n$3\r\nGET\r\n",
+    );
+    check(
+        inline("set 0 1 EXAT 1700000000 XX"),
+        b"*6\r\n$3\r\nSET\r\n$1\r\n0\r\n$1\r\n1\r\n$4\r\nEXAT\r\n$10\r\n1700000000\r\n$2\r\nXX\r\n",
+    );
+    check(
+        inline("set 0 1 KEEPTTL"),
+        b"*4\r\n$3\r\nSET\r\n$1\r\n0\r\n$1\r\n1\r\n$7\r\nKEEPTTL\r\n",
+    );
+    check(
+        inline("set 0 1 XX IFVERSION 42"),
+        b"*6\r\n$3\r\nSET\r\n$1\r\n0\r\n$1\r\n1\r\n$2\r\nXX\r\n$9\r\nIFVERSION\r\n$2\r\n42\r\n",
+    );
+    check(
+        inline("set 0 1 IFEQ 2"),
+        b"*5\r\n$3\r\nSET\r\n$1\r\n0\r\n$1\r\n1\r\n$4\r\nIFEQ\r\n$1\r\n2\r\n",
+    );
+    check(
+        inline("set 0 1 IFLT -2"),
+        b"*5\r\n$3\r\nSET\r\n$1\r\n0\r\n$1\r\n1\r\n$4\r\nIFLT\r\n$2\r\n-2\r\n",
+    );
+    check(
+        SetBitRequest::new(b"0", 7, true).into(),
+        b"*4\r\n$6\r\nSETBIT\r\n$1\r\n0\r\n$1\r\n7\r\n$1\r\n1\r\n",
+    );
+    check(
+        SetInterCardRequest::new(&[b"0", b"1"], Some(2)).into(),
+        b"*6\r\n$10\r\nSINTERCARD\r\n$1\r\n2\r\n$1\r\n0\r\n$1\r\n1\r\n$5\r\nLIMIT\r\n$1\r\n2\r\n",
+    );
+    check(
+        SubscribeRequest::new(&[b"news", b"weather"]).into(),
+        b"*3\r\n$9\r\nSUBSCRIBE\r\n$4\r\nnews\r\n$7\r\nweather\r\n",
+    );
+    check(
+        TtlRequest::new(b"0").into(),
+        b"*2\r\n$3\r\nTTL\r\n$1\r\n0\r\n",
+    );
+    check(
+        TypeRequest::new(b"0").into(),
+        b"*2\r\n$4\r\nTYPE\r\n$1\r\n0\r\n",
+    );
+    check(
+        UnsubscribeRequest::new(&[b"news"]).into(),
+        b"*2\r\n$11\r\nUNSUBSCRIBE\r\n$4\r\nnews\r\n",
+    );
+    check(
+        UnsubscribeRequest::new(&[]).into(),
+        b"*1\r\n$11\r\nUNSUBSCRIBE\r\n",
+    );
+    check(
+        WaitRequest::new(1, 100).into(),
+        b"*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$3\r\n100\r\n",
+    );
+    check(
+        SortedSetInterCardRequest::new(&[b"0"], None).into(),
+        b"*3\r\n$10\r\nZINTERCARD\r\n$1\r\n1\r\n$1\r\n0\r\n",
+    );
+    check(
+        SortedSetMultiPopRequest::new(&[b"0"], SortedSetEnd::Max, Some(2)).into(),
+        b"*6\r\n$5\r\nZMPOP\r\n$1\r\n1\r\n$1\r\n0\r\n$3\r\nMAX\r\n$5\r\nCOUNT\r\n$1\r\n2\r\n",
+    );
+}
+
+#[test]
+fn responses() {
+    let parser = ResponseParser::default();
+    let check = |response: Response, golden: &[u8]| check(&parser, response, golden);
+
+    check(Response::simple_string("OK"), b"+OK\r\n");
+    check(
+        Response::error("ERR unknown command"),
+        b"-ERR unknown command\r\n",
+    );
+    check(Response::integer(0), b":0\r\n");
+    check(Response::integer(-1), b":-1\r\n");
+    check(Response::integer(-2), b":-2\r\n");
+    check(Response::integer(i64::MAX), b":9223372036854775807\r\n");
+    check(Response::bulk_string(b"COFFEE"), b"$6\r\nCOFFEE\r\n");
+    check(Response::bulk_string(b""), b"$0\r\n\r\n");
+    check(Response::bulk_string(b"\r\n"), b"$2\r\n\r\n\r\n");
+    check(Response::null(), b"$-1\r\n");
+    check(array(None), b"*-1\r\n");
+    check(array(Some(vec![])), b"*0\r\n");
+    check(
+        array(Some(vec![Response::integer(1), Response::null()])),
+        b"*2\r\n:1\r\n$-1\r\n",
+    );
+    check(
+        array(Some(vec![
+            array(Some(vec![Response::bulk_string(b"a")])),
+            Response::simple_string("OK"),
+        ])),
+        b"*2\r\n*1\r\n$1\r\na\r\n+OK\r\n",
+    );
+    check(
+        scan_reply(12, [&b"a"[..]]),
+        b"*2\r\n$2\r\n12\r\n*1\r\n$1\r\na\r\n",
+    );
+}
+
+// requests without a public constructor are built from the inline form
+fn inline(command: &str) -> Request {
+    RequestParser::new()
+        .parse(format!("{}\r\n", command).as_bytes())
+        .expect("bad inline command")
+        .into_inner()
+}
+
+fn array(values: Option<Vec<Response>>) -> Response {
+    Response::Array(Array { inner: values })
+}
+
+/// Checks that the value composes to exactly the golden bytes, and that the
+/// golden bytes parse back into the value.
+#[track_caller]
+fn check<T, P>(parser: &P, value: T, golden: &[u8])
+where
+    T: Compose + Debug + PartialEq,
+    P: Parse<T>,
+{
+    let mut composed = Vec::new();
+    let len = value.compose(&mut composed);
+
+    if composed != golden {
+        let offset = composed
+            .iter()
+            .zip(golden)
+            .take_while(|(a, b)| a == b)
+            .count();
+        panic!(
+            "{:?} composed incorrectly, first difference at byte {}\n\
+             expected: \"{}\"\n  actual: \"{}\"",
+            value,
+            offset,
+            escape(golden),
+            escape(&composed),
+        );
+    }
+    assert_eq!(len, composed.len(), "wrong length returned for {:?}", value);
+
+    let parsed = parser
+        .parse(golden)
+        .unwrap_or_else(|e| panic!("failed to parse \"{}\": {}", escape(golden), e));
+    assert_eq!(
+        parsed.consumed(),
+        golden.len(),
+        "parsing \"{}\" left bytes unconsumed",
+        escape(golden),
+    );
+    assert_eq!(parsed.into_inner(), value);
+}
+
+fn escape(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .flat_map(|b| std::ascii::escape_default(*b))
+        .map(char::from)
+        .collect()
+}
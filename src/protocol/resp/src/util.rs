@@ -84,3 +84,17 @@ pub fn take_bulk_string_as_u64(array: &mut Vec<Message>) -> Result<Option<u64>,
         .map_err(|_| Error::new(ErrorKind::Other, "bulk string is not a u64"))
         .map(|v| Some(v))
 }
+
+pub fn take_bulk_string_as_i64(array: &mut Vec<Message>) -> Result<Option<i64>, Error> {
+    let s = take_bulk_string(array)?;
+
+    if s.is_none() {
+        return Ok(None);
+    }
+
+    std::str::from_utf8(&s.unwrap())
+        .map_err(|_| Error::new(ErrorKind::Other, "bulk string not valid utf8"))?
+        .parse::<i64>()
+        .map_err(|_| Error::new(ErrorKind::Other, "bulk string is not an i64"))
+        .map(|v| Some(v))
+}
@@ -2,23 +2,71 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
-//! A protocol crate for Thrift binary protocol.
+//! A protocol crate for Thrift framed transport, carrying messages encoded
+//! with either the binary or the compact protocol.
 
 use protocol_common::BufMut;
 use protocol_common::Compose;
 use protocol_common::Parse;
+use protocol_common::ParseErrorReply;
 use protocol_common::ParseOk;
 use rustcommon_metrics::*;
 
 const THRIFT_HEADER_LEN: usize = std::mem::size_of::<u32>();
 
+// strict binary protocol message header
+const VERSION_MASK: u32 = 0xffff0000;
+const VERSION_1: u32 = 0x80010000;
+const MESSAGE_TYPE_EXCEPTION: u32 = 3;
+
+// the first byte of every compact protocol message
+const COMPACT_PROTOCOL_ID: u8 = 0x82;
+
+// field types used when encoding a `TApplicationException`
+const TYPE_STOP: u8 = 0;
+const TYPE_I32: u8 = 8;
+const TYPE_STRING: u8 = 11;
+
+/// `TApplicationException` type for a message the server could not accept.
+pub const PROTOCOL_ERROR: i32 = 7;
+
 // Stats
 counter!(MESSAGES_PARSED);
+counter!(MESSAGES_PARSED_BINARY);
+counter!(MESSAGES_PARSED_COMPACT);
 counter!(MESSAGES_COMPOSED);
+counter!(MESSAGES_COMPOSED_BINARY);
+counter!(MESSAGES_COMPOSED_COMPACT);
+
+/// The protocol used to encode the messages within the frames. Both are
+/// framed the same way, with a 4 byte length prefix.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolVariant {
+    /// The binary protocol. Strict binary messages begin with the version
+    /// `0x8001`.
+    Binary,
+    /// The compact protocol. Messages begin with the protocol id `0x82`.
+    Compact,
+}
+
+impl ProtocolVariant {
+    /// Checks whether the message body begins with the header for this
+    /// protocol.
+    fn is_header(&self, data: &[u8]) -> bool {
+        match self {
+            Self::Binary => data
+                .get(0..4)
+                .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]) & VERSION_MASK == VERSION_1)
+                .unwrap_or(false),
+            Self::Compact => data.first() == Some(&COMPACT_PROTOCOL_ID),
+        }
+    }
+}
 
 /// An opaque Thrift message
 pub struct Message {
     data: Box<[u8]>,
+    variant: ProtocolVariant,
 }
 
 #[allow(clippy::len_without_is_empty)]
@@ -26,31 +74,161 @@ impl Message {
     pub fn len(&self) -> usize {
         self.data.len()
     }
+
+    /// The protocol the message was framed as.
+    pub fn variant(&self) -> ProtocolVariant {
+        self.variant
+    }
+
+    /// Returns `true` if the message begins with the compact protocol id, so
+    /// that messages in the wrong encoding can be rejected early.
+    pub fn is_compact(&self) -> bool {
+        ProtocolVariant::Compact.is_header(&self.data)
+    }
+
+    /// Creates an exception reply to the call with the given method name and
+    /// sequence id. The message body is a `TApplicationException` encoded with
+    /// the strict binary protocol, which any thrift client can decode.
+    pub fn application_exception(name: &[u8], seqid: i32, kind: i32, message: &str) -> Self {
+        let mut data = Vec::with_capacity(name.len() + message.len() + 27);
+
+        // message header
+        data.extend_from_slice(&(VERSION_1 | MESSAGE_TYPE_EXCEPTION).to_be_bytes());
+        data.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        data.extend_from_slice(name);
+        data.extend_from_slice(&seqid.to_be_bytes());
+
+        // field 1: message
+        data.push(TYPE_STRING);
+        data.extend_from_slice(&1_i16.to_be_bytes());
+        data.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        data.extend_from_slice(message.as_bytes());
+
+        // field 2: type
+        data.push(TYPE_I32);
+        data.extend_from_slice(&2_i16.to_be_bytes());
+        data.extend_from_slice(&kind.to_be_bytes());
+
+        data.push(TYPE_STOP);
+
+        Self {
+            data: data.into_boxed_slice(),
+            variant: ProtocolVariant::Binary,
+        }
+    }
 }
 
 impl Compose for Message {
     fn compose(&self, session: &mut dyn BufMut) -> usize {
-        MESSAGES_COMPOSED.increment();
-        session.put_slice(&(self.data.len() as u32).to_be_bytes());
-        session.put_slice(&self.data);
-        std::mem::size_of::<u32>() + self.data.len()
+        compose_frame(&self.data, self.variant, session)
+    }
+}
+
+/// A Thrift message which borrows its body from the buffer it was parsed
+/// from. Forwarding a borrowed message avoids copying the body, but it must be
+/// composed before the buffer is advanced. Use [`BorrowedMessage::to_owned`]
+/// when the message needs to outlive the buffer.
+///
+/// The thrift proxy can't forward borrowed messages. Its frontend and backend
+/// run on separate threads and hand each message over a queue, so the message
+/// must be owned before the frontend session's buffer is advanced. The proxy
+/// parses with [`Parse`], which borrows and then makes the one copy needed.
+pub struct BorrowedMessage<'a> {
+    data: &'a [u8],
+    variant: ProtocolVariant,
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl<'a> BorrowedMessage<'a> {
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The protocol the message was framed as.
+    pub fn variant(&self) -> ProtocolVariant {
+        self.variant
+    }
+
+    /// Returns `true` if the message begins with the compact protocol id.
+    pub fn is_compact(&self) -> bool {
+        ProtocolVariant::Compact.is_header(self.data)
+    }
+
+    /// Copies the body into an owned [`Message`].
+    pub fn to_owned(&self) -> Message {
+        Message {
+            data: self.data.to_vec().into_boxed_slice(),
+            variant: self.variant,
+        }
+    }
+}
+
+impl<'a> Compose for BorrowedMessage<'a> {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        compose_frame(self.data, self.variant, session)
     }
 }
 
+// writes the length prefix and the body of a message
+fn compose_frame(data: &[u8], variant: ProtocolVariant, session: &mut dyn BufMut) -> usize {
+    MESSAGES_COMPOSED.increment();
+    match variant {
+        ProtocolVariant::Binary => MESSAGES_COMPOSED_BINARY.increment(),
+        ProtocolVariant::Compact => MESSAGES_COMPOSED_COMPACT.increment(),
+    };
+    session.put_slice(&(data.len() as u32).to_be_bytes());
+    session.put_slice(data);
+    std::mem::size_of::<u32>() + data.len()
+}
+
 /// A parser which retrieves the bytes for a complete Thrift message.
 #[derive(Clone)]
 pub struct MessageParser {
     max_size: usize,
+    variant: ProtocolVariant,
+    strict: bool,
 }
 
 impl MessageParser {
+    /// Creates a parser for messages encoded with the binary protocol.
     pub const fn new(max_size: usize) -> Self {
-        Self { max_size }
+        Self {
+            max_size,
+            variant: ProtocolVariant::Binary,
+            strict: false,
+        }
+    }
+
+    /// Creates a parser for messages encoded with the compact protocol.
+    pub const fn compact(max_size: usize) -> Self {
+        Self {
+            max_size,
+            variant: ProtocolVariant::Compact,
+            strict: false,
+        }
+    }
+
+    /// When strict, messages which don't begin with the header for the
+    /// protocol variant are rejected as invalid data. This is off by default,
+    /// and catches clients which use a different encoding than the server.
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn variant(&self) -> ProtocolVariant {
+        self.variant
     }
 }
 
-impl Parse<Message> for MessageParser {
-    fn parse(&self, buffer: &[u8]) -> Result<ParseOk<Message>, std::io::Error> {
+impl MessageParser {
+    /// Parses a message without copying its body out of the buffer. This
+    /// frames messages exactly as [`Parse::parse`] does, and does not
+    /// allocate.
+    pub fn parse_request_borrowed<'a>(
+        &self,
+        buffer: &'a [u8],
+    ) -> Result<ParseOk<BorrowedMessage<'a>>, std::io::Error> {
         if buffer.len() < THRIFT_HEADER_LEN {
             return Err(std::io::Error::from(std::io::ErrorKind::WouldBlock));
         }
@@ -64,21 +242,133 @@ impl Parse<Message> for MessageParser {
         }
 
         if buffer.len() < framed_len {
-            Err(std::io::Error::from(std::io::ErrorKind::WouldBlock))
-        } else {
-            MESSAGES_PARSED.increment();
-            let data = buffer[THRIFT_HEADER_LEN..framed_len]
-                .to_vec()
-                .into_boxed_slice();
-            let message = Message { data };
-            Ok(ParseOk::new(message, framed_len))
+            return Err(std::io::Error::from(std::io::ErrorKind::WouldBlock));
+        }
+
+        let data = &buffer[THRIFT_HEADER_LEN..framed_len];
+
+        if self.strict && !self.variant.is_header(data) {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        }
+
+        MESSAGES_PARSED.increment();
+        match self.variant {
+            ProtocolVariant::Binary => MESSAGES_PARSED_BINARY.increment(),
+            ProtocolVariant::Compact => MESSAGES_PARSED_COMPACT.increment(),
+        };
+
+        let message = BorrowedMessage {
+            data,
+            variant: self.variant,
+        };
+        Ok(ParseOk::new(message, framed_len))
+    }
+}
+
+impl Parse<Message> for MessageParser {
+    fn parse(&self, buffer: &[u8]) -> Result<ParseOk<Message>, std::io::Error> {
+        let parsed = self.parse_request_borrowed(buffer)?;
+        let consumed = parsed.consumed();
+        Ok(ParseOk::new(parsed.into_inner().to_owned(), consumed))
+    }
+
+    fn bytes_needed(&self, buffer: &[u8]) -> Option<usize> {
+        if buffer.len() < THRIFT_HEADER_LEN {
+            return Some(THRIFT_HEADER_LEN - buffer.len());
+        }
+
+        let data_len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        let framed_len = THRIFT_HEADER_LEN + data_len as usize;
+
+        // an over-large frame will never be parsed, so don't ask for it
+        if framed_len > self.max_size {
+            return None;
+        }
+
+        framed_len.checked_sub(buffer.len()).filter(|n| *n > 0)
+    }
+}
+
+impl ParseErrorReply<Message> for MessageParser {
+    /// Replies to a frame which exceeds the max size with a
+    /// `TApplicationException`. The method name and sequence id are copied
+    /// from the start of the message if they have already been read, so that
+    /// the client can match the exception to the call which caused it. The
+    /// exception is only encoded with the binary protocol, so there is no
+    /// reply for the compact protocol.
+    fn parse_error_reply(&self, buffer: &[u8], error: &std::io::Error) -> Option<Message> {
+        if error.kind() != std::io::ErrorKind::InvalidInput || buffer.len() < THRIFT_HEADER_LEN {
+            return None;
         }
+
+        if self.variant != ProtocolVariant::Binary {
+            return None;
+        }
+
+        let data_len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        let framed_len = THRIFT_HEADER_LEN + data_len as usize;
+
+        let (name, seqid) = message_header(&buffer[THRIFT_HEADER_LEN..]).unwrap_or((&[], 0));
+
+        Some(Message::application_exception(
+            name,
+            seqid,
+            PROTOCOL_ERROR,
+            &format!(
+                "frame size {} exceeds max size {}",
+                framed_len, self.max_size
+            ),
+        ))
+    }
+}
+
+/// Reads the method name and sequence id from a strict binary protocol
+/// message header, returning `None` if the header is incomplete or is not in
+/// the strict format.
+fn message_header(buffer: &[u8]) -> Option<(&[u8], i32)> {
+    let version = u32::from_be_bytes(buffer.get(0..4)?.try_into().ok()?);
+    if version & VERSION_MASK != VERSION_1 {
+        return None;
     }
+
+    let name_len = u32::from_be_bytes(buffer.get(4..8)?.try_into().ok()?) as usize;
+    let name = buffer.get(8..8_usize.checked_add(name_len)?)?;
+    let seqid = i32::from_be_bytes(buffer.get(8 + name_len..12 + name_len)?.try_into().ok()?);
+
+    Some((name, seqid))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    // counts the allocations made by each thread, so that tests running in
+    // parallel don't affect each other's counts
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(|count| count.get())
+    }
 
     #[test]
     fn parse() {
@@ -97,6 +387,206 @@ mod tests {
         assert_eq!(consumed, body.len() + THRIFT_HEADER_LEN);
         assert_eq!(*parsed.data, body);
     }
+
+    #[test]
+    fn bytes_needed() {
+        let body = b"COFFEE".to_vec();
+        let len = (body.len() as u32).to_be_bytes();
+
+        let mut message: Vec<u8> = len.to_vec();
+        message.extend_from_slice(&body);
+
+        let parser = MessageParser::new(1024);
+
+        // a partial length prefix only tells us the prefix is incomplete
+        assert_eq!(parser.bytes_needed(&message[0..1]), Some(3));
+
+        for end in THRIFT_HEADER_LEN..message.len() {
+            assert!(parser.parse(&message[0..end]).is_err());
+            assert_eq!(
+                parser.bytes_needed(&message[0..end]),
+                Some(message.len() - end)
+            );
+        }
+
+        assert_eq!(parser.bytes_needed(&message), None);
+    }
+
+    #[test]
+    fn frame_too_large() {
+        // strict header for a call to `ping` with sequence id 7
+        let mut body = vec![0x80, 0x01, 0x00, 0x01];
+        body.extend_from_slice(&4_u32.to_be_bytes());
+        body.extend_from_slice(b"ping");
+        body.extend_from_slice(&7_i32.to_be_bytes());
+        body.resize(1020, 0);
+
+        let mut message = (body.len() as u32).to_be_bytes().to_vec();
+        message.extend_from_slice(&body);
+
+        let parser = MessageParser::new(64);
+
+        let error = parser
+            .parse(&message)
+            .err()
+            .expect("parsed an over-large frame");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        let reply = parser
+            .parse_error_reply(&message, &error)
+            .expect("no reply for an over-large frame");
+
+        let mut buffer = Vec::new();
+        let size = reply.compose(&mut buffer);
+        assert_eq!(size, buffer.len());
+
+        let text = b"frame size 1024 exceeds max size 64";
+
+        let mut data = vec![0x80, 0x01, 0x00, 0x03];
+        data.extend_from_slice(&[0, 0, 0, 4]);
+        data.extend_from_slice(b"ping");
+        data.extend_from_slice(&[0, 0, 0, 7]);
+        data.extend_from_slice(&[11, 0, 1]);
+        data.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        data.extend_from_slice(text);
+        data.extend_from_slice(&[8, 0, 2, 0, 0, 0, 7]);
+        data.push(0);
+
+        let mut expected = (data.len() as u32).to_be_bytes().to_vec();
+        expected.extend_from_slice(&data);
+
+        assert_eq!(buffer, expected);
+
+        // without a readable header the exception is still sent, with an empty
+        // method name and a sequence id of zero
+        let message = [0xff, 0xff, 0xff, 0xff];
+        let error = parser.parse(&message).err().unwrap();
+        let reply = parser.parse_error_reply(&message, &error).unwrap();
+        assert_eq!(&reply.data[4..12], &[0, 0, 0, 0, 0, 0, 0, 0]);
+
+        // incomplete frames don't produce a reply
+        let error = std::io::Error::from(std::io::ErrorKind::WouldBlock);
+        assert!(parser.parse_error_reply(&message, &error).is_none());
+
+        // nor does the compact protocol, which the exception isn't encoded in
+        let parser = MessageParser::compact(64);
+        let message = [0xff, 0xff, 0xff, 0xff];
+        let error = parser.parse(&message).err().unwrap();
+        assert!(parser.parse_error_reply(&message, &error).is_none());
+    }
+
+    #[test]
+    fn variants() {
+        // a compact protocol call to `ping` with sequence id 7
+        let compact = frame(&[0x82, 0x21, 0x07, 0x04, b'p', b'i', b'n', b'g', 0x00]);
+        // and the same call with the strict binary protocol
+        let mut body = vec![0x80, 0x01, 0x00, 0x01];
+        body.extend_from_slice(&4_u32.to_be_bytes());
+        body.extend_from_slice(b"ping");
+        body.extend_from_slice(&7_i32.to_be_bytes());
+        body.push(0x00);
+        let binary = frame(&body);
+
+        // the framing is the same, so either is parsed unless strict
+        for parser in [MessageParser::new(1024), MessageParser::compact(1024)] {
+            for message in [&compact, &binary] {
+                let parsed = parser.parse(message).expect("failed to parse");
+                assert_eq!(parsed.consumed(), message.len());
+                assert_eq!(parsed.into_inner().variant(), parser.variant());
+            }
+        }
+
+        let parsed = MessageParser::new(1024).parse(&compact).unwrap();
+        assert!(parsed.into_inner().is_compact());
+        let parsed = MessageParser::compact(1024).parse(&binary).unwrap();
+        assert!(!parsed.into_inner().is_compact());
+
+        // a strict parser rejects messages in the other encoding
+        let parser = MessageParser::new(1024).strict(true);
+        assert!(parser.parse(&binary).is_ok());
+        let error = parser
+            .parse(&compact)
+            .err()
+            .expect("parsed compact message");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        let parser = MessageParser::compact(1024).strict(true);
+        assert!(parser.parse(&compact).is_ok());
+        let error = parser.parse(&binary).err().expect("parsed binary message");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // an incomplete frame still needs more data
+        let error = parser.parse(&compact[0..6]).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::WouldBlock);
+
+        // a message is composed back into the same frame
+        let message = parser.parse(&compact).unwrap().into_inner();
+        let mut buffer = Vec::new();
+        assert_eq!(message.compose(&mut buffer), compact.len());
+        assert_eq!(buffer, compact);
+    }
+
+    #[test]
+    fn parse_borrowed() {
+        let mut body = vec![0x80, 0x01, 0x00, 0x01];
+        body.extend_from_slice(&4_u32.to_be_bytes());
+        body.extend_from_slice(b"ping");
+        body.extend_from_slice(&7_i32.to_be_bytes());
+        body.push(0x00);
+
+        // two pipelined messages, with part of a third
+        let mut buffer = frame(&body);
+        buffer.extend_from_slice(&frame(b"COFFEE"));
+        buffer.extend_from_slice(&frame(&body)[0..6]);
+
+        let parser = MessageParser::new(1024);
+
+        // parsing, including the failed parse of the partial message, doesn't
+        // allocate
+        let before = allocations();
+        let first = parser.parse_request_borrowed(&buffer).unwrap();
+        let consumed = first.consumed();
+        let first = first.into_inner();
+        let second = parser.parse_request_borrowed(&buffer[consumed..]).unwrap();
+        let end = consumed + second.consumed();
+        let second = second.into_inner();
+        let error = parser
+            .parse_request_borrowed(&buffer[end..])
+            .err()
+            .expect("parsed a partial message");
+        assert_eq!(allocations(), before);
+
+        assert_eq!(error.kind(), std::io::ErrorKind::WouldBlock);
+        assert_eq!(consumed, THRIFT_HEADER_LEN + body.len());
+        assert_eq!(end, consumed + THRIFT_HEADER_LEN + 6);
+        assert_eq!(first.len(), body.len());
+        assert_eq!(second.len(), 6);
+        assert_eq!(first.variant(), ProtocolVariant::Binary);
+        assert!(!first.is_compact());
+
+        // each message is composed back into its own frame, whether borrowed
+        // or owned
+        let mut composed = Vec::new();
+        assert_eq!(first.compose(&mut composed), consumed);
+        assert_eq!(second.compose(&mut composed), end - consumed);
+        assert_eq!(composed, buffer[0..end]);
+
+        let mut composed = Vec::new();
+        first.to_owned().compose(&mut composed);
+        second.to_owned().compose(&mut composed);
+        assert_eq!(composed, buffer[0..end]);
+
+        // and the owned parse frames the same way
+        let owned = parser.parse(&buffer).unwrap();
+        assert_eq!(owned.consumed(), consumed);
+        assert_eq!(*owned.into_inner().data, body);
+    }
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut message = (body.len() as u32).to_be_bytes().to_vec();
+        message.extend_from_slice(body);
+        message
+    }
 }
 
 common::metrics::test_no_duplicates!();
@@ -4,28 +4,59 @@
 
 use crate::*;
 use session::Buf;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 gauge!(ADMIN_CONN_CURR);
 counter!(ADMIN_CONN_ACCEPT);
 counter!(ADMIN_CONN_CLOSE);
+counter!(
+    ADMIN_CONN_IDLE_CLOSE,
+    "number of admin connections closed for waiting too long for a request"
+);
+counter!(
+    ADMIN_CONN_RECYCLE,
+    "number of admin connections closed for reaching their request limit"
+);
+counter!(
+    ADMIN_STATS_DELTA_FRESH,
+    "number of stats delta intervals ended early by a fresh scrape"
+);
+
+// a fresh scrape only ends the current stats delta interval if it has been
+// running for at least this long, so that scrapers can't reduce the intervals
+// seen by everyone else to nothing
+const FRESH_DELTA_MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bounds on how long an admin connection is kept open. A limit of zero is
+/// not enforced.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ConnectionLimits {
+    /// Connections which wait this long for their next request are closed
+    pub idle_timeout: Duration,
+    /// Connections are closed once they have served this many requests
+    pub max_requests: usize,
+}
+
+/// Serves the admin port on the given runtime, which may have several threads
+/// so that many clients can scrape stats at once. The logs are flushed and the
+/// stats snapshots are taken by the calling task, so each of them is only ever
+/// done by one task at a time however many threads serve the admin port.
+pub(crate) async fn admin(
+    mut log_drain: Box<dyn logger::Drain>,
+    admin_listener: std::net::TcpListener,
+    stats_delta_interval: Duration,
+    limits: ConnectionLimits,
+    runtime: tokio::runtime::Handle,
+) {
+    let deltas = Arc::new(Mutex::new(Deltas::default()));
+
+    runtime.spawn(serve(admin_listener, deltas.clone(), limits));
 
-pub(crate) async fn admin(mut log_drain: Box<dyn logger::Drain>, admin_listener: TcpListener) {
     loop {
         let _ = log_drain.flush();
 
-        // accept a new client
-        if let Ok(Ok((socket, _))) =
-            timeout(Duration::from_millis(1), admin_listener.accept()).await
-        {
-            ADMIN_CONN_CURR.increment();
-            ADMIN_CONN_ACCEPT.increment();
-            tokio::spawn(async move {
-                admin::handle_admin_client(socket).await;
-                ADMIN_CONN_CLOSE.increment();
-                ADMIN_CONN_CURR.decrement();
-            });
-        };
-
         let mut rusage = libc::rusage {
             ru_utime: libc::timeval {
                 tv_sec: 0,
@@ -70,18 +101,84 @@ pub(crate) async fn admin(mut log_drain: Box<dyn logger::Drain>, admin_listener:
             RU_NIVCSW.set(rusage.ru_nivcsw as u64);
         }
 
+        // an interval which was ended early by a fresh scrape is followed by a
+        // full one
+        {
+            let mut deltas = deltas.lock().unwrap();
+            if deltas.elapsed() >= stats_delta_interval {
+                deltas.update(readings());
+            }
+        }
+
         tokio::time::sleep(core::time::Duration::from_millis(100)).await;
     }
 }
 
-async fn handle_admin_client(mut socket: tokio::net::TcpStream) {
+/// Accepts admin clients, serving each of them on a task of its own. This must
+/// be run on the runtime which is to serve the clients.
+async fn serve(
+    admin_listener: std::net::TcpListener,
+    deltas: Arc<Mutex<Deltas>>,
+    limits: ConnectionLimits,
+) {
+    let admin_listener =
+        TcpListener::from_std(admin_listener).expect("could not convert to tokio listener");
+
+    loop {
+        match admin_listener.accept().await {
+            Ok((socket, _)) => {
+                ADMIN_CONN_CURR.increment();
+                ADMIN_CONN_ACCEPT.increment();
+                let deltas = deltas.clone();
+                tokio::spawn(async move {
+                    admin::handle_admin_client(socket, deltas, limits).await;
+                    ADMIN_CONN_CLOSE.increment();
+                    ADMIN_CONN_CURR.decrement();
+                });
+            }
+            Err(e) => {
+                // back off, as the error is likely to persist for a while
+                // (eg: out of file descriptors)
+                error!("failed to accept admin connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+async fn handle_admin_client(
+    mut socket: tokio::net::TcpStream,
+    deltas: Arc<Mutex<Deltas>>,
+    limits: ConnectionLimits,
+) {
     // initialize a buffer for incoming bytes from the client
     let mut buf = Buffer::new(INITIAL_BUFFER_SIZE);
 
     // initialize the request parser
     let parser = AdminRequestParser::new();
+
+    let mut requests = 0;
     loop {
-        if do_read(&mut socket, &mut buf).await.is_err() {
+        if limits.max_requests > 0 && requests >= limits.max_requests {
+            ADMIN_CONN_RECYCLE.increment();
+            break;
+        }
+
+        let result = if limits.idle_timeout.is_zero() {
+            do_read(&mut socket, &mut buf).await
+        } else {
+            match tokio::time::timeout(limits.idle_timeout, do_read(&mut socket, &mut buf)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    // the client may have gone away without closing the
+                    // connection, so it is reaped
+                    ADMIN_CONN_IDLE_CLOSE.increment();
+                    break;
+                }
+            }
+        };
+
+        if result.is_err() {
             break;
         }
 
@@ -91,12 +188,32 @@ async fn handle_admin_client(mut socket: tokio::net::TcpStream) {
 
                 let consumed = request.consumed();
                 let request = request.into_inner();
+                requests += 1;
 
                 match request {
                     AdminRequest::Stats { .. } => {
                         ADMIN_RESPONSE_COMPOSE.increment();
 
-                        if stats_response(&mut socket).await.is_err() {
+                        let lines = readings()
+                            .into_iter()
+                            .map(|(name, reading)| stat(&name, reading))
+                            .collect();
+                        if socket.write_all(&stats(lines)).await.is_err() {
+                            break;
+                        }
+                    }
+                    AdminRequest::StatsDelta(fresh) => {
+                        ADMIN_RESPONSE_COMPOSE.increment();
+
+                        let lines = {
+                            let mut deltas = deltas.lock().unwrap();
+                            if fresh && deltas.elapsed() >= FRESH_DELTA_MIN_INTERVAL {
+                                ADMIN_STATS_DELTA_FRESH.increment();
+                                deltas.update(readings());
+                            }
+                            deltas.lines.clone()
+                        };
+                        if socket.write_all(&stats(lines)).await.is_err() {
                             break;
                         }
                     }
@@ -118,8 +235,19 @@ async fn handle_admin_client(mut socket: tokio::net::TcpStream) {
     }
 }
 
-async fn stats_response(socket: &mut tokio::net::TcpStream) -> Result<(), Error> {
-    let mut data = Vec::new();
+/// A reading of a single metric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Reading {
+    /// The cumulative count since the process started
+    Counter(u64),
+    Gauge(i64),
+    Percentile(u64),
+}
+
+/// Reads the current value of each metric, and of each reported percentile
+/// of the heatmaps.
+fn readings() -> Vec<(String, Reading)> {
+    let mut readings = Vec::new();
     for metric in &rustcommon_metrics::metrics() {
         let any = match metric.as_any() {
             Some(any) => any,
@@ -135,6 +263,7 @@ async fn stats_response(socket: &mut tokio::net::TcpStream) -> Result<(), Error>
             || metric.name().starts_with("cas")
             || metric.name().starts_with("decr")
             || metric.name().starts_with("delete")
+            || metric.name().starts_with("getdel")
             || metric.name().starts_with("gets")
             || metric.name().starts_with("incr")
             || metric.name().starts_with("get_cardinality")
@@ -148,25 +277,321 @@ async fn stats_response(socket: &mut tokio::net::TcpStream) -> Result<(), Error>
         }
 
         if let Some(counter) = any.downcast_ref::<Counter>() {
-            data.push(format!("STAT {} {}\r\n", metric.name(), counter.value()));
+            readings.push((metric.name().to_string(), Reading::Counter(counter.value())));
         } else if let Some(gauge) = any.downcast_ref::<Gauge>() {
-            data.push(format!("STAT {} {}\r\n", metric.name(), gauge.value()));
+            readings.push((metric.name().to_string(), Reading::Gauge(gauge.value())));
         } else if let Some(heatmap) = any.downcast_ref::<Heatmap>() {
             for (label, value) in PERCENTILES {
                 let percentile = heatmap.percentile(*value).map(|b| b.high()).unwrap_or(0);
-                data.push(format!(
-                    "STAT {}_{} {}\r\n",
-                    metric.name(),
-                    label,
-                    percentile
+                readings.push((
+                    format!("{}_{}", metric.name(), label),
+                    Reading::Percentile(percentile),
                 ));
             }
         }
     }
+    readings
+}
+
+impl std::fmt::Display for Reading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Counter(value) | Self::Percentile(value) => write!(f, "{}", value),
+            Self::Gauge(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+fn stat(name: &str, reading: Reading) -> String {
+    format!("STAT {} {}\r\n", name, reading)
+}
+
+/// Composes a stats response from its lines, which are sorted by name.
+fn stats(mut lines: Vec<String>) -> Vec<u8> {
+    lines.sort();
+
+    let mut response = lines.concat().into_bytes();
+    response.extend_from_slice(b"END\r\n");
+    response
+}
+
+/// The metrics published by `stats delta`. At the end of each interval every
+/// counter is reported as the increment it made during the interval, while
+/// gauges and percentiles are reported as they were at the end of it. Nothing
+/// is published until the first interval has ended. An interval normally
+/// ends once it has run for the configured duration, but a `stats delta fresh`
+/// request may end it early.
+struct Deltas {
+    // when the current interval started
+    interval_start: Instant,
+    // the value of each counter at the end of the last interval
+    previous: HashMap<String, u64>,
+    lines: Vec<String>,
+}
+
+impl Default for Deltas {
+    fn default() -> Self {
+        Self {
+            interval_start: Instant::now(),
+            previous: HashMap::new(),
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl Deltas {
+    /// How long the current interval has been running.
+    fn elapsed(&self) -> Duration {
+        self.interval_start.elapsed()
+    }
+
+    /// Ends the current interval with the readings taken at its end.
+    fn update(&mut self, readings: Vec<(String, Reading)>) {
+        self.interval_start = Instant::now();
+        self.lines = readings
+            .into_iter()
+            .map(|(name, reading)| match reading {
+                Reading::Counter(value) => {
+                    // counters start from zero, and a counter which wrapped
+                    // around during the interval still gives its increment
+                    let previous = self.previous.insert(name.clone(), value).unwrap_or(0);
+                    stat(&name, Reading::Counter(value.wrapping_sub(previous)))
+                }
+                reading => stat(&name, reading),
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn readings(requests: u64, connections: i64) -> Vec<(String, Reading)> {
+        vec![
+            ("requests".to_string(), Reading::Counter(requests)),
+            ("connections".to_string(), Reading::Gauge(connections)),
+            ("latency_p50".to_string(), Reading::Percentile(100)),
+        ]
+    }
+
+    #[test]
+    fn deltas() {
+        let mut deltas = Deltas::default();
+        assert_eq!(stats(deltas.lines.clone()), b"END\r\n");
+
+        deltas.update(readings(10, 3));
+        assert_eq!(
+            stats(deltas.lines.clone()),
+            b"STAT connections 3\r\nSTAT latency_p50 100\r\nSTAT requests 10\r\nEND\r\n"
+        );
+
+        // counters report their increment, gauges their current value
+        deltas.update(readings(25, 5));
+        assert_eq!(
+            stats(deltas.lines.clone()),
+            b"STAT connections 5\r\nSTAT latency_p50 100\r\nSTAT requests 15\r\nEND\r\n"
+        );
+
+        deltas.update(readings(25, 0));
+        assert!(deltas.lines.contains(&"STAT requests 0\r\n".to_string()));
+        assert!(deltas.lines.contains(&"STAT connections 0\r\n".to_string()));
+    }
+
+    #[test]
+    fn concurrent_scrapes() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(4)
+            .enable_all()
+            .build()
+            .expect("failed to build runtime");
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let deltas = Arc::new(Mutex::new(Deltas::default()));
+        deltas.lock().unwrap().update(readings(10, 3));
+        runtime.spawn(serve(listener, deltas, ConnectionLimits::default()));
+
+        // every client connects at once, and each must be accepted and served
+        // without waiting on the others
+        let start = Instant::now();
+        let clients: Vec<_> = (0..32)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    use std::io::{Read, Write};
+
+                    let mut stream = std::net::TcpStream::connect(addr).expect("failed to connect");
+                    stream
+                        .set_read_timeout(Some(Duration::from_secs(5)))
+                        .unwrap();
+                    stream.write_all(b"stats delta\r\n").unwrap();
+
+                    let mut response = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !response.ends_with(b"END\r\n") {
+                        let n = stream.read(&mut buf).expect("failed to read");
+                        assert!(n > 0, "connection closed");
+                        response.extend_from_slice(&buf[..n]);
+                    }
+                    response
+                })
+            })
+            .collect();
+
+        for client in clients {
+            assert_eq!(
+                client.join().unwrap(),
+                b"STAT connections 3\r\nSTAT latency_p50 100\r\nSTAT requests 10\r\nEND\r\n"
+            );
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    // serves the admin port with the given limits, returning its address
+    fn serve_limited(runtime: &tokio::runtime::Runtime, limits: ConnectionLimits) -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let deltas = Arc::new(Mutex::new(Deltas::default()));
+        runtime.spawn(serve(listener, deltas, limits));
+        addr
+    }
+
+    #[test]
+    fn idle_timeout() {
+        use std::io::Read;
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("failed to build runtime");
+
+        let limits = ConnectionLimits {
+            idle_timeout: Duration::from_millis(200),
+            max_requests: 0,
+        };
+        let addr = serve_limited(&runtime, limits);
+
+        // a client which never sends a request is disconnected once the
+        // timeout has passed
+        let mut stream = std::net::TcpStream::connect(addr).expect("failed to connect");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let start = Instant::now();
+        let mut buf = [0; 1024];
+        assert_eq!(stream.read(&mut buf).expect("failed to read"), 0);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn max_requests() {
+        use std::io::{Read, Write};
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("failed to build runtime");
+
+        let limits = ConnectionLimits {
+            idle_timeout: Duration::ZERO,
+            max_requests: 2,
+        };
+        let addr = serve_limited(&runtime, limits);
+
+        let mut stream = std::net::TcpStream::connect(addr).expect("failed to connect");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // the requests up to the limit are served, then the connection closes
+        let mut buf = [0; 1024];
+        for _ in 0..2 {
+            stream.write_all(b"stats delta\r\n").unwrap();
+            let n = stream.read(&mut buf).expect("failed to read");
+            assert_eq!(&buf[..n], b"END\r\n");
+        }
+        assert_eq!(stream.read(&mut buf).expect("failed to read"), 0);
+    }
+
+    counter!(
+        TEST_FRESH_DELTA,
+        "incremented by the fresh stats delta test"
+    );
+
+    // sends a request on an admin connection and returns the stats it replies
+    // with
+    fn scrape(stream: &mut std::net::TcpStream, request: &[u8]) -> String {
+        use std::io::{Read, Write};
+
+        stream.write_all(request).unwrap();
+
+        let mut response = Vec::new();
+        let mut buf = [0; 4096];
+        while !response.ends_with(b"END\r\n") {
+            let n = stream.read(&mut buf).expect("failed to read");
+            assert!(n > 0, "connection closed");
+            response.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8(response).unwrap()
+    }
+
+    #[test]
+    fn fresh() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("failed to build runtime");
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let deltas = Arc::new(Mutex::new(Deltas::default()));
+        deltas.lock().unwrap().update(super::readings());
+        runtime.spawn(serve(listener, deltas.clone(), ConnectionLimits::default()));
+
+        TEST_FRESH_DELTA.increment();
+
+        let mut stream = std::net::TcpStream::connect(addr).expect("failed to connect");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // the last interval ended before the increment
+        let stats = scrape(&mut stream, b"stats delta\r\n");
+        assert!(stats.contains("STAT test_fresh_delta 0\r\n"));
+
+        // an interval which has only just started isn't ended early
+        let stats = scrape(&mut stream, b"stats delta fresh\r\n");
+        assert!(stats.contains("STAT test_fresh_delta 0\r\n"));
+
+        // otherwise a fresh scrape ends the interval without waiting for the
+        // rest of it, and sees the increment
+        deltas.lock().unwrap().interval_start -= FRESH_DELTA_MIN_INTERVAL;
+        let stats = scrape(&mut stream, b"stats delta fresh\r\n");
+        assert!(stats.contains("STAT test_fresh_delta 1\r\n"));
+
+        // which is what later scrapes see until the next interval ends
+        let stats = scrape(&mut stream, b"stats delta\r\n");
+        assert!(stats.contains("STAT test_fresh_delta 1\r\n"));
+    }
+
+    #[test]
+    fn wraparound() {
+        let mut deltas = Deltas::default();
 
-    data.sort();
-    for line in data {
-        socket.write_all(line.as_bytes()).await?;
+        deltas.update(readings(u64::MAX - 4, 0));
+        deltas.update(readings(5, 0));
+        assert!(deltas.lines.contains(&"STAT requests 10\r\n".to_string()));
     }
-    socket.write_all(b"END\r\n").await
 }
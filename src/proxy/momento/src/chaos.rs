@@ -0,0 +1,187 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Fault injection for testing how clients cope with a slow or lossy proxy.
+//!
+//! When configured, every backend request is preceded by a delay and some
+//! fraction of responses are never written back to the client. The faults are
+//! only compiled in with the `chaos` feature. Without it the hooks do nothing
+//! and a `[proxy.chaos]` config section stops the proxy from starting, so a
+//! release build can't be made to misbehave through its config alone.
+
+use crate::*;
+use config::momento_proxy::Chaos;
+
+#[cfg(feature = "chaos")]
+pub use inject::*;
+
+#[cfg(not(feature = "chaos"))]
+pub fn configure(_config: Chaos) -> Result<(), Error> {
+    Err(Error::new(
+        ErrorKind::Other,
+        "`proxy.chaos` requires the proxy to be built with the `chaos` feature",
+    ))
+}
+
+/// Waits for the configured delay before a backend request is issued.
+#[cfg(not(feature = "chaos"))]
+pub async fn delay() {}
+
+/// Returns `true` if the response to the current request should be discarded
+/// instead of being sent to the client.
+#[cfg(not(feature = "chaos"))]
+pub fn drop_response() -> bool {
+    false
+}
+
+#[cfg(feature = "chaos")]
+mod inject {
+    use super::*;
+    use core::sync::atomic::AtomicU64;
+    use rand::Rng;
+
+    counter!(CHAOS_DELAY, "backend requests delayed by fault injection");
+    counter!(CHAOS_DROP, "responses dropped by fault injection");
+
+    // the drop fraction is held in parts per million so that it fits in an
+    // atomic alongside the delay bounds
+    const PPM: u64 = 1_000_000;
+
+    static FAULTS: Faults = Faults::new();
+
+    /// The currently active faults. A default instance injects nothing.
+    pub struct Faults {
+        delay_min_ms: AtomicU64,
+        delay_max_ms: AtomicU64,
+        drop_ppm: AtomicU64,
+    }
+
+    impl Faults {
+        pub const fn new() -> Self {
+            Self {
+                delay_min_ms: AtomicU64::new(0),
+                delay_max_ms: AtomicU64::new(0),
+                drop_ppm: AtomicU64::new(0),
+            }
+        }
+
+        pub fn set(&self, config: Chaos) -> Result<(), Error> {
+            let fraction = config.drop_fraction();
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("chaos drop fraction of {fraction} is not between 0.0 and 1.0"),
+                ));
+            }
+
+            self.delay_min_ms
+                .store(config.delay_min_ms(), Ordering::Relaxed);
+            self.delay_max_ms
+                .store(config.delay_max_ms(), Ordering::Relaxed);
+            self.drop_ppm
+                .store((fraction * PPM as f64) as u64, Ordering::Relaxed);
+
+            Ok(())
+        }
+
+        pub async fn delay(&self) {
+            let min = self.delay_min_ms.load(Ordering::Relaxed);
+            let max = self.delay_max_ms.load(Ordering::Relaxed);
+            if max == 0 {
+                return;
+            }
+
+            let delay = rand::thread_rng().gen_range(min..=max);
+            CHAOS_DELAY.increment();
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+
+        pub fn drop_response(&self) -> bool {
+            let ppm = self.drop_ppm.load(Ordering::Relaxed);
+            if ppm == 0 {
+                return false;
+            }
+
+            let drop = rand::thread_rng().gen_range(0..PPM) < ppm;
+            if drop {
+                CHAOS_DROP.increment();
+            }
+            drop
+        }
+    }
+
+    /// Activates the faults described by the config.
+    pub fn configure(config: Chaos) -> Result<(), Error> {
+        FAULTS.set(config)
+    }
+
+    /// Waits for the configured delay before a backend request is issued.
+    pub async fn delay() {
+        FAULTS.delay().await
+    }
+
+    /// Returns `true` if the response to the current request should be
+    /// discarded instead of being sent to the client.
+    pub fn drop_response() -> bool {
+        FAULTS.drop_response()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn chaos(toml: &str) -> Chaos {
+            toml::from_str(toml).expect("bad chaos config")
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .expect("failed to build runtime")
+        }
+
+        #[test]
+        fn inactive() {
+            let faults = Faults::new();
+            let start = std::time::Instant::now();
+            runtime().block_on(faults.delay());
+            assert!(start.elapsed() < Duration::from_millis(10));
+            assert!((0..1000).all(|_| !faults.drop_response()));
+        }
+
+        #[test]
+        fn delay() {
+            let faults = Faults::new();
+            faults
+                .set(chaos("delay_min_ms = 20\ndelay_max_ms = 30"))
+                .expect("failed to configure");
+
+            let runtime = runtime();
+            for _ in 0..5 {
+                let start = std::time::Instant::now();
+                runtime.block_on(faults.delay());
+                assert!(start.elapsed() >= Duration::from_millis(20));
+            }
+        }
+
+        #[test]
+        fn drop_fraction() {
+            let faults = Faults::new();
+            faults
+                .set(chaos("drop_fraction = 0.25"))
+                .expect("failed to configure");
+
+            let dropped = (0..100_000).filter(|_| faults.drop_response()).count();
+            assert!((20_000..30_000).contains(&dropped), "dropped: {dropped}");
+
+            faults
+                .set(chaos("drop_fraction = 1.0"))
+                .expect("failed to configure");
+            assert!((0..1000).all(|_| faults.drop_response()));
+
+            assert!(faults.set(chaos("drop_fraction = 1.5")).is_err());
+        }
+    }
+}
@@ -3,19 +3,37 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use crate::protocol::*;
+#[cfg(feature = "resp")]
+use crate::pubsub::Channels;
 use crate::*;
 use session::Buf;
 
+// the reply to a request which isn't allowed in subscriber mode
+#[cfg(feature = "resp")]
+const SUBSCRIBER_MODE_ERROR: &[u8] =
+    b"-ERR only SUBSCRIBE / UNSUBSCRIBE / PUBLISH are allowed in this context\r\n";
+
+// NOTE: each connection handles one request at a time, and the next request
+// isn't parsed until the reply to the previous one has been written. Replies
+// are never reordered, so there is no backlog of them to bound, and there is
+// never more than one request in flight per connection, so no limit on them
+// to configure. A backend call which stalls only holds up its own connection,
+// for at most `BACKEND_TIMEOUT` per attempt, and pipelined requests wait in
+// the socket rather than in memory, which pushes back on the client.
+
+#[cfg(feature = "memcache")]
 pub(crate) async fn handle_memcache_client(
     mut socket: tokio::net::TcpStream,
     mut client: SimpleCacheClient,
     cache_name: String,
+    max_ttl: MaxTtl,
+    lenient_newlines: bool,
 ) {
     // initialize a buffer for incoming bytes from the client
     let mut buf = Buffer::new(INITIAL_BUFFER_SIZE);
 
     // initialize the request parser
-    let parser = memcache::RequestParser::new();
+    let parser = memcache::RequestParser::new().lenient_newlines(lenient_newlines);
 
     // handle incoming data from the client
     loop {
@@ -38,7 +56,7 @@ pub(crate) async fn handle_memcache_client(
                         }
                     }
                     memcache::Request::Set(r) => {
-                        if memcache::set(&mut client, &cache_name, &mut socket, &r)
+                        if memcache::set(&mut client, &cache_name, &mut socket, &r, max_ttl)
                             .await
                             .is_err()
                         {
@@ -52,7 +70,11 @@ pub(crate) async fn handle_memcache_client(
                 buf.advance(consumed);
             }
             Err(e) => match e.kind() {
-                ErrorKind::WouldBlock => {}
+                ErrorKind::WouldBlock => {
+                    if let Some(needed) = parser.bytes_needed(buf.borrow()) {
+                        buf.reserve(needed.min(MAX_READ_HINT));
+                    }
+                }
                 _ => {
                     // invalid request
                     let _ = socket.write_all(b"CLIENT_ERROR\r\n").await;
@@ -63,20 +85,50 @@ pub(crate) async fn handle_memcache_client(
     }
 }
 
+#[cfg(feature = "resp")]
 pub(crate) async fn handle_resp_client(
     mut socket: tokio::net::TcpStream,
     mut client: SimpleCacheClient,
     cache_name: String,
+    max_ttl: MaxTtl,
+    lenient_newlines: bool,
+    channels: Channels,
 ) {
     // initialize a buffer for incoming bytes from the client
     let mut buf = Buffer::new(INITIAL_BUFFER_SIZE);
 
     // initialize the request parser
-    let parser = resp::RequestParser::new();
+    let parser = resp::RequestParser::new().lenient_newlines(lenient_newlines);
+
+    let mut subscriber = channels.subscriber();
 
     // handle incoming data from the client
     loop {
-        if do_read(&mut socket, &mut buf).await.is_err() {
+        // in subscriber mode, messages published to the subscribed channels
+        // are written out as they arrive while waiting for requests
+        if subscriber.is_subscribed() {
+            tokio::select! {
+                message = subscriber.recv() => {
+                    // subscribers which fall too far behind are disconnected
+                    match message {
+                        Some(message) => {
+                            if socket.write_all(&message).await.is_err() {
+                                break;
+                            }
+                        }
+                        None => {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                result = do_read(&mut socket, &mut buf) => {
+                    if result.is_err() {
+                        break;
+                    }
+                }
+            }
+        } else if do_read(&mut socket, &mut buf).await.is_err() {
             break;
         }
 
@@ -85,6 +137,33 @@ pub(crate) async fn handle_resp_client(
                 let consumed = request.consumed();
                 let request = request.into_inner();
 
+                // help doesn't touch the cache, so it's answered directly
+                if let Some(help) = request.help() {
+                    let mut reply = Vec::new();
+                    help.compose(&mut reply);
+                    if socket.write_all(&reply).await.is_err() {
+                        break;
+                    }
+                    buf.advance(consumed);
+                    continue;
+                }
+
+                // only pub/sub commands may be issued in subscriber mode
+                if subscriber.is_subscribed()
+                    && !matches!(
+                        request,
+                        resp::Request::Publish(_)
+                            | resp::Request::Subscribe(_)
+                            | resp::Request::Unsubscribe(_)
+                    )
+                {
+                    if socket.write_all(SUBSCRIBER_MODE_ERROR).await.is_err() {
+                        break;
+                    }
+                    buf.advance(consumed);
+                    continue;
+                }
+
                 match request {
                     resp::Request::Get(r) => {
                         if resp::get(&mut client, &cache_name, &mut socket, r.key())
@@ -95,13 +174,42 @@ pub(crate) async fn handle_resp_client(
                         }
                     }
                     resp::Request::Set(r) => {
-                        if resp::set(&mut client, &cache_name, &mut socket, &r)
+                        if resp::set(&mut client, &cache_name, &mut socket, &r, max_ttl)
                             .await
                             .is_err()
                         {
                             break;
                         }
                     }
+                    resp::Request::Publish(r) => {
+                        let receivers = channels.publish(r.channel(), r.message());
+                        let reply = format!(":{}\r\n", receivers);
+                        if socket.write_all(reply.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                    resp::Request::Subscribe(r) => {
+                        let mut reply = Vec::new();
+                        for channel in r.channels() {
+                            reply.extend_from_slice(&subscriber.subscribe(channel));
+                        }
+                        if socket.write_all(&reply).await.is_err() {
+                            break;
+                        }
+                    }
+                    resp::Request::Unsubscribe(r) => {
+                        let reply = subscriber.unsubscribe(r.channels());
+                        if socket.write_all(&reply).await.is_err() {
+                            break;
+                        }
+                    }
+                    resp::Request::Wait(_) => {
+                        // momento handles durability, there are no replicas
+                        // for the client to wait on
+                        if socket.write_all(b":0\r\n").await.is_err() {
+                            break;
+                        }
+                    }
                     _ => {
                         println!("bad request");
                         let _ = socket.write_all(b"CLIENT_ERROR\r\n").await;
@@ -111,7 +219,11 @@ pub(crate) async fn handle_resp_client(
                 buf.advance(consumed);
             }
             Err(e) => match e.kind() {
-                ErrorKind::WouldBlock => {}
+                ErrorKind::WouldBlock => {
+                    if let Some(needed) = parser.bytes_needed(buf.borrow()) {
+                        buf.reserve(needed.min(MAX_READ_HINT));
+                    }
+                }
                 _ => {
                     println!("bad request");
                     let _ = socket.write_all(b"CLIENT_ERROR\r\n").await;
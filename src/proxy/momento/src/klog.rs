@@ -2,6 +2,7 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+#[cfg(feature = "memcache")]
 pub(crate) fn klog_get(key: &str, response_len: usize) {
     if response_len == 0 {
         klog!("\"get {}\" 0 {}", key, response_len);
@@ -2,15 +2,27 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+#[cfg(feature = "resp")]
+use crate::pubsub::Channels;
 use crate::*;
 use ::net::{TCP_ACCEPT, TCP_CLOSE, TCP_CONN_CURR};
+use config::momento_proxy::PubSub;
 
 pub(crate) async fn listener(
     listener: TcpListener,
     client_builder: SimpleCacheClientBuilder,
     cache_name: String,
     protocol: Protocol,
+    max_ttl: MaxTtl,
+    lenient_newlines: bool,
+    pubsub: PubSub,
 ) {
+    // pub/sub channels are shared by the connections of this listener
+    #[cfg(feature = "resp")]
+    let channels = Channels::new(pubsub);
+    #[cfg(not(feature = "resp"))]
+    let _ = pubsub;
+
     // this acts as our listener thread and spawns tasks for each client
     loop {
         // accept a new client
@@ -19,17 +31,40 @@ pub(crate) async fn listener(
 
             let client = client_builder.clone().build();
             let cache_name = cache_name.clone();
+            #[cfg(feature = "resp")]
+            let channels = channels.clone();
 
             // spawn a task for managing requests for the client
             tokio::spawn(async move {
                 TCP_CONN_CURR.increment();
                 match protocol {
+                    #[cfg(feature = "memcache")]
                     Protocol::Memcache => {
-                        crate::frontend::handle_memcache_client(socket, client, cache_name).await;
+                        crate::frontend::handle_memcache_client(
+                            socket,
+                            client,
+                            cache_name,
+                            max_ttl,
+                            lenient_newlines,
+                        )
+                        .await;
                     }
+                    #[cfg(feature = "resp")]
                     Protocol::Resp => {
-                        crate::frontend::handle_resp_client(socket, client, cache_name).await;
+                        crate::frontend::handle_resp_client(
+                            socket,
+                            client,
+                            cache_name,
+                            max_ttl,
+                            lenient_newlines,
+                            channels,
+                        )
+                        .await;
                     }
+                    // listeners are only started for the protocols which
+                    // are enabled, see `protocol_enabled`
+                    #[allow(unreachable_patterns)]
+                    _ => {}
                 }
 
                 TCP_CONN_CURR.decrement();
@@ -38,3 +73,12 @@ pub(crate) async fn listener(
         }
     }
 }
+
+/// Returns `true` if the proxy was built with support for the protocol, which
+/// is selected by the cargo feature of the same name.
+pub(crate) fn protocol_enabled(protocol: Protocol) -> bool {
+    match protocol {
+        Protocol::Memcache => cfg!(feature = "memcache"),
+        Protocol::Resp => cfg!(feature = "resp"),
+    }
+}
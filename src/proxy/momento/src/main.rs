@@ -28,7 +28,6 @@ use std::io::{Error, ErrorKind};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 use tokio::runtime::Builder;
-use tokio::time::timeout;
 
 pub const KB: usize = 1024;
 pub const MB: usize = 1024 * KB;
@@ -37,10 +36,19 @@ const S: u64 = 1_000_000_000; // one second in nanoseconds
 const US: u64 = 1_000; // one microsecond in nanoseconds
 
 mod admin;
+mod chaos;
 mod frontend;
 mod klog;
 mod listener;
 mod protocol;
+#[cfg(feature = "resp")]
+mod pubsub;
+mod retry;
+
+use retry::{Idempotency, RetryPolicy};
+
+#[cfg(not(any(feature = "memcache", feature = "resp")))]
+compile_error!("at least one of the `memcache` and `resp` features must be enabled");
 
 // NOTES:
 //
@@ -60,6 +68,10 @@ pub const INITIAL_BUFFER_SIZE: usize = 16 * KB;
 // sets an upper bound on how large a request can be
 pub const MAX_REQUEST_SIZE: usize = 100 * MB;
 
+// limits how much buffer space is reserved up front when a partial request
+// says how many more bytes it needs
+pub const MAX_READ_HINT: usize = MB;
+
 // The Momento cache client requires providing a default TTL. For the current
 // implementation of the proxy, we don't actually let the client use the default,
 // we always specify a TTL for each `set`.
@@ -68,6 +80,14 @@ const DEFAULT_TTL_SECONDS: NonZeroU64 = unsafe { NonZeroU64::new_unchecked(3600)
 // we interpret TTLs the same way memcached would
 pub const TIME_TYPE: TimeType = TimeType::Memcache;
 
+// timeout for each individual request to the backend
+pub const BACKEND_TIMEOUT: Duration = Duration::from_millis(200);
+
+// backend requests which fail transiently are retried a bounded number of
+// times with an exponential backoff, see the `retry` module for which
+// failures are considered safe to retry
+pub const BACKEND_RETRY_POLICY: RetryPolicy = RetryPolicy::new(2, Duration::from_millis(10));
+
 pub static PERCENTILES: &[(&str, f64)] = &[
     ("p25", 25.0),
     ("p50", 50.0),
@@ -85,6 +105,8 @@ counter!(BACKEND_REQUEST);
 counter!(BACKEND_EX);
 counter!(BACKEND_EX_RATE_LIMITED);
 counter!(BACKEND_EX_TIMEOUT);
+counter!(BACKEND_RETRIES);
+counter!(BACKEND_RETRY_EXHAUSTED);
 
 counter!(RU_UTIME);
 counter!(RU_STIME);
@@ -166,6 +188,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             std::process::exit(1);
         }
 
+        // requests without a ttl use the default, which isn't checked against
+        // the ceiling when they are handled
+        if cache.max_ttl().apply(ttl.get()) != Some(ttl.get()) {
+            error!("default ttl of {ttl} for cache `{name}` is greater than its max ttl");
+            let _ = log_drain.flush();
+            std::process::exit(1);
+        }
+
         if let Err(e) = cache.socket_addr() {
             error!("listen address for cache `{name}` is not valid: {}", e);
             let _ = log_drain.flush();
@@ -173,6 +203,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    if config.admin().threads() == 0 {
+        error!("the admin port must be served by at least one thread");
+        let _ = log_drain.flush();
+        std::process::exit(1);
+    }
+
+    if let Some(chaos) = config.chaos() {
+        if let Err(e) = chaos::configure(chaos) {
+            error!("{}", e);
+            let _ = log_drain.flush();
+            std::process::exit(1);
+        }
+        warn!("fault injection is enabled: {:?}", chaos);
+    }
+
     // initialize metrics
     common::metrics::init();
 
@@ -228,18 +273,38 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build()
         .expect("failed to launch tokio runtime");
 
-    runtime.block_on(async move { spawn(config, log_drain).await })
+    // the admin port has a runtime of its own, so that clients scraping stats
+    // don't compete with the data threads
+    let mut admin_runtime = Builder::new_multi_thread();
+
+    admin_runtime.thread_name_fn(|| {
+        static ATOMIC_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = ATOMIC_ID.fetch_add(1, Ordering::SeqCst);
+        format!("pelikan_admin_{}", id)
+    });
+
+    let admin_runtime = admin_runtime
+        .worker_threads(config.admin().threads())
+        .enable_all()
+        .build()
+        .expect("failed to launch tokio admin runtime");
+
+    let admin = admin_runtime.handle().clone();
+
+    runtime.block_on(async move { spawn(config, log_drain, admin).await })
 }
 
 async fn spawn(
     config: MomentoProxyConfig,
     mut log_drain: Box<dyn Drain>,
+    admin: tokio::runtime::Handle,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let admin_addr = config
         .admin()
         .socket_addr()
         .expect("bad admin listen address");
-    let admin_listener = TcpListener::bind(&admin_addr).await?;
+    let admin_listener = std::net::TcpListener::bind(admin_addr)?;
+    admin_listener.set_nonblocking(true)?;
     info!("starting proxy admin listener on: {}", admin_addr);
 
     // initialize the Momento cache client
@@ -284,6 +349,16 @@ async fn spawn(
         };
         let ttl = cache.default_ttl();
 
+        if !listener::protocol_enabled(cache.protocol()) {
+            error!(
+                "cache `{}` uses the {:?} protocol, which this build does not include",
+                cache.cache_name(),
+                cache.protocol()
+            );
+            let _ = log_drain.flush();
+            std::process::exit(1);
+        }
+
         let tcp_listener = match std::net::TcpListener::bind(&addr) {
             Ok(v) => {
                 if let Err(e) = v.set_nonblocking(true) {
@@ -327,12 +402,27 @@ async fn spawn(
                 client_builder,
                 cache.cache_name(),
                 cache.protocol(),
+                cache.max_ttl(),
+                cache.lenient_newlines(),
+                config.pubsub(),
             )
             .await;
         });
     }
 
-    admin::admin(log_drain, admin_listener).await;
+    let stats_delta_interval = Duration::from_millis(config.admin().stats_delta_interval() as u64);
+    let limits = admin::ConnectionLimits {
+        idle_timeout: Duration::from_millis(config.admin().idle_timeout() as u64),
+        max_requests: config.admin().max_requests(),
+    };
+    admin::admin(
+        log_drain,
+        admin_listener,
+        stats_delta_interval,
+        limits,
+        admin,
+    )
+    .await;
     Ok(())
 }
 
@@ -3,6 +3,7 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use crate::klog::klog_set;
+use crate::protocol::limit_ttl;
 use crate::{Error, *};
 use ::net::*;
 use protocol_memcache::*;
@@ -12,6 +13,7 @@ pub async fn set(
     cache_name: &str,
     socket: &mut tokio::net::TcpStream,
     request: &protocol_memcache::Set,
+    max_ttl: MaxTtl,
 ) -> Result<(), Error> {
     SET.increment();
 
@@ -51,12 +53,39 @@ pub async fn set(
             None
         };
 
-        match timeout(
-            Duration::from_millis(200),
-            client.set(cache_name, key, &value, ttl),
-        )
-        .await
-        {
+        let ttl = match limit_ttl(ttl, max_ttl) {
+            Ok(ttl) => ttl,
+            Err(()) => {
+                SET_NOT_STORED.increment();
+                if socket
+                    .write_all(b"CLIENT_ERROR ttl is above the maximum\r\n")
+                    .await
+                    .is_err()
+                {
+                    SESSION_SEND_EX.increment();
+                }
+                return Ok(());
+            }
+        };
+
+        chaos::delay().await;
+
+        // a timed out set may still have been applied by the backend, so it is
+        // only retried if the backend rejected it outright
+        let mut retry = BACKEND_RETRY_POLICY.start(Idempotency::NonIdempotent);
+        let result = loop {
+            let result = timeout(BACKEND_TIMEOUT, client.set(cache_name, key, &value, ttl)).await;
+            if !retry.should_retry(&result).await {
+                break result;
+            }
+        };
+
+        // the backend has acted on the request, but the client never hears back
+        if chaos::drop_response() {
+            return Ok(());
+        }
+
+        match result {
             Ok(Ok(result)) => {
                 match result.result {
                     MomentoSetStatus::OK => {
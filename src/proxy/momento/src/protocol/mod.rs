@@ -2,5 +2,46 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+use crate::*;
+
+#[cfg(feature = "memcache")]
 pub mod memcache;
+#[cfg(feature = "resp")]
 pub mod resp;
+
+/// Applies the ceiling to a TTL which is about to be sent to the backend. No
+/// TTL means that the cache's default is used, which is checked against the
+/// ceiling at startup. Returns an error if the TTL is above a strict ceiling.
+pub(crate) fn limit_ttl(
+    ttl: Option<NonZeroU64>,
+    max_ttl: MaxTtl,
+) -> Result<Option<NonZeroU64>, ()> {
+    match ttl {
+        Some(ttl) => max_ttl.apply(ttl.get()).map(NonZeroU64::new).ok_or(()),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits() {
+        let ttl = NonZeroU64::new;
+
+        let unlimited = MaxTtl::default();
+        assert_eq!(limit_ttl(None, unlimited), Ok(None));
+        assert_eq!(limit_ttl(ttl(86400), unlimited), Ok(ttl(86400)));
+
+        let lenient = MaxTtl::new(3600, false);
+        assert_eq!(limit_ttl(None, lenient), Ok(None));
+        assert_eq!(limit_ttl(ttl(60), lenient), Ok(ttl(60)));
+        assert_eq!(limit_ttl(ttl(86400), lenient), Ok(ttl(3600)));
+
+        let strict = MaxTtl::new(3600, true);
+        assert_eq!(limit_ttl(None, strict), Ok(None));
+        assert_eq!(limit_ttl(ttl(3600), strict), Ok(ttl(3600)));
+        assert_eq!(limit_ttl(ttl(86400), strict), Err(()));
+    }
+}
@@ -33,7 +33,17 @@ pub async fn get(
     // know this unwrap is safe
     let key = std::str::from_utf8(key).unwrap();
 
-    match timeout(Duration::from_millis(200), client.get(cache_name, key)).await {
+    chaos::delay().await;
+
+    let mut retry = BACKEND_RETRY_POLICY.start(Idempotency::Idempotent);
+    let result = loop {
+        let result = timeout(BACKEND_TIMEOUT, client.get(cache_name, key)).await;
+        if !retry.should_retry(&result).await {
+            break result;
+        }
+    };
+
+    match result {
         Ok(Ok(response)) => {
             match response.result {
                 MomentoGetStatus::ERROR => {
@@ -93,6 +103,10 @@ pub async fn get(
         }
     }
 
+    if chaos::drop_response() {
+        return Ok(());
+    }
+
     SESSION_SEND.increment();
     SESSION_SEND_BYTE.add(response_buf.len() as _);
     TCP_SEND_BYTE.add(response_buf.len() as _);
@@ -3,6 +3,7 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use crate::klog::klog_set;
+use crate::protocol::limit_ttl;
 use crate::{Error, *};
 use ::net::*;
 use protocol_memcache::*;
@@ -13,6 +14,7 @@ pub async fn set(
     cache_name: &str,
     socket: &mut tokio::net::TcpStream,
     request: &SetRequest,
+    max_ttl: MaxTtl,
 ) -> Result<(), Error> {
     SET.increment();
 
@@ -37,6 +39,21 @@ pub async fn set(
             return Err(Error::from(ErrorKind::InvalidInput));
         }
 
+        // the backend can't compare the stored value or its version and write
+        // it in one step, so a conditional set is refused rather than applied
+        // as if it had no condition
+        if request.condition().is_some() || request.if_version().is_some() {
+            SET_NOT_STORED.increment();
+            if socket
+                .write_all(b"-ERR conditional set is not supported\r\n")
+                .await
+                .is_err()
+            {
+                SESSION_SEND_EX.increment();
+            }
+            return Ok(());
+        }
+
         BACKEND_REQUEST.increment();
 
         let ttl = match request.expire_time() {
@@ -53,12 +70,39 @@ pub async fn set(
             None => None,
         };
 
-        match timeout(
-            Duration::from_millis(200),
-            client.set(cache_name, key, &value, ttl),
-        )
-        .await
-        {
+        let ttl = match limit_ttl(ttl, max_ttl) {
+            Ok(ttl) => ttl,
+            Err(()) => {
+                SET_NOT_STORED.increment();
+                if socket
+                    .write_all(b"-ERR ttl is above the maximum\r\n")
+                    .await
+                    .is_err()
+                {
+                    SESSION_SEND_EX.increment();
+                }
+                return Ok(());
+            }
+        };
+
+        chaos::delay().await;
+
+        // a timed out set may still have been applied by the backend, so it is
+        // only retried if the backend rejected it outright
+        let mut retry = BACKEND_RETRY_POLICY.start(Idempotency::NonIdempotent);
+        let result = loop {
+            let result = timeout(BACKEND_TIMEOUT, client.set(cache_name, key, &value, ttl)).await;
+            if !retry.should_retry(&result).await {
+                break result;
+            }
+        };
+
+        // the backend has acted on the request, but the client never hears back
+        if chaos::drop_response() {
+            return Ok(());
+        }
+
+        match result {
             Ok(Ok(result)) => {
                 match result.result {
                     MomentoSetStatus::OK => {
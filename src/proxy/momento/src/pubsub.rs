@@ -0,0 +1,393 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Minimal pub/sub for resp clients. Channels only exist within the proxy,
+//! they are not backed by Momento, so messages are only delivered to
+//! subscribers connected to the same listener.
+//!
+//! Each connection which subscribes gets its own queue of messages, which the
+//! task serving the connection writes out alongside the replies to its
+//! requests. Publishing a message composes it once and pushes it onto the
+//! queue of every subscriber of the channel. Queues are bounded, and a
+//! subscriber which doesn't keep up either loses its oldest messages or is
+//! disconnected, depending on the configured policy.
+
+use crate::*;
+use config::momento_proxy::{PubSub, SlowSubscriberPolicy};
+use protocol_resp::compose_array;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+counter!(PUBSUB_PUBLISH, "the number of messages published");
+counter!(
+    PUBSUB_MESSAGES_QUEUED,
+    "the number of messages queued for delivery to subscribers"
+);
+counter!(
+    PUBSUB_MESSAGES_DROPPED,
+    "the number of queued messages dropped because a subscriber fell behind"
+);
+counter!(
+    PUBSUB_SLOW_SUBSCRIBER_DISCONNECTS,
+    "the number of subscribers disconnected because they fell behind"
+);
+gauge!(
+    PUBSUB_SUBSCRIPTIONS,
+    "the current number of channel subscriptions across all connections"
+);
+
+// the reply to subscribing to more channels than a connection may have
+const MAX_CHANNELS_ERROR: &[u8] = b"-ERR max number of subscribed channels reached\r\n";
+
+type Message = Arc<[u8]>;
+
+/// The registry of channels and their subscribers, which is shared by all of
+/// the connections of a listener.
+#[derive(Clone)]
+pub(crate) struct Channels {
+    subscribers: Arc<Mutex<HashMap<Vec<u8>, HashMap<u64, Arc<Queue>>>>>,
+    next_id: Arc<AtomicU64>,
+    config: PubSub,
+}
+
+impl Channels {
+    pub fn new(config: PubSub) -> Self {
+        Self {
+            subscribers: Default::default(),
+            next_id: Default::default(),
+            config,
+        }
+    }
+
+    /// Creates a subscriber for a connection, which isn't subscribed to any
+    /// channels yet.
+    pub fn subscriber(&self) -> Subscriber {
+        Subscriber {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            channels: self.clone(),
+            subscribed: BTreeSet::new(),
+            queue: Default::default(),
+        }
+    }
+
+    /// Publishes a message to a channel, returning the number of subscribers
+    /// it was delivered to.
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        PUBSUB_PUBLISH.increment();
+
+        let subscribers = self.subscribers.lock().unwrap();
+        let subscribers = match subscribers.get(channel) {
+            Some(subscribers) => subscribers,
+            None => {
+                return 0;
+            }
+        };
+
+        let mut composed = Vec::new();
+        compose_array(&mut composed, &[b"message", channel, message]);
+        let message: Message = composed.into();
+
+        let mut receivers = 0;
+        for queue in subscribers.values() {
+            if queue.push(message.clone(), &self.config) {
+                receivers += 1;
+            }
+        }
+        PUBSUB_MESSAGES_QUEUED.add(receivers as _);
+        receivers
+    }
+}
+
+/// The messages waiting to be written to a subscriber.
+#[derive(Default)]
+struct Queue {
+    messages: Mutex<VecDeque<Message>>,
+    ready: Notify,
+    disconnected: AtomicBool,
+}
+
+impl Queue {
+    /// Pushes a message onto the queue, applying the slow subscriber policy
+    /// if it is full. Returns `false` if the message won't be delivered
+    /// because the subscriber is disconnected.
+    fn push(&self, message: Message, config: &PubSub) -> bool {
+        if self.disconnected.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() >= config.max_queued_messages() {
+            match config.slow_subscriber_policy() {
+                SlowSubscriberPolicy::DropOldest => {
+                    messages.pop_front();
+                    PUBSUB_MESSAGES_DROPPED.increment();
+                }
+                SlowSubscriberPolicy::Disconnect => {
+                    PUBSUB_SLOW_SUBSCRIBER_DISCONNECTS.increment();
+                    PUBSUB_MESSAGES_DROPPED.add(messages.len() as u64 + 1);
+                    messages.clear();
+                    self.disconnected.store(true, Ordering::Relaxed);
+                    drop(messages);
+                    self.ready.notify_one();
+                    return false;
+                }
+            }
+        }
+        messages.push_back(message);
+        drop(messages);
+
+        self.ready.notify_one();
+        true
+    }
+}
+
+/// The pub/sub state of a single connection. A connection which is
+/// subscribed to at least one channel is in subscriber mode.
+pub(crate) struct Subscriber {
+    id: u64,
+    channels: Channels,
+    subscribed: BTreeSet<Vec<u8>>,
+    queue: Arc<Queue>,
+}
+
+impl Subscriber {
+    /// Returns `true` if the connection is in subscriber mode.
+    pub fn is_subscribed(&self) -> bool {
+        !self.subscribed.is_empty()
+    }
+
+    /// Subscribes to a channel, returning the reply which confirms it or an
+    /// error if the connection is subscribed to too many channels.
+    pub fn subscribe(&mut self, channel: &[u8]) -> Vec<u8> {
+        if !self.subscribed.contains(channel) {
+            if self.subscribed.len() >= self.channels.config.max_channels() {
+                return MAX_CHANNELS_ERROR.to_vec();
+            }
+
+            self.subscribed.insert(channel.to_vec());
+            PUBSUB_SUBSCRIPTIONS.increment();
+            self.channels
+                .subscribers
+                .lock()
+                .unwrap()
+                .entry(channel.to_vec())
+                .or_default()
+                .insert(self.id, self.queue.clone());
+        }
+
+        compose(b"subscribe", channel, self.subscribed.len())
+    }
+
+    /// Unsubscribes from the channels, or from every channel if none are
+    /// given, returning the replies which confirm it.
+    pub fn unsubscribe<'a>(&mut self, channels: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+        let mut channels: Vec<Vec<u8>> = channels.map(|c| c.to_vec()).collect();
+        if channels.is_empty() {
+            channels = self.subscribed.iter().cloned().collect();
+
+            // there is still a reply when there was nothing to unsubscribe
+            if channels.is_empty() {
+                return compose(b"unsubscribe", b"", 0);
+            }
+        }
+
+        let mut reply = Vec::new();
+        for channel in channels {
+            self.remove(&channel);
+            reply.extend_from_slice(&compose(b"unsubscribe", &channel, self.subscribed.len()));
+        }
+        reply
+    }
+
+    /// Waits for the next message published to one of the subscribed
+    /// channels, which is returned ready to write to the connection. Returns
+    /// `None` once the subscriber has been disconnected for falling behind.
+    pub async fn recv(&mut self) -> Option<Message> {
+        loop {
+            if self.queue.disconnected.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            let message = self.queue.messages.lock().unwrap().pop_front();
+            if message.is_some() {
+                return message;
+            }
+
+            self.queue.ready.notified().await;
+        }
+    }
+
+    fn remove(&mut self, channel: &[u8]) {
+        if !self.subscribed.remove(channel) {
+            return;
+        }
+        PUBSUB_SUBSCRIPTIONS.decrement();
+
+        let mut subscribers = self.channels.subscribers.lock().unwrap();
+        if let Some(channel_subscribers) = subscribers.get_mut(channel) {
+            channel_subscribers.remove(&self.id);
+            if channel_subscribers.is_empty() {
+                subscribers.remove(channel);
+            }
+        }
+        drop(subscribers);
+
+        // messages which are still queued when leaving subscriber mode are
+        // discarded, rather than delivered after a later subscribe
+        if self.subscribed.is_empty() {
+            self.queue.messages.lock().unwrap().clear();
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let channels: Vec<Vec<u8>> = self.subscribed.iter().cloned().collect();
+        for channel in channels {
+            self.remove(&channel);
+        }
+    }
+}
+
+// composes the reply to a subscribe or unsubscribe, which is an array of its
+// kind, the channel, and the number of channels subscribed to. An empty
+// channel is composed as a null bulk string
+fn compose(kind: &[u8], channel: &[u8], count: usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(b"*3\r\n");
+    bulk_string(&mut buf, kind);
+    if channel.is_empty() {
+        buf.extend_from_slice(b"$-1\r\n");
+    } else {
+        bulk_string(&mut buf, channel);
+    }
+    buf.extend_from_slice(format!(":{}\r\n", count).as_bytes());
+    buf
+}
+
+fn bulk_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(format!("${}\r\n", value.len()).as_bytes());
+    buf.extend_from_slice(value);
+    buf.extend_from_slice(b"\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn delivery() {
+        let channels = Channels::new(PubSub::default());
+        let mut subscriber = channels.subscriber();
+        let publisher = channels.subscriber();
+        assert!(!publisher.is_subscribed());
+
+        assert_eq!(channels.publish(b"news", b"hello"), 0);
+
+        assert_eq!(
+            subscriber.subscribe(b"news"),
+            b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n"
+        );
+        assert!(subscriber.is_subscribed());
+
+        assert_eq!(channels.publish(b"news", b"hello"), 1);
+        assert_eq!(channels.publish(b"weather", b"sunny"), 0);
+        assert_eq!(
+            &*subscriber.recv().await.unwrap(),
+            b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n"
+        );
+
+        assert_eq!(
+            subscriber.unsubscribe(std::iter::empty()),
+            b"*3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n:0\r\n"
+        );
+        assert!(!subscriber.is_subscribed());
+        assert_eq!(channels.publish(b"news", b"hello"), 0);
+
+        assert_eq!(
+            subscriber.unsubscribe(std::iter::empty()),
+            b"*3\r\n$11\r\nunsubscribe\r\n$-1\r\n:0\r\n"
+        );
+    }
+
+    #[test]
+    fn multiple_subscribers() {
+        let channels = Channels::new(PubSub::default());
+        let mut a = channels.subscriber();
+        let mut b = channels.subscriber();
+
+        a.subscribe(b"news");
+        a.subscribe(b"weather");
+        b.subscribe(b"news");
+        assert_eq!(channels.publish(b"news", b"hello"), 2);
+        assert_eq!(channels.publish(b"weather", b"sunny"), 1);
+
+        // closing a connection removes its subscriptions
+        drop(a);
+        assert_eq!(channels.publish(b"news", b"hello"), 1);
+        assert_eq!(channels.publish(b"weather", b"sunny"), 0);
+        assert!(channels
+            .subscribers
+            .lock()
+            .unwrap()
+            .get(&b"weather"[..])
+            .is_none());
+    }
+
+    // publishes many messages to a subscriber which never reads them
+    fn flood(channels: &Channels, count: usize) -> usize {
+        (0..count)
+            .map(|i| channels.publish(b"news", format!("{}", i).as_bytes()))
+            .sum()
+    }
+
+    fn config(toml: &str) -> PubSub {
+        toml::from_str(toml).expect("bad config")
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let channels = Channels::new(config("max_queued_messages = 4"));
+        let mut subscriber = channels.subscriber();
+        subscriber.subscribe(b"news");
+
+        // every message is delivered, but only the newest are kept
+        assert_eq!(flood(&channels, 1000), 1000);
+        assert_eq!(subscriber.queue.messages.lock().unwrap().len(), 4);
+
+        for i in 996..1000 {
+            let message = subscriber.recv().await.unwrap();
+            assert!(message.ends_with(format!("$3\r\n{}\r\n", i).as_bytes()));
+        }
+    }
+
+    #[tokio::test]
+    async fn disconnect() {
+        let channels = Channels::new(config(
+            "max_queued_messages = 4\nslow_subscriber_policy = \"disconnect\"",
+        ));
+        let mut subscriber = channels.subscriber();
+        subscriber.subscribe(b"news");
+
+        // messages stop being delivered once the queue overflows
+        assert_eq!(flood(&channels, 1000), 4);
+        assert!(subscriber.queue.messages.lock().unwrap().is_empty());
+        assert!(subscriber.recv().await.is_none());
+    }
+
+    #[test]
+    fn max_channels() {
+        let channels = Channels::new(config("max_channels = 1"));
+        let mut subscriber = channels.subscriber();
+
+        assert!(subscriber.subscribe(b"news").starts_with(b"*3\r\n"));
+        assert_eq!(subscriber.subscribe(b"weather"), MAX_CHANNELS_ERROR);
+
+        // subscribing again to the same channel is not a new subscription
+        assert!(subscriber.subscribe(b"news").ends_with(b":1\r\n"));
+        assert_eq!(channels.publish(b"weather", b"sunny"), 0);
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Bounded retries for backend requests.
+//!
+//! A backend failure falls into one of three buckets. If the backend rejected
+//! the request outright (eg: rate limited) nothing was applied and any request
+//! may be retried. If the request timed out we can't know whether the backend
+//! acted on it, so only idempotent requests are retried. Anything else is
+//! returned to the caller as-is.
+
+use crate::*;
+use tokio::time::error::Elapsed;
+
+/// Whether repeating a request is guaranteed to leave the backend in the same
+/// state as sending it once.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Idempotency {
+    /// Reads, which have no side effects on the backend.
+    Idempotent,
+    /// Writes. A `set` which timed out may have been applied and retrying it
+    /// could clobber a newer value written by another client in between.
+    NonIdempotent,
+}
+
+/// The class of a failed backend attempt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The backend refused the request without acting on it.
+    Rejected,
+    /// The outcome of the request is unknown.
+    Indeterminate,
+    /// The request failed and retrying would not help.
+    Permanent,
+}
+
+/// Classifies the result of a single backend attempt.
+pub trait Attempt {
+    /// Returns `None` if the attempt succeeded.
+    fn failure(&self) -> Option<Failure>;
+}
+
+impl<T> Attempt for Result<Result<T, MomentoError>, Elapsed> {
+    fn failure(&self) -> Option<Failure> {
+        match self {
+            Ok(Ok(_)) => None,
+            Ok(Err(MomentoError::LimitExceeded(_))) => Some(Failure::Rejected),
+            Ok(Err(_)) => Some(Failure::Permanent),
+            Err(_) => Some(Failure::Indeterminate),
+        }
+    }
+}
+
+/// Limits on how many times, and how quickly, a request is retried.
+#[derive(Copy, Clone, Debug)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub const fn new(max_retries: usize, backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+        }
+    }
+
+    /// Begin tracking retries for a single request.
+    pub fn start(&self, idempotency: Idempotency) -> Retry {
+        Retry {
+            idempotency,
+            remaining: self.max_retries,
+            backoff: self.backoff,
+        }
+    }
+}
+
+/// Retry state for a single request. The caller drives the loop, issuing the
+/// backend request and then asking if it should be sent again.
+pub struct Retry {
+    idempotency: Idempotency,
+    remaining: usize,
+    backoff: Duration,
+}
+
+impl Retry {
+    /// Returns `true` if the request should be issued again. Sleeps for the
+    /// current backoff, which doubles with each retry, before returning.
+    pub async fn should_retry<T: Attempt>(&mut self, attempt: &T) -> bool {
+        let retryable = match attempt.failure() {
+            None | Some(Failure::Permanent) => false,
+            Some(Failure::Rejected) => true,
+            Some(Failure::Indeterminate) => self.idempotency == Idempotency::Idempotent,
+        };
+
+        if !retryable {
+            return false;
+        }
+
+        if self.remaining == 0 {
+            BACKEND_RETRY_EXHAUSTED.increment();
+            return false;
+        }
+
+        self.remaining -= 1;
+        BACKEND_RETRIES.increment();
+
+        tokio::time::sleep(self.backoff).await;
+        self.backoff *= 2;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    type Outcome = Result<Result<u32, MomentoError>, Elapsed>;
+
+    // produces a real timeout error, as `Elapsed` can't be constructed directly
+    async fn timed_out() -> Outcome {
+        timeout(Duration::from_millis(1), std::future::pending()).await
+    }
+
+    // issues a request which times out on the first attempt and succeeds on
+    // any later attempt, returning the final outcome and the attempt count
+    async fn flaky(policy: RetryPolicy, idempotency: Idempotency) -> (Outcome, usize) {
+        let mut retry = policy.start(idempotency);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let result = if attempts == 1 {
+                timed_out().await
+            } else {
+                Ok(Ok(42))
+            };
+            if retry.should_retry(&result).await {
+                continue;
+            }
+            return (result, attempts);
+        }
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("failed to build runtime")
+    }
+
+    #[test]
+    fn idempotent() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(1));
+        let (result, attempts) = runtime().block_on(flaky(policy, Idempotency::Idempotent));
+        assert_eq!(attempts, 2);
+        assert!(matches!(result, Ok(Ok(42))));
+    }
+
+    #[test]
+    fn non_idempotent() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(1));
+        let (result, attempts) = runtime().block_on(flaky(policy, Idempotency::NonIdempotent));
+        assert_eq!(attempts, 1);
+        assert_eq!(result.failure(), Some(Failure::Indeterminate));
+    }
+
+    #[test]
+    fn exhausted() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(1));
+        let attempts = runtime().block_on(async {
+            let mut retry = policy.start(Idempotency::Idempotent);
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                let result = timed_out().await;
+                if !retry.should_retry(&result).await {
+                    return attempts;
+                }
+            }
+        });
+        assert_eq!(attempts, 3);
+    }
+}
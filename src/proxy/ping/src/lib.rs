@@ -48,7 +48,8 @@ impl Pingproxy {
             FrontendRequest,
             FrontendResponse,
         >::new(&config, log_drain, response_parser, request_parser)
-        .expect("failed to launch");
+        .expect("failed to launch")
+        .keepalive(|| Request::Ping);
         let process = process_builder.spawn();
 
         Self { process }
@@ -0,0 +1,117 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! This test runs the Pingproxy in front of a minimal ping backend with a
+//! short keepalive interval. The backend connection must be kept alive with
+//! pings while no requests are sent, and still serve requests afterwards.
+
+use config::PingproxyConfig;
+use pingproxy::Pingproxy;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// ports which don't collide with the other integration tests
+const PROXY_ADDR: &str = "127.0.0.1:12332";
+const BACKEND_ADDR: &str = "127.0.0.1:12333";
+
+const CONFIG: &str = r#"
+[admin]
+host = "127.0.0.1"
+port = "9988"
+
+[listener]
+address = "127.0.0.1:12332"
+
+[backend]
+keepalive_interval = 100
+endpoints = [
+	"127.0.0.1:12333",
+]
+"#;
+
+fn main() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let pings = Arc::new(AtomicUsize::new(0));
+    backend(connections.clone(), pings.clone());
+
+    let name = format!("pingproxy_keepalive_{}.toml", std::process::id());
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, CONFIG).expect("failed to write config");
+    let config = PingproxyConfig::load(path.to_str().unwrap());
+    let _ = std::fs::remove_file(&path);
+
+    let proxy = Pingproxy::new(config.expect("failed to load config"));
+
+    // wait for the proxy to startup
+    std::thread::sleep(Duration::from_secs(2));
+
+    println!("testing: keepalive");
+    let mut stream = TcpStream::connect(PROXY_ADDR).expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("failed to set read timeout");
+
+    ping(&mut stream);
+    let before = pings.load(Ordering::Relaxed);
+
+    // stay idle for several keepalive intervals
+    std::thread::sleep(Duration::from_secs(1));
+
+    let keepalives = pings.load(Ordering::Relaxed) - before;
+    assert!(keepalives > 0, "no keepalives were sent while idle");
+
+    // the same backend connection still serves requests
+    ping(&mut stream);
+    assert_eq!(connections.load(Ordering::Relaxed), 1);
+
+    proxy.shutdown();
+    println!("status: passed ({} keepalives)", keepalives);
+}
+
+// sends a ping through the proxy and checks that it is answered
+fn ping(stream: &mut TcpStream) {
+    stream
+        .write_all(b"PING\r\n")
+        .expect("failed to send request");
+
+    let mut response = [0; 6];
+    stream
+        .read_exact(&mut response)
+        .expect("failed to read response");
+    assert_eq!(&response, b"PONG\r\n");
+}
+
+// runs a backend which answers each ping, counting the connections it
+// accepts and the pings it receives
+fn backend(connections: Arc<AtomicUsize>, pings: Arc<AtomicUsize>) {
+    let listener = TcpListener::bind(BACKEND_ADDR).expect("failed to bind backend");
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.expect("failed to accept");
+            connections.fetch_add(1, Ordering::Relaxed);
+
+            let pings = pings.clone();
+            std::thread::spawn(move || {
+                let reader = BufReader::new(stream.try_clone().expect("failed to clone"));
+                for line in reader.lines() {
+                    match line {
+                        Ok(line) if line.trim_end() == "PING" => {
+                            pings.fetch_add(1, Ordering::Relaxed);
+                            if stream.write_all(b"PONG\r\n").is_err() {
+                                return;
+                            }
+                        }
+                        _ => {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
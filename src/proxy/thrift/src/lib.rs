@@ -13,6 +13,8 @@ type BackendParser = MessageParser;
 type BackendRequest = Message;
 type BackendResponse = Message;
 
+// messages are owned, rather than borrowed from the session buffers, as they
+// are passed between the frontend and backend threads by queue
 type FrontendParser = MessageParser;
 type FrontendRequest = Message;
 type FrontendResponse = Message;
@@ -14,6 +14,7 @@ use entrystore::Noop;
 use logger::*;
 use protocol_ping::{Request, RequestParser, Response};
 use server::{Process, ProcessBuilder};
+use std::time::Duration;
 
 type Parser = RequestParser;
 type Storage = Noop;
@@ -62,6 +63,14 @@ impl Pingserver {
     pub fn shutdown(self) {
         self.process.shutdown()
     }
+
+    /// Triggers a graceful drain of the process and blocks until the process
+    /// has fully terminated. New connections are refused, and open sessions
+    /// are closed once their in-flight requests complete, or when the timeout
+    /// has passed.
+    pub fn drain(self, timeout: Duration) {
+        self.process.drain(timeout)
+    }
 }
 
 common::metrics::test_no_duplicates!();
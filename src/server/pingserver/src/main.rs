@@ -54,6 +54,11 @@ fn main() {
                 .help("Server configuration file")
                 .index(1),
         )
+        .arg(
+            Arg::with_name("validate")
+                .help("Validate the config and exit without starting the server")
+                .long("validate"),
+        )
         .get_matches();
 
     if matches.is_present("stats") {
@@ -104,6 +109,18 @@ fn main() {
         Default::default()
     };
 
+    if matches.is_present("validate") {
+        let errors = server::validate(&config);
+        if errors.is_empty() {
+            println!("config is valid");
+            std::process::exit(0);
+        }
+        for error in errors {
+            eprintln!("{}", error);
+        }
+        std::process::exit(1);
+    }
+
     // launch
     match Pingserver::new(config) {
         Ok(s) => s.wait(),
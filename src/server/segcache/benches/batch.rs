@@ -0,0 +1,77 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Compares sending each request to the storage thread on its own with
+//! sending requests in batches. The server runs with multiple workers, and
+//! each iteration writes a get on every one of several connections before
+//! reading any of the responses, so that the workers have requests from more
+//! than one client to send to storage at once.
+
+use config::{SegcacheConfig, ServerConfig, WorkerConfig};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use pelikan_segcache_rs::Segcache;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::prelude::IntoRawFd;
+use std::time::Duration;
+
+const CONNECTIONS: usize = 16;
+
+fn batch_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("storage_batch");
+    group.throughput(Throughput::Elements(CONNECTIONS as u64));
+
+    for batch in [1, 32] {
+        // launch a multi-worker server on an unused port
+        let mut config = SegcacheConfig::default();
+        config.worker_mut().set_threads(2);
+        config.worker_mut().set_storage_batch(batch);
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let addr = listener.local_addr().expect("no local address");
+        config
+            .server_mut()
+            .set_listen_fd(Some(listener.into_raw_fd()));
+        let server = Segcache::new(config).expect("failed to launch segcache");
+
+        // wait for server to startup. duration is chosen to be longer than
+        // we'd expect startup to take in a slow ci environment.
+        std::thread::sleep(Duration::from_secs(10));
+
+        let mut streams: Vec<_> = (0..CONNECTIONS).map(|id| connect(addr, id)).collect();
+        let mut buffer = vec![0; 1024 * 1024];
+
+        group.bench_function(format!("get/{}", batch), |b| {
+            b.iter(|| {
+                for (id, stream) in streams.iter_mut().enumerate() {
+                    let msg = format!("get batch_{}\r\n", id);
+                    assert!(stream.write_all(msg.as_bytes()).is_ok());
+                }
+                for (id, stream) in streams.iter_mut().enumerate() {
+                    let response = format!("VALUE batch_{} 0 1\r\n{}\r\nEND\r\n", id, id % 10);
+                    let bytes = stream.read(&mut buffer).expect("read error");
+                    assert_eq!(&buffer[0..bytes], response.as_bytes(), "invalid response");
+                }
+            })
+        });
+
+        // shutdown the server before launching the next one
+        drop(streams);
+        server.shutdown();
+    }
+}
+
+// connects to the server and stores a one byte value for this connection
+fn connect(addr: SocketAddr, id: usize) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).expect("failed to connect");
+    let mut buffer = [0; 8];
+    let msg = format!("set batch_{} 0 0 1\r\n{}\r\n", id, id % 10);
+    assert!(stream.write_all(msg.as_bytes()).is_ok());
+    stream.read_exact(&mut buffer).expect("read error");
+    assert_eq!(&buffer, b"STORED\r\n", "invalid response");
+    stream
+}
+
+criterion_group!(benches, batch_benchmark);
+criterion_main!(benches);
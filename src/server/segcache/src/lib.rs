@@ -11,10 +11,22 @@ use entrystore::Seg;
 use logger::*;
 use protocol_memcache::{Request, RequestParser, Response};
 use server::{Process, ProcessBuilder};
+use std::time::Duration;
 
 type Parser = RequestParser;
 type Storage = Seg;
 
+/// Runs the same checks on the config that are performed when launching
+/// `Segcache`, including those of the storage, returning a description of
+/// each problem found. An empty result means the config is valid.
+pub fn validate(config: &SegcacheConfig) -> Vec<String> {
+    let mut errors = server::validate(config);
+    if let Err(e) = Storage::validate(config) {
+        errors.push(format!("seg: {}", e));
+    }
+    errors
+}
+
 /// This structure represents a running `Segcache` process.
 #[allow(dead_code)]
 pub struct Segcache {
@@ -34,9 +46,13 @@ impl Segcache {
         let storage = Storage::new(&config)?;
 
         // initialize parser
-        let parser = Parser::new()
+        let mut parser = Parser::new()
             .max_value_size(config.seg().segment_size() as usize)
-            .time_type(config.time().time_type());
+            .time_type(config.time().time_type())
+            .lenient_newlines(config.server().lenient_newlines());
+        if let Some(max) = config.server().max_line_length() {
+            parser = parser.max_line_length(max);
+        }
 
         // initialize process
         let process_builder = ProcessBuilder::<Parser, Request, Response, Storage>::new(
@@ -62,6 +78,14 @@ impl Segcache {
     pub fn shutdown(self) {
         self.process.shutdown()
     }
+
+    /// Triggers a graceful drain of the process and blocks until the process
+    /// has fully terminated. New connections are refused, and open sessions
+    /// are closed once their in-flight requests complete, or when the timeout
+    /// has passed.
+    pub fn drain(self, timeout: Duration) {
+        self.process.drain(timeout)
+    }
 }
 
 common::metrics::test_no_duplicates!();
@@ -61,6 +61,11 @@ fn main() {
                 .long("config")
                 .short("c"),
         )
+        .arg(
+            Arg::with_name("validate")
+                .help("Validate the config and exit without starting the server")
+                .long("validate"),
+        )
         .get_matches();
 
     // output stats descriptions and exit if the `stats` option was provided
@@ -117,6 +122,18 @@ fn main() {
         std::process::exit(0);
     }
 
+    if matches.is_present("validate") {
+        let errors = pelikan_segcache_rs::validate(&config);
+        if errors.is_empty() {
+            println!("config is valid");
+            std::process::exit(0);
+        }
+        for error in errors {
+            eprintln!("{}", error);
+        }
+        std::process::exit(1);
+    }
+
     // launch segcache
     match Segcache::new(config) {
         Ok(segcache) => segcache.wait(),
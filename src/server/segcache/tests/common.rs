@@ -7,6 +7,7 @@
 //! for multiple server configurations.
 
 use logger::*;
+use pelikan_segcache_rs::Segcache;
 
 use std::io::{Read, Write};
 use std::net::TcpStream;
@@ -31,6 +32,17 @@ pub fn tests() {
         ],
     );
 
+    // check that a zero-length value is stored and is distinct from a miss
+    test(
+        "set and get empty",
+        &[
+            // store the key with an empty value
+            ("set 21 0 0 0\r\n\r\n", Some("STORED\r\n")),
+            // retrieve the key
+            ("get 21\r\n", Some("VALUE 21 0 0\r\n\r\nEND\r\n")),
+        ],
+    );
+
     test(
         "cas not_found",
         &[
@@ -195,11 +207,58 @@ pub fn tests() {
         ],
     );
 
-    // test unsupported commands
-    test("append", &[("append 7 0 0 1\r\n0\r\n", Some("ERROR\r\n"))]);
+    // writes are refused while in read-only mode, but reads still succeed. the
+    // mode is toggled from the admin port
+    test(
+        "read only populate",
+        &[("set 20 0 0 1\r\n1\r\n", Some("STORED\r\n"))],
+    );
+    admin_test("read only enable", &[("readonly on\r\n", Some("OK\r\n"))]);
+    test(
+        "read only",
+        &[
+            // writes are refused
+            (
+                "set 20 0 0 1\r\n2\r\n",
+                Some("SERVER_ERROR server is in read-only mode\r\n"),
+            ),
+            // reads are served
+            ("get 20\r\n", Some("VALUE 20 0 1\r\n1\r\nEND\r\n")),
+        ],
+    );
+    admin_test("read only disable", &[("readonly off\r\n", Some("OK\r\n"))]);
+    test(
+        "read write",
+        &[
+            // writes are accepted again
+            ("set 20 0 0 1\r\n2\r\n", Some("STORED\r\n")),
+            ("get 20\r\n", Some("VALUE 20 0 1\r\n2\r\nEND\r\n")),
+        ],
+    );
+
+    test(
+        "append",
+        &[
+            // an item is not created for a missing key
+            ("append 22 0 0 1\r\n0\r\n", Some("NOT_STORED\r\n")),
+            ("get 22\r\n", Some("END\r\n")),
+            ("set 22 3 0 1\r\n0\r\n", Some("STORED\r\n")),
+            // flags are kept from the existing item
+            ("append 22 0 0 1\r\n1\r\n", Some("STORED\r\n")),
+            ("get 22\r\n", Some("VALUE 22 3 2\r\n01\r\nEND\r\n")),
+        ],
+    );
     test(
         "prepend",
-        &[("prepend 8 0 0 1\r\n0\r\n", Some("ERROR\r\n"))],
+        &[
+            // an item is not created for a missing key
+            ("prepend 23 0 0 1\r\n0\r\n", Some("NOT_STORED\r\n")),
+            ("get 23\r\n", Some("END\r\n")),
+            ("set 23 3 0 1\r\n0\r\n", Some("STORED\r\n")),
+            // flags are kept from the existing item
+            ("prepend 23 0 0 1\r\n1\r\n", Some("STORED\r\n")),
+            ("get 23\r\n", Some("VALUE 23 3 2\r\n10\r\nEND\r\n")),
+        ],
     );
 
     std::thread::sleep(Duration::from_millis(500));
@@ -272,6 +331,190 @@ fn test(name: &str, data: &[(&str, Option<&str>)]) {
     info!("status: passed\n");
 }
 
+// opens a new connection, sends the start of a `set` without completing it,
+// and checks that the server closes the connection once the request read
+// timeout has elapsed.
+pub fn request_read_timeout_tests(timeout: Duration) {
+    info!("testing: request read timeout");
+    debug!("connecting to server");
+    let mut stream = TcpStream::connect("127.0.0.1:12321").expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("failed to set read timeout");
+
+    // a partial header, the server can't parse this until it sees the rest
+    stream
+        .write_all(b"set 19 0 0 1")
+        .expect("failed to send request");
+
+    // give the server time to notice the stalled request. the workers check
+    // for stalled sessions once per event loop timeout, so allow for that too
+    std::thread::sleep(timeout * 2 + Duration::from_millis(250));
+
+    let mut buf = vec![0; 4096];
+    match stream.read(&mut buf) {
+        Ok(0) => {
+            debug!("connection closed");
+        }
+        Ok(_) => {
+            error!("unexpected response");
+            panic!("status: failed\n");
+        }
+        Err(e) => {
+            error!("connection was not closed: {}", e);
+            panic!("status: failed\n");
+        }
+    }
+    info!("status: passed\n");
+}
+
+// checks that a large request which arrives promptly is served, and that a
+// connection which trickles in the data block of a large `set` is sent an
+// error and closed once the request is over `size` bytes and `timeout` old.
+pub fn request_stall_tests(size: usize, timeout: Duration) {
+    info!("testing: request stall");
+    debug!("connecting to server");
+    let mut stream = TcpStream::connect("127.0.0.1:12321").expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("failed to set read timeout");
+
+    // a request larger than the limit is fine if it completes in time
+    let value = vec![b'a'; 2 * size];
+    let mut request = format!("set 25 0 0 {}\r\n", value.len()).into_bytes();
+    request.extend_from_slice(&value);
+    request.extend_from_slice(b"\r\n");
+    for chunk in request.chunks(size / 2) {
+        stream.write_all(chunk).expect("failed to send request");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let mut buf = vec![0; 4096];
+    let len = stream.read(&mut buf).expect("failed to read response");
+    assert_eq!(&buf[0..len], b"STORED\r\n");
+
+    // a large declared length followed by a slow trickle of data is cut off
+    // once the request is both over the size limit and too old
+    let mut stream = TcpStream::connect("127.0.0.1:12321").expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(10)))
+        .expect("failed to set read timeout");
+    stream
+        .write_all(b"set 26 0 0 1000000\r\n")
+        .expect("failed to send request");
+
+    let start = std::time::Instant::now();
+    let chunk = vec![b'a'; size / 8];
+    let mut len = 0;
+    for _ in 0..100 {
+        if stream.write_all(&chunk).is_err() {
+            break;
+        }
+        match stream.read(&mut buf) {
+            Ok(n) => {
+                len = n;
+                break;
+            }
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => {
+                error!("error reading response: {}", e);
+                panic!("status: failed\n");
+            }
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let expected = b"CLIENT_ERROR request stalled\r\n";
+    if &buf[0..len] != expected {
+        error!("expected: {:?}", expected);
+        error!("received: {:?}", &buf[0..len]);
+        panic!("status: failed\n");
+    }
+    assert!(start.elapsed() >= timeout);
+
+    // the data sent after the server closed the connection may reset it
+    stream
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("failed to set read timeout");
+    match stream.read(&mut buf) {
+        Ok(0) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => {}
+        _ => {
+            error!("connection was not closed");
+            panic!("status: failed\n");
+        }
+    }
+    info!("status: passed\n");
+}
+
+// opens a new connection, sends a `set` whose data block is longer than the
+// declared length, and checks that the server replies with an error before
+// closing the connection.
+pub fn bad_data_chunk_tests() {
+    info!("testing: bad data chunk");
+    debug!("connecting to server");
+    let mut stream = TcpStream::connect("127.0.0.1:12321").expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("failed to set read timeout");
+
+    stream
+        .write_all(b"set 24 0 0 1\r\n12\r\n")
+        .expect("failed to send request");
+
+    let expected = b"CLIENT_ERROR bad data chunk\r\n";
+    let mut buf = vec![0; 4096];
+    let len = stream.read(&mut buf).expect("failed to read response");
+    if &buf[0..len] != expected {
+        error!("expected: {:?}", expected);
+        error!("received: {:?}", &buf[0..len]);
+        panic!("status: failed\n");
+    }
+
+    assert_closed(&mut stream);
+    info!("status: passed\n");
+}
+
+// sends a get whose command line is exactly the max line length, which is
+// answered and leaves the connection usable. a longer line, which never ends,
+// gets an error and the connection is closed.
+pub fn max_line_length_tests(max_line_length: usize) {
+    info!("testing: max line length");
+    let mut stream = TcpStream::connect("127.0.0.1:12321").expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("failed to set read timeout");
+
+    let mut line = "get".to_string();
+    while max_line_length - line.len() > 101 {
+        line.push(' ');
+        line.push_str(&"a".repeat(100));
+    }
+    line.push(' ');
+    line.push_str(&"a".repeat(max_line_length - line.len()));
+    assert_eq!(line.len(), max_line_length);
+
+    let mut buf = vec![0; 4096];
+    for request in [format!("{}\r\n", line), "get b\r\n".to_string()] {
+        stream
+            .write_all(request.as_bytes())
+            .expect("failed to send request");
+        let len = stream.read(&mut buf).expect("failed to read response");
+        assert_eq!(&buf[0..len], b"END\r\n");
+    }
+
+    line.push_str(" b");
+    stream
+        .write_all(line.as_bytes())
+        .expect("failed to send request");
+    let len = stream.read(&mut buf).expect("failed to read response");
+    assert_eq!(&buf[0..len], b"CLIENT_ERROR line too long\r\n");
+
+    assert_closed(&mut stream);
+    info!("status: passed\n");
+}
+
 pub fn admin_tests() {
     debug!("beginning admin tests");
     println!();
@@ -283,6 +526,384 @@ pub fn admin_tests() {
             Some(&format!("VERSION {}\r\n", env!("CARGO_PKG_VERSION"))),
         )],
     );
+
+    admin_test(
+        "readonly",
+        &[
+            ("readonly on\r\n", Some("OK\r\n")),
+            ("readonly off\r\n", Some("OK\r\n")),
+        ],
+    );
+}
+
+// stores keys with flags and ttls, round-trips the storage through a snapshot
+// with `debug reload`, and checks that every key comes back unchanged. the key
+// with a ttl must still expire, so its ttl survived the reload too. builds
+// without the debug feature refuse the reload
+pub fn reload_tests() {
+    test(
+        "reload populate",
+        &[
+            ("set reload_a 7 0 5\r\nalpha\r\n", Some("STORED\r\n")),
+            ("set reload_b 0 0 2\r\n42\r\n", Some("STORED\r\n")),
+            ("set reload_c 3 9 5\r\ngamma\r\n", Some("STORED\r\n")),
+        ],
+    );
+
+    // the reply has the number of items, which includes those stored by
+    // earlier tests. building the fresh storage takes a while, so this waits
+    // longer than other admin requests
+    info!("testing: debug reload");
+    let mut admin = TcpStream::connect("127.0.0.1:9999").expect("failed to connect");
+    admin
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("failed to set read timeout");
+    let response = admin_request(&mut admin, "debug reload\r\n");
+
+    // reload is only available in debug builds
+    if !cfg!(feature = "debug") {
+        assert_eq!(response, "SERVER_ERROR reload is not supported\r\n");
+        info!("status: passed\n");
+        return;
+    }
+
+    assert!(response.starts_with("RELOADED "), "{:?}", response);
+    info!("status: passed\n");
+
+    test(
+        "reload get",
+        &[(
+            "get reload_a reload_b reload_c\r\n",
+            Some("VALUE reload_a 7 5\r\nalpha\r\nVALUE reload_b 0 2\r\n42\r\nVALUE reload_c 3 5\r\ngamma\r\nEND\r\n"),
+        )],
+    );
+
+    std::thread::sleep(Duration::from_secs(10));
+
+    test(
+        "reload ttl",
+        &[(
+            "get reload_a reload_c\r\n",
+            Some("VALUE reload_a 7 5\r\nalpha\r\nEND\r\n"),
+        )],
+    );
+}
+
+// opens two connections, finds one of them in the admin client list, and
+// closes it by id through the admin port. the other connection is closed by
+// its address, and neither close may affect the connection which remains.
+pub fn client_kill_tests() {
+    info!("testing: client kill");
+    let mut a = data_connection();
+    let mut b = data_connection();
+
+    let mut admin = TcpStream::connect("127.0.0.1:9999").expect("failed to connect");
+    admin
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("failed to set read timeout");
+
+    let list = admin_request(&mut admin, "client list\r\n");
+    let addr = format!("addr={}", a.local_addr().expect("no local address"));
+    let id = list
+        .lines()
+        .find(|line| line.ends_with(&addr))
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|field| field.strip_prefix("id="))
+        .unwrap_or_else(|| panic!("connection missing from client list: {:?}", list));
+
+    assert_eq!(
+        admin_request(&mut admin, &format!("client kill id {}\r\n", id)),
+        "KILLED 1\r\n"
+    );
+    assert_closed(&mut a);
+
+    // killing the same id again finds nothing
+    assert_eq!(
+        admin_request(&mut admin, &format!("client kill id {}\r\n", id)),
+        "KILLED 0\r\n"
+    );
+
+    // the other connection is unaffected
+    b.write_all(b"get client_kill\r\n")
+        .expect("failed to send request");
+    let mut buf = vec![0; 4096];
+    let len = b.read(&mut buf).expect("failed to read response");
+    assert_eq!(&buf[0..len], b"END\r\n");
+
+    let request = format!(
+        "client kill addr {}\r\n",
+        b.local_addr().expect("no local address")
+    );
+    assert_eq!(admin_request(&mut admin, &request), "KILLED 1\r\n");
+    assert_closed(&mut b);
+
+    info!("status: passed\n");
+}
+
+// marks a connection as no-evict by its address through the admin port. the
+// connection keeps working while marked, and the mark can be cleared again.
+pub fn client_no_evict_tests() {
+    info!("testing: client noevict");
+    let mut a = data_connection();
+
+    let mut admin = TcpStream::connect("127.0.0.1:9999").expect("failed to connect");
+    admin
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("failed to set read timeout");
+
+    let addr = a.local_addr().expect("no local address");
+    assert_eq!(
+        admin_request(&mut admin, &format!("client noevict addr {} on\r\n", addr)),
+        "UPDATED 1\r\n"
+    );
+
+    let mut buf = vec![0; 4096];
+    a.write_all(b"set client_no_evict 0 0 1\r\n1\r\n")
+        .expect("failed to send request");
+    let len = a.read(&mut buf).expect("failed to read response");
+    assert_eq!(&buf[0..len], b"STORED\r\n");
+
+    a.write_all(b"get client_no_evict\r\n")
+        .expect("failed to send request");
+    let len = a.read(&mut buf).expect("failed to read response");
+    assert_eq!(&buf[0..len], b"VALUE client_no_evict 0 1\r\n1\r\nEND\r\n");
+
+    assert_eq!(
+        admin_request(&mut admin, &format!("client noevict addr {} off\r\n", addr)),
+        "UPDATED 1\r\n"
+    );
+
+    // an id which does not belong to any connection matches nothing
+    assert_eq!(
+        admin_request(&mut admin, "client noevict id 18446744073709551615 on\r\n"),
+        "UPDATED 0\r\n"
+    );
+
+    info!("status: passed\n");
+}
+
+// drives several connections at once so that requests from different clients
+// share a trip to the storage thread, and checks that every response reaches
+// the client which sent the request. each client also pipelines its gets so
+// that the responses must come back in the order they were requested.
+pub fn concurrent_tests() {
+    info!("testing: concurrent clients");
+    let clients: Vec<_> = (0..8)
+        .map(|id| std::thread::spawn(move || concurrent_client(id)))
+        .collect();
+
+    for client in clients {
+        if client.join().is_err() {
+            panic!("status: failed\n");
+        }
+    }
+
+    info!("status: passed\n");
+}
+
+fn concurrent_client(id: usize) {
+    const KEYS: usize = 64;
+
+    let mut stream = TcpStream::connect("127.0.0.1:12321").expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(1000)))
+        .expect("failed to set read timeout");
+
+    let mut pipeline = String::new();
+    let mut expected = String::new();
+
+    for i in 0..KEYS {
+        let key = format!("concurrent_{}_{}", id, i);
+        let value = format!("value_{}_{}", id, i);
+
+        let request = format!("set {} 0 0 {}\r\n{}\r\n", key, value.len(), value);
+        stream
+            .write_all(request.as_bytes())
+            .expect("failed to send request");
+        let mut buf = [0; 8];
+        stream
+            .read_exact(&mut buf)
+            .expect("failed to read response");
+        assert_eq!(&buf, b"STORED\r\n", "client {} set {}", id, key);
+
+        pipeline.push_str(&format!("get {}\r\n", key));
+        expected.push_str(&format!(
+            "VALUE {} 0 {}\r\n{}\r\nEND\r\n",
+            key,
+            value.len(),
+            value
+        ));
+    }
+
+    stream
+        .write_all(pipeline.as_bytes())
+        .expect("failed to send requests");
+    let mut buf = vec![0; expected.len()];
+    stream
+        .read_exact(&mut buf)
+        .expect("failed to read responses");
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        expected,
+        "client {} got mismatched responses",
+        id
+    );
+}
+
+/// Pipelines writes and reads of the same keys on several connections at
+/// once. Each read must observe the write which the connection made just
+/// before it, even though the requests are in flight together.
+pub fn pipelined_tests() {
+    info!("testing: pipelined writes and reads");
+    let clients: Vec<_> = (0..8)
+        .map(|id| std::thread::spawn(move || pipelined_client(id)))
+        .collect();
+
+    for client in clients {
+        if client.join().is_err() {
+            panic!("status: failed\n");
+        }
+    }
+
+    info!("status: passed\n");
+}
+
+fn pipelined_client(id: usize) {
+    const ROUNDS: usize = 256;
+
+    let mut stream = TcpStream::connect("127.0.0.1:12321").expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(1000)))
+        .expect("failed to set read timeout");
+
+    // each connection overwrites a few keys over and over, so a read which ran
+    // before the write ahead of it would return a stale value
+    let mut pipeline = String::new();
+    let mut expected = String::new();
+    for round in 0..ROUNDS {
+        let key = format!("pipelined_{}_{}", id, round % 4);
+        let value = format!("{}", round);
+
+        pipeline.push_str(&format!("set {} 0 0 {}\r\n{}\r\n", key, value.len(), value));
+        pipeline.push_str(&format!("get {}\r\n", key));
+        expected.push_str("STORED\r\n");
+        expected.push_str(&format!(
+            "VALUE {} 0 {}\r\n{}\r\nEND\r\n",
+            key,
+            value.len(),
+            value
+        ));
+    }
+
+    stream
+        .write_all(pipeline.as_bytes())
+        .expect("failed to send requests");
+    let mut buf = vec![0; expected.len()];
+    stream
+        .read_exact(&mut buf)
+        .expect("failed to read responses");
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        expected,
+        "client {} read a stale value",
+        id
+    );
+}
+
+// drains the server while one connection is in the middle of a request and
+// another is idle. the listening socket is closed so new connections are
+// refused, the idle connection is closed, and the in-flight request still gets
+// its response before the connection is closed and the server shuts down.
+pub fn drain_tests(server: Segcache) {
+    info!("testing: graceful drain");
+    let mut idle = data_connection();
+    let mut stream = data_connection();
+
+    // a partial request, which is in flight when the drain starts
+    stream
+        .write_all(b"set 30 0 0 5\r\n12")
+        .expect("failed to send request");
+
+    let drain = std::thread::spawn(move || server.drain(Duration::from_secs(10)));
+
+    // give the listener and workers time to see the drain
+    std::thread::sleep(Duration::from_millis(500));
+
+    if TcpStream::connect("127.0.0.1:12321").is_ok() {
+        error!("connection was accepted while draining");
+        panic!("status: failed\n");
+    }
+
+    assert_closed(&mut idle);
+
+    stream
+        .write_all(b"345\r\n")
+        .expect("failed to send request");
+    let mut buf = vec![0; 4096];
+    let len = stream.read(&mut buf).expect("failed to read response");
+    assert_eq!(&buf[0..len], b"STORED\r\n");
+
+    assert_closed(&mut stream);
+
+    if drain.join().is_err() {
+        panic!("status: failed\n");
+    }
+
+    info!("status: passed\n");
+}
+
+// opens a connection to the data port and waits for a worker to take it
+fn data_connection() -> TcpStream {
+    let mut stream = TcpStream::connect("127.0.0.1:12321").expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("failed to set read timeout");
+
+    stream
+        .write_all(b"get client_kill\r\n")
+        .expect("failed to send request");
+    let mut buf = vec![0; 4096];
+    let len = stream.read(&mut buf).expect("failed to read response");
+    assert_eq!(&buf[0..len], b"END\r\n");
+
+    stream
+}
+
+// sends a request on an admin connection and returns the complete response
+fn admin_request(stream: &mut TcpStream, request: &str) -> String {
+    stream
+        .write_all(request.as_bytes())
+        .expect("failed to send request");
+
+    let mut response = Vec::new();
+    let mut buf = vec![0; 4096];
+    while !response.ends_with(b"\r\n")
+        || (response.starts_with(b"CLIENT") && !response.ends_with(b"END\r\n"))
+    {
+        let len = stream.read(&mut buf).expect("failed to read response");
+        assert!(len > 0, "admin connection closed");
+        response.extend_from_slice(&buf[0..len]);
+    }
+
+    String::from_utf8(response).expect("response is not utf8")
+}
+
+// checks that the server has closed the connection
+fn assert_closed(stream: &mut TcpStream) {
+    let mut buf = vec![0; 4096];
+    match stream.read(&mut buf) {
+        Ok(0) => {
+            debug!("connection closed");
+        }
+        Ok(_) => {
+            error!("unexpected response");
+            panic!("status: failed\n");
+        }
+        Err(e) => {
+            error!("connection was not closed: {}", e);
+            panic!("status: failed\n");
+        }
+    }
 }
 
 // opens a new connection to the admin port, sends a request, and checks the response.
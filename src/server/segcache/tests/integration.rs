@@ -12,14 +12,32 @@ extern crate logger;
 
 use crate::common::*;
 
-use config::SegcacheConfig;
+use config::{SegcacheConfig, ServerConfig, WorkerConfig};
 use pelikan_segcache_rs::Segcache;
 
 use std::time::Duration;
 
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_millis(500);
+const REQUEST_STALL_SIZE: usize = 16 * 1024;
+const REQUEST_STALL_TIMEOUT: Duration = Duration::from_millis(200);
+const MAX_LINE_LENGTH: usize = 1024;
+
 fn main() {
     debug!("launching server");
-    let server = Segcache::new(SegcacheConfig::default()).expect("failed to launch segcache");
+    let mut config = SegcacheConfig::default();
+    config
+        .worker_mut()
+        .set_request_read_timeout(REQUEST_READ_TIMEOUT.as_millis() as usize);
+    config
+        .worker_mut()
+        .set_request_stall_size(REQUEST_STALL_SIZE);
+    config
+        .worker_mut()
+        .set_request_stall_timeout(REQUEST_STALL_TIMEOUT.as_millis() as usize);
+    config
+        .server_mut()
+        .set_max_line_length(Some(MAX_LINE_LENGTH));
+    let server = Segcache::new(config).expect("failed to launch segcache");
 
     // wait for server to startup. duration is chosen to be longer than we'd
     // expect startup to take in a slow ci environment.
@@ -27,11 +45,29 @@ fn main() {
 
     tests();
 
+    request_read_timeout_tests(REQUEST_READ_TIMEOUT);
+
+    request_stall_tests(REQUEST_STALL_SIZE, REQUEST_STALL_TIMEOUT);
+
+    bad_data_chunk_tests();
+
+    max_line_length_tests(MAX_LINE_LENGTH);
+
     admin_tests();
 
-    // shutdown server and join
-    info!("shutdown...");
-    let _ = server.shutdown();
+    reload_tests();
+
+    client_kill_tests();
+
+    client_no_evict_tests();
+
+    concurrent_tests();
+
+    pipelined_tests();
+
+    // drain the server, which shuts it down once the sessions are closed
+    info!("drain...");
+    drain_tests(server);
 
     info!("passed!");
 }
@@ -0,0 +1,191 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! This test launches Segcache as its own process and upgrades it in place
+//! while clients keep connecting. Every connection must be accepted and get a
+//! response, the old process must drain and exit on its own, and the new
+//! process must be left serving both the data and admin ports.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// ports which don't collide with the other integration tests
+const DATA_ADDR: &str = "127.0.0.1:12331";
+const ADMIN_ADDR: &str = "127.0.0.1:9989";
+
+const CONFIG: &str = r#"
+[admin]
+host = "127.0.0.1"
+port = "9989"
+drain_timeout = 10000
+
+[server]
+host = "127.0.0.1"
+port = "12331"
+
+[worker]
+threads = 2
+
+[seg]
+heap_size = 16777216
+"#;
+
+const CLIENTS: usize = 4;
+
+fn main() {
+    let name = format!("segcache_upgrade_{}.toml", std::process::id());
+    let mut cleanup = Cleanup {
+        config: std::env::temp_dir().join(name),
+        pids: Vec::new(),
+    };
+    std::fs::write(&cleanup.config, CONFIG).expect("failed to write config");
+
+    let mut server = Command::new(env!("CARGO_BIN_EXE_pelikan_segcache_rs"))
+        .arg(&cleanup.config)
+        .spawn()
+        .expect("failed to launch segcache");
+    cleanup.pids.push(server.id());
+
+    wait_for(DATA_ADDR);
+    wait_for(ADMIN_ADDR);
+
+    // each client uses a new connection for every request, so none of them
+    // hold the old process open once it has been upgraded
+    let running = Arc::new(AtomicBool::new(true));
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|id| {
+            let running = running.clone();
+            std::thread::spawn(move || client(id, &running))
+        })
+        .collect();
+
+    std::thread::sleep(Duration::from_millis(500));
+
+    println!("testing: upgrade");
+    let response = admin_request("upgrade\r\n");
+    let pid: u32 = response
+        .strip_prefix("UPGRADED ")
+        .and_then(|pid| pid.trim_end().parse().ok())
+        .unwrap_or_else(|| panic!("upgrade failed: {:?}", response));
+    cleanup.pids.push(pid);
+    assert_ne!(pid, server.id());
+
+    // the old process drains and exits on its own
+    let status = wait_exit(&mut server, Duration::from_secs(30));
+    assert!(status.success(), "old process exited with: {}", status);
+
+    // keep the clients going against the new process alone for a while
+    std::thread::sleep(Duration::from_millis(500));
+    running.store(false, Ordering::Relaxed);
+
+    let mut requests = 0;
+    for client in clients {
+        requests += client.join().expect("client failed");
+    }
+    assert!(requests > 0, "no requests were made");
+
+    // the admin listener was handed over too
+    let response = admin_request("version\r\n");
+    assert!(
+        response.starts_with("VERSION "),
+        "bad version: {:?}",
+        response
+    );
+
+    println!("status: passed ({} requests)", requests);
+}
+
+// kills any processes which are still running and removes the config file,
+// even if the test fails
+struct Cleanup {
+    config: PathBuf,
+    pids: Vec<u32>,
+}
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        for pid in &self.pids {
+            let _ = Command::new("kill").arg(pid.to_string()).status();
+        }
+        let _ = std::fs::remove_file(&self.config);
+    }
+}
+
+// sets a key on a new connection until told to stop, returning the number of
+// requests made. panics if any connection or request fails
+fn client(id: usize, running: &AtomicBool) -> usize {
+    let mut requests = 0;
+    while running.load(Ordering::Relaxed) {
+        let mut stream = TcpStream::connect(DATA_ADDR)
+            .unwrap_or_else(|e| panic!("client {} failed to connect: {}", id, e));
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("failed to set read timeout");
+
+        let request = format!("set upgrade_{} 0 0 1\r\n{}\r\n", id, requests % 10);
+        stream
+            .write_all(request.as_bytes())
+            .unwrap_or_else(|e| panic!("client {} failed to send request: {}", id, e));
+
+        let mut response = [0; 8];
+        stream
+            .read_exact(&mut response)
+            .unwrap_or_else(|e| panic!("client {} failed to read response: {}", id, e));
+        assert_eq!(&response, b"STORED\r\n", "client {}", id);
+
+        requests += 1;
+    }
+    requests
+}
+
+// waits for the server to start accepting on the address
+fn wait_for(addr: &str) {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while TcpStream::connect(addr).is_err() {
+        assert!(
+            Instant::now() < deadline,
+            "server never listened on {}",
+            addr
+        );
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// waits for the process to exit on its own
+fn wait_exit(child: &mut Child, timeout: Duration) -> ExitStatus {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().expect("failed to wait for process") {
+            return status;
+        }
+        assert!(Instant::now() < deadline, "process did not exit");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// sends a request on a new admin connection and returns the response line
+fn admin_request(request: &str) -> String {
+    let mut stream = TcpStream::connect(ADMIN_ADDR).expect("failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(60)))
+        .expect("failed to set read timeout");
+    stream
+        .write_all(request.as_bytes())
+        .expect("failed to send request");
+
+    let mut response = Vec::new();
+    let mut buf = [0; 4096];
+    while !response.ends_with(b"\r\n") {
+        let len = stream.read(&mut buf).expect("failed to read response");
+        assert!(len > 0, "admin connection closed");
+        response.extend_from_slice(&buf[0..len]);
+    }
+
+    String::from_utf8(response).expect("response is not utf8")
+}
@@ -52,8 +52,40 @@ impl Buffer {
         self.cap
     }
 
+    /// Frees the memory held by an empty buffer, returning false if the buffer
+    /// still holds data. The buffer is allocated again at its `target_size`
+    /// when space is next reserved.
+    pub fn release(&mut self) -> bool {
+        if self.remaining() > 0 {
+            return false;
+        }
+
+        if self.cap > 0 {
+            SESSION_BUFFER_BYTE.sub(self.cap as _);
+
+            let layout = Layout::array::<u8>(self.cap).unwrap();
+            unsafe { dealloc(self.ptr, layout) };
+            self.ptr = std::ptr::NonNull::dangling().as_ptr();
+            self.cap = 0;
+        }
+
+        self.read_offset = 0;
+        self.write_offset = 0;
+        true
+    }
+
     /// Reserve space for `amt` additional bytes.
     pub fn reserve(&mut self, amt: usize) {
+        // a released buffer is allocated again before it can grow, since
+        // reallocating a zero sized allocation isn't allowed
+        if self.cap == 0 {
+            let layout = Layout::array::<u8>(self.target_size).unwrap();
+            self.ptr = unsafe { alloc(layout) };
+            self.cap = self.target_size;
+
+            SESSION_BUFFER_BYTE.add(self.cap as _);
+        }
+
         // if the buffer is empty, reset the offsets
         if self.remaining() == 0 {
             self.read_offset = 0;
@@ -452,4 +484,37 @@ mod tests {
         assert_eq!(buffer.remaining(), 0);
         assert_eq!(buffer.remaining_mut(), 16);
     }
+
+    #[test]
+    // tests that a released buffer is allocated again when it is written to
+    fn release() {
+        let mut buffer = Buffer::new(16);
+        buffer.put_slice(b"END\r\n");
+
+        // a buffer holding data is not released
+        assert!(!buffer.release());
+        assert_eq!(buffer.capacity(), 16);
+
+        buffer.advance(5);
+        assert!(buffer.release());
+        assert_eq!(buffer.capacity(), 0);
+        assert_eq!(buffer.remaining(), 0);
+        assert_eq!(buffer.remaining_mut(), 0);
+
+        // the next write allocates the target size again
+        buffer.put_slice(b"GET KEY\r\n");
+        assert_eq!(buffer.capacity(), 16);
+        assert_eq!(buffer.remaining(), 9);
+
+        let content: &[u8] = buffer.borrow();
+        assert_eq!(content, b"GET KEY\r\n");
+
+        // and may grow beyond it
+        assert!(!buffer.release());
+        buffer.advance(9);
+        assert!(buffer.release());
+        buffer.put_slice(b"SET SOME_REALLY_LONG_KEY 0 0 1\r\nA\r\n");
+        assert_eq!(buffer.remaining(), 35);
+        assert_eq!(buffer.capacity(), 64);
+    }
 }
@@ -88,7 +88,11 @@ where
                 Ok((request, msg))
             }
             Err(e) => {
-                if e.kind() != ErrorKind::WouldBlock {
+                if e.kind() == ErrorKind::WouldBlock {
+                    if let Some(needed) = self.parser.bytes_needed(src) {
+                        self.session.reserve_read(needed);
+                    }
+                } else {
                     SESSION_RECV_EX.increment();
                 }
                 Err(e)
@@ -117,6 +121,12 @@ where
         self.session.interest()
     }
 
+    /// Returns an error if the underlying session failed to connect, or if it
+    /// is still connecting after its connect timeout.
+    pub fn check_connect(&mut self) -> Result<()> {
+        self.session.check_connect()
+    }
+
     /// Attempt to handshake the underlying session.
     pub fn do_handshake(&mut self) -> Result<()> {
         self.session.do_handshake()
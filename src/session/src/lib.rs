@@ -14,10 +14,12 @@ extern crate log;
 
 mod buffer;
 mod client;
+mod limit;
 mod server;
 
 pub use buffer::*;
 pub use client::ClientSession;
+pub use limit::{IpLimiter, IpPermit};
 pub use server::ServerSession;
 
 use std::os::unix::prelude::AsRawFd;
@@ -28,6 +30,7 @@ use core::fmt::Debug;
 use core::marker::PhantomData;
 use protocol_common::Compose;
 use protocol_common::Parse;
+use protocol_common::SessionAction;
 use rustcommon_metrics::*;
 use rustcommon_time::Nanoseconds;
 use std::collections::VecDeque;
@@ -36,6 +39,8 @@ use std::io::ErrorKind;
 use std::io::Read;
 use std::io::Result;
 use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 const ONE_SECOND: u64 = 1_000_000_000; // in nanoseconds
 
@@ -43,6 +48,10 @@ gauge!(
     SESSION_BUFFER_BYTE,
     "current size of the session buffers in bytes"
 );
+gauge!(
+    SESSION_AVG_READ_BYTE,
+    "moving average of the number of bytes returned by each read from sessions"
+);
 
 counter!(SESSION_RECV, "number of reads from sessions");
 counter!(
@@ -56,6 +65,18 @@ counter!(
     "number of exceptions while writing to sessions"
 );
 counter!(SESSION_SEND_BYTE, "number of bytes written to sessions");
+counter!(
+    SESSION_OUTPUT_BACKPRESSURE,
+    "number of times a session stopped taking requests until its pending responses drained"
+);
+counter!(
+    SESSION_REQUEST_STALL,
+    "number of sessions with a large request which did not complete in time"
+);
+counter!(
+    SESSION_IDLE_BUFFER_RELEASED,
+    "number of times the buffers of an idle session were freed"
+);
 
 heatmap!(
     REQUEST_LATENCY,
@@ -63,32 +84,86 @@ heatmap!(
     "distribution of request latencies in nanoseconds"
 );
 
+// The largest lifetime byte count which is recorded for a session, larger
+// counts are recorded as this value.
+const MAX_CONNECTION_BYTES: u64 = 1 << 40;
+
+heatmap!(
+    CONNECTION_BYTES_RX,
+    MAX_CONNECTION_BYTES,
+    "distribution of the number of bytes read from each session over its lifetime"
+);
+heatmap!(
+    CONNECTION_BYTES_TX,
+    MAX_CONNECTION_BYTES,
+    "distribution of the number of bytes written to each session over its lifetime"
+);
+
 type Instant = rustcommon_time::Instant<Nanoseconds<u64>>;
 
 // The size of one kilobyte, in bytes
 const KB: usize = 1024;
 
-// If the read buffer has less than this amount available before a read, we will
-// grow the read buffer. The selected value is set to the size of a single page.
-const BUFFER_MIN_FREE: usize = 4 * KB;
+// The default bounds on the size of read operations. The lower bound is set to
+// the size of a single page.
+const MIN_READ_SIZE: usize = 4 * KB;
+const MAX_READ_SIZE: usize = 256 * KB;
 
 // The target size of the read operations, the selected value is the upper-bound
 // on TLS fragment size as per RFC 5246:
 // https://datatracker.ietf.org/doc/html/rfc5246#section-6.2.1
 const TARGET_READ_SIZE: usize = 16 * KB;
 
+// The number of consecutive reads which must fill the read size before it is
+// doubled.
+const FULL_READS_TO_GROW: usize = 4;
+
+// The most read buffer space which will be reserved ahead of a read because a
+// parser reported that more bytes are needed. This keeps a bogus length in a
+// partial message from causing a huge allocation.
+const MAX_READ_HINT: usize = 1024 * KB;
+
 // The initial size of any queues which track pending requests and responses.
 // This is *not* a hard bound, but is used to size the initial allocations.
 const NUM_PENDING: usize = 256;
 
+// The id to assign to the next session. Ids start at one and are never reused
+// for the lifetime of the process.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
 /// A `Session` is an underlying `Stream` with its read and write buffers. This
 /// abstraction allows the caller to efficiently read from the underlying stream
 /// by buffering the incoming bytes. It also allows for efficient writing by
 /// first buffering writes to the underlying stream.
 pub struct Session {
+    id: u64,
     stream: Stream,
     read_buffer: Buffer,
     write_buffer: Buffer,
+    read_size: usize,
+    min_read_size: usize,
+    max_read_size: usize,
+    // a moving average of the bytes returned by each read
+    avg_read_size: usize,
+    // the number of reads in a row which filled the read size
+    full_reads: usize,
+    // the place of this session in the count of sessions from its client
+    // address, which is given back when the session is dropped
+    ip_permit: Option<IpPermit>,
+    // when the session was created, which is when its handshake began
+    created: Instant,
+    // the bytes read from and written to the stream, which are recorded in
+    // the lifetime byte distributions when the session is dropped
+    bytes_rx: u64,
+    bytes_tx: u64,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let now = Instant::now();
+        CONNECTION_BYTES_RX.increment(now, self.bytes_rx.min(MAX_CONNECTION_BYTES), 1);
+        CONNECTION_BYTES_TX.increment(now, self.bytes_tx.min(MAX_CONNECTION_BYTES), 1);
+    }
 }
 
 impl AsRawFd for Session {
@@ -108,12 +183,70 @@ impl Session {
     /// `SessionBuffer`s.
     pub fn new(stream: Stream, read_buffer: Buffer, write_buffer: Buffer) -> Self {
         Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             stream,
             read_buffer,
             write_buffer,
+            read_size: TARGET_READ_SIZE,
+            min_read_size: MIN_READ_SIZE,
+            max_read_size: MAX_READ_SIZE,
+            avg_read_size: TARGET_READ_SIZE,
+            full_reads: 0,
+            ip_permit: None,
+            created: Instant::now(),
+            bytes_rx: 0,
+            bytes_tx: 0,
         }
     }
 
+    /// Returns the id which was assigned to the `Session` when it was created.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the address of the remote end of the `Session`.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Attaches the permit which counts this session against the limit on
+    /// sessions from its client address. The permit is given back when the
+    /// session is dropped, wherever that happens.
+    pub fn set_ip_permit(&mut self, permit: IpPermit) {
+        self.ip_permit = Some(permit);
+    }
+
+    /// Sets the bounds on the size of each read. The read size doubles when
+    /// reads keep filling it and halves when the average read is less than a
+    /// quarter of it, so connections which send large requests need fewer
+    /// reads and those which send small ones don't reserve buffer space they
+    /// won't use.
+    pub fn set_read_size_limits(&mut self, min: usize, max: usize) {
+        self.min_read_size = min.max(1);
+        self.max_read_size = max.max(self.min_read_size);
+        self.read_size = self.read_size.clamp(self.min_read_size, self.max_read_size);
+    }
+
+    /// Returns the number of bytes read from the stream so far.
+    pub fn bytes_rx(&self) -> u64 {
+        self.bytes_rx
+    }
+
+    /// Returns the number of bytes written to the stream so far.
+    pub fn bytes_tx(&self) -> u64 {
+        self.bytes_tx
+    }
+
+    /// Returns the current size of each read.
+    pub fn read_size(&self) -> usize {
+        self.read_size
+    }
+
+    /// Returns a moving average of the number of bytes returned by each read.
+    pub fn avg_read_size(&self) -> usize {
+        self.avg_read_size
+    }
+
     /// Return the event `Interest`s for the `Session`.
     pub fn interest(&mut self) -> Interest {
         if self.write_buffer.has_remaining() {
@@ -129,10 +262,24 @@ impl Session {
         self.stream.is_established()
     }
 
+    /// Returns an error if the underlying stream failed to connect, or if it
+    /// is still connecting after its connect timeout.
+    pub fn check_connect(&mut self) -> Result<()> {
+        self.stream.check_connect()
+    }
+
     pub fn is_handshaking(&self) -> bool {
         self.stream.is_handshaking()
     }
 
+    /// Returns true if the session is still handshaking more than `timeout`
+    /// after it was created. This allows the caller to drop clients which open
+    /// a connection but never complete the handshake.
+    pub fn handshake_timed_out(&self, timeout: core::time::Duration) -> bool {
+        self.is_handshaking()
+            && (Instant::now() - self.created).as_nanos() >= timeout.as_nanos() as u64
+    }
+
     /// Fill the read buffer by calling read on the underlying stream until read
     /// would block. Returns the number of bytes read. `Ok(0)` indicates that
     /// the remote side has closed the stream.
@@ -141,12 +288,13 @@ impl Session {
 
         loop {
             // if the buffer has too little space available, expand it
-            if self.read_buffer.remaining_mut() < BUFFER_MIN_FREE {
-                self.read_buffer.reserve(TARGET_READ_SIZE);
+            if self.read_buffer.remaining_mut() < self.read_size {
+                self.read_buffer.reserve(self.read_size);
             }
 
-            // read directly into the read buffer
-            match self.stream.read(self.read_buffer.borrow_mut()) {
+            // read directly into the read buffer, up to the read size
+            let buf: &mut [u8] = self.read_buffer.borrow_mut();
+            match self.stream.read(&mut buf[..self.read_size]) {
                 Ok(0) => {
                     // This means the underlying stream is closed, we need to
                     // notify the caller by returning this result.
@@ -159,6 +307,8 @@ impl Session {
                         self.read_buffer.advance_mut(n);
                     }
                     read += n;
+                    self.bytes_rx += n as u64;
+                    self.adapt_read_size(n);
                 }
                 Err(e) => match e.kind() {
                     ErrorKind::WouldBlock => {
@@ -177,6 +327,28 @@ impl Session {
         }
     }
 
+    fn adapt_read_size(&mut self, read: usize) {
+        self.avg_read_size = (self.avg_read_size * 7 + read) / 8;
+
+        // the gauge averages the reads of all sessions. an update which races
+        // with one from another thread may be lost, which is fine for a metric
+        let avg = SESSION_AVG_READ_BYTE.value() as usize;
+        SESSION_AVG_READ_BYTE.set(((avg * 7 + read) / 8) as i64);
+
+        if read >= self.read_size {
+            self.full_reads += 1;
+            if self.full_reads >= FULL_READS_TO_GROW {
+                self.full_reads = 0;
+                self.read_size = (self.read_size * 2).min(self.max_read_size);
+            }
+        } else {
+            self.full_reads = 0;
+            if self.avg_read_size < self.read_size / 4 {
+                self.read_size = (self.read_size / 2).max(self.min_read_size);
+            }
+        }
+    }
+
     /// Mark `amt` bytes as consumed from the read buffer.
     pub fn consume(&mut self, amt: usize) {
         self.read_buffer.advance(amt)
@@ -220,6 +392,7 @@ impl Session {
             }
         }
 
+        self.bytes_tx += flushed as u64;
         SESSION_SEND_BYTE.add(flushed as _);
 
         Ok(flushed)
@@ -233,9 +406,31 @@ impl Session {
         &mut self.read_buffer
     }
 
+    /// Makes room in the read buffer for the rest of a partial message, so it
+    /// can be completed by the next read instead of several smaller ones.
+    fn reserve_read(&mut self, needed: usize) {
+        self.read_buffer.reserve(needed.min(MAX_READ_HINT));
+    }
+
     pub fn write_buffer_mut(&mut self) -> &mut Buffer {
         &mut self.write_buffer
     }
+
+    /// Frees the read and write buffers if both are empty, returning false if
+    /// either holds data. The buffers are allocated again by the next read or
+    /// write, so the stream stays registered and usable in the meantime.
+    pub fn release_buffers(&mut self) -> bool {
+        if self.read_buffer.has_remaining() || self.write_buffer.has_remaining() {
+            return false;
+        }
+        self.read_buffer.release() && self.write_buffer.release()
+    }
+
+    /// Returns true if the buffers have been freed and not yet allocated
+    /// again.
+    pub fn buffers_released(&self) -> bool {
+        self.read_buffer.capacity() == 0 && self.write_buffer.capacity() == 0
+    }
 }
 
 // NOTE: this is opioniated in that we set the buffer sizes, but should be an
@@ -325,3 +520,179 @@ impl event::Source for Session {
         self.stream.deregister(registry)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUNDS: usize = 64;
+
+    #[test]
+    fn adaptive_read_size() {
+        let listener = Listener::from(TcpListener::bind("127.0.0.1:0").expect("failed to bind"));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let mut large_client = std::net::TcpStream::connect(addr).expect("failed to connect");
+        let mut small_client = std::net::TcpStream::connect(addr).expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let mut large = Session::from(listener.accept().expect("failed to accept"));
+        let mut small = Session::from(listener.accept().expect("failed to accept"));
+        if large.peer_addr().ok() != large_client.local_addr().ok() {
+            std::mem::swap(&mut large, &mut small);
+        }
+
+        for _ in 0..ROUNDS {
+            large_client
+                .write_all(&[0; 32 * KB])
+                .expect("failed to write");
+            small_client.write_all(&[0; 16]).expect("failed to write");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+
+            for session in [&mut large, &mut small] {
+                let _ = session.fill();
+                let len = session.read_buffer.remaining();
+                session.consume(len);
+            }
+        }
+
+        // reads which fill the read size grow it, while small reads shrink it
+        // down to the minimum
+        assert!(large.read_size() > TARGET_READ_SIZE);
+        assert_eq!(small.read_size(), MIN_READ_SIZE);
+        assert!(large.avg_read_size() > small.avg_read_size());
+
+        // the reads of all sessions are averaged in the gauge
+        assert!(SESSION_AVG_READ_BYTE.value() > 0);
+
+        // the read size stays within the limits
+        large.set_read_size_limits(MIN_READ_SIZE, TARGET_READ_SIZE);
+        assert_eq!(large.read_size(), TARGET_READ_SIZE);
+    }
+
+    #[test]
+    fn connection_bytes() {
+        // larger than any other session in these tests, so that these are
+        // the largest values recorded
+        const RX: usize = 8 * KB * KB;
+        const TX: usize = 4 * KB * KB;
+
+        let listener = Listener::from(TcpListener::bind("127.0.0.1:0").expect("failed to bind"));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let mut client = std::net::TcpStream::connect(addr).expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let mut session = Session::from(listener.accept().expect("failed to accept"));
+
+        let writer = std::thread::spawn(move || {
+            client.write_all(&vec![0; RX]).expect("failed to write");
+            let mut read = vec![0; TX];
+            client.read_exact(&mut read).expect("failed to read");
+        });
+
+        let mut pending = TX;
+        while session.bytes_rx() < RX as u64 || session.bytes_tx() < TX as u64 {
+            if session.fill().is_ok() {
+                let len = session.read_buffer.remaining();
+                session.consume(len);
+            }
+            if pending > 0 {
+                let len = pending.min(64 * KB);
+                session.put_slice(&vec![0; len]);
+                pending -= len;
+            }
+            let _ = session.flush();
+        }
+        writer.join().expect("client failed");
+
+        assert_eq!(session.bytes_rx(), RX as u64);
+        assert_eq!(session.bytes_tx(), TX as u64);
+
+        // the counts are recorded once the session is closed
+        drop(session);
+
+        for (heatmap, bytes) in [(&CONNECTION_BYTES_RX, RX), (&CONNECTION_BYTES_TX, TX)] {
+            let bucket = heatmap.percentile(100.0).expect("nothing recorded");
+            assert!(bucket.high() >= bytes as u64);
+            assert!(bucket.high() <= (bytes + bytes / 10) as u64);
+        }
+    }
+
+    // writes a self-signed key and certificate to files, for a tls acceptor
+    fn self_signed(name: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+        use boring::asn1::Asn1Time;
+        use boring::ec::{EcGroup, EcKey};
+        use boring::hash::MessageDigest;
+        use boring::nid::Nid;
+        use boring::pkey::PKey;
+        use boring::x509::{X509NameBuilder, X509};
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", "localhost").unwrap();
+        let subject = subject.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&subject).unwrap();
+        cert.set_issuer_name(&subject).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = cert.build();
+
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let key_file = dir.join(format!("pelikan-{}-{}.key", name, id));
+        let cert_file = dir.join(format!("pelikan-{}-{}.crt", name, id));
+        std::fs::write(&key_file, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        std::fs::write(&cert_file, cert.to_pem().unwrap()).unwrap();
+
+        (key_file, cert_file)
+    }
+
+    #[test]
+    fn handshake_timeout() {
+        let (key_file, cert_file) = self_signed("handshake-timeout");
+        let acceptor = TlsTcpAcceptor::mozilla_intermediate_v5()
+            .expect("failed to create builder")
+            .certificate_file(&cert_file)
+            .private_key_file(&key_file)
+            .build()
+            .expect("failed to initialize tls acceptor");
+        let listener = Listener::from((
+            TcpListener::bind("127.0.0.1:0").expect("failed to bind"),
+            acceptor,
+        ));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        // the client opens the connection but never starts the handshake
+        let _client = std::net::TcpStream::connect(addr).expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let mut session = Session::from(listener.accept().expect("failed to accept"));
+
+        let timeout = std::time::Duration::from_millis(50);
+        assert!(session.is_handshaking());
+        assert!(!session.handshake_timed_out(timeout));
+
+        std::thread::sleep(timeout);
+        assert!(session.do_handshake().is_err());
+        assert!(session.handshake_timed_out(timeout));
+
+        // sessions which don't handshake never time out
+        let listener = Listener::from(TcpListener::bind("127.0.0.1:0").expect("failed to bind"));
+        let addr = listener.local_addr().expect("listener has no local addr");
+        let _client = std::net::TcpStream::connect(addr).expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let session = Session::from(listener.accept().expect("failed to accept"));
+        std::thread::sleep(timeout);
+        assert!(!session.handshake_timed_out(timeout));
+
+        let _ = std::fs::remove_file(key_file);
+        let _ = std::fs::remove_file(cert_file);
+    }
+}
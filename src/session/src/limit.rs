@@ -0,0 +1,125 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Limits on the number of sessions which may be open from each client
+//! address, so that a single misbehaving client can't hold all of them.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+/// Counts the open sessions from each client IP address. Each session holds
+/// an `IpPermit` which keeps its place in the count until it is dropped, so
+/// the count follows the session wherever it is closed. Clones share the same
+/// counts.
+#[derive(Clone)]
+pub struct IpLimiter {
+    max: usize,
+    counts: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl IpLimiter {
+    /// Create a limiter which allows up to `max` sessions from each address.
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            counts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Counts a new session from `ip`, returning the permit which holds its
+    /// place. Returns `None` if there are already as many sessions from `ip`
+    /// as the limit allows.
+    pub fn acquire(&self, ip: IpAddr) -> Option<IpPermit> {
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(ip).or_insert(0);
+        if *count >= self.max {
+            return None;
+        }
+        *count += 1;
+
+        Some(IpPermit {
+            ip,
+            counts: self.counts.clone(),
+        })
+    }
+
+    /// Returns the number of sessions which are open from `ip`.
+    pub fn count(&self, ip: IpAddr) -> usize {
+        self.counts.lock().unwrap().get(&ip).copied().unwrap_or(0)
+    }
+}
+
+/// A place in the count of sessions from one address, which is given back when
+/// this is dropped.
+pub struct IpPermit {
+    ip: IpAddr,
+    counts: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for IpPermit {
+    fn drop(&mut self) {
+        let mut counts = self.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.ip) {
+            *count -= 1;
+            // forget addresses with no sessions, so that the map only grows
+            // with the number of clients which are connected
+            if *count == 0 {
+                counts.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Session;
+    use ::net::{Listener, TcpListener};
+
+    #[test]
+    fn per_ip_limit() {
+        let max = 4;
+        let limiter = IpLimiter::new(max);
+
+        let listener = Listener::from(TcpListener::bind("127.0.0.1:0").expect("failed to bind"));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let clients: Vec<std::net::TcpStream> = (0..(max + 2))
+            .map(|_| std::net::TcpStream::connect(addr).expect("failed to connect"))
+            .collect();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        // sessions from the same address are admitted up to the limit
+        let mut sessions = Vec::new();
+        let mut rejected = 0;
+        for _ in 0..clients.len() {
+            let mut session = Session::from(listener.accept().expect("failed to accept"));
+            let ip = session.peer_addr().expect("no peer addr").ip();
+            match limiter.acquire(ip) {
+                Some(permit) => {
+                    session.set_ip_permit(permit);
+                    sessions.push(session);
+                }
+                None => rejected += 1,
+            }
+        }
+        assert_eq!(sessions.len(), max);
+        assert_eq!(rejected, 2);
+        assert_eq!(limiter.count(addr.ip()), max);
+
+        // other addresses are unaffected
+        let other: IpAddr = "10.0.0.1".parse().unwrap();
+        let permit = limiter.acquire(other).expect("other address was limited");
+        assert_eq!(limiter.count(other), 1);
+        drop(permit);
+        assert_eq!(limiter.count(other), 0);
+
+        // closing a session makes room for another
+        assert!(limiter.acquire(addr.ip()).is_none());
+        sessions.pop();
+        assert_eq!(limiter.count(addr.ip()), max - 1);
+        assert!(limiter.acquire(addr.ip()).is_some());
+    }
+}
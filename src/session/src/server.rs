@@ -29,6 +29,18 @@ pub struct ServerSession<Parser, Tx, Rx> {
     outstanding: VecDeque<(Option<Instant>, usize)>,
     // tracks the time the session buffer was last filled
     timestamp: Instant,
+    // tracks when the bytes of an incomplete request were first read
+    partial: Option<Instant>,
+    // the size and age beyond which an incomplete request is stalled
+    stall_limit: Option<(usize, core::time::Duration)>,
+    // the high and low watermarks for bytes waiting in the write buffer
+    output_watermarks: Option<(usize, usize)>,
+    // true while requests are held back until the write buffer drains
+    backpressured: bool,
+    // true if the values this client writes are exempt from eviction
+    no_evict: bool,
+    // true once the session should be closed after its responses are flushed
+    closing: bool,
     // markers for the receive and transmit types
     _rx: PhantomData<Rx>,
     _tx: PhantomData<Tx>,
@@ -59,6 +71,12 @@ where
             pending: VecDeque::with_capacity(NUM_PENDING),
             outstanding: VecDeque::with_capacity(NUM_PENDING),
             timestamp: Instant::now(),
+            partial: None,
+            stall_limit: None,
+            output_watermarks: None,
+            backpressured: false,
+            no_evict: false,
+            closing: false,
             _rx: PhantomData,
             _tx: PhantomData,
         }
@@ -69,21 +87,182 @@ where
         self.session
     }
 
+    /// Returns the id of the inner `Session`.
+    pub fn id(&self) -> u64 {
+        self.session.id()
+    }
+
+    /// Returns the address of the remote end of the `Session`.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.session.peer_addr()
+    }
+
+    /// Returns `true` if the values written by this client are exempt from
+    /// eviction.
+    pub fn no_evict(&self) -> bool {
+        self.no_evict
+    }
+
+    /// Marks the values written by this client as exempt from eviction, or
+    /// clears the mark. The storage decides how much may be exempt.
+    pub fn set_no_evict(&mut self, no_evict: bool) {
+        self.no_evict = no_evict;
+    }
+
+    /// Applies an action which a request asked for along with its response.
+    /// This must be called after the response has been sent to the session.
+    pub fn apply(&mut self, action: SessionAction) {
+        match action {
+            SessionAction::CloseAfterResponse => {
+                self.closing = true;
+            }
+            // the caller closes the session instead of sending the response,
+            // and no further requests are received in the meantime
+            SessionAction::Close => {
+                self.closing = true;
+            }
+        }
+    }
+
+    /// Returns true once a request has asked for the session to be closed and
+    /// every response has been flushed, so the session can be closed without
+    /// the client missing any of them. No further requests are received from
+    /// a session which is waiting to close.
+    pub fn should_close(&self) -> bool {
+        self.closing && self.session.write_pending() == 0
+    }
+
+    /// Limits how many response bytes may wait in the write buffer. Once more
+    /// than `high` bytes are pending, no further requests are received until
+    /// the client has read enough that `low` or fewer bytes remain. This keeps
+    /// a client which pipelines requests without reading the responses from
+    /// making the write buffer grow without bound.
+    pub fn set_output_watermarks(&mut self, high: usize, low: usize) {
+        self.output_watermarks = Some((high, low.min(high)));
+    }
+
+    /// Limits how long the session may hold a large incomplete request. Once
+    /// more than `size` bytes of a request have been read without it
+    /// completing, and its first bytes were read more than `timeout` ago,
+    /// `receive` fails with `TimedOut` instead of parsing the buffer again. A
+    /// large request which arrives within the timeout is unaffected.
+    pub fn set_stall_limit(&mut self, size: usize, timeout: core::time::Duration) {
+        self.stall_limit = Some((size, timeout));
+    }
+
+    /// Returns true if requests are currently being held back because too
+    /// many response bytes are waiting to be written to the client.
+    pub fn output_backpressured(&mut self) -> bool {
+        if let Some((high, low)) = self.output_watermarks {
+            let pending = self.session.write_pending();
+            if self.backpressured {
+                self.backpressured = pending > low;
+            } else if pending > high {
+                SESSION_OUTPUT_BACKPRESSURE.increment();
+                self.backpressured = true;
+            }
+        }
+        self.backpressured
+    }
+
     /// Attempt to receive a single message from the current session buffer.
+    /// Returns `WouldBlock` without parsing while the session is backpressured
+    /// or waiting to close.
     pub fn receive(&mut self) -> Result<Rx> {
+        if self.closing || self.output_backpressured() {
+            return Err(Error::from(ErrorKind::WouldBlock));
+        }
+
         let src: &[u8] = self.session.borrow();
         match self.parser.parse(src) {
             Ok(res) => {
+                self.partial = None;
                 self.pending.push_back(self.timestamp);
                 let consumed = res.consumed();
                 let msg = res.into_inner();
                 self.session.consume(consumed);
                 Ok(msg)
             }
-            Err(e) => Err(e),
+            Err(e) => {
+                if e.kind() == ErrorKind::WouldBlock {
+                    if let Some(needed) = self.parser.bytes_needed(src) {
+                        self.session.reserve_read(needed);
+                    }
+                    if self.partial.is_none() && self.session.remaining() > 0 {
+                        self.partial = Some(self.timestamp);
+                    }
+                    if let Some((size, timeout)) = self.stall_limit {
+                        if self.session.remaining() > size && self.read_timed_out(timeout) {
+                            SESSION_REQUEST_STALL.increment();
+                            return Err(Error::new(ErrorKind::TimedOut, "request stalled"));
+                        }
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns true if the session holds an incomplete request whose first
+    /// bytes were read more than `timeout` ago. This allows the caller to drop
+    /// clients which stall part-way through sending a request.
+    pub fn read_timed_out(&self, timeout: core::time::Duration) -> bool {
+        if let Some(partial) = self.partial {
+            (Instant::now() - partial).as_nanos() >= timeout.as_nanos() as u64
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if no request or response is in progress, meaning that
+    /// nothing is buffered in either direction and every request which was
+    /// read has been responded to. The session can then be closed without
+    /// interrupting the client part-way through a request.
+    pub fn is_idle(&self) -> bool {
+        self.partial.is_none()
+            && self.pending.is_empty()
+            && self.outstanding.is_empty()
+            && self.session.remaining() == 0
+            && self.session.write_pending() == 0
+    }
+
+    /// Returns how long ago the request which is next to be responded to was
+    /// read, or `None` if no request is waiting for a response.
+    pub fn request_latency(&self) -> Option<core::time::Duration> {
+        self.pending.front().map(|timestamp| {
+            core::time::Duration::from_nanos((Instant::now() - *timestamp).as_nanos())
+        })
+    }
+
+    /// Frees the session buffers if the session has been idle for at least
+    /// `idle`, meaning nothing was read in that time and no request or
+    /// response is in progress. Returns true if the buffers were freed. The
+    /// session remains registered, and its buffers are allocated again when
+    /// the client next sends a request.
+    pub fn release_idle_buffers(&mut self, idle: core::time::Duration) -> bool {
+        if self.session.buffers_released()
+            || self.partial.is_some()
+            || !self.pending.is_empty()
+            || !self.outstanding.is_empty()
+            || (Instant::now() - self.timestamp).as_nanos() < idle.as_nanos() as u64
+        {
+            return false;
+        }
+
+        if self.session.release_buffers() {
+            SESSION_IDLE_BUFFER_RELEASED.increment();
+            true
+        } else {
+            false
         }
     }
 
+    /// Returns true if the session buffers have been freed because the
+    /// session was idle.
+    pub fn buffers_released(&self) -> bool {
+        self.session.buffers_released()
+    }
+
     /// Send a message to the session buffer.
     pub fn send(&mut self, tx: Tx) -> Result<usize> {
         SESSION_SEND.increment();
@@ -258,3 +437,231 @@ impl<Parser, Tx, Rx> event::Source for ServerSession<Parser, Tx, Rx> {
         self.session.deregister(registry)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol_common::{BufMut, ParseOk};
+
+    const RESPONSE_SIZE: usize = 64 * KB;
+    const HIGH_WATERMARK: usize = 256 * KB;
+    const LOW_WATERMARK: usize = 64 * KB;
+    const REQUESTS: usize = 1000;
+
+    // treats each line as a request
+    #[derive(Clone)]
+    struct LineParser;
+
+    impl Parse<()> for LineParser {
+        fn parse(&self, buffer: &[u8]) -> Result<ParseOk<()>> {
+            match buffer.iter().position(|b| *b == b'\n') {
+                Some(end) => Ok(ParseOk::new((), end + 1)),
+                None => Err(Error::from(ErrorKind::WouldBlock)),
+            }
+        }
+    }
+
+    // a response which is large enough to fill the socket buffers quickly
+    struct Payload;
+
+    impl Compose for Payload {
+        fn compose(&self, dst: &mut dyn BufMut) -> usize {
+            dst.put_slice(&[0; RESPONSE_SIZE]);
+            RESPONSE_SIZE
+        }
+    }
+
+    #[test]
+    fn output_backpressure() {
+        let listener = Listener::from(TcpListener::bind("127.0.0.1:0").expect("failed to bind"));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let mut client = Connector::from(TcpConnector::new())
+            .connect(addr)
+            .expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let stream = listener.accept().expect("failed to accept");
+
+        let mut session: ServerSession<LineParser, Payload, ()> =
+            ServerSession::new(Session::from(stream), LineParser);
+        session.set_output_watermarks(HIGH_WATERMARK, LOW_WATERMARK);
+
+        // the client pipelines many requests and never reads a response
+        client
+            .write_all(&b"get\n".repeat(REQUESTS))
+            .expect("failed to write");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let mut responses = 0;
+        loop {
+            let _ = session.fill();
+            match session.receive() {
+                Ok(()) => {
+                    session.send(Payload).expect("failed to send");
+                    let _ = session.flush();
+                    responses += 1;
+                }
+                Err(e) => {
+                    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+                    break;
+                }
+            }
+        }
+
+        // the session stopped with requests still waiting to be processed,
+        // and only a bounded amount of response data was buffered
+        assert!(responses < REQUESTS);
+        assert!(session.remaining() > 0);
+        assert!(session.write_pending() > HIGH_WATERMARK);
+        assert!(session.write_pending() <= HIGH_WATERMARK + RESPONSE_SIZE);
+
+        // no more responses are produced while the client isn't reading
+        let _ = session.flush();
+        assert!(session.receive().is_err());
+
+        // requests are processed again once the client catches up
+        let mut buf = vec![0; 1024 * KB];
+        for _ in 0..1000 {
+            let _ = client.read(&mut buf);
+            let _ = session.flush();
+            if !session.output_backpressured() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(session.write_pending() <= LOW_WATERMARK);
+        assert!(session.receive().is_ok());
+    }
+
+    // a small response, so that it is flushed in a single write
+    struct Pong;
+
+    impl Compose for Pong {
+        fn compose(&self, dst: &mut dyn BufMut) -> usize {
+            dst.put_slice(b"pong\n");
+            5
+        }
+    }
+
+    #[test]
+    fn idle_buffer_release() {
+        let listener = Listener::from(TcpListener::bind("127.0.0.1:0").expect("failed to bind"));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let mut client = Connector::from(TcpConnector::new())
+            .connect(addr)
+            .expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let stream = listener.accept().expect("failed to accept");
+
+        let mut session: ServerSession<LineParser, Pong, ()> =
+            ServerSession::new(Session::from(stream), LineParser);
+        let idle = std::time::Duration::from_millis(50);
+
+        // a session which was just active keeps its buffers
+        assert!(!session.release_idle_buffers(idle));
+        assert!(!session.buffers_released());
+
+        // once idle, the buffers are freed and this is only done once
+        std::thread::sleep(2 * idle);
+        let released = SESSION_IDLE_BUFFER_RELEASED.value();
+        assert!(session.release_idle_buffers(idle));
+        assert!(session.buffers_released());
+        assert!(!session.release_idle_buffers(idle));
+        assert!(SESSION_IDLE_BUFFER_RELEASED.value() > released);
+
+        // the next request allocates the buffers again and is served
+        client.write_all(b"ping\n").expect("failed to write");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        assert!(session.fill().expect("failed to read") > 0);
+        assert!(!session.buffers_released());
+        session.receive().expect("failed to receive");
+        session.send(Pong).expect("failed to send");
+        session.flush().expect("failed to flush");
+
+        let mut buf = [0; 5];
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        client
+            .read_exact(&mut buf)
+            .expect("failed to read response");
+        assert_eq!(&buf, b"pong\n");
+    }
+
+    #[test]
+    fn idle() {
+        let listener = Listener::from(TcpListener::bind("127.0.0.1:0").expect("failed to bind"));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let mut client = Connector::from(TcpConnector::new())
+            .connect(addr)
+            .expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let stream = listener.accept().expect("failed to accept");
+
+        let mut session: ServerSession<LineParser, Pong, ()> =
+            ServerSession::new(Session::from(stream), LineParser);
+        assert!(session.is_idle());
+
+        // a partial request is in progress
+        client.write_all(b"pi").expect("failed to write");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let _ = session.fill();
+        assert!(session.receive().is_err());
+        assert!(!session.is_idle());
+
+        // as is a complete request until it is responded to
+        client.write_all(b"ng\n").expect("failed to write");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let _ = session.fill();
+        session.receive().expect("failed to receive");
+        assert!(!session.is_idle());
+
+        session.send(Pong).expect("failed to send");
+        session.flush().expect("failed to flush");
+        assert!(session.is_idle());
+    }
+
+    #[test]
+    fn close_after_response() {
+        let listener = Listener::from(TcpListener::bind("127.0.0.1:0").expect("failed to bind"));
+        let addr = listener.local_addr().expect("listener has no local addr");
+
+        let mut client = Connector::from(TcpConnector::new())
+            .connect(addr)
+            .expect("failed to connect");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let stream = listener.accept().expect("failed to accept");
+
+        let mut session: ServerSession<LineParser, Pong, ()> =
+            ServerSession::new(Session::from(stream), LineParser);
+
+        // the client pipelines another request after the one which quits
+        client.write_all(b"quit\nping\n").expect("failed to write");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let _ = session.fill();
+
+        session.receive().expect("failed to receive");
+        session.send(Pong).expect("failed to send");
+        session.apply(SessionAction::CloseAfterResponse);
+
+        // the session isn't closed while its response is still buffered, and
+        // the request which followed is never received
+        assert!(!session.should_close());
+        assert_eq!(
+            session.receive().map(|_| ()).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+
+        // once flushed, the client has the response and the session can close
+        session.flush().expect("failed to flush");
+        assert!(session.should_close());
+
+        let mut buf = [0; 5];
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        client
+            .read_exact(&mut buf)
+            .expect("failed to read response");
+        assert_eq!(&buf, b"pong\n");
+    }
+}
@@ -8,8 +8,10 @@ use core::ops::Range;
 use std::fs::{File, OpenOptions};
 use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-#[cfg(os = "linux")]
+#[cfg(target_os = "linux")]
 use std::os::unix::fs::OpenOptionsExt;
 
 use memmap2::{MmapMut, MmapOptions};
@@ -18,6 +20,17 @@ const PAGE_SIZE: usize = 4096;
 const HEADER_SIZE: usize = core::mem::size_of::<Header>();
 const MAGIC: [u8; 8] = *b"PELIKAN!";
 
+// bits of `Header::options` which record the codec used to compress the data
+// region, at most one of these is set
+const OPTION_LZ4: u64 = 1 << 0;
+const OPTION_ZSTD: u64 = 1 << 1;
+
+// the zstd level used to compress the data region, which is the zstd default
+const ZSTD_LEVEL: i32 = 3;
+
+// how often `FlushHandle::wait_timeout` checks if the flush has finished
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
 // NOTE: this must be incremented if there are breaking changes to the on-disk
 // format
 const VERSION: u64 = 0;
@@ -25,7 +38,7 @@ const VERSION: u64 = 0;
 /// The datapool trait defines the abstraction that each datapool implementation
 /// should conform to.
 #[allow(clippy::len_without_is_empty)]
-pub trait Datapool: Send {
+pub trait Datapool: Send + 'static {
     /// Immutable borrow of the data within the datapool
     fn as_slice(&self) -> &[u8];
 
@@ -36,9 +49,189 @@ pub trait Datapool: Send {
     /// This may be a no-op for datapools which cannot persist data.
     fn flush(&mut self) -> Result<(), std::io::Error>;
 
+    /// Starts a flush on a new thread, which takes ownership of the datapool
+    /// and drops it once the flush has finished. This allows the caller to
+    /// carry on, for example to tear down other threads during shutdown, and
+    /// to bound how long it waits for the flush using the returned handle.
+    fn flush_async(self: Box<Self>) -> Result<FlushHandle, std::io::Error> {
+        let mut datapool = self;
+        let thread = std::thread::Builder::new()
+            .name("datapool_flush".to_string())
+            .spawn(move || datapool.flush())?;
+        Ok(FlushHandle { thread })
+    }
+
     fn len(&self) -> usize {
         self.as_slice().len()
     }
+
+    /// Locks the data into memory so that it is never paged out. Every page is
+    /// faulted in before this returns, so there are no page faults when the
+    /// data is accessed later. The lock is released when the datapool is
+    /// dropped.
+    fn lock(&mut self) -> Result<(), std::io::Error> {
+        mlock(self.as_mut_slice())
+    }
+
+    /// Binds the data to a NUMA node so that it is only allocated from memory
+    /// which is local to that node. Pages which are already resident on
+    /// another node are moved. This is only supported on Linux, and returns an
+    /// error if the node does not exist or NUMA policy can't be set.
+    fn bind_numa_node(&mut self, node: usize) -> Result<(), std::io::Error> {
+        mbind(self.as_mut_slice(), node)
+    }
+
+    /// The version of the layout of the data, which is chosen by the user of
+    /// the datapool. Datapools which persist their data store it in the file
+    /// header, and the file must be reopened with the same version. This is
+    /// always zero for datapools which cannot persist data.
+    fn user_version(&self) -> u64 {
+        0
+    }
+
+    /// Sets the version of the layout of the data, which is persisted by the
+    /// next flush. This is a no-op for datapools which cannot persist data.
+    fn set_user_version(&mut self, _user_version: u64) {}
+
+    /// Grows the data to at least the new length (in bytes), keeping the
+    /// existing data. Returns an error with kind `Unsupported` for datapools
+    /// which cannot be resized.
+    fn try_resize(&mut self, _new_len: usize) -> Result<(), std::io::Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "datapool does not support resizing",
+        ))
+    }
+}
+
+/// A flush which is running on its own thread, see `Datapool::flush_async`.
+pub struct FlushHandle {
+    thread: JoinHandle<Result<(), std::io::Error>>,
+}
+
+impl FlushHandle {
+    /// Returns `true` once the flush has finished, whether or not it succeeded.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Blocks until the flush has finished and returns its result.
+    pub fn wait(self) -> Result<(), std::io::Error> {
+        self.thread
+            .join()
+            .unwrap_or_else(|_| Err(Error::new(ErrorKind::Other, "datapool flush panicked")))
+    }
+
+    /// Blocks until the flush has finished, or for at most the timeout. If the
+    /// flush is still running, an error with kind `TimedOut` is returned and
+    /// the flush carries on in the background.
+    pub fn wait_timeout(self, timeout: Duration) -> Result<(), std::io::Error> {
+        let deadline = std::time::Instant::now() + timeout;
+        while !self.is_finished() {
+            if std::time::Instant::now() >= deadline {
+                return Err(Error::new(ErrorKind::TimedOut, "datapool flush timed out"));
+            }
+            std::thread::sleep(FLUSH_POLL_INTERVAL);
+        }
+        self.wait()
+    }
+}
+
+/// Locks a region with `mlock(2)`, which also populates any pages which are not
+/// yet resident. On failure, the error notes if the `RLIMIT_MEMLOCK` limit is
+/// too low for the region, as that is the usual cause.
+fn mlock(data: &mut [u8]) -> Result<(), std::io::Error> {
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    if unsafe { libc::mlock(data.as_ptr() as *const libc::c_void, data.len()) } == 0 {
+        return Ok(());
+    }
+
+    let e = Error::last_os_error();
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    let hint = if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } == 0
+        && limit.rlim_cur < data.len() as u64
+    {
+        format!(
+            " (RLIMIT_MEMLOCK is {} bytes but {} bytes are needed)",
+            limit.rlim_cur,
+            data.len()
+        )
+    } else {
+        String::new()
+    };
+
+    Err(Error::new(
+        e.kind(),
+        format!("failed to lock datapool memory: {}{}", e, hint),
+    ))
+}
+
+// memory policy values from `linux/mempolicy.h`, which are not exposed by libc
+#[cfg(target_os = "linux")]
+const MPOL_BIND: libc::c_int = 2;
+#[cfg(target_os = "linux")]
+const MPOL_MF_STRICT: libc::c_uint = 1 << 0;
+#[cfg(target_os = "linux")]
+const MPOL_MF_MOVE: libc::c_uint = 1 << 1;
+
+/// Sets a `MPOL_BIND` policy for the region with `mbind(2)`, moving any pages
+/// which are already resident so that the whole region is on the node.
+#[cfg(target_os = "linux")]
+fn mbind(data: &mut [u8], node: usize) -> Result<(), std::io::Error> {
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    // the nodemask is a bitmask with one bit per node, and the kernel reads
+    // one less than `maxnode` bits from it
+    let bits = 8 * core::mem::size_of::<libc::c_ulong>();
+    let mut nodemask: Vec<libc::c_ulong> = vec![0; node / bits + 1];
+    nodemask[node / bits] |= 1 << (node % bits);
+    let maxnode = (nodemask.len() * bits + 1) as libc::c_ulong;
+
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            data.as_mut_ptr() as *mut libc::c_void,
+            data.len() as libc::c_ulong,
+            MPOL_BIND,
+            nodemask.as_ptr(),
+            maxnode,
+            MPOL_MF_MOVE | MPOL_MF_STRICT,
+        )
+    };
+
+    if ret == 0 {
+        return Ok(());
+    }
+
+    let e = Error::last_os_error();
+
+    Err(Error::new(
+        e.kind(),
+        format!(
+            "failed to bind datapool memory to NUMA node {}: {}",
+            node, e
+        ),
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mbind(_data: &mut [u8], node: usize) -> Result<(), std::io::Error> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        format!(
+            "failed to bind datapool memory to NUMA node {}: not supported on this platform",
+            node
+        ),
+    ))
 }
 
 /// Represents volatile in-memory storage.
@@ -78,6 +271,62 @@ impl Datapool for Memory {
     }
 }
 
+/// A codec which is used to compress the data region when a `FileBackedMemory`
+/// datapool is written to its file, see `FileBackedMemory::with_compression`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Lz4,
+    Zstd,
+}
+
+impl Codec {
+    fn option(&self) -> u64 {
+        match self {
+            Self::Lz4 => OPTION_LZ4,
+            Self::Zstd => OPTION_ZSTD,
+        }
+    }
+
+    /// Returns the codec recorded in the header options, if any.
+    fn from_options(options: u64) -> Result<Option<Self>, std::io::Error> {
+        match options & (OPTION_LZ4 | OPTION_ZSTD) {
+            0 => Ok(None),
+            OPTION_LZ4 => Ok(Some(Self::Lz4)),
+            OPTION_ZSTD => Ok(Some(Self::Zstd)),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                "header has more than one compression codec",
+            )),
+        }
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Self::Lz4 => Ok(lz4_flex::block::compress(data)),
+            Self::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL),
+        }
+    }
+
+    /// Decompresses into the destination, which must be filled exactly.
+    fn decompress(&self, src: &[u8], dst: &mut [u8]) -> Result<(), std::io::Error> {
+        let len = match self {
+            Self::Lz4 => lz4_flex::block::decompress_into(src, dst)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?,
+            Self::Zstd => zstd::bulk::decompress_to_buffer(src, dst)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+        };
+
+        if len == dst.len() {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                "decompressed size mismatch",
+            ))
+        }
+    }
+}
+
 // NOTE: make sure this is a whole number of pages and that all fields which are
 // accessed are properly aligned to avoid undefined behavior.
 #[repr(packed)]
@@ -91,7 +340,8 @@ pub struct Header {
     time_unix_ns: UnixInstant<Nanoseconds<u64>>,
     user_version: u64,
     options: u64,
-    _pad: [u8; 4008],
+    compressed_len: u64,
+    _pad: [u8; 4000],
 }
 
 impl Header {
@@ -106,7 +356,8 @@ impl Header {
             time_unix_ns: UnixInstant::<Nanoseconds<u64>>::now(),
             user_version: 0,
             options: 0,
-            _pad: [0; 4008],
+            compressed_len: 0,
+            _pad: [0; 4000],
         }
     }
 
@@ -130,6 +381,28 @@ impl Header {
         }
     }
 
+    /// Checks the stored checksum against the blake3 hash of this header,
+    /// with its checksum zeroed, followed by the data region. Returns an error
+    /// with kind `InvalidData` if they don't match, which means the file was
+    /// corrupted or not completely written.
+    pub fn verify_checksum(&self, data: &[u8]) -> Result<(), std::io::Error> {
+        // SAFETY: the header is plain data and is read as a packed struct, so
+        // there are no alignment requirements
+        let mut header = unsafe { std::ptr::read(self) };
+        header.zero_checksum();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(header.as_bytes());
+        hasher.update(data);
+        let hash = hasher.finalize();
+
+        if self.checksum()[0..32] == hash.as_bytes()[0..32] {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::InvalidData, "checksum mismatch"))
+        }
+    }
+
     fn check(&self) -> Result<(), std::io::Error> {
         self.check_magic()?;
         self.check_version()
@@ -165,6 +438,23 @@ impl Header {
     pub fn options(&self) -> u64 {
         self.options
     }
+
+    /// The codec which the data region in the file is compressed with, if any.
+    /// This is `None` if the options are not valid.
+    pub fn compression(&self) -> Option<Codec> {
+        Codec::from_options(self.options).ok().flatten()
+    }
+
+    /// The length (in bytes) of the compressed data region, which is padded
+    /// with zeros to a whole number of pages in the file.
+    fn compressed_len(&self) -> usize {
+        self.compressed_len as usize
+    }
+
+    fn set_compression(&mut self, codec: Codec, compressed_len: usize) {
+        self.options |= codec.option();
+        self.compressed_len = compressed_len as u64;
+    }
 }
 
 /// Represents storage that primarily exists in a file. This is best used in
@@ -172,6 +462,7 @@ impl Header {
 /// cache pollution and interference. It can be used for volatile storage or
 /// allow to resume from a clean shutdown.
 pub struct MmapFile {
+    file: File,
     mmap: MmapMut,
     data: Range<usize>,
     user_version: u64,
@@ -217,8 +508,8 @@ impl MmapFile {
         let mut header = [0; HEADER_SIZE];
         header.copy_from_slice(&mmap[0..HEADER_SIZE]);
 
-        // convert the header to a struct so we can check and manipulate it
-        let header = unsafe { &mut *(header.as_ptr() as *mut Header) };
+        // convert the header to a struct so we can check it
+        let header = unsafe { &*(header.as_ptr() as *const Header) };
 
         // check the header
         header.check()?;
@@ -228,29 +519,13 @@ impl MmapFile {
             return Err(Error::new(ErrorKind::Other, "user version mismatch"));
         }
 
-        // zero out the checksum in the header copy
-        header.zero_checksum();
-
-        // create a hasher
-        let mut hasher = blake3::Hasher::new();
-
-        // hash the header with a zero'd checksum
-        hasher.update(header.as_bytes());
-
-        // calculates the hash of the data region, as a side effect this
-        // prefaults all the pages
-        hasher.update(&mmap[data.start..data.end]);
-
-        // finalize the hash
-        let hash = hasher.finalize();
-
-        // compare the stored checksum in the file to the calculated checksum
-        if mmap[0..32] != hash.as_bytes()[0..32] {
-            return Err(Error::new(ErrorKind::Other, "checksum mismatch"));
-        }
+        // compare the stored checksum to the hash of the file content, as a
+        // side effect this prefaults all the pages
+        header.verify_checksum(&mmap[data.start..data.end])?;
 
         // return the loaded datapool
         Ok(Self {
+            file,
             mmap,
             data,
             user_version,
@@ -300,19 +575,67 @@ impl MmapFile {
         mmap.flush()?;
 
         Ok(Self {
+            file,
             mmap,
             data,
             user_version,
         })
     }
 
-    pub fn header(&self) -> &Header {
-        // load copy the header from the mmap'd file
-        let mut header = [0; HEADER_SIZE];
-        header.copy_from_slice(&self.mmap[0..HEADER_SIZE]);
+    /// Grows the datapool to the new data size (in bytes), rounded up to a
+    /// whole number of pages. The file is extended and mmap'd again, and the
+    /// existing data is kept. The header is updated by flushing, so the file
+    /// can be reopened with the new size. Returns an error if the new size is
+    /// less than the current data size.
+    pub fn resize(&mut self, new_len: usize) -> Result<(), std::io::Error> {
+        if new_len < self.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "cannot shrink below the current data size",
+            ));
+        }
+
+        // we need the data size to be a whole number of pages
+        let pages = ((HEADER_SIZE + new_len) as f64 / PAGE_SIZE as f64).ceil() as usize;
+
+        let total_size = pages * PAGE_SIZE;
+        let old_size = self.mmap.len();
 
-        // convert the header to a struct
-        unsafe { &*(header.as_ptr() as *const Header) }
+        if total_size == old_size {
+            return Ok(());
+        }
+
+        // write back the current mapping before it is replaced
+        self.mmap.flush()?;
+
+        // grow the file to match the total size, the new region reads as zeros
+        self.file.set_len(total_size as u64)?;
+
+        // mmap the file again, which drops the old mapping
+        let mut mmap = unsafe { MmapOptions::new().populate().map_mut(&self.file)? };
+
+        // prefault the new region by writing a zero at the start of each page
+        let mut offset = old_size;
+        while offset < total_size {
+            mmap[offset] = 0;
+            offset += PAGE_SIZE;
+        }
+
+        self.mmap = mmap;
+        self.data = Range {
+            start: HEADER_SIZE,
+            end: total_size,
+        };
+
+        // update the header checksum to cover the new region
+        self.flush()
+    }
+
+    pub fn header(&self) -> &Header {
+        // SAFETY: the header is at the start of the mmap'd file, which is at
+        // least HEADER_SIZE bytes. The header is packed, so there are no
+        // alignment requirements
+        unsafe { &*(self.mmap.as_ptr() as *const Header) }
     }
 
     pub fn time_monotonic_s(&self) -> Instant<Seconds<u32>> {
@@ -386,6 +709,18 @@ impl Datapool for MmapFile {
         // flush again
         self.mmap.flush()
     }
+
+    fn user_version(&self) -> u64 {
+        self.user_version
+    }
+
+    fn set_user_version(&mut self, user_version: u64) {
+        self.user_version = user_version;
+    }
+
+    fn try_resize(&mut self, new_len: usize) -> Result<(), std::io::Error> {
+        self.resize(new_len)
+    }
 }
 
 /// Represents storage that is primarily in-memory, but has an associated file
@@ -400,12 +735,17 @@ impl Datapool for MmapFile {
 /// at this time. Further, there are situations in which even with `O_DIRECT`,
 /// the operating system may still buffer access to/from the file. No effort is
 /// made to detect, avoid, or handle this situation.
+///
+/// The data region can optionally be compressed when it is written to the
+/// file, see `with_compression`. The header is never compressed, and records
+/// the codec so that `open` can decompress the data back into memory.
 pub struct FileBackedMemory {
     memory: Memory,
     header: Box<[u8]>,
     file: File,
     file_data: Range<usize>,
     user_version: u64,
+    compression: Option<Codec>,
 }
 
 impl FileBackedMemory {
@@ -446,10 +786,7 @@ impl FileBackedMemory {
             .write(true)
             .open(path)?;
 
-        // make sure the file size matches the expected size
-        if file.metadata()?.len() != file_total_size.end as u64 {
-            return Err(Error::new(ErrorKind::Other, "filesize mismatch"));
-        }
+        let file_size = file.metadata()?.len();
 
         // calculate the page range for the data region
         let data_pages = (file_data.end - file_data.start) / PAGE_SIZE;
@@ -471,12 +808,8 @@ impl FileBackedMemory {
             file.seek(SeekFrom::Start(0))?;
         }
 
-        // create a new hasher to checksum the file content, including the
-        // header with a zero'd checksum
-        let mut hasher = blake3::Hasher::new();
-
         // turn the raw header into the struct
-        let header = unsafe { &mut *(header.as_ptr() as *mut Header) };
+        let header = unsafe { &*(header.as_ptr() as *const Header) };
 
         // check the header
         header.check()?;
@@ -486,18 +819,66 @@ impl FileBackedMemory {
             return Err(Error::new(ErrorKind::Other, "user version mismatch"));
         }
 
-        // copy the checksum out of the header and zero it in the header
-        let file_checksum = header.checksum().to_owned();
-        header.zero_checksum();
+        // the header records if the data region is compressed
+        let compression = Codec::from_options(header.options())?;
 
-        // hash the header with the zero'd checksum
-        hasher.update(header.as_bytes());
+        if let Some(codec) = compression {
+            // the compressed data region is padded to a whole number of pages
+            let compressed_len = header.compressed_len();
+            let compressed_pages = (compressed_len as f64 / PAGE_SIZE as f64).ceil() as usize;
+
+            // make sure the file size matches the size of the compressed data
+            if file_size != (HEADER_SIZE + compressed_pages * PAGE_SIZE) as u64 {
+                return Err(Error::new(ErrorKind::Other, "filesize mismatch"));
+            }
+
+            // seek to start of the data
+            file.seek(SeekFrom::Start(file_data.start as u64))?;
+
+            // read the compressed data region from the file
+            let mut compressed = vec![0; compressed_pages * PAGE_SIZE];
+            for page in 0..compressed_pages {
+                // retry the read until a complete page is read
+                loop {
+                    let start = page * PAGE_SIZE;
+                    let end = start + PAGE_SIZE;
+
+                    if file.read(&mut compressed[start..end])? == PAGE_SIZE {
+                        break;
+                    }
+                    // if the read was incomplete, we seek back to the right
+                    // spot in the file
+                    file.seek(SeekFrom::Start((HEADER_SIZE + start) as u64))?;
+                }
+            }
+
+            // compare the stored checksum to the hash of the file content
+            // before trusting it enough to decompress
+            header.verify_checksum(&compressed)?;
+
+            // decompress the data region into memory
+            codec.decompress(&compressed[0..compressed_len], memory.as_mut_slice())?;
+
+            // return the loaded datapool
+            return Ok(Self {
+                memory,
+                header: header.as_bytes().to_owned().into_boxed_slice(),
+                file,
+                file_data,
+                user_version,
+                compression,
+            });
+        }
+
+        // make sure the file size matches the expected size
+        if file_size != file_total_size.end as u64 {
+            return Err(Error::new(ErrorKind::Other, "filesize mismatch"));
+        }
 
         // seek to start of the data
         file.seek(SeekFrom::Start(file_data.start as u64))?;
 
-        // read the data region from the file, copy it into memory and hash it
-        // in a single pass
+        // read the data region from the file into memory
         for page in 0..data_pages {
             // retry the read until a complete page is read
             loop {
@@ -505,7 +886,6 @@ impl FileBackedMemory {
                 let end = start + PAGE_SIZE;
 
                 if file.read(&mut memory.as_mut_slice()[start..end])? == PAGE_SIZE {
-                    hasher.update(&memory.as_slice()[start..end]);
                     break;
                 }
                 // if the read was incomplete, we seek back to the right spot in
@@ -514,13 +894,8 @@ impl FileBackedMemory {
             }
         }
 
-        // finalize the hash
-        let hash = hasher.finalize();
-
-        // compare the checksum agaianst what's in the header
-        if file_checksum[0..32] != hash.as_bytes()[0..32] {
-            return Err(Error::new(ErrorKind::Other, "checksum mismatch"));
-        }
+        // compare the stored checksum to the hash of the file content
+        header.verify_checksum(&memory.as_slice()[0..(data_pages * PAGE_SIZE)])?;
 
         // return the loaded datapool
         Ok(Self {
@@ -529,6 +904,7 @@ impl FileBackedMemory {
             file,
             file_data,
             user_version,
+            compression,
         })
     }
 
@@ -591,9 +967,25 @@ impl FileBackedMemory {
             file,
             file_data,
             user_version,
+            compression: None,
         })
     }
 
+    /// Compresses the data region with the codec each time the datapool is
+    /// flushed, which shrinks the file to fit the compressed data. The header
+    /// records the codec, so `open` restores the data without needing to be
+    /// told. A datapool which is opened from a compressed file keeps using the
+    /// same codec.
+    pub fn with_compression(mut self, codec: Codec) -> Self {
+        self.compression = Some(codec);
+        self
+    }
+
+    /// The codec used to compress the data region when it is flushed, if any.
+    pub fn compression(&self) -> Option<Codec> {
+        self.compression
+    }
+
     pub fn header(&self) -> &Header {
         unsafe { &*(self.header.as_ptr() as *const Header) }
     }
@@ -634,20 +1026,39 @@ impl Datapool for FileBackedMemory {
         // set the user version
         header.set_user_version(self.user_version);
 
-        // hash the header with a zero'd checksum
-        hasher.update(header.as_bytes());
-
         // calculate the number of data pages to be copied
         let data_pages = (self.file_data.end - self.file_data.start) / PAGE_SIZE;
 
+        // compress the data region if needed, padding it with zeros to a whole
+        // number of pages for direct io
+        let compressed = match self.compression {
+            Some(codec) => {
+                let mut compressed = codec.compress(self.memory.as_slice())?;
+                header.set_compression(codec, compressed.len());
+                let pages = (compressed.len() as f64 / PAGE_SIZE as f64).ceil() as usize;
+                compressed.resize(pages * PAGE_SIZE, 0);
+                Some(compressed)
+            }
+            None => None,
+        };
+
+        // the data region as it is written to the file
+        let data = match compressed {
+            Some(ref compressed) => &compressed[..],
+            None => &self.memory.as_slice()[0..(data_pages * PAGE_SIZE)],
+        };
+
+        // hash the header with a zero'd checksum
+        hasher.update(header.as_bytes());
+
         // write the data region to the file and hash it in one pass
         self.file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
-        for page in 0..data_pages {
+        for page in 0..(data.len() / PAGE_SIZE) {
             loop {
                 let start = page * PAGE_SIZE;
                 let end = start + PAGE_SIZE;
-                if self.file.write(&self.memory.as_slice()[start..end])? == PAGE_SIZE {
-                    hasher.update(&self.memory.as_slice()[start..end]);
+                if self.file.write(&data[start..end])? == PAGE_SIZE {
+                    hasher.update(&data[start..end]);
                     break;
                 }
                 self.file
@@ -655,6 +1066,18 @@ impl Datapool for FileBackedMemory {
             }
         }
 
+        // the file shrinks to fit compressed data, and is otherwise restored
+        // to the full size
+        let file_size = match compressed {
+            Some(ref compressed) => HEADER_SIZE + compressed.len(),
+            None => {
+                let pages =
+                    ((HEADER_SIZE + self.memory.len()) as f64 / PAGE_SIZE as f64).ceil() as usize;
+                pages * PAGE_SIZE
+            }
+        };
+        self.file.set_len(file_size as u64)?;
+
         // finalize the hash
         let hash = hasher.finalize();
 
@@ -672,8 +1095,270 @@ impl Datapool for FileBackedMemory {
 
         self.file.sync_all()?;
 
+        // keep our copy of the header in sync with the one on disk
+        self.header.copy_from_slice(header.as_bytes());
+
         Ok(())
     }
+
+    fn user_version(&self) -> u64 {
+        self.user_version
+    }
+
+    fn set_user_version(&mut self, user_version: u64) {
+        self.user_version = user_version;
+    }
+}
+
+// A page of memory which is aligned for direct io, for the parts of the file
+// which aren't read into or written from a `Memory` datapool.
+#[repr(C, align(4096))]
+struct Page([u8; PAGE_SIZE]);
+
+impl Page {
+    fn zeroed() -> Box<Self> {
+        Box::new(Self([0; PAGE_SIZE]))
+    }
+}
+
+// Opens the file which backs a `DirectFilePool` for reading and writing. On
+// Linux it is opened with `O_DIRECT` to bypass the page cache, unless the
+// filesystem rejects the flag, in which case it is opened without it.
+fn open_direct<T: AsRef<Path>>(path: T, create_new: bool) -> Result<File, std::io::Error> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true);
+
+    #[cfg(target_os = "linux")]
+    match options
+        .clone()
+        .create_new(create_new)
+        .custom_flags(libc::O_DIRECT)
+        .open(path.as_ref())
+    {
+        // the file may have been created before the flag was rejected
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+            options.create(create_new);
+        }
+        result => return result,
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    options.create_new(create_new);
+
+    options.open(path)
+}
+
+/// Like `FileBackedMemory`, this keeps the data resident in memory and saves
+/// it to a file on `flush`, with the same header and file layout. The file is
+/// accessed with `O_DIRECT` on Linux, so saving and restoring the data does
+/// not fill the page cache with a second copy of it. Every read and write is
+/// a whole number of pages from memory which is aligned for direct io.
+///
+/// Filesystems which don't support `O_DIRECT`, such as tmpfs, fall back to
+/// buffered io, as do other operating systems. The data region is never
+/// compressed.
+pub struct DirectFilePool {
+    memory: Memory,
+    header: Box<[u8]>,
+    file: File,
+    file_data: Range<usize>,
+    user_version: u64,
+}
+
+impl DirectFilePool {
+    pub fn open<T: AsRef<Path>>(
+        path: T,
+        data_size: usize,
+        user_version: u64,
+    ) -> Result<Self, std::io::Error> {
+        // the file is a whole number of pages for direct io
+        let pages = ((HEADER_SIZE + data_size) as f64 / PAGE_SIZE as f64).ceil() as usize;
+
+        // data resides after a small header
+        let file_data = Range {
+            start: HEADER_SIZE,
+            end: HEADER_SIZE + data_size,
+        };
+
+        // open the existing file with read and write access
+        let mut file = open_direct(path, false)?;
+
+        // make sure the file size matches the expected size
+        if file.metadata()?.len() != (pages * PAGE_SIZE) as u64 {
+            return Err(Error::new(ErrorKind::Other, "filesize mismatch"));
+        }
+
+        // read the header from disk
+        let mut header = Page::zeroed();
+        file.seek(SeekFrom::Start(0))?;
+        loop {
+            if file.read(&mut header.0)? == PAGE_SIZE {
+                break;
+            }
+            file.seek(SeekFrom::Start(0))?;
+        }
+
+        // turn the raw header into the struct
+        let header = unsafe { &*(header.0.as_ptr() as *const Header) };
+
+        // check the header
+        header.check()?;
+
+        // check the user version
+        if header.user_version() != user_version {
+            return Err(Error::new(ErrorKind::Other, "user version mismatch"));
+        }
+
+        // read the data region from the file into memory, which is page
+        // aligned
+        let mut memory = Memory::create(data_size)?;
+        let data_pages = data_size / PAGE_SIZE;
+        file.seek(SeekFrom::Start(file_data.start as u64))?;
+        for page in 0..data_pages {
+            // retry the read until a complete page is read
+            loop {
+                let start = page * PAGE_SIZE;
+                let end = start + PAGE_SIZE;
+
+                if file.read(&mut memory.as_mut_slice()[start..end])? == PAGE_SIZE {
+                    break;
+                }
+                // if the read was incomplete, we seek back to the right spot in
+                // the file
+                file.seek(SeekFrom::Start((HEADER_SIZE + start) as u64))?;
+            }
+        }
+
+        // compare the stored checksum to the hash of the file content
+        header.verify_checksum(&memory.as_slice()[0..(data_pages * PAGE_SIZE)])?;
+
+        Ok(Self {
+            memory,
+            header: header.as_bytes().to_owned().into_boxed_slice(),
+            file,
+            file_data,
+            user_version,
+        })
+    }
+
+    pub fn create<T: AsRef<Path>>(
+        path: T,
+        data_size: usize,
+        user_version: u64,
+    ) -> Result<Self, std::io::Error> {
+        // the file is a whole number of pages for direct io
+        let pages = ((HEADER_SIZE + data_size) as f64 / PAGE_SIZE as f64).ceil() as usize;
+
+        // data resides after a small header
+        let file_data = Range {
+            start: HEADER_SIZE,
+            end: pages * PAGE_SIZE,
+        };
+
+        // create a new file with read and write access
+        let mut file = open_direct(path, true)?;
+
+        // grow the file to match the total size
+        file.set_len((pages * PAGE_SIZE) as u64)?;
+
+        // causes file to be zeroed out
+        let zeros = Page::zeroed();
+        for page in 0..pages {
+            loop {
+                if file.write(&zeros.0)? == PAGE_SIZE {
+                    break;
+                }
+                file.seek(SeekFrom::Start((page * PAGE_SIZE) as u64))?;
+            }
+        }
+        file.sync_all()?;
+
+        let memory = Memory::create(data_size)?;
+
+        Ok(Self {
+            memory,
+            header: vec![0; HEADER_SIZE].into_boxed_slice(),
+            file,
+            file_data,
+            user_version,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        unsafe { &*(self.header.as_ptr() as *const Header) }
+    }
+}
+
+impl Datapool for DirectFilePool {
+    fn as_slice(&self) -> &[u8] {
+        self.memory.as_slice()
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.memory.as_mut_slice()
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        // initialize the hasher
+        let mut hasher = blake3::Hasher::new();
+
+        // prepare the header
+        let mut header = Header::new();
+
+        // set the user version
+        header.set_user_version(self.user_version);
+
+        // hash the header with a zero'd checksum
+        hasher.update(header.as_bytes());
+
+        // write the data region to the file and hash it in one pass. the
+        // memory is page aligned, so it is written directly
+        let data_pages = (self.file_data.end - self.file_data.start) / PAGE_SIZE;
+        let data = &self.memory.as_slice()[0..(data_pages * PAGE_SIZE)];
+        self.file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+        for page in 0..data_pages {
+            loop {
+                let start = page * PAGE_SIZE;
+                let end = start + PAGE_SIZE;
+                if self.file.write(&data[start..end])? == PAGE_SIZE {
+                    hasher.update(&data[start..end]);
+                    break;
+                }
+                self.file
+                    .seek(SeekFrom::Start((HEADER_SIZE + start) as u64))?;
+            }
+        }
+
+        // set the checksum in the header to the calculated hash
+        header.set_checksum(hasher.finalize());
+
+        // write the header to the file, from memory which is aligned for
+        // direct io
+        let mut page = Page::zeroed();
+        page.0.copy_from_slice(header.as_bytes());
+        self.file.seek(SeekFrom::Start(0))?;
+        loop {
+            if self.file.write(&page.0)? == PAGE_SIZE {
+                break;
+            }
+            self.file.seek(SeekFrom::Start(0))?;
+        }
+
+        self.file.sync_all()?;
+
+        // keep our copy of the header in sync with the one on disk
+        self.header.copy_from_slice(header.as_bytes());
+
+        Ok(())
+    }
+
+    fn user_version(&self) -> u64 {
+        self.user_version
+    }
+
+    fn set_user_version(&mut self, user_version: u64) {
+        self.user_version = user_version;
+    }
 }
 
 #[cfg(test)]
@@ -689,8 +1374,79 @@ mod tests {
 
     #[test]
     fn memory_datapool() {
-        let datapool = Memory::create(2 * PAGE_SIZE).expect("failed to create pool");
+        let mut datapool = Memory::create(2 * PAGE_SIZE).expect("failed to create pool");
         assert_eq!(datapool.len(), 2 * PAGE_SIZE);
+
+        // memory can't be persisted, so it has no user version
+        datapool.set_user_version(7);
+        assert_eq!(datapool.user_version(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn memory_datapool_lock() {
+        let mut datapool = Memory::create(4 * PAGE_SIZE).expect("failed to create pool");
+
+        match datapool.lock() {
+            Ok(()) => {
+                // every page must be resident once locked
+                let mut resident = [0_u8; 4];
+                let ret = unsafe {
+                    libc::mincore(
+                        datapool.as_mut_slice().as_mut_ptr() as *mut libc::c_void,
+                        4 * PAGE_SIZE,
+                        resident.as_mut_ptr(),
+                    )
+                };
+                assert_eq!(ret, 0);
+                assert!(resident.iter().all(|page| page & 1 == 1));
+            }
+            Err(e) => {
+                // locking may be disallowed in the test environment, in which
+                // case the error must explain what went wrong
+                assert!(e.to_string().starts_with("failed to lock datapool memory"));
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn memory_datapool_numa() {
+        // MPOL_F_ADDR from `linux/mempolicy.h`
+        const MPOL_F_ADDR: libc::c_ulong = 1 << 1;
+
+        let mut datapool = Memory::create(4 * PAGE_SIZE).expect("failed to create pool");
+
+        match datapool.bind_numa_node(0) {
+            Ok(()) => {
+                // the policy of the region must bind it to only node 0
+                let mut mode: libc::c_int = -1;
+                let mut nodemask: libc::c_ulong = 0;
+                let ret = unsafe {
+                    libc::syscall(
+                        libc::SYS_get_mempolicy,
+                        &mut mode as *mut libc::c_int,
+                        &mut nodemask as *mut libc::c_ulong,
+                        (8 * std::mem::size_of::<libc::c_ulong>()) as libc::c_ulong,
+                        datapool.as_mut_slice().as_mut_ptr() as *mut libc::c_void,
+                        MPOL_F_ADDR,
+                    )
+                };
+                assert_eq!(ret, 0);
+                assert_eq!(mode, MPOL_BIND);
+                assert_eq!(nodemask, 1);
+            }
+            Err(e) => {
+                // NUMA policy may be unavailable in the test environment, in
+                // which case the error must explain what went wrong
+                assert!(e
+                    .to_string()
+                    .starts_with("failed to bind datapool memory to NUMA node 0"));
+            }
+        }
+
+        // a node which doesn't exist is always an error
+        assert!(datapool.bind_numa_node(4095).is_err());
     }
 
     #[test]
@@ -740,6 +1496,87 @@ mod tests {
         {
             assert!(MmapFile::open(&path, 2 * PAGE_SIZE, 1).is_err());
         }
+
+        // change the user version, which is persisted by the flush
+        {
+            let mut datapool =
+                MmapFile::open(&path, 2 * PAGE_SIZE, 0).expect("failed to open pool");
+            assert_eq!(datapool.user_version(), 0);
+            datapool.set_user_version(7);
+            datapool.flush().expect("failed to flush");
+        }
+
+        // the datapool now only opens with the new user version
+        {
+            assert!(MmapFile::open(&path, 2 * PAGE_SIZE, 0).is_err());
+            let datapool = MmapFile::open(&path, 2 * PAGE_SIZE, 7).expect("failed to open pool");
+            assert_eq!(datapool.user_version(), 7);
+            assert_eq!(datapool.as_slice()[0..8], magic_b[0..8]);
+        }
+
+        // check that the datapool does not open if the content is corrupted
+        {
+            corrupt(&path, HEADER_SIZE + PAGE_SIZE + 7);
+            let e = MmapFile::open(&path, 2 * PAGE_SIZE, 7)
+                .err()
+                .expect("opened a corrupted pool");
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn mmapfile_resize() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let mut path = tempdir.into_path();
+        path.push("mmap_resize.data");
+
+        let magic_a = [0xDE, 0xCA, 0xFB, 0xAD];
+        let magic_b = [0xBA, 0xDC, 0x0F, 0xFE];
+
+        // create a datapool, write some content to it, and grow it
+        {
+            let mut datapool =
+                MmapFile::create(&path, 2 * PAGE_SIZE, 0).expect("failed to create pool");
+            datapool.as_mut_slice()[0..4].copy_from_slice(&magic_a);
+
+            // shrinking is an error and leaves the datapool as it was
+            assert!(datapool.resize(PAGE_SIZE).is_err());
+            assert_eq!(datapool.len(), 2 * PAGE_SIZE);
+
+            datapool
+                .try_resize(4 * PAGE_SIZE)
+                .expect("failed to resize pool");
+            assert_eq!(datapool.len(), 4 * PAGE_SIZE);
+            assert_eq!(datapool.as_slice()[0..4], magic_a[0..4]);
+
+            // the new region is zeroed and can be written
+            assert!(datapool.as_slice()[2 * PAGE_SIZE..].iter().all(|b| *b == 0));
+            datapool.as_mut_slice()[3 * PAGE_SIZE..3 * PAGE_SIZE + 4].copy_from_slice(&magic_b);
+            datapool.flush().expect("failed to flush");
+        }
+
+        // the old size no longer matches the file
+        assert!(MmapFile::open(&path, 2 * PAGE_SIZE, 0).is_err());
+
+        // reopen with the new size and check both the old and new data
+        {
+            let datapool = MmapFile::open(&path, 4 * PAGE_SIZE, 0).expect("failed to open pool");
+            assert_eq!(datapool.len(), 4 * PAGE_SIZE);
+            assert_eq!(datapool.as_slice()[0..4], magic_a[0..4]);
+            assert_eq!(
+                datapool.as_slice()[3 * PAGE_SIZE..3 * PAGE_SIZE + 4],
+                magic_b[0..4]
+            );
+        }
+        assert_checksum(&path);
+
+        // resizing is not supported by in-memory datapools
+        let mut datapool = Memory::create(PAGE_SIZE).expect("failed to create pool");
+        let e = datapool
+            .try_resize(2 * PAGE_SIZE)
+            .err()
+            .expect("resized a memory pool");
+        assert_eq!(e.kind(), ErrorKind::Unsupported);
     }
 
     #[test]
@@ -790,5 +1627,296 @@ mod tests {
         {
             assert!(FileBackedMemory::open(&path, 2 * PAGE_SIZE, 1).is_err());
         }
+
+        // change the user version, which is persisted by the flush
+        {
+            let mut datapool =
+                FileBackedMemory::open(&path, 2 * PAGE_SIZE, 0).expect("failed to open pool");
+            assert_eq!(datapool.user_version(), 0);
+            datapool.set_user_version(7);
+            datapool.flush().expect("failed to flush");
+        }
+
+        // the datapool now only opens with the new user version
+        {
+            assert!(FileBackedMemory::open(&path, 2 * PAGE_SIZE, 0).is_err());
+            let datapool =
+                FileBackedMemory::open(&path, 2 * PAGE_SIZE, 7).expect("failed to open pool");
+            assert_eq!(datapool.user_version(), 7);
+            assert_eq!(datapool.as_slice()[0..8], magic_b[0..8]);
+        }
+
+        // check that the datapool does not open if the content is corrupted
+        {
+            corrupt(&path, HEADER_SIZE + 3);
+            let e = FileBackedMemory::open(&path, 2 * PAGE_SIZE, 7)
+                .err()
+                .expect("opened a corrupted pool");
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn mmapfile_checksum() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let path = tempdir.path().join("mmap_test.data");
+
+        let mut datapool =
+            MmapFile::create(&path, 2 * PAGE_SIZE, 3).expect("failed to create pool");
+        datapool.as_mut_slice()[0..4].copy_from_slice(&[0xDE, 0xCA, 0xFB, 0xAD]);
+        datapool.as_mut_slice()[PAGE_SIZE + 1] = 0x42;
+        datapool.flush().expect("failed to flush");
+        assert_checksum(&path);
+
+        // each flush writes a new checksum
+        datapool.as_mut_slice()[7] = 0xFF;
+        datapool.flush().expect("failed to flush");
+        assert_checksum(&path);
+        assert_eq!(datapool.header().user_version(), 3);
+    }
+
+    #[test]
+    fn filebackedmemory_checksum() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let path = tempdir.path().join("mmap_test.data");
+
+        let mut datapool =
+            FileBackedMemory::create(&path, 2 * PAGE_SIZE, 3).expect("failed to create pool");
+        datapool.as_mut_slice()[0..4].copy_from_slice(&[0xDE, 0xCA, 0xFB, 0xAD]);
+        datapool.as_mut_slice()[PAGE_SIZE + 1] = 0x42;
+        datapool.flush().expect("failed to flush");
+        assert_checksum(&path);
+
+        // the header we hold matches the one which was written
+        let on_disk = std::fs::read(&path).expect("failed to read file");
+        assert_eq!(datapool.header().as_bytes(), &on_disk[0..HEADER_SIZE]);
+        assert_eq!(datapool.header().user_version(), 3);
+    }
+
+    #[test]
+    fn filebackedmemory_compression() {
+        for codec in [Codec::Lz4, Codec::Zstd] {
+            let tempdir = TempDir::new().expect("failed to generate tempdir");
+            let path = tempdir.path().join("mmap_test.data");
+
+            // a repeating pattern which compresses well
+            let content: Vec<u8> = (0..(16 * PAGE_SIZE)).map(|i| (i % 61) as u8).collect();
+
+            // create a compressed datapool, write the content to it, and close it
+            {
+                let mut datapool = FileBackedMemory::create(&path, 16 * PAGE_SIZE, 0)
+                    .expect("failed to create pool")
+                    .with_compression(codec);
+                datapool.as_mut_slice().copy_from_slice(&content);
+                datapool.flush().expect("failed to flush");
+                assert_eq!(datapool.header().compression(), Some(codec));
+            }
+
+            // the file is smaller than the data, and the checksum covers it
+            let file_size = std::fs::metadata(&path).expect("no metadata").len();
+            assert!(file_size < (HEADER_SIZE + 16 * PAGE_SIZE) as u64);
+            assert_eq!(file_size as usize % PAGE_SIZE, 0);
+            assert_checksum(&path);
+
+            // the header is not compressed
+            let on_disk = std::fs::read(&path).expect("failed to read file");
+            assert_eq!(on_disk[32..40], MAGIC);
+
+            // open the datapool without naming the codec and check the content,
+            // then update it and flush with the same codec
+            {
+                let mut datapool =
+                    FileBackedMemory::open(&path, 16 * PAGE_SIZE, 0).expect("failed to open pool");
+                assert_eq!(datapool.compression(), Some(codec));
+                assert_eq!(datapool.as_slice(), &content[..]);
+
+                datapool.as_mut_slice()[PAGE_SIZE..PAGE_SIZE + 4]
+                    .copy_from_slice(&[0xDE, 0xCA, 0xFB, 0xAD]);
+                datapool.flush().expect("failed to flush");
+            }
+
+            {
+                let datapool =
+                    FileBackedMemory::open(&path, 16 * PAGE_SIZE, 0).expect("failed to open pool");
+                assert_eq!(datapool.as_slice()[..PAGE_SIZE], content[..PAGE_SIZE]);
+                assert_eq!(
+                    datapool.as_slice()[PAGE_SIZE..PAGE_SIZE + 4],
+                    [0xDE, 0xCA, 0xFB, 0xAD]
+                );
+                assert_eq!(
+                    datapool.as_slice()[PAGE_SIZE + 4..],
+                    content[PAGE_SIZE + 4..]
+                );
+            }
+
+            // check that the datapool does not open if the content is corrupted
+            corrupt(&path, HEADER_SIZE + 3);
+            let e = FileBackedMemory::open(&path, 16 * PAGE_SIZE, 0)
+                .err()
+                .expect("opened a corrupted pool");
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn directfilepool_datapool() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let path = tempdir.path().join("direct_test.data");
+
+        // each page has different content, so a page which is read or written
+        // at the wrong offset is detected
+        let content: Vec<u8> = (0..(8 * PAGE_SIZE))
+            .map(|i| (i / PAGE_SIZE + i % 251) as u8)
+            .collect();
+
+        // create a datapool, write the content to it, and close it
+        {
+            let mut datapool =
+                DirectFilePool::create(&path, 8 * PAGE_SIZE, 1).expect("failed to create pool");
+            assert_eq!(datapool.len(), 8 * PAGE_SIZE);
+            datapool.as_mut_slice().copy_from_slice(&content);
+            datapool.flush().expect("failed to flush");
+        }
+
+        // the file has the same layout as one written by `FileBackedMemory`
+        assert_eq!(
+            std::fs::metadata(&path).expect("no metadata").len(),
+            (HEADER_SIZE + 8 * PAGE_SIZE) as u64
+        );
+        assert_checksum(&path);
+        FileBackedMemory::open(&path, 8 * PAGE_SIZE, 1).expect("failed to open as file backed");
+
+        // the content survives a reopen, and an update survives a flush
+        {
+            let mut datapool =
+                DirectFilePool::open(&path, 8 * PAGE_SIZE, 1).expect("failed to open pool");
+            assert_eq!(datapool.as_slice(), &content[..]);
+
+            datapool.as_mut_slice()[PAGE_SIZE..PAGE_SIZE + 4]
+                .copy_from_slice(&[0xDE, 0xCA, 0xFB, 0xAD]);
+            datapool.flush().expect("failed to flush");
+            assert_checksum(&path);
+        }
+
+        {
+            let datapool =
+                DirectFilePool::open(&path, 8 * PAGE_SIZE, 1).expect("failed to open pool");
+            assert_eq!(datapool.as_slice()[..PAGE_SIZE], content[..PAGE_SIZE]);
+            assert_eq!(
+                datapool.as_slice()[PAGE_SIZE..PAGE_SIZE + 4],
+                [0xDE, 0xCA, 0xFB, 0xAD]
+            );
+            assert_eq!(
+                datapool.as_slice()[PAGE_SIZE + 4..],
+                content[PAGE_SIZE + 4..]
+            );
+        }
+
+        // a different user version is refused
+        assert!(DirectFilePool::open(&path, 8 * PAGE_SIZE, 2).is_err());
+
+        // check that the datapool does not open if the content is corrupted
+        corrupt(&path, HEADER_SIZE + 3);
+        let e = DirectFilePool::open(&path, 8 * PAGE_SIZE, 1)
+            .err()
+            .expect("opened a corrupted pool");
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn directfilepool_bypasses_page_cache() {
+        use std::os::unix::io::AsRawFd;
+
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let path = tempdir.path().join("direct_test.data");
+
+        // the file only bypasses the page cache if the filesystem allows it
+        let supported = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(tempdir.path().join("probe"))
+            .is_ok();
+        let is_direct = |datapool: &DirectFilePool| {
+            let flags = unsafe { libc::fcntl(datapool.file.as_raw_fd(), libc::F_GETFL) };
+            flags & libc::O_DIRECT != 0
+        };
+
+        let mut datapool =
+            DirectFilePool::create(&path, 2 * PAGE_SIZE, 0).expect("failed to create pool");
+        assert_eq!(is_direct(&datapool), supported);
+        datapool.flush().expect("failed to flush");
+        drop(datapool);
+
+        let datapool = DirectFilePool::open(&path, 2 * PAGE_SIZE, 0).expect("failed to open pool");
+        assert_eq!(is_direct(&datapool), supported);
+    }
+
+    #[test]
+    fn flush_async() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let path = tempdir.path().join("mmap_test.data");
+
+        let mut datapool: Box<dyn Datapool> = Box::new(
+            FileBackedMemory::create(&path, 2 * PAGE_SIZE, 0).expect("failed to create pool"),
+        );
+        datapool.as_mut_slice()[0..4].copy_from_slice(&[0xDE, 0xCA, 0xFB, 0xAD]);
+
+        let handle = datapool.flush_async().expect("failed to start flush");
+        handle
+            .wait_timeout(Duration::from_secs(10))
+            .expect("failed to flush");
+        assert_checksum(&path);
+
+        // the flushed datapool can be opened again
+        let datapool =
+            FileBackedMemory::open(&path, 2 * PAGE_SIZE, 0).expect("failed to open pool");
+        assert_eq!(datapool.as_slice()[0..4], [0xDE, 0xCA, 0xFB, 0xAD]);
+
+        // a flush which is still running times out
+        let handle = Box::new(SlowDatapool)
+            .flush_async()
+            .expect("failed to start flush");
+        assert!(!handle.is_finished());
+        let e = handle
+            .wait_timeout(Duration::from_millis(10))
+            .expect_err("slow flush did not time out");
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+    }
+
+    // a datapool with no data which takes a while to flush
+    struct SlowDatapool;
+
+    impl Datapool for SlowDatapool {
+        fn as_slice(&self) -> &[u8] {
+            &[]
+        }
+
+        fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut []
+        }
+
+        fn flush(&mut self) -> Result<(), std::io::Error> {
+            std::thread::sleep(Duration::from_millis(500));
+            Ok(())
+        }
+    }
+
+    // checks that the checksum in the header of a file matches a fresh blake3
+    // hash of the file with the checksum zeroed
+    fn assert_checksum(path: &Path) {
+        let mut content = std::fs::read(path).expect("failed to read file");
+        let mut checksum = [0; 32];
+        checksum.copy_from_slice(&content[0..32]);
+        content[0..32].fill(0);
+        assert_eq!(blake3::hash(&content).as_bytes(), &checksum);
+    }
+
+    // flips the bits of one byte of a file
+    fn corrupt(path: &Path, offset: usize) {
+        let mut content = std::fs::read(path).expect("failed to read file");
+        content[offset] ^= 0xFF;
+        std::fs::write(path, content).expect("failed to write file");
     }
 }
@@ -46,6 +46,60 @@ fn get_benchmark(c: &mut Criterion) {
     }
 }
 
+// compares looking up the keys of a multi-get one at a time with looking them
+// up as a batch
+fn multiget_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multiget");
+    group.measurement_time(Duration::from_secs(30));
+
+    for batch_size in [1, 8, 32].iter() {
+        group.throughput(Throughput::Elements(*batch_size as u64));
+
+        let (keys, values) = key_values(16, 1_000_000, 64, 1);
+
+        // a larger table than the cpu cache, so that lookups miss the cache
+        let mut cache = Seg::builder()
+            .hash_power(20)
+            .heap_size(256 * MB)
+            .segment_size(MB as i32)
+            .build()
+            .expect("failed to create cache");
+
+        for key in &keys {
+            let _ = cache.insert(key, &values[0], None, Duration::ZERO);
+        }
+
+        let batches: Vec<Vec<&[u8]>> = keys
+            .chunks(*batch_size)
+            .map(|chunk| chunk.iter().map(|key| key.as_slice()).collect())
+            .collect();
+
+        let mut batch = 0;
+
+        group.bench_function(&format!("serial/{}", batch_size), |b| {
+            b.iter(|| {
+                for key in &batches[batch] {
+                    cache.get(key);
+                }
+                batch += 1;
+                if batch >= batches.len() {
+                    batch = 0;
+                }
+            })
+        });
+
+        group.bench_function(&format!("batched/{}", batch_size), |b| {
+            b.iter(|| {
+                cache.get_batch(&batches[batch]);
+                batch += 1;
+                if batch >= batches.len() {
+                    batch = 0;
+                }
+            })
+        });
+    }
+}
+
 fn key_values(
     key_size: usize,
     key_count: usize,
@@ -109,5 +163,5 @@ fn set_benchmark(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, get_benchmark, set_benchmark,);
+criterion_group!(benches, get_benchmark, multiget_benchmark, set_benchmark,);
 criterion_main!(benches);
@@ -12,6 +12,8 @@ pub struct Builder {
     hash_power: u8,
     overflow_factor: f64,
     segments_builder: SegmentsBuilder,
+    ttl_bucket_max_segments: usize,
+    max_active_ttl_buckets: usize,
 }
 
 // Defines the default parameters
@@ -21,6 +23,8 @@ impl Default for Builder {
             hash_power: 16,
             overflow_factor: 0.0,
             segments_builder: SegmentsBuilder::default(),
+            ttl_bucket_max_segments: 0,
+            max_active_ttl_buckets: 0,
         }
     }
 }
@@ -101,6 +105,9 @@ impl Builder {
     /// and book-keeping overheads compared to using larger segments for the
     /// same total size.
     ///
+    /// The segment size may be at most 8MiB, and the heap size must be a whole
+    /// multiple of the segment size, otherwise `build()` returns an error.
+    ///
     /// ```
     /// use seg::Seg;
     ///
@@ -135,6 +142,53 @@ impl Builder {
         self
     }
 
+    /// Limit the number of segments any single TTL bucket may hold. Once a
+    /// bucket reaches the limit, writes into it evict its oldest segment
+    /// instead of taking a free segment or evicting from other buckets. This
+    /// keeps a workload with one hot TTL from monopolizing the heap. A value
+    /// of zero, the default, disables the limit.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// const MB: usize = 1024 * 1024;
+    ///
+    /// // with 1MB segments, no TTL bucket may hold more than 16MB of items
+    /// let cache = Seg::builder()
+    ///     .heap_size(64 * MB)
+    ///     .segment_size(1 * MB as i32)
+    ///     .ttl_bucket_max_segments(16)
+    ///     .build();
+    /// ```
+    pub fn ttl_bucket_max_segments(mut self, segments: usize) -> Self {
+        self.ttl_bucket_max_segments = segments;
+        self
+    }
+
+    /// Limit the number of TTL buckets which may hold segments at the same
+    /// time. Each active bucket holds at least one segment, so a workload
+    /// which writes with many distinct TTLs can otherwise spread a small number
+    /// of items across a large number of partially filled segments. Once the
+    /// limit is reached, items whose TTL maps to an empty bucket are written to
+    /// the closest active bucket with a shorter TTL instead and expire along
+    /// with it, so items may expire early but never late. Items are only
+    /// coalesced into a bucket which expires them at most 1/8th of their TTL
+    /// early, and items without a TTL are never coalesced. This trades
+    /// expiration precision for bounded overhead. When no active bucket is
+    /// close enough, the items take their own bucket and the limit is exceeded
+    /// until buckets expire. A value of zero, the default, disables the limit.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// // items are spread across no more than 64 distinct expiration times
+    /// let cache = Seg::builder().max_active_ttl_buckets(64).build();
+    /// ```
+    pub fn max_active_ttl_buckets(mut self, buckets: usize) -> Self {
+        self.max_active_ttl_buckets = buckets;
+        self
+    }
+
     /// Specify a backing file to be used for segment storage.
     ///
     /// # Panics
@@ -145,6 +199,66 @@ impl Builder {
         self
     }
 
+    /// Lock the heap into memory at startup. This avoids page faults and
+    /// swapping when items are accessed, at the cost of requiring the memlock
+    /// rlimit to be at least as large as the heap. If the memory can't be
+    /// locked, a warning is logged and the heap is used without locking.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// let cache = Seg::builder().heap_size(1024 * 1024).lock_memory(true).build();
+    /// ```
+    pub fn lock_memory(mut self, lock: bool) -> Self {
+        self.segments_builder = self.segments_builder.lock_memory(lock);
+        self
+    }
+
+    /// Allocate the heap from memory local to a NUMA node, which avoids
+    /// cross-socket memory traffic when the thread accessing the cache runs on
+    /// the same node. This is only supported on Linux. If the memory can't be
+    /// bound to the node, a warning is logged and the heap is used with the
+    /// default allocation policy.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// let cache = Seg::builder().heap_size(1024 * 1024).numa_node(Some(0)).build();
+    /// ```
+    pub fn numa_node(mut self, node: Option<usize>) -> Self {
+        self.segments_builder = self.segments_builder.numa_node(node);
+        self
+    }
+
+    /// Specify the most bytes which may be held by items that are exempt from
+    /// eviction, see [`Seg::insert_no_evict`]. Segments holding exempt items
+    /// are skipped by eviction, so this bounds how much of the heap can't be
+    /// reclaimed until those items expire or are removed. Zero, the default,
+    /// disables the exemption.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// let cache = Seg::builder().heap_size(4 * 1024 * 1024).no_evict_cap(1024 * 1024).build();
+    /// ```
+    pub fn no_evict_cap(mut self, bytes: usize) -> Self {
+        self.segments_builder = self.segments_builder.no_evict_cap(bytes);
+        self
+    }
+
+    /// Checks that the segment and heap sizes are supported without
+    /// allocating anything. The same checks are made by `build`.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// // the heap must hold a whole number of segments
+    /// assert!(Seg::builder().heap_size(1000).segment_size(300).validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), std::io::Error> {
+        self.segments_builder.validate()
+    }
+
     /// Consumes the builder and returns a fully-allocated `Seg` instance.
     ///
     /// ```
@@ -161,13 +275,16 @@ impl Builder {
     pub fn build(self) -> Result<Seg, std::io::Error> {
         let hashtable = HashTable::new(self.hash_power, self.overflow_factor);
         let segments = self.segments_builder.build()?;
-        let ttl_buckets = TtlBuckets::default();
+        let mut ttl_buckets = TtlBuckets::default();
+        ttl_buckets.set_max_nseg(self.ttl_bucket_max_segments);
+        ttl_buckets.set_max_active(self.max_active_ttl_buckets);
 
         Ok(Seg {
             hashtable,
             segments,
             ttl_buckets,
             time: Instant::recent(),
+            degraded: false,
         })
     }
 }
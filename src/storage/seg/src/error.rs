@@ -25,4 +25,6 @@ pub enum SegError {
     DataCorrupted,
     #[error("item is not numeric")]
     NotNumeric,
+    #[error("storage is degraded, writes are rejected")]
+    Degraded,
 }
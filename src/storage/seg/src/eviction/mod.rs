@@ -7,6 +7,7 @@
 
 use core::cmp::{max, Ordering};
 use core::num::NonZeroU32;
+use std::cell::Cell;
 
 use ::rand::Rng;
 
@@ -27,12 +28,15 @@ pub struct Eviction {
     ranked_segs: Box<[Option<NonZeroU32>]>,
     index: usize,
     rng: Box<Random>,
+    no_evict_cap: usize,
+    no_evict_bytes: Cell<i64>,
 }
 
 impl Eviction {
     /// Creates a new `Eviction` struct which will handle up to `nseg` segments
-    /// using the specified eviction policy.
-    pub fn new(nseg: usize, policy: Policy) -> Self {
+    /// using the specified eviction policy. Items which are exempt from
+    /// eviction may hold up to `no_evict_cap` bytes.
+    pub fn new(nseg: usize, policy: Policy, no_evict_cap: usize) -> Self {
         let mut ranked_segs = Vec::with_capacity(0);
         ranked_segs.reserve_exact(nseg);
         ranked_segs.resize_with(nseg, || None);
@@ -44,6 +48,8 @@ impl Eviction {
             ranked_segs,
             index: 0,
             rng: Box::new(rng()),
+            no_evict_cap,
+            no_evict_bytes: Cell::new(0),
         }
     }
 
@@ -52,6 +58,20 @@ impl Eviction {
         self.policy
     }
 
+    /// The most bytes which may be held by items exempt from eviction
+    #[inline]
+    pub fn no_evict_cap(&self) -> usize {
+        self.no_evict_cap
+    }
+
+    /// The bytes held by items exempt from eviction across all segments. Each
+    /// `Segment` updates this as exempt items are written and removed, so
+    /// that the headers don't need to be summed to check the cap
+    #[inline]
+    pub fn no_evict_bytes(&self) -> &Cell<i64> {
+        &self.no_evict_bytes
+    }
+
     /// Returns the segment id of the least valuable segment
     pub fn least_valuable_seg(&mut self) -> Option<NonZeroU32> {
         let index = self.index;
@@ -246,15 +246,81 @@ impl HashTable {
         }
     }
 
+    /// Approximate time of the most recent access to any item in the bucket.
+    /// The bucket timestamp is 16 bits wide, so idle times wrap after ~18
+    /// hours.
+    fn last_access(&self, bucket_info: u64, now: Instant) -> Instant {
+        let curr_ts = (now - self.started).as_secs() & PROC_TS_MASK;
+        let idle = curr_ts.wrapping_sub(get_ts(bucket_info) as u32) & PROC_TS_MASK;
+        now - Duration::from_secs(idle)
+    }
+
     /// Lookup an item by key and return it
     pub fn get(&mut self, key: &[u8], time: Instant, segments: &mut Segments) -> Option<Item> {
         let hash = self.hash(key);
+        self.get_hashed(key, hash, time, segments)
+    }
+
+    /// Lookup a batch of items by key and return them in the same order as
+    /// the keys. The buckets for every key are prefetched before any of them
+    /// are read, so that their cache misses overlap instead of being taken one
+    /// after another.
+    pub fn get_batch(
+        &mut self,
+        keys: &[&[u8]],
+        time: Instant,
+        segments: &mut Segments,
+    ) -> Vec<Option<Item>> {
+        let hashes: Vec<u64> = keys.iter().map(|key| self.hash(key)).collect();
+
+        for hash in &hashes {
+            prefetch(&self.data[(hash & self.mask) as usize]);
+        }
+
+        keys.iter()
+            .zip(hashes)
+            .map(|(key, hash)| self.get_hashed(key, hash, time, segments))
+            .collect()
+    }
+
+    /// Lookup a batch of items by key in the order of their buckets, rather
+    /// than the order of the keys, so that neighbouring buckets are read one
+    /// after another. Each item is returned with the index of its key.
+    pub fn get_batch_unordered(
+        &mut self,
+        keys: &[&[u8]],
+        time: Instant,
+        segments: &mut Segments,
+    ) -> Vec<(usize, Option<Item>)> {
+        let mut hashes: Vec<(usize, u64)> =
+            keys.iter().map(|key| self.hash(key)).enumerate().collect();
+
+        for (_, hash) in &hashes {
+            prefetch(&self.data[(hash & self.mask) as usize]);
+        }
+
+        hashes.sort_by_key(|(_, hash)| hash & self.mask);
+
+        hashes
+            .into_iter()
+            .map(|(index, hash)| (index, self.get_hashed(keys[index], hash, time, segments)))
+            .collect()
+    }
+
+    fn get_hashed(
+        &mut self,
+        key: &[u8],
+        hash: u64,
+        time: Instant,
+        segments: &mut Segments,
+    ) -> Option<Item> {
         let tag = tag_from_hash(hash);
         let bucket_id = hash & self.mask;
 
         let bucket_info = self.data[bucket_id as usize].data[0];
 
         let curr_ts = (time - self.started).as_secs() & PROC_TS_MASK;
+        let last_access = self.last_access(bucket_info, time);
 
         if curr_ts != get_ts(bucket_info) as u32 {
             self.data[bucket_id as usize].data[0] = (bucket_info & !TS_MASK) | (curr_ts as u64);
@@ -285,9 +351,13 @@ impl HashTable {
                         *item_info = (*item_info & !FREQ_MASK) | freq;
                     }
 
+                    let (create_at, ttl) = segments.item_lifetime(*item_info).unwrap();
                     let item = Item::new(
                         current_item,
                         get_cas(self.data[(hash & self.mask) as usize].data[0]),
+                        create_at,
+                        ttl,
+                        last_access,
                     );
                     item.check_magic();
 
@@ -315,9 +385,15 @@ impl HashTable {
                 if current_item.key() != key {
                     HASH_TAG_COLLISION.increment();
                 } else {
+                    let (create_at, ttl) = segments.item_lifetime(*item_info).unwrap();
+                    let bucket_info = self.data[(hash & self.mask) as usize].data[0];
+                    let last_access = self.last_access(bucket_info, Instant::recent());
                     let item = Item::new(
                         current_item,
                         get_cas(self.data[(hash & self.mask) as usize].data[0]),
+                        create_at,
+                        ttl,
+                        last_access,
                     );
                     item.check_magic();
 
@@ -329,6 +405,25 @@ impl HashTable {
         None
     }
 
+    /// Return the frequency for the item with the key, wherever it is stored
+    #[cfg(test)]
+    pub(crate) fn freq_of(&mut self, key: &[u8], segments: &mut Segments) -> Option<u64> {
+        let hash = self.hash(key);
+        let tag = tag_from_hash(hash);
+
+        let iter = IterMut::new(self, hash);
+
+        for item_info in iter {
+            if get_tag(*item_info) == tag
+                && segments.get_item(*item_info).map(|item| item.key() == key) == Some(true)
+            {
+                return Some(get_freq(*item_info) & 0x7F);
+            }
+        }
+
+        None
+    }
+
     /// Return the frequency for the item with the key
     pub fn get_freq(&mut self, key: &[u8], segment: &mut Segment, offset: u64) -> Option<u64> {
         let hash = self.hash(key);
@@ -585,6 +680,15 @@ impl HashTable {
         result
     }
 
+    /// Remove a single item from the cache as part of clearing it
+    pub fn clear(&mut self, key: &[u8], offset: i32, segment: &mut Segment) -> bool {
+        let result = self.remove_from(key, offset, segment);
+        if result {
+            ITEM_FLUSH.increment();
+        }
+        result
+    }
+
     /// Internal function that removes an item from a segment
     fn remove_from(&mut self, key: &[u8], offset: i32, segment: &mut Segment) -> bool {
         let hash = self.hash(key);
@@ -616,6 +720,82 @@ impl HashTable {
         false
     }
 
+    /// Calls `f` with each item in the hashtable, stopping at the first error.
+    /// Items are visited in no particular order, and expired items which have
+    /// not yet been removed are included.
+    pub(crate) fn for_each_item<E>(
+        &mut self,
+        segments: &mut Segments,
+        mut f: impl FnMut(Item) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let now = Instant::recent();
+        for bucket_id in 0..=(self.mask as usize) {
+            let bucket_info = self.data[bucket_id].data[0];
+            let cas = get_cas(bucket_info);
+            let last_access = self.last_access(bucket_info, now);
+
+            let item_infos: Vec<u64> = IterMut::new(self, bucket_id as u64)
+                .map(|item_info| *item_info)
+                .filter(|item_info| *item_info != 0)
+                .collect();
+
+            for item_info in item_infos {
+                let raw = segments.get_item(item_info).unwrap();
+                let (create_at, ttl) = segments.item_lifetime(item_info).unwrap();
+                f(Item::new(raw, cas, create_at, ttl, last_access))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every bucket chain is within the hashtable, and that every
+    /// entry points to a valid item whose key hashes to the bucket and tag of
+    /// the entry. Returns a description of the first inconsistency found.
+    #[cfg(any(test, feature = "debug"))]
+    pub(crate) fn verify(&mut self, segments: &mut Segments) -> Result<(), String> {
+        for bucket_id in 0..=(self.mask as usize) {
+            let len = chain_len(self.data[bucket_id].data[0]);
+            if len > MAX_CHAIN_LEN {
+                return Err(format!(
+                    "hashtable bucket {} has a chain of {} buckets, more than the maximum of {}",
+                    bucket_id, len, MAX_CHAIN_LEN
+                ));
+            }
+
+            let mut chained = bucket_id;
+            for _ in 0..len {
+                chained = self.data[chained].data[N_BUCKET_SLOT - 1] as usize;
+                if chained >= self.data.len() {
+                    return Err(format!(
+                        "hashtable bucket {} is chained to bucket {} which does not exist",
+                        bucket_id, chained
+                    ));
+                }
+            }
+
+            let item_infos: Vec<u64> = IterMut::new(self, bucket_id as u64)
+                .map(|item_info| *item_info)
+                .filter(|item_info| *item_info != 0)
+                .collect();
+
+            for item_info in item_infos {
+                let item = segments.verify_item(item_info)?;
+                let hash = self.hash(item.key());
+                if (hash & self.mask) as usize != bucket_id
+                    || get_tag(item_info) != tag_from_hash(hash)
+                {
+                    return Err(format!(
+                        "hashtable bucket {} has an entry for an item which does not hash to it",
+                        bucket_id
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Internal function used to calculate a hash value for a key
     fn hash(&self, key: &[u8]) -> u64 {
         HASH_LOOKUP.increment();
@@ -624,3 +804,19 @@ impl HashTable {
         hasher.finish()
     }
 }
+
+/// Hints that the bucket will be read soon, so that it can be brought into
+/// the cache ahead of the read.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn prefetch(bucket: &HashBucket) {
+    use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+    // SAFETY: a prefetch has no effect on the program other than on the cache
+    // and the pointer is to a live bucket
+    unsafe { _mm_prefetch::<_MM_HINT_T0>(bucket as *const HashBucket as *const i8) }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+fn prefetch(_bucket: &HashBucket) {}
@@ -19,7 +19,7 @@
 //! Flags:
 //! ```text
 //! ┌──────────────┬──────────────┬──────────────────────────────┐
-//! │    TYPED?    │   PADDING    │             OLEN             │
+//! │    TYPED?    │  NO EVICT?   │             OLEN             │
 //! │              │              │                              │
 //! │    1 bit     │    1 bit     │            6 bit             │
 //! │              │              │                              │
@@ -59,13 +59,16 @@ const VLEN_SHIFT: u32 = 8;
 const TYPE_MASK: u32 = 0xFF000000;
 const TYPE_SHIFT: u32 = 24;
 
-// olen/del/typed
+// olen/no_evict/typed
 /// A mask to get the optional data length in bytes from the item header's flags
 /// field
 const OLEN_MASK: u8 = 0b00111111;
 /// A mask to get the bit indicating the item value should be treated as a
 /// typed value from the item header's flags field
 const TYPED_MASK: u8 = 0b10000000;
+/// A mask to get the bit indicating the item is exempt from eviction from the
+/// item header's flags field
+const NO_EVICT_MASK: u8 = 0b01000000;
 
 use core::convert::TryFrom;
 
@@ -112,7 +115,7 @@ pub struct ItemHeader {
     #[cfg(feature = "magic")]
     magic: u32,
     len: u32,  // packs vlen:24 klen:8
-    flags: u8, // packs is_num:1, deleted:1, olen:6
+    flags: u8, // packs is_num:1, no_evict:1, olen:6
 }
 
 impl ItemHeader {
@@ -203,6 +206,22 @@ impl ItemHeader {
         }
     }
 
+    /// Is the item exempt from eviction?
+    #[inline]
+    pub fn is_no_evict(&self) -> bool {
+        self.flags & NO_EVICT_MASK != 0
+    }
+
+    /// Mark the item as exempt from eviction
+    #[inline]
+    pub fn set_no_evict(&mut self, no_evict: bool) {
+        if no_evict {
+            self.flags |= NO_EVICT_MASK;
+        } else {
+            self.flags &= !NO_EVICT_MASK;
+        }
+    }
+
     /// Mark the item as numeric
     #[inline]
     fn set_typed(&mut self, typed: bool) {
@@ -236,6 +255,7 @@ impl std::fmt::Debug for ItemHeader {
             .field("klen", &self.klen())
             .field("vlen", &self.vlen())
             .field("type", &self.value_type())
+            .field("no_evict", &self.is_no_evict())
             .field("olen", &self.olen())
             .finish()
     }
@@ -250,6 +270,7 @@ impl std::fmt::Debug for ItemHeader {
             .field("klen", &self.klen())
             .field("vlen", &self.vlen())
             .field("typed", &self.is_typed())
+            .field("no_evict", &self.is_no_evict())
             .field("olen", &self.olen())
             .finish()
     }
@@ -8,11 +8,11 @@ mod header;
 mod raw;
 mod reserved;
 
-#[cfg(any(feature = "magic", feature = "debug"))]
 pub(crate) use header::ITEM_MAGIC_SIZE;
 
 use crate::SegError;
 use crate::Value;
+use crate::{Duration, Instant};
 
 pub(crate) use header::{ItemHeader, ITEM_HDR_SIZE};
 pub(crate) use raw::RawItem;
@@ -22,12 +22,28 @@ pub(crate) use reserved::ReservedItem;
 pub struct Item {
     cas: u32,
     raw: RawItem,
+    create_at: Instant,
+    ttl: Duration,
+    last_access: Instant,
 }
 
 impl Item {
-    /// Creates a new `Item` from its parts
-    pub(crate) fn new(raw: RawItem, cas: u32) -> Self {
-        Item { cas, raw }
+    /// Creates a new `Item` from its parts. The creation time and TTL are those
+    /// of the segment holding the item, as items expire with their segment.
+    pub(crate) fn new(
+        raw: RawItem,
+        cas: u32,
+        create_at: Instant,
+        ttl: Duration,
+        last_access: Instant,
+    ) -> Self {
+        Item {
+            cas,
+            raw,
+            create_at,
+            ttl,
+            last_access,
+        }
     }
 
     /// If the `magic` or `debug` features are enabled, this allows for checking
@@ -61,6 +77,70 @@ impl Item {
         self.raw.optional()
     }
 
+    /// The number of bytes the item occupies in its segment, including the
+    /// header and any padding for alignment
+    pub fn size(&self) -> usize {
+        self.raw.size()
+    }
+
+    /// The memcache flags for the item, which are stored as the first four
+    /// bytes of the optional data. Items without flags return zero.
+    pub fn flags(&self) -> u32 {
+        match self.optional() {
+            Some(o) if o.len() >= 4 => u32::from_be_bytes([o[0], o[1], o[2], o[3]]),
+            _ => 0,
+        }
+    }
+
+    /// Returns `true` if the item was written as exempt from eviction, see
+    /// `Seg::insert_no_evict`
+    pub fn is_no_evict(&self) -> bool {
+        self.raw.is_no_evict()
+    }
+
+    /// The length of the value in bytes, without borrowing the value itself
+    pub fn value_len(&self) -> usize {
+        self.raw.vlen() as usize
+    }
+
+    /// The TTL of the segment holding the item. Items are grouped with others
+    /// which have a similar TTL, so this may differ slightly from the TTL the
+    /// item was written with.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The time at which the item expires
+    pub fn expire_at(&self) -> Instant {
+        self.create_at + self.ttl
+    }
+
+    /// The time left before the item expires as of `now`, which is zero once
+    /// it has expired, or `None` if the item doesn't expire
+    pub fn remaining_ttl(&self, now: Instant) -> Option<Duration> {
+        if self.ttl.as_secs() >= crate::MAX_BUCKET_TTL {
+            None
+        } else if self.expire_at() > now {
+            Some(self.expire_at() - now)
+        } else {
+            Some(Duration::from_secs(0))
+        }
+    }
+
+    /// Returns `true` if the item has expired as of `now`, even if it has not
+    /// yet been removed by eager expiration
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expire_at() <= now
+    }
+
+    /// Approximate time the item was last read or written. Access times are
+    /// tracked per hash bucket with a wrapping 16-bit timestamp, so this is
+    /// shared with other items in the same bucket and accesses more than
+    /// ~18 hours ago appear more recent than they were.
+    pub fn last_access(&self) -> Instant {
+        self.last_access
+    }
+
     /// Perform a wrapping addition on the value. Returns an error if the item
     /// is not a numeric type.
     pub fn wrapping_add(&mut self, rhs: u64) -> Result<(), SegError> {
@@ -78,6 +158,7 @@ impl std::fmt::Debug for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.debug_struct("Item")
             .field("cas", &self.cas())
+            .field("ttl", &self.ttl().as_secs())
             .field("raw", &self.raw)
             .finish()
     }
@@ -89,3 +170,74 @@ pub fn size_of(value: &Value) -> usize {
         Value::U64(_) => core::mem::size_of::<u64>(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn is_expired() {
+        let mut cache = Seg::builder().build().expect("failed to create cache");
+        let now = Instant::recent();
+
+        assert!(cache
+            .insert(
+                b"coffee",
+                b"strong",
+                None,
+                std::time::Duration::from_secs(60)
+            )
+            .is_ok());
+        let item = cache.get(b"coffee").expect("item not found");
+        // the ttl is rounded to that of the item's ttl bucket
+        let remaining = (item.expire_at() - now).as_secs();
+        assert!(remaining > 0 && remaining <= 60);
+
+        assert!(!item.is_expired(now));
+        assert!(!item.is_expired(now + Duration::from_secs(remaining - 1)));
+        assert!(item.is_expired(now + Duration::from_secs(remaining)));
+        assert!(item.is_expired(now + Duration::from_secs(remaining + 3600)));
+
+        assert_eq!(
+            item.remaining_ttl(now).map(|d| d.as_secs()),
+            Some(remaining)
+        );
+        assert_eq!(
+            item.remaining_ttl(now + Duration::from_secs(remaining + 3600))
+                .map(|d| d.as_secs()),
+            Some(0)
+        );
+
+        assert!(cache
+            .insert(b"tea", b"green", None, std::time::Duration::ZERO)
+            .is_ok());
+        let item = cache.get(b"tea").expect("item not found");
+        assert!(item.remaining_ttl(now).is_none());
+    }
+
+    #[test]
+    fn metadata() {
+        let mut cache = Seg::builder().build().expect("failed to create cache");
+
+        let flags = 0xDEADBEEF_u32.to_be_bytes();
+        assert!(cache
+            .insert(
+                b"coffee",
+                b"strong",
+                Some(&flags),
+                std::time::Duration::ZERO
+            )
+            .is_ok());
+        let item = cache.get(b"coffee").expect("item not found");
+        assert_eq!(item.flags(), 0xDEADBEEF);
+        assert_eq!(item.value_len(), 6);
+        assert!(item.last_access() <= Instant::recent());
+
+        assert!(cache
+            .insert(b"tea", b"green", None, std::time::Duration::ZERO)
+            .is_ok());
+        let item = cache.get(b"tea").expect("item not found");
+        assert_eq!(item.flags(), 0);
+        assert_eq!(item.value_len(), 5);
+    }
+}
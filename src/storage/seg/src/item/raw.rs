@@ -59,7 +59,7 @@ impl RawItem {
 
     /// Returns the value length as stored in the header
     #[inline]
-    fn vlen(&self) -> u32 {
+    pub(crate) fn vlen(&self) -> u32 {
         self.header().vlen()
     }
 
@@ -88,6 +88,20 @@ impl RawItem {
         self.header().olen()
     }
 
+    /// Is the item exempt from eviction?
+    #[inline]
+    pub(crate) fn is_no_evict(&self) -> bool {
+        self.header().is_no_evict()
+    }
+
+    /// Mark the item as exempt from eviction
+    #[inline]
+    pub(crate) fn set_no_evict(&mut self, no_evict: bool) {
+        unsafe {
+            (*self.header_mut()).set_no_evict(no_evict);
+        }
+    }
+
     /// Borrow the optional data
     pub(crate) fn optional(&self) -> Option<&[u8]> {
         if self.olen() > 0 {
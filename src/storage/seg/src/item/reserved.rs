@@ -29,6 +29,11 @@ impl ReservedItem {
         self.item.define(key, value, optional)
     }
 
+    /// Mark the item as exempt from eviction
+    pub fn set_no_evict(&mut self) {
+        self.item.set_no_evict(true)
+    }
+
     /// Get the `RawItem` that backs the `ReservedItem`
     pub fn item(&self) -> RawItem {
         self.item
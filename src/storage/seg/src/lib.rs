@@ -47,6 +47,7 @@ mod metrics;
 mod rand;
 mod seg;
 mod segments;
+pub mod snapshot;
 mod ttl_buckets;
 
 // tests
@@ -54,14 +55,15 @@ mod ttl_buckets;
 mod tests;
 
 // publicly exported items from submodules
-pub use crate::seg::Seg;
+pub use crate::seg::{ReadKind, Seg};
 pub use builder::Builder;
 pub use error::SegError;
 pub use eviction::Policy;
 pub use item::Item;
 
 // publicly exported items from external crates
-pub use storage_types::Value;
+pub use datapool::FlushHandle;
+pub use storage_types::{OwnedValue, Value};
 
 // type aliases
 pub(crate) type Duration = common::time::Duration<Seconds<u32>>;
@@ -17,6 +17,10 @@ counter!(
     "number of segment allocation attempts which were successful"
 );
 counter!(SEGMENT_EVICT, "number of segments evicted");
+counter!(
+    SEGMENT_EVICT_CAP,
+    "number of segments evicted because their ttl bucket was at its segment cap"
+);
 counter!(
     SEGMENT_EVICT_EX,
     "number of exceptions while evicting segments"
@@ -39,6 +43,18 @@ counter!(
 gauge!(EVICT_TIME, "time, in nanoseconds, spent evicting segments");
 gauge!(SEGMENT_FREE, "current number of free segments");
 gauge!(SEGMENT_CURRENT, "current number of segments");
+gauge!(
+    STORAGE_DEGRADED,
+    "set to 1 while writes are rejected because the datapool failed to flush"
+);
+gauge!(
+    TTL_BUCKET_COUNT,
+    "current number of ttl buckets which hold segments"
+);
+counter!(
+    TTL_BUCKET_COALESCE,
+    "number of writes placed in a nearby ttl bucket because too many were active"
+);
 
 // hash table related
 counter!(HASH_TAG_COLLISION, "number of partial hash collisions");
@@ -63,9 +79,17 @@ counter!(
 // item related
 counter!(ITEM_ALLOCATE, "number of times items have been allocated");
 counter!(ITEM_REPLACE, "number of times items have been replaced");
-counter!(ITEM_DELETE, "number of items removed from the hash table");
-counter!(ITEM_EXPIRE, "number of items removed due to expiration");
-counter!(ITEM_EVICT, "number of items removed due to eviction");
+// items removed from the cache, counted by the reason they were removed
+counter!(ITEM_DELETE, "number of items removed by a delete");
+counter!(
+    ITEM_EXPIRE,
+    "number of items removed because their ttl elapsed"
+);
+counter!(
+    ITEM_EVICT,
+    "number of items removed to make room for new items"
+);
+counter!(ITEM_FLUSH, "number of items removed by clearing the cache");
 counter!(ITEM_COMPACTED, "number of items which have been compacted");
 gauge!(ITEM_CURRENT, "current number of live items");
 gauge!(
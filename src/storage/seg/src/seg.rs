@@ -7,9 +7,23 @@
 use crate::Value;
 use crate::*;
 use std::cmp::min;
+use std::time::SystemTime;
 
 const RESERVE_RETRIES: usize = 3;
 
+/// What a lookup reads from an item. Eviction policies which retain items by
+/// how often they are accessed, such as merge eviction, only count value
+/// reads. This keeps monitoring which polls metadata, such as ttls, from
+/// protecting the items it looks at from eviction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReadKind {
+    /// The value is read, which counts as an access of the item.
+    Value,
+    /// Only metadata such as the ttl or size is read. The item's frequency and
+    /// access time are left unchanged.
+    Metadata,
+}
+
 /// A pre-allocated key-value store with eager expiration. It uses a
 /// segment-structured design that stores data in fixed-size segments, grouping
 /// objects with nearby expiration time into the same segment, and lifting most
@@ -19,6 +33,9 @@ pub struct Seg {
     pub(crate) segments: Segments,
     pub(crate) ttl_buckets: TtlBuckets,
     pub(crate) time: Instant,
+    /// Set while the datapool can't be flushed, writes are rejected until a
+    /// flush succeeds again
+    pub(crate) degraded: bool,
 }
 
 impl Seg {
@@ -57,7 +74,9 @@ impl Seg {
         self.segments.items()
     }
 
-    /// Get the item in the `Seg` with the provided key
+    /// Get the item in the `Seg` with the provided key. Items which have
+    /// expired are not returned, even if they have not yet been removed by
+    /// `expire`.
     ///
     /// ```
     /// use seg::{Policy, Seg};
@@ -71,7 +90,83 @@ impl Seg {
     /// assert_eq!(item.value(), b"strong");
     /// ```
     pub fn get(&mut self, key: &[u8]) -> Option<Item> {
-        self.hashtable.get(key, self.time, &mut self.segments)
+        let now = Instant::recent();
+        self.hashtable
+            .get(key, self.time, &mut self.segments)
+            .filter(|item| !item.is_expired(now))
+    }
+
+    /// Get the items in the `Seg` with the provided keys, in the same order as
+    /// the keys. The results are the same as calling `get` for each key in
+    /// turn, but the hashtable lookups for the batch are overlapped, which
+    /// makes multi-key reads faster.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// cache.insert(b"coffee", b"strong", None, Duration::ZERO);
+    ///
+    /// let keys: [&[u8]; 2] = [b"coffee", b"tea"];
+    /// let items = cache.get_batch(&keys);
+    /// assert_eq!(items[0].as_ref().unwrap().value(), b"strong");
+    /// assert!(items[1].is_none());
+    /// ```
+    pub fn get_batch(&mut self, keys: &[&[u8]]) -> Vec<Option<Item>> {
+        let now = Instant::recent();
+        self.hashtable
+            .get_batch(keys, self.time, &mut self.segments)
+            .into_iter()
+            .map(|item| item.filter(|item| !item.is_expired(now)))
+            .collect()
+    }
+
+    /// Get the items in the `Seg` for a batch of keys, in whatever order is
+    /// quickest to look them up in. Each item is returned with the index of
+    /// its key, and a missing item is `None`. Every access is the same as
+    /// for [`Seg::get_batch`].
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// cache.insert(b"coffee", b"strong", None, Duration::ZERO);
+    ///
+    /// let keys: [&[u8]; 2] = [b"coffee", b"tea"];
+    /// for (index, item) in cache.get_batch_unordered(&keys) {
+    ///     assert_eq!(item.is_some(), keys[index] == b"coffee");
+    /// }
+    /// ```
+    pub fn get_batch_unordered(&mut self, keys: &[&[u8]]) -> Vec<(usize, Option<Item>)> {
+        let now = Instant::recent();
+        self.hashtable
+            .get_batch_unordered(keys, self.time, &mut self.segments)
+            .into_iter()
+            .map(|(index, item)| (index, item.filter(|item| !item.is_expired(now))))
+            .collect()
+    }
+
+    /// Get the item in the `Seg` with the provided key, where `kind` is what
+    /// will be read from it. Only value reads count as an access of the item.
+    ///
+    /// ```
+    /// use seg::{ReadKind, Seg};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// cache.insert(b"coffee", b"strong", None, Duration::from_secs(60));
+    ///
+    /// // checking the ttl doesn't protect the item from eviction
+    /// let item = cache.read(b"coffee", ReadKind::Metadata).unwrap();
+    /// assert!(item.ttl().as_secs() <= 60);
+    /// ```
+    pub fn read(&mut self, key: &[u8], kind: ReadKind) -> Option<Item> {
+        match kind {
+            ReadKind::Value => self.get(key),
+            ReadKind::Metadata => self.get_no_freq_incr(key),
+        }
     }
 
     /// Get the item in the `Seg` with the provided key without
@@ -84,7 +179,10 @@ impl Seg {
     /// assert!(cache.get_no_freq_incr(b"coffee").is_none());
     /// ```
     pub fn get_no_freq_incr(&mut self, key: &[u8]) -> Option<Item> {
-        self.hashtable.get_no_freq_incr(key, &mut self.segments)
+        let now = Instant::recent();
+        self.hashtable
+            .get_no_freq_incr(key, &mut self.segments)
+            .filter(|item| !item.is_expired(now))
     }
 
     /// Insert a new item into the cache. May return an error indicating that
@@ -111,7 +209,46 @@ impl Seg {
         optional: Option<&[u8]>,
         ttl: std::time::Duration,
     ) -> Result<(), SegError> {
-        let value: Value = value.into();
+        self.insert_item(key, value.into(), optional, ttl, false)
+    }
+
+    /// Insert a new item into the cache, as with `insert`, but exempt it from
+    /// eviction. The segment holding the item is skipped by eviction until the
+    /// item is replaced, removed, or expires. Items are only exempt while the
+    /// exempt items hold no more than the cap set by `Builder::no_evict_cap`,
+    /// beyond that they are inserted as ordinary items.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().no_evict_cap(1024).build().expect("failed to create cache");
+    ///
+    /// cache.insert_no_evict(b"drink", b"coffee", None, Duration::ZERO);
+    /// let item = cache.get(b"drink").expect("didn't get item back");
+    /// assert!(item.is_no_evict());
+    /// ```
+    pub fn insert_no_evict<'a, T: Into<Value<'a>>>(
+        &mut self,
+        key: &'a [u8],
+        value: T,
+        optional: Option<&[u8]>,
+        ttl: std::time::Duration,
+    ) -> Result<(), SegError> {
+        self.insert_item(key, value.into(), optional, ttl, true)
+    }
+
+    fn insert_item(
+        &mut self,
+        key: &[u8],
+        value: Value,
+        optional: Option<&[u8]>,
+        ttl: std::time::Duration,
+        no_evict: bool,
+    ) -> Result<(), SegError> {
+        if self.degraded {
+            return Err(SegError::Degraded);
+        }
 
         // default optional data is empty
         let optional = optional.unwrap_or(&[]);
@@ -123,11 +260,11 @@ impl Seg {
 
         // try to get a `ReservedItem`
         let mut retries = RESERVE_RETRIES;
-        let reserved;
+        let mut reserved;
         loop {
             match self
                 .ttl_buckets
-                .get_mut_bucket(ttl)
+                .get_mut_bucket_for_write(ttl)
                 .reserve(size, &mut self.segments)
             {
                 Ok(mut reserved_item) => {
@@ -138,6 +275,19 @@ impl Seg {
                 Err(TtlBucketsError::ItemOversized { size }) => {
                     return Err(SegError::ItemOversized { size });
                 }
+                Err(TtlBucketsError::SegmentCapReached) => {
+                    // make room within the bucket itself, so that one ttl
+                    // can't push out items from the others
+                    if self
+                        .ttl_buckets
+                        .get_mut_bucket_for_write(ttl)
+                        .evict_head(&mut self.hashtable, &mut self.segments)
+                    {
+                        continue;
+                    } else {
+                        retries -= 1;
+                    }
+                }
                 Err(TtlBucketsError::NoFreeSegments) => {
                     if self
                         .segments
@@ -162,6 +312,15 @@ impl Seg {
             retries -= 1;
         }
 
+        // the item is only exempt from eviction while there is room under the
+        // cap, otherwise it is stored as an ordinary item
+        if no_evict && self.segments.can_exempt(size) {
+            reserved.set_no_evict();
+            if let Ok(mut segment) = self.segments.get_mut(reserved.seg()) {
+                segment.mark_no_evict(size as i32);
+            }
+        }
+
         // insert into the hashtable, or roll-back by removing the item
         // TODO(bmartin): we can probably roll-back the offset and re-use the
         // space in the segment, currently we consume the space even if the
@@ -190,6 +349,37 @@ impl Seg {
         }
     }
 
+    /// Store a new value for the key and return the value it replaced, if any,
+    /// as a single operation. As with `insert`, the new item takes the
+    /// provided optional data and TTL. If the insert fails, the old value is
+    /// left in place and the error is returned.
+    ///
+    /// ```
+    /// use seg::{OwnedValue, Seg};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// // there is no previous value to return
+    /// assert!(cache.swap(b"drink", b"coffee", None, Duration::ZERO).unwrap().is_none());
+    ///
+    /// let old = cache.swap(b"drink", b"whisky", None, Duration::ZERO).unwrap();
+    /// assert!(old == Some(OwnedValue::Bytes(b"coffee".to_vec().into_boxed_slice())));
+    /// ```
+    pub fn swap<'a, T: Into<Value<'a>>>(
+        &mut self,
+        key: &'a [u8],
+        value: T,
+        optional: Option<&[u8]>,
+        ttl: std::time::Duration,
+    ) -> Result<Option<OwnedValue>, SegError> {
+        // the old value must be copied out before the insert, as the insert
+        // may evict or compact the segment holding it
+        let old = self.get(key).map(|item| item.value().to_owned());
+        self.insert(key, value, optional, ttl)?;
+        Ok(old)
+    }
+
     /// Performs a CAS operation, inserting the item only if the CAS value
     /// matches the current value for that item.
     ///
@@ -227,6 +417,10 @@ impl Seg {
         ttl: std::time::Duration,
         cas: u32,
     ) -> Result<(), SegError> {
+        if self.degraded {
+            return Err(SegError::Degraded);
+        }
+
         match self.hashtable.try_update_cas(key, cas, &mut self.segments) {
             Ok(()) => self.insert(key, value, optional, ttl),
             Err(e) => Err(e),
@@ -256,6 +450,61 @@ impl Seg {
             .delete(key, &mut self.ttl_buckets, &mut self.segments)
     }
 
+    /// Returns the approximate number of bytes used to store the item with the
+    /// given key. This is the size of the item within its segment plus the
+    /// hashtable entry which points to it. Does not count as an access of the
+    /// item.
+    /// ```
+    /// use seg::{Policy, Seg};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// assert!(cache.memory_usage(b"coffee").is_none());
+    ///
+    /// cache.insert(b"coffee", b"strong", None, Duration::ZERO);
+    /// assert!(cache.memory_usage(b"coffee").unwrap() > b"strong".len());
+    /// ```
+    pub fn memory_usage(&mut self, key: &[u8]) -> Option<usize> {
+        self.read(key, ReadKind::Metadata)
+            .map(|item| item.size() + core::mem::size_of::<u64>())
+    }
+
+    /// Returns the Unix time, in seconds, at which the item with the given key
+    /// expires. Items expire along with their segment, so this is the time the
+    /// segment was created plus the TTL of the bucket it belongs to. Items
+    /// written without a TTL live in the bucket with the longest TTL and are
+    /// reported as `Some(None)`, as are items whose TTL put them in that same
+    /// bucket. Returns `None` if the key is not found. Does not count as an
+    /// access of the item.
+    /// ```
+    /// use seg::{Policy, Seg};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// assert!(cache.expire_time(b"coffee").is_none());
+    ///
+    /// cache.insert(b"coffee", b"strong", None, Duration::ZERO);
+    /// assert_eq!(cache.expire_time(b"coffee"), Some(None));
+    ///
+    /// cache.insert(b"tea", b"green", None, Duration::from_secs(60));
+    /// assert!(cache.expire_time(b"tea").unwrap().is_some());
+    /// ```
+    pub fn expire_time(&mut self, key: &[u8]) -> Option<Option<u64>> {
+        let item = self.read(key, ReadKind::Metadata)?;
+
+        let remaining = match item.remaining_ttl(Instant::recent()) {
+            Some(remaining) => remaining,
+            None => return Some(None),
+        };
+
+        let epoch = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Some(Some(epoch + u64::from(remaining.as_secs())))
+    }
+
     /// Loops through the TTL Buckets to handle eager expiration, returns the
     /// number of segments expired
     /// ```
@@ -291,6 +540,111 @@ impl Seg {
             .clear(&mut self.hashtable, &mut self.segments)
     }
 
+    /// Flushes the data to the backing store of the datapool, if it has one.
+    /// If the flush fails, for example because the file has become read-only
+    /// or the disk is full, the cache is degraded: reads, deletes and
+    /// expiration continue as normal, but writes return
+    /// `SegError::Degraded`. The next successful flush restores writes.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// // a cache held only in memory always flushes successfully
+    /// assert!(cache.flush().is_ok());
+    /// assert!(!cache.is_degraded());
+    /// assert!(cache.insert(b"drink", b"coffee", None, Duration::ZERO).is_ok());
+    /// ```
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        match self.segments.flush() {
+            Ok(()) => {
+                if self.degraded {
+                    info!("datapool flushed, writes are accepted again");
+                    self.degraded = false;
+                    STORAGE_DEGRADED.set(0);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                if !self.degraded {
+                    error!("failed to flush datapool, rejecting writes: {}", e);
+                    self.degraded = true;
+                    STORAGE_DEGRADED.set(1);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Consumes the cache and flushes the data to the backing store of the
+    /// datapool on a new thread. This lets a caller which is shutting down
+    /// start the flush, carry on tearing down, and then wait for the flush
+    /// using the returned handle, optionally with a timeout.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// let flush = cache.flush_async().expect("failed to start flush");
+    /// assert!(flush.wait_timeout(Duration::from_secs(10)).is_ok());
+    /// ```
+    pub fn flush_async(self) -> Result<FlushHandle, std::io::Error> {
+        self.segments.flush_async()
+    }
+
+    /// Returns `true` if writes are being rejected because the last flush of
+    /// the datapool failed.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    /// Replaces the datapool with one which wraps it, so that failures can be
+    /// injected into its operations. This is only intended for testing.
+    #[cfg(feature = "debug")]
+    pub fn wrap_datapool<F>(&mut self, f: F) -> Result<(), std::io::Error>
+    where
+        F: FnOnce(Box<dyn datapool::Datapool>) -> Box<dyn datapool::Datapool>,
+    {
+        self.segments.wrap_datapool(f)
+    }
+
+    /// Immediately evicts up to `segments` segments, chosen by the eviction
+    /// policy as they would be for an insert which finds no free segment.
+    /// Returns the number of items which were evicted, and stops early if no
+    /// segment can be evicted. This allows eviction policies to be tested
+    /// deterministically.
+    #[cfg(any(test, feature = "debug"))]
+    pub fn evict(&mut self, segments: usize) -> usize {
+        let before = self.segments.items();
+        for _ in 0..segments {
+            if self
+                .segments
+                .evict(&mut self.ttl_buckets, &mut self.hashtable)
+                .is_err()
+            {
+                break;
+            }
+        }
+        before.saturating_sub(self.segments.items())
+    }
+
+    /// Runs a self-consistency check over the storage, returning a
+    /// description of the first inconsistency found. This checks that no
+    /// segment reports more bytes than it can hold, that the segment chain of
+    /// every TTL bucket is well formed, and that every hashtable entry points
+    /// to a valid item within a segment which is in use.
+    /// *NOTE*: this operation is expensive, as it reads the entire hashtable
+    #[cfg(any(test, feature = "debug"))]
+    pub fn verify(&mut self) -> Result<(), String> {
+        self.segments.verify_headers()?;
+        self.ttl_buckets.verify(&mut self.segments)?;
+        self.hashtable.verify(&mut self.segments)
+    }
+
     /// Checks the integrity of all segments
     /// *NOTE*: this operation is relatively expensive
     #[cfg(feature = "debug")]
@@ -302,10 +656,132 @@ impl Seg {
         }
     }
 
+    /// Appends the bytes to the value of an existing item, keeping its
+    /// optional data and the time at which it expires. Numeric values are
+    /// treated as their decimal representation. Returns an error if the item
+    /// is not found.
+    ///
+    /// ```
+    /// use seg::{Policy, Seg, SegError};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// // append does not create an item
+    /// assert_eq!(cache.append(b"drink", b" coffee"), Err(SegError::NotFound));
+    /// assert!(cache.get(b"drink").is_none());
+    ///
+    /// cache.insert(b"drink", b"black", None, Duration::ZERO);
+    /// assert!(cache.append(b"drink", b" coffee").is_ok());
+    /// let item = cache.get(b"drink").expect("didn't get item back");
+    /// assert_eq!(item.value(), b"black coffee");
+    /// ```
+    pub fn append(&mut self, key: &[u8], value: &[u8]) -> Result<(), SegError> {
+        self.concat(key, value, false)
+    }
+
+    /// Prepends the bytes to the value of an existing item, keeping its
+    /// optional data and the time at which it expires. Numeric values are
+    /// treated as their decimal representation. Returns an error if the item
+    /// is not found.
+    ///
+    /// ```
+    /// use seg::{Policy, Seg, SegError};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// // prepend does not create an item
+    /// assert_eq!(cache.prepend(b"drink", b"black "), Err(SegError::NotFound));
+    /// assert!(cache.get(b"drink").is_none());
+    ///
+    /// cache.insert(b"drink", b"coffee", None, Duration::ZERO);
+    /// assert!(cache.prepend(b"drink", b"black ").is_ok());
+    /// let item = cache.get(b"drink").expect("didn't get item back");
+    /// assert_eq!(item.value(), b"black coffee");
+    /// ```
+    pub fn prepend(&mut self, key: &[u8], value: &[u8]) -> Result<(), SegError> {
+        self.concat(key, value, true)
+    }
+
+    fn concat(&mut self, key: &[u8], value: &[u8], prepend: bool) -> Result<(), SegError> {
+        // the existing item must be copied out before the insert, as the
+        // insert may evict or compact the segment holding it
+        let item = self.get_no_freq_incr(key).ok_or(SegError::NotFound)?;
+
+        let existing = match item.value() {
+            Value::Bytes(b) => b.to_vec(),
+            Value::U64(v) => format!("{}", v).into_bytes(),
+        };
+        let optional = item.optional().map(|o| o.to_vec());
+
+        // a zero ttl means no expiry, so the remaining ttl is rounded up to
+        // keep an item which is about to expire from living forever
+        let ttl = match item.remaining_ttl(Instant::recent()) {
+            Some(remaining) => {
+                std::time::Duration::from_secs(u64::from(remaining.as_secs().max(1)))
+            }
+            None => std::time::Duration::ZERO,
+        };
+
+        let mut concatenated = Vec::with_capacity(existing.len() + value.len());
+        if prepend {
+            concatenated.extend_from_slice(value);
+            concatenated.extend_from_slice(&existing);
+        } else {
+            concatenated.extend_from_slice(&existing);
+            concatenated.extend_from_slice(value);
+        }
+
+        self.insert(key, &concatenated[..], optional.as_deref(), ttl)
+    }
+
+    /// Changes the TTL of an existing item, keeping its value and optional
+    /// data. A zero TTL means the item does not expire. As with an insert, the
+    /// item is rewritten into a segment of the matching TTL bucket. Returns an
+    /// error if the item is not found.
+    ///
+    /// ```
+    /// use seg::{Policy, Seg, SegError};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// // touch does not create an item
+    /// assert_eq!(cache.touch(b"drink", Duration::ZERO), Err(SegError::NotFound));
+    ///
+    /// cache.insert(b"drink", b"coffee", None, Duration::from_secs(60));
+    /// assert!(cache.touch(b"drink", Duration::ZERO).is_ok());
+    /// assert_eq!(cache.expire_time(b"drink"), Some(None));
+    /// let item = cache.get(b"drink").expect("didn't get item back");
+    /// assert_eq!(item.value(), b"coffee");
+    /// ```
+    pub fn touch(&mut self, key: &[u8], ttl: std::time::Duration) -> Result<(), SegError> {
+        if self.degraded {
+            return Err(SegError::Degraded);
+        }
+
+        // as with concat, the item is copied out before the insert
+        let item = self.get_no_freq_incr(key).ok_or(SegError::NotFound)?;
+        let optional = item.optional().map(|o| o.to_vec());
+
+        match item.value() {
+            Value::Bytes(b) => {
+                let value = b.to_vec();
+                self.insert(key, &value[..], optional.as_deref(), ttl)
+            }
+            Value::U64(v) => self.insert(key, v, optional.as_deref(), ttl),
+        }
+    }
+
     /// Perform a wrapping addition on the value stored at the supplied key.
     /// Returns an error if the key is invalid, the item is not found, or the
     /// stored value is not a numeric type.
     pub fn wrapping_add(&mut self, key: &[u8], rhs: u64) -> Result<Item, SegError> {
+        if self.degraded {
+            return Err(SegError::Degraded);
+        }
+
         let mut item = self
             .hashtable
             .get(key, self.time, &mut self.segments)
@@ -318,6 +794,10 @@ impl Seg {
     /// key. Returns an error if the key is invalid, the item is not found, or
     /// the stored value is not a numeric type.
     pub fn saturating_sub(&mut self, key: &[u8], rhs: u64) -> Result<Item, SegError> {
+        if self.degraded {
+            return Err(SegError::Degraded);
+        }
+
         let mut item = self
             .hashtable
             .get(key, self.time, &mut self.segments)
@@ -5,17 +5,30 @@
 //! A builder struct for initializing segment storage.
 
 use crate::eviction::*;
+use crate::hashtable::{OFFSET_MASK, OFFSET_UNIT_IN_BIT};
 use crate::item::*;
 use crate::segments::*;
 
+use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
 
+/// Item offsets within a segment are stored in the hashtable in units of 8
+/// bytes using 20 bits, which limits segments to 8MiB.
+const MAX_SEGMENT_SIZE: usize = ((OFFSET_MASK + 1) << OFFSET_UNIT_IN_BIT) as usize;
+
+/// Segment ids are stored in the hashtable using 24 bits, and the id zero is
+/// reserved.
+const MAX_SEGMENTS: usize = (1 << 24) - 1;
+
 /// The `SegmentsBuilder` allows for the configuration of the segment storage.
 pub(crate) struct SegmentsBuilder {
     pub(super) heap_size: usize,
     pub(super) segment_size: i32,
     pub(super) evict_policy: Policy,
     pub(super) datapool_path: Option<PathBuf>,
+    pub(super) lock_memory: bool,
+    pub(super) numa_node: Option<usize>,
+    pub(super) no_evict_cap: usize,
 }
 
 impl Default for SegmentsBuilder {
@@ -25,25 +38,18 @@ impl Default for SegmentsBuilder {
             heap_size: 64 * 1024 * 1024,
             evict_policy: Policy::Random,
             datapool_path: None,
+            lock_memory: false,
+            numa_node: None,
+            no_evict_cap: 0,
         }
     }
 }
 
 impl<'a> SegmentsBuilder {
-    /// Set the segment size in bytes.
-    ///
-    /// # Panics
-    ///
-    /// This function will panic if the size is not greater than the per-item
-    /// overhead. Currently this means that the minimum size is 6 bytes when
-    /// built without magic/debug, or 10 bytes when built with magic/debug.
+    /// Set the segment size in bytes. The size must be greater than the
+    /// per-item overhead and no larger than 8MiB, which is checked when the
+    /// segments are built.
     pub fn segment_size(mut self, bytes: i32) -> Self {
-        #[cfg(not(feature = "magic"))]
-        assert!(bytes > ITEM_HDR_SIZE as i32);
-
-        #[cfg(feature = "magic")]
-        assert!(bytes > ITEM_HDR_SIZE as i32 + ITEM_MAGIC_SIZE as i32);
-
         self.segment_size = bytes;
         self
     }
@@ -70,8 +76,75 @@ impl<'a> SegmentsBuilder {
         self
     }
 
+    /// Lock the segment storage into memory with `mlock` so that it is never
+    /// swapped out and does not page fault once the cache is running.
+    pub fn lock_memory(mut self, lock: bool) -> Self {
+        self.lock_memory = lock;
+        self
+    }
+
+    /// Bind the segment storage to a NUMA node so that it is allocated from
+    /// memory local to that node. `None` leaves the allocation to the default
+    /// policy of the operating system.
+    pub fn numa_node(mut self, node: Option<usize>) -> Self {
+        self.numa_node = node;
+        self
+    }
+
+    /// Set the most bytes which may be held by items that are exempt from
+    /// eviction. Zero disables the exemption.
+    pub fn no_evict_cap(mut self, bytes: usize) -> Self {
+        self.no_evict_cap = bytes;
+        self
+    }
+
+    /// Checks that the segment size is supported and that the heap divides
+    /// evenly into a supported number of segments.
+    pub fn validate(&self) -> Result<(), std::io::Error> {
+        let min_segment_size = ITEM_HDR_SIZE + ITEM_MAGIC_SIZE + 1;
+
+        if self.segment_size < min_segment_size as i32
+            || self.segment_size as usize > MAX_SEGMENT_SIZE
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "segment size {} is out of range, it must be between {} and {} bytes",
+                    self.segment_size, min_segment_size, MAX_SEGMENT_SIZE
+                ),
+            ));
+        }
+
+        let segment_size = self.segment_size as usize;
+
+        if self.heap_size % segment_size != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "heap size {} is not a multiple of the segment size {}",
+                    self.heap_size, segment_size
+                ),
+            ));
+        }
+
+        let segments = self.heap_size / segment_size;
+
+        if segments == 0 || segments > MAX_SEGMENTS {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "heap size {} holds {} segments of {} bytes, it must hold between 1 and {}",
+                    self.heap_size, segments, segment_size, MAX_SEGMENTS
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Construct the [`Segments`] from the builder
     pub fn build(self) -> Result<Segments, std::io::Error> {
+        self.validate()?;
         Segments::from_builder(self)
     }
 }
@@ -15,11 +15,11 @@
 //! │   PREV SEG   │   NEXT SEG   │  CREATE AT   │   MERGE AT   │
 //! │              │              │              │              │
 //! │    32 bit    │    32 bit    │    32 bit    │    32 bit    │
-//! ├──────────────┼──┬──┬────────┴──────────────┴──────────────┤
-//! │     TTL      │  │  │               PADDING                │   Accessible
-//! │              │  │◀─┼──────────────────────────────────────┼──    8 bit
-//! │    32 bit    │8b│8b│                80 bit                │
-//! ├──────────────┴──┴──┴──────────────────────────────────────┤    Evictable
+//! ├──────────────┼──┬──┬────────┼──────────────┼──────────────┤
+//! │     TTL      │  │  │  PAD   │NO EVICT BYTES│   PADDING    │   Accessible
+//! │              │  │◀─┼────────┼──────────────┼──────────────┼──    8 bit
+//! │    32 bit    │8b│8b│ 16 bit │    32 bit    │    32 bit    │
+//! ├──────────────┴──┴──┴────────┴──────────────┴──────────────┤    Evictable
 //! │                          PADDING                          │      8 bit
 //! │                                                           │
 //! │                          128 bit                          │
@@ -60,7 +60,10 @@ pub struct SegmentHeader {
     accessible: bool,
     /// Is the segment evictable?
     evictable: bool,
-    _pad: [u8; 25],
+    /// The number of live bytes in the segment which belong to items that are
+    /// exempt from eviction
+    no_evict_bytes: i32,
+    _pad: [u8; 20],
 }
 
 impl SegmentHeader {
@@ -77,7 +80,8 @@ impl SegmentHeader {
             merge_at: Instant::recent(),
             accessible: false,
             evictable: false,
-            _pad: [0; 25],
+            no_evict_bytes: 0,
+            _pad: [0; 20],
         }
     }
 
@@ -108,6 +112,7 @@ impl SegmentHeader {
 
         self.write_offset = offset;
         self.live_bytes = offset;
+        self.no_evict_bytes = 0;
     }
 
     #[inline]
@@ -214,6 +219,26 @@ impl SegmentHeader {
         prev
     }
 
+    #[inline]
+    /// The number of live bytes in the segment which belong to items that are
+    /// exempt from eviction. The segment can't be evicted while this is
+    /// non-zero.
+    pub fn no_evict_bytes(&self) -> i32 {
+        self.no_evict_bytes
+    }
+
+    #[inline]
+    /// Increment the number of bytes held by items exempt from eviction.
+    pub fn incr_no_evict_bytes(&mut self, bytes: i32) {
+        self.no_evict_bytes += bytes;
+    }
+
+    #[inline]
+    /// Decrement the number of bytes held by items exempt from eviction.
+    pub fn decr_no_evict_bytes(&mut self, bytes: i32) {
+        self.no_evict_bytes -= bytes;
+    }
+
     #[inline]
     /// Returns an option containing the previous segment id if there is one.
     pub fn prev_seg(&self) -> Option<NonZeroU32> {
@@ -269,9 +294,11 @@ impl SegmentHeader {
     // for the instant + duration portion. We set the allow pragma to silence
     // the false positive.
     #[allow(clippy::suspicious_operation_groupings)]
-    /// Can the segment be evicted?
+    /// Can the segment be evicted? Segments holding items which are exempt
+    /// from eviction can't be, though they still expire.
     pub fn can_evict(&self) -> bool {
         self.evictable()
+            && self.no_evict_bytes == 0
             && self.next_seg().is_some()
             && (self.create_at() + self.ttl()) >= (Instant::recent() + SEG_MATURE_TIME)
     }
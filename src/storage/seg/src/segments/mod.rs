@@ -21,6 +21,19 @@ pub(crate) use header::SegmentHeader;
 pub(crate) use segment::Segment;
 pub(crate) use segments::Segments;
 
+/// The reason items are being removed from a segment, which decides the
+/// metric that counts them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Removal {
+    /// Removed to make room for new items
+    Evict,
+    /// Removed because their ttl has elapsed
+    Expire,
+    /// Removed by clearing the cache, either directly or because the segment
+    /// was created before the `flush_at` cutoff
+    Flush,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -5,6 +5,7 @@
 use super::{SegmentHeader, SegmentsError};
 use crate::*;
 use core::num::NonZeroU32;
+use std::cell::Cell;
 
 pub const SEG_MAGIC: u64 = 0xBADC0FFEEBADCAFE;
 
@@ -14,6 +15,9 @@ pub const SEG_MAGIC: u64 = 0xBADC0FFEEBADCAFE;
 pub struct Segment<'a> {
     header: &'a mut SegmentHeader,
     data: &'a mut [u8],
+    /// The bytes held by exempt items across all segments, which is updated
+    /// along with the header of this segment
+    no_evict_bytes: &'a Cell<i64>,
 }
 
 impl<'a> Segment<'a> {
@@ -21,8 +25,13 @@ impl<'a> Segment<'a> {
     pub fn from_raw_parts(
         header: &'a mut segments::header::SegmentHeader,
         data: &'a mut [u8],
+        no_evict_bytes: &'a Cell<i64>,
     ) -> Self {
-        Segment { header, data }
+        Segment {
+            header,
+            data,
+            no_evict_bytes,
+        }
     }
 
     /// Initialize the segment. Sets the magic bytes in the data segment (if the
@@ -152,6 +161,13 @@ impl<'a> Segment<'a> {
         self.header.live_bytes()
     }
 
+    /// Return the number of live bytes held by items which are exempt from
+    /// eviction. The segment can't be evicted while this is non-zero.
+    #[inline]
+    pub fn no_evict_bytes(&self) -> i32 {
+        self.header.no_evict_bytes()
+    }
+
     /// Return the number of live items in the segment.
     #[inline]
     pub fn live_items(&self) -> i32 {
@@ -264,6 +280,15 @@ impl<'a> Segment<'a> {
         self.header.incr_live_items();
     }
 
+    /// Marks an item which was allocated in this segment as exempt from
+    /// eviction, which keeps the segment from being evicted until the item is
+    /// removed.
+    pub(crate) fn mark_no_evict(&mut self, bytes: i32) {
+        self.header.incr_no_evict_bytes(bytes);
+        self.no_evict_bytes
+            .set(self.no_evict_bytes.get() + bytes as i64);
+    }
+
     /// Allocate a new `RawItem` with the given size
     ///
     /// # Safety
@@ -298,6 +323,12 @@ impl<'a> Segment<'a> {
 
         let item_size = item.size() as i64;
 
+        if item.is_no_evict() {
+            self.header.decr_no_evict_bytes(item_size as i32);
+            self.no_evict_bytes
+                .set(self.no_evict_bytes.get() - item_size);
+        }
+
         ITEM_CURRENT.decrement();
         ITEM_CURRENT_BYTES.sub(item_size);
         ITEM_DEAD.increment();
@@ -464,6 +495,9 @@ impl<'a> Segment<'a> {
                 self.remove_item_at(read_offset);
                 target.header.incr_live_items();
                 target.header.incr_live_bytes(item_size as i32);
+                if item.is_no_evict() {
+                    target.mark_no_evict(item_size as i32);
+                }
                 target.set_write_offset(write_offset as i32 + item_size as i32);
                 items_copied += 1;
                 bytes_copied += item_size;
@@ -587,9 +621,8 @@ impl<'a> Segment<'a> {
     }
 
     /// Remove all items from the segment, unlinking them from the hashtable.
-    /// If expire is true, this is treated as an expiration option. Otherwise it
-    /// is treated as an eviction.
-    pub(crate) fn clear(&mut self, hashtable: &mut HashTable, expire: bool) {
+    /// The reason for the removal decides which metric counts the items.
+    pub(crate) fn clear(&mut self, hashtable: &mut HashTable, removal: Removal) {
         self.set_accessible(false);
         self.set_evictable(false);
 
@@ -620,10 +653,11 @@ impl<'a> Segment<'a> {
             let deleted = !hashtable.is_item_at(item.key(), self.id(), offset as u64);
             if !deleted {
                 trace!("evicting from hashtable");
-                let removed = if expire {
-                    hashtable.expire(item.key(), offset.try_into().unwrap(), self)
-                } else {
-                    hashtable.evict(item.key(), offset.try_into().unwrap(), self)
+                let item_offset = offset.try_into().unwrap();
+                let removed = match removal {
+                    Removal::Evict => hashtable.evict(item.key(), item_offset, self),
+                    Removal::Expire => hashtable.expire(item.key(), item_offset, self),
+                    Removal::Flush => hashtable.clear(item.key(), item_offset, self),
                 };
                 if !removed {
                     // this *shouldn't* happen, but to keep header integrity, we
@@ -24,9 +24,12 @@ pub(crate) struct Segments {
     cap: u32,
     /// Head of the free segment queue
     free_q: Option<NonZeroU32>,
-    /// Time last flushed
+    /// Time of the last `flush_all`, segments created before it are cleared
     flush_at: Instant,
-    /// Eviction configuration and state
+    /// Time the segment data was last persisted to the datapool
+    persisted_at: Instant,
+    /// Eviction configuration and state, including the running total of
+    /// bytes held by items which are exempt from eviction
     evict: Box<Eviction>,
 }
 
@@ -72,12 +75,33 @@ impl Segments {
             Box::new(Memory::create(heap_size)?)
         };
 
+        // failing to bind is not fatal, the storage still works but may be
+        // accessed across NUMA nodes
+        if let Some(node) = builder.numa_node {
+            if let Err(e) = data.bind_numa_node(node) {
+                warn!("{}, continuing without NUMA binding", e);
+            }
+        }
+
+        // failing to lock is not fatal, the pages are already prefaulted and
+        // will only be lost to swapping under memory pressure
+        if builder.lock_memory {
+            if let Err(e) = data.lock() {
+                warn!("{}, continuing without locked memory", e);
+            }
+        }
+
+        let evict = Box::new(Eviction::new(segments, evict_policy, builder.no_evict_cap));
+
         for idx in 0..segments {
             let begin = segment_size as usize * idx;
             let end = begin + segment_size as usize;
 
-            let mut segment =
-                Segment::from_raw_parts(&mut headers[idx], &mut data.as_mut_slice()[begin..end]);
+            let mut segment = Segment::from_raw_parts(
+                &mut headers[idx],
+                &mut data.as_mut_slice()[begin..end],
+                evict.no_evict_bytes(),
+            );
             segment.init();
 
             let id = idx as u32 + 1; // we index segments from 1
@@ -98,7 +122,8 @@ impl Segments {
             free_q: NonZeroU32::new(1),
             data,
             flush_at: Instant::now(),
-            evict: Box::new(Eviction::new(segments, evict_policy)),
+            persisted_at: Instant::now(),
+            evict,
         })
     }
 
@@ -114,6 +139,13 @@ impl Segments {
         self.free as usize
     }
 
+    /// Returns `true` if an item of the given size may be exempt from
+    /// eviction without the items which are exempt holding more than the cap.
+    pub(crate) fn can_exempt(&self, bytes: usize) -> bool {
+        let cap = self.evict.no_evict_cap();
+        cap > 0 && self.evict.no_evict_bytes().get() as usize + bytes <= cap
+    }
+
     /// Returns the time the segments were last flushed
     pub fn flush_at(&self) -> Instant {
         self.flush_at
@@ -124,6 +156,40 @@ impl Segments {
         self.flush_at = instant;
     }
 
+    /// Returns the time the segment data was last persisted to the datapool
+    #[cfg(test)]
+    pub fn persisted_at(&self) -> Instant {
+        self.persisted_at
+    }
+
+    /// Persists the segment data to the backing store of the datapool. The
+    /// persist time is only updated if the flush succeeds. This is unrelated
+    /// to `flush_at`, which is the cutoff set by `flush_all`.
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.data.flush()?;
+        self.persisted_at = Instant::now();
+        Ok(())
+    }
+
+    /// Starts persisting the segment data on a new thread, consuming the
+    /// segments. See `Datapool::flush_async`.
+    pub fn flush_async(self) -> Result<FlushHandle, std::io::Error> {
+        self.data.flush_async()
+    }
+
+    /// Replaces the datapool with one which wraps it, so tests can inject
+    /// failures into its operations.
+    #[cfg(any(test, feature = "debug"))]
+    pub(crate) fn wrap_datapool<F>(&mut self, f: F) -> Result<(), std::io::Error>
+    where
+        F: FnOnce(Box<dyn Datapool>) -> Box<dyn Datapool>,
+    {
+        let placeholder: Box<dyn Datapool> = Box::new(Memory::create(1)?);
+        let data = std::mem::replace(&mut self.data, placeholder);
+        self.data = f(data);
+        Ok(())
+    }
+
     /// Retrieve a `RawItem` from the segment id and offset encoded in the
     /// item info.
     pub(crate) fn get_item(&mut self, item_info: u64) -> Option<RawItem> {
@@ -132,6 +198,91 @@ impl Segments {
         self.get_item_at(seg_id, offset)
     }
 
+    /// Checks that the item info from a hashtable entry points to a whole item
+    /// below the write offset of a segment which is in use, and returns it.
+    #[cfg(any(test, feature = "debug"))]
+    pub(crate) fn verify_item(&mut self, item_info: u64) -> Result<RawItem, String> {
+        let seg_id = get_seg_id(item_info).ok_or("hashtable entry has no segment id")?;
+        let offset = get_offset(item_info) as usize;
+
+        let header = self.headers.get(seg_id.get() as usize - 1).ok_or_else(|| {
+            format!(
+                "hashtable entry points to segment {} which does not exist",
+                seg_id
+            )
+        })?;
+        if !header.accessible() {
+            return Err(format!(
+                "hashtable entry points to segment {} which is not in use",
+                seg_id
+            ));
+        }
+
+        let write_offset = header.write_offset().max(0) as usize;
+        if offset + ITEM_HDR_SIZE > write_offset {
+            return Err(format!(
+                "hashtable entry points to offset {} of segment {}, past its write offset {}",
+                offset, seg_id, write_offset
+            ));
+        }
+
+        let item = self.get_item_at(Some(seg_id), offset).unwrap();
+        if item.klen() == 0 || offset + item.size() > write_offset {
+            return Err(format!(
+                "hashtable entry points to a malformed item at offset {} of segment {}",
+                offset, seg_id
+            ));
+        }
+
+        Ok(item)
+    }
+
+    /// Checks that no segment header reports more bytes written or live than
+    /// the segment can hold, and that the bytes held by exempt items add up to
+    /// the running total.
+    #[cfg(any(test, feature = "debug"))]
+    pub(crate) fn verify_headers(&self) -> Result<(), String> {
+        let no_evict_bytes: i64 = self
+            .headers
+            .iter()
+            .map(|header| header.no_evict_bytes() as i64)
+            .sum();
+        if no_evict_bytes != self.evict.no_evict_bytes().get() {
+            return Err(format!(
+                "segments hold {} bytes of exempt items, but the total is {}",
+                no_evict_bytes,
+                self.evict.no_evict_bytes().get()
+            ));
+        }
+
+        for header in self.headers.iter() {
+            if header.write_offset() > self.segment_size {
+                return Err(format!(
+                    "segment {} has a write offset of {}, past its capacity of {}",
+                    header.id(),
+                    header.write_offset(),
+                    self.segment_size
+                ));
+            }
+            if header.live_bytes() > self.segment_size {
+                return Err(format!(
+                    "segment {} reports {} live bytes, more than its capacity of {}",
+                    header.id(),
+                    header.live_bytes(),
+                    self.segment_size
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the creation time and TTL of the segment holding the item
+    pub(crate) fn item_lifetime(&self, item_info: u64) -> Option<(Instant, Duration)> {
+        let seg_id = get_seg_id(item_info)?;
+        let header = self.headers.get(seg_id.get() as usize - 1)?;
+        Some((header.create_at(), header.ttl()))
+    }
+
     /// Retrieve a `RawItem` from a specific segment id at the given offset
     // TODO(bmartin): consider changing the return type here and removing asserts?
     pub(crate) fn get_item_at(
@@ -148,6 +299,7 @@ impl Segments {
         let mut segment = Segment::from_raw_parts(
             &mut self.headers[seg_id as usize - 1],
             &mut self.data.as_mut_slice()[seg_begin..seg_end],
+            self.evict.no_evict_bytes(),
         );
 
         segment.get_item_at(offset)
@@ -158,10 +310,10 @@ impl Segments {
         &mut self,
         id: NonZeroU32,
         hashtable: &mut HashTable,
-        expire: bool,
+        removal: Removal,
     ) -> Result<(), ()> {
         let mut segment = self.get_mut(id).unwrap();
-        if segment.next_seg().is_none() && !expire {
+        if segment.next_seg().is_none() && removal != Removal::Expire {
             Err(())
         } else {
             // TODO(bmartin): this should probably result in an error and not be
@@ -169,7 +321,7 @@ impl Segments {
             assert!(segment.evictable(), "segment was not evictable");
             segment.set_evictable(false);
             segment.set_accessible(false);
-            segment.clear(hashtable, expire);
+            segment.clear(hashtable, removal);
             Ok(())
         }
     }
@@ -228,7 +380,7 @@ impl Segments {
                 SEGMENT_EVICT.increment();
                 if let Some(id) = self.least_valuable_seg(ttl_buckets) {
                     let result = self
-                        .clear_segment(id, hashtable, false)
+                        .clear_segment(id, hashtable, Removal::Evict)
                         .map_err(|_| SegmentsError::EvictFailure);
 
                     if result.is_err() {
@@ -264,7 +416,7 @@ impl Segments {
 
             let seg_data = &mut self.data.as_mut_slice()[seg_start..seg_end];
 
-            let segment = Segment::from_raw_parts(header, seg_data);
+            let segment = Segment::from_raw_parts(header, seg_data, self.evict.no_evict_bytes());
             segment.check_magic();
             Ok(segment)
         } else {
@@ -320,8 +472,10 @@ impl Segments {
                     (&mut second[start_a..end_a], &mut first[start_b..end_b])
                 };
 
-                let segment_a = Segment::from_raw_parts(&mut *header_a, data_a);
-                let segment_b = Segment::from_raw_parts(&mut *header_b, data_b);
+                let segment_a =
+                    Segment::from_raw_parts(&mut *header_a, data_a, self.evict.no_evict_bytes());
+                let segment_b =
+                    Segment::from_raw_parts(&mut *header_b, data_b, self.evict.no_evict_bytes());
 
                 segment_a.check_magic();
                 segment_b.check_magic();
@@ -376,7 +530,13 @@ impl Segments {
         assert!(!self.headers[id_idx].evictable());
         self.headers[id_idx].set_accessible(false);
 
-        self.headers[id_idx].reset();
+        // exempt items are removed before the segment is freed, but the total
+        // is kept consistent with the headers if any are left
+        let header = &mut self.headers[id_idx];
+        self.evict
+            .no_evict_bytes()
+            .set(self.evict.no_evict_bytes().get() - header.no_evict_bytes() as i64);
+        header.reset();
 
         self.free += 1;
     }
@@ -523,7 +683,7 @@ impl Segments {
             if segment.live_items() == 0 && segment.can_evict() {
                 // even though the item has zero live items, we clear it as a
                 // way of updating the dead item metrics.
-                segment.clear(hashtable, false);
+                segment.clear(hashtable, Removal::Evict);
 
                 segment.set_evictable(false);
                 // if it's the head of a ttl bucket, we need to manually relink
@@ -793,7 +953,7 @@ impl Segments {
             );
 
             next_id = src.next_seg();
-            src.clear(hashtable, false);
+            src.clear(hashtable, Removal::Evict);
             self.push_free(src_id);
             merged += 1;
         }
@@ -900,7 +1060,7 @@ impl Segments {
             );
 
             next_id = src.next_seg();
-            src.clear(hashtable, false);
+            src.clear(hashtable, Removal::Evict);
             self.push_free(src_id);
             merged += 1;
         }
@@ -0,0 +1,383 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Snapshots hold items as a stream of records, so that they can be restored
+//! into a cache of any size. Restoring reads the snapshot through a bounded
+//! buffer and inserts one item at a time, so a snapshot which is larger than
+//! the memory available is restored without loading it all at once. Items
+//! which don't fit in the cache evict others according to the eviction
+//! policy, just as they would for any other insert.
+//!
+//! A snapshot starts with a header holding the magic bytes and the format
+//! version. Each record which follows is made of:
+//! * a 12 byte record header with the ttl in seconds (`u32`, zero for no
+//!   expiry), the optional data length (`u8`), key length (`u8`), value type
+//!   (`u8`, `0` for bytes and `1` for a `u64`), a reserved byte, and the value
+//!   length (`u32`). Integers are big-endian.
+//! * the optional data, the key, and the value.
+
+use crate::*;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
+
+const MAGIC: [u8; 8] = *b"PELISNAP";
+const VERSION: u64 = 0;
+
+const HEADER_SIZE: usize = MAGIC.len() + core::mem::size_of::<u64>();
+const RECORD_HEADER_SIZE: usize = 12;
+
+const VALUE_BYTES: u8 = 0;
+const VALUE_U64: u8 = 1;
+
+// the size of the buffer used to read the snapshot while restoring
+const RESTORE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Writes the header which starts every snapshot.
+pub fn write_header<W: Write>(writer: &mut W) -> Result<(), Error> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_be_bytes())
+}
+
+/// Writes a single item to a snapshot. A ttl of zero means that the item does
+/// not expire.
+pub fn write_item<W: Write>(
+    writer: &mut W,
+    key: &[u8],
+    value: Value,
+    optional: Option<&[u8]>,
+    ttl: std::time::Duration,
+) -> Result<(), Error> {
+    let optional = optional.unwrap_or(&[]);
+    if key.len() > u8::MAX as usize || optional.len() > u8::MAX as usize {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "key or optional data too long",
+        ));
+    }
+
+    let u64_bytes;
+    let (kind, value) = match value {
+        Value::Bytes(b) => (VALUE_BYTES, b),
+        Value::U64(v) => {
+            u64_bytes = v.to_be_bytes();
+            (VALUE_U64, &u64_bytes[..])
+        }
+    };
+    if value.len() > u32::MAX as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "value too long"));
+    }
+
+    let ttl = std::cmp::min(ttl.as_secs(), u32::MAX as u64) as u32;
+
+    let mut header = [0; RECORD_HEADER_SIZE];
+    header[0..4].copy_from_slice(&ttl.to_be_bytes());
+    header[4] = optional.len() as u8;
+    header[5] = key.len() as u8;
+    header[6] = kind;
+    header[8..12].copy_from_slice(&(value.len() as u32).to_be_bytes());
+
+    writer.write_all(&header)?;
+    writer.write_all(optional)?;
+    writer.write_all(key)?;
+    writer.write_all(value)
+}
+
+impl Seg {
+    /// Writes every item in the cache to a snapshot, returning the number of
+    /// items written. Items which have expired are left out, and the others
+    /// are written with the time they have left to live, rounded up to the
+    /// next second.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// cache.insert(b"coffee", b"hot", None, Duration::ZERO);
+    ///
+    /// let mut data = Vec::new();
+    /// assert_eq!(cache.dump(&mut data).unwrap(), 1);
+    ///
+    /// let mut copy = Seg::builder().build().expect("failed to create cache");
+    /// assert_eq!(copy.restore(&data[..]).unwrap(), 1);
+    /// assert_eq!(copy.get(b"coffee").unwrap().value(), b"hot");
+    /// ```
+    pub fn dump<W: Write>(&mut self, writer: W) -> Result<usize, Error> {
+        let mut writer = BufWriter::new(writer);
+        write_header(&mut writer)?;
+
+        let now = Instant::recent();
+        let mut dumped = 0;
+
+        self.hashtable.for_each_item(&mut self.segments, |item| {
+            if item.is_expired(now) {
+                return Ok::<(), Error>(());
+            }
+
+            // a zero ttl means no expiry, and items which are about to
+            // expire have at least a second left
+            let ttl = match item.remaining_ttl(now) {
+                Some(remaining) => {
+                    std::time::Duration::from_secs(u64::from(remaining.as_secs().max(1)))
+                }
+                None => std::time::Duration::ZERO,
+            };
+
+            write_item(&mut writer, item.key(), item.value(), item.optional(), ttl)?;
+            dumped += 1;
+            Ok(())
+        })?;
+
+        writer.flush()?;
+        Ok(dumped)
+    }
+
+    /// Restores the items in a snapshot, returning the number of items which
+    /// were inserted. Items which can't be inserted, such as those which are
+    /// too large for a segment or which find no free segment when eviction is
+    /// disabled, are skipped. Items which are already in the cache are
+    /// replaced.
+    ///
+    /// ```
+    /// use seg::{snapshot, Seg, Value};
+    /// use std::time::Duration;
+    ///
+    /// let mut data = Vec::new();
+    /// snapshot::write_header(&mut data).unwrap();
+    /// snapshot::write_item(&mut data, b"coffee", Value::Bytes(b"hot"), None, Duration::ZERO)
+    ///     .unwrap();
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// assert_eq!(cache.restore(&data[..]).unwrap(), 1);
+    /// assert_eq!(cache.get(b"coffee").unwrap().value(), b"hot");
+    /// ```
+    pub fn restore<R: Read>(&mut self, reader: R) -> Result<usize, Error> {
+        let mut reader = BufReader::with_capacity(RESTORE_BUFFER_SIZE, reader);
+
+        let mut header = [0; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        if header[0..8] != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a snapshot"));
+        }
+        let version = u64::from_be_bytes(header[8..16].try_into().unwrap());
+        if version != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported snapshot version: {}", version),
+            ));
+        }
+
+        // values can't be larger than a segment, which bounds the memory used
+        // for each record of a valid snapshot
+        let max_value_len = self.segments.segment_size() as usize;
+
+        let mut optional = Vec::new();
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+        let mut restored = 0;
+
+        loop {
+            let mut header = [0; RECORD_HEADER_SIZE];
+            if !read_record_header(&mut reader, &mut header)? {
+                return Ok(restored);
+            }
+
+            let ttl = u32::from_be_bytes(header[0..4].try_into().unwrap());
+            let kind = header[6];
+            let value_len = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+
+            if value_len > max_value_len || (kind == VALUE_U64 && value_len != 8) {
+                return Err(Error::new(ErrorKind::InvalidData, "bad snapshot record"));
+            }
+
+            optional.resize(header[4] as usize, 0);
+            key.resize(header[5] as usize, 0);
+            value.resize(value_len, 0);
+            reader.read_exact(&mut optional)?;
+            reader.read_exact(&mut key)?;
+            reader.read_exact(&mut value)?;
+
+            let v = match kind {
+                VALUE_BYTES => Value::Bytes(&value),
+                VALUE_U64 => Value::U64(u64::from_be_bytes(value[..].try_into().unwrap())),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "bad snapshot value type",
+                    ));
+                }
+            };
+            let o = Some(&optional[..]).filter(|o| !o.is_empty());
+            let ttl = std::time::Duration::from_secs(ttl as u64);
+
+            match self.insert(&key, v, o, ttl) {
+                Ok(()) => {
+                    restored += 1;
+                }
+                Err(e) => {
+                    debug!("skipped restoring item: {}", e);
+                }
+            }
+        }
+    }
+}
+
+// reads the header of the next record, returning `false` if the snapshot ended
+// cleanly before it
+fn read_record_header<R: Read>(
+    reader: &mut R,
+    header: &mut [u8; RECORD_HEADER_SIZE],
+) -> Result<bool, Error> {
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(Error::from(ErrorKind::UnexpectedEof)),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // a reader which counts the bytes read through it
+    struct Counting<'a> {
+        inner: &'a [u8],
+        read: usize,
+    }
+
+    impl Read for Counting<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let n = self.inner.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn restore_larger_than_heap() {
+        let segment_size = 4096;
+        let segments = 16;
+
+        let mut cache = Seg::builder()
+            .segment_size(segment_size)
+            .heap_size(segments * segment_size as usize)
+            .eviction(Policy::Fifo)
+            .build()
+            .expect("failed to create cache");
+
+        // the snapshot is several times larger than the heap
+        let items = 4096;
+        let value = [0xA5; 100];
+        let mut data = Vec::new();
+        write_header(&mut data).unwrap();
+        for i in 0..items {
+            let key = format!("{:08}", i);
+            let flags = (i as u32).to_be_bytes();
+            write_item(
+                &mut data,
+                key.as_bytes(),
+                Value::Bytes(&value),
+                Some(&flags),
+                Duration::ZERO,
+            )
+            .unwrap();
+        }
+        assert!(data.len() > 4 * segments * segment_size as usize);
+
+        let mut reader = Counting {
+            inner: &data,
+            read: 0,
+        };
+        assert_eq!(cache.restore(&mut reader).unwrap(), items);
+        assert_eq!(reader.read, data.len());
+
+        // eviction makes room for the items as they are restored. segments
+        // created within the same second have no order under fifo eviction,
+        // so only the last item, which is in the segment still being written,
+        // is known to be kept
+        let retained = cache.items();
+        assert!(retained > 0 && retained < items);
+        let mut found = 0;
+        for i in 0..items {
+            if let Some(item) = cache.get(format!("{:08}", i).as_bytes()) {
+                assert_eq!(item.value(), value);
+                assert_eq!(item.flags(), i as u32);
+                found += 1;
+            }
+        }
+        assert_eq!(found, retained);
+        assert!(cache.get(format!("{:08}", items - 1).as_bytes()).is_some());
+    }
+
+    #[test]
+    fn dump_restore() {
+        let mut cache = Seg::builder().build().expect("failed to create cache");
+        let flags = 42_u32.to_be_bytes();
+        cache
+            .insert(b"coffee", b"hot", Some(&flags), Duration::ZERO)
+            .unwrap();
+        cache
+            .insert(b"number", 7_u64, None, Duration::from_secs(60))
+            .unwrap();
+        cache
+            .insert(b"deleted", b"gone", None, Duration::ZERO)
+            .unwrap();
+        assert!(cache.delete(b"deleted"));
+
+        let mut data = Vec::new();
+        assert_eq!(cache.dump(&mut data).unwrap(), 2);
+
+        let mut copy = Seg::builder().build().expect("failed to create cache");
+        assert_eq!(copy.restore(&data[..]).unwrap(), 2);
+        assert_eq!(copy.items(), 2);
+
+        let coffee = copy.get(b"coffee").unwrap();
+        assert_eq!(coffee.value(), b"hot");
+        assert_eq!(coffee.flags(), 42);
+        assert_eq!(copy.expire_time(b"coffee"), Some(None));
+
+        assert_eq!(copy.get(b"number").unwrap().value(), 7_u64);
+        // items are grouped into segments by ttl, so the expiry of the copy
+        // may differ slightly
+        let expire = cache.expire_time(b"number").unwrap().unwrap();
+        let restored = copy.expire_time(b"number").unwrap().unwrap();
+        assert!(expire.abs_diff(restored) <= 8);
+        assert!(copy.get(b"deleted").is_none());
+    }
+
+    #[test]
+    fn restore_values() {
+        let mut cache = Seg::builder().build().expect("failed to create cache");
+
+        let mut data = Vec::new();
+        write_header(&mut data).unwrap();
+        write_item(&mut data, b"number", Value::U64(42), None, Duration::ZERO).unwrap();
+        write_item(
+            &mut data,
+            b"ttl",
+            Value::Bytes(b""),
+            None,
+            Duration::from_secs(60),
+        )
+        .unwrap();
+
+        assert_eq!(cache.restore(&data[..]).unwrap(), 2);
+        assert_eq!(cache.get(b"number").unwrap().value(), 42_u64);
+        assert_eq!(cache.expire_time(b"number"), Some(None));
+        assert!(cache.expire_time(b"ttl").unwrap().is_some());
+
+        // a truncated record is an error, while the items before it are kept
+        let mut cache = Seg::builder().build().expect("failed to create cache");
+        assert!(cache.restore(&data[..data.len() - 1]).is_err());
+        assert!(cache.get(b"number").is_some());
+
+        assert!(cache.restore(&b"PELIKAN!\0\0\0\0\0\0\0\0"[..]).is_err());
+    }
+}
@@ -79,6 +79,214 @@ fn get() {
     assert_eq!(item.value(), b"strong", "item is: {:?}", item);
 }
 
+#[test]
+fn memory_usage() {
+    let ttl = Duration::ZERO;
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .build()
+        .expect("failed to create cache");
+    assert!(cache.memory_usage(b"small").is_none());
+
+    assert!(cache.insert(b"small", b"a", None, ttl).is_ok());
+    assert!(cache.insert(b"large", &[0; 1024][..], None, ttl).is_ok());
+
+    let small = cache.memory_usage(b"small").unwrap();
+    let large = cache.memory_usage(b"large").unwrap();
+    assert!(small > ITEM_HDR_SIZE + 5 + 1);
+    assert!(large > small);
+    assert!(large >= 1024);
+}
+
+#[test]
+fn ttl_bucket_max_segments() {
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .ttl_bucket_max_segments(4)
+        .build()
+        .expect("failed to create cache");
+
+    // an item in a different ttl bucket, which must not be evicted
+    assert!(cache
+        .insert(b"other", b"value", None, Duration::from_secs(3600))
+        .is_ok());
+    assert_eq!(cache.segments.free(), 63);
+
+    // fill a single ttl bucket with enough data for many segments
+    let value = [0; 128];
+    for i in 0..1024 {
+        let key = format!("key{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), &value[..], None, Duration::ZERO)
+            .is_ok());
+    }
+
+    // the bucket is held at its cap, leaving the rest of the heap free
+    assert_eq!(cache.segments.free(), 59);
+
+    assert!(cache.get(b"other").is_some());
+    assert!(cache.get(b"key0").is_none());
+    assert!(cache.get(b"key1023").is_some());
+}
+
+#[test]
+fn max_active_ttl_buckets() {
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .max_active_ttl_buckets(4)
+        .build()
+        .expect("failed to create cache");
+
+    // each of these ttls falls into its own bucket
+    for i in 0..32 {
+        let key = format!("key{}", i);
+        let ttl = Duration::from_secs(1000 + 8 * i);
+        assert!(cache.insert(key.as_bytes(), b"value", None, ttl).is_ok());
+    }
+
+    // most of the ttls share a bucket, the rest are too far from any active
+    // bucket and exceed the limit
+    let active = cache.ttl_buckets.active();
+    assert!((4..8).contains(&active), "active: {}", active);
+
+    // items which didn't get their own bucket share a nearby one, so they
+    // expire no later, and not much earlier, than the ttl they were written
+    // with
+    for i in 0..32 {
+        let key = format!("key{}", i);
+        let item = cache.get(key.as_bytes()).expect("item is missing");
+        let ttl = item.ttl().as_secs() as i64;
+        let expected = 1000 + 8 * i as i64;
+        assert!(ttl <= expected + 1, "ttl: {} expected: {}", ttl, expected);
+        assert!(
+            expected - ttl <= expected / 8 + 8,
+            "ttl: {} expected: {}",
+            ttl,
+            expected
+        );
+    }
+
+    // a distant ttl is not coalesced into a much shorter bucket
+    assert!(cache
+        .insert(b"later", b"value", None, Duration::from_secs(3600))
+        .is_ok());
+    assert_eq!(cache.ttl_buckets.active(), active + 1);
+    let ttl = cache.get(b"later").unwrap().ttl().as_secs();
+    assert!((3600 - 128..=3600).contains(&ttl), "ttl: {}", ttl);
+
+    // but a nearby one is
+    assert!(cache
+        .insert(b"close", b"value", None, Duration::from_secs(4000))
+        .is_ok());
+    assert_eq!(cache.ttl_buckets.active(), active + 1);
+    let ttl = cache.get(b"close").unwrap().ttl().as_secs();
+    assert!((3500..=4000).contains(&ttl), "ttl: {}", ttl);
+
+    // items without a ttl never share a bucket which would expire them
+    assert!(cache
+        .insert(b"forever", b"value", None, Duration::ZERO)
+        .is_ok());
+    assert_eq!(cache.ttl_buckets.active(), active + 2);
+    let ttl = cache.get(b"forever").unwrap().ttl().as_secs();
+    assert!(ttl > 90 * 86400, "ttl: {}", ttl);
+
+    // a ttl shorter than that of every active bucket can't be coalesced
+    // without outliving it, so it takes a bucket of its own
+    assert!(cache
+        .insert(b"sooner", b"value", None, Duration::from_secs(10))
+        .is_ok());
+    assert_eq!(cache.ttl_buckets.active(), active + 3);
+    let ttl = cache.get(b"sooner").unwrap().ttl().as_secs();
+    assert!(ttl <= 10, "ttl: {}", ttl);
+
+    // once the active buckets are emptied, ttls get buckets of their own again
+    cache.clear();
+    assert_eq!(cache.ttl_buckets.active(), 0);
+    assert!(cache
+        .insert(b"later", b"value", None, Duration::from_secs(3600))
+        .is_ok());
+    let ttl = cache.get(b"later").unwrap().ttl().as_secs();
+    assert!((3600 - 128..=3600).contains(&ttl), "ttl: {}", ttl);
+}
+
+#[test]
+fn swap() {
+    let ttl = Duration::ZERO;
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .build()
+        .expect("failed to create cache");
+
+    assert!(matches!(
+        cache.swap(b"coffee", b"strong", None, ttl),
+        Ok(None)
+    ));
+
+    let old = cache
+        .swap(b"coffee", b"weak", None, ttl)
+        .expect("swap failed")
+        .expect("no old value");
+    assert!(old.as_value() == b"strong");
+
+    let item = cache.get(b"coffee").unwrap();
+    assert_eq!(item.value(), b"weak", "item is: {:?}", item);
+    assert_eq!(cache.items(), 1);
+}
+
+#[test]
+fn expire_time() {
+    let mut cache = Seg::builder().build().expect("failed to create cache");
+    assert!(cache.expire_time(b"coffee").is_none());
+
+    assert!(cache
+        .insert(b"coffee", b"strong", None, Duration::from_secs(300))
+        .is_ok());
+
+    // the item expires with its segment, which was created by this insert
+    let epoch = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let ttl = u64::from(cache.get_no_freq_incr(b"coffee").unwrap().ttl().as_secs());
+    let expire_time = cache.expire_time(b"coffee").unwrap().unwrap();
+    assert!(
+        expire_time + 1 >= epoch + ttl,
+        "{} vs {}",
+        expire_time,
+        epoch + ttl
+    );
+    assert!(
+        expire_time <= epoch + ttl + 1,
+        "{} vs {}",
+        expire_time,
+        epoch + ttl
+    );
+
+    // items without a ttl have no expiry to report
+    assert!(cache.insert(b"tea", b"green", None, Duration::ZERO).is_ok());
+    assert_eq!(cache.expire_time(b"tea"), Some(None));
+}
+
 #[test]
 fn cas() {
     let ttl = Duration::ZERO;
@@ -380,6 +588,140 @@ fn expiration() {
     assert_eq!(cache.segments.free(), segments);
 }
 
+#[test]
+fn lazy_expiration() {
+    let mut cache = Seg::builder().build().expect("failed to create cache");
+
+    assert!(cache
+        .insert(b"latte", b"", None, Duration::from_secs(1))
+        .is_ok());
+    assert!(cache.get(b"latte").is_some());
+
+    // once expired, the item is a miss before eager expiration removes it
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    common::time::refresh_clock();
+    assert!(cache.get(b"latte").is_none());
+    assert!(cache.get_no_freq_incr(b"latte").is_none());
+    assert_eq!(cache.items(), 1);
+
+    cache.expire();
+    assert_eq!(cache.items(), 0);
+}
+
+#[test]
+fn flush_keeps_items() {
+    let mut cache = Seg::builder().build().expect("failed to create cache");
+    let flush_at = cache.segments.flush_at();
+
+    assert!(cache
+        .insert(b"drink", b"coffee", None, Duration::from_secs(3600))
+        .is_ok());
+    assert!(cache
+        .insert(b"forever", b"tea", None, Duration::ZERO)
+        .is_ok());
+
+    // the flush happens in a later second than the segments were created in,
+    // which would clear them if it were taken as a flush_all
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    common::time::refresh_clock();
+    assert!(cache.flush().is_ok());
+    assert!(cache.segments.persisted_at() > flush_at);
+    assert_eq!(cache.segments.flush_at(), flush_at);
+
+    // persisting the data doesn't expire anything
+    cache.expire();
+    assert_eq!(cache.items(), 2);
+    assert_eq!(cache.get(b"drink").unwrap().value(), b"coffee");
+    assert_eq!(cache.get(b"forever").unwrap().value(), b"tea");
+}
+
+#[test]
+fn metadata_reads() {
+    let mut cache = Seg::builder().build().expect("failed to create cache");
+
+    let ttl = Duration::from_secs(3600);
+    assert!(cache.insert(b"polled", b"value", None, ttl).is_ok());
+    assert!(cache.insert(b"read", b"value", None, ttl).is_ok());
+
+    // at most one access a second is counted, so the reads are spread out,
+    // with `expire()` advancing the cache clock as the storage worker would
+    for _ in 0..3 {
+        assert!(cache.read(b"polled", ReadKind::Metadata).is_some());
+        assert!(cache.expire_time(b"polled").is_some());
+        assert!(cache.memory_usage(b"polled").is_some());
+        assert!(cache.read(b"read", ReadKind::Value).is_some());
+
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        common::time::refresh_clock();
+        cache.expire();
+    }
+
+    // merge eviction retains the items with the highest frequency, so polling
+    // the ttl of an item doesn't keep it in the cache, while reading it does
+    assert_eq!(
+        cache.hashtable.freq_of(b"polled", &mut cache.segments),
+        Some(0)
+    );
+    assert!(
+        cache
+            .hashtable
+            .freq_of(b"read", &mut cache.segments)
+            .unwrap()
+            >= 2
+    );
+}
+
+#[test]
+fn get_batch() {
+    let mut cache = Seg::builder().build().expect("failed to create cache");
+
+    // every other key is present, and one holds a numeric value
+    let keys: Vec<Vec<u8>> = (0..256).map(|i| format!("key{}", i).into_bytes()).collect();
+    for key in keys.iter().step_by(2) {
+        assert!(cache
+            .insert(key, key.as_slice(), None, Duration::ZERO)
+            .is_ok());
+    }
+    assert!(cache
+        .insert(b"number", 42_u64, None, Duration::ZERO)
+        .is_ok());
+
+    // a batch may repeat keys
+    let mut batch: Vec<&[u8]> = keys.iter().map(|key| key.as_slice()).collect();
+    batch.push(b"number");
+    batch.push(b"key0");
+    batch.push(b"key1");
+
+    let batched = cache.get_batch(&batch);
+    assert_eq!(batched.len(), batch.len());
+
+    for (key, batched) in batch.iter().zip(batched) {
+        let serial = cache.get(key);
+        match (batched, serial) {
+            (Some(batched), Some(serial)) => {
+                assert_eq!(batched.key(), *key);
+                assert!(batched.value() == serial.value());
+                assert_eq!(batched.cas(), serial.cas());
+            }
+            (None, None) => {}
+            _ => panic!("batched and serial results differ for {:?}", key),
+        }
+    }
+
+    assert!(cache.get_batch(&[]).is_empty());
+
+    // an unordered batch finds the same items, once for each key
+    let mut unordered = cache.get_batch_unordered(&batch);
+    assert_eq!(unordered.len(), batch.len());
+    unordered.sort_by_key(|(index, _)| *index);
+    for ((index, item), key) in unordered.iter().zip(batch.iter()) {
+        assert_eq!(batch[*index], *key);
+        assert_eq!(item.is_some(), cache.get(key).is_some());
+    }
+
+    assert!(cache.get_batch_unordered(&[]).is_empty());
+}
+
 #[test]
 fn clear() {
     let ttl = Duration::ZERO;
@@ -409,6 +751,173 @@ fn clear() {
     assert!(cache.get(b"coffee").is_none());
 }
 
+#[test]
+fn evict() {
+    let ttl = Duration::ZERO;
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .eviction(Policy::Fifo)
+        .build()
+        .expect("failed to create cache");
+
+    // fill several segments, in key order
+    let value = [0; 1000];
+    let keys: Vec<String> = (0..16).map(|i| format!("{:08}", i)).collect();
+    for key in &keys {
+        assert!(cache.insert(key.as_bytes(), &value[..], None, ttl).is_ok());
+    }
+    assert_eq!(cache.items(), keys.len());
+    assert!(cache.segments.free() < segments - 1);
+
+    // a whole segment is evicted. the segments were all created within the
+    // same second, which leaves their order under fifo eviction open, so
+    // only the number of keys which become misses is known
+    let evicted = cache.evict(1);
+    assert!(evicted > 0 && evicted < keys.len());
+    assert_eq!(cache.items(), keys.len() - evicted);
+    let missing = keys
+        .iter()
+        .filter(|key| cache.get(key.as_bytes()).is_none())
+        .count();
+    assert_eq!(missing, evicted);
+
+    // the segment being written to is never evicted
+    cache.evict(segments);
+    assert!(cache.get(keys.last().unwrap().as_bytes()).is_some());
+}
+
+#[test]
+fn no_evict() {
+    let ttl = Duration::ZERO;
+    let segment_size = 4096;
+    let segments = 16;
+    let heap_size = segments * segment_size as usize;
+
+    // each item takes 1016 bytes, so the cap has room for 8 exempt items
+    let value = [0; 1000];
+    let item_size = 1016;
+    let exempt = 8;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .eviction(Policy::Fifo)
+        .no_evict_cap(exempt * item_size)
+        .build()
+        .expect("failed to create cache");
+
+    // tag more items than the cap allows, the rest are stored as ordinary
+    // items
+    let tagged: Vec<String> = (0..12).map(|i| format!("tag{:05}", i)).collect();
+    for key in &tagged {
+        assert!(cache
+            .insert_no_evict(key.as_bytes(), &value[..], None, ttl)
+            .is_ok());
+    }
+    for key in &tagged[..exempt] {
+        assert!(cache.get(key.as_bytes()).unwrap().is_no_evict());
+    }
+    for key in &tagged[exempt..] {
+        assert!(!cache.get(key.as_bytes()).unwrap().is_no_evict());
+    }
+
+    // write several times the heap size of untagged items, which evicts all
+    // the segments holding only untagged items many times over
+    let untagged: Vec<String> = (0..256).map(|i| format!("key{:05}", i)).collect();
+    for key in &untagged {
+        assert!(cache.insert(key.as_bytes(), &value[..], None, ttl).is_ok());
+    }
+
+    // the exempt items survive, while most of the other items are evicted.
+    // which of those are kept is left open, as the segments were all created
+    // within the same second
+    for key in &tagged[..exempt] {
+        assert!(cache.get(key.as_bytes()).is_some());
+    }
+    let retained = tagged[exempt..]
+        .iter()
+        .chain(&untagged)
+        .filter(|key| cache.get(key.as_bytes()).is_some())
+        .count();
+    assert!(retained < untagged.len() / 4, "retained: {}", retained);
+    assert!(cache.verify().is_ok());
+
+    // removing an exempt item makes room under the cap for another
+    assert!(cache.delete(tagged[0].as_bytes()));
+    assert!(cache
+        .insert_no_evict(b"tag99999", &value[..], None, ttl)
+        .is_ok());
+    assert!(cache.get(b"tag99999").unwrap().is_no_evict());
+    assert!(cache
+        .insert_no_evict(b"tag99998", &value[..], None, ttl)
+        .is_ok());
+    assert!(!cache.get(b"tag99998").unwrap().is_no_evict());
+}
+
+#[test]
+fn verify() {
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .eviction(Policy::Fifo)
+        .build()
+        .expect("failed to create cache");
+    assert_eq!(cache.verify(), Ok(()));
+
+    // fill a chain of several segments, with some items removed and one
+    // segment evicted
+    let value = [0; 1000];
+    let keys: Vec<String> = (0..16).map(|i| format!("{:08}", i)).collect();
+    for key in &keys {
+        assert!(cache
+            .insert(key.as_bytes(), &value[..], None, Duration::ZERO)
+            .is_ok());
+    }
+    assert!(cache
+        .insert(b"coffee", b"strong", None, Duration::from_secs(60))
+        .is_ok());
+    assert!(cache.delete(keys[8].as_bytes()));
+    assert!(cache.evict(1) > 0);
+    assert_eq!(cache.verify(), Ok(()));
+
+    // the first segment of the chain is the one written after the evicted one
+    let head = NonZeroU32::new(2).unwrap();
+    let next = cache.segments.get_mut(head).unwrap().next_seg();
+    assert!(next.is_some());
+
+    // a broken link leaves the chain short of its tail
+    cache.segments.get_mut(head).unwrap().set_next_seg(None);
+    let error = cache.verify().unwrap_err();
+    assert!(error.contains("chain ends at"), "bad error: {}", error);
+    cache.segments.get_mut(head).unwrap().set_next_seg(next);
+    assert_eq!(cache.verify(), Ok(()));
+
+    // items written past the write offset are referenced by the hashtable
+    let write_offset = cache.segments.get_mut(head).unwrap().write_offset();
+    cache.segments.get_mut(head).unwrap().set_write_offset(0);
+    let error = cache.verify().unwrap_err();
+    assert!(
+        error.contains("past its write offset"),
+        "bad error: {}",
+        error
+    );
+    cache
+        .segments
+        .get_mut(head)
+        .unwrap()
+        .set_write_offset(write_offset);
+    assert_eq!(cache.verify(), Ok(()));
+}
+
 #[test]
 fn wrapping_add() {
     let ttl = Duration::ZERO;
@@ -731,3 +1240,142 @@ fn fuzz_2() {
     let _ = cache.insert(&[1], &[3, 0, 1], None, Duration::from_secs(0));
     let _ = cache.insert(&[1], &[3, 4, 2], None, Duration::from_secs(114));
 }
+
+#[test]
+fn segment_size_validation() {
+    // a heap which divides evenly into segments of a supported size
+    assert!(Seg::builder()
+        .segment_size(4096)
+        .heap_size(4096 * 64)
+        .build()
+        .is_ok());
+
+    // the heap must be a whole number of segments
+    let error = Seg::builder()
+        .segment_size(4096)
+        .heap_size(4096 * 64 + 1)
+        .build()
+        .err()
+        .expect("built with a partial segment");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(error
+        .to_string()
+        .contains("not a multiple of the segment size"));
+
+    // segments must be able to hold an item header
+    let error = Seg::builder()
+        .segment_size(ITEM_HDR_SIZE as i32)
+        .heap_size(ITEM_HDR_SIZE * 64)
+        .build()
+        .err()
+        .expect("built with tiny segments");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(error.to_string().contains("out of range"));
+
+    // offsets into segments larger than 8MiB can't be stored in the hashtable
+    let error = Seg::builder()
+        .segment_size(16 * 1024 * 1024)
+        .heap_size(64 * 1024 * 1024)
+        .build()
+        .err()
+        .expect("built with huge segments");
+    assert!(error.to_string().contains("out of range"));
+
+    // the heap must hold at least one segment
+    assert!(Seg::builder()
+        .segment_size(4096)
+        .heap_size(0)
+        .build()
+        .is_err());
+}
+
+// a datapool whose flushes fail while the shared flag is set
+struct FailingDatapool {
+    inner: Box<dyn datapool::Datapool>,
+    fail: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl datapool::Datapool for FailingDatapool {
+    fn as_slice(&self) -> &[u8] {
+        self.inner.as_slice()
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.inner.as_mut_slice()
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        if self.fail.load(std::sync::atomic::Ordering::Relaxed) {
+            Err(std::io::Error::from_raw_os_error(28)) // ENOSPC
+        } else {
+            self.inner.flush()
+        }
+    }
+}
+
+#[test]
+fn degraded() {
+    let fail = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let mut cache = Seg::builder()
+        .segment_size(4096)
+        .heap_size(4096 * 64)
+        .build()
+        .expect("failed to create cache");
+    let failing = fail.clone();
+    cache
+        .segments
+        .wrap_datapool(|inner| {
+            Box::new(FailingDatapool {
+                inner,
+                fail: failing,
+            })
+        })
+        .expect("failed to wrap datapool");
+
+    assert!(cache
+        .insert(b"drink", b"coffee", None, Duration::ZERO)
+        .is_ok());
+    assert!(cache.insert(b"count", 1_u64, None, Duration::ZERO).is_ok());
+    assert!(cache.flush().is_ok());
+    assert!(!cache.is_degraded());
+
+    // a failed flush degrades the cache
+    fail.store(true, std::sync::atomic::Ordering::Relaxed);
+    assert!(cache.flush().is_err());
+    assert!(cache.is_degraded());
+
+    // reads are still served
+    let item = cache.get(b"drink").expect("didn't get item back");
+    assert_eq!(item.value(), b"coffee");
+
+    // writes are rejected and leave the existing items unchanged
+    assert_eq!(
+        cache.insert(b"drink", b"tea", None, Duration::ZERO),
+        Err(SegError::Degraded)
+    );
+    assert_eq!(
+        cache.cas(b"drink", b"tea", None, Duration::ZERO, item.cas()),
+        Err(SegError::Degraded)
+    );
+    assert_eq!(cache.append(b"drink", b"!"), Err(SegError::Degraded));
+    assert_eq!(
+        cache.wrapping_add(b"count", 1).err(),
+        Some(SegError::Degraded)
+    );
+    assert_eq!(cache.get(b"drink").unwrap().value(), b"coffee");
+    assert_eq!(cache.get(b"count").unwrap().value(), 1);
+
+    // deletes are allowed, as they only free space
+    assert!(cache.delete(b"count"));
+
+    // flushes which keep failing leave the cache degraded
+    assert!(cache.flush().is_err());
+    assert!(cache.is_degraded());
+
+    // the cache recovers once a flush succeeds
+    fail.store(false, std::sync::atomic::Ordering::Relaxed);
+    assert!(cache.flush().is_ok());
+    assert!(!cache.is_degraded());
+    assert!(cache.insert(b"drink", b"tea", None, Duration::ZERO).is_ok());
+    assert_eq!(cache.get(b"drink").unwrap().value(), b"tea");
+}
@@ -10,4 +10,6 @@ pub enum TtlBucketsError {
     ItemOversized { size: usize },
     #[error("ttl bucket expansion failed, no free segments")]
     NoFreeSegments,
+    #[error("ttl bucket expansion failed, bucket is at its segment cap")]
+    SegmentCapReached,
 }
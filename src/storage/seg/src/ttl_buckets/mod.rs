@@ -23,3 +23,5 @@ mod tests;
 pub use error::TtlBucketsError;
 pub use ttl_bucket::TtlBucket;
 pub use ttl_buckets::TtlBuckets;
+
+pub(crate) use ttl_buckets::MAX_BUCKET_TTL;
@@ -11,11 +11,11 @@
 //! │   HEAD SEG   │   TAIL SEG   │     TTL     │     NSEG     │
 //! │              │              │             │              │
 //! │    32 bit    │    32 bit    │    32 bit   │    32 bit    │
-//! ├──────────────┼──────────────┴─────────────┴──────────────┤
-//! │  NEXT MERGE  │                  PADDING                  │
-//! │              │                                           │
-//! │    32 bit    │                  96 bit                   │
-//! ├──────────────┴───────────────────────────────────────────┤
+//! ├──────────────┼──────────────┼─────────────┴──────────────┤
+//! │  NEXT MERGE  │   MAX NSEG   │          PADDING           │
+//! │              │              │                            │
+//! │    32 bit    │    32 bit    │           64 bit           │
+//! ├──────────────┴──────────────┴────────────────────────────┤
 //! │                         PADDING                          │
 //! │                                                          │
 //! │                         128 bit                          │
@@ -39,7 +39,8 @@ pub struct TtlBucket {
     ttl: i32,
     nseg: i32,
     next_to_merge: Option<NonZeroU32>,
-    _pad: [u8; 44],
+    max_nseg: u32,
+    _pad: [u8; 40],
 }
 
 impl TtlBucket {
@@ -51,10 +52,16 @@ impl TtlBucket {
             ttl,
             nseg: 0,
             next_to_merge: None,
-            _pad: [0; 44],
+            max_nseg: 0,
+            _pad: [0; 40],
         }
     }
 
+    /// Returns the TTL, in seconds, of the items in the `TtlBucket`.
+    pub(super) fn ttl(&self) -> i32 {
+        self.ttl
+    }
+
     /// Returns the segment ID of the head of the `TtlBucket`.
     pub fn head(&self) -> Option<NonZeroU32> {
         self.head
@@ -76,6 +83,108 @@ impl TtlBucket {
         self.next_to_merge = next;
     }
 
+    /// Checks that the segment chain of the `TtlBucket` only holds segments
+    /// which exist, is linked the same way in both directions, and ends at
+    /// the tail. Following the links back also means a cycle is reported
+    /// instead of being walked forever.
+    #[cfg(any(test, feature = "debug"))]
+    pub(super) fn verify(&self, segments: &mut Segments) -> Result<(), String> {
+        let mut prev = None;
+        let mut seg_id = self.head;
+        while let Some(id) = seg_id {
+            let segment = segments.get_mut(id).map_err(|_| {
+                format!(
+                    "ttl bucket for {}s references segment {} which does not exist",
+                    self.ttl, id
+                )
+            })?;
+            if segment.prev_seg() != prev {
+                return Err(format!(
+                    "segment {} in the ttl bucket for {}s does not link back to segment {:?}",
+                    id, self.ttl, prev
+                ));
+            }
+            prev = Some(id);
+            seg_id = segment.next_seg();
+        }
+
+        if prev != self.tail {
+            return Err(format!(
+                "ttl bucket for {}s has tail {:?} but its chain ends at {:?}",
+                self.ttl, self.tail, prev
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Limit the number of segments in the `TtlBucket`. Zero means the bucket
+    /// may grow until there are no free segments.
+    pub(super) fn set_max_nseg(&mut self, max: u32) {
+        self.max_nseg = max;
+    }
+
+    /// Returns `true` if the `TtlBucket` has a segment cap and its segment
+    /// chain has reached it.
+    fn at_segment_cap(&self, segments: &mut Segments) -> bool {
+        if self.max_nseg == 0 {
+            return false;
+        }
+
+        // the chain is walked instead of trusting a running count, as segments
+        // are unlinked by merge eviction without the bucket being involved
+        let mut nseg = 0;
+        let mut seg_id = self.head;
+        while let Some(id) = seg_id {
+            nseg += 1;
+            if nseg >= self.max_nseg {
+                return true;
+            }
+            seg_id = segments.get_mut(id).ok().and_then(|s| s.next_seg());
+        }
+
+        false
+    }
+
+    /// Evict the head segment of this `TtlBucket`, which is the segment that
+    /// would expire first. Used to make room when the bucket is at its segment
+    /// cap, so that the bucket does not take segments from other buckets.
+    /// Returns `false` if there was no segment to evict.
+    pub(crate) fn evict_head(
+        &mut self,
+        hashtable: &mut HashTable,
+        segments: &mut Segments,
+    ) -> bool {
+        let seg_id = match self.head {
+            Some(id) => id,
+            None => {
+                return false;
+            }
+        };
+
+        let mut segment = segments.get_mut(seg_id).unwrap();
+
+        // items which are exempt from eviction keep the segment in place
+        if segment.no_evict_bytes() > 0 {
+            return false;
+        }
+
+        if let Some(next) = segment.next_seg() {
+            self.head = Some(next);
+        } else {
+            self.head = None;
+            self.tail = None;
+        }
+        if self.next_to_merge == Some(seg_id) {
+            self.next_to_merge = None;
+        }
+        segment.clear(hashtable, Removal::Evict);
+        segments.push_free(seg_id);
+        SEGMENT_EVICT.increment();
+        SEGMENT_EVICT_CAP.increment();
+        true
+    }
+
     /// Expire segments from this TtlBucket, returns the number of segments
     /// expired.
     pub(super) fn expire(&mut self, hashtable: &mut HashTable, segments: &mut Segments) -> usize {
@@ -91,16 +200,24 @@ impl TtlBucket {
             if let Some(seg_id) = seg_id {
                 let flush_at = segments.flush_at();
                 let mut segment = segments.get_mut(seg_id).unwrap();
-                if segment.create_at() + segment.ttl() <= ts || segment.create_at() < flush_at {
+                let is_expired = segment.create_at() + segment.ttl() <= ts;
+                if is_expired || segment.create_at() < flush_at {
                     if let Some(next) = segment.next_seg() {
                         self.head = Some(next);
                     } else {
                         self.head = None;
                         self.tail = None;
                     }
-                    let _ = segment.clear(hashtable, true);
+                    // segments older than the cutoff are what remains of a
+                    // clear, so they count as cleared rather than expired
+                    if is_expired {
+                        let _ = segment.clear(hashtable, Removal::Expire);
+                        SEGMENT_EXPIRE.increment();
+                    } else {
+                        let _ = segment.clear(hashtable, Removal::Flush);
+                        SEGMENT_CLEAR.increment();
+                    }
                     segments.push_free(seg_id);
-                    SEGMENT_EXPIRE.increment();
                     expired += 1;
                 } else {
                     return expired;
@@ -130,7 +247,7 @@ impl TtlBucket {
                     self.head = None;
                     self.tail = None;
                 }
-                let _ = segment.clear(hashtable, true);
+                let _ = segment.clear(hashtable, Removal::Flush);
                 segments.push_free(seg_id);
                 SEGMENT_CLEAR.increment();
                 cleared += 1;
@@ -174,7 +291,8 @@ impl TtlBucket {
     /// Reserve space in this `TtlBucket` for an item with the specified size in
     /// bytes. This function will return an error if the item is oversized, or
     /// if there is no space in the `TtlBucket` for the item and the `TtlBucket`
-    /// could not be expanded with a segment from the free queue.
+    /// could not be expanded with a segment from the free queue or is at its
+    /// segment cap.
     pub(crate) fn reserve(
         &mut self,
         size: usize,
@@ -204,6 +322,9 @@ impl TtlBucket {
                     }
                 }
             }
+            if self.at_segment_cap(segments) {
+                return Err(TtlBucketsError::SegmentCapReached);
+            }
             self.try_expand(segments)?;
         }
     }
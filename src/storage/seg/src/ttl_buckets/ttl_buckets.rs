@@ -39,12 +39,21 @@ const TTL_BOUNDARY_1: i32 = 1 << (TTL_BUCKET_INTERVAL_N_BIT_1 + N_BUCKET_PER_STE
 const TTL_BOUNDARY_2: i32 = 1 << (TTL_BUCKET_INTERVAL_N_BIT_2 + N_BUCKET_PER_STEP_N_BIT);
 const TTL_BOUNDARY_3: i32 = 1 << (TTL_BUCKET_INTERVAL_N_BIT_3 + N_BUCKET_PER_STEP_N_BIT);
 
+/// Items may be coalesced into a bucket which expires them at most
+/// `ttl >> MAX_COALESCE_N_BIT` seconds early.
+const MAX_COALESCE_N_BIT: i32 = 3;
+
 const MAX_N_TTL_BUCKET: usize = N_BUCKET_PER_STEP * 4;
 const MAX_TTL_BUCKET_IDX: usize = MAX_N_TTL_BUCKET - 1;
 
+/// The TTL, in seconds, of the last bucket. Items written without a TTL are
+/// stored in this bucket as well.
+pub(crate) const MAX_BUCKET_TTL: u32 = (TTL_BUCKET_INTERVAL_4 * (N_BUCKET_PER_STEP - 1) + 1) as u32;
+
 pub struct TtlBuckets {
     pub(crate) buckets: Box<[TtlBucket]>,
     pub(crate) last_expired: Instant,
+    max_active: u32,
 }
 
 impl TtlBuckets {
@@ -75,9 +84,30 @@ impl TtlBuckets {
         Self {
             buckets,
             last_expired,
+            max_active: 0,
         }
     }
 
+    /// Limit the number of segments which any single `TtlBucket` may hold. A
+    /// value of zero removes the limit.
+    pub(crate) fn set_max_nseg(&mut self, max: usize) {
+        let max = std::cmp::min(max, u32::MAX as usize) as u32;
+        for bucket in self.buckets.iter_mut() {
+            bucket.set_max_nseg(max);
+        }
+    }
+
+    /// Limit the number of `TtlBucket`s which may hold segments at once. A
+    /// value of zero removes the limit.
+    pub(crate) fn set_max_active(&mut self, max: usize) {
+        self.max_active = std::cmp::min(max, MAX_N_TTL_BUCKET) as u32;
+    }
+
+    /// Returns the number of `TtlBucket`s which currently hold segments.
+    pub(crate) fn active(&self) -> usize {
+        self.buckets.iter().filter(|b| b.head().is_some()).count()
+    }
+
     /// Get the index of the `TtlBucket` for the given TTL.
     pub(crate) fn get_bucket_index(&self, ttl: Duration) -> usize {
         let ttl = ttl.as_secs() as i32;
@@ -109,6 +139,47 @@ impl TtlBuckets {
         unsafe { self.buckets.get_unchecked_mut(index) }
     }
 
+    /// Get a mutable reference to the `TtlBucket` which new items with the
+    /// given TTL should be written to. This is the bucket for the TTL, unless
+    /// that bucket holds no segments and the limit on active buckets has been
+    /// reached. The items are then written to the closest active bucket with
+    /// a shorter TTL, so that they share its expiration time instead of taking
+    /// up another bucket.
+    ///
+    /// Items are never written to a bucket with a longer TTL, as they would
+    /// outlive the TTL they were written with, nor to a bucket which would
+    /// expire them more than 1/8th of their TTL early. Items without a TTL are
+    /// never coalesced, as they must not expire at all. In these cases the
+    /// items take their own bucket and the limit is exceeded.
+    pub(crate) fn get_mut_bucket_for_write(&mut self, ttl: Duration) -> &mut TtlBucket {
+        let mut index = self.get_bucket_index(ttl);
+
+        if self.max_active > 0
+            && index != MAX_TTL_BUCKET_IDX
+            && self.buckets[index].head().is_none()
+        {
+            let active = self.active();
+            TTL_BUCKET_COUNT.set(active as _);
+
+            if active >= self.max_active as usize {
+                let ttl = ttl.as_secs() as i32;
+                let min_ttl = ttl - (ttl >> MAX_COALESCE_N_BIT);
+
+                let shorter = (0..index)
+                    .rev()
+                    .take_while(|i| self.buckets[*i].ttl() >= min_ttl)
+                    .find(|i| self.buckets[*i].head().is_some());
+
+                if let Some(shorter) = shorter {
+                    TTL_BUCKET_COALESCE.increment();
+                    index = shorter;
+                }
+            }
+        }
+
+        &mut self.buckets[index]
+    }
+
     pub(crate) fn expire(&mut self, hashtable: &mut HashTable, segments: &mut Segments) -> usize {
         let now = Instant::now();
 
@@ -123,6 +194,7 @@ impl TtlBuckets {
         for bucket in self.buckets.iter_mut() {
             expired += bucket.expire(hashtable, segments);
         }
+        TTL_BUCKET_COUNT.set(self.active() as _);
         let duration = start.elapsed();
         debug!("expired: {} segments in {:?}", expired, duration);
         EXPIRE_TIME.add(duration.as_nanos() as _);
@@ -141,6 +213,16 @@ impl TtlBuckets {
         CLEAR_TIME.add(duration.as_nanos() as _);
         cleared
     }
+
+    /// Checks the segment chain of every `TtlBucket`, returning a description
+    /// of the first inconsistency found.
+    #[cfg(any(test, feature = "debug"))]
+    pub(crate) fn verify(&self, segments: &mut Segments) -> Result<(), String> {
+        for bucket in self.buckets.iter() {
+            bucket.verify(segments)?;
+        }
+        Ok(())
+    }
 }
 
 impl Default for TtlBuckets {
@@ -0,0 +1,96 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Checks that items are counted by the reason they were removed. The metrics
+//! are process-wide, so this is kept to a single test in its own binary to
+//! make sure nothing else removes items while the counters are compared.
+
+use rustcommon_metrics::Counter;
+use seg::*;
+
+use std::time::Duration;
+
+// the current value of one of the item removal counters
+fn counter(name: &str) -> u64 {
+    for metric in &rustcommon_metrics::metrics() {
+        if metric.name() == name {
+            if let Some(counter) = metric.as_any().and_then(|a| a.downcast_ref::<Counter>()) {
+                return counter.value();
+            }
+        }
+    }
+    panic!("no counter named: {}", name);
+}
+
+// the changes in the evicted, expired, deleted, and flushed counters while
+// running `f`
+fn removals<F: FnOnce()>(f: F) -> (u64, u64, u64, u64) {
+    let before = (
+        counter("item_evict"),
+        counter("item_expire"),
+        counter("item_delete"),
+        counter("item_flush"),
+    );
+    f();
+    (
+        counter("item_evict") - before.0,
+        counter("item_expire") - before.1,
+        counter("item_delete") - before.2,
+        counter("item_flush") - before.3,
+    )
+}
+
+#[test]
+fn removal_metrics() {
+    let segment_size = 4096;
+    let segments = 4;
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(segments * segment_size as usize)
+        .eviction(Policy::Fifo)
+        .build()
+        .expect("failed to create cache");
+
+    // deletes only count as deletes, and misses aren't counted
+    assert!(cache
+        .insert(b"coffee", b"strong", None, Duration::ZERO)
+        .is_ok());
+    let (evicted, expired, deleted, flushed) = removals(|| {
+        assert!(cache.delete(b"coffee"));
+        assert!(!cache.delete(b"coffee"));
+    });
+    assert_eq!((evicted, expired, deleted, flushed), (0, 0, 1, 0));
+
+    // items which outlive their ttl are counted as expired
+    for key in [&b"latte"[..], b"mocha", b"cortado"] {
+        assert!(cache.insert(key, b"", None, Duration::from_secs(1)).is_ok());
+    }
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    let (evicted, expired, deleted, flushed) = removals(|| {
+        cache.expire();
+    });
+    assert_eq!((evicted, expired, deleted, flushed), (0, 3, 0, 0));
+
+    // filling the cache past its capacity evicts the oldest items. each
+    // segment holds four of these items, so this writes twice the capacity
+    let value = [0; 1000];
+    let (evicted, expired, deleted, flushed) = removals(|| {
+        for i in 0..(8 * segments) {
+            let key = format!("{:08}", i);
+            assert!(cache
+                .insert(key.as_bytes(), &value[..], None, Duration::ZERO)
+                .is_ok());
+        }
+    });
+    assert!(evicted > 0);
+    assert_eq!((expired, deleted, flushed), (0, 0, 0));
+
+    // clearing the cache counts the remaining items as flushed
+    let (evicted, expired, deleted, flushed) = removals(|| {
+        cache.clear();
+    });
+    assert_eq!((evicted, expired, deleted), (0, 0, 0));
+    assert!(flushed > 0);
+    assert!(cache.get(b"00000015").is_none());
+}